@@ -180,7 +180,13 @@ impl Credentials {
     }
 }
 
-async fn fetch_jwks(
+/// Get the JWKS of a client, fetching it from its `jwks_uri` if needed
+///
+/// # Errors
+///
+/// Returns an error if the `jwks_uri` is not a valid HTTP request URI, or if
+/// the JWKS could not be fetched
+pub async fn fetch_jwks(
     http_client_factory: &HttpClientFactory,
     jwks: &JwksOrJwksUri,
 ) -> Result<PublicJsonWebKeySet, BoxError> {
@@ -191,8 +197,7 @@ async fn fetch_jwks(
 
     let request = http::Request::builder()
         .uri(uri.as_str())
-        .body(mas_http::EmptyBody::new())
-        .unwrap();
+        .body(mas_http::EmptyBody::new())?;
 
     let mut client = http_client_factory
         .client("client.fetch_jwks")
@@ -0,0 +1,426 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Verification of DPoP proofs, as defined by RFC 9449
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use headers::{Header, HeaderName, HeaderValue};
+use http::Method;
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_jose::{
+    claims::{self, ClaimError, TimeOptions},
+    jwa::{AsymmetricKeyFromJwkError, AsymmetricVerifyingKey},
+    jwt::{Jwt, JwtDecodeError, JwtVerificationError},
+};
+use mas_storage::{Clock, RepositoryAccess};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use url::Url;
+
+/// The `typ` header value of DPoP proof JWTs
+const DPOP_JWT_TYPE: &str = "dpop+jwt";
+
+/// The signing algorithms accepted for DPoP proofs.
+///
+/// This is the list of asymmetric algorithms supported by `mas-jose`, as
+/// proofs can't be signed with a shared secret.
+pub const SUPPORTED_ALGORITHMS: [JsonWebSignatureAlg; 10] = [
+    JsonWebSignatureAlg::Rs256,
+    JsonWebSignatureAlg::Rs384,
+    JsonWebSignatureAlg::Rs512,
+    JsonWebSignatureAlg::Ps256,
+    JsonWebSignatureAlg::Ps384,
+    JsonWebSignatureAlg::Ps512,
+    JsonWebSignatureAlg::Es256,
+    JsonWebSignatureAlg::Es384,
+    JsonWebSignatureAlg::Es256K,
+    JsonWebSignatureAlg::EdDsa,
+];
+
+/// The [`SUPPORTED_ALGORITHMS`], as advertised in the `algs` parameter of the
+/// `WWW-Authenticate` header
+pub const SUPPORTED_ALGORITHMS_HEADER: &str =
+    "RS256 RS384 RS512 PS256 PS384 PS512 ES256 ES384 ES256K EdDSA";
+
+/// How long after being issued a DPoP proof is accepted
+const MAX_AGE: Duration = Duration::minutes(5);
+
+static DPOP: HeaderName = HeaderName::from_static("dpop");
+
+/// The `DPoP` HTTP header, which carries a DPoP proof JWT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DPoPHeader(String);
+
+impl DPoPHeader {
+    /// Get the raw DPoP proof JWT
+    #[must_use]
+    pub fn proof(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Header for DPoPHeader {
+    fn name() -> &'static HeaderName {
+        &DPOP
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        Self: Sized,
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let value = values.next().ok_or_else(headers::Error::invalid)?;
+
+        // There must be exactly one DPoP header
+        if values.next().is_some() {
+            return Err(headers::Error::invalid());
+        }
+
+        let value = value.to_str().map_err(|_| headers::Error::invalid())?;
+        Ok(Self(value.to_owned()))
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        if let Ok(value) = HeaderValue::from_str(&self.0) {
+            values.extend(std::iter::once(value));
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DPoPProofError {
+    #[error("DPoP proof is not a valid JWT")]
+    InvalidJwt(#[from] JwtDecodeError),
+
+    #[error("DPoP proof has an invalid type")]
+    InvalidType,
+
+    #[error("DPoP proof is signed with an unsupported algorithm")]
+    UnsupportedAlgorithm,
+
+    #[error("DPoP proof does not include a public key")]
+    MissingKey,
+
+    #[error("DPoP proof public key is not suitable for the algorithm")]
+    InvalidKey(#[from] AsymmetricKeyFromJwkError),
+
+    #[error("invalid DPoP proof signature")]
+    InvalidSignature(#[from] JwtVerificationError),
+
+    #[error("invalid DPoP proof claims")]
+    InvalidClaims(#[from] ClaimError),
+
+    #[error("DPoP proof was issued too long ago")]
+    TooOld,
+
+    #[error("DPoP proof has an invalid htu claim")]
+    InvalidUri(#[from] url::ParseError),
+
+    #[error("DPoP proof was issued for another HTTP method")]
+    MethodMismatch,
+
+    #[error("DPoP proof was issued for another URI")]
+    UriMismatch,
+
+    #[error("DPoP proof is missing the access token hash")]
+    MissingAccessTokenHash,
+
+    #[error("DPoP proof was issued for another access token")]
+    AccessTokenHashMismatch,
+
+    #[error("DPoP proof is bound to another key")]
+    KeyMismatch,
+
+    #[error("DPoP proof was already used")]
+    Replayed,
+}
+
+/// A verified DPoP proof
+///
+/// The signature and the freshness of the proof are checked when it is
+/// parsed. Callers are then expected to check that the proof was issued for
+/// the current request, and for the access token presented alongside it, if
+/// any, and that it wasn't used before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DPoPProof {
+    jkt: String,
+    jti: String,
+    iat: DateTime<Utc>,
+    htm: String,
+    htu: Url,
+    ath: Option<String>,
+}
+
+/// Remove the query and fragment from a URL, as they are not part of the
+/// `htu` comparison
+fn strip_url(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url
+}
+
+impl DPoPProof {
+    /// Parse and verify a DPoP proof JWT
+    ///
+    /// # Parameters
+    ///
+    /// * `proof`: The DPoP proof JWT, as found in the `DPoP` header
+    /// * `now`: The current time, used to check the `iat` claim
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the proof is not a valid DPoP proof, if its
+    /// signature is invalid, or if it was not issued recently
+    pub fn verify(proof: &str, now: DateTime<Utc>) -> Result<Self, DPoPProofError> {
+        let jwt: Jwt<'_, HashMap<String, Value>> = Jwt::try_from(proof)?;
+
+        if jwt.header().typ() != Some(DPOP_JWT_TYPE) {
+            return Err(DPoPProofError::InvalidType);
+        }
+
+        let alg = jwt.header().alg();
+        if !SUPPORTED_ALGORITHMS.contains(alg) {
+            return Err(DPoPProofError::UnsupportedAlgorithm);
+        }
+
+        let jwk = jwt.header().jwk().ok_or(DPoPProofError::MissingKey)?;
+        let key = AsymmetricVerifyingKey::from_jwk_and_alg(jwk.params(), alg)?;
+        jwt.verify(&key)?;
+
+        let mut claims = jwt.payload().clone();
+        let time_options = TimeOptions::new(now);
+
+        let jti = claims::JTI.extract_required(&mut claims)?;
+        let htm = claims::HTM.extract_required(&mut claims)?;
+        let htu = claims::HTU.extract_required(&mut claims)?;
+        let iat = claims::IAT.extract_required_with_options(&mut claims, &time_options)?;
+        let ath = claims::ATH.extract_optional(&mut claims)?;
+
+        // The IAT validator only checks that the proof was not issued in the
+        // future, so we also make sure it was issued recently
+        let iat = *iat;
+        if iat < now - MAX_AGE {
+            return Err(DPoPProofError::TooOld);
+        }
+
+        let htu = Url::parse(&htu)?;
+
+        Ok(Self {
+            jkt: jwk.params().thumbprint_sha256(),
+            jti,
+            iat,
+            htm,
+            htu,
+            ath,
+        })
+    }
+
+    /// The SHA-256 thumbprint of the key which signed this proof
+    #[must_use]
+    pub fn jkt(&self) -> &str {
+        &self.jkt
+    }
+
+    /// Check that this proof was signed by the key with the given thumbprint
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the proof was signed by another key
+    pub fn verify_jkt(&self, jkt: &str) -> Result<(), DPoPProofError> {
+        if self.jkt == jkt {
+            Ok(())
+        } else {
+            Err(DPoPProofError::KeyMismatch)
+        }
+    }
+
+    /// Check that this proof was issued for the given HTTP request
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP method or the URI don't match
+    pub fn verify_request(&self, method: &Method, uri: &Url) -> Result<(), DPoPProofError> {
+        if self.htm != method.as_str() {
+            return Err(DPoPProofError::MethodMismatch);
+        }
+
+        if strip_url(&self.htu) != strip_url(uri) {
+            return Err(DPoPProofError::UriMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Record the use of this proof in the database, until it is too old to be
+    /// accepted anyway
+    ///
+    /// Returns `false` if a proof with the same `jti` was already used with the
+    /// same key. The proof is only remembered once the transaction is saved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying repository fails
+    pub async fn consume<E>(
+        &self,
+        repo: &mut impl RepositoryAccess<Error = E>,
+        clock: &impl Clock,
+    ) -> Result<bool, E> {
+        repo.oauth2_dpop_proof()
+            .consume(clock, &self.jkt, &self.jti, self.iat + MAX_AGE)
+            .await
+    }
+
+    /// Check that this proof was issued for the given access token
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the proof has no `ath` claim, or if it doesn't
+    /// match the access token
+    pub fn verify_access_token(&self, access_token: &str) -> Result<(), DPoPProofError> {
+        let ath = self
+            .ath
+            .as_deref()
+            .ok_or(DPoPProofError::MissingAccessTokenHash)?;
+
+        let hash = BASE64URL_NOPAD.encode(&Sha256::digest(access_token.as_bytes()));
+        if ath == hash {
+            Ok(())
+        } else {
+            Err(DPoPProofError::AccessTokenHashMismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use mas_jose::{
+        jwk::{JsonWebKeyPublicParameters, PublicJsonWebKey},
+        jwt::JsonWebSignatureHeader,
+    };
+    use mas_keystore::PrivateKey;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    use super::*;
+
+    fn sign_proof(
+        rng: &mut rand_chacha::ChaChaRng,
+        key: &PrivateKey,
+        typ: &str,
+        claims: &Value,
+    ) -> String {
+        let alg = match key {
+            PrivateKey::Ed25519(_) => JsonWebSignatureAlg::EdDsa,
+            _ => JsonWebSignatureAlg::Es256,
+        };
+        let signer = key.signing_key_for_alg(&alg).unwrap();
+        let jwk = PublicJsonWebKey::new(JsonWebKeyPublicParameters::from(key));
+        let header = JsonWebSignatureHeader::new(alg)
+            .with_typ(typ.to_owned())
+            .with_jwk(jwk);
+        Jwt::sign_with_rng(rng, header, claims.clone(), &signer)
+            .unwrap()
+            .into_string()
+    }
+
+    #[test]
+    fn verify_proof() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let now = Utc.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap();
+        let key = PrivateKey::generate_ec_p256(&mut rng);
+        let expected_jkt = JsonWebKeyPublicParameters::from(&key).thumbprint_sha256();
+
+        let access_token = "mat_abcdef";
+        let ath = BASE64URL_NOPAD.encode(&Sha256::digest(access_token.as_bytes()));
+        let claims = json!({
+            "jti": "proof-1",
+            "htm": "POST",
+            "htu": "https://example.com/oauth2/token",
+            "iat": now.timestamp(),
+            "ath": ath,
+        });
+
+        let proof = sign_proof(&mut rng, &key, DPOP_JWT_TYPE, &claims);
+        let proof = DPoPProof::verify(&proof, now).unwrap();
+        assert_eq!(proof.jkt(), expected_jkt);
+        proof.verify_jkt(&expected_jkt).unwrap();
+
+        let uri = Url::parse("https://example.com/oauth2/token?foo=bar").unwrap();
+        proof.verify_request(&Method::POST, &uri).unwrap();
+        proof.verify_access_token(access_token).unwrap();
+
+        assert!(matches!(
+            proof.verify_request(&Method::GET, &uri),
+            Err(DPoPProofError::MethodMismatch)
+        ));
+        let other = Url::parse("https://example.com/oauth2/userinfo").unwrap();
+        assert!(matches!(
+            proof.verify_request(&Method::POST, &other),
+            Err(DPoPProofError::UriMismatch)
+        ));
+        let other = Url::parse("https://other.example.com/oauth2/token").unwrap();
+        assert!(matches!(
+            proof.verify_request(&Method::POST, &other),
+            Err(DPoPProofError::UriMismatch)
+        ));
+        assert!(matches!(
+            proof.verify_access_token("mat_other"),
+            Err(DPoPProofError::AccessTokenHashMismatch)
+        ));
+        assert!(matches!(
+            proof.verify_jkt("other"),
+            Err(DPoPProofError::KeyMismatch)
+        ));
+
+        // The same proof is rejected once it is too old
+        let later = now + Duration::minutes(10);
+        let proof = sign_proof(&mut rng, &key, DPOP_JWT_TYPE, &claims);
+        assert!(matches!(
+            DPoPProof::verify(&proof, later),
+            Err(DPoPProofError::TooOld)
+        ));
+
+        // Proofs with the wrong type are rejected
+        let proof = sign_proof(&mut rng, &key, "JWT", &claims);
+        assert!(matches!(
+            DPoPProof::verify(&proof, now),
+            Err(DPoPProofError::InvalidType)
+        ));
+    }
+
+    #[test]
+    fn verify_ed25519_proof() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let now = Utc.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap();
+        let key = PrivateKey::generate_ed25519(&mut rng);
+        let expected_jkt = JsonWebKeyPublicParameters::from(&key).thumbprint_sha256();
+
+        let claims = json!({
+            "jti": "proof-1",
+            "htm": "POST",
+            "htu": "https://example.com/oauth2/token",
+            "iat": now.timestamp(),
+        });
+
+        let proof = sign_proof(&mut rng, &key, DPOP_JWT_TYPE, &claims);
+        let proof = DPoPProof::verify(&proof, now).unwrap();
+        proof.verify_jkt(&expected_jkt).unwrap();
+    }
+
+    #[test]
+    fn supported_algorithms_header() {
+        let algs: Vec<_> = SUPPORTED_ALGORITHMS
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(SUPPORTED_ALGORITHMS_HEADER, algs.join(" "));
+    }
+}
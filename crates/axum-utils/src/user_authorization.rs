@@ -15,8 +15,12 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use axum_extra::typed_header::{TypedHeader, TypedHeaderRejectionReason};
-use headers::{authorization::Bearer, Authorization, Header, HeaderMapExt, HeaderName};
-use http::{header::WWW_AUTHENTICATE, HeaderMap, HeaderValue, Request, StatusCode};
+use chrono::{DateTime, Utc};
+use headers::{
+    authorization::{Bearer, Credentials},
+    Authorization, Header, HeaderMapExt, HeaderName,
+};
+use http::{header::WWW_AUTHENTICATE, HeaderMap, HeaderValue, Method, Request, StatusCode};
 use mas_data_model::Session;
 use mas_storage::{
     oauth2::{OAuth2AccessTokenRepository, OAuth2SessionRepository},
@@ -24,6 +28,9 @@ use mas_storage::{
 };
 use serde::{de::DeserializeOwned, Deserialize};
 use thiserror::Error;
+use url::Url;
+
+use crate::dpop::{DPoPHeader, DPoPProof, DPoPProofError, SUPPORTED_ALGORITHMS_HEADER};
 
 #[derive(Debug, Deserialize)]
 struct AuthorizedForm<F> {
@@ -34,10 +41,34 @@ struct AuthorizedForm<F> {
     inner: F,
 }
 
+/// An access token sent with the `DPoP` authorization scheme
+#[derive(Debug, Clone)]
+struct DPoPToken {
+    token: String,
+    value: HeaderValue,
+}
+
+impl Credentials for DPoPToken {
+    const SCHEME: &'static str = "DPoP";
+
+    fn decode(value: &HeaderValue) -> Option<Self> {
+        let token = value.to_str().ok()?.get(Self::SCHEME.len() + 1..)?;
+        Some(Self {
+            token: token.trim_start().to_owned(),
+            value: value.clone(),
+        })
+    }
+
+    fn encode(&self) -> HeaderValue {
+        self.value.clone()
+    }
+}
+
 #[derive(Debug)]
 enum AccessToken {
     Form(String),
     Header(String),
+    DPoP(String),
     None,
 }
 
@@ -47,7 +78,7 @@ impl AccessToken {
         repo: &mut impl RepositoryAccess<Error = E>,
     ) -> Result<(mas_data_model::AccessToken, Session), AuthorizationVerificationError<E>> {
         let token = match self {
-            AccessToken::Form(t) | AccessToken::Header(t) => t,
+            AccessToken::Form(t) | AccessToken::Header(t) | AccessToken::DPoP(t) => t,
             AccessToken::None => return Err(AuthorizationVerificationError::MissingToken),
         };
 
@@ -70,24 +101,85 @@ impl AccessToken {
 #[derive(Debug)]
 pub struct UserAuthorization<F = ()> {
     access_token: AccessToken,
+    dpop_proof: Option<String>,
+    method: Method,
     form: Option<F>,
 }
 
+/// Record the use of a DPoP proof, failing if it was already used
+async fn consume_dpop_proof<E>(
+    repo: &mut impl RepositoryAccess<Error = E>,
+    clock: &impl Clock,
+    proof: &DPoPProof,
+) -> Result<(), AuthorizationVerificationError<E>> {
+    if proof.consume(repo, clock).await? {
+        Ok(())
+    } else {
+        Err(AuthorizationVerificationError::InvalidDPoPProof(
+            DPoPProofError::Replayed,
+        ))
+    }
+}
+
 impl<F: Send> UserAuthorization<F> {
+    /// Check that the token is presented the way it should be, depending on
+    /// whether it is bound to a DPoP key or not, returning the DPoP proof if
+    /// there is one
+    fn verify_binding<E>(
+        &self,
+        token: &mas_data_model::AccessToken,
+        endpoint: &Url,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DPoPProof>, AuthorizationVerificationError<E>> {
+        match (&self.access_token, &token.dpop_jkt) {
+            (AccessToken::DPoP(raw_token), Some(jkt)) => {
+                let proof = self
+                    .dpop_proof
+                    .as_deref()
+                    .ok_or(AuthorizationVerificationError::MissingDPoPProof)?;
+
+                let verify = || -> Result<DPoPProof, DPoPProofError> {
+                    let proof = DPoPProof::verify(proof, now)?;
+                    proof.verify_jkt(jkt)?;
+                    proof.verify_access_token(raw_token)?;
+                    proof.verify_request(&self.method, endpoint)?;
+                    Ok(proof)
+                };
+
+                verify()
+                    .map(Some)
+                    .map_err(AuthorizationVerificationError::InvalidDPoPProof)
+            }
+
+            // Bound tokens must be presented with the DPoP scheme, and the DPoP
+            // scheme can only be used with bound tokens
+            (_, Some(_)) | (AccessToken::DPoP(_), None) => {
+                Err(AuthorizationVerificationError::InvalidToken)
+            }
+
+            (_, None) => Ok(None),
+        }
+    }
+
     // TODO: take scopes to validate as parameter
     /// Verify a user authorization and return the session and the protected
     /// form value
     ///
+    /// `endpoint` is the public URL of the endpoint, which DPoP proofs are
+    /// checked against. DPoP proofs are recorded in `repo` to detect replays,
+    /// so the caller must save it.
+    ///
     /// # Errors
     ///
     /// Returns an error if the token is invalid, if the user session ended or
     /// if the form is missing
     pub async fn protected_form<E>(
-        self,
+        mut self,
         repo: &mut impl RepositoryAccess<Error = E>,
         clock: &impl Clock,
+        endpoint: &Url,
     ) -> Result<(Session, F), AuthorizationVerificationError<E>> {
-        let Some(form) = self.form else {
+        let Some(form) = self.form.take() else {
             return Err(AuthorizationVerificationError::MissingForm);
         };
 
@@ -97,12 +189,20 @@ impl<F: Send> UserAuthorization<F> {
             return Err(AuthorizationVerificationError::InvalidToken);
         }
 
+        if let Some(proof) = self.verify_binding(&token, endpoint, clock.now())? {
+            consume_dpop_proof(repo, clock, &proof).await?;
+        }
+
         Ok((session, form))
     }
 
     // TODO: take scopes to validate as parameter
     /// Verify a user authorization and return the session
     ///
+    /// `endpoint` is the public URL of the endpoint, which DPoP proofs are
+    /// checked against. DPoP proofs are recorded in `repo` to detect replays,
+    /// so the caller must save it.
+    ///
     /// # Errors
     ///
     /// Returns an error if the token is invalid or if the user session ended
@@ -110,6 +210,7 @@ impl<F: Send> UserAuthorization<F> {
         self,
         repo: &mut impl RepositoryAccess<Error = E>,
         clock: &impl Clock,
+        endpoint: &Url,
     ) -> Result<Session, AuthorizationVerificationError<E>> {
         let (token, session) = self.access_token.fetch(repo).await?;
 
@@ -117,6 +218,10 @@ impl<F: Send> UserAuthorization<F> {
             return Err(AuthorizationVerificationError::InvalidToken);
         }
 
+        if let Some(proof) = self.verify_binding(&token, endpoint, clock.now())? {
+            consume_dpop_proof(repo, clock, &proof).await?;
+        }
+
         Ok(session)
     }
 }
@@ -139,6 +244,12 @@ pub enum AuthorizationVerificationError<E> {
     #[error("missing form")]
     MissingForm,
 
+    #[error("missing DPoP proof")]
+    MissingDPoPProof,
+
+    #[error("invalid DPoP proof")]
+    InvalidDPoPProof(#[source] DPoPProofError),
+
     #[error(transparent)]
     Internal(#[from] E),
 }
@@ -181,6 +292,10 @@ enum WwwAuthenticate {
         error: BearerError,
         error_description: Option<HeaderValue>,
     },
+    DPoP {
+        error: HeaderValue,
+        error_description: Option<HeaderValue>,
+    },
 }
 
 impl Header for WwwAuthenticate {
@@ -221,6 +336,23 @@ impl Header for WwwAuthenticate {
 
                 ("Bearer", params)
             }
+            WwwAuthenticate::DPoP {
+                error,
+                error_description,
+            } => {
+                let mut params = HashMap::new();
+                params.insert("error", error.clone());
+                params.insert(
+                    "algs",
+                    HeaderValue::from_static(SUPPORTED_ALGORITHMS_HEADER),
+                );
+
+                if let Some(error_description) = error_description {
+                    params.insert("error_description", error_description.clone());
+                }
+
+                ("DPoP", params)
+            }
         };
 
         let params = params.into_iter().map(|(k, v)| format!(" {k}={v:?}"));
@@ -274,6 +406,15 @@ where
                 });
                 (StatusCode::BAD_REQUEST, headers).into_response()
             }
+            Self::MissingDPoPProof | Self::InvalidDPoPProof(_) => {
+                let mut headers = HeaderMap::new();
+
+                headers.typed_insert(WwwAuthenticate::DPoP {
+                    error: HeaderValue::from_static("invalid_dpop_proof"),
+                    error_description: None,
+                });
+                (StatusCode::UNAUTHORIZED, headers).into_response()
+            }
             Self::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
         }
     }
@@ -297,15 +438,35 @@ where
 
         // Take the Authorization header
         let token_from_header = match header {
-            Ok(header) => Some(header.token().to_owned()),
+            Ok(header) => Some(AccessToken::Header(header.token().to_owned())),
             Err(err) => match err.reason() {
                 // If it's missing it is fine
                 TypedHeaderRejectionReason::Missing => None,
-                // If the header could not be parsed, return the error
-                _ => return Err(UserAuthorizationError::InvalidHeader),
+                // If it's not a Bearer token, it might be a DPoP-bound one
+                _ => {
+                    let header = TypedHeader::<Authorization<DPoPToken>>::from_request_parts(
+                        &mut parts, state,
+                    )
+                    .await
+                    .map_err(|_| UserAuthorizationError::InvalidHeader)?;
+                    let TypedHeader(Authorization(DPoPToken { token, .. })) = header;
+                    Some(AccessToken::DPoP(token))
+                }
             },
         };
 
+        // Take the DPoP proof, if any
+        let dpop_proof =
+            match TypedHeader::<DPoPHeader>::from_request_parts(&mut parts, state).await {
+                Ok(TypedHeader(header)) => Some(header.proof().to_owned()),
+                Err(err) => match err.reason() {
+                    TypedHeaderRejectionReason::Missing => None,
+                    _ => return Err(UserAuthorizationError::InvalidHeader),
+                },
+            };
+
+        let method = parts.method.clone();
+
         let req = Request::from_parts(parts, body);
 
         // Take the form value
@@ -325,11 +486,16 @@ where
         let access_token = match (token_from_header, token_from_form) {
             // Ensure the token should not be in both the form and the access token
             (Some(_), Some(_)) => return Err(UserAuthorizationError::TokenInFormAndHeader),
-            (Some(t), None) => AccessToken::Header(t),
+            (Some(t), None) => t,
             (None, Some(t)) => AccessToken::Form(t),
             (None, None) => AccessToken::None,
         };
 
-        Ok(UserAuthorization { access_token, form })
+        Ok(UserAuthorization {
+            access_token,
+            dpop_proof,
+            method,
+            form,
+        })
     }
 }
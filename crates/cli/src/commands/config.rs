@@ -8,12 +8,15 @@ use std::process::ExitCode;
 
 use anyhow::Context;
 use camino::Utf8PathBuf;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use figment::Figment;
-use mas_config::{ConfigurationSection, RootConfig, SyncConfig};
+use mas_config::{ConfigurationSection, KeyConfig, KeyState, KeyType, RootConfig, SyncConfig};
 use mas_storage::SystemClock;
 use mas_storage_pg::MIGRATOR;
-use rand::SeedableRng;
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    SeedableRng,
+};
 use tokio::io::AsyncWriteExt;
 use tracing::{info, info_span, Instrument};
 
@@ -48,6 +51,27 @@ enum Subcommand {
         output: Option<Utf8PathBuf>,
     },
 
+    /// Generate a new signing key, to stage it for a key rotation
+    ///
+    /// The key is generated in the `pending` state, so that it is published
+    /// in the JWKS without being used for signing yet. The configuration
+    /// snippet to add to the `secrets.keys` list is written to the standard
+    /// output.
+    GenerateKey {
+        /// The type of key to generate
+        #[clap(long = "type", value_enum, default_value_t = KeyTypeArg::Rsa)]
+        key_type: KeyTypeArg,
+
+        /// The ID of the key. A random one is generated if not specified
+        #[clap(long)]
+        kid: Option<String>,
+
+        /// Write the private key to this file, instead of embedding it in the
+        /// configuration snippet
+        #[clap(long)]
+        key_file: Option<Utf8PathBuf>,
+    },
+
     /// Sync the clients and providers from the config file to the database
     Sync {
         /// Prune elements that are in the database but not in the config file
@@ -61,6 +85,36 @@ enum Subcommand {
     },
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum KeyTypeArg {
+    /// An RSA key
+    Rsa,
+
+    /// An ECDSA key on the P-256 curve
+    EcP256,
+
+    /// An ECDSA key on the P-384 curve
+    EcP384,
+
+    /// An ECDSA key on the secp256k1 curve
+    EcK256,
+
+    /// An Ed25519 key
+    Ed25519,
+}
+
+impl From<KeyTypeArg> for KeyType {
+    fn from(value: KeyTypeArg) -> Self {
+        match value {
+            KeyTypeArg::Rsa => Self::Rsa,
+            KeyTypeArg::EcP256 => Self::EcP256,
+            KeyTypeArg::EcP384 => Self::EcP384,
+            KeyTypeArg::EcK256 => Self::EcK256,
+            KeyTypeArg::Ed25519 => Self::Ed25519,
+        }
+    }
+}
+
 impl Options {
     pub async fn run(self, figment: &Figment) -> anyhow::Result<ExitCode> {
         use Subcommand as SC;
@@ -106,6 +160,41 @@ impl Options {
                 }
             }
 
+            SC::GenerateKey {
+                key_type,
+                kid,
+                key_file,
+            } => {
+                let _span = info_span!("cli.config.generate_key").entered();
+
+                // XXX: we should disallow SeedableRng::from_entropy
+                let mut rng = rand_chacha::ChaChaRng::from_entropy();
+                let kid = kid.unwrap_or_else(|| Alphanumeric.sample_string(&mut rng, 10));
+                let key = KeyType::from(key_type).generate_pem(&mut rng).await?;
+
+                let key_config = if let Some(key_file) = key_file {
+                    info!("Writing private key to {key_file:?}");
+                    // Don't overwrite an existing key, and keep the new one private
+                    let mut file = tokio::fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .mode(0o600)
+                        .open(&key_file)
+                        .await
+                        .with_context(|| format!("could not create {key_file:?}"))?;
+                    file.write_all(key.as_bytes()).await?;
+                    KeyConfig::new_with_key_file(kid, key_file)
+                } else {
+                    KeyConfig::new(kid, key)
+                };
+
+                let key_config = key_config.with_state(KeyState::Pending);
+                let snippet = serde_yaml::to_string(&[key_config])?;
+
+                info!("Add this key to the `secrets.keys` list, and mark it as `active` once relying parties had time to refresh their copy of the JWKS");
+                tokio::io::stdout().write_all(snippet.as_bytes()).await?;
+            }
+
             SC::Sync { prune, dry_run } => {
                 let config = SyncConfig::extract(figment)?;
                 let clock = SystemClock::default();
@@ -122,6 +211,7 @@ impl Options {
 
                 crate::sync::config_sync(
                     config.upstream_oauth2,
+                    config.upstream_saml2,
                     config.clients,
                     &mut conn,
                     &encrypter,
@@ -14,7 +14,10 @@ use figment::Figment;
 use mas_config::{
     ConfigurationSection, ConfigurationSectionExt, DatabaseConfig, MatrixConfig, PasswordsConfig,
 };
-use mas_data_model::{Device, TokenType, Ulid, UpstreamOAuthProvider, User};
+use mas_data_model::{
+    AuditContext, AuditEventKind, Device, TokenType, Ulid, UpstreamOAuthProvider, User,
+    WebhookEventKind,
+};
 use mas_email::Address;
 use mas_handlers::HttpClientFactory;
 use mas_matrix::HomeserverConnection;
@@ -22,11 +25,13 @@ use mas_matrix_synapse::SynapseConnection;
 use mas_storage::{
     compat::{CompatAccessTokenRepository, CompatSessionFilter, CompatSessionRepository},
     job::{
-        DeactivateUserJob, JobRepositoryExt, ProvisionUserJob, ReactivateUserJob, SyncDevicesJob,
+        schedule_backchannel_logout_jobs, DeactivateUserJob, DispatchWebhookEventJob, JobFilter,
+        JobRepository, JobRepositoryExt, JobStatus, ProvisionUserJob, ReactivateUserJob,
+        SyncDevicesJob,
     },
     oauth2::OAuth2SessionFilter,
     user::{BrowserSessionFilter, UserEmailRepository, UserPasswordRepository, UserRepository},
-    Clock, RepositoryAccess, SystemClock,
+    Clock, Pagination, RepositoryAccess, SystemClock,
 };
 use mas_storage_pg::{DatabaseError, PgRepository};
 use rand::{RngCore, SeedableRng};
@@ -54,6 +59,13 @@ fn parse_upstream_provider_mapping(s: &str) -> Result<UpstreamProviderMapping, a
     })
 }
 
+fn parse_job_status(s: &str) -> Result<JobStatus, anyhow::Error> {
+    JobStatus::ALL
+        .into_iter()
+        .find(|status| status.as_str().eq_ignore_ascii_case(s))
+        .with_context(|| format!("Unknown job status {s:?}"))
+}
+
 #[derive(Parser, Debug)]
 pub(super) struct Options {
     #[command(subcommand)]
@@ -167,6 +179,49 @@ enum Subcommand {
         #[clap(long)]
         ignore_password_complexity: bool,
     },
+
+    /// Inspect and manage the background job queue
+    #[command(subcommand)]
+    Jobs(JobsSubcommand),
+}
+
+#[derive(Parser, Debug)]
+enum JobsSubcommand {
+    /// List jobs in the queue
+    List {
+        /// Only list the jobs with this status, like `failed` or `pending`
+        #[arg(long, value_parser = parse_job_status)]
+        status: Option<JobStatus>,
+
+        /// Only list the jobs with this name, like `provision-user`
+        #[arg(long)]
+        name: Option<String>,
+
+        /// How many jobs to list at most
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+
+    /// Show the details of a job, including its payload
+    Show {
+        /// ID of the job
+        id: Ulid,
+    },
+
+    /// Show how many jobs are in the queue, by name and status
+    Stats,
+
+    /// Put a failed or killed job back in the queue
+    Retry {
+        /// ID of the job
+        id: Ulid,
+    },
+
+    /// Cancel a job which is pending or waiting to be retried
+    Cancel {
+        /// ID of the job
+        id: Ulid,
+    },
 }
 
 impl Options {
@@ -176,8 +231,12 @@ impl Options {
         let clock = SystemClock::default();
         // XXX: we should disallow SeedableRng::from_entropy
         let mut rng = rand_chacha::ChaChaRng::from_entropy();
+        // Everything done through those commands is attributed to the operator
+        let audit = AuditContext::cli();
 
         match self.subcommand {
+            SC::Jobs(subcommand) => run_jobs(subcommand, figment, &clock).await,
+
             SC::SetPassword {
                 username,
                 password,
@@ -213,6 +272,17 @@ impl Options {
                     .add(&mut rng, &clock, &user, version, hashed_password, None)
                     .await?;
 
+                repo.audit_event()
+                    .add(
+                        &mut rng,
+                        &clock,
+                        &audit,
+                        AuditEventKind::UserPasswordChanged,
+                        Some(&user),
+                        serde_json::json!({ "ignore_complexity": ignore_complexity }),
+                    )
+                    .await?;
+
                 info!(%user.id, %user.username, "Password changed");
                 repo.into_inner().commit().await?;
 
@@ -250,6 +320,13 @@ impl Options {
                     repo.user_email().set_as_primary(&email).await?;
                 }
 
+                repo.job()
+                    .schedule_job(
+                        DispatchWebhookEventJob::new(WebhookEventKind::UserEmailVerified, &user)
+                            .with_data(serde_json::json!({ "email": email.email })),
+                    )
+                    .await?;
+
                 repo.into_inner().commit().await?;
                 info!(?email, "Email marked as verified");
 
@@ -347,7 +424,18 @@ impl Options {
                 let affected = if dry_run {
                     repo.compat_session().count(filter).await?
                 } else {
-                    repo.compat_session().finish_bulk(&clock, filter).await?
+                    let affected = repo.compat_session().finish_bulk(&clock, filter).await?;
+                    repo.audit_event()
+                        .add(
+                            &mut rng,
+                            &clock,
+                            &audit,
+                            AuditEventKind::SessionEnded,
+                            Some(&user),
+                            serde_json::json!({ "session_type": "compat", "count": affected }),
+                        )
+                        .await?;
+                    affected
                 };
 
                 match affected {
@@ -360,7 +448,20 @@ impl Options {
                 let affected = if dry_run {
                     repo.oauth2_session().count(filter).await?
                 } else {
-                    repo.oauth2_session().finish_bulk(&clock, filter).await?
+                    // Notify the clients which registered a back-channel logout URI
+                    schedule_backchannel_logout_jobs(&mut repo, filter).await?;
+                    let affected = repo.oauth2_session().finish_bulk(&clock, filter).await?;
+                    repo.audit_event()
+                        .add(
+                            &mut rng,
+                            &clock,
+                            &audit,
+                            AuditEventKind::SessionEnded,
+                            Some(&user),
+                            serde_json::json!({ "session_type": "oauth2", "count": affected }),
+                        )
+                        .await?;
+                    affected
                 };
 
                 match affected {
@@ -373,7 +474,18 @@ impl Options {
                 let affected = if dry_run {
                     repo.browser_session().count(filter).await?
                 } else {
-                    repo.browser_session().finish_bulk(&clock, filter).await?
+                    let affected = repo.browser_session().finish_bulk(&clock, filter).await?;
+                    repo.audit_event()
+                        .add(
+                            &mut rng,
+                            &clock,
+                            &audit,
+                            AuditEventKind::SessionEnded,
+                            Some(&user),
+                            serde_json::json!({ "session_type": "browser", "count": affected }),
+                        )
+                        .await?;
+                    affected
                 };
 
                 match affected {
@@ -420,6 +532,24 @@ impl Options {
                 // synchronously yet.
                 let user = repo.user().lock(&clock, user).await?;
 
+                repo.audit_event()
+                    .add(
+                        &mut rng,
+                        &clock,
+                        &audit,
+                        AuditEventKind::UserLocked,
+                        Some(&user),
+                        serde_json::json!({ "deactivate": deactivate }),
+                    )
+                    .await?;
+
+                repo.job()
+                    .schedule_job(
+                        DispatchWebhookEventJob::new(WebhookEventKind::UserLocked, &user)
+                            .with_data(serde_json::json!({ "deactivate": deactivate })),
+                    )
+                    .await?;
+
                 if deactivate {
                     warn!(%user.id, "Scheduling user deactivation");
                     repo.job()
@@ -450,6 +580,24 @@ impl Options {
                     .schedule_job(ReactivateUserJob::new(&user))
                     .await?;
 
+                repo.audit_event()
+                    .add(
+                        &mut rng,
+                        &clock,
+                        &audit,
+                        AuditEventKind::UserUnlocked,
+                        Some(&user),
+                        serde_json::json!({}),
+                    )
+                    .await?;
+
+                repo.job()
+                    .schedule_job(DispatchWebhookEventJob::new(
+                        WebhookEventKind::UserUnlocked,
+                        &user,
+                    ))
+                    .await?;
+
                 repo.into_inner().commit().await?;
 
                 Ok(ExitCode::SUCCESS)
@@ -722,6 +870,160 @@ impl std::fmt::Display for HumanReadable<&UpstreamOAuthProvider> {
     }
 }
 
+#[allow(clippy::too_many_lines)]
+async fn run_jobs(
+    subcommand: JobsSubcommand,
+    figment: &Figment,
+    clock: &dyn Clock,
+) -> anyhow::Result<ExitCode> {
+    use JobsSubcommand as JC;
+
+    let database_config = DatabaseConfig::extract_or_default(figment)?;
+    let mut conn = database_connection_from_config(&database_config).await?;
+    let txn = conn.begin().await?;
+    let mut repo = PgRepository::from_conn(txn);
+    let term = Term::buffered_stdout();
+
+    match subcommand {
+        JC::List {
+            status,
+            name,
+            limit,
+        } => {
+            let _span = info_span!("cli.manage.jobs.list").entered();
+
+            let filter = JobFilter::new();
+            let filter = match &name {
+                Some(name) => filter.for_name(name),
+                None => filter,
+            };
+            let filter = match status {
+                Some(status) => filter.with_status(status),
+                None => filter,
+            };
+
+            let count = repo.job().count(filter).await?;
+            let page = repo.job().list(filter, Pagination::first(limit)).await?;
+
+            for job in &page.edges {
+                term.write_line(&format!(
+                    "{id}  {name}  {status}  {attempts}/{max_attempts}  {last_error}",
+                    id = job.id,
+                    name = pad_str(&job.name, 28, Alignment::Left, None),
+                    status = pad_str(job.status.as_str(), 7, Alignment::Left, None),
+                    attempts = job.attempts,
+                    max_attempts = job.max_attempts,
+                    last_error = job.last_error.as_deref().unwrap_or_default(),
+                ))?;
+            }
+
+            if page.has_next_page {
+                term.write_line(&format!(
+                    "{}",
+                    style(format!("Showing {} out of {count} jobs", page.edges.len())).dim()
+                ))?;
+            }
+        }
+
+        JC::Show { id } => {
+            let _span = info_span!("cli.manage.jobs.show", job.id = %id).entered();
+
+            let job = repo.job().lookup(id).await?.context("Job not found")?;
+
+            let key = Style::new().bold();
+            let fields = [
+                ("ID", job.id.to_string()),
+                ("Name", job.name),
+                ("Status", job.status.to_string()),
+                ("Attempts", format!("{}/{}", job.attempts, job.max_attempts)),
+                ("Run at", job.run_at.to_rfc3339()),
+                ("Last error", job.last_error.unwrap_or_default()),
+                ("Locked by", job.locked_by.unwrap_or_default()),
+                (
+                    "Locked at",
+                    job.locked_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                ),
+                (
+                    "Done at",
+                    job.done_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                ),
+            ];
+
+            for (name, value) in fields {
+                term.write_line(&format!(
+                    "{}: {value}",
+                    key.apply_to(pad_str(name, 10, Alignment::Right, None))
+                ))?;
+            }
+
+            term.write_line(&format!("{}:", key.apply_to("Payload")))?;
+            term.write_line(&serde_json::to_string_pretty(&job.payload)?)?;
+        }
+
+        JC::Stats => {
+            let _span = info_span!("cli.manage.jobs.stats").entered();
+
+            let stats = repo.job().stats(clock).await?;
+            if stats.is_empty() {
+                term.write_line("The job queue is empty")?;
+            }
+
+            for stat in stats {
+                let oldest = stat
+                    .oldest_due_at
+                    .map(|due_at| format!(", oldest due at {}", due_at.to_rfc3339()))
+                    .unwrap_or_default();
+                term.write_line(&format!(
+                    "{name}  {status}  {count} ({retried} retried{oldest})",
+                    name = pad_str(&stat.name, 28, Alignment::Left, None),
+                    status = pad_str(stat.status.as_str(), 7, Alignment::Left, None),
+                    count = stat.count,
+                    retried = stat.retried,
+                ))?;
+            }
+        }
+
+        JC::Retry { id } => {
+            let _span = info_span!("cli.manage.jobs.retry", job.id = %id).entered();
+
+            let job = repo.job().lookup(id).await?.context("Job not found")?;
+            if !job.status.can_be_retried() {
+                error!(
+                    job.id = %id,
+                    job.status = %job.status,
+                    "Only failed or killed jobs can be retried"
+                );
+                return Ok(ExitCode::from(1));
+            }
+
+            info!(job.id = %id, job.name = job.name, "Putting the job back in the queue");
+            repo.job().retry(clock, job).await?;
+        }
+
+        JC::Cancel { id } => {
+            let _span = info_span!("cli.manage.jobs.cancel", job.id = %id).entered();
+
+            let job = repo.job().lookup(id).await?.context("Job not found")?;
+            if !job.status.can_be_cancelled() {
+                error!(
+                    job.id = %id,
+                    job.status = %job.status,
+                    "Only pending jobs or jobs waiting to be retried can be cancelled"
+                );
+                return Ok(ExitCode::from(1));
+            }
+
+            warn!(job.id = %id, job.name = job.name, "Cancelling the job");
+            repo.job().cancel(clock, job).await?;
+        }
+    }
+
+    term.flush()?;
+    repo.into_inner().commit().await?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
 async fn check_and_normalize_username<'a>(
     localpart_or_mxid: &'a str,
     repo: &mut dyn RepositoryAccess<Error = DatabaseError>,
@@ -931,6 +1233,13 @@ impl UserCreationRequest<'_> {
 
         repo.job().schedule_job(provision_job).await?;
 
+        repo.job()
+            .schedule_job(
+                DispatchWebhookEventJob::new(WebhookEventKind::UserRegistered, &user)
+                    .with_data(serde_json::json!({ "method": "cli" })),
+            )
+            .await?;
+
         Ok(user)
     }
 }
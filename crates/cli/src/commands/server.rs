@@ -12,6 +12,7 @@ use figment::Figment;
 use itertools::Itertools;
 use mas_config::{
     AppConfig, ClientsConfig, ConfigurationSection, ConfigurationSectionExt, UpstreamOAuth2Config,
+    UpstreamSaml2Config,
 };
 use mas_handlers::{ActivityTracker, CookieManager, HttpClientFactory, Limiter, MetadataCache};
 use mas_listener::{server::Server, shutdown::ShutdownStream};
@@ -30,9 +31,10 @@ use tracing::{info, info_span, warn, Instrument};
 use crate::{
     app_state::AppState,
     util::{
-        database_pool_from_config, mailer_from_config, password_manager_from_config,
-        policy_factory_from_config, register_sighup, site_config_from_config,
-        templates_from_config,
+        database_pool_from_config, mailer_from_config, maintenance_settings_from_config,
+        password_manager_from_config, policy_factory_from_config, register_sighup,
+        session_expiration_settings_from_config, site_config_from_config, templates_from_config,
+        webhook_targets_from_config,
     },
 };
 
@@ -99,9 +101,11 @@ impl Options {
             let mut conn = pool.acquire().await?;
             let clients_config = ClientsConfig::extract_or_default(figment)?;
             let upstream_oauth2_config = UpstreamOAuth2Config::extract_or_default(figment)?;
+            let upstream_saml2_config = UpstreamSaml2Config::extract_or_default(figment)?;
 
             crate::sync::config_sync(
                 upstream_oauth2_config,
+                upstream_saml2_config,
                 clients_config,
                 &mut conn,
                 &encrypter,
@@ -141,6 +145,7 @@ impl Options {
             &config.passwords,
             &config.account,
             &config.captcha,
+            &config.upstream_oauth2,
         )?;
 
         // Load and compile the templates
@@ -171,6 +176,11 @@ impl Options {
                 &mailer,
                 homeserver_connection.clone(),
                 url_builder.clone(),
+                key_store.clone(),
+                http_client_factory.clone(),
+                webhook_targets_from_config(&config.webhooks),
+                maintenance_settings_from_config(&config.maintenance),
+                session_expiration_settings_from_config(&config.sessions),
             )
             .await?;
             // TODO: grab the handle
@@ -179,7 +189,14 @@ impl Options {
 
         let listeners_config = config.http.listeners.clone();
 
-        let password_manager = password_manager_from_config(&config.passwords).await?;
+        let mut password_manager = password_manager_from_config(&config.passwords).await?;
+        if let Some(ldap) = mas_handlers::ldap::Ldap::new(&config.ldap) {
+            if password_manager.is_enabled() {
+                password_manager = password_manager.with_ldap(ldap);
+            } else {
+                warn!("LDAP authentication is configured, but password authentication is disabled. LDAP authentication will not be available.");
+            }
+        }
 
         // The upstream OIDC metadata cache
         let metadata_cache = MetadataCache::new();
@@ -198,9 +215,6 @@ impl Options {
         // Explicitly the config to properly zeroize secret keys
         drop(config);
 
-        // Listen for SIGHUP
-        register_sighup(&templates, &activity_tracker)?;
-
         limiter.start();
 
         let graphql_schema = mas_handlers::graphql_schema(
@@ -238,6 +252,7 @@ impl Options {
         };
 
         let mut fd_manager = listenfd::ListenFd::from_env();
+        let mut tls_certificates = Vec::new();
 
         let servers: Vec<Server<_>> = listeners_config
             .into_iter()
@@ -247,7 +262,9 @@ impl Options {
 
                 // Load the TLS config
                 let tls_config = if let Some(tls_config) = config.tls.as_ref() {
-                    let tls_config = crate::server::build_tls_server_config(tls_config)?;
+                    let (tls_config, tls_certificate) =
+                        crate::server::build_tls_server_config(tls_config)?;
+                    tls_certificates.extend(tls_certificate);
                     Some(Arc::new(tls_config))
                 } else {
                     None
@@ -302,6 +319,9 @@ impl Options {
             .flatten_ok()
             .collect::<Result<Vec<_>, _>>()?;
 
+        // Listen for SIGHUP
+        register_sighup(&state.templates, &state.activity_tracker, tls_certificates)?;
+
         let shutdown = ShutdownStream::default()
             .with_timeout(Duration::from_secs(60))
             .with_signal(SignalKind::terminate())?
@@ -6,6 +6,7 @@
 
 use std::process::ExitCode;
 
+use anyhow::Context;
 use clap::Parser;
 use figment::Figment;
 use mas_config::{AppConfig, ConfigurationSection};
@@ -19,7 +20,9 @@ use rand::{
 use tracing::{info, info_span};
 
 use crate::util::{
-    database_pool_from_config, mailer_from_config, site_config_from_config, templates_from_config,
+    database_pool_from_config, mailer_from_config, maintenance_settings_from_config,
+    session_expiration_settings_from_config, site_config_from_config, templates_from_config,
+    webhook_targets_from_config,
 };
 
 #[derive(Parser, Debug, Default)]
@@ -34,6 +37,13 @@ impl Options {
         info!("Connecting to the database");
         let pool = database_pool_from_config(&config.database).await?;
 
+        // Initialize the key store
+        let key_store = config
+            .secrets
+            .key_store()
+            .await
+            .context("could not import keys from config")?;
+
         let url_builder = UrlBuilder::new(
             config.http.public_base.clone(),
             config.http.issuer.clone(),
@@ -48,6 +58,7 @@ impl Options {
             &config.passwords,
             &config.account,
             &config.captcha,
+            &config.upstream_oauth2,
         )?;
 
         // Load and compile the templates
@@ -62,9 +73,13 @@ impl Options {
             config.matrix.homeserver.clone(),
             config.matrix.endpoint.clone(),
             config.matrix.secret.clone(),
-            http_client_factory,
+            http_client_factory.clone(),
         );
 
+        let webhook_targets = webhook_targets_from_config(&config.webhooks);
+        let maintenance_settings = maintenance_settings_from_config(&config.maintenance);
+        let session_expiration_settings = session_expiration_settings_from_config(&config.sessions);
+
         drop(config);
 
         #[allow(clippy::disallowed_methods)]
@@ -72,7 +87,19 @@ impl Options {
         let worker_name = Alphanumeric.sample_string(&mut rng, 10);
 
         info!(worker_name, "Starting task scheduler");
-        let monitor = mas_tasks::init(&worker_name, &pool, &mailer, conn, url_builder).await?;
+        let monitor = mas_tasks::init(
+            &worker_name,
+            &pool,
+            &mailer,
+            conn,
+            url_builder,
+            key_store,
+            http_client_factory,
+            webhook_targets,
+            maintenance_settings,
+            session_expiration_settings,
+        )
+        .await?;
 
         span.exit();
 
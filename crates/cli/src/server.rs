@@ -8,6 +8,7 @@ use std::{
     future::ready,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, ToSocketAddrs},
     os::unix::net::UnixListener,
+    sync::Arc,
 };
 
 use anyhow::Context;
@@ -16,13 +17,16 @@ use axum::{
     extract::{FromRef, MatchedPath},
     Extension, Router,
 };
+use camino::Utf8PathBuf;
 use hyper::{
     header::{HeaderValue, CACHE_CONTROL, USER_AGENT},
     Method, Request, Response, StatusCode, Version,
 };
 use listenfd::ListenFd;
 use mas_config::{HttpBindConfig, HttpResource, HttpTlsConfig, UnixOrTcp};
-use mas_listener::{unix_or_tcp::UnixOrTcpListener, ConnectionInfo};
+use mas_listener::{
+    maybe_tls::ReloadableCertResolver, unix_or_tcp::UnixOrTcpListener, ConnectionInfo,
+};
 use mas_router::Route;
 use mas_templates::Templates;
 use mas_tower::{
@@ -35,12 +39,13 @@ use opentelemetry_semantic_conventions::trace::{
     HTTP_REQUEST_METHOD, HTTP_RESPONSE_STATUS_CODE, HTTP_ROUTE, NETWORK_PROTOCOL_NAME,
     NETWORK_PROTOCOL_VERSION, URL_PATH, URL_QUERY, URL_SCHEME, USER_AGENT_ORIGINAL,
 };
-use rustls::ServerConfig;
+use rustls::{sign::CertifiedKey, ServerConfig};
 use sentry_tower::{NewSentryLayer, SentryHttpLayer};
 use tower::Layer;
 use tower_http::{services::ServeDir, set_header::SetResponseHeaderLayer};
 use tracing::Span;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::app_state::AppState;
 
@@ -228,6 +233,7 @@ pub fn build_router(
                 let (_, api_router) = mas_handlers::admin_api_router::<AppState>();
                 router.merge(api_router)
             }
+            mas_config::HttpResource::Scim => router.merge(mas_handlers::scim_router::<AppState>()),
             // TODO: do a better handler here
             mas_config::HttpResource::ConnectionInfo => router.route(
                 "/connection-info",
@@ -276,16 +282,83 @@ pub fn build_router(
         .with_state(state)
 }
 
-pub fn build_tls_server_config(config: &HttpTlsConfig) -> Result<ServerConfig, anyhow::Error> {
+fn load_certified_key(config: &HttpTlsConfig) -> Result<CertifiedKey, anyhow::Error> {
     let (key, chain) = config.load()?;
+    let key = rustls::crypto::aws_lc_rs::sign::any_supported_type(&key)
+        .context("unsupported TLS private key")?;
+
+    let certified_key = CertifiedKey::new(chain, key);
+    certified_key
+        .keys_match()
+        .context("TLS private key does not match the certificate")?;
+
+    Ok(certified_key)
+}
 
-    let mut config = rustls::ServerConfig::builder()
+/// The TLS certificate of a listener, which can be reloaded from disk while the
+/// server is running
+///
+/// Only the paths of the files are kept, along with the key password, so that
+/// the rest of the configuration can be dropped and zeroized.
+#[derive(Clone)]
+pub struct TlsCertificateReloader {
+    certificate_file: Utf8PathBuf,
+    key_file: Utf8PathBuf,
+    password: Option<Zeroizing<String>>,
+    password_file: Option<Utf8PathBuf>,
+    resolver: Arc<ReloadableCertResolver>,
+}
+
+impl TlsCertificateReloader {
+    /// Create a reloader for the given listener configuration, if both the
+    /// certificate and the key are read from files
+    fn new(config: &HttpTlsConfig, resolver: Arc<ReloadableCertResolver>) -> Option<Self> {
+        Some(Self {
+            certificate_file: config.certificate_file.clone()?,
+            key_file: config.key_file.clone()?,
+            password: config.password.clone().map(Zeroizing::new),
+            password_file: config.password_file.clone(),
+            resolver,
+        })
+    }
+
+    /// Load the certificate chain and key again, and use them for new
+    /// connections. On error, the current certificate is kept.
+    pub fn reload(&self) -> Result<(), anyhow::Error> {
+        let mut config = HttpTlsConfig {
+            certificate: None,
+            certificate_file: Some(self.certificate_file.clone()),
+            key: None,
+            key_file: Some(self.key_file.clone()),
+            password: self.password.as_deref().cloned(),
+            password_file: self.password_file.clone(),
+        };
+        let certified_key = load_certified_key(&config);
+        config.password.zeroize();
+
+        self.resolver.replace(certified_key?);
+        Ok(())
+    }
+}
+
+/// Build the TLS configuration of a listener
+///
+/// A [`TlsCertificateReloader`] is returned only if the certificate and the
+/// key are read from files, as inline ones can't change without a restart.
+pub fn build_tls_server_config(
+    config: &HttpTlsConfig,
+) -> Result<(ServerConfig, Option<TlsCertificateReloader>), anyhow::Error> {
+    let certified_key = load_certified_key(config).context("failed to build TLS server config")?;
+    let resolver = Arc::new(ReloadableCertResolver::new(certified_key));
+
+    let mut server_config = rustls::ServerConfig::builder()
         .with_no_client_auth()
-        .with_single_cert(chain, key)
-        .context("failed to build TLS server config")?;
-    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        .with_cert_resolver(resolver.clone());
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    let reloader = TlsCertificateReloader::new(config, resolver);
 
-    Ok(config)
+    Ok((server_config, reloader))
 }
 
 pub fn build_listeners(
@@ -356,3 +429,76 @@ pub fn build_listeners(
 
     Ok(listeners)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SERVER_CERT_PEM: &str = include_str!("../../listener/examples/demo/certs/server.pem");
+    static SERVER_KEY_PEM: &str = include_str!("../../listener/examples/demo/certs/server-key.pem");
+    static CLIENT_KEY_PEM: &str = include_str!("../../listener/examples/demo/certs/client-key.pem");
+
+    fn cert_file(name: &str) -> Utf8PathBuf {
+        Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../listener/examples/demo/certs")
+            .join(name)
+    }
+
+    fn tls_config(certificate: &str, key: &str) -> HttpTlsConfig {
+        HttpTlsConfig {
+            certificate: Some(certificate.to_owned()),
+            certificate_file: None,
+            key: Some(key.to_owned()),
+            key_file: None,
+            password: None,
+            password_file: None,
+        }
+    }
+
+    #[test]
+    fn test_load_certified_key() {
+        let certified_key = load_certified_key(&tls_config(SERVER_CERT_PEM, SERVER_KEY_PEM));
+        assert!(certified_key.is_ok());
+
+        // The key doesn't match the certificate
+        let certified_key = load_certified_key(&tls_config(SERVER_CERT_PEM, CLIENT_KEY_PEM));
+        assert!(certified_key.is_err());
+    }
+
+    #[test]
+    fn test_reload_keeps_certificate_on_mismatch() {
+        // Building the server config needs a process-wide crypto provider. Another
+        // test may have installed it already.
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        // Inline certificates can't be reloaded
+        let (_server_config, reloader) =
+            build_tls_server_config(&tls_config(SERVER_CERT_PEM, SERVER_KEY_PEM)).unwrap();
+        assert!(reloader.is_none());
+
+        let config = HttpTlsConfig {
+            certificate: None,
+            certificate_file: Some(cert_file("server.pem")),
+            key: None,
+            key_file: Some(cert_file("server-key.pem")),
+            password: None,
+            password_file: None,
+        };
+        let (_server_config, reloader) = build_tls_server_config(&config).unwrap();
+        let reloader = reloader.unwrap();
+        let before = reloader.resolver.current();
+
+        // Reloading the same files replaces the certificate
+        reloader.reload().unwrap();
+        assert!(!Arc::ptr_eq(&before, &reloader.resolver.current()));
+        let before = reloader.resolver.current();
+
+        // A mismatched pair is refused, and the current certificate is kept
+        let reloader = TlsCertificateReloader {
+            key_file: cert_file("client-key.pem"),
+            ..reloader
+        };
+        assert!(reloader.reload().is_err());
+        assert!(Arc::ptr_eq(&before, &reloader.resolver.current()));
+    }
+}
@@ -8,7 +8,14 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 
-use mas_config::{ClientsConfig, UpstreamOAuth2Config};
+use mas_config::{
+    ClientsConfig, UpstreamOAuth2Config, UpstreamSaml2Config, UpstreamSaml2SsoBinding,
+};
+use mas_data_model::{
+    UpstreamOAuthProviderDiscoveryMode, UpstreamOAuthProviderPkceMode,
+    UpstreamOAuthProviderSamlBinding, UpstreamOAuthProviderSamlSettings,
+};
+use mas_handlers::upstream_oauth2::{map_claims_imports, map_discovery_mode, map_pkce_method};
 use mas_keystore::Encrypter;
 use mas_storage::{
     upstream_oauth2::{UpstreamOAuthProviderFilter, UpstreamOAuthProviderParams},
@@ -18,61 +25,10 @@ use mas_storage_pg::PgRepository;
 use sqlx::{postgres::PgAdvisoryLock, Connection, PgConnection};
 use tracing::{error, info, info_span, warn};
 
-fn map_import_action(
-    config: mas_config::UpstreamOAuth2ImportAction,
-) -> mas_data_model::UpstreamOAuthProviderImportAction {
-    match config {
-        mas_config::UpstreamOAuth2ImportAction::Ignore => {
-            mas_data_model::UpstreamOAuthProviderImportAction::Ignore
-        }
-        mas_config::UpstreamOAuth2ImportAction::Suggest => {
-            mas_data_model::UpstreamOAuthProviderImportAction::Suggest
-        }
-        mas_config::UpstreamOAuth2ImportAction::Force => {
-            mas_data_model::UpstreamOAuthProviderImportAction::Force
-        }
-        mas_config::UpstreamOAuth2ImportAction::Require => {
-            mas_data_model::UpstreamOAuthProviderImportAction::Require
-        }
-    }
-}
-
-fn map_claims_imports(
-    config: &mas_config::UpstreamOAuth2ClaimsImports,
-) -> mas_data_model::UpstreamOAuthProviderClaimsImports {
-    mas_data_model::UpstreamOAuthProviderClaimsImports {
-        subject: mas_data_model::UpstreamOAuthProviderSubjectPreference {
-            template: config.subject.template.clone(),
-        },
-        localpart: mas_data_model::UpstreamOAuthProviderImportPreference {
-            action: map_import_action(config.localpart.action),
-            template: config.localpart.template.clone(),
-        },
-        displayname: mas_data_model::UpstreamOAuthProviderImportPreference {
-            action: map_import_action(config.displayname.action),
-            template: config.displayname.template.clone(),
-        },
-        email: mas_data_model::UpstreamOAuthProviderImportPreference {
-            action: map_import_action(config.email.action),
-            template: config.email.template.clone(),
-        },
-        verify_email: match config.email.set_email_verification {
-            mas_config::UpstreamOAuth2SetEmailVerification::Always => {
-                mas_data_model::UpsreamOAuthProviderSetEmailVerification::Always
-            }
-            mas_config::UpstreamOAuth2SetEmailVerification::Never => {
-                mas_data_model::UpsreamOAuthProviderSetEmailVerification::Never
-            }
-            mas_config::UpstreamOAuth2SetEmailVerification::Import => {
-                mas_data_model::UpsreamOAuthProviderSetEmailVerification::Import
-            }
-        },
-    }
-}
-
 #[tracing::instrument(name = "config.sync", skip_all, err(Debug))]
 pub async fn config_sync(
     upstream_oauth2_config: UpstreamOAuth2Config,
+    upstream_saml2_config: UpstreamSaml2Config,
     clients_config: ClientsConfig,
     connection: &mut PgConnection,
     encrypter: &Encrypter,
@@ -99,12 +55,50 @@ pub async fn config_sync(
 
     {
         let _span = info_span!("cli.config.sync.providers").entered();
+        // Both OAuth 2.0 providers and SAML 2.0 identity providers are stored as
+        // upstream providers, so their IDs must not collide
+        let saml_ids = upstream_saml2_config
+            .providers
+            .iter()
+            .map(|p| p.id)
+            .collect::<BTreeSet<_>>();
+        if let Some(provider) = upstream_oauth2_config
+            .providers
+            .iter()
+            .find(|p| saml_ids.contains(&p.id))
+        {
+            anyhow::bail!(
+                "Provider {} is defined both as an OAuth 2.0 provider and as a SAML 2.0 identity provider",
+                provider.id
+            );
+        }
+
         let config_ids = upstream_oauth2_config
             .providers
             .iter()
             .filter(|p| p.enabled)
             .map(|p| p.id)
+            .chain(
+                upstream_saml2_config
+                    .providers
+                    .iter()
+                    .filter(|p| p.enabled)
+                    .map(|p| p.id),
+            )
+            .collect::<BTreeSet<_>>();
+
+        // When providers can be managed through the admin API, the config file is
+        // not authoritative, and only providers it knows about are considered
+        let admin_api_allowed = upstream_oauth2_config.allow_admin_api;
+        let known_ids = upstream_oauth2_config
+            .providers
+            .iter()
+            .map(|p| p.id)
+            .chain(saml_ids)
             .collect::<BTreeSet<_>>();
+        if admin_api_allowed {
+            info!("Providers are managed through the admin API, leaving other providers untouched");
+        }
 
         // Let's assume we have less than 1000 providers
         let page = repo
@@ -126,6 +120,10 @@ pub async fn config_sync(
         let mut existing_disabled = BTreeMap::new();
         // Process the existing providers
         for provider in page.edges {
+            if admin_api_allowed && !known_ids.contains(&provider.id) {
+                continue;
+            }
+
             if provider.enabled() {
                 if config_ids.contains(&provider.id) {
                     existing_enabled_ids.insert(provider.id);
@@ -193,17 +191,7 @@ pub async fn config_sync(
                 .map(|client_secret| encrypter.encrypt_to_string(client_secret.as_bytes()))
                 .transpose()?;
 
-            let discovery_mode = match provider.discovery_mode {
-                mas_config::UpstreamOAuth2DiscoveryMode::Oidc => {
-                    mas_data_model::UpstreamOAuthProviderDiscoveryMode::Oidc
-                }
-                mas_config::UpstreamOAuth2DiscoveryMode::Insecure => {
-                    mas_data_model::UpstreamOAuthProviderDiscoveryMode::Insecure
-                }
-                mas_config::UpstreamOAuth2DiscoveryMode::Disabled => {
-                    mas_data_model::UpstreamOAuthProviderDiscoveryMode::Disabled
-                }
-            };
+            let discovery_mode = map_discovery_mode(provider.discovery_mode);
 
             if discovery_mode.is_disabled() {
                 if provider.authorization_endpoint.is_none() {
@@ -219,17 +207,7 @@ pub async fn config_sync(
                 }
             }
 
-            let pkce_mode = match provider.pkce_method {
-                mas_config::UpstreamOAuth2PkceMethod::Auto => {
-                    mas_data_model::UpstreamOAuthProviderPkceMode::Auto
-                }
-                mas_config::UpstreamOAuth2PkceMethod::Always => {
-                    mas_data_model::UpstreamOAuthProviderPkceMode::S256
-                }
-                mas_config::UpstreamOAuth2PkceMethod::Never => {
-                    mas_data_model::UpstreamOAuthProviderPkceMode::Disabled
-                }
-            };
+            let pkce_mode = map_pkce_method(provider.pkce_method);
 
             repo.upstream_oauth_provider()
                 .upsert(
@@ -239,12 +217,14 @@ pub async fn config_sync(
                         issuer: provider.issuer,
                         human_name: provider.human_name,
                         brand_name: provider.brand_name,
-                        scope: provider.scope.parse()?,
-                        token_endpoint_auth_method: provider.token_endpoint_auth_method.into(),
+                        scope: Some(provider.scope.parse()?),
+                        token_endpoint_auth_method: Some(
+                            provider.token_endpoint_auth_method.into(),
+                        ),
                         token_endpoint_signing_alg: provider
                             .token_endpoint_auth_signing_alg
                             .clone(),
-                        client_id: provider.client_id,
+                        client_id: Some(provider.client_id),
                         encrypted_client_secret,
                         claims_imports: map_claims_imports(&provider.claims_imports),
                         token_endpoint_override: provider.token_endpoint,
@@ -256,6 +236,62 @@ pub async fn config_sync(
                             .additional_authorization_parameters
                             .into_iter()
                             .collect(),
+                        saml: None,
+                    },
+                )
+                .await?;
+        }
+
+        for provider in upstream_saml2_config.providers {
+            if !provider.enabled {
+                continue;
+            }
+
+            let _span = info_span!("provider", %provider.id).entered();
+            if existing_enabled_ids.contains(&provider.id) {
+                info!("Updating SAML 2.0 provider");
+            } else if existing_disabled.contains_key(&provider.id) {
+                info!("Enabling and updating SAML 2.0 provider");
+            } else {
+                info!("Adding SAML 2.0 provider");
+            }
+
+            if dry_run {
+                continue;
+            }
+
+            let sso_binding = match provider.sso_binding {
+                UpstreamSaml2SsoBinding::Redirect => UpstreamOAuthProviderSamlBinding::Redirect,
+                UpstreamSaml2SsoBinding::Post => UpstreamOAuthProviderSamlBinding::Post,
+            };
+
+            repo.upstream_oauth_provider()
+                .upsert(
+                    clock,
+                    provider.id,
+                    UpstreamOAuthProviderParams {
+                        issuer: provider.idp_entity_id,
+                        human_name: provider.human_name,
+                        brand_name: provider.brand_name,
+                        scope: None,
+                        token_endpoint_auth_method: None,
+                        token_endpoint_signing_alg: None,
+                        client_id: None,
+                        encrypted_client_secret: None,
+                        claims_imports: map_claims_imports(&provider.claims_imports),
+                        token_endpoint_override: None,
+                        authorization_endpoint_override: None,
+                        jwks_uri_override: None,
+                        discovery_mode: UpstreamOAuthProviderDiscoveryMode::Disabled,
+                        pkce_mode: UpstreamOAuthProviderPkceMode::Disabled,
+                        additional_authorization_parameters: Vec::new(),
+                        saml: Some(UpstreamOAuthProviderSamlSettings {
+                            sp_entity_id: provider.sp_entity_id,
+                            idp_sso_url: provider.idp_sso_url,
+                            idp_certificates: provider.idp_certificates,
+                            sso_binding,
+                            name_id_format: provider.name_id_format,
+                        }),
                     },
                 )
                 .await?;
@@ -321,6 +357,16 @@ pub async fn config_sync(
                     jwks.cloned(),
                     jwks_uri.cloned(),
                     client.redirect_uris,
+                    client.post_logout_redirect_uris,
+                    client.backchannel_logout_uri,
+                    client.backchannel_logout_session_required,
+                    client.require_pushed_authorization_requests,
+                    client.request_object_signing_alg,
+                    client.require_signed_request_object,
+                    client.id_token_encrypted_response_alg,
+                    client.id_token_encrypted_response_enc,
+                    client.userinfo_encrypted_response_alg,
+                    client.userinfo_encrypted_response_enc,
                 )
                 .await?;
         }
@@ -9,20 +9,24 @@ use std::time::Duration;
 use anyhow::Context;
 use mas_config::{
     AccountConfig, BrandingConfig, CaptchaConfig, DatabaseConfig, EmailConfig, EmailSmtpMode,
-    EmailTransportKind, ExperimentalConfig, MatrixConfig, PasswordsConfig, PolicyConfig,
-    TemplatesConfig,
+    EmailTransportKind, ExperimentalConfig, MaintenanceConfig, MatrixConfig, PasswordsConfig,
+    PolicyConfig, SessionExpirationConfig, SessionsConfig, TemplatesConfig, UpstreamOAuth2Config,
+    WebhookEvent, WebhooksConfig,
 };
-use mas_data_model::SiteConfig;
+use mas_data_model::{SiteConfig, WebhookEventKind};
 use mas_email::{MailTransport, Mailer};
 use mas_handlers::{passwords::PasswordManager, ActivityTracker};
 use mas_policy::PolicyFactory;
 use mas_router::UrlBuilder;
+use mas_tasks::{MaintenanceSettings, SessionExpiration, SessionExpirationSettings, WebhookTarget};
 use mas_templates::{SiteConfigExt, TemplateLoadingError, Templates};
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     ConnectOptions, PgConnection, PgPool,
 };
-use tracing::{error, info, log::LevelFilter};
+use tracing::{error, info, log::LevelFilter, warn};
+
+use crate::server::TlsCertificateReloader;
 
 pub async fn password_manager_from_config(
     config: &PasswordsConfig,
@@ -104,6 +108,9 @@ pub async fn policy_factory_from_config(
         register: config.register_entrypoint.clone(),
         client_registration: config.client_registration_entrypoint.clone(),
         authorization_grant: config.authorization_grant_entrypoint.clone(),
+        authorization_request: config.authorization_request_entrypoint.clone(),
+        token_exchange: config.token_exchange_entrypoint.clone(),
+        jwt_bearer: config.jwt_bearer_entrypoint.clone(),
         email: config.email_entrypoint.clone(),
     };
 
@@ -140,6 +147,84 @@ pub fn captcha_config_from_config(
     }))
 }
 
+pub fn webhook_targets_from_config(config: &WebhooksConfig) -> Vec<WebhookTarget> {
+    config
+        .targets
+        .iter()
+        .map(|target| WebhookTarget {
+            name: target.name.clone(),
+            url: target.url.clone(),
+            secret: target.secret.clone(),
+            events: target
+                .events
+                .iter()
+                .map(|event| match event {
+                    WebhookEvent::UserRegistered => WebhookEventKind::UserRegistered,
+                    WebhookEvent::UserEmailVerified => WebhookEventKind::UserEmailVerified,
+                    WebhookEvent::UserLocked => WebhookEventKind::UserLocked,
+                    WebhookEvent::UserUnlocked => WebhookEventKind::UserUnlocked,
+                    WebhookEvent::UserDeactivated => WebhookEventKind::UserDeactivated,
+                    WebhookEvent::UserReactivated => WebhookEventKind::UserReactivated,
+                    WebhookEvent::UpstreamOAuthLinkCreated => {
+                        WebhookEventKind::UpstreamOAuthLinkCreated
+                    }
+                })
+                .collect(),
+            max_attempts: target.max_attempts,
+        })
+        .collect()
+}
+
+pub fn maintenance_settings_from_config(config: &MaintenanceConfig) -> MaintenanceSettings {
+    MaintenanceSettings {
+        schedule: config.schedule.clone(),
+        batch_size: config.batch_size,
+        refresh_token_retention: config.refresh_token_retention,
+        session_retention: config.session_retention,
+        authorization_grant_retention: config.authorization_grant_retention,
+        device_code_grant_retention: config.device_code_grant_retention,
+        recovery_session_retention: config.recovery_session_retention,
+        upstream_oauth_session_retention: config.upstream_oauth_session_retention,
+    }
+}
+
+fn session_expiration_from_config(config: &SessionExpirationConfig) -> SessionExpiration {
+    SessionExpiration {
+        idle_timeout: config.idle_timeout,
+        max_lifetime: config.max_lifetime,
+    }
+}
+
+pub fn session_expiration_settings_from_config(
+    config: &SessionsConfig,
+) -> SessionExpirationSettings {
+    let oauth2 = SessionExpiration {
+        idle_timeout: config.oauth2.idle_timeout,
+        max_lifetime: config.oauth2.max_lifetime,
+    };
+
+    // Client-specific settings fall back to the OAuth 2.0 ones
+    let oauth2_clients = config
+        .oauth2
+        .clients
+        .iter()
+        .map(|client| {
+            let expiration = SessionExpiration {
+                idle_timeout: client.idle_timeout.or(oauth2.idle_timeout),
+                max_lifetime: client.max_lifetime.or(oauth2.max_lifetime),
+            };
+            (client.client_id, expiration)
+        })
+        .collect();
+
+    SessionExpirationSettings {
+        oauth2,
+        oauth2_clients,
+        compat: session_expiration_from_config(&config.compat),
+        browser: session_expiration_from_config(&config.browser),
+    }
+}
+
 pub fn site_config_from_config(
     branding_config: &BrandingConfig,
     matrix_config: &MatrixConfig,
@@ -147,6 +232,7 @@ pub fn site_config_from_config(
     password_config: &PasswordsConfig,
     account_config: &AccountConfig,
     captcha_config: &CaptchaConfig,
+    upstream_oauth2_config: &UpstreamOAuth2Config,
 ) -> Result<SiteConfig, anyhow::Error> {
     let captcha = captcha_config_from_config(captcha_config)?;
     Ok(SiteConfig {
@@ -167,6 +253,12 @@ pub fn site_config_from_config(
             && account_config.password_recovery_enabled,
         captcha,
         minimum_password_complexity: password_config.minimum_complexity(),
+        upstream_oauth2_admin_api_allowed: upstream_oauth2_config.allow_admin_api,
+        upstream_oauth2_config_provider_ids: upstream_oauth2_config
+            .providers
+            .iter()
+            .map(|provider| provider.id)
+            .collect(),
     })
 }
 
@@ -303,10 +395,11 @@ pub async fn database_connection_from_config(
         .context("could not connect to the database")
 }
 
-/// Reload templates on SIGHUP
+/// Reload templates and listener TLS certificates on SIGHUP
 pub fn register_sighup(
     templates: &Templates,
     activity_tracker: &ActivityTracker,
+    tls_certificates: Vec<TlsCertificateReloader>,
 ) -> anyhow::Result<()> {
     #[cfg(unix)]
     {
@@ -321,12 +414,23 @@ pub fn register_sighup(
                     break;
                 };
 
-                info!("SIGHUP received, reloading templates & flushing activity tracker");
+                info!("SIGHUP received, reloading templates & TLS certificates, and flushing activity tracker");
 
                 activity_tracker.flush().await;
                 templates.clone().reload().await.unwrap_or_else(|err| {
                     error!(?err, "Error while reloading templates");
                 });
+
+                // Established connections keep using the previous certificate,
+                // only new handshakes use the reloaded one
+                for tls_certificate in &tls_certificates {
+                    tls_certificate.reload().unwrap_or_else(|err| {
+                        warn!(
+                            ?err,
+                            "Error while reloading TLS certificate, keeping the current one"
+                        );
+                    });
+                }
             }
         });
     }
@@ -7,8 +7,14 @@
 use std::ops::Deref;
 
 use figment::Figment;
-use mas_iana::oauth::OAuthClientAuthenticationMethod;
-use mas_jose::jwk::PublicJsonWebKeySet;
+use mas_iana::{
+    jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg},
+    oauth::OAuthClientAuthenticationMethod,
+};
+use mas_jose::{
+    jwa::{SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS, SUPPORTED_ENCRYPTION_ALGORITHMS},
+    jwk::PublicJsonWebKeySet,
+};
 use schemars::JsonSchema;
 use serde::{de::Error, Deserialize, Serialize};
 use ulid::Ulid;
@@ -16,6 +22,15 @@ use url::Url;
 
 use super::ConfigurationSection;
 
+const fn default_false() -> bool {
+    false
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_default_false(value: &bool) -> bool {
+    *value == default_false()
+}
+
 #[derive(JsonSchema, Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum JwksOrJwksUri {
@@ -97,10 +112,74 @@ pub struct ClientConfig {
     /// List of allowed redirect URIs
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub redirect_uris: Vec<Url>,
+
+    /// List of allowed post-logout redirect URIs, used by the OpenID Connect
+    /// RP-Initiated Logout endpoint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_logout_redirect_uris: Vec<Url>,
+
+    /// URL called to notify the client when a user session ends, using the
+    /// OpenID Connect Back-Channel Logout protocol
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backchannel_logout_uri: Option<Url>,
+
+    /// Whether the client requires the `sid` claim in the logout tokens sent
+    /// to the `backchannel_logout_uri`. Defaults to `false`.
+    #[serde(default = "default_false", skip_serializing_if = "is_default_false")]
+    pub backchannel_logout_session_required: bool,
+
+    /// Whether the client must use the Pushed Authorization Request endpoint
+    /// to start authorization requests. Defaults to `false`.
+    #[serde(default = "default_false", skip_serializing_if = "is_default_false")]
+    pub require_pushed_authorization_requests: bool,
+
+    /// The JWS algorithm the client must use to sign request objects sent to
+    /// the authorization endpoint. If not set, any supported algorithm is
+    /// accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_object_signing_alg: Option<JsonWebSignatureAlg>,
+
+    /// Whether the client must send its authorization requests as signed
+    /// request objects, using the `request` parameter. Request objects are
+    /// verified using the `jwks`/`jwks_uri`, or the `client_secret` for
+    /// HMAC-based algorithms. Defaults to `false`.
+    #[serde(default = "default_false", skip_serializing_if = "is_default_false")]
+    pub require_signed_request_object: bool,
+
+    /// The JWE algorithm used to encrypt the ID tokens sent to this client,
+    /// using one of the keys from `jwks`/`jwks_uri`. If not set, ID tokens
+    /// are only signed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+
+    /// The JWE content encryption algorithm used to encrypt the ID tokens
+    /// sent to this client. Defaults to `A128CBC-HS256` when
+    /// `id_token_encrypted_response_alg` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+
+    /// The JWE algorithm used to encrypt the userinfo responses sent to this
+    /// client, using one of the keys from `jwks`/`jwks_uri`. If not set,
+    /// userinfo responses are not encrypted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+
+    /// The JWE content encryption algorithm used to encrypt the userinfo
+    /// responses sent to this client. Defaults to `A128CBC-HS256` when
+    /// `userinfo_encrypted_response_alg` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
 }
 
 impl ClientConfig {
     fn validate(&self) -> Result<(), figment::error::Error> {
+        self.validate_encrypted_responses()?;
+
+        // Clients getting encrypted responses need a JWKS, even if they don't use it
+        // for authentication
+        let encrypts_responses = self.id_token_encrypted_response_alg.is_some()
+            || self.userinfo_encrypted_response_alg.is_some();
+
         let auth_method = self.client_auth_method;
         match self.client_auth_method {
             ClientAuthMethodConfig::PrivateKeyJwt => {
@@ -135,14 +214,14 @@ impl ClientConfig {
                     return Err(error.with_path("client_auth_method"));
                 }
 
-                if self.jwks.is_some() {
+                if self.jwks.is_some() && !encrypts_responses {
                     let error = figment::error::Error::custom(format!(
                         "jwks is not allowed with {auth_method}"
                     ));
                     return Err(error.with_path("jwks"));
                 }
 
-                if self.jwks_uri.is_some() {
+                if self.jwks_uri.is_some() && !encrypts_responses {
                     let error = figment::error::Error::custom(format!(
                         "jwks_uri is not allowed with {auth_method}"
                     ));
@@ -151,6 +230,13 @@ impl ClientConfig {
             }
 
             ClientAuthMethodConfig::None => {
+                if self.require_signed_request_object {
+                    let error = figment::error::Error::custom(
+                        "require_signed_request_object is not allowed with none authentication method",
+                    );
+                    return Err(error.with_path("require_signed_request_object"));
+                }
+
                 if self.client_secret.is_some() {
                     let error = figment::error::Error::custom(
                         "client_secret is not allowed with none authentication method",
@@ -158,14 +244,14 @@ impl ClientConfig {
                     return Err(error.with_path("client_secret"));
                 }
 
-                if self.jwks.is_some() {
+                if self.jwks.is_some() && !encrypts_responses {
                     let error = figment::error::Error::custom(
                         "jwks is not allowed with none authentication method",
                     );
                     return Err(error);
                 }
 
-                if self.jwks_uri.is_some() {
+                if self.jwks_uri.is_some() && !encrypts_responses {
                     let error = figment::error::Error::custom(
                         "jwks_uri is not allowed with none authentication method",
                     );
@@ -177,6 +263,61 @@ impl ClientConfig {
         Ok(())
     }
 
+    fn validate_encrypted_responses(&self) -> Result<(), figment::error::Error> {
+        for (field, alg, enc_field, enc) in [
+            (
+                "id_token_encrypted_response_alg",
+                &self.id_token_encrypted_response_alg,
+                "id_token_encrypted_response_enc",
+                &self.id_token_encrypted_response_enc,
+            ),
+            (
+                "userinfo_encrypted_response_alg",
+                &self.userinfo_encrypted_response_alg,
+                "userinfo_encrypted_response_enc",
+                &self.userinfo_encrypted_response_enc,
+            ),
+        ] {
+            let Some(alg) = alg else {
+                if enc.is_some() {
+                    let error = figment::error::Error::custom(format!(
+                        "{enc_field} requires {field} to be set"
+                    ));
+                    return Err(error.with_path(enc_field));
+                }
+
+                continue;
+            };
+
+            if !SUPPORTED_ENCRYPTION_ALGORITHMS.contains(alg) {
+                let error = figment::error::Error::custom(format!("{alg} is not supported"));
+                return Err(error.with_path(field));
+            }
+
+            if let Some(enc) = enc {
+                if !SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS.contains(enc) {
+                    let error = figment::error::Error::custom(format!("{enc} is not supported"));
+                    return Err(error.with_path(enc_field));
+                }
+            }
+
+            if self.jwks.is_none() && self.jwks_uri.is_none() {
+                let error = figment::error::Error::custom(format!(
+                    "jwks or jwks_uri is required for {field}"
+                ));
+                return Err(error.with_path(field));
+            }
+
+            if self.jwks.is_some() && self.jwks_uri.is_some() {
+                let error =
+                    figment::error::Error::custom("jwks and jwks_uri are mutually exclusive");
+                return Err(error.with_path("jwks"));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Authentication method used for this client
     #[must_use]
     pub fn client_auth_method(&self) -> OAuthClientAuthenticationMethod {
@@ -266,6 +407,11 @@ mod tests {
                       client_auth_method: none
                       redirect_uris:
                         - https://exemple.fr/callback
+                      post_logout_redirect_uris:
+                        - https://exemple.fr/logged-out
+                      backchannel_logout_uri: https://exemple.fr/backchannel-logout
+                      backchannel_logout_session_required: true
+                      require_pushed_authorization_requests: true
 
                     - client_id: 01GFWR32NCQ12B8Z0J8CPXRRB6
                       client_auth_method: client_secret_basic
@@ -281,6 +427,11 @@ mod tests {
 
                     - client_id: 01GFWR4BNFDCC4QDG6AMSP1VRR
                       client_auth_method: private_key_jwt
+                      request_object_signing_alg: RS256
+                      require_signed_request_object: true
+                      id_token_encrypted_response_alg: RSA-OAEP
+                      userinfo_encrypted_response_alg: RSA-OAEP-256
+                      userinfo_encrypted_response_enc: A256GCM
                       jwks:
                         keys:
                         - kid: "03e84aed4ef4431014e8617567864c4efaaaede9"
@@ -313,12 +464,47 @@ mod tests {
                 config.0[0].redirect_uris,
                 vec!["https://exemple.fr/callback".parse().unwrap()]
             );
+            assert_eq!(
+                config.0[0].post_logout_redirect_uris,
+                vec!["https://exemple.fr/logged-out".parse().unwrap()]
+            );
+            assert_eq!(
+                config.0[0].backchannel_logout_uri,
+                Some("https://exemple.fr/backchannel-logout".parse().unwrap())
+            );
+            assert!(config.0[0].backchannel_logout_session_required);
+            assert!(config.0[0].require_pushed_authorization_requests);
 
             assert_eq!(
                 config.0[1].client_id,
                 Ulid::from_str("01GFWR32NCQ12B8Z0J8CPXRRB6").unwrap()
             );
             assert_eq!(config.0[1].redirect_uris, Vec::new());
+            assert_eq!(config.0[1].post_logout_redirect_uris, Vec::new());
+            assert_eq!(config.0[1].backchannel_logout_uri, None);
+            assert!(!config.0[1].backchannel_logout_session_required);
+            assert!(!config.0[1].require_pushed_authorization_requests);
+            assert_eq!(config.0[1].request_object_signing_alg, None);
+            assert!(!config.0[1].require_signed_request_object);
+
+            assert_eq!(
+                config.0[4].request_object_signing_alg,
+                Some(JsonWebSignatureAlg::Rs256)
+            );
+            assert!(config.0[4].require_signed_request_object);
+            assert_eq!(
+                config.0[4].id_token_encrypted_response_alg,
+                Some(JsonWebEncryptionAlg::RsaOaep)
+            );
+            assert_eq!(config.0[4].id_token_encrypted_response_enc, None);
+            assert_eq!(
+                config.0[4].userinfo_encrypted_response_alg,
+                Some(JsonWebEncryptionAlg::RsaOaep256)
+            );
+            assert_eq!(
+                config.0[4].userinfo_encrypted_response_enc,
+                Some(JsonWebEncryptionEnc::A256Gcm)
+            );
 
             Ok(())
         });
@@ -311,6 +311,9 @@ pub enum Resource {
     /// Admin API, served at `/api/admin/v1`
     AdminApi,
 
+    /// SCIM 2.0 provisioning API, served at `/scim/v2`
+    Scim,
+
     /// Mount a "/connection-info" handler which helps debugging informations on
     /// the upstream connection
     #[serde(rename = "connection-info")]
@@ -335,7 +338,8 @@ pub struct ListenerConfig {
     /// List of sockets to bind
     pub binds: Vec<BindConfig>,
 
-    /// Accept `HAProxy`'s Proxy Protocol V1
+    /// Accept `HAProxy`'s Proxy Protocol. Both the text (v1) and binary (v2)
+    /// versions are supported, and detected automatically on each connection.
     #[serde(default)]
     pub proxy_protocol: bool,
 
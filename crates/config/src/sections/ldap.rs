@@ -0,0 +1,297 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use schemars::JsonSchema;
+use serde::{de::Error, Deserialize, Serialize};
+use url::Url;
+
+use crate::ConfigurationSection;
+
+const fn default_connection_timeout() -> u64 {
+    5
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_default_connection_timeout(value: &u64) -> bool {
+    *value == default_connection_timeout()
+}
+
+fn default_user_filter() -> String {
+    "(uid={{ username }})".to_owned()
+}
+
+fn is_default_user_filter(value: &str) -> bool {
+    value == default_user_filter()
+}
+
+/// How the LDAP directory is used alongside the passwords stored in the
+/// database
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LdapMode {
+    /// Passwords stored in the database are checked first, and the directory
+    /// is only used for users which don't have one
+    #[default]
+    Fallback,
+
+    /// The directory is checked first. Passwords stored in the database are
+    /// only used for users which are not found in the directory
+    Primary,
+}
+
+impl LdapMode {
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How attributes of the directory entry are mapped to the user
+///
+/// Each value is a Jinja2 template, rendered with the attributes of the entry
+/// available in the `user` variable, and its distinguished name in
+/// `user.dn`. Only the first value of multi-valued attributes is available.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LdapAttributeMapping {
+    /// The template used to compute a stable identifier for the entry, used
+    /// to link it to a user.
+    ///
+    /// If not provided, the default template is `{{ user.entryUUID }}`. It
+    /// should render an immutable attribute, like `entryUUID` or
+    /// `objectGUID`, so that renaming or moving the entry doesn't create a new
+    /// user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+
+    /// The template used for the localpart of new users.
+    ///
+    /// If not provided, the default template is `{{ user.uid }}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub localpart: Option<String>,
+
+    /// The template used for the display name of new users.
+    ///
+    /// If not provided, the default template is `{{ user.cn }}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub displayname: Option<String>,
+
+    /// The template used for the email address of new users.
+    ///
+    /// If not provided, the default template is `{{ user.mail }}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+impl LdapAttributeMapping {
+    fn is_default(&self) -> bool {
+        self.subject.is_none()
+            && self.localpart.is_none()
+            && self.displayname.is_none()
+            && self.email.is_none()
+    }
+}
+
+/// Configuration section to check passwords against an LDAP directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct LdapConfig {
+    /// The URL of the directory server, like `ldaps://ldap.example.com`. If
+    /// not set, LDAP authentication is disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<Url>,
+
+    /// How the directory is used alongside the passwords stored in the
+    /// database
+    #[serde(default, skip_serializing_if = "LdapMode::is_default")]
+    pub mode: LdapMode,
+
+    /// Whether to upgrade `ldap://` connections with `StartTLS`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub starttls: bool,
+
+    /// How long to wait for the connection to the server to be established,
+    /// in seconds. Defaults to 5 seconds.
+    #[serde(
+        default = "default_connection_timeout",
+        skip_serializing_if = "is_default_connection_timeout"
+    )]
+    #[schemars(range(min = 1))]
+    pub connection_timeout: u64,
+
+    /// The DN used to search for users. If not set, the search is done with
+    /// an anonymous bind.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_dn: Option<String>,
+
+    /// The password used along with `bind_dn`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_password: Option<String>,
+
+    /// The DN under which users are searched
+    #[serde(default)]
+    pub base_dn: String,
+
+    /// The filter used to find the entry of a user, as a Jinja2 template.
+    ///
+    /// The username entered on the login form is available in the `username`
+    /// variable, already escaped. Defaults to `(uid={{ username }})`
+    #[serde(
+        default = "default_user_filter",
+        skip_serializing_if = "is_default_user_filter"
+    )]
+    pub user_filter: String,
+
+    /// How attributes of the directory entry are mapped to the user
+    #[serde(default, skip_serializing_if = "LdapAttributeMapping::is_default")]
+    pub attributes: LdapAttributeMapping,
+}
+
+impl LdapConfig {
+    /// Returns true if LDAP authentication is enabled
+    #[must_use]
+    pub fn enabled(&self) -> bool {
+        self.url.is_some()
+    }
+
+    /// Returns true if the configuration is the default one
+    pub(crate) fn is_default(&self) -> bool {
+        !self.enabled()
+    }
+}
+
+impl ConfigurationSection for LdapConfig {
+    const PATH: Option<&'static str> = Some("ldap");
+
+    fn validate(&self, figment: &figment::Figment) -> Result<(), figment::Error> {
+        let metadata = figment.find_metadata(Self::PATH.unwrap());
+
+        let error_on_field = |mut error: figment::error::Error, field: &'static str| {
+            error.metadata = metadata.cloned();
+            error.profile = Some(figment::Profile::Default);
+            error.path = vec![Self::PATH.unwrap().to_owned(), field.to_owned()];
+            error
+        };
+
+        let Some(url) = &self.url else {
+            return Ok(());
+        };
+
+        if !matches!(url.scheme(), "ldap" | "ldaps") {
+            return Err(error_on_field(
+                figment::error::Error::custom("the URL scheme must be ldap or ldaps"),
+                "url",
+            ));
+        }
+
+        if self.starttls && url.scheme() == "ldaps" {
+            return Err(error_on_field(
+                figment::error::Error::custom("starttls can't be used with an ldaps URL"),
+                "starttls",
+            ));
+        }
+
+        if self.bind_dn.is_some() != self.bind_password.is_some() {
+            return Err(error_on_field(
+                figment::error::Error::custom("bind_dn and bind_password must be set together"),
+                "bind_password",
+            ));
+        }
+
+        if self.base_dn.is_empty() {
+            return Err(error_on_field(
+                figment::error::Error::custom("base_dn is required to enable LDAP"),
+                "base_dn",
+            ));
+        }
+
+        if self.connection_timeout == 0 {
+            return Err(error_on_field(
+                figment::error::Error::custom("connection_timeout must be at least 1"),
+                "connection_timeout",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::{
+        providers::{Format, Yaml},
+        Figment, Jail,
+    };
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                  ldap:
+                    url: ldaps://ldap.example.com
+                    mode: primary
+                    bind_dn: cn=mas,ou=services,dc=example,dc=com
+                    bind_password: hunter2
+                    base_dn: ou=people,dc=example,dc=com
+                    attributes:
+                      subject: "{{ user.entryUUID }}"
+                "#,
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<LdapConfig>("ldap")?;
+            config.validate(&figment)?;
+
+            assert!(config.enabled());
+            assert_eq!(config.mode, LdapMode::Primary);
+            assert!(!config.starttls);
+            assert_eq!(config.connection_timeout, 5);
+            assert_eq!(config.user_filter, "(uid={{ username }})");
+            assert_eq!(
+                config.attributes.subject.as_deref(),
+                Some("{{ user.entryUUID }}")
+            );
+            assert!(config.attributes.localpart.is_none());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn invalid_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  ldap:
+                    url: ldaps://ldap.example.com
+                    bind_dn: cn=mas,ou=services,dc=example,dc=com
+                    base_dn: ou=people,dc=example,dc=com
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<LdapConfig>("ldap")?;
+            assert!(config.validate(&figment).is_err());
+
+            jail.create_file(
+                "config.yaml",
+                r"
+                  ldap:
+                    url: https://ldap.example.com
+                    base_dn: ou=people,dc=example,dc=com
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<LdapConfig>("ldap")?;
+            assert!(config.validate(&figment).is_err());
+
+            Ok(())
+        });
+    }
+}
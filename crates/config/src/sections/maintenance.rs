@@ -0,0 +1,307 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use chrono::Duration;
+use schemars::JsonSchema;
+use serde::{de::Error, Deserialize, Serialize};
+use serde_with::serde_as;
+
+use crate::ConfigurationSection;
+
+fn default_schedule() -> String {
+    "*/15 * * * * *".to_owned()
+}
+
+fn is_default_schedule(value: &str) -> bool {
+    value == default_schedule()
+}
+
+const fn default_batch_size() -> usize {
+    1000
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_default_batch_size(value: &usize) -> bool {
+    *value == default_batch_size()
+}
+
+fn default_refresh_token_retention() -> Duration {
+    Duration::microseconds(7 * 24 * 60 * 60 * 1000 * 1000)
+}
+
+fn is_default_refresh_token_retention(value: &Duration) -> bool {
+    *value == default_refresh_token_retention()
+}
+
+fn default_session_retention() -> Duration {
+    Duration::microseconds(30 * 24 * 60 * 60 * 1000 * 1000)
+}
+
+fn is_default_session_retention(value: &Duration) -> bool {
+    *value == default_session_retention()
+}
+
+fn default_grant_retention() -> Duration {
+    Duration::microseconds(24 * 60 * 60 * 1000 * 1000)
+}
+
+fn is_default_grant_retention(value: &Duration) -> bool {
+    *value == default_grant_retention()
+}
+
+fn default_recovery_session_retention() -> Duration {
+    Duration::microseconds(7 * 24 * 60 * 60 * 1000 * 1000)
+}
+
+fn is_default_recovery_session_retention(value: &Duration) -> bool {
+    *value == default_recovery_session_retention()
+}
+
+fn default_upstream_oauth_session_retention() -> Duration {
+    Duration::microseconds(7 * 24 * 60 * 60 * 1000 * 1000)
+}
+
+fn is_default_upstream_oauth_session_retention(value: &Duration) -> bool {
+    *value == default_upstream_oauth_session_retention()
+}
+
+/// Configuration of the database maintenance jobs
+///
+/// Those jobs periodically remove data which is not useful anymore from the
+/// database. Rows are removed in batches, each in its own transaction, to
+/// avoid holding locks for too long.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+pub struct MaintenanceConfig {
+    /// When to run the maintenance jobs, as a cron expression with seconds.
+    /// Defaults to every 15 seconds (`*/15 * * * * *`).
+    #[serde(
+        default = "default_schedule",
+        skip_serializing_if = "is_default_schedule"
+    )]
+    pub schedule: String,
+
+    /// How many rows should be removed at most in a single transaction.
+    /// Defaults to 1000.
+    #[schemars(range(min = 1))]
+    #[serde(
+        default = "default_batch_size",
+        skip_serializing_if = "is_default_batch_size"
+    )]
+    pub batch_size: usize,
+
+    /// How long consumed refresh tokens are kept, in seconds. Defaults to 7
+    /// days.
+    #[schemars(with = "u64")]
+    #[serde(
+        default = "default_refresh_token_retention",
+        skip_serializing_if = "is_default_refresh_token_retention"
+    )]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub refresh_token_retention: Duration,
+
+    /// How long finished OAuth 2.0, compatibility and browser sessions are
+    /// kept, in seconds. Defaults to 30 days.
+    #[schemars(with = "u64")]
+    #[serde(
+        default = "default_session_retention",
+        skip_serializing_if = "is_default_session_retention"
+    )]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub session_retention: Duration,
+
+    /// How long authorization grants are kept after their creation, in
+    /// seconds. Defaults to 1 day.
+    #[schemars(with = "u64")]
+    #[serde(
+        default = "default_grant_retention",
+        skip_serializing_if = "is_default_grant_retention"
+    )]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub authorization_grant_retention: Duration,
+
+    /// How long device code grants are kept after they expired, in seconds.
+    /// Defaults to 1 day.
+    #[schemars(with = "u64")]
+    #[serde(
+        default = "default_grant_retention",
+        skip_serializing_if = "is_default_grant_retention"
+    )]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub device_code_grant_retention: Duration,
+
+    /// How long account recovery sessions are kept after their creation, in
+    /// seconds. Defaults to 7 days.
+    #[schemars(with = "u64")]
+    #[serde(
+        default = "default_recovery_session_retention",
+        skip_serializing_if = "is_default_recovery_session_retention"
+    )]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub recovery_session_retention: Duration,
+
+    /// How long upstream OAuth 2.0 authorization sessions are kept after their
+    /// creation, in seconds. Defaults to 7 days.
+    #[schemars(with = "u64")]
+    #[serde(
+        default = "default_upstream_oauth_session_retention",
+        skip_serializing_if = "is_default_upstream_oauth_session_retention"
+    )]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub upstream_oauth_session_retention: Duration,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            schedule: default_schedule(),
+            batch_size: default_batch_size(),
+            refresh_token_retention: default_refresh_token_retention(),
+            session_retention: default_session_retention(),
+            authorization_grant_retention: default_grant_retention(),
+            device_code_grant_retention: default_grant_retention(),
+            recovery_session_retention: default_recovery_session_retention(),
+            upstream_oauth_session_retention: default_upstream_oauth_session_retention(),
+        }
+    }
+}
+
+impl MaintenanceConfig {
+    pub(crate) fn is_default(&self) -> bool {
+        is_default_schedule(&self.schedule)
+            && is_default_batch_size(&self.batch_size)
+            && is_default_refresh_token_retention(&self.refresh_token_retention)
+            && is_default_session_retention(&self.session_retention)
+            && is_default_grant_retention(&self.authorization_grant_retention)
+            && is_default_grant_retention(&self.device_code_grant_retention)
+            && is_default_recovery_session_retention(&self.recovery_session_retention)
+            && is_default_upstream_oauth_session_retention(&self.upstream_oauth_session_retention)
+    }
+}
+
+impl ConfigurationSection for MaintenanceConfig {
+    const PATH: Option<&'static str> = Some("maintenance");
+
+    fn validate(&self, figment: &figment::Figment) -> Result<(), figment::Error> {
+        let metadata = figment.find_metadata(Self::PATH.unwrap());
+
+        let error_on_field = |mut error: figment::error::Error, field: &'static str| {
+            error.metadata = metadata.cloned();
+            error.profile = Some(figment::Profile::Default);
+            error.path = vec![Self::PATH.unwrap().to_owned(), field.to_owned()];
+            error
+        };
+
+        // The expression itself is parsed when the worker starts, but catch
+        // the common mistake of using a 5-field expression without seconds
+        let fields = self.schedule.split_whitespace().count();
+        if !(6..=7).contains(&fields) {
+            return Err(error_on_field(
+                figment::error::Error::custom(format!(
+                    "expected a cron expression with 6 or 7 fields (including seconds), got {fields}"
+                )),
+                "schedule",
+            ));
+        }
+
+        if self.batch_size == 0 {
+            return Err(error_on_field(
+                figment::error::Error::custom("batch size must be at least 1"),
+                "batch_size",
+            ));
+        }
+
+        let retentions = [
+            ("refresh_token_retention", self.refresh_token_retention),
+            ("session_retention", self.session_retention),
+            (
+                "authorization_grant_retention",
+                self.authorization_grant_retention,
+            ),
+            (
+                "device_code_grant_retention",
+                self.device_code_grant_retention,
+            ),
+            (
+                "recovery_session_retention",
+                self.recovery_session_retention,
+            ),
+            (
+                "upstream_oauth_session_retention",
+                self.upstream_oauth_session_retention,
+            ),
+        ];
+        for (field, retention) in retentions {
+            if retention < Duration::zero() {
+                return Err(error_on_field(
+                    figment::error::Error::custom("retention period must not be negative"),
+                    field,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::{
+        providers::{Format, Yaml},
+        Figment, Jail,
+    };
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  maintenance:
+                    schedule: '0 */5 * * * *'
+                    batch_size: 500
+                    session_retention: 86400
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<MaintenanceConfig>("maintenance")?;
+            config.validate(&figment)?;
+
+            assert_eq!(config.schedule, "0 */5 * * * *");
+            assert_eq!(config.batch_size, 500);
+            assert_eq!(config.session_retention, Duration::try_days(1).unwrap());
+            assert_eq!(
+                config.refresh_token_retention,
+                Duration::try_days(7).unwrap()
+            );
+            assert!(!config.is_default());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn schedule_without_seconds() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  maintenance:
+                    schedule: '*/5 * * * *'
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<MaintenanceConfig>("maintenance")?;
+            let error = config.validate(&figment).unwrap_err();
+            assert_eq!(error.path, vec!["maintenance", "schedule"]);
+
+            Ok(())
+        });
+    }
+}
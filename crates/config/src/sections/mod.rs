@@ -16,14 +16,19 @@ mod database;
 mod email;
 mod experimental;
 mod http;
+mod ldap;
+mod maintenance;
 mod matrix;
 mod passwords;
 mod policy;
 mod rate_limiting;
 mod secrets;
+mod sessions;
 mod telemetry;
 mod templates;
 mod upstream_oauth2;
+mod upstream_saml2;
+mod webhooks;
 
 pub use self::{
     account::AccountConfig,
@@ -37,11 +42,17 @@ pub use self::{
         BindConfig as HttpBindConfig, HttpConfig, ListenerConfig as HttpListenerConfig,
         Resource as HttpResource, TlsConfig as HttpTlsConfig, UnixOrTcp,
     },
+    ldap::{LdapAttributeMapping, LdapConfig, LdapMode},
+    maintenance::MaintenanceConfig,
     matrix::MatrixConfig,
     passwords::{Algorithm as PasswordAlgorithm, PasswordsConfig},
     policy::PolicyConfig,
     rate_limiting::RateLimitingConfig,
-    secrets::SecretsConfig,
+    secrets::{KeyConfig, KeyState, KeyType, SecretsConfig},
+    sessions::{
+        ClientSessionExpirationConfig, OAuth2SessionExpirationConfig, SessionExpirationConfig,
+        SessionsConfig,
+    },
     telemetry::{
         MetricsConfig, MetricsExporterKind, Propagator, TelemetryConfig, TracingConfig,
         TracingExporterKind,
@@ -51,8 +62,11 @@ pub use self::{
         ClaimsImports as UpstreamOAuth2ClaimsImports, DiscoveryMode as UpstreamOAuth2DiscoveryMode,
         EmailImportPreference as UpstreamOAuth2EmailImportPreference,
         ImportAction as UpstreamOAuth2ImportAction, PkceMethod as UpstreamOAuth2PkceMethod,
-        SetEmailVerification as UpstreamOAuth2SetEmailVerification, UpstreamOAuth2Config,
+        SetEmailVerification as UpstreamOAuth2SetEmailVerification,
+        TokenAuthMethod as UpstreamOAuth2TokenAuthMethod, UpstreamOAuth2Config,
     },
+    upstream_saml2::{SsoBinding as UpstreamSaml2SsoBinding, UpstreamSaml2Config},
+    webhooks::{WebhookEvent, WebhookTargetConfig, WebhooksConfig},
 };
 use crate::util::ConfigurationSection;
 
@@ -90,6 +104,10 @@ pub struct RootConfig {
     #[serde(default)]
     pub passwords: PasswordsConfig,
 
+    /// Configuration related to checking passwords against an LDAP directory
+    #[serde(default, skip_serializing_if = "LdapConfig::is_default")]
+    pub ldap: LdapConfig,
+
     /// Configuration related to the homeserver
     pub matrix: MatrixConfig,
 
@@ -106,6 +124,10 @@ pub struct RootConfig {
     #[serde(default, skip_serializing_if = "UpstreamOAuth2Config::is_default")]
     pub upstream_oauth2: UpstreamOAuth2Config,
 
+    /// Configuration related to upstream SAML 2.0 identity providers
+    #[serde(default, skip_serializing_if = "UpstreamSaml2Config::is_default")]
+    pub upstream_saml2: UpstreamSaml2Config,
+
     /// Configuration section for tweaking the branding of the service
     #[serde(default, skip_serializing_if = "BrandingConfig::is_default")]
     pub branding: BrandingConfig,
@@ -119,6 +141,18 @@ pub struct RootConfig {
     #[serde(default, skip_serializing_if = "AccountConfig::is_default")]
     pub account: AccountConfig,
 
+    /// Configuration section to send user lifecycle events to HTTP endpoints
+    #[serde(default, skip_serializing_if = "WebhooksConfig::is_default")]
+    pub webhooks: WebhooksConfig,
+
+    /// Configuration section for the database maintenance jobs
+    #[serde(default, skip_serializing_if = "MaintenanceConfig::is_default")]
+    pub maintenance: MaintenanceConfig,
+
+    /// Configuration section to automatically finish inactive or old sessions
+    #[serde(default, skip_serializing_if = "SessionsConfig::is_default")]
+    pub sessions: SessionsConfig,
+
     /// Experimental configuration options
     #[serde(default, skip_serializing_if = "ExperimentalConfig::is_default")]
     pub experimental: ExperimentalConfig,
@@ -133,14 +167,19 @@ impl ConfigurationSection for RootConfig {
         self.templates.validate(figment)?;
         self.email.validate(figment)?;
         self.passwords.validate(figment)?;
+        self.ldap.validate(figment)?;
         self.secrets.validate(figment)?;
         self.matrix.validate(figment)?;
         self.policy.validate(figment)?;
         self.rate_limiting.validate(figment)?;
         self.upstream_oauth2.validate(figment)?;
+        self.upstream_saml2.validate(figment)?;
         self.branding.validate(figment)?;
         self.captcha.validate(figment)?;
         self.account.validate(figment)?;
+        self.webhooks.validate(figment)?;
+        self.maintenance.validate(figment)?;
+        self.sessions.validate(figment)?;
         self.experimental.validate(figment)?;
 
         Ok(())
@@ -165,14 +204,19 @@ impl RootConfig {
             templates: TemplatesConfig::default(),
             email: EmailConfig::default(),
             passwords: PasswordsConfig::default(),
+            ldap: LdapConfig::default(),
             secrets: SecretsConfig::generate(&mut rng).await?,
             matrix: MatrixConfig::generate(&mut rng),
             policy: PolicyConfig::default(),
             rate_limiting: RateLimitingConfig::default(),
             upstream_oauth2: UpstreamOAuth2Config::default(),
+            upstream_saml2: UpstreamSaml2Config::default(),
             branding: BrandingConfig::default(),
             captcha: CaptchaConfig::default(),
             account: AccountConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            sessions: SessionsConfig::default(),
             experimental: ExperimentalConfig::default(),
         })
     }
@@ -187,15 +231,20 @@ impl RootConfig {
             telemetry: TelemetryConfig::default(),
             templates: TemplatesConfig::default(),
             passwords: PasswordsConfig::default(),
+            ldap: LdapConfig::default(),
             email: EmailConfig::default(),
             secrets: SecretsConfig::test(),
             matrix: MatrixConfig::test(),
             policy: PolicyConfig::default(),
             rate_limiting: RateLimitingConfig::default(),
             upstream_oauth2: UpstreamOAuth2Config::default(),
+            upstream_saml2: UpstreamSaml2Config::default(),
             branding: BrandingConfig::default(),
             captcha: CaptchaConfig::default(),
             account: AccountConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            sessions: SessionsConfig::default(),
             experimental: ExperimentalConfig::default(),
         }
     }
@@ -222,6 +271,9 @@ pub struct AppConfig {
     #[serde(default)]
     pub passwords: PasswordsConfig,
 
+    #[serde(default)]
+    pub ldap: LdapConfig,
+
     pub matrix: MatrixConfig,
 
     #[serde(default)]
@@ -230,6 +282,9 @@ pub struct AppConfig {
     #[serde(default)]
     pub rate_limiting: RateLimitingConfig,
 
+    #[serde(default)]
+    pub upstream_oauth2: UpstreamOAuth2Config,
+
     #[serde(default)]
     pub branding: BrandingConfig,
 
@@ -239,6 +294,15 @@ pub struct AppConfig {
     #[serde(default)]
     pub account: AccountConfig,
 
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+
+    #[serde(default)]
+    pub sessions: SessionsConfig,
+
     #[serde(default)]
     pub experimental: ExperimentalConfig,
 }
@@ -250,13 +314,18 @@ impl ConfigurationSection for AppConfig {
         self.templates.validate(figment)?;
         self.email.validate(figment)?;
         self.passwords.validate(figment)?;
+        self.ldap.validate(figment)?;
         self.secrets.validate(figment)?;
         self.matrix.validate(figment)?;
         self.policy.validate(figment)?;
         self.rate_limiting.validate(figment)?;
+        self.upstream_oauth2.validate(figment)?;
         self.branding.validate(figment)?;
         self.captcha.validate(figment)?;
         self.account.validate(figment)?;
+        self.webhooks.validate(figment)?;
+        self.maintenance.validate(figment)?;
+        self.sessions.validate(figment)?;
         self.experimental.validate(figment)?;
 
         Ok(())
@@ -277,6 +346,9 @@ pub struct SyncConfig {
 
     #[serde(default)]
     pub upstream_oauth2: UpstreamOAuth2Config,
+
+    #[serde(default)]
+    pub upstream_saml2: UpstreamSaml2Config,
 }
 
 impl ConfigurationSection for SyncConfig {
@@ -285,6 +357,7 @@ impl ConfigurationSection for SyncConfig {
         self.secrets.validate(figment)?;
         self.clients.validate(figment)?;
         self.upstream_oauth2.validate(figment)?;
+        self.upstream_saml2.validate(figment)?;
 
         Ok(())
     }
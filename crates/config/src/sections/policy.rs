@@ -54,6 +54,30 @@ fn is_default_authorization_grant_entrypoint(value: &String) -> bool {
     *value == default_authorization_grant_entrypoint()
 }
 
+fn default_authorization_request_entrypoint() -> String {
+    "authorization_request/violation".to_owned()
+}
+
+fn is_default_authorization_request_entrypoint(value: &String) -> bool {
+    *value == default_authorization_request_entrypoint()
+}
+
+fn default_token_exchange_entrypoint() -> String {
+    "token_exchange/violation".to_owned()
+}
+
+fn is_default_token_exchange_entrypoint(value: &String) -> bool {
+    *value == default_token_exchange_entrypoint()
+}
+
+fn default_jwt_bearer_entrypoint() -> String {
+    "jwt_bearer/violation".to_owned()
+}
+
+fn is_default_jwt_bearer_entrypoint(value: &String) -> bool {
+    *value == default_jwt_bearer_entrypoint()
+}
+
 fn default_password_entrypoint() -> String {
     "password/violation".to_owned()
 }
@@ -111,6 +135,27 @@ pub struct PolicyConfig {
     )]
     pub authorization_grant_entrypoint: String,
 
+    /// Entrypoint to use when evaluating authorization requests
+    #[serde(
+        default = "default_authorization_request_entrypoint",
+        skip_serializing_if = "is_default_authorization_request_entrypoint"
+    )]
+    pub authorization_request_entrypoint: String,
+
+    /// Entrypoint to use when evaluating token exchange requests
+    #[serde(
+        default = "default_token_exchange_entrypoint",
+        skip_serializing_if = "is_default_token_exchange_entrypoint"
+    )]
+    pub token_exchange_entrypoint: String,
+
+    /// Entrypoint to use when evaluating JWT bearer authorization grants
+    #[serde(
+        default = "default_jwt_bearer_entrypoint",
+        skip_serializing_if = "is_default_jwt_bearer_entrypoint"
+    )]
+    pub jwt_bearer_entrypoint: String,
+
     /// Entrypoint to use when changing password
     #[serde(
         default = "default_password_entrypoint",
@@ -137,6 +182,9 @@ impl Default for PolicyConfig {
             client_registration_entrypoint: default_client_registration_entrypoint(),
             register_entrypoint: default_register_entrypoint(),
             authorization_grant_entrypoint: default_authorization_grant_entrypoint(),
+            authorization_request_entrypoint: default_authorization_request_entrypoint(),
+            token_exchange_entrypoint: default_token_exchange_entrypoint(),
+            jwt_bearer_entrypoint: default_jwt_bearer_entrypoint(),
             password_entrypoint: default_password_entrypoint(),
             email_entrypoint: default_email_entrypoint(),
             data: default_data(),
@@ -151,6 +199,11 @@ impl PolicyConfig {
             && is_default_client_registration_entrypoint(&self.client_registration_entrypoint)
             && is_default_register_entrypoint(&self.register_entrypoint)
             && is_default_authorization_grant_entrypoint(&self.authorization_grant_entrypoint)
+            && is_default_authorization_request_entrypoint(
+                &self.authorization_request_entrypoint,
+            )
+            && is_default_token_exchange_entrypoint(&self.token_exchange_entrypoint)
+            && is_default_jwt_bearer_entrypoint(&self.jwt_bearer_entrypoint)
             && is_default_password_entrypoint(&self.password_entrypoint)
             && is_default_email_entrypoint(&self.email_entrypoint)
             && is_default_data(&self.data)
@@ -8,7 +8,7 @@ use std::borrow::Cow;
 
 use anyhow::{bail, Context};
 use camino::Utf8PathBuf;
-use mas_jose::jwk::{JsonWebKey, JsonWebKeySet};
+use mas_jose::jwk::{JsonWebKey, JsonWebKeyPrivateParameters};
 use mas_keystore::{Encrypter, Keystore, PrivateKey};
 use rand::{
     distributions::{Alphanumeric, DistString},
@@ -26,10 +26,101 @@ fn example_secret() -> &'static str {
     "0000111122223333444455556666777788889999aaaabbbbccccddddeeeeffff"
 }
 
+/// The state of a signing key in its rotation lifecycle
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyState {
+    /// The key is published, but not used for signing yet
+    Pending,
+
+    /// The key is published and used for signing
+    #[default]
+    Active,
+
+    /// The key is not used for signing anymore, but is still published so that
+    /// existing tokens can be verified
+    Retiring,
+}
+
+impl KeyState {
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl From<KeyState> for mas_keystore::KeyState {
+    fn from(state: KeyState) -> Self {
+        match state {
+            KeyState::Pending => Self::Pending,
+            KeyState::Active => Self::Active,
+            KeyState::Retiring => Self::Retiring,
+        }
+    }
+}
+
+/// Type of signing key to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    /// An RSA key
+    Rsa,
+
+    /// An ECDSA key on the P-256 curve
+    EcP256,
+
+    /// An ECDSA key on the P-384 curve
+    EcP384,
+
+    /// An ECDSA key on the secp256k1 curve
+    EcK256,
+
+    /// An Ed25519 key
+    Ed25519,
+}
+
+impl KeyType {
+    /// Generate a new random private key of this type, encoded as PEM
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key could not be generated or encoded
+    #[tracing::instrument(skip(rng))]
+    pub async fn generate_pem<R>(self, rng: R) -> anyhow::Result<String>
+    where
+        R: Rng + Send,
+    {
+        let span = tracing::Span::current();
+        let key_rng = rand_chacha::ChaChaRng::from_rng(rng)?;
+        let key = task::spawn_blocking(move || {
+            let _entered = span.enter();
+            let ret = match self {
+                Self::Rsa => PrivateKey::generate_rsa(key_rng)?,
+                Self::EcP256 => PrivateKey::generate_ec_p256(key_rng),
+                Self::EcP384 => PrivateKey::generate_ec_p384(key_rng),
+                Self::EcK256 => PrivateKey::generate_ec_k256(key_rng),
+                Self::Ed25519 => PrivateKey::generate_ed25519(key_rng),
+            };
+            info!("Done generating {self:?} key");
+            anyhow::Ok(ret)
+        })
+        .await
+        .context("could not join blocking task")??;
+
+        Ok(key.to_pem(pem_rfc7468::LineEnding::LF)?.to_string())
+    }
+}
+
+/// A private key used for signing
 #[derive(JsonSchema, Serialize, Deserialize, Clone, Debug)]
 pub struct KeyConfig {
     kid: String,
 
+    /// The state of the key in its rotation lifecycle. Pending keys are
+    /// published but not used for signing yet, and retiring keys are still
+    /// published but not used for signing anymore. Defaults to `active`.
+    #[serde(default, skip_serializing_if = "KeyState::is_default")]
+    state: KeyState,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     password: Option<String>,
 
@@ -43,6 +134,50 @@ pub struct KeyConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schemars(with = "Option<String>")]
     key_file: Option<Utf8PathBuf>,
+
+    /// The private key as a JSON Web Key, like an `OKP` key on the `Ed25519`
+    /// curve. It can't be encrypted with a password.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<std::collections::HashMap<String, String>>")]
+    jwk: Option<JsonWebKeyPrivateParameters>,
+}
+
+impl KeyConfig {
+    /// Create a key with its PEM-encoded private key embedded in the
+    /// configuration
+    #[must_use]
+    pub fn new(kid: String, key: String) -> Self {
+        Self {
+            kid,
+            state: KeyState::default(),
+            password: None,
+            password_file: None,
+            key: Some(key),
+            key_file: None,
+            jwk: None,
+        }
+    }
+
+    /// Create a key with its private key loaded from a file
+    #[must_use]
+    pub fn new_with_key_file(kid: String, key_file: Utf8PathBuf) -> Self {
+        Self {
+            kid,
+            state: KeyState::default(),
+            password: None,
+            password_file: None,
+            key: None,
+            key_file: Some(key_file),
+            jwk: None,
+        }
+    }
+
+    /// Set the state of the key in its rotation lifecycle
+    #[must_use]
+    pub fn with_state(mut self, state: KeyState) -> Self {
+        self.state = state;
+        self
+    }
 }
 
 /// Application secrets
@@ -59,6 +194,9 @@ pub struct SecretsConfig {
     pub encryption: [u8; 32],
 
     /// List of private keys to use for signing and encrypting payloads
+    ///
+    /// Only the keys in the `active` state are used for signing. All of them
+    /// are published in the JSON Web Key Set.
     #[serde(default)]
     keys: Vec<KeyConfig>,
 }
@@ -83,10 +221,18 @@ impl SecretsConfig {
             };
 
             // Read the key either embedded in the config file or on disk
-            let key = match (&item.key, &item.key_file) {
-                (None, None) => bail!("Missing `key` or `key_file`"),
-                (Some(_), Some(_)) => bail!("Cannot specify both `key` and `key_file`"),
-                (Some(key), None) => {
+            let key = match (&item.key, &item.key_file, &item.jwk) {
+                (None, None, None) => bail!("Missing `key`, `key_file` or `jwk`"),
+                (Some(_), Some(_), _) | (Some(_), _, Some(_)) | (_, Some(_), Some(_)) => {
+                    bail!("Cannot specify more than one of `key`, `key_file` and `jwk`")
+                }
+                (None, None, Some(jwk)) => {
+                    if password.is_some() {
+                        bail!("Cannot specify a password for a `jwk` key");
+                    }
+                    PrivateKey::load_jwk(jwk)?
+                }
+                (Some(key), None, None) => {
                     // If the key was embedded in the config file, assume it is formatted as PEM
                     if let Some(password) = password {
                         PrivateKey::load_encrypted_pem(key, password.as_bytes())?
@@ -94,7 +240,7 @@ impl SecretsConfig {
                         PrivateKey::load_pem(key)?
                     }
                 }
-                (None, Some(path)) => {
+                (None, Some(path), None) => {
                     // When reading from disk, it might be either PEM or DER. `PrivateKey::load*`
                     // will try both.
                     let key = tokio::fs::read(path).await?;
@@ -109,11 +255,10 @@ impl SecretsConfig {
             let key = JsonWebKey::new(key)
                 .with_kid(item.kid.clone())
                 .with_use(mas_iana::jose::JsonWebKeyUse::Sig);
-            keys.push(key);
+            keys.push((item.state.into(), key));
         }
 
-        let keys = JsonWebKeySet::new(keys);
-        Ok(Keystore::new(keys))
+        Ok(Keystore::with_states(keys))
     }
 
     /// Derive an [`Encrypter`] out of the config
@@ -141,15 +286,24 @@ impl ConfigurationSection for SecretsConfig {
                 Err(error)
             };
 
-            if key.key.is_none() && key.key_file.is_none() {
-                return annotate(figment::Error::from(
-                    "Missing `key` or `key_file`".to_owned(),
-                ));
+            let sources = [key.key.is_some(), key.key_file.is_some(), key.jwk.is_some()];
+            match sources.into_iter().filter(|set| *set).count() {
+                0 => {
+                    return annotate(figment::Error::from(
+                        "Missing `key`, `key_file` or `jwk`".to_owned(),
+                    ));
+                }
+                1 => {}
+                _ => {
+                    return annotate(figment::Error::from(
+                        "Cannot specify more than one of `key`, `key_file` and `jwk`".to_owned(),
+                    ));
+                }
             }
 
-            if key.key.is_some() && key.key_file.is_some() {
+            if key.jwk.is_some() && (key.password.is_some() || key.password_file.is_some()) {
                 return annotate(figment::Error::from(
-                    "Cannot specify both `key` and `key_file`".to_owned(),
+                    "Cannot specify a password for a `jwk` key".to_owned(),
                 ));
             }
 
@@ -158,6 +312,26 @@ impl ConfigurationSection for SecretsConfig {
                     "Cannot specify both `password` and `password_file`".to_owned(),
                 ));
             }
+
+            if self.keys[..index].iter().any(|other| other.kid == key.kid) {
+                return annotate(figment::Error::from(format!(
+                    "Duplicate key ID {:?}",
+                    key.kid
+                )));
+            }
+        }
+
+        // Staging or retiring keys must not leave the service without any key
+        // to sign with
+        if !self.keys.is_empty() && !self.keys.iter().any(|key| key.state == KeyState::Active) {
+            let mut error =
+                figment::Error::from("At least one key must be in the `active` state".to_owned());
+            error.metadata = figment
+                .find_metadata(&format!("{root}.keys", root = Self::PATH.unwrap()))
+                .cloned();
+            error.profile = Some(figment::Profile::Default);
+            error.path = vec![Self::PATH.unwrap().to_owned(), "keys".to_owned()];
+            return Err(error);
         }
 
         Ok(())
@@ -172,87 +346,28 @@ impl SecretsConfig {
     {
         info!("Generating keys...");
 
-        let span = tracing::info_span!("rsa");
-        let key_rng = rand_chacha::ChaChaRng::from_rng(&mut rng)?;
-        let rsa_key = task::spawn_blocking(move || {
-            let _entered = span.enter();
-            let ret = PrivateKey::generate_rsa(key_rng).unwrap();
-            info!("Done generating RSA key");
-            ret
-        })
-        .await
-        .context("could not join blocking task")?;
-        let rsa_key = KeyConfig {
-            kid: Alphanumeric.sample_string(&mut rng, 10),
-            password: None,
-            password_file: None,
-            key: Some(rsa_key.to_pem(pem_rfc7468::LineEnding::LF)?.to_string()),
-            key_file: None,
-        };
-
-        let span = tracing::info_span!("ec_p256");
-        let key_rng = rand_chacha::ChaChaRng::from_rng(&mut rng)?;
-        let ec_p256_key = task::spawn_blocking(move || {
-            let _entered = span.enter();
-            let ret = PrivateKey::generate_ec_p256(key_rng);
-            info!("Done generating EC P-256 key");
-            ret
-        })
-        .await
-        .context("could not join blocking task")?;
-        let ec_p256_key = KeyConfig {
-            kid: Alphanumeric.sample_string(&mut rng, 10),
-            password: None,
-            password_file: None,
-            key: Some(ec_p256_key.to_pem(pem_rfc7468::LineEnding::LF)?.to_string()),
-            key_file: None,
-        };
-
-        let span = tracing::info_span!("ec_p384");
-        let key_rng = rand_chacha::ChaChaRng::from_rng(&mut rng)?;
-        let ec_p384_key = task::spawn_blocking(move || {
-            let _entered = span.enter();
-            let ret = PrivateKey::generate_ec_p384(key_rng);
-            info!("Done generating EC P-256 key");
-            ret
-        })
-        .await
-        .context("could not join blocking task")?;
-        let ec_p384_key = KeyConfig {
-            kid: Alphanumeric.sample_string(&mut rng, 10),
-            password: None,
-            password_file: None,
-            key: Some(ec_p384_key.to_pem(pem_rfc7468::LineEnding::LF)?.to_string()),
-            key_file: None,
-        };
-
-        let span = tracing::info_span!("ec_k256");
-        let key_rng = rand_chacha::ChaChaRng::from_rng(&mut rng)?;
-        let ec_k256_key = task::spawn_blocking(move || {
-            let _entered = span.enter();
-            let ret = PrivateKey::generate_ec_k256(key_rng);
-            info!("Done generating EC secp256k1 key");
-            ret
-        })
-        .await
-        .context("could not join blocking task")?;
-        let ec_k256_key = KeyConfig {
-            kid: Alphanumeric.sample_string(&mut rng, 10),
-            password: None,
-            password_file: None,
-            key: Some(ec_k256_key.to_pem(pem_rfc7468::LineEnding::LF)?.to_string()),
-            key_file: None,
-        };
+        let mut keys = Vec::new();
+        for key_type in [
+            KeyType::Rsa,
+            KeyType::EcP256,
+            KeyType::EcP384,
+            KeyType::EcK256,
+        ] {
+            let key = key_type.generate_pem(&mut rng).await?;
+            let kid = Alphanumeric.sample_string(&mut rng, 10);
+            keys.push(KeyConfig::new(kid, key));
+        }
 
         Ok(Self {
             encryption: rng.gen(),
-            keys: vec![rsa_key, ec_p256_key, ec_p384_key, ec_k256_key],
+            keys,
         })
     }
 
     pub(crate) fn test() -> Self {
         let rsa_key = KeyConfig {
             kid: "abcdef".to_owned(),
+            state: KeyState::default(),
             password: None,
             password_file: None,
             key: Some(
@@ -271,9 +386,11 @@ impl SecretsConfig {
                 .to_owned(),
             ),
             key_file: None,
+            jwk: None,
         };
         let ecdsa_key = KeyConfig {
             kid: "ghijkl".to_owned(),
+            state: KeyState::default(),
             password: None,
             password_file: None,
             key: Some(
@@ -287,6 +404,7 @@ impl SecretsConfig {
                 .to_owned(),
             ),
             key_file: None,
+            jwk: None,
         };
 
         Self {
@@ -295,3 +413,136 @@ impl SecretsConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use figment::{
+        providers::{Format, Yaml},
+        Figment, Jail,
+    };
+
+    use super::*;
+
+    #[test]
+    fn load_config_with_states() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  secrets:
+                    encryption: 0000111122223333444455556666777788889999aaaabbbbccccddddeeeeffff
+                    keys:
+                      - kid: old
+                        state: retiring
+                        key_file: old.pem
+                      - kid: current
+                        key_file: current.pem
+                      - kid: next
+                        state: pending
+                        key_file: next.pem
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<SecretsConfig>("secrets")?;
+            config.validate(&figment)?;
+
+            let states: Vec<_> = config.keys.iter().map(|key| key.state).collect();
+            assert_eq!(
+                states,
+                vec![KeyState::Retiring, KeyState::Active, KeyState::Pending]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn load_jwk_key() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  secrets:
+                    encryption: 0000111122223333444455556666777788889999aaaabbbbccccddddeeeeffff
+                    keys:
+                      - kid: ed25519
+                        jwk:
+                          kty: OKP
+                          crv: Ed25519
+                          d: nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A
+                          x: 11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<SecretsConfig>("secrets")?;
+            config.validate(&figment)?;
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let keystore = runtime.block_on(config.key_store()).unwrap();
+            assert!(keystore
+                .signing_key_for_algorithm(&mas_iana::jose::JsonWebSignatureAlg::EdDsa)
+                .is_some());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn jwk_and_key_file() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  secrets:
+                    encryption: 0000111122223333444455556666777788889999aaaabbbbccccddddeeeeffff
+                    keys:
+                      - kid: ed25519
+                        key_file: ed25519.pem
+                        jwk:
+                          kty: OKP
+                          crv: Ed25519
+                          d: nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A
+                          x: 11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<SecretsConfig>("secrets")?;
+            let error = config.validate(&figment).unwrap_err();
+            assert_eq!(error.path, vec!["secrets", "keys", "0"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn no_active_key() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  secrets:
+                    encryption: 0000111122223333444455556666777788889999aaaabbbbccccddddeeeeffff
+                    keys:
+                      - kid: old
+                        state: retiring
+                        key_file: old.pem
+                      - kid: next
+                        state: pending
+                        key_file: next.pem
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<SecretsConfig>("secrets")?;
+            let error = config.validate(&figment).unwrap_err();
+            assert_eq!(error.path, vec!["secrets", "keys"]);
+
+            Ok(())
+        });
+    }
+}
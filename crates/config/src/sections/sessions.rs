@@ -0,0 +1,286 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeSet;
+
+use chrono::Duration;
+use schemars::JsonSchema;
+use serde::{de::Error, Deserialize, Serialize};
+use serde_with::serde_as;
+use ulid::Ulid;
+
+use crate::ConfigurationSection;
+
+/// How long sessions of a kind can last before they are automatically
+/// finished
+#[serde_as]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, JsonSchema, Serialize)]
+pub struct SessionExpirationConfig {
+    /// How long a session can stay inactive before it is finished, in
+    /// seconds. Sessions which were never used are considered active when
+    /// they were created. By default, sessions never expire for inactivity.
+    #[schemars(with = "Option<u64>", range(min = 60))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub idle_timeout: Option<Duration>,
+
+    /// How long a session can last in total, regardless of its activity, in
+    /// seconds. By default, sessions have no maximum lifetime.
+    #[schemars(with = "Option<u64>", range(min = 60))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub max_lifetime: Option<Duration>,
+}
+
+impl SessionExpirationConfig {
+    pub(crate) fn is_default(&self) -> bool {
+        self.idle_timeout.is_none() && self.max_lifetime.is_none()
+    }
+
+    fn validate(&self) -> Result<(), figment::error::Error> {
+        for (field, value) in [
+            ("idle_timeout", self.idle_timeout),
+            ("max_lifetime", self.max_lifetime),
+        ] {
+            if value.is_some_and(|value| value < Duration::try_minutes(1).unwrap()) {
+                let error =
+                    figment::error::Error::custom("must be at least 60 seconds").with_path(field);
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Expiration of the OAuth 2.0 sessions of a specific client
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, JsonSchema, Serialize)]
+pub struct ClientSessionExpirationConfig {
+    /// The ID of the client
+    #[schemars(
+        with = "String",
+        regex(pattern = r"^[0123456789ABCDEFGHJKMNPQRSTVWXYZ]{26}$"),
+        description = "A ULID as per https://github.com/ulid/spec"
+    )]
+    pub client_id: Ulid,
+
+    /// How long a session of this client can stay inactive before it is
+    /// finished, in seconds. If not set, the default OAuth 2.0 setting
+    /// applies.
+    #[schemars(with = "Option<u64>", range(min = 60))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub idle_timeout: Option<Duration>,
+
+    /// How long a session of this client can last in total, in seconds. If
+    /// not set, the default OAuth 2.0 setting applies.
+    #[schemars(with = "Option<u64>", range(min = 60))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub max_lifetime: Option<Duration>,
+}
+
+/// Expiration of OAuth 2.0 sessions
+#[serde_as]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, JsonSchema, Serialize)]
+pub struct OAuth2SessionExpirationConfig {
+    /// How long a session can stay inactive before it is finished, in
+    /// seconds. Sessions which were never used are considered active when
+    /// they were created. By default, sessions never expire for inactivity.
+    #[schemars(with = "Option<u64>", range(min = 60))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub idle_timeout: Option<Duration>,
+
+    /// How long a session can last in total, regardless of its activity, in
+    /// seconds. By default, sessions have no maximum lifetime.
+    #[schemars(with = "Option<u64>", range(min = 60))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub max_lifetime: Option<Duration>,
+
+    /// Overrides of those settings for specific clients
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clients: Vec<ClientSessionExpirationConfig>,
+}
+
+impl OAuth2SessionExpirationConfig {
+    pub(crate) fn is_default(&self) -> bool {
+        self.idle_timeout.is_none() && self.max_lifetime.is_none() && self.clients.is_empty()
+    }
+
+    fn validate(&self) -> Result<(), figment::error::Error> {
+        SessionExpirationConfig {
+            idle_timeout: self.idle_timeout,
+            max_lifetime: self.max_lifetime,
+        }
+        .validate()?;
+
+        let mut client_ids = BTreeSet::new();
+        for (index, client) in self.clients.iter().enumerate() {
+            let with_path = |mut error: figment::error::Error| {
+                error.path.insert(0, "clients".to_owned());
+                error.path.insert(1, index.to_string());
+                error
+            };
+
+            if !client_ids.insert(client.client_id) {
+                let error = figment::error::Error::custom(format!(
+                    "duplicate client ID {}",
+                    client.client_id
+                ))
+                .with_path("client_id");
+                return Err(with_path(error));
+            }
+
+            SessionExpirationConfig {
+                idle_timeout: client.idle_timeout,
+                max_lifetime: client.max_lifetime,
+            }
+            .validate()
+            .map_err(with_path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Configuration section to automatically finish sessions which are inactive
+/// or too old
+///
+/// Sessions are checked periodically by a background job, which relies on the
+/// activity recorded when sessions are used.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+pub struct SessionsConfig {
+    /// Expiration of OAuth 2.0 sessions
+    #[serde(
+        default,
+        skip_serializing_if = "OAuth2SessionExpirationConfig::is_default"
+    )]
+    pub oauth2: OAuth2SessionExpirationConfig,
+
+    /// Expiration of compatibility sessions, created through the legacy Matrix
+    /// login API
+    #[serde(default, skip_serializing_if = "SessionExpirationConfig::is_default")]
+    pub compat: SessionExpirationConfig,
+
+    /// Expiration of browser sessions, used to log in to the service itself
+    #[serde(default, skip_serializing_if = "SessionExpirationConfig::is_default")]
+    pub browser: SessionExpirationConfig,
+}
+
+impl SessionsConfig {
+    pub(crate) fn is_default(&self) -> bool {
+        self.oauth2.is_default() && self.compat.is_default() && self.browser.is_default()
+    }
+}
+
+impl ConfigurationSection for SessionsConfig {
+    const PATH: Option<&'static str> = Some("sessions");
+
+    fn validate(&self, figment: &figment::Figment) -> Result<(), figment::Error> {
+        let with_location = |kind: &'static str| {
+            move |mut error: figment::error::Error| {
+                error.metadata = figment.find_metadata(Self::PATH.unwrap()).cloned();
+                error.profile = Some(figment::Profile::Default);
+                error.path.insert(0, Self::PATH.unwrap().to_owned());
+                error.path.insert(1, kind.to_owned());
+                error
+            }
+        };
+
+        self.oauth2.validate().map_err(with_location("oauth2"))?;
+        self.compat.validate().map_err(with_location("compat"))?;
+        self.browser.validate().map_err(with_location("browser"))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use figment::{
+        providers::{Format, Yaml},
+        Figment, Jail,
+    };
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  sessions:
+                    oauth2:
+                      idle_timeout: 2592000
+                      clients:
+                        - client_id: 01GFWR28C4KNE04WG3HKXB7C9R
+                          idle_timeout: 86400
+                          max_lifetime: 604800
+                    compat:
+                      idle_timeout: 2592000
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<SessionsConfig>("sessions")?;
+            config.validate(&figment)?;
+
+            assert_eq!(
+                config.oauth2.idle_timeout,
+                Some(Duration::try_days(30).unwrap())
+            );
+            assert_eq!(config.oauth2.max_lifetime, None);
+            assert_eq!(
+                config.oauth2.clients,
+                vec![ClientSessionExpirationConfig {
+                    client_id: Ulid::from_str("01GFWR28C4KNE04WG3HKXB7C9R").unwrap(),
+                    idle_timeout: Some(Duration::try_days(1).unwrap()),
+                    max_lifetime: Some(Duration::try_days(7).unwrap()),
+                }]
+            );
+            assert_eq!(
+                config.compat.idle_timeout,
+                Some(Duration::try_days(30).unwrap())
+            );
+            assert!(config.browser.is_default());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn duplicate_clients() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  sessions:
+                    oauth2:
+                      clients:
+                        - client_id: 01GFWR28C4KNE04WG3HKXB7C9R
+                          idle_timeout: 86400
+                        - client_id: 01GFWR28C4KNE04WG3HKXB7C9R
+                          max_lifetime: 604800
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<SessionsConfig>("sessions")?;
+            let error = config.validate(&figment).unwrap_err();
+            assert_eq!(
+                error.path,
+                vec!["sessions", "oauth2", "clients", "1", "client_id"]
+            );
+
+            Ok(())
+        });
+    }
+}
@@ -18,14 +18,26 @@ use crate::ConfigurationSection;
 /// Upstream OAuth 2.0 providers configuration
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct UpstreamOAuth2Config {
+    /// Whether providers can be managed through the admin API.
+    ///
+    /// When enabled, this configuration file is no longer authoritative:
+    /// providers listed here are still synced to the database on startup, but
+    /// providers which only exist in the database are left untouched instead
+    /// of being disabled or deleted.
+    ///
+    /// Defaults to `false`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_admin_api: bool,
+
     /// List of OAuth 2.0 providers
+    #[serde(default)]
     pub providers: Vec<Provider>,
 }
 
 impl UpstreamOAuth2Config {
     /// Returns true if the configuration is the default one
     pub(crate) fn is_default(&self) -> bool {
-        self.providers.is_empty()
+        !self.allow_admin_api && self.providers.is_empty()
     }
 }
 
@@ -279,7 +291,7 @@ pub struct ClaimsImports {
 }
 
 impl ClaimsImports {
-    const fn is_default(&self) -> bool {
+    pub(crate) const fn is_default(&self) -> bool {
         self.subject.is_default()
             && self.localpart.is_default()
             && self.displayname.is_default()
@@ -0,0 +1,221 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use schemars::JsonSchema;
+use serde::{de::Error, Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use ulid::Ulid;
+use url::Url;
+
+use super::upstream_oauth2::ClaimsImports;
+use crate::ConfigurationSection;
+
+/// Upstream SAML 2.0 identity providers configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct UpstreamSaml2Config {
+    /// List of SAML 2.0 identity providers
+    #[serde(default)]
+    pub providers: Vec<Provider>,
+}
+
+impl UpstreamSaml2Config {
+    /// Returns true if the configuration is the default one
+    pub(crate) fn is_default(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+impl ConfigurationSection for UpstreamSaml2Config {
+    const PATH: Option<&'static str> = Some("upstream_saml2");
+
+    fn validate(&self, figment: &figment::Figment) -> Result<(), figment::Error> {
+        for (index, provider) in self.providers.iter().enumerate() {
+            if provider.idp_certificates.is_empty() {
+                let mut error = figment::Error::custom(
+                    "At least one certificate is needed to verify the signature of assertions",
+                );
+                error.metadata = figment
+                    .find_metadata(&format!("{root}.providers", root = Self::PATH.unwrap()))
+                    .cloned();
+                error.profile = Some(figment::Profile::Default);
+                error.path = vec![
+                    Self::PATH.unwrap().to_owned(),
+                    "providers".to_owned(),
+                    index.to_string(),
+                    "idp_certificates".to_owned(),
+                ];
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How authentication requests are sent to the identity provider
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SsoBinding {
+    /// `redirect`: Use the HTTP-Redirect binding, where the request is sent in
+    /// the query parameters
+    #[default]
+    Redirect,
+
+    /// `post`: Use the HTTP-POST binding, where the request is sent through an
+    /// auto-submitted form
+    Post,
+}
+
+impl SsoBinding {
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    const fn is_default(&self) -> bool {
+        matches!(self, SsoBinding::Redirect)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_default_true(value: &bool) -> bool {
+    *value
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Provider {
+    /// Whether this provider is enabled.
+    ///
+    /// Defaults to `true`
+    #[serde(default = "default_true", skip_serializing_if = "is_default_true")]
+    pub enabled: bool,
+
+    /// An internal unique identifier for this provider
+    ///
+    /// It must not be used by any provider in the `upstream_oauth2` section
+    #[schemars(
+        with = "String",
+        regex(pattern = r"^[0123456789ABCDEFGHJKMNPQRSTVWXYZ]{26}$"),
+        description = "A ULID as per https://github.com/ulid/spec"
+    )]
+    pub id: Ulid,
+
+    /// A human-readable name for the provider, that will be shown to users
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_name: Option<String>,
+
+    /// A brand identifier used to customise the UI
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brand_name: Option<String>,
+
+    /// The entity ID of the identity provider, which must be the issuer of
+    /// its responses
+    pub idp_entity_id: String,
+
+    /// The URL of the single sign-on service of the identity provider
+    pub idp_sso_url: Url,
+
+    /// The X.509 certificates of the identity provider, used to verify the
+    /// signature of its responses.
+    ///
+    /// Each certificate is either PEM-encoded or the base64-encoded DER value
+    /// found in the identity provider metadata. Only RSA keys are supported.
+    pub idp_certificates: Vec<String>,
+
+    /// How authentication requests are sent to the identity provider
+    ///
+    /// Defaults to `redirect`
+    #[serde(default, skip_serializing_if = "SsoBinding::is_default")]
+    pub sso_binding: SsoBinding,
+
+    /// The entity ID of this service provider, as registered at the identity
+    /// provider. Responses must have it as their audience.
+    pub sp_entity_id: String,
+
+    /// The format of the subject identifier to ask for, e.g.
+    /// `urn:oasis:names:tc:SAML:2.0:nameid-format:persistent`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_id_format: Option<String>,
+
+    /// How attributes of the assertion should be imported
+    ///
+    /// The subject identifier is available as `sub`, and attributes are
+    /// available both by their name and by their friendly name, as a string
+    /// if they have a single value, or as a list of strings otherwise.
+    #[serde(default, skip_serializing_if = "ClaimsImports::is_default")]
+    pub claims_imports: ClaimsImports,
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::{
+        providers::{Format, Yaml},
+        Figment, Jail,
+    };
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                    upstream_saml2:
+                      providers:
+                        - id: 01JA2M4Y3B9X5K7Q8W0E6R1T2Y
+                          idp_entity_id: https://idp.example.com/metadata
+                          idp_sso_url: https://idp.example.com/sso
+                          idp_certificates:
+                            - MIIC...
+                          sso_binding: post
+                          sp_entity_id: https://mas.example.com/
+                          claims_imports:
+                            localpart:
+                              action: require
+                              template: "{{ user.uid }}"
+                "#,
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<UpstreamSaml2Config>("upstream_saml2")?;
+            config.validate(&figment)?;
+
+            assert_eq!(config.providers.len(), 1);
+            let provider = &config.providers[0];
+            assert!(provider.enabled);
+            assert_eq!(provider.sso_binding, SsoBinding::Post);
+            assert_eq!(provider.sp_entity_id, "https://mas.example.com/");
+            assert_eq!(provider.name_id_format, None);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn missing_certificates() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                    upstream_saml2:
+                      providers:
+                        - id: 01JA2M4Y3B9X5K7Q8W0E6R1T2Y
+                          idp_entity_id: https://idp.example.com/metadata
+                          idp_sso_url: https://idp.example.com/sso
+                          idp_certificates: []
+                          sp_entity_id: https://mas.example.com/
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<UpstreamSaml2Config>("upstream_saml2")?;
+            assert!(config.validate(&figment).is_err());
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeSet;
+
+use schemars::JsonSchema;
+use serde::{de::Error, Deserialize, Serialize};
+use url::Url;
+
+use crate::ConfigurationSection;
+
+const fn default_max_attempts() -> u32 {
+    10
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_default_max_attempts(value: &u32) -> bool {
+    *value == default_max_attempts()
+}
+
+/// A user lifecycle event which can be sent to a webhook target
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub enum WebhookEvent {
+    /// A new user registered
+    #[serde(rename = "user.registered")]
+    UserRegistered,
+
+    /// A user verified one of their email addresses
+    #[serde(rename = "user.email_verified")]
+    UserEmailVerified,
+
+    /// A user was locked
+    #[serde(rename = "user.locked")]
+    UserLocked,
+
+    /// A user was unlocked
+    #[serde(rename = "user.unlocked")]
+    UserUnlocked,
+
+    /// A user was deactivated
+    #[serde(rename = "user.deactivated")]
+    UserDeactivated,
+
+    /// A user was reactivated
+    #[serde(rename = "user.reactivated")]
+    UserReactivated,
+
+    /// A user was linked to an upstream OAuth 2.0 provider account
+    #[serde(rename = "upstream_oauth_link.created")]
+    UpstreamOAuthLinkCreated,
+}
+
+/// An HTTP endpoint which receives user lifecycle events
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct WebhookTargetConfig {
+    /// A unique name for this target, used to keep track of deliveries
+    pub name: String,
+
+    /// The URL to which events are sent with a `POST` request
+    pub url: Url,
+
+    /// The secret used to sign the payloads.
+    ///
+    /// Each request has a `X-MAS-Webhook-Signature: v1=<signature>` header,
+    /// where the signature is the hex-encoded HMAC-SHA256 of the
+    /// `X-MAS-Webhook-Timestamp` header, a dot, and the request body.
+    pub secret: String,
+
+    /// Which events should be sent to this target. If empty, all events are
+    /// sent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<WebhookEvent>,
+
+    /// How many times the delivery of an event should be attempted before
+    /// giving up on it. Defaults to 10.
+    ///
+    /// Retries are spaced out with an exponential backoff.
+    #[serde(
+        default = "default_max_attempts",
+        skip_serializing_if = "is_default_max_attempts"
+    )]
+    #[schemars(range(min = 1))]
+    pub max_attempts: u32,
+}
+
+/// Configuration section to send user lifecycle events to HTTP endpoints
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct WebhooksConfig {
+    /// List of webhook targets
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<WebhookTargetConfig>,
+}
+
+impl WebhooksConfig {
+    /// Returns true if the configuration is the default one
+    pub(crate) fn is_default(&self) -> bool {
+        self.targets.is_empty()
+    }
+}
+
+impl ConfigurationSection for WebhooksConfig {
+    const PATH: Option<&'static str> = Some("webhooks");
+
+    fn validate(&self, figment: &figment::Figment) -> Result<(), figment::Error> {
+        let metadata = figment.find_metadata(Self::PATH.unwrap());
+
+        let error_on_target =
+            |mut error: figment::error::Error, index: usize, field: &'static str| {
+                error.metadata = metadata.cloned();
+                error.profile = Some(figment::Profile::Default);
+                error.path = vec![
+                    Self::PATH.unwrap().to_owned(),
+                    "targets".to_owned(),
+                    index.to_string(),
+                    field.to_owned(),
+                ];
+                error
+            };
+
+        let mut names = BTreeSet::new();
+        for (index, target) in self.targets.iter().enumerate() {
+            if !names.insert(target.name.as_str()) {
+                return Err(error_on_target(
+                    figment::error::Error::custom(format!(
+                        "duplicate webhook target name {:?}",
+                        target.name
+                    )),
+                    index,
+                    "name",
+                ));
+            }
+
+            if target.secret.is_empty() {
+                return Err(error_on_target(
+                    figment::error::Error::custom("the secret must not be empty"),
+                    index,
+                    "secret",
+                ));
+            }
+
+            if target.max_attempts == 0 {
+                return Err(error_on_target(
+                    figment::error::Error::custom("max_attempts must be at least 1"),
+                    index,
+                    "max_attempts",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::{
+        providers::{Format, Yaml},
+        Figment, Jail,
+    };
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  webhooks:
+                    targets:
+                      - name: crm
+                        url: https://crm.example.com/hooks/mas
+                        secret: hunter2
+                        events:
+                          - user.registered
+                          - user.deactivated
+
+                      - name: audit
+                        url: https://audit.example.com/
+                        secret: correcthorse
+                        max_attempts: 3
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<WebhooksConfig>("webhooks")?;
+            config.validate(&figment)?;
+
+            assert_eq!(config.targets.len(), 2);
+            assert_eq!(config.targets[0].name, "crm");
+            assert_eq!(
+                config.targets[0].events,
+                vec![WebhookEvent::UserRegistered, WebhookEvent::UserDeactivated]
+            );
+            assert_eq!(config.targets[0].max_attempts, 10);
+            assert!(config.targets[1].events.is_empty());
+            assert_eq!(config.targets[1].max_attempts, 3);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn duplicate_names() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  webhooks:
+                    targets:
+                      - name: crm
+                        url: https://crm.example.com/hooks/mas
+                        secret: hunter2
+                      - name: crm
+                        url: https://crm.example.com/hooks/other
+                        secret: hunter2
+                ",
+            )?;
+
+            let figment = Figment::new().merge(Yaml::file("config.yaml"));
+            let config = figment.extract_inner::<WebhooksConfig>("webhooks")?;
+            let error = config.validate(&figment).unwrap_err();
+            assert_eq!(error.path, ["webhooks", "targets", "1", "name"]);
+
+            Ok(())
+        });
+    }
+}
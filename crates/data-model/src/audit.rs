@@ -0,0 +1,251 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use thiserror::Error;
+use ulid::Ulid;
+
+use crate::{Session, User};
+
+/// The kind of security-relevant event recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum AuditEventKind {
+    /// A user logged in
+    #[serde(rename = "user.login")]
+    UserLogin,
+
+    /// A login attempt failed
+    #[serde(rename = "user.login_failed")]
+    UserLoginFailed,
+
+    /// The password of a user was set or changed
+    #[serde(rename = "user.password_changed")]
+    UserPasswordChanged,
+
+    /// An email address was added to a user
+    #[serde(rename = "user.email_added")]
+    UserEmailAdded,
+
+    /// An email address was removed from a user
+    #[serde(rename = "user.email_removed")]
+    UserEmailRemoved,
+
+    /// The primary email address of a user changed
+    #[serde(rename = "user.primary_email_changed")]
+    UserPrimaryEmailChanged,
+
+    /// A passkey was registered to a user
+    #[serde(rename = "user.passkey_added")]
+    UserPasskeyAdded,
+
+    /// A passkey was removed from a user
+    #[serde(rename = "user.passkey_removed")]
+    UserPasskeyRemoved,
+
+    /// A user was locked
+    #[serde(rename = "user.locked")]
+    UserLocked,
+
+    /// A user was unlocked
+    #[serde(rename = "user.unlocked")]
+    UserUnlocked,
+
+    /// A user was deactivated
+    #[serde(rename = "user.deactivated")]
+    UserDeactivated,
+
+    /// A browser, compatibility or OAuth 2.0 session was ended
+    #[serde(rename = "session.ended")]
+    SessionEnded,
+
+    /// The policy denied an operation
+    #[serde(rename = "policy.denied")]
+    PolicyDenied,
+
+    /// A write operation was done through the admin API
+    #[serde(rename = "admin_api.call")]
+    AdminApiCall,
+}
+
+impl AuditEventKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::UserLogin => "user.login",
+            Self::UserLoginFailed => "user.login_failed",
+            Self::UserPasswordChanged => "user.password_changed",
+            Self::UserEmailAdded => "user.email_added",
+            Self::UserEmailRemoved => "user.email_removed",
+            Self::UserPrimaryEmailChanged => "user.primary_email_changed",
+            Self::UserPasskeyAdded => "user.passkey_added",
+            Self::UserPasskeyRemoved => "user.passkey_removed",
+            Self::UserLocked => "user.locked",
+            Self::UserUnlocked => "user.unlocked",
+            Self::UserDeactivated => "user.deactivated",
+            Self::SessionEnded => "session.ended",
+            Self::PolicyDenied => "policy.denied",
+            Self::AdminApiCall => "admin_api.call",
+        }
+    }
+}
+
+impl std::fmt::Display for AuditEventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("Invalid audit event kind {0:?}")]
+pub struct InvalidAuditEventKindError(String);
+
+impl std::str::FromStr for AuditEventKind {
+    type Err = InvalidAuditEventKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user.login" => Ok(Self::UserLogin),
+            "user.login_failed" => Ok(Self::UserLoginFailed),
+            "user.password_changed" => Ok(Self::UserPasswordChanged),
+            "user.email_added" => Ok(Self::UserEmailAdded),
+            "user.email_removed" => Ok(Self::UserEmailRemoved),
+            "user.primary_email_changed" => Ok(Self::UserPrimaryEmailChanged),
+            "user.passkey_added" => Ok(Self::UserPasskeyAdded),
+            "user.passkey_removed" => Ok(Self::UserPasskeyRemoved),
+            "user.locked" => Ok(Self::UserLocked),
+            "user.unlocked" => Ok(Self::UserUnlocked),
+            "user.deactivated" => Ok(Self::UserDeactivated),
+            "session.ended" => Ok(Self::SessionEnded),
+            "policy.denied" => Ok(Self::PolicyDenied),
+            "admin_api.call" => Ok(Self::AdminApiCall),
+            s => Err(InvalidAuditEventKindError(s.to_owned())),
+        }
+    }
+}
+
+/// Who triggered an audited event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditActor {
+    /// Nobody was authenticated, like during a login attempt
+    Anonymous,
+
+    /// A user acting on their own account
+    User { user_id: Ulid },
+
+    /// A client using an access token with the admin scope
+    AdminToken {
+        session_id: Ulid,
+        user_id: Option<Ulid>,
+    },
+
+    /// An operator using the `mas-cli manage` commands
+    Cli,
+
+    /// The server itself, like a background job
+    System,
+}
+
+impl AuditActor {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Anonymous => "anonymous",
+            Self::User { .. } => "user",
+            Self::AdminToken { .. } => "admin_token",
+            Self::Cli => "cli",
+            Self::System => "system",
+        }
+    }
+
+    /// The ID of the user behind this actor, if any
+    #[must_use]
+    pub fn user_id(self) -> Option<Ulid> {
+        match self {
+            Self::User { user_id } => Some(user_id),
+            Self::AdminToken { user_id, .. } => user_id,
+            Self::Anonymous | Self::Cli | Self::System => None,
+        }
+    }
+}
+
+/// Where an audited event comes from: the actor, and the client it used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditContext {
+    pub actor: AuditActor,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl AuditContext {
+    #[must_use]
+    pub fn new(actor: AuditActor) -> Self {
+        Self {
+            actor,
+            ip_address: None,
+            user_agent: None,
+        }
+    }
+
+    /// Context for events triggered by an unauthenticated client
+    #[must_use]
+    pub fn anonymous() -> Self {
+        Self::new(AuditActor::Anonymous)
+    }
+
+    /// Context for events triggered by a user on their own account
+    #[must_use]
+    pub fn user(user: &User) -> Self {
+        Self::new(AuditActor::User { user_id: user.id })
+    }
+
+    /// Context for events triggered through an admin token
+    #[must_use]
+    pub fn admin_token(session: &Session) -> Self {
+        Self::new(AuditActor::AdminToken {
+            session_id: session.id,
+            user_id: session.user_id,
+        })
+    }
+
+    /// Context for events triggered by the command line interface
+    #[must_use]
+    pub fn cli() -> Self {
+        Self::new(AuditActor::Cli)
+    }
+
+    /// Context for events triggered by the server itself
+    #[must_use]
+    pub fn system() -> Self {
+        Self::new(AuditActor::System)
+    }
+
+    #[must_use]
+    pub fn with_ip_address(mut self, ip_address: Option<IpAddr>) -> Self {
+        self.ip_address = ip_address;
+        self
+    }
+
+    #[must_use]
+    pub fn with_user_agent(mut self, user_agent: Option<String>) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditEvent {
+    pub id: Ulid,
+    pub created_at: DateTime<Utc>,
+    pub kind: AuditEventKind,
+    pub user_id: Option<Ulid>,
+    pub actor: AuditActor,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub details: serde_json::Value,
+}
@@ -8,6 +8,7 @@
 
 use thiserror::Error;
 
+pub(crate) mod audit;
 pub(crate) mod compat;
 pub(crate) mod oauth2;
 mod site_config;
@@ -15,6 +16,7 @@ pub(crate) mod tokens;
 pub(crate) mod upstream_oauth2;
 pub(crate) mod user_agent;
 pub(crate) mod users;
+pub(crate) mod webhooks;
 
 /// Error when an invalid state transition is attempted.
 #[derive(Debug, Error)]
@@ -24,13 +26,15 @@ pub struct InvalidTransitionError;
 pub use ulid::Ulid;
 
 pub use self::{
+    audit::{AuditActor, AuditContext, AuditEvent, AuditEventKind, InvalidAuditEventKindError},
     compat::{
         CompatAccessToken, CompatRefreshToken, CompatRefreshTokenState, CompatSession,
         CompatSessionState, CompatSsoLogin, CompatSsoLoginState, Device,
     },
     oauth2::{
         AuthorizationCode, AuthorizationGrant, AuthorizationGrantStage, Client, DeviceCodeGrant,
-        DeviceCodeGrantState, InvalidRedirectUriError, JwksOrJwksUri, Pkce, Session, SessionState,
+        DeviceCodeGrantState, InvalidPostLogoutRedirectUriError, InvalidRedirectUriError,
+        JwksOrJwksUri, Pkce, PushedAuthorizationRequest, Session, SessionState,
     },
     site_config::{CaptchaConfig, CaptchaService, SiteConfig},
     tokens::{
@@ -41,11 +45,17 @@ pub use self::{
         UpstreamOAuthAuthorizationSessionState, UpstreamOAuthLink, UpstreamOAuthProvider,
         UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
         UpstreamOAuthProviderImportAction, UpstreamOAuthProviderImportPreference,
-        UpstreamOAuthProviderPkceMode, UpstreamOAuthProviderSubjectPreference,
+        UpstreamOAuthProviderKind, UpstreamOAuthProviderPkceMode, UpstreamOAuthProviderSamlBinding,
+        UpstreamOAuthProviderSamlSettings, UpstreamOAuthProviderSubjectPreference,
     },
     user_agent::{DeviceType, UserAgent},
     users::{
         Authentication, AuthenticationMethod, BrowserSession, Password, User, UserEmail,
-        UserEmailVerification, UserEmailVerificationState, UserRecoverySession, UserRecoveryTicket,
+        UserEmailVerification, UserEmailVerificationState, UserLdapLink, UserPasskey,
+        UserRecoverySession, UserRecoveryTicket, UserTotpRecoveryCode, UserTotpSecret,
+        ACR_MULTI_FACTOR,
+    },
+    webhooks::{
+        InvalidWebhookEventKindError, WebhookDelivery, WebhookDeliveryState, WebhookEventKind,
     },
 };
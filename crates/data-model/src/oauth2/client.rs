@@ -5,7 +5,10 @@
 // Please see LICENSE in the repository root for full details.
 
 use chrono::{DateTime, Utc};
-use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
+use mas_iana::{
+    jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg},
+    oauth::OAuthClientAuthenticationMethod,
+};
 use mas_jose::jwk::PublicJsonWebKeySet;
 use oauth2_types::{
     oidc::ApplicationType,
@@ -28,6 +31,7 @@ pub enum JwksOrJwksUri {
     JwksUri(Url),
 }
 
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Client {
     pub id: Ulid,
@@ -72,6 +76,20 @@ pub struct Client {
     /// JWS alg algorithm REQUIRED for signing `UserInfo` Responses.
     pub userinfo_signed_response_alg: Option<JsonWebSignatureAlg>,
 
+    /// JWE alg algorithm REQUIRED for encrypting the ID Token issued to this
+    /// Client
+    pub id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+
+    /// JWE enc algorithm REQUIRED for encrypting the ID Token issued to this
+    /// Client
+    pub id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+
+    /// JWE alg algorithm REQUIRED for encrypting `UserInfo` Responses
+    pub userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+
+    /// JWE enc algorithm REQUIRED for encrypting `UserInfo` Responses
+    pub userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+
     /// Requested authentication method for the token endpoint
     pub token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
 
@@ -83,6 +101,33 @@ pub struct Client {
     /// URI using the https scheme that a third party can use to initiate a
     /// login by the RP
     pub initiate_login_uri: Option<Url>,
+
+    /// Array of URLs supplied by the RP to which it MAY request that the
+    /// End-User's User Agent be redirected after a logout has been performed
+    pub post_logout_redirect_uris: Vec<Url>,
+
+    /// URL that will be called by the OP to notify the RP that a session has
+    /// ended, using the Back-Channel Logout protocol
+    pub backchannel_logout_uri: Option<Url>,
+
+    /// Whether the RP requires the `sid` claim in the logout tokens sent to
+    /// the `backchannel_logout_uri`
+    pub backchannel_logout_session_required: bool,
+
+    /// Whether the client must use the Pushed Authorization Request endpoint
+    /// to start authorization requests
+    pub require_pushed_authorization_requests: bool,
+
+    /// JWS alg algorithm that MUST be used for signing Request Objects sent
+    /// to the authorization endpoint
+    pub request_object_signing_alg: Option<JsonWebSignatureAlg>,
+
+    /// Whether the client must send its authorization requests as signed
+    /// Request Objects
+    pub require_signed_request_object: bool,
+
+    /// Whether the client is managed through the configuration file
+    pub is_static: bool,
 }
 
 #[derive(Debug, Error)]
@@ -97,6 +142,10 @@ pub enum InvalidRedirectUriError {
     NoneRegistered,
 }
 
+#[derive(Debug, Error)]
+#[error("post_logout_redirect_uri is not allowed for this client")]
+pub struct InvalidPostLogoutRedirectUriError;
+
 impl Client {
     /// Determine which redirect URI to use for the given request.
     ///
@@ -120,6 +169,23 @@ impl Client {
         }
     }
 
+    /// Check that the given post-logout redirect URI is registered for this
+    /// client.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the given URL is not registered
+    pub fn validate_post_logout_redirect_uri(
+        &self,
+        post_logout_redirect_uri: &Url,
+    ) -> Result<(), InvalidPostLogoutRedirectUriError> {
+        if uri_matches_one_of(post_logout_redirect_uri, &self.post_logout_redirect_uris) {
+            Ok(())
+        } else {
+            Err(InvalidPostLogoutRedirectUriError)
+        }
+    }
+
     /// Create a client metadata object for this client
     pub fn into_metadata(self) -> ClientMetadata {
         let (jwks, jwks_uri) = match self.jwks {
@@ -149,23 +215,30 @@ impl Client {
             software_version: None,
             sector_identifier_uri: None,
             subject_type: None,
-            id_token_encrypted_response_alg: None,
-            id_token_encrypted_response_enc: None,
-            userinfo_encrypted_response_alg: None,
-            userinfo_encrypted_response_enc: None,
-            request_object_signing_alg: None,
+            id_token_encrypted_response_alg: self.id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc: self.id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg: self.userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc: self.userinfo_encrypted_response_enc,
+            request_object_signing_alg: self.request_object_signing_alg,
             request_object_encryption_alg: None,
             request_object_encryption_enc: None,
             default_max_age: None,
             require_auth_time: None,
             default_acr_values: None,
             request_uris: None,
-            require_signed_request_object: None,
-            require_pushed_authorization_requests: None,
+            require_signed_request_object: self.require_signed_request_object.then_some(true),
+            require_pushed_authorization_requests: self
+                .require_pushed_authorization_requests
+                .then_some(true),
             introspection_signed_response_alg: None,
             introspection_encrypted_response_alg: None,
             introspection_encrypted_response_enc: None,
-            post_logout_redirect_uris: None,
+            post_logout_redirect_uris: (!self.post_logout_redirect_uris.is_empty())
+                .then_some(self.post_logout_redirect_uris),
+            backchannel_logout_uri: self.backchannel_logout_uri,
+            backchannel_logout_session_required: self
+                .backchannel_logout_session_required
+                .then_some(true),
         }
     }
 
@@ -195,7 +268,23 @@ impl Client {
                 token_endpoint_auth_signing_alg: None,
                 id_token_signed_response_alg: None,
                 userinfo_signed_response_alg: None,
+                id_token_encrypted_response_alg: None,
+                id_token_encrypted_response_enc: None,
+                userinfo_encrypted_response_alg: None,
+                userinfo_encrypted_response_enc: None,
                 jwks: None,
+                post_logout_redirect_uris: vec![Url::parse(
+                    "https://client1.example.com/logged-out",
+                )
+                .unwrap()],
+                backchannel_logout_uri: Some(
+                    Url::parse("https://client1.example.com/backchannel-logout").unwrap(),
+                ),
+                backchannel_logout_session_required: true,
+                require_pushed_authorization_requests: false,
+                request_object_signing_alg: None,
+                require_signed_request_object: false,
+                is_static: false,
             },
             // Another client without any URIs set
             Self {
@@ -215,7 +304,18 @@ impl Client {
                 token_endpoint_auth_signing_alg: None,
                 id_token_signed_response_alg: None,
                 userinfo_signed_response_alg: None,
+                id_token_encrypted_response_alg: None,
+                id_token_encrypted_response_enc: None,
+                userinfo_encrypted_response_alg: None,
+                userinfo_encrypted_response_enc: None,
                 jwks: None,
+                post_logout_redirect_uris: Vec::new(),
+                backchannel_logout_uri: None,
+                backchannel_logout_session_required: false,
+                require_pushed_authorization_requests: false,
+                request_object_signing_alg: None,
+                require_signed_request_object: false,
+                is_static: false,
             },
         ]
     }
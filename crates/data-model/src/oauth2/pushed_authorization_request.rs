@@ -0,0 +1,131 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use ulid::Ulid;
+use url::Url;
+
+use crate::InvalidTransitionError;
+
+/// The prefix of the `request_uri` given back to clients by the Pushed
+/// Authorization Request endpoint, as defined by RFC 9126
+const REQUEST_URI_PREFIX: &str = "urn:ietf:params:oauth:request_uri:";
+
+/// An authorization request pushed by a client to the Pushed Authorization
+/// Request endpoint, waiting to be used on the authorization endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PushedAuthorizationRequest {
+    pub id: Ulid,
+
+    /// The ID of the client which pushed the request
+    pub client_id: Ulid,
+
+    /// The parameters of the authorization request, as they were sent by the
+    /// client, without the client credentials
+    pub parameters: BTreeMap<String, String>,
+
+    pub created_at: DateTime<Utc>,
+
+    /// When the request stops being usable
+    pub expires_at: DateTime<Utc>,
+
+    /// When the request was used on the authorization endpoint. Requests can
+    /// only be used once.
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+impl PushedAuthorizationRequest {
+    /// The `request_uri` the client uses to refer to this request on the
+    /// authorization endpoint
+    #[must_use]
+    pub fn request_uri(&self) -> String {
+        format!("{REQUEST_URI_PREFIX}{}", self.id)
+    }
+
+    /// Extract the ID of a pushed authorization request from a `request_uri`
+    ///
+    /// Returns `None` if the URI was not issued by the Pushed Authorization
+    /// Request endpoint
+    #[must_use]
+    pub fn id_from_request_uri(request_uri: &Url) -> Option<Ulid> {
+        request_uri
+            .as_str()
+            .strip_prefix(REQUEST_URI_PREFIX)?
+            .parse()
+            .ok()
+    }
+
+    /// Whether this request can still be used on the authorization endpoint
+    #[must_use]
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.consumed_at.is_none() && now < self.expires_at
+    }
+
+    /// Mark the request as used on the authorization endpoint
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request was already consumed
+    pub fn consume(mut self, consumed_at: DateTime<Utc>) -> Result<Self, InvalidTransitionError> {
+        if self.consumed_at.is_some() {
+            return Err(InvalidTransitionError);
+        }
+
+        self.consumed_at = Some(consumed_at);
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_request_uri_roundtrip() {
+        let now = Utc.with_ymd_and_hms(2024, 9, 27, 12, 0, 0).unwrap();
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let request = PushedAuthorizationRequest {
+            id: Ulid::from_datetime_with_source(now.into(), &mut rng),
+            client_id: Ulid::from_datetime_with_source(now.into(), &mut rng),
+            parameters: BTreeMap::new(),
+            created_at: now,
+            expires_at: now + Duration::try_seconds(60).unwrap(),
+            consumed_at: None,
+        };
+
+        let request_uri: Url = request.request_uri().parse().unwrap();
+        assert_eq!(
+            PushedAuthorizationRequest::id_from_request_uri(&request_uri),
+            Some(request.id)
+        );
+
+        let other: Url = "https://example.com/request.jwt".parse().unwrap();
+        assert_eq!(
+            PushedAuthorizationRequest::id_from_request_uri(&other),
+            None
+        );
+
+        let invalid: Url = "urn:ietf:params:oauth:request_uri:not-an-id"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            PushedAuthorizationRequest::id_from_request_uri(&invalid),
+            None
+        );
+
+        assert!(request.is_valid(now));
+        assert!(!request.is_valid(now + Duration::try_seconds(60).unwrap()));
+
+        let consumed = request.consume(now).unwrap();
+        assert!(!consumed.is_valid(now));
+        assert!(consumed.consume(now).is_err());
+    }
+}
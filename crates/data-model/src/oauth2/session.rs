@@ -7,7 +7,7 @@
 use std::net::IpAddr;
 
 use chrono::{DateTime, Utc};
-use oauth2_types::scope::Scope;
+use oauth2_types::{requests::GrantType, scope::Scope};
 use serde::Serialize;
 use ulid::Ulid;
 
@@ -83,6 +83,13 @@ pub struct Session {
     pub user_agent: Option<UserAgent>,
     pub last_active_at: Option<DateTime<Utc>>,
     pub last_active_ip: Option<IpAddr>,
+
+    /// The grant type which started this session, if it was recorded
+    pub grant_type: Option<GrantType>,
+
+    /// The client which obtained this session on behalf of the session's
+    /// client, through a token exchange
+    pub acting_client_id: Option<Ulid>,
 }
 
 impl std::ops::Deref for Session {
@@ -4,7 +4,10 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
+use std::collections::BTreeSet;
+
 use chrono::Duration;
+use ulid::Ulid;
 use url::Url;
 
 /// Which Captcha service is being used
@@ -74,4 +77,12 @@ pub struct SiteConfig {
     /// Minimum password complexity, between 0 and 4.
     /// This is a score from zxcvbn.
     pub minimum_password_complexity: u8,
+
+    /// Whether upstream OAuth 2.0 providers can be managed through the admin
+    /// API.
+    pub upstream_oauth2_admin_api_allowed: bool,
+
+    /// IDs of the upstream OAuth 2.0 providers listed in the configuration
+    /// file, which can't be changed through the admin API.
+    pub upstream_oauth2_config_provider_ids: BTreeSet<Ulid>,
 }
@@ -55,6 +55,9 @@ pub struct AccessToken {
     pub access_token: String,
     pub created_at: DateTime<Utc>,
     pub expires_at: Option<DateTime<Utc>>,
+    /// The JWK SHA-256 thumbprint of the DPoP key this token is bound to, if
+    /// any
+    pub dpop_jkt: Option<String>,
 }
 
 impl AccessToken {
@@ -150,6 +153,9 @@ pub struct RefreshToken {
     pub session_id: Ulid,
     pub created_at: DateTime<Utc>,
     pub access_token_id: Option<Ulid>,
+    /// The JWK SHA-256 thumbprint of the DPoP key this token is bound to, if
+    /// any
+    pub dpop_jkt: Option<String>,
 }
 
 impl std::ops::Deref for RefreshToken {
@@ -15,7 +15,9 @@ pub use self::{
         DiscoveryMode as UpstreamOAuthProviderDiscoveryMode,
         ImportAction as UpstreamOAuthProviderImportAction,
         ImportPreference as UpstreamOAuthProviderImportPreference,
-        PkceMode as UpstreamOAuthProviderPkceMode,
+        PkceMode as UpstreamOAuthProviderPkceMode, ProviderKind as UpstreamOAuthProviderKind,
+        SamlBinding as UpstreamOAuthProviderSamlBinding,
+        SamlSettings as UpstreamOAuthProviderSamlSettings,
         SetEmailVerification as UpsreamOAuthProviderSetEmailVerification,
         SubjectPreference as UpstreamOAuthProviderSubjectPreference, UpstreamOAuthProvider,
     },
@@ -12,6 +12,50 @@ use thiserror::Error;
 use ulid::Ulid;
 use url::Url;
 
+/// The kind of an upstream identity provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// An OpenID Connect provider
+    #[default]
+    Oidc,
+
+    /// A SAML 2.0 identity provider
+    Saml,
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("Invalid provider kind {0:?}")]
+pub struct InvalidProviderKindError(String);
+
+impl std::str::FromStr for ProviderKind {
+    type Err = InvalidProviderKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "oidc" => Ok(Self::Oidc),
+            "saml" => Ok(Self::Saml),
+            s => Err(InvalidProviderKindError(s.to_owned())),
+        }
+    }
+}
+
+impl ProviderKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Oidc => "oidc",
+            Self::Saml => "saml",
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum DiscoveryMode {
@@ -119,6 +163,7 @@ impl std::fmt::Display for PkceMode {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct UpstreamOAuthProvider {
     pub id: Ulid,
+    pub kind: ProviderKind,
     pub issuer: String,
     pub human_name: Option<String>,
     pub brand_name: Option<String>,
@@ -127,15 +172,20 @@ pub struct UpstreamOAuthProvider {
     pub jwks_uri_override: Option<Url>,
     pub authorization_endpoint_override: Option<Url>,
     pub token_endpoint_override: Option<Url>,
-    pub scope: Scope,
-    pub client_id: String,
+    /// The scope to request, only set for OpenID Connect providers
+    pub scope: Option<Scope>,
+    /// The OAuth 2.0 client ID, only set for OpenID Connect providers
+    pub client_id: Option<String>,
     pub encrypted_client_secret: Option<String>,
     pub token_endpoint_signing_alg: Option<JsonWebSignatureAlg>,
-    pub token_endpoint_auth_method: OAuthClientAuthenticationMethod,
+    /// The method used to authenticate to the token endpoint, only set for
+    /// OpenID Connect providers
+    pub token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
     pub created_at: DateTime<Utc>,
     pub disabled_at: Option<DateTime<Utc>>,
     pub claims_imports: ClaimsImports,
     pub additional_authorization_parameters: Vec<(String, String)>,
+    pub saml: Option<SamlSettings>,
 }
 
 impl PartialOrd for UpstreamOAuthProvider {
@@ -156,6 +206,57 @@ impl UpstreamOAuthProvider {
     pub const fn enabled(&self) -> bool {
         self.disabled_at.is_none()
     }
+
+    /// Returns `true` if the provider is a SAML 2.0 identity provider
+    #[must_use]
+    pub fn is_saml(&self) -> bool {
+        self.kind == ProviderKind::Saml
+    }
+
+    /// Returns `true` if the provider is an OpenID Connect provider
+    #[must_use]
+    pub fn is_oidc(&self) -> bool {
+        self.kind == ProviderKind::Oidc
+    }
+}
+
+/// The binding used to send authentication requests to a SAML 2.0 identity
+/// provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SamlBinding {
+    /// Redirect the user to the identity provider, with the request in the
+    /// query parameters
+    #[default]
+    Redirect,
+
+    /// Make the user's browser POST the request to the identity provider
+    Post,
+}
+
+/// The settings specific to SAML 2.0 identity providers
+///
+/// For those providers, the `issuer` of the [`UpstreamOAuthProvider`] is the
+/// entity ID of the identity provider.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SamlSettings {
+    /// Our own entity ID, sent as the issuer of the authentication requests
+    pub sp_entity_id: String,
+
+    /// The URL of the single sign-on service of the identity provider
+    pub idp_sso_url: Url,
+
+    /// The certificates of the identity provider, used to verify the
+    /// signatures of its responses
+    pub idp_certificates: Vec<String>,
+
+    /// The binding used to send authentication requests
+    #[serde(default)]
+    pub sso_binding: SamlBinding,
+
+    /// The format of the subject identifier to ask for
+    #[serde(default)]
+    pub name_id_format: Option<String>,
 }
 
 /// Whether to set the email as verified when importing it from the upstream
@@ -19,12 +19,14 @@ pub enum UpstreamOAuthAuthorizationSessionState {
         completed_at: DateTime<Utc>,
         link_id: Ulid,
         id_token: Option<String>,
+        saml_attributes: Option<serde_json::Value>,
     },
     Consumed {
         completed_at: DateTime<Utc>,
         consumed_at: DateTime<Utc>,
         link_id: Ulid,
         id_token: Option<String>,
+        saml_attributes: Option<serde_json::Value>,
     },
 }
 
@@ -48,6 +50,33 @@ impl UpstreamOAuthAuthorizationSessionState {
                 completed_at,
                 link_id: link.id,
                 id_token,
+                saml_attributes: None,
+            }),
+            Self::Completed { .. } | Self::Consumed { .. } => Err(InvalidTransitionError),
+        }
+    }
+
+    /// Mark the upstream authorization session as completed, with the
+    /// attributes of the SAML assertion sent by the identity provider.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the upstream authorization session state is not
+    /// [`Pending`].
+    ///
+    /// [`Pending`]: UpstreamOAuthAuthorizationSessionState::Pending
+    pub fn complete_with_saml_attributes(
+        self,
+        completed_at: DateTime<Utc>,
+        link: &UpstreamOAuthLink,
+        saml_attributes: serde_json::Value,
+    ) -> Result<Self, InvalidTransitionError> {
+        match self {
+            Self::Pending => Ok(Self::Completed {
+                completed_at,
+                link_id: link.id,
+                id_token: None,
+                saml_attributes: Some(saml_attributes),
             }),
             Self::Completed { .. } | Self::Consumed { .. } => Err(InvalidTransitionError),
         }
@@ -67,11 +96,13 @@ impl UpstreamOAuthAuthorizationSessionState {
                 completed_at,
                 link_id,
                 id_token,
+                saml_attributes,
             } => Ok(Self::Consumed {
                 completed_at,
                 link_id,
                 consumed_at,
                 id_token,
+                saml_attributes,
             }),
             Self::Pending | Self::Consumed { .. } => Err(InvalidTransitionError),
         }
@@ -124,6 +155,26 @@ impl UpstreamOAuthAuthorizationSessionState {
         }
     }
 
+    /// Get the attributes of the SAML assertion for the upstream authorization
+    /// session.
+    ///
+    /// Returns `None` if the upstream authorization session state is
+    /// [`Pending`], or if the session was not completed through SAML.
+    ///
+    /// [`Pending`]: UpstreamOAuthAuthorizationSessionState::Pending
+    #[must_use]
+    pub fn saml_attributes(&self) -> Option<&serde_json::Value> {
+        match self {
+            Self::Pending => None,
+            Self::Completed {
+                saml_attributes, ..
+            }
+            | Self::Consumed {
+                saml_attributes, ..
+            } => saml_attributes.as_ref(),
+        }
+    }
+
     /// Get the time at which the upstream OAuth 2.0 authorization session was
     /// consumed.
     ///
@@ -174,7 +225,11 @@ pub struct UpstreamOAuthAuthorizationSession {
     pub provider_id: Ulid,
     pub state_str: String,
     pub code_challenge_verifier: Option<String>,
-    pub nonce: String,
+    /// The `nonce` sent to an OpenID Connect provider
+    pub nonce: Option<String>,
+    /// The ID of the authentication request sent to a SAML 2.0 identity
+    /// provider
+    pub saml_request_id: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -187,6 +242,13 @@ impl std::ops::Deref for UpstreamOAuthAuthorizationSession {
 }
 
 impl UpstreamOAuthAuthorizationSession {
+    /// Returns `true` if the session was started with a SAML 2.0 identity
+    /// provider
+    #[must_use]
+    pub fn is_saml(&self) -> bool {
+        self.saml_request_id.is_some()
+    }
+
     /// Mark the upstream OAuth 2.0 authorization session as completed. Returns
     /// the updated session.
     ///
@@ -206,6 +268,28 @@ impl UpstreamOAuthAuthorizationSession {
         Ok(self)
     }
 
+    /// Mark the upstream authorization session as completed, with the
+    /// attributes of the SAML assertion sent by the identity provider. Returns
+    /// the updated session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the upstream authorization session state is not
+    /// [`Pending`].
+    ///
+    /// [`Pending`]: UpstreamOAuthAuthorizationSessionState::Pending
+    pub fn complete_with_saml_attributes(
+        mut self,
+        completed_at: DateTime<Utc>,
+        link: &UpstreamOAuthLink,
+        saml_attributes: serde_json::Value,
+    ) -> Result<Self, InvalidTransitionError> {
+        self.state =
+            self.state
+                .complete_with_saml_attributes(completed_at, link, saml_attributes)?;
+        Ok(self)
+    }
+
     /// Mark the upstream OAuth 2.0 authorization session as consumed. Returns
     /// the updated session.
     ///
@@ -64,13 +64,133 @@ pub struct Authentication {
     pub authentication_method: AuthenticationMethod,
 }
 
+/// The `acr` value advertised in ID tokens when the user authenticated with
+/// more than one factor
+pub const ACR_MULTI_FACTOR: &str = "http://schemas.openid.net/pape/policies/2007/06/multi-factor";
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum AuthenticationMethod {
-    Password { user_password_id: Ulid },
-    UpstreamOAuth2 { upstream_oauth2_session_id: Ulid },
+    Password {
+        user_password_id: Ulid,
+    },
+    UpstreamOAuth2 {
+        upstream_oauth2_session_id: Ulid,
+    },
+    Totp {
+        user_totp_secret_id: Ulid,
+    },
+    TotpRecoveryCode {
+        user_totp_recovery_code_id: Ulid,
+    },
+    Passkey {
+        user_passkey_id: Ulid,
+        /// Whether the passkey was the second factor after a password
+        after_password: bool,
+    },
+    Ldap {
+        user_ldap_link_id: Ulid,
+    },
     Unknown,
 }
 
+impl AuthenticationMethod {
+    /// Returns `true` if this authentication completed a second factor after
+    /// the password.
+    ///
+    /// TOTP authentications are only ever recorded after a successful password
+    /// check, so they imply both factors. Passkeys always require user
+    /// verification on the authenticator, which makes them multi-factor on
+    /// their own.
+    #[must_use]
+    pub fn is_multi_factor(&self) -> bool {
+        matches!(
+            self,
+            Self::Totp { .. } | Self::TotpRecoveryCode { .. } | Self::Passkey { .. }
+        )
+    }
+
+    /// The Authentication Method Reference values, as defined by RFC 8176,
+    /// which describe this authentication
+    #[must_use]
+    pub fn amr(&self) -> &'static [&'static str] {
+        match self {
+            Self::Password { .. } | Self::Ldap { .. } => &["pwd"],
+            Self::Totp { .. } => &["pwd", "otp", "mfa"],
+            Self::TotpRecoveryCode { .. } => &["pwd", "mfa"],
+            Self::Passkey { after_password, .. } => {
+                if *after_password {
+                    &["pwd", "hwk", "mfa"]
+                } else {
+                    &["hwk", "mfa"]
+                }
+            }
+            Self::UpstreamOAuth2 { .. } | Self::Unknown => &[],
+        }
+    }
+}
+
+/// A TOTP secret enrolled by a user as a second authentication factor
+///
+/// The secret only becomes usable once the user confirmed the enrollment by
+/// entering a valid code, which sets `confirmed_at`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserTotpSecret {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    pub encrypted_secret: String,
+    /// The last time step for which a code was accepted, to prevent the same
+    /// code from being used twice
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+impl UserTotpSecret {
+    /// Returns `true` if the enrollment of this secret was confirmed
+    #[must_use]
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+}
+
+/// A single-use recovery code, which can be used instead of a TOTP code if the
+/// user lost access to their authenticator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserTotpRecoveryCode {
+    pub id: Ulid,
+    pub user_totp_secret_id: Ulid,
+    pub hashed_code: String,
+    pub created_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+/// A `WebAuthn` credential, also known as a passkey, registered by a user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserPasskey {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    /// The ID of the credential on the authenticator, encoded in base64url
+    pub credential_id: String,
+    /// A name chosen by the user to recognize the credential
+    pub name: String,
+    /// The serialized credential, with its public key and signature counter
+    pub credential: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A link between a user and an entry in an LDAP directory, created when the
+/// user first logs in with their directory credentials
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserLdapLink {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    /// The stable identifier of the directory entry, taken from the attribute
+    /// configured as the subject
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+}
+
 /// A session to recover a user if they have lost their credentials
 ///
 /// For each session intiated, there may be multiple [`UserRecoveryTicket`]s
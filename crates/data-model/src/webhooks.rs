@@ -0,0 +1,207 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use ulid::Ulid;
+
+use crate::InvalidTransitionError;
+
+/// The kind of user lifecycle event which can be sent to webhook targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WebhookEventKind {
+    /// A new user registered
+    #[serde(rename = "user.registered")]
+    UserRegistered,
+
+    /// A user verified one of their email addresses
+    #[serde(rename = "user.email_verified")]
+    UserEmailVerified,
+
+    /// A user was locked
+    #[serde(rename = "user.locked")]
+    UserLocked,
+
+    /// A user was unlocked
+    #[serde(rename = "user.unlocked")]
+    UserUnlocked,
+
+    /// A user was deactivated
+    #[serde(rename = "user.deactivated")]
+    UserDeactivated,
+
+    /// A user was reactivated
+    #[serde(rename = "user.reactivated")]
+    UserReactivated,
+
+    /// A user was linked to an upstream OAuth 2.0 provider account
+    #[serde(rename = "upstream_oauth_link.created")]
+    UpstreamOAuthLinkCreated,
+}
+
+impl WebhookEventKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::UserRegistered => "user.registered",
+            Self::UserEmailVerified => "user.email_verified",
+            Self::UserLocked => "user.locked",
+            Self::UserUnlocked => "user.unlocked",
+            Self::UserDeactivated => "user.deactivated",
+            Self::UserReactivated => "user.reactivated",
+            Self::UpstreamOAuthLinkCreated => "upstream_oauth_link.created",
+        }
+    }
+}
+
+impl std::fmt::Display for WebhookEventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("Invalid webhook event kind {0:?}")]
+pub struct InvalidWebhookEventKindError(String);
+
+impl std::str::FromStr for WebhookEventKind {
+    type Err = InvalidWebhookEventKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user.registered" => Ok(Self::UserRegistered),
+            "user.email_verified" => Ok(Self::UserEmailVerified),
+            "user.locked" => Ok(Self::UserLocked),
+            "user.unlocked" => Ok(Self::UserUnlocked),
+            "user.deactivated" => Ok(Self::UserDeactivated),
+            "user.reactivated" => Ok(Self::UserReactivated),
+            "upstream_oauth_link.created" => Ok(Self::UpstreamOAuthLinkCreated),
+            s => Err(InvalidWebhookEventKindError(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub enum WebhookDeliveryState {
+    /// The delivery is waiting for its first attempt, or for a retry
+    #[default]
+    Pending,
+
+    /// The target acknowledged the delivery
+    Delivered { delivered_at: DateTime<Utc> },
+
+    /// The delivery ran out of attempts, and won't be retried
+    Failed { failed_at: DateTime<Utc> },
+}
+
+impl WebhookDeliveryState {
+    /// Returns `true` if the delivery state is [`Pending`].
+    ///
+    /// [`Pending`]: WebhookDeliveryState::Pending
+    #[must_use]
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending)
+    }
+
+    /// Returns `true` if the delivery state is [`Delivered`].
+    ///
+    /// [`Delivered`]: WebhookDeliveryState::Delivered
+    #[must_use]
+    pub fn is_delivered(&self) -> bool {
+        matches!(self, Self::Delivered { .. })
+    }
+
+    /// Returns `true` if the delivery state is [`Failed`].
+    ///
+    /// [`Failed`]: WebhookDeliveryState::Failed
+    #[must_use]
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed { .. })
+    }
+
+    /// Transitions the delivery state to [`Delivered`].
+    ///
+    /// # Parameters
+    ///
+    /// * `delivered_at` - The time at which the target acknowledged the
+    ///   delivery
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delivery is not [`Pending`].
+    ///
+    /// [`Delivered`]: WebhookDeliveryState::Delivered
+    /// [`Pending`]: WebhookDeliveryState::Pending
+    pub fn deliver(self, delivered_at: DateTime<Utc>) -> Result<Self, InvalidTransitionError> {
+        match self {
+            Self::Pending => Ok(Self::Delivered { delivered_at }),
+            Self::Delivered { .. } | Self::Failed { .. } => Err(InvalidTransitionError),
+        }
+    }
+
+    /// Transitions the delivery state to [`Failed`].
+    ///
+    /// # Parameters
+    ///
+    /// * `failed_at` - The time at which the delivery was given up
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delivery is not [`Pending`].
+    ///
+    /// [`Failed`]: WebhookDeliveryState::Failed
+    /// [`Pending`]: WebhookDeliveryState::Pending
+    pub fn fail(self, failed_at: DateTime<Utc>) -> Result<Self, InvalidTransitionError> {
+        match self {
+            Self::Pending => Ok(Self::Failed { failed_at }),
+            Self::Delivered { .. } | Self::Failed { .. } => Err(InvalidTransitionError),
+        }
+    }
+
+    #[must_use]
+    pub fn delivered_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Delivered { delivered_at } => Some(*delivered_at),
+            Self::Pending | Self::Failed { .. } => None,
+        }
+    }
+
+    #[must_use]
+    pub fn failed_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Failed { failed_at } => Some(*failed_at),
+            Self::Pending | Self::Delivered { .. } => None,
+        }
+    }
+}
+
+/// A user lifecycle event, to be delivered to a single webhook target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WebhookDelivery {
+    pub id: Ulid,
+    pub state: WebhookDeliveryState,
+    pub created_at: DateTime<Utc>,
+
+    /// The name of the target, as set in the configuration
+    pub target: String,
+    pub event: WebhookEventKind,
+    pub user_id: Option<Ulid>,
+
+    /// The event-specific data sent to the target
+    pub data: serde_json::Value,
+
+    pub attempts: u32,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl std::ops::Deref for WebhookDelivery {
+    type Target = WebhookDeliveryState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
@@ -8,15 +8,15 @@ use std::convert::Infallible;
 
 use aide::OperationIo;
 use axum::{
-    extract::FromRequestParts,
+    extract::{FromRequestParts, OriginalUri},
     response::{IntoResponse, Response},
     Json,
 };
 use axum_extra::TypedHeader;
 use headers::{authorization::Bearer, Authorization};
-use hyper::StatusCode;
-use mas_data_model::{Session, User};
-use mas_storage::{BoxClock, BoxRepository, RepositoryError};
+use hyper::{header::USER_AGENT, Method, StatusCode};
+use mas_data_model::{AuditContext, AuditEventKind, Session, User};
+use mas_storage::{BoxClock, BoxRepository, BoxRng, RepositoryError};
 use ulid::Ulid;
 
 use super::response::ErrorResponse;
@@ -48,6 +48,10 @@ pub enum Rejection {
     #[error("Access token expired")]
     TokenExpired,
 
+    /// The access token is bound to a DPoP key, which this API doesn't support
+    #[error("DPoP-bound access tokens are not supported")]
+    DPoPBoundToken,
+
     /// The session associated with the access token was revoked
     #[error("Access token revoked")]
     SessionRevoked,
@@ -70,13 +74,14 @@ pub enum Rejection {
 }
 
 impl Rejection {
-    fn status_code(&self) -> StatusCode {
+    pub(crate) fn status_code(&self) -> StatusCode {
         match self {
             Self::InvalidAuthorizationHeader | Self::MissingAuthorizationHeader => {
                 StatusCode::BAD_REQUEST
             }
             Self::UnknownAccessToken
             | Self::TokenExpired
+            | Self::DPoPBoundToken
             | Self::SessionRevoked
             | Self::UserLocked
             | Self::MissingScope => StatusCode::UNAUTHORIZED,
@@ -97,6 +102,13 @@ impl IntoResponse for Rejection {
 ///
 /// Because we need to load the database repository and the clock, we keep them
 /// in the context to avoid creating two instances for each request.
+///
+/// Write operations are recorded in the audit log, in a separate transaction
+/// committed before the handler runs. It can't go through `repo`: handlers
+/// only save it when the operation succeeds, so failed and rejected attempts,
+/// which auditors care about the most, would be rolled back with it. If the
+/// audit event can't be written, the call is rejected before the handler runs,
+/// so that no write operation happens without a trace.
 #[non_exhaustive]
 #[derive(OperationIo)]
 #[aide(input)]
@@ -105,6 +117,7 @@ pub struct CallContext {
     pub clock: BoxClock,
     pub user: Option<User>,
     pub session: Session,
+    pub audit: AuditContext,
 }
 
 #[async_trait::async_trait]
@@ -114,6 +127,7 @@ where
     BoundActivityTracker: FromRequestParts<S, Rejection = Infallible>,
     BoxRepository: FromRequestParts<S>,
     BoxClock: FromRequestParts<S, Rejection = Infallible>,
+    BoxRng: FromRequestParts<S, Rejection = Infallible>,
     <BoxRepository as FromRequestParts<S>>::Rejection:
         Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
 {
@@ -135,6 +149,12 @@ where
             Err(e) => match e {},
         };
 
+        let rng = BoxRng::from_request_parts(parts, state).await;
+        let mut rng = match rng {
+            Ok(r) => r,
+            Err(e) => match e {},
+        };
+
         // Load the database repository
         let mut repo = BoxRepository::from_request_parts(parts, state)
             .await
@@ -202,17 +222,64 @@ where
             return Err(Rejection::TokenExpired);
         }
 
+        // This API only accepts bearer tokens, so tokens bound to a DPoP key can't be
+        // used here
+        if token.dpop_jkt.is_some() {
+            return Err(Rejection::DPoPBoundToken);
+        }
+
         // For now, we only check that the session has the admin scope
         // Later we might want to check other route-specific scopes
         if !session.scope.contains("urn:mas:admin") {
             return Err(Rejection::MissingScope);
         }
 
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned);
+        let audit = AuditContext::admin_token(&session)
+            .with_ip_address(activity_tracker.ip())
+            .with_user_agent(user_agent);
+
+        // Record write operations in the audit log. This uses its own transaction,
+        // committed right away, so that the event is kept even if the handler
+        // fails and drops `repo` without saving it
+        if !matches!(parts.method, Method::GET | Method::HEAD | Method::OPTIONS) {
+            // The router is nested, so we need the original URI to get the full path
+            let path = parts
+                .extensions
+                .get::<OriginalUri>()
+                .map_or(parts.uri.path(), |uri| uri.path())
+                .to_owned();
+            let mut audit_repo = BoxRepository::from_request_parts(parts, state)
+                .await
+                .map_err(Into::into)
+                .map_err(Rejection::RepositorySetup)?;
+            audit_repo
+                .audit_event()
+                .add(
+                    &mut rng,
+                    &clock,
+                    &audit,
+                    AuditEventKind::AdminApiCall,
+                    None,
+                    serde_json::json!({
+                        "method": parts.method.as_str(),
+                        "path": path,
+                    }),
+                )
+                .await?;
+            audit_repo.save().await?;
+        }
+
         Ok(Self {
             repo,
             clock,
             user,
             session,
+            audit,
         })
     }
 }
@@ -17,7 +17,9 @@ use axum::{
 use hyper::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
 use indexmap::IndexMap;
 use mas_axum_utils::FancyError;
+use mas_data_model::SiteConfig;
 use mas_http::CorsLayerExt;
+use mas_keystore::Encrypter;
 use mas_matrix::BoxHomeserverConnection;
 use mas_router::{
     ApiDoc, ApiDocCallback, OAuth2AuthorizationEndpoint, OAuth2TokenEndpoint, Route, SimpleRoute,
@@ -27,7 +29,7 @@ use mas_storage::BoxRng;
 use mas_templates::{ApiDocContext, Templates};
 use tower_http::cors::{Any, CorsLayer};
 
-mod call_context;
+pub(crate) mod call_context;
 mod model;
 mod params;
 mod response;
@@ -42,6 +44,8 @@ where
     S: Clone + Send + Sync + 'static,
     BoxHomeserverConnection: FromRef<S>,
     PasswordManager: FromRef<S>,
+    SiteConfig: FromRef<S>,
+    Encrypter: FromRef<S>,
     BoxRng: FromRequestParts<S>,
     CallContext: FromRequestParts<S>,
     Templates: FromRef<S>,
@@ -56,16 +60,71 @@ where
         .nest("/api/admin/v1", self::v1::router())
         .finish_api_with(&mut api, |t| {
             t.title("Matrix Authentication Service admin API")
+                .tag(Tag {
+                    name: "audit-event".to_owned(),
+                    description: Some(
+                        "Browse the audit log of security-relevant events".to_owned(),
+                    ),
+                    ..Tag::default()
+                })
+                .tag(Tag {
+                    name: "compat-session".to_owned(),
+                    description: Some(
+                        "Manage compatibility sessions from legacy clients".to_owned(),
+                    ),
+                    ..Tag::default()
+                })
+                .tag(Tag {
+                    name: "job".to_owned(),
+                    description: Some("Inspect and manage the background job queue".to_owned()),
+                    ..Tag::default()
+                })
+                .tag(Tag {
+                    name: "oauth2-client".to_owned(),
+                    description: Some("Manage OAuth 2.0 clients".to_owned()),
+                    ..Tag::default()
+                })
                 .tag(Tag {
                     name: "oauth2-session".to_owned(),
                     description: Some("Manage OAuth2 sessions".to_owned()),
                     ..Tag::default()
                 })
+                .tag(Tag {
+                    name: "upstream-oauth-link".to_owned(),
+                    description: Some(
+                        "Manage links between local users and upstream OAuth 2.0 providers"
+                            .to_owned(),
+                    ),
+                    ..Tag::default()
+                })
+                .tag(Tag {
+                    name: "upstream-oauth-provider".to_owned(),
+                    description: Some("Manage upstream OAuth 2.0 providers".to_owned()),
+                    ..Tag::default()
+                })
                 .tag(Tag {
                     name: "user".to_owned(),
                     description: Some("Manage users".to_owned()),
                     ..Tag::default()
                 })
+                .tag(Tag {
+                    name: "user-email".to_owned(),
+                    description: Some("Manage emails associated with users".to_owned()),
+                    ..Tag::default()
+                })
+                .tag(Tag {
+                    name: "user-session".to_owned(),
+                    description: Some("Manage browser sessions of users".to_owned()),
+                    ..Tag::default()
+                })
+                .tag(Tag {
+                    name: "webhook-delivery".to_owned(),
+                    description: Some(
+                        "Monitor the delivery of user lifecycle events to webhook targets"
+                            .to_owned(),
+                    ),
+                    ..Tag::default()
+                })
                 .security_scheme(
                     "oauth2",
                     SecurityScheme::OAuth2 {
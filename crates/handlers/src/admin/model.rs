@@ -4,12 +4,15 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
-use std::net::IpAddr;
+use std::{collections::BTreeMap, net::IpAddr};
 
 use chrono::{DateTime, Utc};
+use mas_data_model::JwksOrJwksUri;
+use mas_jose::jwk::PublicJsonWebKeySet;
 use schemars::JsonSchema;
 use serde::Serialize;
 use ulid::Ulid;
+use url::Url;
 
 /// A resource, with a type and an ID
 pub trait Resource {
@@ -134,6 +137,9 @@ pub struct OAuth2Session {
 
     /// The last IP address used by the session
     last_active_ip: Option<IpAddr>,
+
+    /// The grant type which started this session, if it was recorded
+    grant_type: Option<String>,
 }
 
 impl From<mas_data_model::Session> for OAuth2Session {
@@ -149,6 +155,7 @@ impl From<mas_data_model::Session> for OAuth2Session {
             user_agent: session.user_agent.map(|ua| ua.raw),
             last_active_at: session.last_active_at,
             last_active_ip: session.last_active_ip,
+            grant_type: session.grant_type.map(|grant_type| grant_type.to_string()),
         }
     }
 }
@@ -168,6 +175,7 @@ impl OAuth2Session {
                 user_agent: Some("Mozilla/5.0".to_owned()),
                 last_active_at: Some(DateTime::default()),
                 last_active_ip: Some("127.0.0.1".parse().unwrap()),
+                grant_type: Some("authorization_code".to_owned()),
             },
             Self {
                 id: Ulid::from_bytes([0x02; 16]),
@@ -180,6 +188,7 @@ impl OAuth2Session {
                 user_agent: None,
                 last_active_at: None,
                 last_active_ip: None,
+                grant_type: Some("client_credentials".to_owned()),
             },
             Self {
                 id: Ulid::from_bytes([0x03; 16]),
@@ -192,6 +201,7 @@ impl OAuth2Session {
                 user_agent: Some("Mozilla/5.0".to_owned()),
                 last_active_at: Some(DateTime::default()),
                 last_active_ip: Some("127.0.0.1".parse().unwrap()),
+                grant_type: None,
             },
         ]
     }
@@ -205,3 +215,1013 @@ impl Resource for OAuth2Session {
         self.id
     }
 }
+
+/// An upstream OAuth 2.0 provider
+#[derive(Serialize, JsonSchema)]
+pub struct UpstreamOAuthProvider {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// The kind of provider, either `oidc` or `saml`
+    kind: String,
+
+    /// The OIDC issuer of the provider
+    issuer: String,
+
+    /// A human-readable name for the provider
+    human_name: Option<String>,
+
+    /// A brand identifier, e.g. "apple" or "google"
+    brand_name: Option<String>,
+
+    /// The client ID used when authenticating to the provider. Only set for
+    /// OpenID Connect providers
+    client_id: Option<String>,
+
+    /// Whether a client secret is set for this provider
+    has_client_secret: bool,
+
+    /// The method used to authenticate to the provider's token endpoint. Only
+    /// set for OpenID Connect providers
+    token_endpoint_auth_method: Option<String>,
+
+    /// The JWS algorithm used when authenticating to the provider with the
+    /// `client_secret_jwt` or `private_key_jwt` methods
+    token_endpoint_auth_signing_alg: Option<String>,
+
+    /// The scope requested during the authorization flow. Only set for OpenID
+    /// Connect providers
+    scope: Option<String>,
+
+    /// How the provider metadata is discovered
+    discovery_mode: String,
+
+    /// Whether PKCE is used during the authorization flow
+    pkce_mode: String,
+
+    /// The URL used as the authorization endpoint, overriding the discovered
+    /// one
+    authorization_endpoint_override: Option<Url>,
+
+    /// The URL used as the token endpoint, overriding the discovered one
+    token_endpoint_override: Option<Url>,
+
+    /// The URL used to fetch the provider's public keys, overriding the
+    /// discovered one
+    jwks_uri_override: Option<Url>,
+
+    /// Additional parameters included in the authorization request
+    additional_authorization_parameters: BTreeMap<String, String>,
+
+    /// When the provider was created
+    created_at: DateTime<Utc>,
+
+    /// When the provider was disabled. If null, the provider is enabled.
+    disabled_at: Option<DateTime<Utc>>,
+}
+
+impl From<mas_data_model::UpstreamOAuthProvider> for UpstreamOAuthProvider {
+    fn from(provider: mas_data_model::UpstreamOAuthProvider) -> Self {
+        Self {
+            id: provider.id,
+            kind: provider.kind.to_string(),
+            issuer: provider.issuer,
+            human_name: provider.human_name,
+            brand_name: provider.brand_name,
+            client_id: provider.client_id,
+            has_client_secret: provider.encrypted_client_secret.is_some(),
+            token_endpoint_auth_method: provider
+                .token_endpoint_auth_method
+                .map(|method| method.to_string()),
+            token_endpoint_auth_signing_alg: provider
+                .token_endpoint_signing_alg
+                .map(|alg| alg.to_string()),
+            scope: provider.scope.map(|scope| scope.to_string()),
+            discovery_mode: provider.discovery_mode.to_string(),
+            pkce_mode: provider.pkce_mode.to_string(),
+            authorization_endpoint_override: provider.authorization_endpoint_override,
+            token_endpoint_override: provider.token_endpoint_override,
+            jwks_uri_override: provider.jwks_uri_override,
+            additional_authorization_parameters: provider
+                .additional_authorization_parameters
+                .into_iter()
+                .collect(),
+            created_at: provider.created_at,
+            disabled_at: provider.disabled_at,
+        }
+    }
+}
+
+impl UpstreamOAuthProvider {
+    /// Samples of upstream OAuth 2.0 providers
+    pub fn samples() -> [Self; 2] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                kind: "oidc".to_owned(),
+                issuer: "https://accounts.google.com".to_owned(),
+                human_name: Some("Google".to_owned()),
+                brand_name: Some("google".to_owned()),
+                client_id: Some("mas".to_owned()),
+                has_client_secret: true,
+                token_endpoint_auth_method: Some("client_secret_basic".to_owned()),
+                token_endpoint_auth_signing_alg: None,
+                scope: Some("openid profile email".to_owned()),
+                discovery_mode: "oidc".to_owned(),
+                pkce_mode: "auto".to_owned(),
+                authorization_endpoint_override: None,
+                token_endpoint_override: None,
+                jwks_uri_override: None,
+                additional_authorization_parameters: BTreeMap::new(),
+                created_at: DateTime::default(),
+                disabled_at: None,
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                kind: "oidc".to_owned(),
+                issuer: "https://idp.example.com".to_owned(),
+                human_name: Some("Example".to_owned()),
+                brand_name: None,
+                client_id: Some("mas".to_owned()),
+                has_client_secret: false,
+                token_endpoint_auth_method: Some("private_key_jwt".to_owned()),
+                token_endpoint_auth_signing_alg: Some("RS256".to_owned()),
+                scope: Some("openid".to_owned()),
+                discovery_mode: "disabled".to_owned(),
+                pkce_mode: "s256".to_owned(),
+                authorization_endpoint_override: Some(
+                    "https://idp.example.com/authorize".parse().unwrap(),
+                ),
+                token_endpoint_override: Some("https://idp.example.com/token".parse().unwrap()),
+                jwks_uri_override: Some("https://idp.example.com/jwks".parse().unwrap()),
+                additional_authorization_parameters: BTreeMap::from([(
+                    "prompt".to_owned(),
+                    "login".to_owned(),
+                )]),
+                created_at: DateTime::default(),
+                disabled_at: Some(DateTime::default()),
+            },
+        ]
+    }
+}
+
+impl Resource for UpstreamOAuthProvider {
+    const KIND: &'static str = "upstream-oauth-provider";
+    const PATH: &'static str = "/api/admin/v1/upstream-oauth-providers";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
+
+/// A compatibility session for legacy clients
+#[derive(Serialize, JsonSchema)]
+pub struct CompatSession {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// The ID of the user that owns this session
+    #[schemars(with = "super::schema::Ulid")]
+    user_id: Ulid,
+
+    /// The Matrix device ID of this session
+    device_id: String,
+
+    /// The ID of the user session that started this session, if any
+    #[schemars(with = "Option<super::schema::Ulid>")]
+    user_session_id: Option<Ulid>,
+
+    /// The redirect URI used to login in the client, if it was an SSO login
+    redirect_uri: Option<Url>,
+
+    /// The time this session was created
+    created_at: DateTime<Utc>,
+
+    /// The user agent string that started this session, if any
+    user_agent: Option<String>,
+
+    /// The time this session was last active
+    last_active_at: Option<DateTime<Utc>>,
+
+    /// The last IP address recorded for this session
+    last_active_ip: Option<IpAddr>,
+
+    /// The time this session was finished
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl
+    From<(
+        mas_data_model::CompatSession,
+        Option<mas_data_model::CompatSsoLogin>,
+    )> for CompatSession
+{
+    fn from(
+        (session, sso_login): (
+            mas_data_model::CompatSession,
+            Option<mas_data_model::CompatSsoLogin>,
+        ),
+    ) -> Self {
+        let finished_at = session.finished_at();
+        Self {
+            id: session.id,
+            user_id: session.user_id,
+            device_id: session.device.to_string(),
+            user_session_id: session.user_session_id,
+            redirect_uri: sso_login.map(|sso| sso.redirect_uri),
+            created_at: session.created_at,
+            user_agent: session.user_agent.map(|ua| ua.raw),
+            last_active_at: session.last_active_at,
+            last_active_ip: session.last_active_ip,
+            finished_at,
+        }
+    }
+}
+
+impl CompatSession {
+    /// Samples of compatibility sessions
+    pub fn samples() -> [Self; 3] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                user_id: Ulid::from_bytes([0x01; 16]),
+                device_id: "AABBCCDDEE".to_owned(),
+                user_session_id: Some(Ulid::from_bytes([0x11; 16])),
+                redirect_uri: Some("https://example.com/redirect".parse().unwrap()),
+                created_at: DateTime::default(),
+                user_agent: Some("Mozilla/5.0".to_owned()),
+                last_active_at: Some(DateTime::default()),
+                last_active_ip: Some([1, 2, 3, 4].into()),
+                finished_at: None,
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                user_id: Ulid::from_bytes([0x01; 16]),
+                device_id: "FFGGHHIIJJ".to_owned(),
+                user_session_id: None,
+                redirect_uri: None,
+                created_at: DateTime::default(),
+                user_agent: Some("Mozilla/5.0".to_owned()),
+                last_active_at: Some(DateTime::default()),
+                last_active_ip: Some([1, 2, 3, 4].into()),
+                finished_at: Some(DateTime::default()),
+            },
+            Self {
+                id: Ulid::from_bytes([0x03; 16]),
+                user_id: Ulid::from_bytes([0x01; 16]),
+                device_id: "KKLLMMNNOO".to_owned(),
+                user_session_id: None,
+                redirect_uri: None,
+                created_at: DateTime::default(),
+                user_agent: None,
+                last_active_at: None,
+                last_active_ip: None,
+                finished_at: None,
+            },
+        ]
+    }
+}
+
+impl Resource for CompatSession {
+    const KIND: &'static str = "compat-session";
+    const PATH: &'static str = "/api/admin/v1/compat-sessions";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
+
+/// The browser (cookie) session for a user
+#[derive(Serialize, JsonSchema)]
+pub struct UserSession {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// When the object was created
+    created_at: DateTime<Utc>,
+
+    /// When the session was finished
+    finished_at: Option<DateTime<Utc>>,
+
+    /// The ID of the user who owns the session
+    #[schemars(with = "super::schema::Ulid")]
+    user_id: Ulid,
+
+    /// The user agent string of the client which started this session
+    user_agent: Option<String>,
+
+    /// The last time the session was active
+    last_active_at: Option<DateTime<Utc>>,
+
+    /// The last IP address used by the session
+    last_active_ip: Option<IpAddr>,
+}
+
+impl From<mas_data_model::BrowserSession> for UserSession {
+    fn from(session: mas_data_model::BrowserSession) -> Self {
+        Self {
+            id: session.id,
+            created_at: session.created_at,
+            finished_at: session.finished_at,
+            user_id: session.user.id,
+            user_agent: session.user_agent.map(|ua| ua.raw),
+            last_active_at: session.last_active_at,
+            last_active_ip: session.last_active_ip,
+        }
+    }
+}
+
+impl UserSession {
+    /// Samples of user sessions
+    pub fn samples() -> [Self; 3] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                created_at: DateTime::default(),
+                finished_at: None,
+                user_id: Ulid::from_bytes([0x02; 16]),
+                user_agent: Some("Mozilla/5.0".to_owned()),
+                last_active_at: Some(DateTime::default()),
+                last_active_ip: Some("127.0.0.1".parse().unwrap()),
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                created_at: DateTime::default(),
+                finished_at: None,
+                user_id: Ulid::from_bytes([0x03; 16]),
+                user_agent: None,
+                last_active_at: None,
+                last_active_ip: None,
+            },
+            Self {
+                id: Ulid::from_bytes([0x03; 16]),
+                created_at: DateTime::default(),
+                finished_at: Some(DateTime::default()),
+                user_id: Ulid::from_bytes([0x04; 16]),
+                user_agent: Some("Mozilla/5.0".to_owned()),
+                last_active_at: Some(DateTime::default()),
+                last_active_ip: Some("127.0.0.1".parse().unwrap()),
+            },
+        ]
+    }
+}
+
+impl Resource for UserSession {
+    const KIND: &'static str = "user-session";
+    const PATH: &'static str = "/api/admin/v1/user-sessions";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
+
+/// An email address for a user
+#[derive(Serialize, JsonSchema)]
+pub struct UserEmail {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// When the object was created
+    created_at: DateTime<Utc>,
+
+    /// The ID of the user who owns this email address
+    #[schemars(with = "super::schema::Ulid")]
+    user_id: Ulid,
+
+    /// The email address
+    email: String,
+
+    /// When the email address was verified. If null, the email address is
+    /// pending verification.
+    verified_at: Option<DateTime<Utc>>,
+}
+
+impl From<mas_data_model::UserEmail> for UserEmail {
+    fn from(email: mas_data_model::UserEmail) -> Self {
+        Self {
+            id: email.id,
+            created_at: email.created_at,
+            user_id: email.user_id,
+            email: email.email,
+            verified_at: email.confirmed_at,
+        }
+    }
+}
+
+impl UserEmail {
+    /// Samples of user emails
+    pub fn samples() -> [Self; 2] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                created_at: DateTime::default(),
+                user_id: Ulid::from_bytes([0x02; 16]),
+                email: "alice@example.com".to_owned(),
+                verified_at: Some(DateTime::default()),
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                created_at: DateTime::default(),
+                user_id: Ulid::from_bytes([0x02; 16]),
+                email: "alice@example.org".to_owned(),
+                verified_at: None,
+            },
+        ]
+    }
+}
+
+impl Resource for UserEmail {
+    const KIND: &'static str = "user-email";
+    const PATH: &'static str = "/api/admin/v1/user-emails";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
+
+/// A link between a local user and an upstream account
+#[derive(Serialize, JsonSchema)]
+pub struct UpstreamOAuthLink {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// When the object was created
+    created_at: DateTime<Utc>,
+
+    /// The ID of the provider
+    #[schemars(with = "super::schema::Ulid")]
+    provider_id: Ulid,
+
+    /// The subject of the upstream account, unique per provider
+    subject: String,
+
+    /// The ID of the user who owns this link, if any
+    #[schemars(with = "Option<super::schema::Ulid>")]
+    user_id: Option<Ulid>,
+}
+
+impl From<mas_data_model::UpstreamOAuthLink> for UpstreamOAuthLink {
+    fn from(link: mas_data_model::UpstreamOAuthLink) -> Self {
+        Self {
+            id: link.id,
+            created_at: link.created_at,
+            provider_id: link.provider_id,
+            subject: link.subject,
+            user_id: link.user_id,
+        }
+    }
+}
+
+impl UpstreamOAuthLink {
+    /// Samples of upstream OAuth 2.0 links
+    pub fn samples() -> [Self; 2] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                created_at: DateTime::default(),
+                provider_id: Ulid::from_bytes([0x02; 16]),
+                subject: "john-42".to_owned(),
+                user_id: Some(Ulid::from_bytes([0x03; 16])),
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                created_at: DateTime::default(),
+                provider_id: Ulid::from_bytes([0x03; 16]),
+                subject: "jane-123".to_owned(),
+                user_id: None,
+            },
+        ]
+    }
+}
+
+impl Resource for UpstreamOAuthLink {
+    const KIND: &'static str = "upstream-oauth-link";
+    const PATH: &'static str = "/api/admin/v1/upstream-oauth-links";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
+
+/// An OAuth 2.0 client
+#[derive(Serialize, JsonSchema)]
+pub struct OAuth2Client {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// The client ID used by the client to identify itself
+    client_id: String,
+
+    /// A human-readable name for the client
+    client_name: Option<String>,
+
+    /// Whether the client is managed through the configuration file. Those
+    /// clients can't be modified through the admin API
+    is_static: bool,
+
+    /// The kind of application, either `web` or `native`
+    application_type: Option<String>,
+
+    /// The URIs the client is allowed to redirect to during the authorization
+    /// flow
+    redirect_uris: Vec<Url>,
+
+    /// The grant types the client is allowed to use
+    grant_types: Vec<String>,
+
+    /// The method used by the client to authenticate to the token endpoint
+    token_endpoint_auth_method: Option<String>,
+
+    /// The JWS algorithm used by the client when authenticating with the
+    /// `client_secret_jwt` or `private_key_jwt` methods
+    token_endpoint_auth_signing_alg: Option<String>,
+
+    /// Whether a client secret is set for this client
+    has_client_secret: bool,
+
+    /// The client secret. This is only returned when the client is created or
+    /// its secret is rotated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret: Option<String>,
+
+    /// The JSON Web Key Set used by the `private_key_jwt` method
+    jwks: Option<PublicJsonWebKeySet>,
+
+    /// The URL of the JSON Web Key Set used by the `private_key_jwt` method
+    jwks_uri: Option<Url>,
+
+    /// The number of active OAuth 2.0 sessions for this client
+    active_session_count: usize,
+}
+
+impl From<(mas_data_model::Client, usize)> for OAuth2Client {
+    fn from((client, active_session_count): (mas_data_model::Client, usize)) -> Self {
+        let (jwks, jwks_uri) = match client.jwks {
+            Some(JwksOrJwksUri::Jwks(jwks)) => (Some(jwks), None),
+            Some(JwksOrJwksUri::JwksUri(jwks_uri)) => (None, Some(jwks_uri)),
+            None => (None, None),
+        };
+
+        Self {
+            id: client.id,
+            client_id: client.client_id,
+            client_name: client.client_name,
+            is_static: client.is_static,
+            application_type: client.application_type.map(|t| t.to_string()),
+            redirect_uris: client.redirect_uris,
+            grant_types: client.grant_types.iter().map(ToString::to_string).collect(),
+            token_endpoint_auth_method: client
+                .token_endpoint_auth_method
+                .map(|method| method.to_string()),
+            token_endpoint_auth_signing_alg: client
+                .token_endpoint_auth_signing_alg
+                .map(|alg| alg.to_string()),
+            has_client_secret: client.encrypted_client_secret.is_some(),
+            client_secret: None,
+            jwks,
+            jwks_uri,
+            active_session_count,
+        }
+    }
+}
+
+impl OAuth2Client {
+    /// Include the plaintext client secret in the response
+    #[must_use]
+    pub fn with_client_secret(mut self, client_secret: String) -> Self {
+        self.client_secret = Some(client_secret);
+        self
+    }
+
+    /// Samples of OAuth 2.0 clients
+    pub fn samples() -> [Self; 2] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                client_id: "01040G2081040G2081040G2081".to_owned(),
+                client_name: Some("Element".to_owned()),
+                is_static: false,
+                application_type: Some("web".to_owned()),
+                redirect_uris: vec!["https://app.example.com/callback".parse().unwrap()],
+                grant_types: vec!["authorization_code".to_owned(), "refresh_token".to_owned()],
+                token_endpoint_auth_method: Some("client_secret_basic".to_owned()),
+                token_endpoint_auth_signing_alg: None,
+                has_client_secret: true,
+                client_secret: None,
+                jwks: None,
+                jwks_uri: None,
+                active_session_count: 42,
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                client_id: "02081040G2081040G2081040G2".to_owned(),
+                client_name: None,
+                is_static: true,
+                application_type: None,
+                redirect_uris: Vec::new(),
+                grant_types: vec!["client_credentials".to_owned()],
+                token_endpoint_auth_method: Some("private_key_jwt".to_owned()),
+                token_endpoint_auth_signing_alg: Some("RS256".to_owned()),
+                has_client_secret: false,
+                client_secret: None,
+                jwks: None,
+                jwks_uri: Some("https://service.example.com/jwks.json".parse().unwrap()),
+                active_session_count: 0,
+            },
+        ]
+    }
+}
+
+impl Resource for OAuth2Client {
+    const KIND: &'static str = "oauth2-client";
+    const PATH: &'static str = "/api/admin/v1/oauth2-clients";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
+
+/// An entry of the audit log
+#[derive(Serialize, JsonSchema)]
+pub struct AuditEvent {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// When the event was recorded
+    created_at: DateTime<Utc>,
+
+    /// The kind of event, like `user.login` or `session.ended`
+    kind: String,
+
+    /// The ID of the user concerned by the event, if any
+    #[schemars(with = "Option<super::schema::Ulid>")]
+    user_id: Option<Ulid>,
+
+    /// Who triggered the event: `anonymous`, `user`, `admin_token`, `cli` or `system`
+    actor_type: String,
+
+    /// The ID of the user who triggered the event, if any
+    #[schemars(with = "Option<super::schema::Ulid>")]
+    actor_user_id: Option<Ulid>,
+
+    /// The ID of the OAuth 2.0 session of the admin token which triggered the
+    /// event, if any
+    #[schemars(with = "Option<super::schema::Ulid>")]
+    actor_session_id: Option<Ulid>,
+
+    /// The IP address of the client which triggered the event
+    ip_address: Option<IpAddr>,
+
+    /// The user agent string of the client which triggered the event
+    user_agent: Option<String>,
+
+    /// Structured details about the event, which depend on its kind
+    details: serde_json::Value,
+}
+
+impl From<mas_data_model::AuditEvent> for AuditEvent {
+    fn from(event: mas_data_model::AuditEvent) -> Self {
+        let actor_session_id = match event.actor {
+            mas_data_model::AuditActor::AdminToken { session_id, .. } => Some(session_id),
+            _ => None,
+        };
+
+        Self {
+            id: event.id,
+            created_at: event.created_at,
+            kind: event.kind.as_str().to_owned(),
+            user_id: event.user_id,
+            actor_type: event.actor.as_str().to_owned(),
+            actor_user_id: event.actor.user_id(),
+            actor_session_id,
+            ip_address: event.ip_address,
+            user_agent: event.user_agent,
+            details: event.details,
+        }
+    }
+}
+
+impl AuditEvent {
+    /// Samples of audit events
+    pub fn samples() -> [Self; 3] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                created_at: DateTime::default(),
+                kind: "user.login_failed".to_owned(),
+                user_id: Some(Ulid::from_bytes([0x02; 16])),
+                actor_type: "anonymous".to_owned(),
+                actor_user_id: None,
+                actor_session_id: None,
+                ip_address: Some("127.0.0.1".parse().unwrap()),
+                user_agent: Some("Mozilla/5.0".to_owned()),
+                details: serde_json::json!({ "reason": "invalid_credentials" }),
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                created_at: DateTime::default(),
+                kind: "user.login".to_owned(),
+                user_id: Some(Ulid::from_bytes([0x02; 16])),
+                actor_type: "user".to_owned(),
+                actor_user_id: Some(Ulid::from_bytes([0x02; 16])),
+                actor_session_id: None,
+                ip_address: Some("127.0.0.1".parse().unwrap()),
+                user_agent: Some("Mozilla/5.0".to_owned()),
+                details: serde_json::json!({ "method": "password" }),
+            },
+            Self {
+                id: Ulid::from_bytes([0x03; 16]),
+                created_at: DateTime::default(),
+                kind: "user.locked".to_owned(),
+                user_id: Some(Ulid::from_bytes([0x03; 16])),
+                actor_type: "admin_token".to_owned(),
+                actor_user_id: None,
+                actor_session_id: Some(Ulid::from_bytes([0x04; 16])),
+                ip_address: Some("127.0.0.1".parse().unwrap()),
+                user_agent: None,
+                details: serde_json::json!({}),
+            },
+        ]
+    }
+}
+
+impl Resource for AuditEvent {
+    const KIND: &'static str = "audit-event";
+    const PATH: &'static str = "/api/admin/v1/audit-events";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
+
+/// The delivery of a user lifecycle event to a webhook target
+#[derive(Serialize, JsonSchema)]
+pub struct WebhookDelivery {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// When the event was queued for delivery
+    created_at: DateTime<Utc>,
+
+    /// The name of the webhook target, as set in the configuration
+    target: String,
+
+    /// The kind of event, like `user.registered` or `user.locked`
+    event: String,
+
+    /// The ID of the user concerned by the event, if any
+    #[schemars(with = "Option<super::schema::Ulid>")]
+    user_id: Option<Ulid>,
+
+    /// The status of the delivery: `pending`, `delivered` or `failed`
+    ///
+    /// Failed deliveries ran out of attempts, and won't be retried.
+    status: &'static str,
+
+    /// The event-specific data sent to the target
+    data: serde_json::Value,
+
+    /// How many times the delivery was attempted
+    attempts: u32,
+
+    /// When the delivery was last attempted
+    last_attempt_at: Option<DateTime<Utc>>,
+
+    /// The error of the last failed attempt, if any
+    last_error: Option<String>,
+
+    /// When the target acknowledged the delivery
+    delivered_at: Option<DateTime<Utc>>,
+
+    /// When the delivery was given up
+    failed_at: Option<DateTime<Utc>>,
+}
+
+impl From<mas_data_model::WebhookDelivery> for WebhookDelivery {
+    fn from(delivery: mas_data_model::WebhookDelivery) -> Self {
+        let status = if delivery.is_delivered() {
+            "delivered"
+        } else if delivery.is_failed() {
+            "failed"
+        } else {
+            "pending"
+        };
+
+        Self {
+            id: delivery.id,
+            created_at: delivery.created_at,
+            target: delivery.target,
+            event: delivery.event.as_str().to_owned(),
+            user_id: delivery.user_id,
+            status,
+            data: delivery.data,
+            attempts: delivery.attempts,
+            last_attempt_at: delivery.last_attempt_at,
+            last_error: delivery.last_error,
+            delivered_at: delivery.state.delivered_at(),
+            failed_at: delivery.state.failed_at(),
+        }
+    }
+}
+
+impl WebhookDelivery {
+    /// Samples of webhook deliveries
+    pub fn samples() -> [Self; 3] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                created_at: DateTime::default(),
+                target: "crm".to_owned(),
+                event: "user.registered".to_owned(),
+                user_id: Some(Ulid::from_bytes([0x02; 16])),
+                status: "delivered",
+                data: serde_json::json!({
+                    "user": { "id": Ulid::from_bytes([0x02; 16]), "username": "alice" },
+                    "method": "password",
+                }),
+                attempts: 1,
+                last_attempt_at: Some(DateTime::default()),
+                last_error: None,
+                delivered_at: Some(DateTime::default()),
+                failed_at: None,
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                created_at: DateTime::default(),
+                target: "crm".to_owned(),
+                event: "user.locked".to_owned(),
+                user_id: Some(Ulid::from_bytes([0x03; 16])),
+                status: "pending",
+                data: serde_json::json!({
+                    "user": { "id": Ulid::from_bytes([0x03; 16]), "username": "bob" },
+                }),
+                attempts: 2,
+                last_attempt_at: Some(DateTime::default()),
+                last_error: Some(
+                    "Webhook target responded with an unexpected status code: 502 Bad Gateway"
+                        .to_owned(),
+                ),
+                delivered_at: None,
+                failed_at: None,
+            },
+            Self {
+                id: Ulid::from_bytes([0x03; 16]),
+                created_at: DateTime::default(),
+                target: "audit".to_owned(),
+                event: "user.deactivated".to_owned(),
+                user_id: Some(Ulid::from_bytes([0x04; 16])),
+                status: "failed",
+                data: serde_json::json!({
+                    "user": { "id": Ulid::from_bytes([0x04; 16]), "username": "charlie" },
+                    "erase": false,
+                }),
+                attempts: 10,
+                last_attempt_at: Some(DateTime::default()),
+                last_error: Some("Failed to send the request to the webhook target".to_owned()),
+                delivered_at: None,
+                failed_at: Some(DateTime::default()),
+            },
+        ]
+    }
+}
+
+impl Resource for WebhookDelivery {
+    const KIND: &'static str = "webhook-delivery";
+    const PATH: &'static str = "/api/admin/v1/webhook-deliveries";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
+
+/// A job in the background job queue
+#[derive(Serialize, JsonSchema)]
+pub struct Job {
+    #[serde(skip)]
+    id: Ulid,
+
+    /// The name of the job, like `provision-user` or `verify-email`
+    name: String,
+
+    /// The status of the job: `pending`, `running`, `retry`, `done`, `failed`
+    /// or `killed`
+    ///
+    /// Failed jobs ran out of attempts, and killed jobs were cancelled. Both
+    /// can be retried.
+    status: &'static str,
+
+    /// The payload of the job
+    payload: serde_json::Value,
+
+    /// How many times the job was attempted
+    attempts: u32,
+
+    /// How many times the job can be attempted before it fails
+    max_attempts: u32,
+
+    /// When the job is (or was) due to run
+    run_at: DateTime<Utc>,
+
+    /// The error of the last failed attempt, if any
+    last_error: Option<String>,
+
+    /// The worker which picked up the job, if any
+    locked_by: Option<String>,
+
+    /// When the job was picked up by a worker
+    locked_at: Option<DateTime<Utc>>,
+
+    /// When the job completed, failed or was cancelled
+    done_at: Option<DateTime<Utc>>,
+}
+
+impl From<mas_storage::job::QueuedJob> for Job {
+    fn from(job: mas_storage::job::QueuedJob) -> Self {
+        Self {
+            id: job.id,
+            name: job.name,
+            status: Self::status(job.status),
+            payload: job.payload,
+            attempts: job.attempts,
+            max_attempts: job.max_attempts,
+            run_at: job.run_at,
+            last_error: job.last_error,
+            locked_by: job.locked_by,
+            locked_at: job.locked_at,
+            done_at: job.done_at,
+        }
+    }
+}
+
+impl Job {
+    /// The status of a job, as exposed by the API
+    pub const fn status(status: mas_storage::job::JobStatus) -> &'static str {
+        use mas_storage::job::JobStatus;
+
+        match status {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Retry => "retry",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+            JobStatus::Killed => "killed",
+        }
+    }
+
+    /// Samples of jobs
+    pub fn samples() -> [Self; 3] {
+        [
+            Self {
+                id: Ulid::from_bytes([0x01; 16]),
+                name: "provision-user".to_owned(),
+                status: "pending",
+                payload: serde_json::json!({ "user_id": Ulid::from_bytes([0x02; 16]) }),
+                attempts: 0,
+                max_attempts: 25,
+                run_at: DateTime::default(),
+                last_error: None,
+                locked_by: None,
+                locked_at: None,
+                done_at: None,
+            },
+            Self {
+                id: Ulid::from_bytes([0x02; 16]),
+                name: "verify-email".to_owned(),
+                status: "retry",
+                payload: serde_json::json!({
+                    "user_email_id": Ulid::from_bytes([0x03; 16]),
+                    "language": "en",
+                }),
+                attempts: 2,
+                max_attempts: 25,
+                run_at: DateTime::default(),
+                last_error: Some("Failed to send the email".to_owned()),
+                locked_by: Some("verify-email-01J4X8ZQ1P6Y3K8RM3ZQ3VJ9E2".to_owned()),
+                locked_at: Some(DateTime::default()),
+                done_at: None,
+            },
+            Self {
+                id: Ulid::from_bytes([0x03; 16]),
+                name: "sync-devices".to_owned(),
+                status: "failed",
+                payload: serde_json::json!({ "user_id": Ulid::from_bytes([0x04; 16]) }),
+                attempts: 25,
+                max_attempts: 25,
+                run_at: DateTime::default(),
+                last_error: Some("Failed to reach the homeserver".to_owned()),
+                locked_by: Some("sync-devices-01J4X8ZQ1P6Y3K8RM3ZQ3VJ9E2".to_owned()),
+                locked_at: Some(DateTime::default()),
+                done_at: Some(DateTime::default()),
+            },
+        ]
+    }
+}
+
+impl Resource for Job {
+    const KIND: &'static str = "job";
+    const PATH: &'static str = "/api/admin/v1/jobs";
+
+    fn id(&self) -> Ulid {
+        self.id
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{
+    extract::{rejection::QueryRejection, Query},
+    response::IntoResponse,
+    Json,
+};
+use axum_macros::FromRequestParts;
+use chrono::{DateTime, SecondsFormat, Utc};
+use hyper::StatusCode;
+use mas_data_model::AuditEventKind;
+use mas_storage::{audit::AuditEventFilter, Page};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{AuditEvent, Resource},
+        params::Pagination,
+        response::{ErrorResponse, PaginatedResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(FromRequestParts, Deserialize, JsonSchema, OperationIo)]
+#[serde(rename = "AuditEventFilter")]
+#[aide(input_with = "Query<FilterParams>")]
+#[from_request(via(Query), rejection(RouteError))]
+pub struct FilterParams {
+    /// Retrieve the events concerning the given user
+    #[serde(rename = "filter[user]")]
+    #[schemars(with = "Option<crate::admin::schema::Ulid>")]
+    user: Option<Ulid>,
+
+    /// Retrieve the events of the given kind, like `user.login` or
+    /// `session.ended`
+    #[serde(rename = "filter[kind]")]
+    kind: Option<String>,
+
+    /// Retrieve the events recorded after the given date
+    #[serde(rename = "filter[after]")]
+    after: Option<DateTime<Utc>>,
+
+    /// Retrieve the events recorded before the given date
+    #[serde(rename = "filter[before]")]
+    before: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Display for FilterParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut sep = '?';
+
+        if let Some(user) = self.user {
+            write!(f, "{sep}filter[user]={user}")?;
+            sep = '&';
+        }
+
+        if let Some(kind) = &self.kind {
+            write!(f, "{sep}filter[kind]={kind}")?;
+            sep = '&';
+        }
+
+        if let Some(after) = self.after {
+            let after = after.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+            write!(f, "{sep}filter[after]={after}")?;
+            sep = '&';
+        }
+
+        if let Some(before) = self.before {
+            let before = before.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+            write!(f, "{sep}filter[before]={before}")?;
+            sep = '&';
+        }
+
+        let _ = sep;
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("User ID {0} not found")]
+    UserNotFound(Ulid),
+
+    #[error("Unknown audit event kind {0:?}")]
+    UnknownKind(String),
+
+    #[error("Invalid filter parameters")]
+    InvalidFilter(#[from] QueryRejection),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UserNotFound(_) => StatusCode::NOT_FOUND,
+            Self::UnknownKind(_) | Self::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("listAuditEvents")
+        .summary("List audit events")
+        .description("Retrieve a list of entries of the audit log, most recent last.")
+        .tag("audit-event")
+        .response_with::<200, Json<PaginatedResponse<AuditEvent>>, _>(|t| {
+            let events = AuditEvent::samples();
+            let pagination = mas_storage::Pagination::first(events.len());
+            let page = Page {
+                edges: events.into(),
+                has_next_page: true,
+                has_previous_page: false,
+            };
+
+            t.description("Paginated response of audit events")
+                .example(PaginatedResponse::new(
+                    page,
+                    pagination,
+                    42,
+                    AuditEvent::PATH,
+                ))
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response =
+                ErrorResponse::from_error(&RouteError::UnknownKind("user.unknown".to_owned()));
+            t.description("Unknown event kind").example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::UserNotFound(Ulid::nil()));
+            t.description("User was not found").example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.audit_events.list", skip_all, err)]
+pub async fn handler(
+    CallContext { mut repo, .. }: CallContext,
+    Pagination(pagination): Pagination,
+    params: FilterParams,
+) -> Result<Json<PaginatedResponse<AuditEvent>>, RouteError> {
+    let base = format!("{path}{params}", path = AuditEvent::PATH);
+    let filter = AuditEventFilter::default();
+
+    // Load the user from the filter
+    let user = if let Some(user_id) = params.user {
+        let user = repo
+            .user()
+            .lookup(user_id)
+            .await?
+            .ok_or(RouteError::UserNotFound(user_id))?;
+
+        Some(user)
+    } else {
+        None
+    };
+
+    let filter = match &user {
+        Some(user) => filter.for_user(user),
+        None => filter,
+    };
+
+    let filter = match &params.kind {
+        Some(kind) => {
+            let kind: AuditEventKind = kind
+                .parse()
+                .map_err(|_| RouteError::UnknownKind(kind.clone()))?;
+            filter.with_kind(kind)
+        }
+        None => filter,
+    };
+
+    let filter = match params.after {
+        Some(after) => filter.created_after(after),
+        None => filter,
+    };
+
+    let filter = match params.before {
+        Some(before) => filter.created_before(before),
+        None => filter,
+    };
+
+    let page = repo.audit_event().list(filter, pagination).await?;
+    let count = repo.audit_event().count(filter).await?;
+
+    Ok(Json(PaginatedResponse::new(
+        page.map(AuditEvent::from),
+        pagination,
+        count,
+        &base,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use hyper::{Request, StatusCode};
+    use mas_data_model::{AuditContext, AuditEventKind};
+    use mas_storage::{audit::AuditEventRepository, user::UserRepository, Clock, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_audit_event_list(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let alice = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let bob = repo
+            .user()
+            .add(&mut rng, &state.clock, "bob".to_owned())
+            .await
+            .unwrap();
+
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &state.clock,
+                &AuditContext::anonymous(),
+                AuditEventKind::UserLoginFailed,
+                Some(&alice),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+        let after = state.clock.now();
+        state.clock.advance(Duration::minutes(1));
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &state.clock,
+                &AuditContext::user(&alice),
+                AuditEventKind::UserLogin,
+                Some(&alice),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &state.clock,
+                &AuditContext::cli(),
+                AuditEventKind::UserLocked,
+                Some(&bob),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/api/admin/v1/audit-events")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 3);
+        assert_eq!(body["data"][0]["attributes"]["kind"], "user.login_failed");
+        assert_eq!(body["data"][0]["attributes"]["actor_type"], "anonymous");
+
+        // Filter by user
+        let request = Request::get(format!(
+            "/api/admin/v1/audit-events?filter[user]={}",
+            bob.id
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+        assert_eq!(body["data"][0]["attributes"]["kind"], "user.locked");
+        assert_eq!(body["data"][0]["attributes"]["actor_type"], "cli");
+
+        // Filter by kind
+        let request = Request::get("/api/admin/v1/audit-events?filter[kind]=user.login")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+        assert_eq!(
+            body["data"][0]["attributes"]["actor_user_id"],
+            alice.id.to_string()
+        );
+
+        // Filter by date
+        let request = Request::get(format!(
+            "/api/admin/v1/audit-events?filter[after]={}",
+            after.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 2);
+
+        // Unknown kind
+        let request = Request::get("/api/admin/v1/audit-events?filter[kind]=user.unknown")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Unknown user
+        let request = Request::get(format!(
+            "/api/admin/v1/audit-events?filter[user]={}",
+            ulid::Ulid::nil()
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        // Failed write operations are recorded as well
+        let request = Request::post(format!("/api/admin/v1/users/{}/lock", ulid::Ulid::nil()))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        let request = Request::get("/api/admin/v1/audit-events?filter[kind]=admin_api.call")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+        assert_eq!(body["data"][0]["attributes"]["actor_type"], "admin_token");
+        assert_eq!(
+            body["data"][0]["attributes"]["details"]["path"],
+            format!("/api/admin/v1/users/{}/lock", ulid::Ulid::nil())
+        );
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_admin_api_call_audit_failure(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool.clone()).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let alice = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Simulate the audit log being unavailable
+        sqlx::query(
+            "CREATE FUNCTION fail_audit_event() RETURNS trigger LANGUAGE plpgsql AS $$ BEGIN RAISE EXCEPTION 'audit log unavailable'; END $$",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "CREATE TRIGGER fail_audit_event BEFORE INSERT ON audit_events FOR EACH ROW EXECUTE FUNCTION fail_audit_event()",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // The call is rejected before the handler runs
+        let request = Request::post(format!("/api/admin/v1/users/{}/lock", alice.id))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+
+        let mut repo = state.repository().await.unwrap();
+        let alice = repo.user().lookup(alice.id).await.unwrap().unwrap();
+        assert!(alice.locked_at.is_none());
+        repo.cancel().await.unwrap();
+
+        // Read operations aren't recorded, so they still work
+        let request = Request::get("/api/admin/v1/audit-events")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 0);
+    }
+}
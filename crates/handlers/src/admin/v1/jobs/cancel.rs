@@ -0,0 +1,177 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{response::IntoResponse, Json};
+use hyper::StatusCode;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{Job, Resource},
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Job ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error("Job ID {0} is {1}, only pending jobs or jobs waiting to be retried can be cancelled")]
+    NotCancellable(Ulid, &'static str),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::NotCancellable(_, _) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("cancelJob")
+        .summary("Cancel a job")
+        .description(
+            "Remove a pending job from the queue, or one waiting to be retried.
+The job is marked as killed, and can be retried later.
+Jobs which are currently running can't be cancelled.",
+        )
+        .tag("job")
+        .response_with::<200, Json<SingleResponse<Job>>, _>(|t| {
+            let [_, retrying, ..] = Job::samples();
+            let id = retrying.id();
+            let response = SingleResponse::new(retrying, format!("/api/admin/v1/jobs/{id}/cancel"));
+            t.description("Job was cancelled").example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response =
+                ErrorResponse::from_error(&RouteError::NotCancellable(Ulid::nil(), "running"));
+            t.description("Job can't be cancelled").example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("Job was not found").example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.jobs.cancel", skip_all, err)]
+pub async fn handler(
+    CallContext {
+        mut repo, clock, ..
+    }: CallContext,
+    id: UlidPathParam,
+) -> Result<Json<SingleResponse<Job>>, RouteError> {
+    let id = *id;
+    let job = repo
+        .job()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    if !job.status.can_be_cancelled() {
+        return Err(RouteError::NotCancellable(id, Job::status(job.status)));
+    }
+
+    let job = repo.job().cancel(&clock, job).await?;
+
+    repo.save().await?;
+
+    Ok(Json(SingleResponse::new(
+        Job::from(job),
+        format!("/api/admin/v1/jobs/{id}/cancel"),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{
+        job::{JobFilter, JobRepository, JobRepositoryExt, ProvisionUserJob},
+        user::UserRepository,
+        Clock, Pagination, RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_cancel_job(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let alice = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        repo.job()
+            .schedule_job(ProvisionUserJob::new(&alice))
+            .await
+            .unwrap();
+        let page = repo
+            .job()
+            .list(JobFilter::new(), Pagination::first(1))
+            .await
+            .unwrap();
+        let id = page.edges[0].id;
+        repo.save().await.unwrap();
+
+        let request = Request::post(format!("/api/admin/v1/jobs/{id}/cancel"))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["attributes"]["status"], "killed");
+        assert_eq!(
+            body["data"]["attributes"]["done_at"],
+            serde_json::json!(state.clock.now())
+        );
+
+        // Cancelling it again fails
+        let request = Request::post(format!("/api/admin/v1/jobs/{id}/cancel"))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["errors"][0]["title"],
+            format!("Job ID {id} is killed, only pending jobs or jobs waiting to be retried can be cancelled")
+        );
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_cancel_unknown_job(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/jobs/01040G2081040G2081040G2081/cancel")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+}
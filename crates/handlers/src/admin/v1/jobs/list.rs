@@ -0,0 +1,258 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{
+    extract::{rejection::QueryRejection, Query},
+    response::IntoResponse,
+    Json,
+};
+use axum_macros::FromRequestParts;
+use hyper::StatusCode;
+use mas_storage::{job::JobFilter, Page};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{Job, Resource},
+        params::Pagination,
+        response::{ErrorResponse, PaginatedResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum JobStatus {
+    Pending,
+    Running,
+    Retry,
+    Done,
+    Failed,
+    Killed,
+}
+
+impl From<JobStatus> for mas_storage::job::JobStatus {
+    fn from(status: JobStatus) -> Self {
+        match status {
+            JobStatus::Pending => Self::Pending,
+            JobStatus::Running => Self::Running,
+            JobStatus::Retry => Self::Retry,
+            JobStatus::Done => Self::Done,
+            JobStatus::Failed => Self::Failed,
+            JobStatus::Killed => Self::Killed,
+        }
+    }
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(Job::status((*self).into()))
+    }
+}
+
+#[derive(FromRequestParts, Deserialize, JsonSchema, OperationIo)]
+#[serde(rename = "JobFilter")]
+#[aide(input_with = "Query<FilterParams>")]
+#[from_request(via(Query), rejection(RouteError))]
+pub struct FilterParams {
+    /// Retrieve the jobs with the given status
+    ///
+    /// Failed jobs ran out of attempts, and killed jobs were cancelled.
+    #[serde(rename = "filter[status]")]
+    status: Option<JobStatus>,
+
+    /// Retrieve the jobs with the given name, like `provision-user`
+    #[serde(rename = "filter[name]")]
+    name: Option<String>,
+}
+
+impl std::fmt::Display for FilterParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut sep = '?';
+
+        if let Some(status) = self.status {
+            write!(f, "{sep}filter[status]={status}")?;
+            sep = '&';
+        }
+
+        if let Some(name) = &self.name {
+            write!(f, "{sep}filter[name]={name}")?;
+            sep = '&';
+        }
+
+        let _ = sep;
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Invalid filter parameters")]
+    InvalidFilter(#[from] QueryRejection),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("listJobs")
+        .summary("List jobs")
+        .description(
+            "Retrieve a list of jobs from the background job queue.
+
+Use the `filter[status]=failed` parameter to list the jobs which ran out of attempts.",
+        )
+        .tag("job")
+        .response_with::<200, Json<PaginatedResponse<Job>>, _>(|t| {
+            let jobs = Job::samples();
+            let pagination = mas_storage::Pagination::first(jobs.len());
+            let page = Page {
+                edges: jobs.into(),
+                has_next_page: true,
+                has_previous_page: false,
+            };
+
+            t.description("Paginated response of jobs")
+                .example(PaginatedResponse::new(page, pagination, 42, Job::PATH))
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.jobs.list", skip_all, err)]
+pub async fn handler(
+    CallContext { mut repo, .. }: CallContext,
+    Pagination(pagination): Pagination,
+    params: FilterParams,
+) -> Result<Json<PaginatedResponse<Job>>, RouteError> {
+    let base = format!("{path}{params}", path = Job::PATH);
+    let filter = JobFilter::new();
+
+    let filter = match &params.name {
+        Some(name) => filter.for_name(name),
+        None => filter,
+    };
+
+    let filter = match params.status {
+        Some(status) => filter.with_status(status.into()),
+        None => filter,
+    };
+
+    let page = repo.job().list(filter, pagination).await?;
+    let count = repo.job().count(filter).await?;
+
+    Ok(Json(PaginatedResponse::new(
+        page.map(Job::from),
+        pagination,
+        count,
+        &base,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{
+        job::{JobRepositoryExt, ProvisionUserJob, SyncDevicesJob},
+        user::UserRepository,
+        RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_job_list(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool.clone()).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let alice = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let failed = repo
+            .job()
+            .schedule_job(ProvisionUserJob::new(&alice))
+            .await
+            .unwrap();
+        repo.job()
+            .schedule_job(SyncDevicesJob::new(&alice))
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Simulate the first job running out of attempts
+        sqlx::query(
+            "UPDATE apalis.jobs SET status = 'Failed', attempts = 25, last_error = 'boom' WHERE id = $1",
+        )
+        .bind(failed.to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let request = Request::get("/api/admin/v1/jobs").bearer(&token).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 2);
+
+        // Filter by status
+        let request = Request::get("/api/admin/v1/jobs?filter[status]=failed")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+        assert_eq!(body["data"][0]["attributes"]["name"], "provision-user");
+        assert_eq!(body["data"][0]["attributes"]["status"], "failed");
+        assert_eq!(body["data"][0]["attributes"]["last_error"], "boom");
+
+        let request = Request::get("/api/admin/v1/jobs?filter[status]=pending")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+        assert_eq!(body["data"][0]["attributes"]["name"], "sync-devices");
+
+        // Filter by name
+        let request = Request::get("/api/admin/v1/jobs?filter[name]=sync-devices")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+
+        // Unknown status
+        let request = Request::get("/api/admin/v1/jobs?filter[status]=unknown")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+}
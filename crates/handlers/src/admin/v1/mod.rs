@@ -9,24 +9,94 @@ use aide::axum::{
     ApiRouter,
 };
 use axum::extract::{FromRef, FromRequestParts};
+use mas_data_model::SiteConfig;
+use mas_keystore::Encrypter;
 use mas_matrix::BoxHomeserverConnection;
 use mas_storage::BoxRng;
 
 use super::call_context::CallContext;
 use crate::passwords::PasswordManager;
 
+mod audit_events;
+mod compat_sessions;
+mod jobs;
+mod oauth2_clients;
 mod oauth2_sessions;
+mod upstream_oauth_links;
+mod upstream_oauth_providers;
+mod user_emails;
+mod user_sessions;
 mod users;
+mod webhook_deliveries;
 
 pub fn router<S>() -> ApiRouter<S>
 where
     S: Clone + Send + Sync + 'static,
     BoxHomeserverConnection: FromRef<S>,
     PasswordManager: FromRef<S>,
+    SiteConfig: FromRef<S>,
+    Encrypter: FromRef<S>,
     BoxRng: FromRequestParts<S>,
     CallContext: FromRequestParts<S>,
 {
     ApiRouter::<S>::new()
+        .api_route(
+            "/audit-events",
+            get_with(self::audit_events::list, self::audit_events::list_doc),
+        )
+        .api_route(
+            "/audit-events/:id",
+            get_with(self::audit_events::get, self::audit_events::get_doc),
+        )
+        .api_route(
+            "/compat-sessions",
+            get_with(self::compat_sessions::list, self::compat_sessions::list_doc),
+        )
+        .api_route(
+            "/compat-sessions/:id",
+            get_with(self::compat_sessions::get, self::compat_sessions::get_doc),
+        )
+        .api_route(
+            "/compat-sessions/:id/finish",
+            post_with(
+                self::compat_sessions::finish,
+                self::compat_sessions::finish_doc,
+            ),
+        )
+        .api_route("/jobs", get_with(self::jobs::list, self::jobs::list_doc))
+        .api_route("/jobs/:id", get_with(self::jobs::get, self::jobs::get_doc))
+        .api_route(
+            "/jobs/:id/retry",
+            post_with(self::jobs::retry, self::jobs::retry_doc),
+        )
+        .api_route(
+            "/jobs/:id/cancel",
+            post_with(self::jobs::cancel, self::jobs::cancel_doc),
+        )
+        .api_route(
+            "/oauth2-clients",
+            get_with(self::oauth2_clients::list, self::oauth2_clients::list_doc)
+                .post_with(self::oauth2_clients::add, self::oauth2_clients::add_doc),
+        )
+        .api_route(
+            "/oauth2-clients/:id",
+            get_with(self::oauth2_clients::get, self::oauth2_clients::get_doc)
+                .put_with(
+                    self::oauth2_clients::update,
+                    self::oauth2_clients::update_doc,
+                )
+                .delete_with(
+                    self::oauth2_clients::delete,
+                    self::oauth2_clients::delete_doc,
+                ),
+        )
+        .api_route(
+            "/oauth2-clients/:id/rotate-secret",
+            post_with(
+                self::oauth2_clients::rotate_secret,
+                self::oauth2_clients::rotate_secret_doc,
+            ),
+        )
         .api_route(
             "/oauth2-sessions",
             get_with(self::oauth2_sessions::list, self::oauth2_sessions::list_doc),
@@ -35,6 +105,53 @@ where
             "/oauth2-sessions/:id",
             get_with(self::oauth2_sessions::get, self::oauth2_sessions::get_doc),
         )
+        .api_route(
+            "/upstream-oauth-links",
+            get_with(
+                self::upstream_oauth_links::list,
+                self::upstream_oauth_links::list_doc,
+            ),
+        )
+        .api_route(
+            "/upstream-oauth-links/:id",
+            get_with(
+                self::upstream_oauth_links::get,
+                self::upstream_oauth_links::get_doc,
+            ),
+        )
+        .api_route(
+            "/upstream-oauth-providers",
+            get_with(
+                self::upstream_oauth_providers::list,
+                self::upstream_oauth_providers::list_doc,
+            )
+            .post_with(
+                self::upstream_oauth_providers::add,
+                self::upstream_oauth_providers::add_doc,
+            ),
+        )
+        .api_route(
+            "/upstream-oauth-providers/:id",
+            get_with(
+                self::upstream_oauth_providers::get,
+                self::upstream_oauth_providers::get_doc,
+            )
+            .put_with(
+                self::upstream_oauth_providers::update,
+                self::upstream_oauth_providers::update_doc,
+            )
+            .delete_with(
+                self::upstream_oauth_providers::delete,
+                self::upstream_oauth_providers::delete_doc,
+            ),
+        )
+        .api_route(
+            "/upstream-oauth-providers/:id/disable",
+            post_with(
+                self::upstream_oauth_providers::disable,
+                self::upstream_oauth_providers::disable_doc,
+            ),
+        )
         .api_route(
             "/users",
             get_with(self::users::list, self::users::list_doc)
@@ -68,4 +185,51 @@ where
             "/users/:id/unlock",
             post_with(self::users::unlock, self::users::unlock_doc),
         )
+        .api_route(
+            "/user-emails",
+            get_with(self::user_emails::list, self::user_emails::list_doc)
+                .post_with(self::user_emails::add, self::user_emails::add_doc),
+        )
+        .api_route(
+            "/user-emails/:id",
+            get_with(self::user_emails::get, self::user_emails::get_doc)
+                .delete_with(self::user_emails::delete, self::user_emails::delete_doc),
+        )
+        .api_route(
+            "/user-emails/:id/verify",
+            post_with(self::user_emails::verify, self::user_emails::verify_doc),
+        )
+        .api_route(
+            "/user-emails/:id/set-primary",
+            post_with(
+                self::user_emails::set_primary,
+                self::user_emails::set_primary_doc,
+            ),
+        )
+        .api_route(
+            "/user-sessions",
+            get_with(self::user_sessions::list, self::user_sessions::list_doc),
+        )
+        .api_route(
+            "/user-sessions/:id",
+            get_with(self::user_sessions::get, self::user_sessions::get_doc),
+        )
+        .api_route(
+            "/user-sessions/:id/finish",
+            post_with(self::user_sessions::finish, self::user_sessions::finish_doc),
+        )
+        .api_route(
+            "/webhook-deliveries",
+            get_with(
+                self::webhook_deliveries::list,
+                self::webhook_deliveries::list_doc,
+            ),
+        )
+        .api_route(
+            "/webhook-deliveries/:id",
+            get_with(
+                self::webhook_deliveries::get,
+                self::webhook_deliveries::get_doc,
+            ),
+        )
 }
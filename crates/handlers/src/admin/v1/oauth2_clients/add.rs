@@ -0,0 +1,253 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, NoApi, OperationIo};
+use axum::{extract::State, response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_keystore::Encrypter;
+use mas_storage::BoxRng;
+use tracing::info;
+
+use super::{
+    generate_client_secret,
+    request::{InvalidClientError, Request},
+};
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::OAuth2Client,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error(transparent)]
+    InvalidClient(#[from] InvalidClientError),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_keystore::aead::Error);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidClient(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("createOAuth2Client")
+        .summary("Create a new OAuth 2.0 client")
+        .description("If the client uses a client secret based authentication method, a secret is generated and returned in the `client_secret` attribute.
+This is the only time the secret is returned, it can't be retrieved afterwards.")
+        .tag("oauth2-client")
+        .response_with::<200, Json<SingleResponse<OAuth2Client>>, _>(|t| {
+            let [sample, ..] = OAuth2Client::samples();
+            let sample = sample.with_client_secret("fJ3hXqGkY8vTz2LwR9sA".to_owned());
+            let response = SingleResponse::new_canonical(sample);
+            t.description("OAuth 2.0 client was created")
+                .example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::InvalidClient(
+                InvalidClientError::MissingRedirectUri,
+            ));
+            t.description("Client parameters are invalid")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.oauth2_clients.add", skip_all, err)]
+pub async fn handler(
+    CallContext {
+        mut repo, clock, ..
+    }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
+    State(encrypter): State<Encrypter>,
+    Json(params): Json<Request>,
+) -> Result<Json<SingleResponse<OAuth2Client>>, RouteError> {
+    params.validate()?;
+
+    let (client_secret, encrypted_client_secret) = if params.uses_client_secret() {
+        let (client_secret, encrypted_client_secret) =
+            generate_client_secret(&mut rng, &encrypter)?;
+        (Some(client_secret), Some(encrypted_client_secret))
+    } else {
+        (None, None)
+    };
+
+    let token_endpoint_auth_method = params.auth_method();
+    let client = repo
+        .oauth2_client()
+        .add(
+            &mut rng,
+            &clock,
+            params.redirect_uris,
+            encrypted_client_secret,
+            None,
+            params.grant_types,
+            params.client_name,
+            None,
+            None,
+            None,
+            None,
+            params.jwks_uri,
+            params.jwks,
+            None,
+            None,
+            Some(token_endpoint_auth_method),
+            params.token_endpoint_auth_signing_alg,
+            None,
+            Vec::new(),
+            None,
+            false,
+            false,
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+    repo.save().await?;
+
+    info!(client.id = %client.id, "Added OAuth 2.0 client");
+
+    // A freshly created client can't have any session yet
+    let mut client = OAuth2Client::from((client, 0));
+    if let Some(client_secret) = client_secret {
+        client = client.with_client_secret(client_secret);
+    }
+
+    Ok(Json(SingleResponse::new_canonical(client)))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{oauth2::OAuth2ClientRepository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_add_client(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/oauth2-clients")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "client_name": "Backend service",
+                "redirect_uris": ["https://app.example.com/callback"],
+                "grant_types": ["authorization_code", "refresh_token"],
+                "token_endpoint_auth_method": "client_secret_basic",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["type"], "oauth2-client");
+        assert_eq!(body["data"]["attributes"]["client_name"], "Backend service");
+        assert_eq!(body["data"]["attributes"]["is_static"], false);
+        assert_eq!(body["data"]["attributes"]["has_client_secret"], true);
+        assert_eq!(body["data"]["attributes"]["active_session_count"], 0);
+        let client_secret = body["data"]["attributes"]["client_secret"]
+            .as_str()
+            .unwrap();
+        let id = body["data"]["id"].as_str().unwrap();
+
+        // Check that the client was created with an encrypted secret
+        let mut repo = state.repository().await.unwrap();
+        let client = repo
+            .oauth2_client()
+            .lookup(id.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let encrypted = client.encrypted_client_secret.unwrap();
+        let decrypted = state.encrypter.decrypt_string(&encrypted).unwrap();
+        assert_eq!(decrypted, client_secret.as_bytes());
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_add_public_client(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/oauth2-clients")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "redirect_uris": ["https://app.example.com/callback"],
+                "token_endpoint_auth_method": "none",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["attributes"]["has_client_secret"], false);
+        assert!(body["data"]["attributes"].get("client_secret").is_none());
+        assert_eq!(
+            body["data"]["attributes"]["grant_types"],
+            serde_json::json!(["authorization_code", "refresh_token"])
+        );
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_add_invalid_client(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        // private_key_jwt requires a JWKS
+        let request = Request::post("/api/admin/v1/oauth2-clients")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "grant_types": ["client_credentials"],
+                "token_endpoint_auth_method": "private_key_jwt",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["errors"][0]["title"],
+            "A JWKS or a JWKS URI is required for the private_key_jwt authentication method"
+        );
+
+        // The authorization code grant requires a redirect URI
+        let request = Request::post("/api/admin/v1/oauth2-clients")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "token_endpoint_auth_method": "none",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["errors"][0]["title"],
+            "At least one redirect URI is required for the authorization_code grant"
+        );
+    }
+}
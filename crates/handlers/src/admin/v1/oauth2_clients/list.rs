@@ -0,0 +1,205 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{
+    extract::{rejection::QueryRejection, Query},
+    response::IntoResponse,
+    Json,
+};
+use axum_macros::FromRequestParts;
+use hyper::StatusCode;
+use mas_storage::{oauth2::OAuth2ClientFilter, Page};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use super::load_clients;
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{OAuth2Client, Resource},
+        params::Pagination,
+        response::{ErrorResponse, PaginatedResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(FromRequestParts, Deserialize, JsonSchema, OperationIo)]
+#[serde(rename = "OAuth2ClientFilter")]
+#[aide(input_with = "Query<FilterParams>")]
+#[from_request(via(Query), rejection(RouteError))]
+pub struct FilterParams {
+    /// Retrieve clients which are managed through the configuration file (or
+    /// not)
+    ///
+    /// Defaults to retrieve all clients, static and dynamic.
+    #[serde(rename = "filter[static]")]
+    is_static: Option<bool>,
+}
+
+impl std::fmt::Display for FilterParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut sep = '?';
+
+        if let Some(is_static) = self.is_static {
+            write!(f, "{sep}filter[static]={is_static}")?;
+            sep = '&';
+        }
+
+        let _ = sep;
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Invalid filter parameters")]
+    InvalidFilter(#[from] QueryRejection),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("listOAuth2Clients")
+        .summary("List OAuth 2.0 clients")
+        .tag("oauth2-client")
+        .response_with::<200, Json<PaginatedResponse<OAuth2Client>>, _>(|t| {
+            let clients = OAuth2Client::samples();
+            let pagination = mas_storage::Pagination::first(clients.len());
+            let page = Page {
+                edges: clients.into(),
+                has_next_page: true,
+                has_previous_page: false,
+            };
+
+            t.description("Paginated response of OAuth 2.0 clients")
+                .example(PaginatedResponse::new(
+                    page,
+                    pagination,
+                    42,
+                    OAuth2Client::PATH,
+                ))
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.oauth2_clients.list", skip_all, err)]
+pub async fn handler(
+    CallContext { mut repo, .. }: CallContext,
+    Pagination(pagination): Pagination,
+    params: FilterParams,
+) -> Result<Json<PaginatedResponse<OAuth2Client>>, RouteError> {
+    let base = format!("{path}{params}", path = OAuth2Client::PATH);
+    let filter = OAuth2ClientFilter::new();
+
+    let filter = match params.is_static {
+        Some(true) => filter.static_only(),
+        Some(false) => filter.dynamic_only(),
+        None => filter,
+    };
+
+    let page = repo.oauth2_client().list(filter, pagination).await?;
+    let count = repo.oauth2_client().count(filter).await?;
+
+    let edges = load_clients(&mut repo, page.edges).await?;
+
+    let page = Page {
+        edges,
+        has_next_page: page.has_next_page,
+        has_previous_page: page.has_previous_page,
+    };
+
+    Ok(Json(PaginatedResponse::new(page, pagination, count, &base)))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use sqlx::PgPool;
+    use ulid::Ulid;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_list(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        // This registers a dynamic client with one active session
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let mut repo = state.repository().await.unwrap();
+        repo.oauth2_client()
+            .upsert_static(
+                Ulid::from_bytes([0x01; 16]),
+                OAuthClientAuthenticationMethod::None,
+                None,
+                None,
+                None,
+                vec!["https://example.com/callback".parse().unwrap()],
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/api/admin/v1/oauth2-clients")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 2);
+
+        let request = Request::get("/api/admin/v1/oauth2-clients?filter[static]=true")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+        assert_eq!(body["data"][0]["id"], "01040G2081040G2081040G2081");
+        assert_eq!(body["data"][0]["attributes"]["is_static"], true);
+        assert_eq!(body["data"][0]["attributes"]["active_session_count"], 0);
+
+        let request = Request::get("/api/admin/v1/oauth2-clients?filter[static]=false")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+        assert_eq!(body["data"][0]["attributes"]["is_static"], false);
+        assert_eq!(body["data"][0]["attributes"]["active_session_count"], 1);
+        assert_eq!(
+            body["links"]["self"],
+            "/api/admin/v1/oauth2-clients?filter[static]=false&page[first]=10"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeSet;
+
+use mas_data_model::Client;
+use mas_keystore::Encrypter;
+use mas_storage::{oauth2::OAuth2SessionFilter, BoxRepository, RepositoryError};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    RngCore,
+};
+
+use crate::admin::model::OAuth2Client;
+
+mod add;
+mod delete;
+mod get;
+mod list;
+mod request;
+mod rotate_secret;
+mod update;
+
+pub use self::{
+    add::{doc as add_doc, handler as add},
+    delete::{doc as delete_doc, handler as delete},
+    get::{doc as get_doc, handler as get},
+    list::{doc as list_doc, handler as list},
+    rotate_secret::{doc as rotate_secret_doc, handler as rotate_secret},
+    update::{doc as update_doc, handler as update},
+};
+
+/// Generate a new random client secret
+///
+/// Returns both the plaintext secret, which is only ever shown once to the
+/// caller, and its encrypted form, which is stored in the database.
+fn generate_client_secret(
+    rng: &mut (impl RngCore + ?Sized),
+    encrypter: &Encrypter,
+) -> Result<(String, String), mas_keystore::aead::Error> {
+    let client_secret = Alphanumeric.sample_string(rng, 20);
+    let encrypted_client_secret = encrypter.encrypt_to_string(client_secret.as_bytes())?;
+    Ok((client_secret, encrypted_client_secret))
+}
+
+/// Build the API representation of a client, counting its active sessions
+async fn load_client(
+    repo: &mut BoxRepository,
+    client: Client,
+) -> Result<OAuth2Client, RepositoryError> {
+    let filter = OAuth2SessionFilter::new().for_client(&client).active_only();
+    let active_session_count = repo.oauth2_session().count(filter).await?;
+    Ok(OAuth2Client::from((client, active_session_count)))
+}
+
+/// Build the API representation of a batch of clients, counting their active
+/// sessions in a single query
+async fn load_clients(
+    repo: &mut BoxRepository,
+    clients: Vec<Client>,
+) -> Result<Vec<OAuth2Client>, RepositoryError> {
+    let ids: BTreeSet<_> = clients.iter().map(|client| client.id).collect();
+    let counts = repo.oauth2_session().count_active_by_clients(ids).await?;
+    Ok(clients
+        .into_iter()
+        .map(|client| {
+            let active_session_count = counts.get(&client.id).copied().unwrap_or(0);
+            OAuth2Client::from((client, active_session_count))
+        })
+        .collect())
+}
@@ -0,0 +1,169 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use mas_config::ClientAuthMethodConfig;
+use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
+use mas_jose::jwk::PublicJsonWebKeySet;
+use oauth2_types::requests::GrantType;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidClientError {
+    #[error("Grant type {0} is not supported")]
+    UnsupportedGrantType(GrantType),
+
+    #[error("At least one redirect URI is required for the authorization_code grant")]
+    MissingRedirectUri,
+
+    #[error("Clients with the none authentication method can't use the client_credentials grant")]
+    PublicClientCredentials,
+
+    #[error("A JWKS or a JWKS URI is required for the private_key_jwt authentication method")]
+    MissingJwks,
+
+    #[error("A JWKS or a JWKS URI can't be set with the selected authentication method")]
+    UnexpectedJwks,
+
+    #[error("The JWKS and the JWKS URI are mutually exclusive")]
+    JwksAndJwksUri,
+
+    #[error("A signing algorithm can't be set with the selected authentication method")]
+    UnexpectedSigningAlg,
+}
+
+fn default_grant_types() -> Vec<GrantType> {
+    vec![GrantType::AuthorizationCode, GrantType::RefreshToken]
+}
+
+/// # JSON payload for creating or updating an OAuth 2.0 client
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename = "OAuth2ClientRequest")]
+pub struct Request {
+    /// A human-readable name for the client
+    #[serde(default)]
+    pub client_name: Option<String>,
+
+    /// The URIs the client is allowed to redirect to during the authorization
+    /// flow
+    #[serde(default)]
+    pub redirect_uris: Vec<Url>,
+
+    /// The grant types the client is allowed to use
+    ///
+    /// Defaults to `authorization_code` and `refresh_token`
+    #[serde(default = "default_grant_types")]
+    #[schemars(with = "Vec<String>")]
+    pub grant_types: Vec<GrantType>,
+
+    /// The method used by the client to authenticate to the token endpoint
+    ///
+    /// A client secret is generated for the `client_secret_basic`,
+    /// `client_secret_post` and `client_secret_jwt` methods.
+    pub token_endpoint_auth_method: ClientAuthMethodConfig,
+
+    /// The JWS algorithm the client must use when authenticating with the
+    /// `client_secret_jwt` or `private_key_jwt` methods
+    ///
+    /// If not set, any supported algorithm is accepted
+    #[serde(default)]
+    pub token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+
+    /// The JSON Web Key Set used by the `private_key_jwt` method. Mutually
+    /// exclusive with `jwks_uri`
+    #[serde(default)]
+    pub jwks: Option<PublicJsonWebKeySet>,
+
+    /// The URL of the JSON Web Key Set used by the `private_key_jwt` method.
+    /// Mutually exclusive with `jwks`
+    #[serde(default)]
+    pub jwks_uri: Option<Url>,
+}
+
+impl Request {
+    /// The authentication method requested for the client
+    pub fn auth_method(&self) -> OAuthClientAuthenticationMethod {
+        match self.token_endpoint_auth_method {
+            ClientAuthMethodConfig::None => OAuthClientAuthenticationMethod::None,
+            ClientAuthMethodConfig::ClientSecretBasic => {
+                OAuthClientAuthenticationMethod::ClientSecretBasic
+            }
+            ClientAuthMethodConfig::ClientSecretPost => {
+                OAuthClientAuthenticationMethod::ClientSecretPost
+            }
+            ClientAuthMethodConfig::ClientSecretJwt => {
+                OAuthClientAuthenticationMethod::ClientSecretJwt
+            }
+            ClientAuthMethodConfig::PrivateKeyJwt => OAuthClientAuthenticationMethod::PrivateKeyJwt,
+        }
+    }
+
+    /// Whether the requested authentication method needs a client secret
+    pub fn uses_client_secret(&self) -> bool {
+        matches!(
+            self.token_endpoint_auth_method,
+            ClientAuthMethodConfig::ClientSecretBasic
+                | ClientAuthMethodConfig::ClientSecretPost
+                | ClientAuthMethodConfig::ClientSecretJwt
+        )
+    }
+
+    /// Check that the request describes a valid client
+    pub fn validate(&self) -> Result<(), InvalidClientError> {
+        for grant_type in &self.grant_types {
+            if !matches!(
+                grant_type,
+                GrantType::AuthorizationCode
+                    | GrantType::RefreshToken
+                    | GrantType::ClientCredentials
+                    | GrantType::DeviceCode
+                    | GrantType::TokenExchange
+                    | GrantType::JwtBearer
+            ) {
+                return Err(InvalidClientError::UnsupportedGrantType(grant_type.clone()));
+            }
+        }
+
+        if self.grant_types.contains(&GrantType::AuthorizationCode) && self.redirect_uris.is_empty()
+        {
+            return Err(InvalidClientError::MissingRedirectUri);
+        }
+
+        if matches!(
+            self.token_endpoint_auth_method,
+            ClientAuthMethodConfig::None
+        ) && self.grant_types.contains(&GrantType::ClientCredentials)
+        {
+            return Err(InvalidClientError::PublicClientCredentials);
+        }
+
+        let has_jwks = self.jwks.is_some() || self.jwks_uri.is_some();
+        if matches!(
+            self.token_endpoint_auth_method,
+            ClientAuthMethodConfig::PrivateKeyJwt
+        ) {
+            if !has_jwks {
+                return Err(InvalidClientError::MissingJwks);
+            }
+        } else if has_jwks {
+            return Err(InvalidClientError::UnexpectedJwks);
+        }
+
+        if self.jwks.is_some() && self.jwks_uri.is_some() {
+            return Err(InvalidClientError::JwksAndJwksUri);
+        }
+
+        if !matches!(
+            self.token_endpoint_auth_method,
+            ClientAuthMethodConfig::ClientSecretJwt | ClientAuthMethodConfig::PrivateKeyJwt
+        ) && self.token_endpoint_auth_signing_alg.is_some()
+        {
+            return Err(InvalidClientError::UnexpectedSigningAlg);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, NoApi, OperationIo};
+use axum::{extract::State, response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_iana::oauth::OAuthClientAuthenticationMethod;
+use mas_keystore::Encrypter;
+use mas_storage::BoxRng;
+use tracing::info;
+use ulid::Ulid;
+
+use super::{generate_client_secret, load_client};
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{OAuth2Client, Resource},
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("OAuth 2.0 client ID {0} is managed through the configuration file")]
+    ManagedByConfig(Ulid),
+
+    #[error("OAuth 2.0 client ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error("OAuth 2.0 client ID {0} does not use a client secret")]
+    NoClientSecret(Ulid),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_keystore::aead::Error);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ManagedByConfig(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::NoClientSecret(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("rotateOAuth2ClientSecret")
+        .summary("Rotate the secret of an OAuth 2.0 client")
+        .description(
+            "Generate a new client secret, which replaces the current one immediately.
+The new secret is returned in the `client_secret` attribute, and can't be retrieved afterwards.
+Clients managed through the configuration file can't have their secret rotated.",
+        )
+        .tag("oauth2-client")
+        .response_with::<200, Json<SingleResponse<OAuth2Client>>, _>(|t| {
+            let [sample, ..] = OAuth2Client::samples();
+            let id = sample.id();
+            let sample = sample.with_client_secret("fJ3hXqGkY8vTz2LwR9sA".to_owned());
+            let response = SingleResponse::new(
+                sample,
+                format!("/api/admin/v1/oauth2-clients/{id}/rotate-secret"),
+            );
+            t.description("The client secret was rotated")
+                .example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NoClientSecret(Ulid::nil()));
+            t.description("The client does not use a client secret")
+                .example(response)
+        })
+        .response_with::<403, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::ManagedByConfig(Ulid::nil()));
+            t.description("Client is managed through the configuration file")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("OAuth 2.0 client was not found")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.oauth2_clients.rotate_secret", skip_all, err)]
+pub async fn handler(
+    CallContext { mut repo, .. }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
+    State(encrypter): State<Encrypter>,
+    id: UlidPathParam,
+) -> Result<Json<SingleResponse<OAuth2Client>>, RouteError> {
+    let id = *id;
+    let client = repo
+        .oauth2_client()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    if client.is_static {
+        return Err(RouteError::ManagedByConfig(id));
+    }
+
+    if !matches!(
+        client.token_endpoint_auth_method,
+        Some(
+            OAuthClientAuthenticationMethod::ClientSecretBasic
+                | OAuthClientAuthenticationMethod::ClientSecretPost
+                | OAuthClientAuthenticationMethod::ClientSecretJwt
+        )
+    ) {
+        return Err(RouteError::NoClientSecret(id));
+    }
+
+    let (client_secret, encrypted_client_secret) = generate_client_secret(&mut rng, &encrypter)?;
+    let client = repo
+        .oauth2_client()
+        .set_client_secret(client, Some(encrypted_client_secret))
+        .await?;
+
+    let client = load_client(&mut repo, client).await?;
+
+    repo.save().await?;
+
+    info!(client.id = %id, "Rotated the OAuth 2.0 client secret");
+
+    Ok(Json(SingleResponse::new(
+        client.with_client_secret(client_secret),
+        format!("/api/admin/v1/oauth2-clients/{id}/rotate-secret"),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_router::SimpleRoute;
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_rotate_secret(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/oauth2-clients")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "grant_types": ["client_credentials"],
+                "token_endpoint_auth_method": "client_secret_post",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let id = body["data"]["id"].as_str().unwrap().to_owned();
+        let old_secret = body["data"]["attributes"]["client_secret"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+
+        let request = Request::post(format!("/api/admin/v1/oauth2-clients/{id}/rotate-secret"))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let new_secret = body["data"]["attributes"]["client_secret"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+        assert_ne!(old_secret, new_secret);
+
+        // The old secret should not work anymore
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": id,
+                "client_secret": old_secret,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // But the new one does
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": id,
+                "client_secret": new_secret,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_rotate_secret_public_client(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/oauth2-clients")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "redirect_uris": ["https://app.example.com/callback"],
+                "token_endpoint_auth_method": "none",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let id = body["data"]["id"].as_str().unwrap().to_owned();
+
+        let request = Request::post(format!("/api/admin/v1/oauth2-clients/{id}/rotate-secret"))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,314 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, NoApi, OperationIo};
+use axum::{extract::State, response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_keystore::Encrypter;
+use mas_storage::BoxRng;
+use tracing::info;
+use ulid::Ulid;
+
+use super::{
+    generate_client_secret, load_client,
+    request::{InvalidClientError, Request},
+};
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::OAuth2Client,
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("OAuth 2.0 client ID {0} is managed through the configuration file")]
+    ManagedByConfig(Ulid),
+
+    #[error("OAuth 2.0 client ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error(transparent)]
+    InvalidClient(#[from] InvalidClientError),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_keystore::aead::Error);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ManagedByConfig(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::InvalidClient(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("updateOAuth2Client")
+        .summary("Update an OAuth 2.0 client")
+        .description("Replace the settings of an OAuth 2.0 client.
+The existing client secret is kept if the new authentication method uses one, and removed otherwise.
+If the client had no secret and the new authentication method needs one, a secret is generated and returned in the `client_secret` attribute.
+Clients managed through the configuration file can't be updated.")
+        .tag("oauth2-client")
+        .response_with::<200, Json<SingleResponse<OAuth2Client>>, _>(|t| {
+            let [sample, ..] = OAuth2Client::samples();
+            let response = SingleResponse::new_canonical(sample);
+            t.description("OAuth 2.0 client was updated")
+                .example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::InvalidClient(
+                InvalidClientError::MissingRedirectUri,
+            ));
+            t.description("Client parameters are invalid")
+                .example(response)
+        })
+        .response_with::<403, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::ManagedByConfig(Ulid::nil()));
+            t.description("Client is managed through the configuration file")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("OAuth 2.0 client was not found")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.oauth2_clients.update", skip_all, err)]
+pub async fn handler(
+    CallContext { mut repo, .. }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
+    State(encrypter): State<Encrypter>,
+    id: UlidPathParam,
+    Json(params): Json<Request>,
+) -> Result<Json<SingleResponse<OAuth2Client>>, RouteError> {
+    let id = *id;
+    let client = repo
+        .oauth2_client()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    if client.is_static {
+        return Err(RouteError::ManagedByConfig(id));
+    }
+
+    params.validate()?;
+
+    // Figure out what should happen to the client secret
+    let mut new_client_secret = None;
+    let encrypted_client_secret = if params.uses_client_secret() {
+        if client.encrypted_client_secret.is_some() {
+            client.encrypted_client_secret.clone()
+        } else {
+            let (client_secret, encrypted_client_secret) =
+                generate_client_secret(&mut rng, &encrypter)?;
+            new_client_secret = Some(client_secret);
+            Some(encrypted_client_secret)
+        }
+    } else {
+        None
+    };
+
+    let token_endpoint_auth_method = params.auth_method();
+    let mut client = repo
+        .oauth2_client()
+        .update(
+            client,
+            params.client_name,
+            params.redirect_uris,
+            params.grant_types,
+            token_endpoint_auth_method,
+            params.token_endpoint_auth_signing_alg,
+            params.jwks,
+            params.jwks_uri,
+        )
+        .await?;
+
+    if client.encrypted_client_secret != encrypted_client_secret {
+        client = repo
+            .oauth2_client()
+            .set_client_secret(client, encrypted_client_secret)
+            .await?;
+    }
+
+    let mut client = load_client(&mut repo, client).await?;
+
+    repo.save().await?;
+
+    info!(client.id = %id, "Updated OAuth 2.0 client");
+
+    if let Some(client_secret) = new_client_secret {
+        client = client.with_client_secret(client_secret);
+    }
+
+    Ok(Json(SingleResponse::new_canonical(client)))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_storage::{oauth2::OAuth2ClientRepository, RepositoryAccess};
+    use sqlx::PgPool;
+    use ulid::Ulid;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_update_client(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/oauth2-clients")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "redirect_uris": ["https://app.example.com/callback"],
+                "token_endpoint_auth_method": "client_secret_post",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let id = body["data"]["id"].as_str().unwrap().to_owned();
+
+        let mut repo = state.repository().await.unwrap();
+        let client = repo
+            .oauth2_client()
+            .lookup(id.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        let encrypted_client_secret = client.encrypted_client_secret.unwrap();
+        repo.save().await.unwrap();
+
+        // Changing to another secret-based method keeps the secret
+        let request = Request::put(format!("/api/admin/v1/oauth2-clients/{id}"))
+            .bearer(&token)
+            .json(serde_json::json!({
+                "client_name": "Renamed",
+                "redirect_uris": ["https://app.example.com/other-callback"],
+                "grant_types": ["authorization_code", "client_credentials"],
+                "token_endpoint_auth_method": "client_secret_basic",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["attributes"]["client_name"], "Renamed");
+        assert_eq!(
+            body["data"]["attributes"]["redirect_uris"],
+            serde_json::json!(["https://app.example.com/other-callback"])
+        );
+        assert_eq!(
+            body["data"]["attributes"]["token_endpoint_auth_method"],
+            "client_secret_basic"
+        );
+        assert!(body["data"]["attributes"].get("client_secret").is_none());
+
+        let mut repo = state.repository().await.unwrap();
+        let client = repo
+            .oauth2_client()
+            .lookup(id.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            client.encrypted_client_secret,
+            Some(encrypted_client_secret)
+        );
+        repo.save().await.unwrap();
+
+        // Switching to private_key_jwt removes the secret
+        let request = Request::put(format!("/api/admin/v1/oauth2-clients/{id}"))
+            .bearer(&token)
+            .json(serde_json::json!({
+                "grant_types": ["client_credentials"],
+                "token_endpoint_auth_method": "private_key_jwt",
+                "jwks_uri": "https://app.example.com/jwks.json",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["attributes"]["has_client_secret"], false);
+        assert_eq!(
+            body["data"]["attributes"]["jwks_uri"],
+            "https://app.example.com/jwks.json"
+        );
+
+        // Switching back to a secret-based method generates a new secret
+        let request = Request::put(format!("/api/admin/v1/oauth2-clients/{id}"))
+            .bearer(&token)
+            .json(serde_json::json!({
+                "grant_types": ["client_credentials"],
+                "token_endpoint_auth_method": "client_secret_post",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["attributes"]["has_client_secret"], true);
+        assert!(body["data"]["attributes"]["client_secret"].is_string());
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_update_static_client(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let mut repo = state.repository().await.unwrap();
+        let client = repo
+            .oauth2_client()
+            .upsert_static(
+                Ulid::from_bytes([0x01; 16]),
+                OAuthClientAuthenticationMethod::None,
+                None,
+                None,
+                None,
+                vec!["https://example.com/callback".parse().unwrap()],
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::put(format!("/api/admin/v1/oauth2-clients/{}", client.id))
+            .bearer(&token)
+            .json(serde_json::json!({
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "none",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["errors"][0]["title"],
+            "OAuth 2.0 client ID 01040G2081040G2081040G2081 is managed through the configuration file"
+        );
+    }
+}
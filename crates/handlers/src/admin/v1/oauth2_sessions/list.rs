@@ -316,7 +316,8 @@ mod tests {
                 "scope": "urn:mas:admin",
                 "user_agent": null,
                 "last_active_at": null,
-                "last_active_ip": null
+                "last_active_ip": null,
+                "grant_type": "client_credentials"
               },
               "links": {
                 "self": "/api/admin/v1/oauth2-sessions/01FSHN9AG0MKGTBNZ16RDR3PVY"
@@ -0,0 +1,44 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+mod get;
+mod list;
+
+pub use self::{
+    get::{doc as get_doc, handler as get},
+    list::{doc as list_doc, handler as list},
+};
+
+#[cfg(test)]
+mod test_utils {
+    use mas_data_model::{
+        UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
+        UpstreamOAuthProviderPkceMode,
+    };
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_storage::upstream_oauth2::UpstreamOAuthProviderParams;
+    use oauth2_types::scope::{Scope, OPENID};
+
+    pub fn oidc_provider_params(name: &str) -> UpstreamOAuthProviderParams {
+        UpstreamOAuthProviderParams {
+            issuer: format!("https://{name}.example.com/"),
+            human_name: Some(name.to_owned()),
+            brand_name: Some(name.to_owned()),
+            scope: Some(Scope::from_iter([OPENID])),
+            token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::ClientSecretBasic),
+            token_endpoint_signing_alg: None,
+            client_id: Some("client".to_owned()),
+            encrypted_client_secret: None,
+            claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+            authorization_endpoint_override: None,
+            token_endpoint_override: None,
+            jwks_uri_override: None,
+            discovery_mode: UpstreamOAuthProviderDiscoveryMode::Oidc,
+            pkce_mode: UpstreamOAuthProviderPkceMode::Auto,
+            additional_authorization_parameters: Vec::new(),
+            saml: None,
+        }
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, NoApi, OperationIo};
+use axum::{extract::State, response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::SiteConfig;
+use mas_keystore::Encrypter;
+use mas_storage::BoxRng;
+use tracing::info;
+
+use super::request::{InvalidProviderError, Request};
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::UpstreamOAuthProvider,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Upstream OAuth 2.0 providers are managed through the configuration file")]
+    ManagedByConfig,
+
+    #[error(transparent)]
+    InvalidProvider(#[from] InvalidProviderError),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_keystore::aead::Error);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ManagedByConfig => StatusCode::FORBIDDEN,
+            Self::InvalidProvider(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("createUpstreamOAuthProvider")
+        .summary("Create a new upstream OAuth 2.0 provider")
+        .description("This endpoint is only available when `upstream_oauth2.allow_admin_api` is enabled in the configuration.")
+        .tag("upstream-oauth-provider")
+        .response_with::<200, Json<SingleResponse<UpstreamOAuthProvider>>, _>(|t| {
+            let [sample, ..] = UpstreamOAuthProvider::samples();
+            let response = SingleResponse::new_canonical(sample);
+            t.description("Upstream OAuth 2.0 provider was created")
+                .example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::InvalidProvider(
+                InvalidProviderError::MissingClientSecret,
+            ));
+            t.description("Provider parameters are invalid")
+                .example(response)
+        })
+        .response_with::<403, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::ManagedByConfig);
+            t.description("Providers can't be managed through the admin API")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.upstream_oauth_providers.add", skip_all, err)]
+pub async fn handler(
+    CallContext {
+        mut repo, clock, ..
+    }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
+    State(site_config): State<SiteConfig>,
+    State(encrypter): State<Encrypter>,
+    Json(params): Json<Request>,
+) -> Result<Json<SingleResponse<UpstreamOAuthProvider>>, RouteError> {
+    if !site_config.upstream_oauth2_admin_api_allowed {
+        return Err(RouteError::ManagedByConfig);
+    }
+
+    params.validate(false)?;
+    let params = params.into_params(&encrypter, None)?;
+
+    let provider = repo
+        .upstream_oauth_provider()
+        .add(&mut rng, &clock, params)
+        .await?;
+
+    repo.save().await?;
+
+    info!(provider.id = %provider.id, "Added upstream OAuth 2.0 provider");
+
+    Ok(Json(SingleResponse::new_canonical(
+        UpstreamOAuthProvider::from(provider),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_data_model::SiteConfig;
+    use mas_storage::{upstream_oauth2::UpstreamOAuthProviderRepository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, test_site_config, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_add_provider(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/upstream-oauth-providers")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "issuer": "https://idp.example.com/",
+                "human_name": "Example",
+                "client_id": "mas",
+                "client_secret": "hunter2",
+                "token_endpoint_auth_method": "client_secret_basic",
+                "scope": "openid profile",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["type"], "upstream-oauth-provider");
+        assert_eq!(
+            body["data"]["attributes"]["issuer"],
+            "https://idp.example.com/"
+        );
+        assert_eq!(body["data"]["attributes"]["has_client_secret"], true);
+        // The secret itself should never be returned
+        assert!(body["data"]["attributes"].get("client_secret").is_none());
+        let id = body["data"]["id"].as_str().unwrap();
+
+        // Check that the provider was created with an encrypted secret
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .lookup(id.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let encrypted = provider.encrypted_client_secret.unwrap();
+        assert_ne!(encrypted, "hunter2");
+        let decrypted = state.encrypter.decrypt_string(&encrypted).unwrap();
+        assert_eq!(decrypted, b"hunter2");
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_add_provider_missing_secret(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/upstream-oauth-providers")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "issuer": "https://idp.example.com/",
+                "client_id": "mas",
+                "token_endpoint_auth_method": "client_secret_post",
+                "scope": "openid",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["errors"][0]["title"],
+            "A client secret is required for the selected authentication method"
+        );
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_add_provider_managed_by_config(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool_with_site_config(
+            pool,
+            SiteConfig {
+                upstream_oauth2_admin_api_allowed: false,
+                ..test_site_config()
+            },
+        )
+        .await
+        .unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/upstream-oauth-providers")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "issuer": "https://idp.example.com/",
+                "client_id": "mas",
+                "token_endpoint_auth_method": "none",
+                "scope": "openid",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+    }
+}
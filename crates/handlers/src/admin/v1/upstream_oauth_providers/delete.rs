@@ -0,0 +1,192 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{extract::State, response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::SiteConfig;
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{
+    admin::{call_context::CallContext, params::UlidPathParam, response::ErrorResponse},
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Upstream OAuth 2.0 providers are managed through the configuration file")]
+    ManagedByConfig,
+
+    #[error("Upstream OAuth 2.0 provider ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error("Upstream OAuth 2.0 provider ID {0} is defined in the configuration file")]
+    DefinedInConfig(Ulid),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ManagedByConfig => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::DefinedInConfig(_) => StatusCode::CONFLICT,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("deleteUpstreamOAuthProvider")
+        .summary("Delete an upstream OAuth 2.0 provider")
+        .description("This permanently deletes the provider, along with all the links between users and this provider.
+Consider disabling the provider instead if those links should be kept.
+This endpoint is only available when `upstream_oauth2.allow_admin_api` is enabled in the configuration, and not for providers listed in the configuration file.")
+        .tag("upstream-oauth-provider")
+        .response_with::<204, (), _>(|t| t.description("Upstream OAuth 2.0 provider was deleted"))
+        .response_with::<403, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::ManagedByConfig);
+            t.description("Providers can't be managed through the admin API")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("Upstream OAuth 2.0 provider was not found")
+                .example(response)
+        })
+        .response_with::<409, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::DefinedInConfig(Ulid::nil()));
+            t.description("Upstream OAuth 2.0 provider is defined in the configuration file")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(
+    name = "handler.admin.v1.upstream_oauth_providers.delete",
+    skip_all,
+    err
+)]
+pub async fn handler(
+    CallContext { mut repo, .. }: CallContext,
+    State(site_config): State<SiteConfig>,
+    id: UlidPathParam,
+) -> Result<StatusCode, RouteError> {
+    if !site_config.upstream_oauth2_admin_api_allowed {
+        return Err(RouteError::ManagedByConfig);
+    }
+
+    let id = *id;
+    if site_config
+        .upstream_oauth2_config_provider_ids
+        .contains(&id)
+    {
+        return Err(RouteError::DefinedInConfig(id));
+    }
+
+    let provider = repo
+        .upstream_oauth_provider()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    repo.upstream_oauth_provider().delete(provider).await?;
+
+    repo.save().await?;
+
+    info!(provider.id = %id, "Deleted upstream OAuth 2.0 provider");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{upstream_oauth2::UpstreamOAuthProviderRepository, Clock, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_disable_and_delete_provider(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::post("/api/admin/v1/upstream-oauth-providers")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "issuer": "https://idp.example.com/",
+                "client_id": "mas",
+                "token_endpoint_auth_method": "none",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let id = body["data"]["id"].as_str().unwrap().to_owned();
+
+        // Disable it
+        let request = Request::post(format!(
+            "/api/admin/v1/upstream-oauth-providers/{id}/disable"
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["data"]["attributes"]["disabled_at"],
+            serde_json::json!(state.clock.now())
+        );
+
+        // It should only be listed with the disabled filter
+        let request = Request::get("/api/admin/v1/upstream-oauth-providers?filter[enabled]=true")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 0);
+
+        let request = Request::get("/api/admin/v1/upstream-oauth-providers?filter[enabled]=false")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+
+        // Then delete it
+        let request = Request::delete(format!("/api/admin/v1/upstream-oauth-providers/{id}"))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NO_CONTENT);
+
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .lookup(id.parse().unwrap())
+            .await
+            .unwrap();
+        assert!(provider.is_none());
+
+        // Deleting it again should fail
+        let request = Request::delete(format!("/api/admin/v1/upstream-oauth-providers/{id}"))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{extract::State, response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::SiteConfig;
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{Resource, UpstreamOAuthProvider},
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Upstream OAuth 2.0 providers are managed through the configuration file")]
+    ManagedByConfig,
+
+    #[error("Upstream OAuth 2.0 provider ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error("Upstream OAuth 2.0 provider ID {0} is defined in the configuration file")]
+    DefinedInConfig(Ulid),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ManagedByConfig => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::DefinedInConfig(_) => StatusCode::CONFLICT,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("disableUpstreamOAuthProvider")
+        .summary("Disable an upstream OAuth 2.0 provider")
+        .description("Disabling a provider hides it from the login page and prevents users from using it.
+Existing links to this provider are kept, and the provider can be enabled again by updating it.
+This endpoint is only available when `upstream_oauth2.allow_admin_api` is enabled in the configuration, and not for providers listed in the configuration file.")
+        .tag("upstream-oauth-provider")
+        .response_with::<200, Json<SingleResponse<UpstreamOAuthProvider>>, _>(|t| {
+            // In the samples, the second provider is the one disabled
+            let [_google, example] = UpstreamOAuthProvider::samples();
+            let id = example.id();
+            let response = SingleResponse::new(
+                example,
+                format!("/api/admin/v1/upstream-oauth-providers/{id}/disable"),
+            );
+            t.description("Upstream OAuth 2.0 provider was disabled")
+                .example(response)
+        })
+        .response_with::<403, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::ManagedByConfig);
+            t.description("Providers can't be managed through the admin API")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("Upstream OAuth 2.0 provider was not found")
+                .example(response)
+        })
+        .response_with::<409, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::DefinedInConfig(Ulid::nil()));
+            t.description("Upstream OAuth 2.0 provider is defined in the configuration file")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(
+    name = "handler.admin.v1.upstream_oauth_providers.disable",
+    skip_all,
+    err
+)]
+pub async fn handler(
+    CallContext {
+        mut repo, clock, ..
+    }: CallContext,
+    State(site_config): State<SiteConfig>,
+    id: UlidPathParam,
+) -> Result<Json<SingleResponse<UpstreamOAuthProvider>>, RouteError> {
+    if !site_config.upstream_oauth2_admin_api_allowed {
+        return Err(RouteError::ManagedByConfig);
+    }
+
+    let id = *id;
+    if site_config
+        .upstream_oauth2_config_provider_ids
+        .contains(&id)
+    {
+        return Err(RouteError::DefinedInConfig(id));
+    }
+
+    let mut provider = repo
+        .upstream_oauth_provider()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    if provider.enabled() {
+        provider = repo
+            .upstream_oauth_provider()
+            .disable(&clock, provider)
+            .await?;
+
+        info!(provider.id = %provider.id, "Disabled upstream OAuth 2.0 provider");
+    }
+
+    repo.save().await?;
+
+    Ok(Json(SingleResponse::new(
+        UpstreamOAuthProvider::from(provider),
+        format!("/api/admin/v1/upstream-oauth-providers/{id}/disable"),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_data_model::{
+        SiteConfig, UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
+        UpstreamOAuthProviderPkceMode,
+    };
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_storage::{
+        upstream_oauth2::{UpstreamOAuthProviderParams, UpstreamOAuthProviderRepository},
+        Clock, RepositoryAccess,
+    };
+    use oauth2_types::scope::{Scope, OPENID};
+    use sqlx::PgPool;
+    use ulid::Ulid;
+
+    use crate::test_utils::{setup, test_site_config, RequestBuilderExt, ResponseExt, TestState};
+
+    fn params() -> UpstreamOAuthProviderParams {
+        UpstreamOAuthProviderParams {
+            issuer: "https://idp.example.com/".to_owned(),
+            human_name: None,
+            brand_name: None,
+            scope: Some(Scope::from_iter([OPENID])),
+            token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::None),
+            token_endpoint_signing_alg: None,
+            client_id: Some("mas".to_owned()),
+            encrypted_client_secret: None,
+            claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+            authorization_endpoint_override: None,
+            token_endpoint_override: None,
+            jwks_uri_override: None,
+            discovery_mode: UpstreamOAuthProviderDiscoveryMode::Oidc,
+            pkce_mode: UpstreamOAuthProviderPkceMode::Auto,
+            additional_authorization_parameters: Vec::new(),
+            saml: None,
+        }
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_disable_provider(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(&mut state.rng(), &state.clock, params())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::post(format!(
+            "/api/admin/v1/upstream-oauth-providers/{}/disable",
+            provider.id
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let disabled_at = serde_json::json!(state.clock.now());
+        assert_eq!(body["data"]["attributes"]["disabled_at"], disabled_at);
+
+        // Disabling it again is a no-op, which keeps the original timestamp
+        state
+            .clock
+            .advance(chrono::Duration::try_minutes(1).unwrap());
+        let request = Request::post(format!(
+            "/api/admin/v1/upstream-oauth-providers/{}/disable",
+            provider.id
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["attributes"]["disabled_at"], disabled_at);
+
+        // Unknown providers are reported as such
+        let request = Request::post(
+            "/api/admin/v1/upstream-oauth-providers/01040G2081040G2081040G2081/disable",
+        )
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_disable_provider_defined_in_config(pool: PgPool) {
+        setup();
+        let id = Ulid::from_string("01040G2081040G2081040G2081").unwrap();
+        let mut state = TestState::from_pool_with_site_config(
+            pool,
+            SiteConfig {
+                upstream_oauth2_config_provider_ids: [id].into(),
+                ..test_site_config()
+            },
+        )
+        .await
+        .unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let mut repo = state.repository().await.unwrap();
+        repo.upstream_oauth_provider()
+            .upsert(&state.clock, id, params())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::post(format!(
+            "/api/admin/v1/upstream-oauth-providers/{id}/disable"
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CONFLICT);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["errors"][0]["title"],
+            "Upstream OAuth 2.0 provider ID 01040G2081040G2081040G2081 is defined in the configuration file"
+        );
+
+        // The provider is still enabled
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .lookup(id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(provider.enabled());
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_disable_provider_managed_by_config(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool_with_site_config(
+            pool,
+            SiteConfig {
+                upstream_oauth2_admin_api_allowed: false,
+                ..test_site_config()
+            },
+        )
+        .await
+        .unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(&mut state.rng(), &state.clock, params())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::post(format!(
+            "/api/admin/v1/upstream-oauth-providers/{}/disable",
+            provider.id
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeMap;
+
+use mas_config::{
+    UpstreamOAuth2ClaimsImports, UpstreamOAuth2DiscoveryMode, UpstreamOAuth2PkceMethod,
+    UpstreamOAuth2TokenAuthMethod,
+};
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_keystore::Encrypter;
+use mas_storage::upstream_oauth2::UpstreamOAuthProviderParams;
+use oauth2_types::scope::Scope;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::upstream_oauth2::{map_claims_imports, map_discovery_mode, map_pkce_method};
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidProviderError {
+    #[error("A client secret is required for the selected authentication method")]
+    MissingClientSecret,
+
+    #[error("A client secret can't be set with the selected authentication method")]
+    UnexpectedClientSecret,
+
+    #[error("A signing algorithm is required for the selected authentication method")]
+    MissingSigningAlg,
+
+    #[error("A signing algorithm can't be set with the selected authentication method")]
+    UnexpectedSigningAlg,
+
+    #[error("The authorization endpoint, token endpoint and JWKS URI are required when discovery is disabled")]
+    MissingEndpoints,
+}
+
+/// # JSON payload for creating or updating an upstream OAuth 2.0 provider
+///
+/// This has the same shape as a provider in the `upstream_oauth2` section of
+/// the configuration file.
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename = "UpstreamOAuthProviderRequest")]
+pub struct Request {
+    /// The OIDC issuer URL
+    issuer: String,
+
+    /// A human-readable name for the provider, that will be shown to users
+    #[serde(default)]
+    human_name: Option<String>,
+
+    /// A brand identifier used to customise the UI, e.g. `apple`, `google`,
+    /// `github`, etc.
+    #[serde(default)]
+    brand_name: Option<String>,
+
+    /// The client ID to use when authenticating with the provider
+    client_id: String,
+
+    /// The client secret to use when authenticating with the provider
+    ///
+    /// Used by the `client_secret_basic`, `client_secret_post`, and
+    /// `client_secret_jwt` methods. When updating a provider, the existing
+    /// secret is kept if this is omitted.
+    #[serde(default)]
+    client_secret: Option<String>,
+
+    /// The method to authenticate the client with the provider
+    token_endpoint_auth_method: UpstreamOAuth2TokenAuthMethod,
+
+    /// The JWS algorithm to use when authenticating the client with the
+    /// provider
+    ///
+    /// Used by the `client_secret_jwt` and `private_key_jwt` methods
+    #[serde(default)]
+    token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+
+    /// The scopes to request from the provider
+    #[schemars(with = "String")]
+    scope: Scope,
+
+    /// How to discover the provider's configuration
+    ///
+    /// Defaults to `oidc`, which uses OIDC discovery with strict metadata
+    /// verification
+    #[serde(default)]
+    discovery_mode: UpstreamOAuth2DiscoveryMode,
+
+    /// Whether to use proof key for code exchange (PKCE) when requesting and
+    /// exchanging the token.
+    ///
+    /// Defaults to `auto`, which uses PKCE if the provider supports it.
+    #[serde(default)]
+    pkce_method: UpstreamOAuth2PkceMethod,
+
+    /// The URL to use for the provider's authorization endpoint
+    ///
+    /// Defaults to the `authorization_endpoint` provided through discovery
+    #[serde(default)]
+    authorization_endpoint: Option<Url>,
+
+    /// The URL to use for the provider's token endpoint
+    ///
+    /// Defaults to the `token_endpoint` provided through discovery
+    #[serde(default)]
+    token_endpoint: Option<Url>,
+
+    /// The URL to use for getting the provider's public keys
+    ///
+    /// Defaults to the `jwks_uri` provided through discovery
+    #[serde(default)]
+    jwks_uri: Option<Url>,
+
+    /// How claims should be imported from the `id_token` provided by the
+    /// provider
+    #[serde(default)]
+    claims_imports: UpstreamOAuth2ClaimsImports,
+
+    /// Additional parameters to include in the authorization request
+    #[serde(default)]
+    additional_authorization_parameters: BTreeMap<String, String>,
+}
+
+impl Request {
+    fn uses_client_secret(&self) -> bool {
+        matches!(
+            self.token_endpoint_auth_method,
+            UpstreamOAuth2TokenAuthMethod::ClientSecretBasic
+                | UpstreamOAuth2TokenAuthMethod::ClientSecretPost
+                | UpstreamOAuth2TokenAuthMethod::ClientSecretJwt
+        )
+    }
+
+    fn uses_signing_alg(&self) -> bool {
+        matches!(
+            self.token_endpoint_auth_method,
+            UpstreamOAuth2TokenAuthMethod::ClientSecretJwt
+                | UpstreamOAuth2TokenAuthMethod::PrivateKeyJwt
+        )
+    }
+
+    /// Check that the request describes a valid provider
+    ///
+    /// `has_existing_client_secret` tells whether the provider being updated
+    /// already has a client secret, which is kept if none is provided.
+    pub fn validate(&self, has_existing_client_secret: bool) -> Result<(), InvalidProviderError> {
+        if self.uses_client_secret() {
+            if self.client_secret.is_none() && !has_existing_client_secret {
+                return Err(InvalidProviderError::MissingClientSecret);
+            }
+        } else if self.client_secret.is_some() {
+            return Err(InvalidProviderError::UnexpectedClientSecret);
+        }
+
+        if self.uses_signing_alg() {
+            if self.token_endpoint_auth_signing_alg.is_none() {
+                return Err(InvalidProviderError::MissingSigningAlg);
+            }
+        } else if self.token_endpoint_auth_signing_alg.is_some() {
+            return Err(InvalidProviderError::UnexpectedSigningAlg);
+        }
+
+        if matches!(self.discovery_mode, UpstreamOAuth2DiscoveryMode::Disabled)
+            && (self.authorization_endpoint.is_none()
+                || self.token_endpoint.is_none()
+                || self.jwks_uri.is_none())
+        {
+            return Err(InvalidProviderError::MissingEndpoints);
+        }
+
+        Ok(())
+    }
+
+    /// Convert the request to the parameters used by the repository,
+    /// encrypting the client secret on the way.
+    ///
+    /// This assumes the request was [validated](Self::validate) beforehand.
+    pub fn into_params(
+        self,
+        encrypter: &Encrypter,
+        existing_encrypted_client_secret: Option<String>,
+    ) -> Result<UpstreamOAuthProviderParams, mas_keystore::aead::Error> {
+        let encrypted_client_secret = if self.uses_client_secret() {
+            match self.client_secret {
+                Some(client_secret) => Some(encrypter.encrypt_to_string(client_secret.as_bytes())?),
+                None => existing_encrypted_client_secret,
+            }
+        } else {
+            None
+        };
+
+        Ok(UpstreamOAuthProviderParams {
+            issuer: self.issuer,
+            human_name: self.human_name,
+            brand_name: self.brand_name,
+            scope: Some(self.scope),
+            token_endpoint_auth_method: Some(self.token_endpoint_auth_method.into()),
+            token_endpoint_signing_alg: self.token_endpoint_auth_signing_alg,
+            client_id: Some(self.client_id),
+            encrypted_client_secret,
+            claims_imports: map_claims_imports(&self.claims_imports),
+            authorization_endpoint_override: self.authorization_endpoint,
+            token_endpoint_override: self.token_endpoint,
+            jwks_uri_override: self.jwks_uri,
+            discovery_mode: map_discovery_mode(self.discovery_mode),
+            pkce_mode: map_pkce_method(self.pkce_method),
+            additional_authorization_parameters: self
+                .additional_authorization_parameters
+                .into_iter()
+                .collect(),
+            saml: None,
+        })
+    }
+}
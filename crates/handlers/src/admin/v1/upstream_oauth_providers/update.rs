@@ -0,0 +1,277 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{extract::State, response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::SiteConfig;
+use mas_keystore::Encrypter;
+use tracing::info;
+use ulid::Ulid;
+
+use super::request::{InvalidProviderError, Request};
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::UpstreamOAuthProvider,
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Upstream OAuth 2.0 providers are managed through the configuration file")]
+    ManagedByConfig,
+
+    #[error("Upstream OAuth 2.0 provider ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error("Upstream OAuth 2.0 provider ID {0} is defined in the configuration file")]
+    DefinedInConfig(Ulid),
+
+    #[error(transparent)]
+    InvalidProvider(#[from] InvalidProviderError),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_keystore::aead::Error);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ManagedByConfig => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::DefinedInConfig(_) => StatusCode::CONFLICT,
+            Self::InvalidProvider(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("updateUpstreamOAuthProvider")
+        .summary("Update an upstream OAuth 2.0 provider")
+        .description("Replace the configuration of an upstream OAuth 2.0 provider.
+If the provider was disabled, it gets enabled again.
+The existing client secret is kept if none is provided.
+This endpoint is only available when `upstream_oauth2.allow_admin_api` is enabled in the configuration, and not for providers listed in the configuration file.")
+        .tag("upstream-oauth-provider")
+        .response_with::<200, Json<SingleResponse<UpstreamOAuthProvider>>, _>(|t| {
+            let [sample, ..] = UpstreamOAuthProvider::samples();
+            let response = SingleResponse::new_canonical(sample);
+            t.description("Upstream OAuth 2.0 provider was updated")
+                .example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::InvalidProvider(
+                InvalidProviderError::MissingClientSecret,
+            ));
+            t.description("Provider parameters are invalid")
+                .example(response)
+        })
+        .response_with::<403, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::ManagedByConfig);
+            t.description("Providers can't be managed through the admin API")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("Upstream OAuth 2.0 provider was not found")
+                .example(response)
+        })
+        .response_with::<409, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::DefinedInConfig(Ulid::nil()));
+            t.description("Upstream OAuth 2.0 provider is defined in the configuration file")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(
+    name = "handler.admin.v1.upstream_oauth_providers.update",
+    skip_all,
+    err
+)]
+pub async fn handler(
+    CallContext {
+        mut repo, clock, ..
+    }: CallContext,
+    State(site_config): State<SiteConfig>,
+    State(encrypter): State<Encrypter>,
+    id: UlidPathParam,
+    Json(params): Json<Request>,
+) -> Result<Json<SingleResponse<UpstreamOAuthProvider>>, RouteError> {
+    if !site_config.upstream_oauth2_admin_api_allowed {
+        return Err(RouteError::ManagedByConfig);
+    }
+
+    let id = *id;
+    if site_config
+        .upstream_oauth2_config_provider_ids
+        .contains(&id)
+    {
+        return Err(RouteError::DefinedInConfig(id));
+    }
+
+    let provider = repo
+        .upstream_oauth_provider()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    params.validate(provider.encrypted_client_secret.is_some())?;
+    let params = params.into_params(&encrypter, provider.encrypted_client_secret)?;
+
+    let provider = repo
+        .upstream_oauth_provider()
+        .upsert(&clock, id, params)
+        .await?;
+
+    repo.save().await?;
+
+    info!(provider.id = %provider.id, "Updated upstream OAuth 2.0 provider");
+
+    Ok(Json(SingleResponse::new_canonical(
+        UpstreamOAuthProvider::from(provider),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_data_model::{
+        UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
+        UpstreamOAuthProviderPkceMode,
+    };
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_storage::{
+        upstream_oauth2::{UpstreamOAuthProviderParams, UpstreamOAuthProviderRepository},
+        RepositoryAccess,
+    };
+    use oauth2_types::scope::{Scope, OPENID};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_update_provider(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let encrypted_client_secret = state.encrypter.encrypt_to_string(b"hunter2").unwrap();
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                UpstreamOAuthProviderParams {
+                    issuer: "https://idp.example.com/".to_owned(),
+                    human_name: None,
+                    brand_name: None,
+                    scope: Some(Scope::from_iter([OPENID])),
+                    token_endpoint_auth_method: Some(
+                        OAuthClientAuthenticationMethod::ClientSecretPost,
+                    ),
+                    token_endpoint_signing_alg: None,
+                    client_id: Some("mas".to_owned()),
+                    encrypted_client_secret: Some(encrypted_client_secret.clone()),
+                    claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+                    authorization_endpoint_override: None,
+                    token_endpoint_override: None,
+                    jwks_uri_override: None,
+                    discovery_mode: UpstreamOAuthProviderDiscoveryMode::Oidc,
+                    pkce_mode: UpstreamOAuthProviderPkceMode::Auto,
+                    additional_authorization_parameters: Vec::new(),
+                    saml: None,
+                },
+            )
+            .await
+            .unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .disable(&state.clock, provider)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Update the provider without sending the client secret
+        let request = Request::put(format!(
+            "/api/admin/v1/upstream-oauth-providers/{}",
+            provider.id
+        ))
+        .bearer(&token)
+        .json(serde_json::json!({
+            "issuer": "https://idp.example.com/",
+            "human_name": "Example",
+            "client_id": "mas",
+            "token_endpoint_auth_method": "client_secret_basic",
+            "scope": "openid email",
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["attributes"]["human_name"], "Example");
+        assert_eq!(body["data"]["attributes"]["scope"], "email openid");
+        assert_eq!(
+            body["data"]["attributes"]["token_endpoint_auth_method"],
+            "client_secret_basic"
+        );
+        // Updating the provider enables it again
+        assert_eq!(
+            body["data"]["attributes"]["disabled_at"],
+            serde_json::Value::Null
+        );
+
+        // The client secret should have been kept
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .lookup(provider.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            provider.encrypted_client_secret,
+            Some(encrypted_client_secret)
+        );
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_update_unknown_provider(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request =
+            Request::put("/api/admin/v1/upstream-oauth-providers/01040G2081040G2081040G2081")
+                .bearer(&token)
+                .json(serde_json::json!({
+                    "issuer": "https://idp.example.com/",
+                    "client_id": "mas",
+                    "token_endpoint_auth_method": "none",
+                    "scope": "openid",
+                }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["errors"][0]["title"],
+            "Upstream OAuth 2.0 provider ID 01040G2081040G2081040G2081 not found"
+        );
+    }
+}
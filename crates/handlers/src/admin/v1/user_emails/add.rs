@@ -0,0 +1,214 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, NoApi, OperationIo};
+use axum::{response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::AuditEventKind;
+use mas_storage::BoxRng;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::UserEmail,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("User ID {0} not found")]
+    UserNotFound(Ulid),
+
+    #[error("Email address is not valid")]
+    EmailNotValid,
+
+    #[error("User already has this email address")]
+    EmailAlreadyExists,
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UserNotFound(_) => StatusCode::NOT_FOUND,
+            Self::EmailNotValid => StatusCode::BAD_REQUEST,
+            Self::EmailAlreadyExists => StatusCode::CONFLICT,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+/// # JSON payload for the `POST /api/admin/v1/user-emails` endpoint
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename = "AddUserEmailRequest")]
+pub struct Request {
+    /// The ID of the user to which the email address should be added.
+    #[schemars(with = "crate::admin::schema::Ulid")]
+    user_id: Ulid,
+
+    /// The email address to add.
+    email: String,
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("addUserEmail")
+        .summary("Add a user email")
+        .description(
+            "Add an email address to a user.
+The email address is added as pending verification, and no verification email is sent.
+Use the verify endpoint to mark it as verified.",
+        )
+        .tag("user-email")
+        .response_with::<200, Json<SingleResponse<UserEmail>>, _>(|t| {
+            // In the samples, the second email is the one pending verification
+            let [_, pending] = UserEmail::samples();
+            let response = SingleResponse::new_canonical(pending);
+            t.description("User email was added").example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::EmailNotValid);
+            t.description("Email address is not valid")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::UserNotFound(Ulid::nil()));
+            t.description("User was not found").example(response)
+        })
+        .response_with::<409, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::EmailAlreadyExists);
+            t.description("User already has this email address")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.user_emails.add", skip_all, err)]
+pub async fn handler(
+    CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
+    Json(params): Json<Request>,
+) -> Result<Json<SingleResponse<UserEmail>>, RouteError> {
+    let user = repo
+        .user()
+        .lookup(params.user_id)
+        .await?
+        .ok_or(RouteError::UserNotFound(params.user_id))?;
+
+    if params.email.parse::<lettre::Address>().is_err() {
+        return Err(RouteError::EmailNotValid);
+    }
+
+    if repo
+        .user_email()
+        .find(&user, &params.email)
+        .await?
+        .is_some()
+    {
+        return Err(RouteError::EmailAlreadyExists);
+    }
+
+    let user_email = repo
+        .user_email()
+        .add(&mut rng, &clock, &user, params.email)
+        .await?;
+
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &audit,
+            AuditEventKind::UserEmailAdded,
+            Some(&user),
+            serde_json::json!({ "email": user_email.email }),
+        )
+        .await?;
+
+    repo.save().await?;
+
+    info!(user.id = %user.id, user_email.id = %user_email.id, "Added email address to user");
+
+    Ok(Json(SingleResponse::new_canonical(UserEmail::from(
+        user_email,
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{user::UserRepository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_add_email(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::post("/api/admin/v1/user-emails")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "user_id": user.id,
+                "email": "alice@example.com",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["type"], "user-email");
+        assert_eq!(body["data"]["attributes"]["email"], "alice@example.com");
+        assert_eq!(body["data"]["attributes"]["user_id"], user.id.to_string());
+        assert_eq!(
+            body["data"]["attributes"]["verified_at"],
+            serde_json::Value::Null
+        );
+
+        // Adding it a second time should fail
+        let request = Request::post("/api/admin/v1/user-emails")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "user_id": user.id,
+                "email": "alice@example.com",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CONFLICT);
+
+        // Invalid email addresses are rejected
+        let request = Request::post("/api/admin/v1/user-emails")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "user_id": user.id,
+                "email": "not an email",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+}
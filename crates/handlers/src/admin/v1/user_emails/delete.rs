@@ -0,0 +1,188 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, NoApi, OperationIo};
+use axum::{response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::AuditEventKind;
+use mas_storage::{
+    job::{JobRepositoryExt, ProvisionUserJob},
+    BoxRng,
+};
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{
+    admin::{call_context::CallContext, params::UlidPathParam, response::ErrorResponse},
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("User email ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error("User email ID {0} is the primary email address of the user")]
+    Primary(Ulid),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Primary(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("deleteUserEmail")
+        .summary("Delete a user email")
+        .description(
+            "The primary email address of a user can't be deleted.
+Set another email address as primary first.",
+        )
+        .tag("user-email")
+        .response_with::<204, (), _>(|t| t.description("User email was deleted"))
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::Primary(Ulid::nil()));
+            t.description("User email is the primary email address")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("User email was not found").example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.user_emails.delete", skip_all, err)]
+pub async fn handler(
+    CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
+    id: UlidPathParam,
+) -> Result<StatusCode, RouteError> {
+    let id = *id;
+    let user_email = repo
+        .user_email()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    let user = repo
+        .user()
+        .lookup(user_email.user_id)
+        .await?
+        .ok_or_else(|| RouteError::Internal("Could not load the email owner".into()))?;
+
+    if user.primary_user_email_id == Some(user_email.id) {
+        return Err(RouteError::Primary(id));
+    }
+
+    let email = user_email.email.clone();
+    repo.user_email().remove(user_email).await?;
+
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &audit,
+            AuditEventKind::UserEmailRemoved,
+            Some(&user),
+            serde_json::json!({ "email": email }),
+        )
+        .await?;
+
+    // Schedule a job to update the user on the homeserver
+    repo.job()
+        .schedule_job(ProvisionUserJob::new(&user))
+        .await?;
+
+    repo.save().await?;
+
+    info!(user.id = %user.id, user_email.id = %id, "Deleted email address");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{
+        user::{UserEmailRepository, UserRepository},
+        RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_delete_email(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let primary = repo
+            .user_email()
+            .add(
+                &mut rng,
+                &state.clock,
+                &user,
+                "alice@example.com".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.user_email().set_as_primary(&primary).await.unwrap();
+        let secondary = repo
+            .user_email()
+            .add(
+                &mut rng,
+                &state.clock,
+                &user,
+                "alice@example.org".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // The primary email address can't be deleted
+        let request = Request::delete(format!("/api/admin/v1/user-emails/{}", primary.id))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let request = Request::delete(format!("/api/admin/v1/user-emails/{}", secondary.id))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NO_CONTENT);
+
+        let mut repo = state.repository().await.unwrap();
+        let email = repo.user_email().lookup(secondary.id).await.unwrap();
+        assert!(email.is_none());
+    }
+}
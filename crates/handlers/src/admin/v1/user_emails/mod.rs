@@ -0,0 +1,20 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+mod add;
+mod delete;
+mod get;
+mod list;
+mod set_primary;
+mod verify;
+
+pub use self::{
+    add::{doc as add_doc, handler as add},
+    delete::{doc as delete_doc, handler as delete},
+    get::{doc as get_doc, handler as get},
+    list::{doc as list_doc, handler as list},
+    set_primary::{doc as set_primary_doc, handler as set_primary},
+    verify::{doc as verify_doc, handler as verify},
+};
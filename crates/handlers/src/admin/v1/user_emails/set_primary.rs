@@ -0,0 +1,198 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, NoApi, OperationIo};
+use axum::{response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::AuditEventKind;
+use mas_storage::BoxRng;
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{Resource, UserEmail},
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("User email ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error("User email ID {0} is not verified")]
+    NotVerified(Ulid),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::NotVerified(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("setPrimaryUserEmail")
+        .summary("Set a user email as primary")
+        .description(
+            "Make the email address the primary email address of its user.
+Only verified email addresses can be set as primary.",
+        )
+        .tag("user-email")
+        .response_with::<200, Json<SingleResponse<UserEmail>>, _>(|t| {
+            let [verified, ..] = UserEmail::samples();
+            let id = verified.id();
+            let response = SingleResponse::new(
+                verified,
+                format!("/api/admin/v1/user-emails/{id}/set-primary"),
+            );
+            t.description("User email was set as primary")
+                .example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotVerified(Ulid::nil()));
+            t.description("User email is not verified")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("User email was not found").example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.user_emails.set_primary", skip_all, err)]
+pub async fn handler(
+    CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
+    id: UlidPathParam,
+) -> Result<Json<SingleResponse<UserEmail>>, RouteError> {
+    let id = *id;
+    let user_email = repo
+        .user_email()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    if user_email.confirmed_at.is_none() {
+        return Err(RouteError::NotVerified(id));
+    }
+
+    let user = repo
+        .user()
+        .lookup(user_email.user_id)
+        .await?
+        .ok_or_else(|| RouteError::Internal("Could not load the email owner".into()))?;
+
+    repo.user_email().set_as_primary(&user_email).await?;
+
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &audit,
+            AuditEventKind::UserPrimaryEmailChanged,
+            Some(&user),
+            serde_json::json!({ "email": user_email.email }),
+        )
+        .await?;
+
+    repo.save().await?;
+
+    info!(user.id = %user_email.user_id, user_email.id = %user_email.id, "Set primary email address");
+
+    Ok(Json(SingleResponse::new(
+        UserEmail::from(user_email),
+        format!("/api/admin/v1/user-emails/{id}/set-primary"),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{
+        user::{UserEmailRepository, UserRepository},
+        RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_set_primary(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let user_email = repo
+            .user_email()
+            .add(
+                &mut rng,
+                &state.clock,
+                &user,
+                "alice@example.com".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Unverified email addresses can't be set as primary
+        let request = Request::post(format!(
+            "/api/admin/v1/user-emails/{}/set-primary",
+            user_email.id
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let mut repo = state.repository().await.unwrap();
+        repo.user_email()
+            .mark_as_verified(&state.clock, user_email.clone())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::post(format!(
+            "/api/admin/v1/user-emails/{}/set-primary",
+            user_email.id
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+        assert_eq!(user.primary_user_email_id, Some(user_email.id));
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::WebhookEventKind;
+use mas_storage::job::{DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob};
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{Resource, UserEmail},
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("User email ID {0} not found")]
+    NotFound(Ulid),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("verifyUserEmail")
+        .summary("Mark a user email as verified")
+        .description(
+            "Mark the email address as verified, without going through the verification code flow.
+If the user has no primary email address yet, this one becomes the primary email address.
+Verified email addresses are then synced with the homeserver.",
+        )
+        .tag("user-email")
+        .response_with::<200, Json<SingleResponse<UserEmail>>, _>(|t| {
+            let [verified, ..] = UserEmail::samples();
+            let id = verified.id();
+            let response =
+                SingleResponse::new(verified, format!("/api/admin/v1/user-emails/{id}/verify"));
+            t.description("User email was verified").example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("User email was not found").example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.user_emails.verify", skip_all, err)]
+pub async fn handler(
+    CallContext {
+        mut repo, clock, ..
+    }: CallContext,
+    id: UlidPathParam,
+) -> Result<Json<SingleResponse<UserEmail>>, RouteError> {
+    let id = *id;
+    let mut user_email = repo
+        .user_email()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    if user_email.confirmed_at.is_none() {
+        let user = repo
+            .user()
+            .lookup(user_email.user_id)
+            .await?
+            .ok_or_else(|| RouteError::Internal("Could not load the email owner".into()))?;
+
+        if user.primary_user_email_id.is_none() {
+            repo.user_email().set_as_primary(&user_email).await?;
+        }
+
+        user_email = repo
+            .user_email()
+            .mark_as_verified(&clock, user_email)
+            .await?;
+
+        info!(user.id = %user.id, user_email.id = %user_email.id, "Marked email address as verified");
+        repo.job()
+            .schedule_job(ProvisionUserJob::new(&user))
+            .await?;
+
+        repo.job()
+            .schedule_job(
+                DispatchWebhookEventJob::new(WebhookEventKind::UserEmailVerified, &user)
+                    .with_data(serde_json::json!({ "email": user_email.email })),
+            )
+            .await?;
+    }
+
+    repo.save().await?;
+
+    Ok(Json(SingleResponse::new(
+        UserEmail::from(user_email),
+        format!("/api/admin/v1/user-emails/{id}/verify"),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{
+        user::{UserEmailRepository, UserRepository},
+        Clock, RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_verify_email(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let user_email = repo
+            .user_email()
+            .add(
+                &mut rng,
+                &state.clock,
+                &user,
+                "alice@example.com".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::post(format!(
+            "/api/admin/v1/user-emails/{}/verify",
+            user_email.id
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["data"]["attributes"]["verified_at"],
+            serde_json::json!(state.clock.now())
+        );
+
+        // It should have become the primary email address of the user
+        let mut repo = state.repository().await.unwrap();
+        let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+        assert_eq!(user.primary_user_email_id, Some(user_email.id));
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, NoApi, OperationIo};
+use axum::{response::IntoResponse, Json};
+use hyper::StatusCode;
+use mas_data_model::AuditEventKind;
+use mas_storage::{
+    job::{schedule_backchannel_logout_jobs, JobRepositoryExt, SyncDevicesJob},
+    oauth2::OAuth2SessionFilter,
+    BoxRng,
+};
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{Resource, UserSession},
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("User session ID {0} not found")]
+    NotFound(Ulid),
+
+    #[error("User session ID {0} is already finished")]
+    AlreadyFinished(Ulid),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::AlreadyFinished(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("finishUserSession")
+        .summary("Finish a user session")
+        .description("Calling this endpoint will finish the user session, logging the user out of this browser.
+The OAuth 2.0 sessions started from this browser session are finished as well, and their clients are notified if they registered a back-channel logout URI.")
+        .tag("user-session")
+        .response_with::<200, Json<SingleResponse<UserSession>>, _>(|t| {
+            // In the samples, the third session is the one finished
+            let [_, _, finished] = UserSession::samples();
+            let id = finished.id();
+            let response = SingleResponse::new(
+                finished,
+                format!("/api/admin/v1/user-sessions/{id}/finish"),
+            );
+            t.description("User session was finished").example(response)
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::AlreadyFinished(Ulid::nil()));
+            t.description("Session is already finished")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("User session was not found")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.user_sessions.finish", skip_all, err)]
+pub async fn handler(
+    CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
+    id: UlidPathParam,
+) -> Result<Json<SingleResponse<UserSession>>, RouteError> {
+    let id = *id;
+    let session = repo
+        .browser_session()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    if !session.active() {
+        return Err(RouteError::AlreadyFinished(id));
+    }
+
+    let filter = OAuth2SessionFilter::new()
+        .for_browser_session(&session)
+        .active_only();
+
+    // Notify the clients which have sessions started from this browser
+    // session that it ended
+    let scheduled = schedule_backchannel_logout_jobs(&mut repo, filter).await?;
+    info!(
+        user_session.id = %session.id,
+        "Scheduled {scheduled} back-channel logout jobs"
+    );
+
+    // End all the OAuth 2.0 sessions which were started from this browser
+    // session
+    let affected = repo.oauth2_session().finish_bulk(&clock, filter).await?;
+
+    // Schedule a job to sync the devices of the user with the homeserver
+    if affected > 0 {
+        repo.job()
+            .schedule_job(SyncDevicesJob::new(&session.user))
+            .await?;
+    }
+
+    let session = repo.browser_session().finish(&clock, session).await?;
+
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &audit,
+            AuditEventKind::SessionEnded,
+            Some(&session.user),
+            serde_json::json!({ "session_type": "browser", "session_id": session.id }),
+        )
+        .await?;
+
+    repo.save().await?;
+
+    Ok(Json(SingleResponse::new(
+        UserSession::from(session),
+        format!("/api/admin/v1/user-sessions/{id}/finish"),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_storage::{
+        oauth2::{OAuth2ClientRepository, OAuth2SessionRepository},
+        user::{BrowserSessionRepository, UserRepository},
+        Clock, RepositoryAccess,
+    };
+    use oauth2_types::{
+        requests::GrantType,
+        scope::{Scope, OPENID},
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_finish_session(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let session = repo
+            .browser_session()
+            .add(&mut rng, &state.clock, &user, None)
+            .await
+            .unwrap();
+        let client = repo
+            .oauth2_client()
+            .add(
+                &mut rng,
+                &state.clock,
+                vec!["https://example.com/redirect".parse().unwrap()],
+                None,
+                None,
+                vec![GrantType::AuthorizationCode],
+                Some("Example".to_owned()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let oauth2_session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut rng,
+                &state.clock,
+                &client,
+                &session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::post(format!("/api/admin/v1/user-sessions/{}/finish", session.id))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["data"]["attributes"]["finished_at"],
+            serde_json::json!(state.clock.now())
+        );
+
+        // The OAuth 2.0 session started from it was finished as well
+        let mut repo = state.repository().await.unwrap();
+        let oauth2_session = repo
+            .oauth2_session()
+            .lookup(oauth2_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(oauth2_session.is_finished());
+        repo.save().await.unwrap();
+
+        // Finishing it again should fail
+        let request = Request::post(format!("/api/admin/v1/user-sessions/{}/finish", session.id))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -7,9 +7,10 @@
 use aide::{transform::TransformOperation, NoApi, OperationIo};
 use axum::{extract::State, response::IntoResponse, Json};
 use hyper::StatusCode;
+use mas_data_model::WebhookEventKind;
 use mas_matrix::BoxHomeserverConnection;
 use mas_storage::{
-    job::{JobRepositoryExt, ProvisionUserJob},
+    job::{DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob},
     BoxRng,
 };
 use schemars::JsonSchema;
@@ -22,39 +23,9 @@ use crate::{
         model::User,
         response::{ErrorResponse, SingleResponse},
     },
-    impl_from_error_for_route,
+    impl_from_error_for_route, username_valid,
 };
 
-fn valid_username_character(c: char) -> bool {
-    c.is_ascii_lowercase()
-        || c.is_ascii_digit()
-        || c == '='
-        || c == '_'
-        || c == '-'
-        || c == '.'
-        || c == '/'
-        || c == '+'
-}
-
-// XXX: this should be shared with the graphql handler
-fn username_valid(username: &str) -> bool {
-    if username.is_empty() || username.len() > 255 {
-        return false;
-    }
-
-    // Should not start with an underscore
-    if username.starts_with('_') {
-        return false;
-    }
-
-    // Should only contain valid characters
-    if !username.chars().all(valid_username_character) {
-        return false;
-    }
-
-    true
-}
-
 #[derive(Debug, thiserror::Error, OperationIo)]
 #[aide(output_with = "Json<ErrorResponse>")]
 pub enum RouteError {
@@ -168,6 +139,13 @@ pub async fn handler(
         .schedule_job(ProvisionUserJob::new(&user))
         .await?;
 
+    repo.job()
+        .schedule_job(
+            DispatchWebhookEventJob::new(WebhookEventKind::UserRegistered, &user)
+                .with_data(serde_json::json!({ "method": "admin_api" })),
+        )
+        .await?;
+
     repo.save().await?;
 
     Ok(Json(SingleResponse::new_canonical(User::from(user))))
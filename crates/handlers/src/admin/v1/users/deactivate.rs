@@ -4,10 +4,14 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
-use aide::{transform::TransformOperation, OperationIo};
+use aide::{transform::TransformOperation, NoApi, OperationIo};
 use axum::{response::IntoResponse, Json};
 use hyper::StatusCode;
-use mas_storage::job::{DeactivateUserJob, JobRepositoryExt};
+use mas_data_model::AuditEventKind;
+use mas_storage::{
+    job::{DeactivateUserJob, JobRepositoryExt},
+    BoxRng,
+};
 use tracing::info;
 use ulid::Ulid;
 
@@ -67,8 +71,12 @@ This invalidates any existing session, and will ask the homeserver to make them
 #[tracing::instrument(name = "handler.admin.v1.users.deactivate", skip_all, err)]
 pub async fn handler(
     CallContext {
-        mut repo, clock, ..
+        mut repo,
+        clock,
+        audit,
+        ..
     }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
     id: UlidPathParam,
 ) -> Result<Json<SingleResponse<User>>, RouteError> {
     let id = *id;
@@ -87,6 +95,17 @@ pub async fn handler(
         .schedule_job(DeactivateUserJob::new(&user, true))
         .await?;
 
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &audit,
+            AuditEventKind::UserDeactivated,
+            Some(&user),
+            serde_json::json!({}),
+        )
+        .await?;
+
     repo.save().await?;
 
     Ok(Json(SingleResponse::new(
@@ -4,10 +4,15 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
-use aide::{transform::TransformOperation, OperationIo};
+use aide::{transform::TransformOperation, NoApi, OperationIo};
 use axum::{extract::State, response::IntoResponse, Json};
 use hyper::StatusCode;
+use mas_data_model::{AuditEventKind, WebhookEventKind};
 use mas_matrix::BoxHomeserverConnection;
+use mas_storage::{
+    job::{DispatchWebhookEventJob, JobRepositoryExt},
+    BoxRng,
+};
 use ulid::Ulid;
 
 use crate::{
@@ -66,7 +71,13 @@ pub fn doc(operation: TransformOperation) -> TransformOperation {
 
 #[tracing::instrument(name = "handler.admin.v1.users.unlock", skip_all, err)]
 pub async fn handler(
-    CallContext { mut repo, .. }: CallContext,
+    CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    }: CallContext,
+    NoApi(mut rng): NoApi<BoxRng>,
     State(homeserver): State<BoxHomeserverConnection>,
     id: UlidPathParam,
 ) -> Result<Json<SingleResponse<User>>, RouteError> {
@@ -87,6 +98,24 @@ pub async fn handler(
     // Now unlock the user in our database
     let user = repo.user().unlock(user).await?;
 
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &audit,
+            AuditEventKind::UserUnlocked,
+            Some(&user),
+            serde_json::json!({}),
+        )
+        .await?;
+
+    repo.job()
+        .schedule_job(DispatchWebhookEventJob::new(
+            WebhookEventKind::UserUnlocked,
+            &user,
+        ))
+        .await?;
+
     repo.save().await?;
 
     Ok(Json(SingleResponse::new(
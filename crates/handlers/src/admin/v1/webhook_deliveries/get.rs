@@ -0,0 +1,155 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{response::IntoResponse, Json};
+use hyper::StatusCode;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::WebhookDelivery,
+        params::UlidPathParam,
+        response::{ErrorResponse, SingleResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Webhook delivery ID {0} not found")]
+    NotFound(Ulid),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("getWebhookDelivery")
+        .summary("Get a webhook delivery")
+        .tag("webhook-delivery")
+        .response_with::<200, Json<SingleResponse<WebhookDelivery>>, _>(|t| {
+            let [sample, ..] = WebhookDelivery::samples();
+            let response = SingleResponse::new_canonical(sample);
+            t.description("Webhook delivery was found")
+                .example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::NotFound(Ulid::nil()));
+            t.description("Webhook delivery was not found")
+                .example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.webhook_deliveries.get", skip_all, err)]
+pub async fn handler(
+    CallContext { mut repo, .. }: CallContext,
+    id: UlidPathParam,
+) -> Result<Json<SingleResponse<WebhookDelivery>>, RouteError> {
+    let delivery = repo
+        .webhook_delivery()
+        .lookup(*id)
+        .await?
+        .ok_or(RouteError::NotFound(*id))?;
+
+    Ok(Json(SingleResponse::new_canonical(WebhookDelivery::from(
+        delivery,
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_data_model::WebhookEventKind;
+    use mas_storage::{user::UserRepository, webhook::WebhookDeliveryRepository, RepositoryAccess};
+    use sqlx::PgPool;
+    use ulid::Ulid;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_get(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let alice = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let delivery = repo
+            .webhook_delivery()
+            .add(
+                &mut rng,
+                &state.clock,
+                "crm".to_owned(),
+                WebhookEventKind::UserLocked,
+                Some(&alice),
+                serde_json::json!({ "user": { "username": "alice" } }),
+            )
+            .await
+            .unwrap();
+        let delivery = repo
+            .webhook_delivery()
+            .record_failed_attempt(&state.clock, delivery, "HTTP 502".to_owned())
+            .await
+            .unwrap();
+        repo.webhook_delivery()
+            .mark_as_failed(&state.clock, delivery.clone())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get(format!("/api/admin/v1/webhook-deliveries/{}", delivery.id))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["data"]["type"], "webhook-delivery");
+        assert_eq!(body["data"]["attributes"]["target"], "crm");
+        assert_eq!(body["data"]["attributes"]["event"], "user.locked");
+        assert_eq!(body["data"]["attributes"]["user_id"], alice.id.to_string());
+        assert_eq!(body["data"]["attributes"]["status"], "failed");
+        assert_eq!(body["data"]["attributes"]["attempts"], 1);
+        assert_eq!(body["data"]["attributes"]["last_error"], "HTTP 502");
+        assert_eq!(
+            body["data"]["attributes"]["data"]["user"]["username"],
+            "alice"
+        );
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_not_found(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        let request = Request::get(format!("/api/admin/v1/webhook-deliveries/{}", Ulid::nil()))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+}
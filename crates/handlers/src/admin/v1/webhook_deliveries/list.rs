@@ -0,0 +1,393 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use aide::{transform::TransformOperation, OperationIo};
+use axum::{
+    extract::{rejection::QueryRejection, Query},
+    response::IntoResponse,
+    Json,
+};
+use axum_macros::FromRequestParts;
+use hyper::StatusCode;
+use mas_data_model::WebhookEventKind;
+use mas_storage::{webhook::WebhookDeliveryFilter, Page};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use ulid::Ulid;
+
+use crate::{
+    admin::{
+        call_context::CallContext,
+        model::{Resource, WebhookDelivery},
+        params::Pagination,
+        response::{ErrorResponse, PaginatedResponse},
+    },
+    impl_from_error_for_route,
+};
+
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl std::fmt::Display for WebhookDeliveryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Delivered => write!(f, "delivered"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(FromRequestParts, Deserialize, JsonSchema, OperationIo)]
+#[serde(rename = "WebhookDeliveryFilter")]
+#[aide(input_with = "Query<FilterParams>")]
+#[from_request(via(Query), rejection(RouteError))]
+pub struct FilterParams {
+    /// Retrieve the deliveries with the given status
+    ///
+    /// Failed deliveries ran out of attempts, and won't be retried.
+    #[serde(rename = "filter[status]")]
+    status: Option<WebhookDeliveryStatus>,
+
+    /// Retrieve the deliveries to the given webhook target
+    #[serde(rename = "filter[target]")]
+    target: Option<String>,
+
+    /// Retrieve the deliveries of the given kind of event, like
+    /// `user.registered` or `user.locked`
+    #[serde(rename = "filter[event]")]
+    event: Option<String>,
+
+    /// Retrieve the deliveries of events concerning the given user
+    #[serde(rename = "filter[user]")]
+    #[schemars(with = "Option<crate::admin::schema::Ulid>")]
+    user: Option<Ulid>,
+}
+
+impl std::fmt::Display for FilterParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut sep = '?';
+
+        if let Some(status) = self.status {
+            write!(f, "{sep}filter[status]={status}")?;
+            sep = '&';
+        }
+
+        if let Some(target) = &self.target {
+            write!(f, "{sep}filter[target]={target}")?;
+            sep = '&';
+        }
+
+        if let Some(event) = &self.event {
+            write!(f, "{sep}filter[event]={event}")?;
+            sep = '&';
+        }
+
+        if let Some(user) = self.user {
+            write!(f, "{sep}filter[user]={user}")?;
+            sep = '&';
+        }
+
+        let _ = sep;
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error, OperationIo)]
+#[aide(output_with = "Json<ErrorResponse>")]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("User ID {0} not found")]
+    UserNotFound(Ulid),
+
+    #[error("Unknown webhook event {0:?}")]
+    UnknownEvent(String),
+
+    #[error("Invalid filter parameters")]
+    InvalidFilter(#[from] QueryRejection),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let error = ErrorResponse::from_error(&self);
+        let status = match self {
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UserNotFound(_) => StatusCode::NOT_FOUND,
+            Self::UnknownEvent(_) | Self::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(error)).into_response()
+    }
+}
+
+pub fn doc(operation: TransformOperation) -> TransformOperation {
+    operation
+        .id("listWebhookDeliveries")
+        .summary("List webhook deliveries")
+        .description(
+            "Retrieve a list of deliveries of user lifecycle events to webhook targets.
+
+Use the `filter[status]=failed` parameter to list the deliveries which ran out of attempts.",
+        )
+        .tag("webhook-delivery")
+        .response_with::<200, Json<PaginatedResponse<WebhookDelivery>>, _>(|t| {
+            let deliveries = WebhookDelivery::samples();
+            let pagination = mas_storage::Pagination::first(deliveries.len());
+            let page = Page {
+                edges: deliveries.into(),
+                has_next_page: true,
+                has_previous_page: false,
+            };
+
+            t.description("Paginated response of webhook deliveries")
+                .example(PaginatedResponse::new(
+                    page,
+                    pagination,
+                    42,
+                    WebhookDelivery::PATH,
+                ))
+        })
+        .response_with::<400, RouteError, _>(|t| {
+            let response =
+                ErrorResponse::from_error(&RouteError::UnknownEvent("user.unknown".to_owned()));
+            t.description("Unknown event").example(response)
+        })
+        .response_with::<404, RouteError, _>(|t| {
+            let response = ErrorResponse::from_error(&RouteError::UserNotFound(Ulid::nil()));
+            t.description("User was not found").example(response)
+        })
+}
+
+#[tracing::instrument(name = "handler.admin.v1.webhook_deliveries.list", skip_all, err)]
+pub async fn handler(
+    CallContext { mut repo, .. }: CallContext,
+    Pagination(pagination): Pagination,
+    params: FilterParams,
+) -> Result<Json<PaginatedResponse<WebhookDelivery>>, RouteError> {
+    let base = format!("{path}{params}", path = WebhookDelivery::PATH);
+    let filter = WebhookDeliveryFilter::default();
+
+    // Load the user from the filter
+    let user = if let Some(user_id) = params.user {
+        let user = repo
+            .user()
+            .lookup(user_id)
+            .await?
+            .ok_or(RouteError::UserNotFound(user_id))?;
+
+        Some(user)
+    } else {
+        None
+    };
+
+    let filter = match &user {
+        Some(user) => filter.for_user(user),
+        None => filter,
+    };
+
+    let filter = match &params.target {
+        Some(target) => filter.for_target(target),
+        None => filter,
+    };
+
+    let filter = match &params.event {
+        Some(event) => {
+            let event: WebhookEventKind = event
+                .parse()
+                .map_err(|_| RouteError::UnknownEvent(event.clone()))?;
+            filter.for_event(event)
+        }
+        None => filter,
+    };
+
+    let filter = match params.status {
+        Some(WebhookDeliveryStatus::Pending) => filter.pending_only(),
+        Some(WebhookDeliveryStatus::Delivered) => filter.delivered_only(),
+        Some(WebhookDeliveryStatus::Failed) => filter.failed_only(),
+        None => filter,
+    };
+
+    let page = repo.webhook_delivery().list(filter, pagination).await?;
+    let count = repo.webhook_delivery().count(filter).await?;
+
+    Ok(Json(PaginatedResponse::new(
+        page.map(WebhookDelivery::from),
+        pagination,
+        count,
+        &base,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_data_model::WebhookEventKind;
+    use mas_storage::{user::UserRepository, webhook::WebhookDeliveryRepository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_webhook_delivery_list(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let alice = repo
+            .user()
+            .add(&mut rng, &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let bob = repo
+            .user()
+            .add(&mut rng, &state.clock, "bob".to_owned())
+            .await
+            .unwrap();
+
+        // Delivered to the CRM
+        let delivery = repo
+            .webhook_delivery()
+            .add(
+                &mut rng,
+                &state.clock,
+                "crm".to_owned(),
+                WebhookEventKind::UserRegistered,
+                Some(&alice),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+        repo.webhook_delivery()
+            .mark_as_delivered(&state.clock, delivery)
+            .await
+            .unwrap();
+
+        // Still pending
+        repo.webhook_delivery()
+            .add(
+                &mut rng,
+                &state.clock,
+                "crm".to_owned(),
+                WebhookEventKind::UserLocked,
+                Some(&bob),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+
+        // Dead-lettered
+        let delivery = repo
+            .webhook_delivery()
+            .add(
+                &mut rng,
+                &state.clock,
+                "audit".to_owned(),
+                WebhookEventKind::UserLocked,
+                Some(&bob),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+        let delivery = repo
+            .webhook_delivery()
+            .record_failed_attempt(&state.clock, delivery, "connection refused".to_owned())
+            .await
+            .unwrap();
+        repo.webhook_delivery()
+            .mark_as_failed(&state.clock, delivery)
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let request = Request::get("/api/admin/v1/webhook-deliveries")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 3);
+        assert_eq!(body["data"][0]["attributes"]["status"], "delivered");
+
+        // Filter by status
+        let request = Request::get("/api/admin/v1/webhook-deliveries?filter[status]=failed")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+        assert_eq!(body["data"][0]["attributes"]["target"], "audit");
+        assert_eq!(
+            body["data"][0]["attributes"]["last_error"],
+            "connection refused"
+        );
+
+        let request = Request::get("/api/admin/v1/webhook-deliveries?filter[status]=pending")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+
+        // Filter by target
+        let request = Request::get("/api/admin/v1/webhook-deliveries?filter[target]=crm")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 2);
+
+        // Filter by event
+        let request = Request::get("/api/admin/v1/webhook-deliveries?filter[event]=user.locked")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 2);
+
+        // Filter by user
+        let request = Request::get(format!(
+            "/api/admin/v1/webhook-deliveries?filter[user]={}",
+            alice.id
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["meta"]["count"], 1);
+
+        // Unknown event
+        let request = Request::get("/api/admin/v1/webhook-deliveries?filter[event]=user.unknown")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Unknown user
+        let request = Request::get(format!(
+            "/api/admin/v1/webhook-deliveries?filter[user]={}",
+            ulid::Ulid::nil()
+        ))
+        .bearer(&token)
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+}
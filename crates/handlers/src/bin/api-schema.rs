@@ -58,6 +58,8 @@ impl_from_ref!(mas_router::UrlBuilder);
 impl_from_ref!(mas_templates::Templates);
 impl_from_ref!(mas_matrix::BoxHomeserverConnection);
 impl_from_ref!(mas_keystore::Keystore);
+impl_from_ref!(mas_keystore::Encrypter);
+impl_from_ref!(mas_data_model::SiteConfig);
 impl_from_ref!(mas_handlers::passwords::PasswordManager);
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
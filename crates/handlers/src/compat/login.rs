@@ -9,11 +9,14 @@ use axum_extra::typed_header::TypedHeader;
 use chrono::Duration;
 use hyper::StatusCode;
 use mas_axum_utils::sentry::SentryEventID;
+use mas_config::LdapMode;
 use mas_data_model::{
-    CompatSession, CompatSsoLoginState, Device, SiteConfig, TokenType, User, UserAgent,
+    AuditActor, AuditContext, AuditEventKind, CompatSession, CompatSsoLoginState, Device,
+    SiteConfig, TokenType, User, UserAgent,
 };
 use mas_matrix::BoxHomeserverConnection;
 use mas_storage::{
+    audit::AuditEventRepository,
     compat::{
         CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository,
         CompatSsoLoginRepository,
@@ -29,7 +32,8 @@ use zeroize::Zeroizing;
 
 use super::MatrixError;
 use crate::{
-    impl_from_error_for_route, passwords::PasswordManager, rate_limit::PasswordCheckLimitedError,
+    impl_from_error_for_route, ldap::LdapLoginError, passwords::PasswordManager,
+    rate_limit::PasswordCheckLimitedError, views::login_totp::has_second_factor,
     BoundActivityTracker, Limiter, RequesterFingerprint,
 };
 
@@ -168,10 +172,27 @@ pub enum RouteError {
 
     #[error("failed to provision device")]
     ProvisionDeviceFailed(#[source] anyhow::Error),
+
+    #[error("LDAP login failed")]
+    Ldap(#[source] LdapLoginError),
+
+    #[error("user has a second factor enrolled")]
+    SecondFactorRequired,
 }
 
 impl_from_error_for_route!(mas_storage::RepositoryError);
 
+impl RouteError {
+    /// Whether this is a server-side failure, rather than a refused login
+    fn is_internal(&self) -> bool {
+        match self {
+            Self::Internal(_) | Self::SessionNotFound | Self::ProvisionDeviceFailed(_) => true,
+            Self::Ldap(e) => e.is_internal(),
+            _ => false,
+        }
+    }
+}
+
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
         let event_id = sentry::capture_error(&self);
@@ -183,6 +204,11 @@ impl IntoResponse for RouteError {
                     status: StatusCode::INTERNAL_SERVER_ERROR,
                 }
             }
+            Self::Ldap(ref e) if e.is_internal() => MatrixError {
+                errcode: "M_UNKNOWN",
+                error: "Internal server error",
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+            },
             Self::RateLimited(_) => MatrixError {
                 errcode: "M_LIMIT_EXCEEDED",
                 error: "Too many login attempts",
@@ -193,13 +219,17 @@ impl IntoResponse for RouteError {
                 error: "Invalid login type",
                 status: StatusCode::BAD_REQUEST,
             },
-            Self::UserNotFound | Self::NoPassword | Self::PasswordVerificationFailed(_) => {
-                MatrixError {
-                    errcode: "M_FORBIDDEN",
-                    error: "Invalid username/password",
-                    status: StatusCode::FORBIDDEN,
-                }
-            }
+            // Users with a second factor get the same error as a wrong password,
+            // so that this doesn't tell whether the password was right
+            Self::UserNotFound
+            | Self::NoPassword
+            | Self::PasswordVerificationFailed(_)
+            | Self::Ldap(_)
+            | Self::SecondFactorRequired => MatrixError {
+                errcode: "M_FORBIDDEN",
+                error: "Invalid username/password",
+                status: StatusCode::FORBIDDEN,
+            },
             Self::LoginTookTooLong => MatrixError {
                 errcode: "M_FORBIDDEN",
                 error: "Login token expired",
@@ -231,15 +261,18 @@ pub(crate) async fn post(
     Json(input): Json<RequestBody>,
 ) -> Result<impl IntoResponse, RouteError> {
     let user_agent = user_agent.map(|ua| UserAgent::parse(ua.as_str().to_owned()));
-    let (mut session, user) = match (password_manager.is_enabled(), input.credentials) {
+    let audit = AuditContext::anonymous()
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.as_ref().map(|ua| ua.raw.clone()));
+    let (mut session, user, method) = match (password_manager.is_enabled(), input.credentials) {
         (
             true,
             Credentials::Password {
-                identifier: Identifier::User { user },
+                identifier: Identifier::User { user: username },
                 password,
             },
         ) => {
-            user_password_login(
+            let res = user_password_login(
                 &mut rng,
                 &clock,
                 &password_manager,
@@ -247,13 +280,42 @@ pub(crate) async fn post(
                 requester,
                 &mut repo,
                 &homeserver,
-                user,
+                username.clone(),
                 password,
             )
-            .await?
+            .await;
+
+            match res {
+                Ok((session, user)) => (session, user, "compat_password"),
+                Err(e) if e.is_internal() => return Err(e),
+                Err(e) => {
+                    // Record the failed attempt against the targeted user, if it exists
+                    let user = repo.user().find_by_username(&username).await?;
+                    repo.audit_event()
+                        .add(
+                            &mut rng,
+                            &clock,
+                            &audit,
+                            AuditEventKind::UserLoginFailed,
+                            user.as_ref(),
+                            serde_json::json!({
+                                "username": username,
+                                "method": "compat_password",
+                                "error": e.to_string(),
+                            }),
+                        )
+                        .await?;
+                    repo.save().await?;
+
+                    return Err(e);
+                }
+            }
         }
 
-        (_, Credentials::Token { token }) => token_login(&mut repo, &clock, &token).await?,
+        (_, Credentials::Token { token }) => {
+            let (session, user) = token_login(&mut repo, &clock, &token).await?;
+            (session, user, "compat_token")
+        }
 
         _ => {
             return Err(RouteError::Unsupported);
@@ -293,6 +355,20 @@ pub(crate) async fn post(
         None
     };
 
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &AuditContext {
+                actor: AuditActor::User { user_id: user.id },
+                ..audit
+            },
+            AuditEventKind::UserLogin,
+            Some(&user),
+            serde_json::json!({ "method": method, "session_type": "compat", "session_id": session.id }),
+        )
+        .await?;
+
     repo.save().await?;
 
     activity_tracker
@@ -375,7 +451,9 @@ async fn token_login(
     Ok((session, user))
 }
 
-async fn user_password_login(
+/// Check the password of a user, against the database and the LDAP directory
+/// if there is one
+async fn check_password(
     mut rng: &mut (impl RngCore + CryptoRng + Send),
     clock: &impl Clock,
     password_manager: &PasswordManager,
@@ -383,26 +461,58 @@ async fn user_password_login(
     requester: RequesterFingerprint,
     repo: &mut BoxRepository,
     homeserver: &BoxHomeserverConnection,
-    username: String,
+    username: &str,
     password: String,
-) -> Result<(CompatSession, User), RouteError> {
+) -> Result<User, RouteError> {
+    let ldap = password_manager.ldap();
+
+    // In primary mode, the directory is checked first, and passwords stored in the
+    // database are only used for users which are not in the directory
+    if let Some(ldap) = ldap.filter(|ldap| ldap.mode() == LdapMode::Primary) {
+        limiter.check_password_for_username(requester, username)?;
+        let res = crate::ldap::login(ldap, repo, &mut rng, clock, homeserver, username, &password)
+            .await
+            .map_err(RouteError::Ldap)?;
+
+        if let Some((user, _link)) = res {
+            limiter.check_password(requester, &user)?;
+            return Ok(user);
+        }
+    }
+
     // Find the user
-    let user = repo
-        .user()
-        .find_by_username(&username)
-        .await?
+    let user = repo.user().find_by_username(username).await?;
+
+    // Lookup its password
+    let user_password = match &user {
+        Some(user) => repo.user_password().active(user).await?,
+        None => None,
+    };
+
+    // In fallback mode, the directory is checked for users which don't have a
+    // password in the database
+    if let Some(ldap) = ldap.filter(|ldap| ldap.mode() == LdapMode::Fallback) {
+        if user_password.is_none() {
+            limiter.check_password_for_username(requester, username)?;
+            let (user, _link) =
+                crate::ldap::login(ldap, repo, &mut rng, clock, homeserver, username, &password)
+                    .await
+                    .map_err(RouteError::Ldap)?
+                    .ok_or(RouteError::UserNotFound)?;
+
+            limiter.check_password(requester, &user)?;
+            return Ok(user);
+        }
+    }
+
+    let user = user
         .filter(mas_data_model::User::is_valid)
         .ok_or(RouteError::UserNotFound)?;
 
     // Check the rate limit
     limiter.check_password(requester, &user)?;
 
-    // Lookup its password
-    let user_password = repo
-        .user_password()
-        .active(&user)
-        .await?
-        .ok_or(RouteError::NoPassword)?;
+    let user_password = user_password.ok_or(RouteError::NoPassword)?;
 
     // Verify the password
     let password = Zeroizing::new(password.into_bytes());
@@ -431,6 +541,39 @@ async fn user_password_login(
             .await?;
     }
 
+    Ok(user)
+}
+
+async fn user_password_login(
+    mut rng: &mut (impl RngCore + CryptoRng + Send),
+    clock: &impl Clock,
+    password_manager: &PasswordManager,
+    limiter: &Limiter,
+    requester: RequesterFingerprint,
+    repo: &mut BoxRepository,
+    homeserver: &BoxHomeserverConnection,
+    username: String,
+    password: String,
+) -> Result<(CompatSession, User), RouteError> {
+    let user = check_password(
+        &mut rng,
+        clock,
+        password_manager,
+        limiter,
+        requester,
+        repo,
+        homeserver,
+        &username,
+        password,
+    )
+    .await?;
+
+    // The password login flow has no way to ask for a second factor, so users
+    // who enrolled one have to go through SSO
+    if has_second_factor(repo, &user).await? {
+        return Err(RouteError::SecondFactorRequired);
+    }
+
     // Lock the user sync to make sure we don't get into a race condition
     repo.user().acquire_lock_for_sync(&user).await?;
 
@@ -454,6 +597,10 @@ async fn user_password_login(
 mod tests {
     use hyper::Request;
     use mas_matrix::{HomeserverConnection, ProvisionRequest};
+    use mas_storage::{
+        audit::AuditEventFilter,
+        user::{UserPasskeyRepository, UserTotpRepository},
+    };
     use rand::distributions::{Alphanumeric, DistString};
     use sqlx::PgPool;
 
@@ -660,6 +807,185 @@ mod tests {
         // The response should be the same as the previous one, so that we don't leak if
         // it's the user that is invalid or the password.
         assert_eq!(body, old_body);
+
+        // The logins and the failed attempt on alice should have been recorded in the
+        // audit log
+        let mut repo = state.repository().await.unwrap();
+        let filter = AuditEventFilter::new().for_user(&user);
+        let succeeded = repo
+            .audit_event()
+            .count(filter.with_kind(AuditEventKind::UserLogin))
+            .await
+            .unwrap();
+        assert_eq!(succeeded, 2);
+        let failed = repo
+            .audit_event()
+            .count(filter.with_kind(AuditEventKind::UserLoginFailed))
+            .await
+            .unwrap();
+        assert_eq!(failed, 1);
+
+        // The attempt on the unknown user is recorded too, just not attached to a user
+        let failed = repo
+            .audit_event()
+            .count(AuditEventFilter::new().with_kind(AuditEventKind::UserLoginFailed))
+            .await
+            .unwrap();
+        assert_eq!(failed, 2);
+    }
+
+    /// Test that a user with a TOTP second factor can't login with just a
+    /// password.
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_password_login_with_totp(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let mxid = state.homeserver_connection.mxid(&user.username);
+        state
+            .homeserver_connection
+            .provision_user(&ProvisionRequest::new(mxid, &user.sub))
+            .await
+            .unwrap();
+
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(
+                &mut state.rng(),
+                Zeroizing::new("password".to_owned().into_bytes()),
+            )
+            .await
+            .unwrap();
+
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let user_totp_secret = repo
+            .user_totp()
+            .add(&mut state.rng(), &state.clock, &user, "secret".to_owned())
+            .await
+            .unwrap();
+        repo.user_totp()
+            .confirm(&state.clock, user_totp_secret)
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        // The password is right, but the login should still be refused
+        let request = Request::post("/_matrix/client/v3/login").json(serde_json::json!({
+            "type": "m.login.password",
+            "identifier": {
+                "type": "m.id.user",
+                "user": "alice",
+            },
+            "password": "password",
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["errcode"], "M_FORBIDDEN");
+        // The error is the same as with a wrong password
+        assert_eq!(body["error"], "Invalid username/password");
+
+        // No session should have been started
+        let mut repo = state.repository().await.unwrap();
+        let count = repo
+            .compat_session()
+            .count(mas_storage::compat::CompatSessionFilter::new().for_user(&user))
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    /// Test that a user with a passkey can't login with just a password.
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_password_login_with_passkey(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let mxid = state.homeserver_connection.mxid(&user.username);
+        state
+            .homeserver_connection
+            .provision_user(&ProvisionRequest::new(mxid, &user.sub))
+            .await
+            .unwrap();
+
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(
+                &mut state.rng(),
+                Zeroizing::new("password".to_owned().into_bytes()),
+            )
+            .await
+            .unwrap();
+
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+
+        repo.user_passkey()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "My laptop".to_owned(),
+                "Y3JlZGVudGlhbA".to_owned(),
+                "{}".to_owned(),
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        // The password is right, but the login should still be refused
+        let request = Request::post("/_matrix/client/v3/login").json(serde_json::json!({
+            "type": "m.login.password",
+            "identifier": {
+                "type": "m.id.user",
+                "user": "alice",
+            },
+            "password": "password",
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["errcode"], "M_FORBIDDEN");
     }
 
     /// Test that password logins are rate limited.
@@ -24,12 +24,12 @@ use axum::{
 use axum_extra::typed_header::TypedHeader;
 use chrono::{DateTime, Utc};
 use futures_util::TryStreamExt;
-use headers::{authorization::Bearer, Authorization, ContentType, HeaderValue};
+use headers::{authorization::Bearer, Authorization, ContentType, HeaderValue, UserAgent};
 use hyper::header::CACHE_CONTROL;
 use mas_axum_utils::{
     cookies::CookieJar, sentry::SentryEventID, FancyError, SessionInfo, SessionInfoExt,
 };
-use mas_data_model::{BrowserSession, Session, SiteConfig, User};
+use mas_data_model::{AuditContext, BrowserSession, Session, SiteConfig, User};
 use mas_matrix::HomeserverConnection;
 use mas_policy::{InstantiateError, Policy, PolicyFactory};
 use mas_storage::{BoxClock, BoxRepository, BoxRng, Clock, RepositoryError, SystemClock};
@@ -264,6 +264,12 @@ async fn get_requester(
             return Err(RouteError::InvalidToken);
         }
 
+        // This API only accepts bearer tokens, so tokens bound to a DPoP key can't be
+        // used here
+        if token.dpop_jkt.is_some() {
+            return Err(RouteError::InvalidToken);
+        }
+
         if !session.scope.contains("urn:mas:graphql:*") {
             return Err(RouteError::MissingScope);
         }
@@ -295,6 +301,7 @@ pub async fn post(
     cookie_jar: CookieJar,
     content_type: Option<TypedHeader<ContentType>>,
     authorization: Option<TypedHeader<Authorization<Bearer>>>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     body: Body,
 ) -> Result<impl IntoResponse, RouteError> {
     let body = body.into_data_stream();
@@ -311,6 +318,10 @@ pub async fn post(
         token,
     )
     .await?;
+    let audit = requester
+        .audit_context()
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.map(|TypedHeader(ua)| ua.as_str().to_owned()));
 
     let content_type = content_type.map(|TypedHeader(h)| h.to_string());
 
@@ -321,7 +332,8 @@ pub async fn post(
         MultipartOptions::default(),
     )
     .await?
-    .data(requester); // XXX: this should probably return another error response?
+    .data(requester) // XXX: this should probably return another error response?
+    .data(audit);
 
     let span = span_for_graphql_request(&request);
     let response = schema.execute(request).instrument(span).await;
@@ -347,6 +359,7 @@ pub async fn get(
     activity_tracker: BoundActivityTracker,
     cookie_jar: CookieJar,
     authorization: Option<TypedHeader<Authorization<Bearer>>>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     RawQuery(query): RawQuery,
 ) -> Result<impl IntoResponse, FancyError> {
     let token = authorization
@@ -362,9 +375,14 @@ pub async fn get(
         token,
     )
     .await?;
+    let audit = requester
+        .audit_context()
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.map(|TypedHeader(ua)| ua.as_str().to_owned()));
 
-    let request =
-        async_graphql::http::parse_query_string(&query.unwrap_or_default())?.data(requester);
+    let request = async_graphql::http::parse_query_string(&query.unwrap_or_default())?
+        .data(requester)
+        .data(audit);
 
     let span = span_for_graphql_request(&request);
     let response = schema.execute(request).instrument(span).await;
@@ -432,6 +450,12 @@ impl OwnerId for mas_data_model::UserEmail {
     }
 }
 
+impl OwnerId for mas_data_model::UserPasskey {
+    fn owner_id(&self) -> Option<Ulid> {
+        Some(self.user_id)
+    }
+}
+
 impl OwnerId for Session {
     fn owner_id(&self) -> Option<Ulid> {
         self.user_id
@@ -511,6 +535,19 @@ impl Requester {
             Self::BrowserSession(_) | Self::Anonymous => false,
         }
     }
+
+    /// Who to attribute the audited events triggered by this requester to
+    fn audit_context(&self) -> AuditContext {
+        match self {
+            Self::OAuth2Session(tuple) if self.is_admin() => AuditContext::admin_token(&tuple.0),
+            Self::BrowserSession(session) => AuditContext::user(&session.user),
+            Self::OAuth2Session(tuple) => tuple
+                .1
+                .as_ref()
+                .map_or_else(AuditContext::anonymous, AuditContext::user),
+            Self::Anonymous => AuditContext::anonymous(),
+        }
+    }
 }
 
 impl From<BrowserSession> for Requester {
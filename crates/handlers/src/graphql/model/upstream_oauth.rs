@@ -41,9 +41,10 @@ impl UpstreamOAuth2Provider {
         &self.provider.issuer
     }
 
-    /// Client ID used for this provider.
-    pub async fn client_id(&self) -> &str {
-        &self.provider.client_id
+    /// Client ID used for this provider. Not set for SAML 2.0 identity
+    /// providers.
+    pub async fn client_id(&self) -> Option<&str> {
+        self.provider.client_id.as_deref()
     }
 }
 
@@ -5,7 +5,12 @@
 // Please see LICENSE in the repository root for full details.
 
 use async_graphql::{Context, Enum, InputObject, Object, ID};
-use mas_storage::RepositoryAccess;
+use mas_data_model::AuditEventKind;
+use mas_storage::{
+    job::{schedule_backchannel_logout_jobs, JobRepositoryExt, SyncDevicesJob},
+    oauth2::OAuth2SessionFilter,
+    RepositoryAccess,
+};
 
 use crate::graphql::{
     model::{BrowserSession, NodeType},
@@ -84,8 +89,38 @@ impl BrowserSessionMutations {
             return Ok(EndBrowserSessionPayload::NotFound);
         }
 
+        let filter = OAuth2SessionFilter::new()
+            .for_browser_session(&session)
+            .active_only();
+
+        // Notify the clients which have sessions started from this browser
+        // session that it ended
+        schedule_backchannel_logout_jobs(&mut repo, filter).await?;
+
+        // End all the OAuth 2.0 sessions which were started from this browser
+        // session
+        let affected = repo.oauth2_session().finish_bulk(&clock, filter).await?;
+
+        // Schedule a job to sync the devices of the user with the homeserver
+        if affected > 0 {
+            repo.job()
+                .schedule_job(SyncDevicesJob::new(&session.user))
+                .await?;
+        }
+
         let session = repo.browser_session().finish(&clock, session).await?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &clock,
+                ctx.audit_context(),
+                AuditEventKind::SessionEnded,
+                Some(&session.user),
+                serde_json::json!({ "session_type": "browser", "session_id": session.id }),
+            )
+            .await?;
+
         repo.save().await?;
 
         Ok(EndBrowserSessionPayload::Ended(Box::new(session)))
@@ -6,6 +6,7 @@
 
 use anyhow::Context as _;
 use async_graphql::{Context, Enum, InputObject, Object, ID};
+use mas_data_model::AuditEventKind;
 use mas_storage::{
     compat::CompatSessionRepository,
     job::{JobRepositoryExt, SyncDevicesJob},
@@ -98,6 +99,17 @@ impl CompatSessionMutations {
 
         let session = repo.compat_session().finish(&clock, session).await?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &clock,
+                ctx.audit_context(),
+                AuditEventKind::SessionEnded,
+                Some(&user),
+                serde_json::json!({ "session_type": "compat", "session_id": session.id }),
+            )
+            .await?;
+
         repo.save().await?;
 
         Ok(EndCompatSessionPayload::Ended(Box::new(session)))
@@ -10,6 +10,7 @@ mod matrix;
 mod oauth2_session;
 mod user;
 mod user_email;
+mod user_passkey;
 
 use async_graphql::MergedObject;
 
@@ -17,6 +18,7 @@ use async_graphql::MergedObject;
 #[derive(Default, MergedObject)]
 pub struct Mutation(
     user_email::UserEmailMutations,
+    user_passkey::UserPasskeyMutations,
     user::UserMutations,
     oauth2_session::OAuth2SessionMutations,
     compat_session::CompatSessionMutations,
@@ -7,9 +7,9 @@
 use anyhow::Context as _;
 use async_graphql::{Context, Description, Enum, InputObject, Object, ID};
 use chrono::Duration;
-use mas_data_model::{Device, TokenType};
+use mas_data_model::{AuditEventKind, Device, TokenType};
 use mas_storage::{
-    job::{JobRepositoryExt, SyncDevicesJob},
+    job::{BackchannelLogoutScheduler, JobRepositoryExt, SyncDevicesJob},
     oauth2::{
         OAuth2AccessTokenRepository, OAuth2ClientRepository, OAuth2RefreshTokenRepository,
         OAuth2SessionRepository,
@@ -181,7 +181,7 @@ impl OAuth2SessionMutations {
         };
         let access_token = repo
             .oauth2_access_token()
-            .add(&mut rng, &clock, &session, access_token, ttl)
+            .add(&mut rng, &clock, &session, access_token, ttl, None)
             .await?;
 
         let refresh_token = if permanent {
@@ -227,7 +227,7 @@ impl OAuth2SessionMutations {
             return Ok(EndOAuth2SessionPayload::NotFound);
         }
 
-        if let Some(user_id) = session.user_id {
+        let user = if let Some(user_id) = session.user_id {
             let user = repo
                 .user()
                 .lookup(user_id)
@@ -236,10 +236,30 @@ impl OAuth2SessionMutations {
 
             // Schedule a job to sync the devices of the user with the homeserver
             repo.job().schedule_job(SyncDevicesJob::new(&user)).await?;
-        }
+
+            Some(user)
+        } else {
+            None
+        };
+
+        // Notify the client if it registered a back-channel logout URI
+        BackchannelLogoutScheduler::default()
+            .schedule(&mut repo, &session)
+            .await?;
 
         let session = repo.oauth2_session().finish(&clock, session).await?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &clock,
+                ctx.audit_context(),
+                AuditEventKind::SessionEnded,
+                user.as_ref(),
+                serde_json::json!({ "session_type": "oauth2", "session_id": session.id }),
+            )
+            .await?;
+
         repo.save().await?;
 
         Ok(EndOAuth2SessionPayload::Ended(session))
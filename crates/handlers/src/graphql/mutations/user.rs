@@ -6,17 +6,21 @@
 
 use anyhow::Context as _;
 use async_graphql::{Context, Description, Enum, InputObject, Object, ID};
+use mas_data_model::{AuditEventKind, WebhookEventKind};
 use mas_storage::{
-    job::{DeactivateUserJob, JobRepositoryExt, ProvisionUserJob},
+    job::{DeactivateUserJob, DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob},
     user::UserRepository,
 };
 use tracing::{info, warn};
 use zeroize::Zeroizing;
 
-use crate::graphql::{
-    model::{NodeType, User},
-    state::ContextExt,
-    Requester, UserId,
+use crate::{
+    graphql::{
+        model::{NodeType, User},
+        state::ContextExt,
+        Requester, UserId,
+    },
+    username_valid,
 };
 
 #[derive(Default)]
@@ -323,36 +327,6 @@ impl SetPasswordPayload {
     }
 }
 
-fn valid_username_character(c: char) -> bool {
-    c.is_ascii_lowercase()
-        || c.is_ascii_digit()
-        || c == '='
-        || c == '_'
-        || c == '-'
-        || c == '.'
-        || c == '/'
-        || c == '+'
-}
-
-// XXX: this should probably be moved somewhere else
-fn username_valid(username: &str) -> bool {
-    if username.is_empty() || username.len() > 255 {
-        return false;
-    }
-
-    // Should not start with an underscore
-    if username.starts_with('_') {
-        return false;
-    }
-
-    // Should only contain valid characters
-    if !username.chars().all(valid_username_character) {
-        return false;
-    }
-
-    true
-}
-
 #[Object]
 impl UserMutations {
     /// Add a user. This is only available to administrators.
@@ -402,6 +376,13 @@ impl UserMutations {
             .schedule_job(ProvisionUserJob::new(&user))
             .await?;
 
+        repo.job()
+            .schedule_job(
+                DispatchWebhookEventJob::new(WebhookEventKind::UserRegistered, &user)
+                    .with_data(serde_json::json!({ "method": "admin" })),
+            )
+            .await?;
+
         repo.save().await?;
 
         Ok(AddUserPayload::Added(user))
@@ -433,6 +414,24 @@ impl UserMutations {
 
         let user = repo.user().lock(&state.clock(), user).await?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &state.clock(),
+                ctx.audit_context(),
+                AuditEventKind::UserLocked,
+                Some(&user),
+                serde_json::json!({ "deactivate": deactivate }),
+            )
+            .await?;
+
+        repo.job()
+            .schedule_job(
+                DispatchWebhookEventJob::new(WebhookEventKind::UserLocked, &user)
+                    .with_data(serde_json::json!({ "deactivate": deactivate })),
+            )
+            .await?;
+
         if deactivate {
             info!("Scheduling deactivation of user {}", user.id);
             repo.job()
@@ -474,6 +473,24 @@ impl UserMutations {
         // Now unlock the user in our database
         let user = repo.user().unlock(user).await?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &state.clock(),
+                ctx.audit_context(),
+                AuditEventKind::UserUnlocked,
+                Some(&user),
+                serde_json::json!({}),
+            )
+            .await?;
+
+        repo.job()
+            .schedule_job(DispatchWebhookEventJob::new(
+                WebhookEventKind::UserUnlocked,
+                &user,
+            ))
+            .await?;
+
         repo.save().await?;
 
         Ok(UnlockUserPayload::Unlocked(user))
@@ -650,6 +667,17 @@ impl UserMutations {
             )
             .await?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &state.clock(),
+                ctx.audit_context(),
+                AuditEventKind::UserPasswordChanged,
+                Some(&user),
+                serde_json::json!({}),
+            )
+            .await?;
+
         repo.save().await?;
 
         Ok(SetPasswordPayload {
@@ -747,6 +775,17 @@ impl UserMutations {
             )
             .await?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &clock,
+                ctx.audit_context(),
+                AuditEventKind::UserPasswordChanged,
+                Some(&user),
+                serde_json::json!({ "recovery_session_id": session.id }),
+            )
+            .await?;
+
         // Mark the session as consumed
         repo.user_recovery()
             .consume_ticket(&clock, ticket, session)
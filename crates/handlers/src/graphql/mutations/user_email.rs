@@ -6,8 +6,9 @@
 
 use anyhow::Context as _;
 use async_graphql::{Context, Description, Enum, InputObject, Object, ID};
+use mas_data_model::{AuditEventKind, WebhookEventKind};
 use mas_storage::{
-    job::{JobRepositoryExt, ProvisionUserJob, VerifyEmailJob},
+    job::{DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob, VerifyEmailJob},
     user::{UserEmailRepository, UserRepository},
     RepositoryAccess,
 };
@@ -416,6 +417,22 @@ impl UserEmailMutations {
             let mut policy = state.policy().await?;
             let res = policy.evaluate_email(&input.email).await?;
             if !res.valid() {
+                repo.audit_event()
+                    .add(
+                        &mut state.rng(),
+                        &state.clock(),
+                        ctx.audit_context(),
+                        AuditEventKind::PolicyDenied,
+                        Some(&user),
+                        serde_json::json!({
+                            "operation": "add_email",
+                            "email": input.email,
+                            "violations": res.violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+                        }),
+                    )
+                    .await?;
+                repo.save().await?;
+
                 return Ok(AddEmailPayload::Denied {
                     violations: res.violations,
                 });
@@ -435,6 +452,17 @@ impl UserEmailMutations {
                 .add(&mut rng, &clock, &user, input.email)
                 .await?;
 
+            repo.audit_event()
+                .add(
+                    &mut rng,
+                    &clock,
+                    ctx.audit_context(),
+                    AuditEventKind::UserEmailAdded,
+                    Some(&user),
+                    serde_json::json!({ "email": user_email.email }),
+                )
+                .await?;
+
             (true, user_email)
         };
 
@@ -445,6 +473,13 @@ impl UserEmailMutations {
                     .user_email()
                     .mark_as_verified(&state.clock(), user_email)
                     .await?;
+
+                repo.job()
+                    .schedule_job(
+                        DispatchWebhookEventJob::new(WebhookEventKind::UserEmailVerified, &user)
+                            .with_data(serde_json::json!({ "email": user_email.email })),
+                    )
+                    .await?;
             } else {
                 // TODO: figure out the locale
                 repo.job()
@@ -571,6 +606,13 @@ impl UserEmailMutations {
             .schedule_job(ProvisionUserJob::new(&user))
             .await?;
 
+        repo.job()
+            .schedule_job(
+                DispatchWebhookEventJob::new(WebhookEventKind::UserEmailVerified, &user)
+                    .with_data(serde_json::json!({ "email": user_email.email })),
+            )
+            .await?;
+
         repo.save().await?;
 
         Ok(VerifyEmailPayload::Verified(user_email))
@@ -615,6 +657,17 @@ impl UserEmailMutations {
 
         repo.user_email().remove(user_email.clone()).await?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &state.clock(),
+                ctx.audit_context(),
+                AuditEventKind::UserEmailRemoved,
+                Some(&user),
+                serde_json::json!({ "email": user_email.email }),
+            )
+            .await?;
+
         // Schedule a job to update the user
         repo.job()
             .schedule_job(ProvisionUserJob::new(&user))
@@ -665,6 +718,17 @@ impl UserEmailMutations {
             .await?
             .context("Failed to load user")?;
 
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &state.clock(),
+                ctx.audit_context(),
+                AuditEventKind::UserPrimaryEmailChanged,
+                Some(&user),
+                serde_json::json!({ "email": user_email.email }),
+            )
+            .await?;
+
         repo.save().await?;
 
         Ok(SetPrimaryEmailPayload::Set(user))
@@ -0,0 +1,152 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use anyhow::Context as _;
+use async_graphql::{Context, Description, Enum, InputObject, Object, ID};
+use mas_data_model::AuditEventKind;
+use mas_storage::{
+    user::{UserPasskeyRepository, UserRepository},
+    RepositoryAccess,
+};
+
+use crate::{
+    graphql::{
+        model::{NodeType, User, UserPasskey},
+        state::ContextExt,
+    },
+    webauthn::has_recent_authentication,
+};
+
+#[derive(Default)]
+pub struct UserPasskeyMutations {
+    _private: (),
+}
+
+/// The input for the `removePasskey` mutation
+#[derive(InputObject)]
+struct RemovePasskeyInput {
+    /// The ID of the passkey to remove
+    user_passkey_id: ID,
+}
+
+/// The status of the `removePasskey` mutation
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum RemovePasskeyStatus {
+    /// The passkey was removed
+    Removed,
+
+    /// The passkey was not found
+    NotFound,
+
+    /// The user must authenticate again before removing a passkey
+    ReauthenticationRequired,
+}
+
+/// The payload of the `removePasskey` mutation
+#[derive(Description)]
+enum RemovePasskeyPayload {
+    Removed(mas_data_model::UserPasskey),
+    NotFound,
+    ReauthenticationRequired,
+}
+
+#[Object(use_type_description)]
+impl RemovePasskeyPayload {
+    /// Status of the operation
+    async fn status(&self) -> RemovePasskeyStatus {
+        match self {
+            RemovePasskeyPayload::Removed(_) => RemovePasskeyStatus::Removed,
+            RemovePasskeyPayload::NotFound => RemovePasskeyStatus::NotFound,
+            RemovePasskeyPayload::ReauthenticationRequired => {
+                RemovePasskeyStatus::ReauthenticationRequired
+            }
+        }
+    }
+
+    /// The passkey that was removed
+    async fn passkey(&self) -> Option<UserPasskey> {
+        match self {
+            RemovePasskeyPayload::Removed(passkey) => Some(UserPasskey(passkey.clone())),
+            RemovePasskeyPayload::NotFound | RemovePasskeyPayload::ReauthenticationRequired => None,
+        }
+    }
+
+    /// The user to whom the passkey belonged
+    async fn user(&self, ctx: &Context<'_>) -> Result<Option<User>, async_graphql::Error> {
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+
+        let user_id = match self {
+            RemovePasskeyPayload::Removed(passkey) => passkey.user_id,
+            RemovePasskeyPayload::NotFound | RemovePasskeyPayload::ReauthenticationRequired => {
+                return Ok(None)
+            }
+        };
+
+        let user = repo
+            .user()
+            .lookup(user_id)
+            .await?
+            .context("User not found")?;
+
+        Ok(Some(User(user)))
+    }
+}
+
+#[Object]
+impl UserPasskeyMutations {
+    /// Remove a passkey
+    async fn remove_passkey(
+        &self,
+        ctx: &Context<'_>,
+        input: RemovePasskeyInput,
+    ) -> Result<RemovePasskeyPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let user_passkey_id = NodeType::UserPasskey.extract_ulid(&input.user_passkey_id)?;
+        let requester = ctx.requester();
+
+        let mut repo = state.repository().await?;
+
+        let user_passkey = repo.user_passkey().lookup(user_passkey_id).await?;
+        let Some(user_passkey) = user_passkey else {
+            return Ok(RemovePasskeyPayload::NotFound);
+        };
+
+        if !requester.is_owner_or_admin(&user_passkey) {
+            return Ok(RemovePasskeyPayload::NotFound);
+        }
+
+        // Passkeys are enough to log in, so users need to have authenticated
+        // recently to remove one from their browser session
+        if let Some(browser_session) = requester.browser_session() {
+            if !has_recent_authentication(&mut repo, &state.clock(), browser_session).await? {
+                return Ok(RemovePasskeyPayload::ReauthenticationRequired);
+            }
+        }
+
+        let user = repo
+            .user()
+            .lookup(user_passkey.user_id)
+            .await?
+            .context("Failed to load user")?;
+
+        repo.user_passkey().remove(user_passkey.clone()).await?;
+
+        repo.audit_event()
+            .add(
+                &mut state.rng(),
+                &state.clock(),
+                ctx.audit_context(),
+                AuditEventKind::UserPasskeyRemoved,
+                Some(&user),
+                serde_json::json!({ "passkey_id": user_passkey.id, "name": user_passkey.name }),
+            )
+            .await?;
+
+        repo.save().await?;
+
+        Ok(RemovePasskeyPayload::Removed(user_passkey))
+    }
+}
@@ -9,7 +9,7 @@ use async_graphql::{Context, MergedObject, Object, ID};
 use crate::graphql::{
     model::{
         Anonymous, BrowserSession, CompatSession, Node, NodeType, OAuth2Client, OAuth2Session,
-        SiteConfig, User, UserEmail,
+        SiteConfig, User, UserEmail, UserPasskey,
     },
     state::ContextExt,
 };
@@ -182,6 +182,31 @@ impl BaseQuery {
         Ok(Some(UserEmail(user_email)))
     }
 
+    /// Fetch a user passkey by its ID.
+    async fn user_passkey(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> Result<Option<UserPasskey>, async_graphql::Error> {
+        let state = ctx.state();
+        let id = NodeType::UserPasskey.extract_ulid(&id)?;
+        let requester = ctx.requester();
+
+        let mut repo = state.repository().await?;
+        let user_passkey = repo.user_passkey().lookup(id).await?;
+        repo.cancel().await?;
+
+        let Some(user_passkey) = user_passkey else {
+            return Ok(None);
+        };
+
+        if !requester.is_owner_or_admin(&user_passkey) {
+            return Ok(None);
+        }
+
+        Ok(Some(UserPasskey(user_passkey)))
+    }
+
     /// Fetches an object given its ID.
     async fn node(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Node>, async_graphql::Error> {
         // Special case for the anonymous user
@@ -221,6 +246,11 @@ impl BaseQuery {
                 .await?
                 .map(|e| Node::UserEmail(Box::new(e))),
 
+            NodeType::UserPasskey => self
+                .user_passkey(ctx, id)
+                .await?
+                .map(|p| Node::UserPasskey(Box::new(p))),
+
             NodeType::CompatSession => self
                 .compat_session(ctx, id)
                 .await?
@@ -4,7 +4,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
-use mas_data_model::SiteConfig;
+use mas_data_model::{AuditContext, SiteConfig};
 use mas_matrix::HomeserverConnection;
 use mas_policy::Policy;
 use mas_storage::{BoxClock, BoxRepository, BoxRng, RepositoryError};
@@ -28,6 +28,8 @@ pub trait ContextExt {
     fn state(&self) -> &BoxState;
 
     fn requester(&self) -> &Requester;
+
+    fn audit_context(&self) -> &AuditContext;
 }
 
 impl ContextExt for async_graphql::Context<'_> {
@@ -38,4 +40,8 @@ impl ContextExt for async_graphql::Context<'_> {
     fn requester(&self) -> &Requester {
         self.data_unchecked()
     }
+
+    fn audit_context(&self) -> &AuditContext {
+        self.data_unchecked()
+    }
 }
@@ -6,11 +6,14 @@
 
 use axum::http::Request;
 use hyper::StatusCode;
-use mas_data_model::{AccessToken, Client, TokenType, User};
+use mas_axum_utils::SessionInfoExt;
+use mas_data_model::{AccessToken, AuditEventKind, Client, TokenType, User};
 use mas_matrix::{HomeserverConnection, ProvisionRequest};
 use mas_router::SimpleRoute;
 use mas_storage::{
+    audit::AuditEventFilter,
     oauth2::{OAuth2AccessTokenRepository, OAuth2ClientRepository},
+    user::{BrowserSessionRepository, UserPasskeyRepository, UserPasswordRepository},
     RepositoryAccess,
 };
 use oauth2_types::{
@@ -22,7 +25,9 @@ use sqlx::PgPool;
 
 use crate::{
     test_utils,
-    test_utils::{setup, RequestBuilderExt, ResponseExt, TestState},
+    test_utils::{
+        add_passkey, setup, CookieHelper, RequestBuilderExt, ResponseExt, SoftPasskey, TestState,
+    },
 };
 
 async fn create_test_client(state: &TestState) -> Client {
@@ -50,6 +55,16 @@ async fn create_test_client(state: &TestState) -> Client {
             None,
             None,
             None,
+            vec![],
+            None,
+            false,
+            false,
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
         )
         .await
         .unwrap();
@@ -100,7 +115,14 @@ async fn start_oauth_session(
 
     let access_token = repo
         .oauth2_access_token()
-        .add(&mut rng, &state.clock, &session, access_token_str, None)
+        .add(
+            &mut rng,
+            &state.clock,
+            &session,
+            access_token_str,
+            None,
+            None,
+        )
         .await
         .unwrap();
 
@@ -775,3 +797,111 @@ async fn test_add_user(pool: PgPool) {
         })
     );
 }
+
+/// Test the removePasskey mutation
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_remove_passkey(pool: PgPool) {
+    setup();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let mut rng = state.rng();
+    let alice = create_test_user(&state, "alice").await;
+    let bob = create_test_user(&state, "bob").await;
+    let user_passkey = add_passkey(&state, &alice, &mut SoftPasskey::new(&mut rng)).await;
+
+    // Start a browser session for both users, only bob's being authenticated
+    let mut repo = state.repository().await.unwrap();
+    let alice_session = repo
+        .browser_session()
+        .add(&mut rng, &state.clock, &alice, None)
+        .await
+        .unwrap();
+    let bob_session = repo
+        .browser_session()
+        .add(&mut rng, &state.clock, &bob, None)
+        .await
+        .unwrap();
+    let bob_password = repo
+        .user_password()
+        .add(&mut rng, &state.clock, &bob, 1, "hash".to_owned(), None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_password(&mut rng, &state.clock, &bob_session, &bob_password)
+        .await
+        .unwrap();
+    repo.save().await.unwrap();
+
+    let alice_cookies = CookieHelper::new();
+    alice_cookies.import(state.cookie_jar().set_session(&alice_session));
+    let bob_cookies = CookieHelper::new();
+    bob_cookies.import(state.cookie_jar().set_session(&bob_session));
+
+    let remove = |cookies: &CookieHelper| {
+        cookies.with_cookies(Request::post("/graphql").json(serde_json::json!({
+            "query": r"
+                mutation RemovePasskey($id: ID!) {
+                    removePasskey(input: { userPasskeyId: $id }) {
+                        status
+                    }
+                }
+            ",
+            "variables": {
+                "id": format!("user_passkey:{}", user_passkey.id),
+            },
+        })))
+    };
+
+    // Alice didn't authenticate recently
+    let response = state.request(remove(&alice_cookies)).await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        serde_json::json!({ "removePasskey": { "status": "REAUTHENTICATION_REQUIRED" } })
+    );
+
+    // Bob can't remove Alice's passkey
+    let response = state.request(remove(&bob_cookies)).await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        serde_json::json!({ "removePasskey": { "status": "NOT_FOUND" } })
+    );
+
+    // Once Alice authenticated, she can remove it
+    let mut repo = state.repository().await.unwrap();
+    let alice_password = repo
+        .user_password()
+        .add(&mut rng, &state.clock, &alice, 1, "hash".to_owned(), None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_password(&mut rng, &state.clock, &alice_session, &alice_password)
+        .await
+        .unwrap();
+    repo.save().await.unwrap();
+
+    let response = state.request(remove(&alice_cookies)).await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        serde_json::json!({ "removePasskey": { "status": "REMOVED" } })
+    );
+
+    let mut repo = state.repository().await.unwrap();
+    assert!(repo
+        .user_passkey()
+        .lookup(user_passkey.id)
+        .await
+        .unwrap()
+        .is_none());
+    let filter = AuditEventFilter::new()
+        .for_user(&alice)
+        .with_kind(AuditEventKind::UserPasskeyRemoved);
+    assert_eq!(repo.audit_event().count(filter).await.unwrap(), 1);
+}
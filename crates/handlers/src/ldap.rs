@@ -0,0 +1,612 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Password authentication against an LDAP directory
+//!
+//! The user entry is first searched with the configured filter, using the
+//! service account if there is one, then the password is checked by binding
+//! as that entry. Users are created on their first successful login, and
+//! linked to the directory entry through its subject.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use mas_config::{LdapConfig, LdapMode};
+use mas_data_model::{
+    Authentication, BrowserSession, Password, User, UserLdapLink, WebhookEventKind,
+};
+use mas_matrix::BoxHomeserverConnection;
+use mas_storage::{
+    job::{DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob},
+    BoxRepository, Clock, RepositoryAccess, RepositoryError,
+};
+use minijinja::{Environment, Value};
+use rand::{CryptoRng, RngCore};
+use thiserror::Error;
+
+use crate::{upstream_oauth2::template::environment, username_valid};
+
+const DEFAULT_SUBJECT_TEMPLATE: &str = "{{ user.entryUUID }}";
+const DEFAULT_LOCALPART_TEMPLATE: &str = "{{ user.uid }}";
+const DEFAULT_DISPLAYNAME_TEMPLATE: &str = "{{ user.cn }}";
+const DEFAULT_EMAIL_TEMPLATE: &str = "{{ user.mail }}";
+
+/// The LDAP result code returned when a bind fails because of wrong
+/// credentials
+const INVALID_CREDENTIALS: u32 = 49;
+
+#[derive(Debug, Error)]
+pub enum LdapAuthenticationError {
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+
+    #[error("Multiple directory entries match the username")]
+    AmbiguousUsername,
+
+    #[error("Failed to render the {attribute} attribute")]
+    Template {
+        attribute: &'static str,
+        #[source]
+        source: minijinja::Error,
+    },
+
+    #[error("The {0} attribute is empty")]
+    EmptyAttribute(&'static str),
+
+    #[error(transparent)]
+    Directory(#[from] LdapError),
+}
+
+/// The attributes of a directory entry, mapped to a user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdapUser {
+    pub subject: String,
+    pub localpart: String,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Ldap {
+    inner: Arc<LdapInner>,
+}
+
+struct LdapInner {
+    url: String,
+    mode: LdapMode,
+    starttls: bool,
+    timeout: Duration,
+    bind: Option<(String, String)>,
+    base_dn: String,
+    user_filter: String,
+    subject_template: String,
+    localpart_template: String,
+    displayname_template: String,
+    email_template: String,
+}
+
+impl Ldap {
+    /// Creates a new `Ldap` backend based on a `LdapConfig`.
+    ///
+    /// Returns `None` if LDAP authentication is not enabled.
+    #[must_use]
+    pub fn new(config: &LdapConfig) -> Option<Self> {
+        let url = config.url.as_ref()?;
+        let bind = config.bind_dn.clone().zip(config.bind_password.clone());
+        let attributes = &config.attributes;
+
+        Some(Self {
+            inner: Arc::new(LdapInner {
+                url: url.to_string(),
+                mode: config.mode,
+                starttls: config.starttls,
+                timeout: Duration::from_secs(config.connection_timeout),
+                bind,
+                base_dn: config.base_dn.clone(),
+                user_filter: config.user_filter.clone(),
+                subject_template: attributes
+                    .subject
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_SUBJECT_TEMPLATE.to_owned()),
+                localpart_template: attributes
+                    .localpart
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_LOCALPART_TEMPLATE.to_owned()),
+                displayname_template: attributes
+                    .displayname
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_DISPLAYNAME_TEMPLATE.to_owned()),
+                email_template: attributes
+                    .email
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_EMAIL_TEMPLATE.to_owned()),
+            }),
+        })
+    }
+
+    /// How the directory is used alongside the passwords stored in the
+    /// database
+    #[must_use]
+    pub fn mode(&self) -> LdapMode {
+        self.inner.mode
+    }
+
+    /// Render the filter used to search the entry of the given username
+    fn user_filter(&self, username: &str) -> Result<String, LdapAuthenticationError> {
+        let username = ldap_escape(username);
+        environment()
+            .render_str(
+                &self.inner.user_filter,
+                minijinja::context! { username => username },
+            )
+            .map_err(|source| LdapAuthenticationError::Template {
+                attribute: "user_filter",
+                source,
+            })
+    }
+
+    /// Map the attributes of a directory entry to a user
+    fn map_entry(&self, entry: SearchEntry) -> Result<LdapUser, LdapAuthenticationError> {
+        let mut attributes = HashMap::new();
+        for (name, values) in entry.bin_attrs {
+            if let Some(value) = values.into_iter().next() {
+                attributes.insert(name, Value::from(Arc::new(value)));
+            }
+        }
+        for (name, values) in entry.attrs {
+            if let Some(value) = values.into_iter().next() {
+                attributes.insert(name, Value::from(value));
+            }
+        }
+        attributes.insert("dn".to_owned(), Value::from(entry.dn));
+
+        let env = {
+            let mut env = environment();
+            env.add_global("user", Value::from_iter(attributes));
+            env
+        };
+
+        let subject = render_attribute(&env, "subject", &self.inner.subject_template)?
+            .ok_or(LdapAuthenticationError::EmptyAttribute("subject"))?;
+        let localpart = render_attribute(&env, "localpart", &self.inner.localpart_template)?
+            .ok_or(LdapAuthenticationError::EmptyAttribute("localpart"))?;
+
+        // The display name and email are optional, so we don't fail if they can't
+        // be rendered
+        let display_name = render_attribute(&env, "displayname", &self.inner.displayname_template)
+            .unwrap_or_else(|e| {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    "Could not map the display name"
+                );
+                None
+            });
+        let email =
+            render_attribute(&env, "email", &self.inner.email_template).unwrap_or_else(|e| {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    "Could not map the email"
+                );
+                None
+            });
+
+        Ok(LdapUser {
+            subject,
+            localpart,
+            display_name,
+            email,
+        })
+    }
+
+    /// Check the credentials of a user against the directory
+    ///
+    /// Returns `None` if no entry matches the username.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LdapAuthenticationError::InvalidCredentials`] if the password
+    /// is wrong, or another error if the directory could not be queried.
+    #[tracing::instrument(name = "ldap.authenticate", skip_all, err)]
+    pub async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<LdapUser>, LdapAuthenticationError> {
+        // Most servers treat a bind with an empty password as an anonymous bind,
+        // which would succeed
+        if password.is_empty() {
+            return Err(LdapAuthenticationError::InvalidCredentials);
+        }
+
+        let filter = self.user_filter(username)?;
+
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(self.inner.timeout)
+            .set_starttls(self.inner.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.inner.url).await?;
+        ldap3::drive!(conn);
+        ldap.with_timeout(self.inner.timeout);
+
+        if let Some((bind_dn, bind_password)) = &self.inner.bind {
+            ldap.simple_bind(bind_dn, bind_password).await?.success()?;
+        }
+
+        let (entries, _) = ldap
+            .search(
+                &self.inner.base_dn,
+                Scope::Subtree,
+                &filter,
+                vec!["*", "entryUUID"],
+            )
+            .await?
+            .success()?;
+
+        let mut entries = entries.into_iter().map(SearchEntry::construct);
+        let Some(entry) = entries.next() else {
+            ldap.unbind().await?;
+            return Ok(None);
+        };
+
+        if entries.next().is_some() {
+            ldap.unbind().await?;
+            return Err(LdapAuthenticationError::AmbiguousUsername);
+        }
+
+        // Now check the password by binding as the user
+        let result = ldap.simple_bind(&entry.dn, password).await?;
+        ldap.unbind().await?;
+        if result.rc == INVALID_CREDENTIALS {
+            return Err(LdapAuthenticationError::InvalidCredentials);
+        }
+        result.success()?;
+
+        self.map_entry(entry).map(Some)
+    }
+}
+
+/// Render an attribute template, returning `None` if it renders to an empty
+/// string
+fn render_attribute(
+    env: &Environment,
+    attribute: &'static str,
+    template: &str,
+) -> Result<Option<String>, LdapAuthenticationError> {
+    let value = env
+        .render_str(template, ())
+        .map_err(|source| LdapAuthenticationError::Template { attribute, source })?;
+
+    if value.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(value))
+    }
+}
+
+/// The credential checked by a successful password login
+pub(crate) enum PasswordCredential {
+    /// A password stored in the database
+    Password(Password),
+
+    /// The password of a directory entry
+    Ldap(UserLdapLink),
+}
+
+impl PasswordCredential {
+    /// The login method recorded in audit events and webhooks
+    pub fn method(&self) -> &'static str {
+        match self {
+            Self::Password(_) => "password",
+            Self::Ldap(_) => "ldap",
+        }
+    }
+
+    /// Mark a browser session as authenticated by this credential
+    pub async fn authenticate_session(
+        &self,
+        repo: &mut BoxRepository,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        session: &BrowserSession,
+    ) -> Result<Authentication, RepositoryError> {
+        match self {
+            Self::Password(user_password) => {
+                repo.browser_session()
+                    .authenticate_with_password(rng, clock, session, user_password)
+                    .await
+            }
+            Self::Ldap(user_ldap_link) => {
+                repo.browser_session()
+                    .authenticate_with_ldap(rng, clock, session, user_ldap_link)
+                    .await
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum LdapLoginError {
+    #[error(transparent)]
+    Authentication(#[from] LdapAuthenticationError),
+
+    #[error("User is locked")]
+    UserLocked,
+
+    #[error("Username {0:?} is not valid")]
+    UsernameNotValid(String),
+
+    #[error("Username {0:?} is already taken")]
+    UsernameTaken(String),
+
+    #[error(transparent)]
+    Homeserver(anyhow::Error),
+
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+}
+
+impl LdapLoginError {
+    /// Returns `true` if the login failed because of an issue on our side or
+    /// with the directory, rather than because of the user credentials
+    pub fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            Self::Repository(_)
+                | Self::Homeserver(_)
+                | Self::Authentication(LdapAuthenticationError::Directory(_))
+        )
+    }
+}
+
+/// Check the credentials against the directory, and find the user linked to
+/// the matching entry, creating it if needed
+///
+/// Returns `None` if no entry matches the username.
+pub(crate) async fn login(
+    ldap: &Ldap,
+    repo: &mut BoxRepository,
+    rng: &mut (impl RngCore + CryptoRng + Send),
+    clock: &impl Clock,
+    homeserver: &BoxHomeserverConnection,
+    username: &str,
+    password: &str,
+) -> Result<Option<(User, UserLdapLink)>, LdapLoginError> {
+    let Some(entry) = ldap.authenticate(username, password).await? else {
+        return Ok(None);
+    };
+
+    link_entry(repo, rng, clock, homeserver, entry)
+        .await
+        .map(Some)
+}
+
+/// Find the user linked to an authenticated directory entry, creating it if
+/// needed
+async fn link_entry(
+    repo: &mut BoxRepository,
+    rng: &mut (impl RngCore + CryptoRng + Send),
+    clock: &impl Clock,
+    homeserver: &BoxHomeserverConnection,
+    entry: LdapUser,
+) -> Result<(User, UserLdapLink), LdapLoginError> {
+    let link = repo
+        .user_ldap_link()
+        .find_by_subject(&entry.subject)
+        .await?;
+    if let Some(link) = link {
+        let user = repo
+            .user()
+            .lookup(link.user_id)
+            .await?
+            .filter(User::is_valid)
+            .ok_or(LdapLoginError::UserLocked)?;
+
+        return Ok((user, link));
+    }
+
+    // This is the first login of this entry, create a user for it
+    if !username_valid(&entry.localpart) {
+        return Err(LdapLoginError::UsernameNotValid(entry.localpart));
+    }
+
+    // We don't link existing users to the directory, as this would let an entry
+    // take over a local account just because the localpart matches
+    if repo.user().exists(&entry.localpart).await? {
+        return Err(LdapLoginError::UsernameTaken(entry.localpart));
+    }
+
+    let available = homeserver
+        .is_localpart_available(&entry.localpart)
+        .await
+        .map_err(LdapLoginError::Homeserver)?;
+    if !available {
+        return Err(LdapLoginError::UsernameTaken(entry.localpart));
+    }
+
+    let user = repo.user().add(rng, clock, entry.localpart).await?;
+    let link = repo
+        .user_ldap_link()
+        .add(rng, clock, &user, entry.subject)
+        .await?;
+
+    let mut job = ProvisionUserJob::new(&user);
+    if let Some(display_name) = entry.display_name {
+        job = job.set_display_name(display_name);
+    }
+    repo.job().schedule_job(job).await?;
+
+    repo.job()
+        .schedule_job(
+            DispatchWebhookEventJob::new(WebhookEventKind::UserRegistered, &user)
+                .with_data(serde_json::json!({ "method": "ldap" })),
+        )
+        .await?;
+
+    // Addresses coming from the directory are trusted
+    if let Some(email) = entry.email {
+        if email.parse::<lettre::Address>().is_ok() {
+            let user_email = repo.user_email().add(rng, clock, &user, email).await?;
+            let user_email = repo
+                .user_email()
+                .mark_as_verified(clock, user_email)
+                .await?;
+            repo.user_email().set_as_primary(&user_email).await?;
+
+            repo.job()
+                .schedule_job(
+                    DispatchWebhookEventJob::new(WebhookEventKind::UserEmailVerified, &user)
+                        .with_data(serde_json::json!({ "email": user_email.email })),
+                )
+                .await?;
+        } else {
+            tracing::warn!(%email, "Ignoring invalid email address from the directory");
+        }
+    }
+
+    tracing::info!(user.id = %user.id, user_ldap_link.id = %link.id, "Created user from the directory");
+
+    Ok((user, link))
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_config::LdapAttributeMapping;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_utils::{setup, TestState};
+
+    fn backend(attributes: LdapAttributeMapping) -> Ldap {
+        Ldap::new(&LdapConfig {
+            url: Some("ldap://localhost".parse().unwrap()),
+            base_dn: "ou=people,dc=example,dc=com".to_owned(),
+            user_filter: "(&(objectClass=person)(uid={{ username }}))".to_owned(),
+            attributes,
+            ..LdapConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn entry() -> SearchEntry {
+        SearchEntry {
+            dn: "uid=alice,ou=people,dc=example,dc=com".to_owned(),
+            attrs: HashMap::from([
+                (
+                    "entryUUID".to_owned(),
+                    vec!["5f3e4a2c-7b1d-4c8e-9a6f-0d2b1e3c4a5b".to_owned()],
+                ),
+                ("uid".to_owned(), vec!["alice".to_owned()]),
+                ("cn".to_owned(), vec!["Alice".to_owned()]),
+                (
+                    "mail".to_owned(),
+                    vec![
+                        "alice@example.com".to_owned(),
+                        "alice.doe@example.com".to_owned(),
+                    ],
+                ),
+            ]),
+            bin_attrs: HashMap::from([("objectGUID".to_owned(), vec![vec![0xab, 0xcd]])]),
+        }
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert!(Ldap::new(&LdapConfig::default()).is_none());
+    }
+
+    #[test]
+    fn test_user_filter() {
+        let ldap = backend(LdapAttributeMapping::default());
+        assert_eq!(
+            ldap.user_filter("alice").unwrap(),
+            "(&(objectClass=person)(uid=alice))"
+        );
+
+        // The username can't be used to inject conditions in the filter
+        assert_eq!(
+            ldap.user_filter("*)(uid=admin").unwrap(),
+            r"(&(objectClass=person)(uid=\2a\29\28uid=admin))"
+        );
+    }
+
+    #[test]
+    fn test_default_mapping() {
+        let ldap = backend(LdapAttributeMapping::default());
+        let user = ldap.map_entry(entry()).unwrap();
+        assert_eq!(
+            user,
+            LdapUser {
+                subject: "5f3e4a2c-7b1d-4c8e-9a6f-0d2b1e3c4a5b".to_owned(),
+                localpart: "alice".to_owned(),
+                display_name: Some("Alice".to_owned()),
+                email: Some("alice@example.com".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_custom_mapping() {
+        let ldap = backend(LdapAttributeMapping {
+            subject: Some("{{ user.objectGUID | b64encode }}".to_owned()),
+            localpart: Some("{{ user.uid | upper }}".to_owned()),
+            displayname: Some("{{ user.displayName }}".to_owned()),
+            email: None,
+        });
+        let user = ldap.map_entry(entry()).unwrap();
+        assert_eq!(user.subject, "q80=");
+        assert_eq!(user.localpart, "ALICE");
+        assert_eq!(user.display_name, None);
+        assert_eq!(user.email.as_deref(), Some("alice@example.com"));
+
+        // The localpart is required
+        let ldap = backend(LdapAttributeMapping {
+            localpart: Some("{{ user.sAMAccountName }}".to_owned()),
+            ..LdapAttributeMapping::default()
+        });
+        assert!(matches!(
+            ldap.map_entry(entry()),
+            Err(LdapAuthenticationError::EmptyAttribute("localpart"))
+        ));
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_link_survives_dn_change(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+        let homeserver: BoxHomeserverConnection = Box::new(state.homeserver_connection.clone());
+        let ldap = backend(LdapAttributeMapping::default());
+
+        // The first login creates the user and links it to the entry
+        let mut repo = state.repository().await.unwrap();
+        let alice = ldap.map_entry(entry()).unwrap();
+        let (user, link) = link_entry(&mut repo, &mut rng, &state.clock, &homeserver, alice)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+        assert_eq!(user.username, "alice");
+
+        // The entry is then renamed and moved, which changes its DN and uid but not
+        // its entryUUID
+        let mut renamed = entry();
+        renamed.dn = "uid=alice.doe,ou=staff,dc=example,dc=com".to_owned();
+        renamed
+            .attrs
+            .insert("uid".to_owned(), vec!["alice.doe".to_owned()]);
+
+        let mut repo = state.repository().await.unwrap();
+        let renamed = ldap.map_entry(renamed).unwrap();
+        let (same_user, same_link) =
+            link_entry(&mut repo, &mut rng, &state.clock, &homeserver, renamed)
+                .await
+                .unwrap();
+        repo.save().await.unwrap();
+
+        // The entry is still linked to the same user, and no new user was created
+        assert_eq!(same_link.id, link.id);
+        assert_eq!(same_user.id, user.id);
+        let mut repo = state.repository().await.unwrap();
+        assert!(!repo.user().exists("alice.doe").await.unwrap());
+    }
+}
@@ -50,10 +50,14 @@ mod admin;
 mod compat;
 mod graphql;
 mod health;
+pub mod ldap;
 mod oauth2;
 pub mod passwords;
+mod scim;
+mod totp;
 pub mod upstream_oauth2;
 mod views;
+mod webauthn;
 
 mod activity_tracker;
 mod captcha;
@@ -78,6 +82,38 @@ macro_rules! impl_from_error_for_route {
     };
 }
 
+// Those characters are allowed in the localpart of Matrix IDs
+fn valid_username_character(c: char) -> bool {
+    c.is_ascii_lowercase()
+        || c.is_ascii_digit()
+        || c == '='
+        || c == '_'
+        || c == '-'
+        || c == '.'
+        || c == '/'
+        || c == '+'
+}
+
+/// Check whether the given username is a valid Matrix ID localpart, before
+/// provisioning a user with it
+pub(crate) fn username_valid(username: &str) -> bool {
+    if username.is_empty() || username.len() > 255 {
+        return false;
+    }
+
+    // Should not start with an underscore
+    if username.starts_with('_') {
+        return false;
+    }
+
+    // Should only contain valid characters
+    if !username.chars().all(valid_username_character) {
+        return false;
+    }
+
+    true
+}
+
 pub use mas_axum_utils::{
     cookies::CookieManager, http_client_factory::HttpClientFactory, ErrorWrapper,
 };
@@ -90,6 +126,7 @@ pub use self::{
     },
     preferred_language::PreferredLanguage,
     rate_limit::{Limiter, RequesterFingerprint},
+    scim::router as scim_router,
     upstream_oauth2::cache::MetadataCache,
 };
 
@@ -223,6 +260,10 @@ where
             mas_router::OAuth2DeviceAuthorizationEndpoint::route(),
             post(self::oauth2::device::authorize::post),
         )
+        .route(
+            mas_router::OAuth2PushedAuthorizationRequestEndpoint::route(),
+            post(self::oauth2::pushed_authorization_request::post),
+        )
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -355,6 +396,14 @@ where
             mas_router::Login::route(),
             get(self::views::login::get).post(self::views::login::post),
         )
+        .route(
+            mas_router::LoginTotp::route(),
+            get(self::views::login_totp::get).post(self::views::login_totp::post),
+        )
+        .route(
+            mas_router::LoginPasskey::route(),
+            get(self::views::login_passkey::get).post(self::views::login_passkey::post),
+        )
         .route(mas_router::Logout::route(), post(self::views::logout::post))
         .route(
             mas_router::Reauth::route(),
@@ -374,6 +423,14 @@ where
             get(self::views::account::emails::add::get)
                 .post(self::views::account::emails::add::post),
         )
+        .route(
+            mas_router::AccountTotp::route(),
+            get(self::views::account::totp::get).post(self::views::account::totp::post),
+        )
+        .route(
+            mas_router::AccountPasskeys::route(),
+            get(self::views::account::passkeys::get).post(self::views::account::passkeys::post),
+        )
         .route(
             mas_router::AccountRecoveryStart::route(),
             get(self::views::recovery::start::get).post(self::views::recovery::start::post),
@@ -386,6 +443,10 @@ where
             mas_router::OAuth2AuthorizationEndpoint::route(),
             get(self::oauth2::authorization::get),
         )
+        .route(
+            mas_router::OidcEndSession::route(),
+            get(self::oauth2::end_session::get).post(self::oauth2::end_session::post),
+        )
         .route(
             mas_router::ContinueAuthorizationGrant::route(),
             get(self::oauth2::authorization::complete::get),
@@ -410,6 +471,18 @@ where
             mas_router::UpstreamOAuth2Link::route(),
             get(self::upstream_oauth2::link::get).post(self::upstream_oauth2::link::post),
         )
+        .route(
+            mas_router::UpstreamSaml2Metadata::route(),
+            get(self::upstream_oauth2::saml::metadata),
+        )
+        .route(
+            mas_router::UpstreamSaml2AssertionConsumer::route(),
+            post(self::upstream_oauth2::saml::assertion_consumer),
+        )
+        .route(
+            mas_router::UpstreamSaml2Complete::route(),
+            get(self::upstream_oauth2::saml::complete),
+        )
         .route(
             mas_router::DeviceCodeLink::route(),
             get(self::oauth2::device::link::get),
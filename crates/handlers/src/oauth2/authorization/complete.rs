@@ -9,25 +9,33 @@ use axum::{
     response::{Html, IntoResponse, Response},
 };
 use hyper::StatusCode;
-use mas_axum_utils::{cookies::CookieJar, csrf::CsrfExt, sentry::SentryEventID, SessionInfoExt};
-use mas_data_model::{AuthorizationGrant, BrowserSession, Client, Device};
+use mas_axum_utils::{
+    cookies::CookieJar, csrf::CsrfExt, http_client_factory::HttpClientFactory,
+    sentry::SentryEventID, SessionInfoExt,
+};
+use mas_data_model::{
+    AuditContext, AuditEventKind, AuthorizationGrant, BrowserSession, Client, Device,
+};
 use mas_keystore::Keystore;
 use mas_policy::{EvaluationResult, Policy};
 use mas_router::{PostAuthAction, UrlBuilder};
 use mas_storage::{
+    audit::AuditEventRepository,
     oauth2::{OAuth2AuthorizationGrantRepository, OAuth2ClientRepository, OAuth2SessionRepository},
     user::BrowserSessionRepository,
     BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
 };
 use mas_templates::{PolicyViolationContext, TemplateContext, Templates};
-use oauth2_types::requests::AuthorizationResponse;
+use oauth2_types::requests::{AuthorizationResponse, GrantType};
 use thiserror::Error;
 use tracing::warn;
 use ulid::Ulid;
 
 use super::callback::CallbackDestination;
 use crate::{
-    impl_from_error_for_route, oauth2::generate_id_token, BoundActivityTracker, PreferredLanguage,
+    impl_from_error_for_route,
+    oauth2::{encrypt_id_token, generate_id_token, id_token_encryption_key},
+    BoundActivityTracker, PreferredLanguage,
 };
 
 #[derive(Debug, Error)]
@@ -87,6 +95,7 @@ pub(crate) async fn get(
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
     State(key_store): State<Keystore>,
+    State(http_client_factory): State<HttpClientFactory>,
     policy: Policy,
     activity_tracker: BoundActivityTracker,
     mut repo: BoxRepository,
@@ -132,6 +141,7 @@ pub(crate) async fn get(
         &activity_tracker,
         repo,
         key_store,
+        &http_client_factory,
         policy,
         &url_builder,
         grant,
@@ -194,6 +204,7 @@ impl_from_error_for_route!(GrantCompletionError: super::callback::IntoCallbackDe
 impl_from_error_for_route!(GrantCompletionError: mas_policy::LoadError);
 impl_from_error_for_route!(GrantCompletionError: mas_policy::EvaluationError);
 impl_from_error_for_route!(GrantCompletionError: super::super::IdTokenSignatureError);
+impl_from_error_for_route!(GrantCompletionError: super::super::ResponseEncryptionError);
 
 pub(crate) async fn complete(
     rng: &mut (impl rand::RngCore + rand::CryptoRng + Send),
@@ -201,6 +212,7 @@ pub(crate) async fn complete(
     activity_tracker: &BoundActivityTracker,
     mut repo: BoxRepository,
     key_store: Keystore,
+    http_client_factory: &HttpClientFactory,
     mut policy: Policy,
     url_builder: &UrlBuilder,
     grant: AuthorizationGrant,
@@ -230,6 +242,25 @@ pub(crate) async fn complete(
         .await?;
 
     if !res.valid() {
+        let audit =
+            AuditContext::user(&browser_session.user).with_ip_address(activity_tracker.ip());
+        repo.audit_event()
+            .add(
+                rng,
+                clock,
+                &audit,
+                AuditEventKind::PolicyDenied,
+                Some(&browser_session.user),
+                serde_json::json!({
+                    "operation": "authorization_grant",
+                    "client_id": client.id,
+                    "grant_id": grant.id,
+                    "violations": res.violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+                }),
+            )
+            .await?;
+        repo.save().await?;
+
         return Err(GrantCompletionError::PolicyViolation(grant, res));
     }
 
@@ -250,12 +281,31 @@ pub(crate) async fn complete(
         return Err(GrantCompletionError::RequiresConsent);
     }
 
+    // Resolve the key to encrypt the ID token with before starting the session, as
+    // it may need to fetch the client JWKS, which shouldn't happen while holding
+    // the writes of the transaction
+    let id_token_encryption_key = if grant.response_type_id_token {
+        id_token_encryption_key(http_client_factory, client).await?
+    } else {
+        None
+    };
+
     // All good, let's start the session
     let session = repo
         .oauth2_session()
         .add_from_browser_session(rng, clock, client, browser_session, grant.scope.clone())
         .await?;
 
+    let grant_type = if grant.code.is_some() {
+        GrantType::AuthorizationCode
+    } else {
+        GrantType::Implicit
+    };
+    let session = repo
+        .oauth2_session()
+        .record_grant_type(session, grant_type)
+        .await?;
+
     let grant = repo
         .oauth2_authorization_grant()
         .fulfill(clock, &session, grant)
@@ -266,7 +316,7 @@ pub(crate) async fn complete(
 
     // Did they request an ID token?
     if grant.response_type_id_token {
-        params.id_token = Some(generate_id_token(
+        let id_token = generate_id_token(
             rng,
             clock,
             url_builder,
@@ -276,6 +326,11 @@ pub(crate) async fn complete(
             browser_session,
             None,
             Some(&valid_authentication),
+        )?;
+        params.id_token = Some(encrypt_id_token(
+            rng,
+            id_token_encryption_key.as_ref(),
+            id_token,
         )?);
     }
 
@@ -4,19 +4,30 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
+use std::collections::BTreeMap;
+
 use axum::{
     extract::{Form, State},
     response::{Html, IntoResponse, Response},
 };
 use hyper::StatusCode;
-use mas_axum_utils::{cookies::CookieJar, csrf::CsrfExt, sentry::SentryEventID, SessionInfoExt};
-use mas_data_model::{AuthorizationCode, Pkce};
-use mas_keystore::Keystore;
-use mas_policy::Policy;
+use mas_axum_utils::{
+    cookies::CookieJar, csrf::CsrfExt, http_client_factory::HttpClientFactory,
+    sentry::SentryEventID, SessionInfoExt,
+};
+use mas_data_model::{
+    AuditContext, AuditEventKind, AuthorizationCode, Client, Pkce, PushedAuthorizationRequest,
+};
+use mas_keystore::{Encrypter, Keystore};
+use mas_policy::{Policy, Violation};
 use mas_router::{PostAuthAction, UrlBuilder};
 use mas_storage::{
-    oauth2::{OAuth2AuthorizationGrantRepository, OAuth2ClientRepository},
-    BoxClock, BoxRepository, BoxRng,
+    audit::AuditEventRepository,
+    oauth2::{
+        OAuth2AuthorizationGrantRepository, OAuth2ClientRepository,
+        OAuth2PushedAuthorizationRequestRepository,
+    },
+    BoxClock, BoxRepository, BoxRng, Clock,
 };
 use mas_templates::{PolicyViolationContext, TemplateContext, Templates};
 use oauth2_types::{
@@ -26,15 +37,20 @@ use oauth2_types::{
     response_type::ResponseType,
 };
 use rand::{distributions::Alphanumeric, Rng};
-use serde::Deserialize;
+use serde::{de::value::MapDeserializer, Deserialize};
 use thiserror::Error;
 use tracing::warn;
+use url::Url;
 
-use self::{callback::CallbackDestination, complete::GrantCompletionError};
+use self::{
+    callback::CallbackDestination, complete::GrantCompletionError,
+    request_object::RequestObjectError,
+};
 use crate::{impl_from_error_for_route, BoundActivityTracker, PreferredLanguage};
 
 mod callback;
 pub mod complete;
+pub(crate) mod request_object;
 
 #[derive(Debug, Error)]
 pub enum RouteError {
@@ -44,6 +60,21 @@ pub enum RouteError {
     #[error("could not find client")]
     ClientNotFound,
 
+    #[error("invalid parameters")]
+    InvalidParameters(#[source] serde::de::value::Error),
+
+    #[error("invalid or expired request_uri")]
+    InvalidRequestUri,
+
+    #[error("client must use pushed authorization requests")]
+    PushedAuthorizationRequired,
+
+    #[error("invalid request object")]
+    InvalidRequestObject(#[from] RequestObjectError),
+
+    #[error("policy denied the request")]
+    DeniedByPolicy(Vec<Violation>),
+
     #[error("invalid response mode")]
     InvalidResponseMode,
 
@@ -65,6 +96,36 @@ impl IntoResponse for RouteError {
             RouteError::ClientNotFound => {
                 (StatusCode::BAD_REQUEST, "could not find client").into_response()
             }
+            RouteError::InvalidParameters(e) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid authorization request ({e})"),
+            )
+                .into_response(),
+            RouteError::InvalidRequestUri => {
+                (StatusCode::BAD_REQUEST, "invalid or expired request_uri").into_response()
+            }
+            RouteError::PushedAuthorizationRequired => (
+                StatusCode::BAD_REQUEST,
+                "this client must use pushed authorization requests",
+            )
+                .into_response(),
+            RouteError::InvalidRequestObject(e) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid request object ({e})"),
+            )
+                .into_response(),
+            RouteError::DeniedByPolicy(violations) => (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Authorization request denied by the policy ({})",
+                    violations
+                        .into_iter()
+                        .map(|violation| violation.msg)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )
+                .into_response(),
             RouteError::InvalidResponseMode => {
                 (StatusCode::BAD_REQUEST, "invalid response mode").into_response()
             }
@@ -91,10 +152,55 @@ impl_from_error_for_route!(mas_policy::EvaluationError);
 #[derive(Deserialize)]
 pub(crate) struct Params {
     #[serde(flatten)]
-    auth: AuthorizationRequest,
+    pub(crate) auth: AuthorizationRequest,
 
     #[serde(flatten)]
-    pkce: Option<pkce::AuthorizationRequest>,
+    pub(crate) pkce: Option<pkce::AuthorizationRequest>,
+}
+
+impl Params {
+    /// Parse the parameters of an authorization request from their raw form
+    /// values
+    pub(crate) fn from_parameters(
+        parameters: BTreeMap<String, String>,
+    ) -> Result<Self, serde::de::value::Error> {
+        Self::deserialize(MapDeserializer::new(parameters.into_iter()))
+    }
+}
+
+/// Load the parameters of a request the client pushed to the Pushed
+/// Authorization Request endpoint, and mark it as used
+///
+/// The caller must save the repository on every path once this succeeded,
+/// including when the request is then rejected, else the request could be used
+/// again
+async fn load_pushed_request(
+    clock: &impl Clock,
+    repo: &mut BoxRepository,
+    client: &Client,
+    request_uri: &str,
+) -> Result<BTreeMap<String, String>, RouteError> {
+    let id = Url::parse(request_uri)
+        .ok()
+        .as_ref()
+        .and_then(PushedAuthorizationRequest::id_from_request_uri)
+        .ok_or(RouteError::InvalidRequestUri)?;
+
+    let request = repo
+        .oauth2_pushed_authorization_request()
+        .lookup(id)
+        .await?
+        .filter(|request| request.client_id == client.id && request.is_valid(clock.now()))
+        .ok_or(RouteError::InvalidRequestUri)?;
+
+    let request = repo
+        .oauth2_pushed_authorization_request()
+        .consume(clock, request)
+        .await?
+        // It was used by a concurrent request
+        .ok_or(RouteError::InvalidRequestUri)?;
+
+    Ok(request.parameters)
 }
 
 /// Given a list of response types and an optional user-defined response mode,
@@ -121,9 +227,36 @@ fn resolve_response_mode(
     }
 }
 
+/// Parse the parameters of an authorization request, and resolve the
+/// redirect_uri and response_mode to figure out where to send errors back to
+fn validate_request(
+    client: &Client,
+    parameters: BTreeMap<String, String>,
+) -> Result<(Params, Url, ResponseMode, CallbackDestination), RouteError> {
+    let params = Params::from_parameters(parameters).map_err(RouteError::InvalidParameters)?;
+
+    // And resolve the redirect_uri and response_mode
+    let redirect_uri = client
+        .resolve_redirect_uri(&params.auth.redirect_uri)?
+        .clone();
+    let response_mode = resolve_response_mode(
+        &params.auth.response_type,
+        params.auth.response_mode.clone(),
+    )?;
+
+    // Now we have a proper callback destination to go to on error
+    let callback_destination = CallbackDestination::try_new(
+        &response_mode,
+        redirect_uri.clone(),
+        params.auth.state.clone(),
+    )?;
+
+    Ok((params, redirect_uri, response_mode, callback_destination))
+}
+
 #[tracing::instrument(
     name = "handlers.oauth2.authorization.get",
-    fields(client.id = %params.auth.client_id),
+    fields(client.id),
     skip_all,
     err,
 )]
@@ -135,32 +268,90 @@ pub(crate) async fn get(
     State(templates): State<Templates>,
     State(key_store): State<Keystore>,
     State(url_builder): State<UrlBuilder>,
-    policy: Policy,
+    State(http_client_factory): State<HttpClientFactory>,
+    State(encrypter): State<Encrypter>,
+    mut policy: Policy,
     activity_tracker: BoundActivityTracker,
     mut repo: BoxRepository,
     cookie_jar: CookieJar,
-    Form(params): Form<Params>,
+    Form(mut parameters): Form<BTreeMap<String, String>>,
 ) -> Result<Response, RouteError> {
     // First, figure out what client it is
+    let client_id = parameters
+        .get("client_id")
+        .ok_or(RouteError::ClientNotFound)?;
+    tracing::Span::current().record("client.id", client_id.as_str());
+
     let client = repo
         .oauth2_client()
-        .find_by_client_id(&params.auth.client_id)
+        .find_by_client_id(client_id)
         .await?
         .ok_or(RouteError::ClientNotFound)?;
 
-    // And resolve the redirect_uri and response_mode
-    let redirect_uri = client
-        .resolve_redirect_uri(&params.auth.redirect_uri)?
-        .clone();
-    let response_type = params.auth.response_type;
-    let response_mode = resolve_response_mode(&response_type, params.auth.response_mode)?;
+    // If the client pushed the request beforehand, or sent it as a signed
+    // request object, the other parameters are ignored, and the ones from the
+    // request are used instead
+    if let Some(request_uri) = parameters.get("request_uri") {
+        parameters = load_pushed_request(&clock, &mut repo, &client, request_uri).await?;
+        parameters.insert("client_id".to_owned(), client.client_id.clone());
+    } else if client.require_pushed_authorization_requests {
+        return Err(RouteError::PushedAuthorizationRequired);
+    } else {
+        let signed_request_object = if let Some(request) = parameters.get("request") {
+            parameters = self::request_object::verify(
+                &clock,
+                &http_client_factory,
+                &encrypter,
+                &url_builder,
+                &client,
+                request,
+            )
+            .await?;
+            parameters.insert("client_id".to_owned(), client.client_id.clone());
+            true
+        } else {
+            false
+        };
 
-    // Now we have a proper callback destination to go to on error
-    let callback_destination = CallbackDestination::try_new(
-        &response_mode,
-        redirect_uri.clone(),
-        params.auth.state.clone(),
-    )?;
+        // Let the policy decide whether the client may send this request, for
+        // example if it has to be signed. Pushed requests were already checked
+        // when they were pushed
+        let res = policy
+            .evaluate_authorization_request(&client, signed_request_object)
+            .await?;
+        if !res.valid() {
+            let audit = AuditContext::anonymous().with_ip_address(activity_tracker.ip());
+            repo.audit_event()
+                .add(
+                    &mut rng,
+                    &clock,
+                    &audit,
+                    AuditEventKind::PolicyDenied,
+                    None,
+                    serde_json::json!({
+                        "operation": "authorization_request",
+                        "client_id": client.id,
+                        "violations": res.violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+                    }),
+                )
+                .await?;
+            repo.save().await?;
+
+            return Err(RouteError::DeniedByPolicy(res.violations));
+        }
+    }
+
+    // A pushed request was consumed above, so the repository must be saved even
+    // if the request turns out to be invalid, else it could be used again
+    let (params, redirect_uri, response_mode, callback_destination) =
+        match validate_request(&client, parameters) {
+            Ok(validated) => validated,
+            Err(err) => {
+                repo.save().await?;
+                return Err(err);
+            }
+        };
+    let response_type = params.auth.response_type;
 
     // Get the session info from the cookie
     let (session_info, cookie_jar) = cookie_jar.session_info();
@@ -174,29 +365,10 @@ pub(crate) async fn get(
             let maybe_session = session_info.load_session(&mut repo).await?;
             let prompt = params.auth.prompt.as_deref().unwrap_or_default();
 
-            // Check if the request/request_uri/registration params are used. If so, reply
-            // with the right error since we don't support them.
-            if params.auth.request.is_some() {
-                return Ok(callback_destination
-                    .go(
-                        &templates,
-                        ClientError::from(ClientErrorCode::RequestNotSupported),
-                    )
-                    .await?);
-            }
-
-            if params.auth.request_uri.is_some() {
-                return Ok(callback_destination
-                    .go(
-                        &templates,
-                        ClientError::from(ClientErrorCode::RequestUriNotSupported),
-                    )
-                    .await?);
-            }
-
             // Check if the client asked for a `token` response type, and bail out if it's
             // the case, since we don't support them
             if response_type.has_token() {
+                repo.save().await?;
                 return Ok(callback_destination
                     .go(
                         &templates,
@@ -208,6 +380,7 @@ pub(crate) async fn get(
             // If the client asked for a `id_token` response type, we must check if it can
             // use the `implicit` grant type
             if response_type.has_id_token() && !client.grant_types.contains(&GrantType::Implicit) {
+                repo.save().await?;
                 return Ok(callback_destination
                     .go(
                         &templates,
@@ -216,7 +389,10 @@ pub(crate) async fn get(
                     .await?);
             }
 
+            // Check if the registration param is used. If so, reply with the right error
+            // since we don't support it
             if params.auth.registration.is_some() {
+                repo.save().await?;
                 return Ok(callback_destination
                     .go(
                         &templates,
@@ -227,6 +403,7 @@ pub(crate) async fn get(
 
             // Fail early if prompt=none and there is no active session
             if prompt.contains(&Prompt::None) && maybe_session.is_none() {
+                repo.save().await?;
                 return Ok(callback_destination
                     .go(
                         &templates,
@@ -238,6 +415,7 @@ pub(crate) async fn get(
             let code: Option<AuthorizationCode> = if response_type.has_code() {
                 // Check if it is allowed to use this grant type
                 if !client.grant_types.contains(&GrantType::AuthorizationCode) {
+                    repo.save().await?;
                     return Ok(callback_destination
                         .go(
                             &templates,
@@ -263,6 +441,7 @@ pub(crate) async fn get(
                 // If the request had PKCE params but no code asked, it should get back with an
                 // error
                 if params.pkce.is_some() {
+                    repo.save().await?;
                     return Ok(callback_destination
                         .go(
                             &templates,
@@ -341,6 +520,7 @@ pub(crate) async fn get(
                         &activity_tracker,
                         repo,
                         key_store,
+                        &http_client_factory,
                         policy,
                         &url_builder,
                         grant,
@@ -391,6 +571,7 @@ pub(crate) async fn get(
                         &activity_tracker,
                         repo,
                         key_store,
+                        &http_client_factory,
                         policy,
                         &url_builder,
                         grant,
@@ -0,0 +1,487 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Verification of signed request objects, as defined by RFC 9101
+
+use std::collections::{BTreeMap, HashMap};
+
+use mas_axum_utils::{client_authorization::fetch_jwks, http_client_factory::HttpClientFactory};
+use mas_data_model::Client;
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_jose::{
+    claims::{self, ClaimError, TimeOptions},
+    jwt::{Jwt, JwtDecodeError},
+};
+use mas_keystore::Encrypter;
+use mas_router::UrlBuilder;
+use mas_storage::Clock;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RequestObjectError {
+    #[error("request object is not a valid JWT")]
+    InvalidJwt(#[from] JwtDecodeError),
+
+    #[error("request object must be signed")]
+    Unsigned,
+
+    #[error("request object must be signed with {expected}, got {got}")]
+    AlgorithmMismatch {
+        expected: JsonWebSignatureAlg,
+        got: JsonWebSignatureAlg,
+    },
+
+    #[error("client has no key to verify the request object")]
+    NoKey,
+
+    #[error("failed to decrypt client secret")]
+    Decryption,
+
+    #[error("failed to fetch the client JWKS")]
+    JwksFetchFailed,
+
+    #[error("invalid request object signature")]
+    InvalidSignature,
+
+    #[error("invalid request object claims")]
+    InvalidClaims(#[from] ClaimError),
+
+    #[error("request object was issued for another client")]
+    ClientIdMismatch,
+
+    #[error("request object must not contain the request or request_uri parameters")]
+    NestedRequest,
+}
+
+/// Verify a request object sent by the given client, and return the
+/// authorization request parameters it contains
+///
+/// The signature is checked against the client JWKS, or the client secret for
+/// HMAC-based algorithms. As required by RFC 9101 §10.8 and FAPI, the `iss`
+/// claim must be the client ID, the `aud` claim must contain our issuer, and
+/// the `exp` claim must be present. If present, the `client_id` claim must
+/// match the client, and the `nbf` claim must be valid.
+pub(crate) async fn verify(
+    clock: &impl Clock,
+    http_client_factory: &HttpClientFactory,
+    encrypter: &Encrypter,
+    url_builder: &UrlBuilder,
+    client: &Client,
+    request: &str,
+) -> Result<BTreeMap<String, String>, RequestObjectError> {
+    let jwt: Jwt<'_, HashMap<String, Value>> = Jwt::try_from(request)?;
+
+    let alg = jwt.header().alg();
+    if *alg == JsonWebSignatureAlg::None {
+        return Err(RequestObjectError::Unsigned);
+    }
+
+    if let Some(expected) = &client.request_object_signing_alg {
+        if alg != expected {
+            return Err(RequestObjectError::AlgorithmMismatch {
+                expected: expected.clone(),
+                got: alg.clone(),
+            });
+        }
+    }
+
+    match alg {
+        JsonWebSignatureAlg::Hs256 | JsonWebSignatureAlg::Hs384 | JsonWebSignatureAlg::Hs512 => {
+            let encrypted_client_secret = client
+                .encrypted_client_secret
+                .as_ref()
+                .ok_or(RequestObjectError::NoKey)?;
+
+            let client_secret = encrypter
+                .decrypt_string(encrypted_client_secret)
+                .map_err(|_| RequestObjectError::Decryption)?;
+
+            jwt.verify_with_shared_secret(client_secret)
+                .map_err(|_| RequestObjectError::InvalidSignature)?;
+        }
+
+        _ => {
+            let jwks = client.jwks.as_ref().ok_or(RequestObjectError::NoKey)?;
+
+            let jwks = fetch_jwks(http_client_factory, jwks)
+                .await
+                .map_err(|_| RequestObjectError::JwksFetchFailed)?;
+
+            jwt.verify_with_jwks(&jwks)
+                .map_err(|_| RequestObjectError::InvalidSignature)?;
+        }
+    }
+
+    let mut claims = jwt.payload().clone();
+    let time_options = TimeOptions::new(clock.now());
+
+    claims::ISS.extract_required_with_options(&mut claims, client.client_id.as_str())?;
+    claims::AUD
+        .extract_required_with_options(&mut claims, &url_builder.oidc_issuer().to_string())?;
+    claims::EXP.extract_required_with_options(&mut claims, &time_options)?;
+    claims::NBF.extract_optional_with_options(&mut claims, &time_options)?;
+    claims::IAT.extract_optional_with_options(&mut claims, time_options)?;
+    claims::JTI.extract_optional(&mut claims)?;
+
+    if claims.contains_key("request") || claims.contains_key("request_uri") {
+        return Err(RequestObjectError::NestedRequest);
+    }
+
+    // The remaining claims are the authorization request parameters. Non-string
+    // values, like `max_age` or `claims`, are passed in their JSON form
+    let parameters: BTreeMap<String, String> = claims
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::String(value) => (key, value),
+            value => (key, value.to_string()),
+        })
+        .collect();
+
+    if parameters
+        .get("client_id")
+        .is_some_and(|client_id| *client_id != client.client_id)
+    {
+        return Err(RequestObjectError::ClientIdMismatch);
+    }
+
+    Ok(parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_iana::jose::JsonWebSignatureAlg;
+    use mas_jose::{
+        jwa::SymmetricKey,
+        jwt::{JsonWebSignatureHeader, Jwt},
+    };
+    use mas_router::SimpleRoute;
+    use mas_storage::Clock;
+    use oauth2_types::{
+        errors::{ClientError, ClientErrorCode},
+        registration::ClientRegistrationResponse,
+        requests::PushedAuthorizationResponse,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    fn authorize(parameters: &[(&str, &str)]) -> Request<String> {
+        let query = serde_urlencoded::to_string(parameters).unwrap();
+        Request::get(format!(
+            "{}?{query}",
+            mas_router::OAuth2AuthorizationEndpoint::PATH
+        ))
+        .empty()
+    }
+
+    fn request_claims(state: &TestState, client_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "iss": client_id,
+            "aud": state.url_builder.oidc_issuer().as_str(),
+            "exp": (state.clock.now() + chrono::Duration::try_minutes(5).unwrap()).timestamp(),
+            "client_id": client_id,
+            "response_type": "code",
+            "redirect_uri": "https://example.com/callback",
+            "scope": "openid",
+            "state": "abcd",
+        })
+    }
+
+    fn sign_with_key_store(state: &TestState, claims: serde_json::Value) -> String {
+        let key = state
+            .key_store
+            .signing_key_for_algorithm(&JsonWebSignatureAlg::Rs256)
+            .unwrap();
+        let signer = key
+            .params()
+            .signing_key_for_alg(&JsonWebSignatureAlg::Rs256)
+            .unwrap();
+        let header = JsonWebSignatureHeader::new(JsonWebSignatureAlg::Rs256);
+        Jwt::sign_with_rng(&mut state.rng(), header, claims, &signer)
+            .unwrap()
+            .into_string()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_signed_request_object(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Register a client which signs its request objects with one of our keys
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "private_key_jwt",
+                "jwks": state.key_store.public_jwks(),
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "request_object_signing_alg": "RS256",
+                "require_signed_request_object": true,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        // Plain authorization requests are rejected
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("response_type", "code"),
+                ("redirect_uri", "https://example.com/callback"),
+                ("scope", "openid"),
+            ]))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // A valid request object sends the user to the login page
+        let request_object = sign_with_key_store(&state, request_claims(&state, &client_id));
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("request", &request_object),
+            ]))
+            .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let location = response.headers().get(LOCATION).unwrap().to_str().unwrap();
+        assert!(location.starts_with("/login"));
+
+        // Tampering with the signature is detected
+        let mut tampered = request_object.clone();
+        let last = if tampered.ends_with('A') { "B" } else { "A" };
+        tampered.replace_range(tampered.len() - 1.., last);
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("request", &tampered),
+            ]))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // The request object must be meant for us
+        let mut claims = request_claims(&state, &client_id);
+        claims["aud"] = "https://example.com/".into();
+        let request_object = sign_with_key_store(&state, claims);
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("request", &request_object),
+            ]))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // The request object must be issued by the client
+        let mut claims = request_claims(&state, &client_id);
+        claims["iss"] = "another-client".into();
+        let request_object = sign_with_key_store(&state, claims);
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("request", &request_object),
+            ]))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // The iss, aud and exp claims are required
+        for claim in ["iss", "aud", "exp"] {
+            let mut claims = request_claims(&state, &client_id);
+            claims.as_object_mut().unwrap().remove(claim);
+            let request_object = sign_with_key_store(&state, claims);
+            let response = state
+                .request(authorize(&[
+                    ("client_id", &client_id),
+                    ("request", &request_object),
+                ]))
+                .await;
+            response.assert_status(StatusCode::BAD_REQUEST);
+        }
+
+        // Expired request objects are rejected
+        let mut claims = request_claims(&state, &client_id);
+        claims["exp"] = (state.clock.now() - chrono::Duration::try_hours(1).unwrap())
+            .timestamp()
+            .into();
+        let request_object = sign_with_key_store(&state, claims);
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("request", &request_object),
+            ]))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Unsigned request objects are rejected
+        let request_object = format!(
+            "eyJhbGciOiJub25lIn0.{}.",
+            request_object.split('.').nth(1).unwrap()
+        );
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("request", &request_object),
+            ]))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_policy_requires_signed_request_object(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Register a client which doesn't ask for signed request objects itself
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "private_key_jwt",
+                "jwks": state.key_store.public_jwks(),
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "require_signed_request_object": false,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let plain_request = [
+            ("client_id", client_id.as_str()),
+            ("response_type", "code"),
+            ("redirect_uri", "https://example.com/callback"),
+            ("scope", "openid"),
+        ];
+
+        // Only other clients must sign their requests
+        let state = {
+            let mut state = state;
+            state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
+                "require_signed_request_object": ["01HWQCPA5KF10FNCETY9402WGF"],
+            }))
+            .await
+            .unwrap();
+            state
+        };
+        let response = state.request(authorize(&plain_request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        // The operator requires signed request objects from every client
+        let state = {
+            let mut state = state;
+            state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
+                "require_signed_request_object": true,
+            }))
+            .await
+            .unwrap();
+            state
+        };
+        let response = state.request(authorize(&plain_request)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(response
+            .body()
+            .contains("client must use signed request objects"));
+
+        // Signed requests are still accepted
+        let request_object = sign_with_key_store(&state, request_claims(&state, &client_id));
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("request", &request_object),
+            ]))
+            .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let location = response.headers().get(LOCATION).unwrap().to_str().unwrap();
+        assert!(location.starts_with("/login"));
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_pushed_request_object_shared_secret(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "client_secret_post",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "require_signed_request_object": true,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let ClientRegistrationResponse {
+            client_id,
+            client_secret,
+            ..
+        } = response.json();
+        let client_secret = client_secret.unwrap();
+
+        // Pushing plain parameters is rejected
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "response_type": "code",
+                "redirect_uri": "https://example.com/callback",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Push a request object signed with the client secret
+        let key = SymmetricKey::new_for_alg(
+            client_secret.as_bytes().to_vec(),
+            &JsonWebSignatureAlg::Hs256,
+        )
+        .unwrap();
+        let header = JsonWebSignatureHeader::new(JsonWebSignatureAlg::Hs256);
+        let request_object =
+            Jwt::sign_with_rng(&mut rng, header, request_claims(&state, &client_id), &key)
+                .unwrap()
+                .into_string();
+
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "request": request_object,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let PushedAuthorizationResponse { request_uri, .. } = response.json();
+
+        let response = state
+            .request(authorize(&[
+                ("client_id", &client_id),
+                ("request_uri", &request_uri),
+            ]))
+            .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        // A request object signed with another secret is rejected
+        let key =
+            SymmetricKey::new_for_alg(b"not the secret".to_vec(), &JsonWebSignatureAlg::Hs256)
+                .unwrap();
+        let header = JsonWebSignatureHeader::new(JsonWebSignatureAlg::Hs256);
+        let request_object =
+            Jwt::sign_with_rng(&mut rng, header, request_claims(&state, &client_id), &key)
+                .unwrap()
+                .into_string();
+
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "request": request_object,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let error: ClientError = response.json();
+        assert_eq!(error.error, ClientErrorCode::InvalidRequestObject);
+    }
+}
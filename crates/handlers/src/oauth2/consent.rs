@@ -15,10 +15,11 @@ use mas_axum_utils::{
     sentry::SentryEventID,
     SessionInfoExt,
 };
-use mas_data_model::{AuthorizationGrantStage, Device};
+use mas_data_model::{AuditContext, AuditEventKind, AuthorizationGrantStage, Device};
 use mas_policy::Policy;
 use mas_router::{PostAuthAction, UrlBuilder};
 use mas_storage::{
+    audit::AuditEventRepository,
     oauth2::{OAuth2AuthorizationGrantRepository, OAuth2ClientRepository},
     BoxClock, BoxRepository, BoxRng,
 };
@@ -189,6 +190,24 @@ pub(crate) async fn post(
         .await?;
 
     if !res.valid() {
+        let audit = AuditContext::user(&session.user).with_ip_address(activity_tracker.ip());
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::PolicyDenied,
+                Some(&session.user),
+                serde_json::json!({
+                    "operation": "authorization_grant",
+                    "client_id": client.id,
+                    "grant_id": grant.id,
+                    "violations": res.violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+                }),
+            )
+            .await?;
+        repo.save().await?;
+
         return Err(RouteError::PolicyViolation);
     }
 
@@ -16,9 +16,10 @@ use mas_axum_utils::{
     csrf::{CsrfExt, ProtectedForm},
     FancyError, SessionInfoExt,
 };
+use mas_data_model::{AuditContext, AuditEventKind};
 use mas_policy::Policy;
 use mas_router::UrlBuilder;
-use mas_storage::{BoxClock, BoxRepository, BoxRng};
+use mas_storage::{audit::AuditEventRepository, BoxClock, BoxRepository, BoxRng};
 use mas_templates::{DeviceConsentContext, PolicyViolationContext, TemplateContext, Templates};
 use serde::Deserialize;
 use tracing::warn;
@@ -163,6 +164,24 @@ pub(crate) async fn post(
     if !res.valid() {
         warn!(violation = ?res, "Device code grant for client {} denied by policy", client.id);
 
+        let audit = AuditContext::user(&session.user).with_ip_address(activity_tracker.ip());
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::PolicyDenied,
+                Some(&session.user),
+                serde_json::json!({
+                    "operation": "device_code_grant",
+                    "client_id": client.id,
+                    "grant_id": grant.id,
+                    "violations": res.violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+                }),
+            )
+            .await?;
+        repo.save().await?;
+
         let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
         let ctx = PolicyViolationContext::for_device_code_grant(grant, client)
             .with_session(session)
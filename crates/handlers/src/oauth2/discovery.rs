@@ -5,11 +5,16 @@
 // Please see LICENSE in the repository root for full details.
 
 use axum::{extract::State, response::IntoResponse, Json};
+use mas_axum_utils::dpop::SUPPORTED_ALGORITHMS as SUPPORTED_DPOP_ALGORITHMS;
+use mas_data_model::ACR_MULTI_FACTOR;
 use mas_iana::oauth::{
     OAuthAuthorizationEndpointResponseType, OAuthClientAuthenticationMethod,
     PkceCodeChallengeMethod,
 };
-use mas_jose::jwa::SUPPORTED_SIGNING_ALGORITHMS;
+use mas_jose::jwa::{
+    SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS, SUPPORTED_ENCRYPTION_ALGORITHMS,
+    SUPPORTED_SIGNING_ALGORITHMS,
+};
 use mas_keystore::Keystore;
 use mas_router::UrlBuilder;
 use oauth2_types::{
@@ -61,11 +66,14 @@ pub(crate) async fn get(
     let authorization_endpoint = Some(url_builder.oauth_authorization_endpoint());
     let token_endpoint = Some(url_builder.oauth_token_endpoint());
     let device_authorization_endpoint = Some(url_builder.oauth_device_authorization_endpoint());
+    let pushed_authorization_request_endpoint =
+        Some(url_builder.oauth_pushed_authorization_request_endpoint());
     let jwks_uri = Some(url_builder.jwks_uri());
     let introspection_endpoint = Some(url_builder.oauth_introspection_endpoint());
     let revocation_endpoint = Some(url_builder.oauth_revocation_endpoint());
     let userinfo_endpoint = Some(url_builder.oidc_userinfo_endpoint());
     let registration_endpoint = Some(url_builder.oauth_registration_endpoint());
+    let end_session_endpoint = Some(url_builder.oidc_end_session_endpoint());
 
     let scopes_supported = Some(vec![scope::OPENID.to_string(), scope::EMAIL.to_string()]);
 
@@ -86,6 +94,8 @@ pub(crate) async fn get(
         GrantType::RefreshToken,
         GrantType::ClientCredentials,
         GrantType::DeviceCode,
+        GrantType::TokenExchange,
+        GrantType::JwtBearer,
     ]);
 
     let token_endpoint_auth_methods_supported = client_auth_methods_supported.clone();
@@ -99,7 +109,13 @@ pub(crate) async fn get(
     let introspection_endpoint_auth_methods_supported =
         client_auth_methods_supported.map(|v| v.into_iter().map(Into::into).collect());
     let introspection_endpoint_auth_signing_alg_values_supported =
-        client_auth_signing_alg_values_supported;
+        client_auth_signing_alg_values_supported.clone();
+
+    // Request objects are verified with the same keys as client assertions
+    let request_object_signing_alg_values_supported = client_auth_signing_alg_values_supported;
+
+    // DPoP proofs can be signed with any asymmetric algorithm we support
+    let dpop_signing_alg_values_supported = Some(SUPPORTED_DPOP_ALGORITHMS.to_vec());
 
     let code_challenge_methods_supported = Some(vec![
         PkceCodeChallengeMethod::Plain,
@@ -111,6 +127,16 @@ pub(crate) async fn get(
     let id_token_signing_alg_values_supported = jwt_signing_alg_values_supported.clone();
     let userinfo_signing_alg_values_supported = jwt_signing_alg_values_supported;
 
+    // ID tokens and userinfo responses are encrypted using the client's JWKS
+    let jwt_encryption_alg_values_supported = Some(SUPPORTED_ENCRYPTION_ALGORITHMS.to_vec());
+    let jwt_encryption_enc_values_supported =
+        Some(SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS.to_vec());
+
+    let id_token_encryption_alg_values_supported = jwt_encryption_alg_values_supported.clone();
+    let id_token_encryption_enc_values_supported = jwt_encryption_enc_values_supported.clone();
+    let userinfo_encryption_alg_values_supported = jwt_encryption_alg_values_supported;
+    let userinfo_encryption_enc_values_supported = jwt_encryption_enc_values_supported;
+
     let display_values_supported = Some(vec![Display::Page]);
 
     let claim_types_supported = Some(vec![ClaimType::Normal]);
@@ -125,11 +151,21 @@ pub(crate) async fn get(
         "auth_time".to_owned(),
         "at_hash".to_owned(),
         "c_hash".to_owned(),
+        "sid".to_owned(),
+        "amr".to_owned(),
+        "acr".to_owned(),
     ]);
 
+    let acr_values_supported = Some(vec![ACR_MULTI_FACTOR.to_owned()]);
+
     let claims_parameter_supported = Some(false);
-    let request_parameter_supported = Some(false);
-    let request_uri_parameter_supported = Some(false);
+    let request_parameter_supported = Some(true);
+    // The only request URIs supported are the ones given back by the Pushed
+    // Authorization Request endpoint
+    let request_uri_parameter_supported = Some(true);
+
+    let backchannel_logout_supported = Some(true);
+    let backchannel_logout_session_supported = Some(true);
 
     let prompt_values_supported = Some({
         let mut v = vec![Prompt::None, Prompt::Login];
@@ -165,14 +201,25 @@ pub(crate) async fn get(
         subject_types_supported,
         id_token_signing_alg_values_supported,
         userinfo_signing_alg_values_supported,
+        id_token_encryption_alg_values_supported,
+        id_token_encryption_enc_values_supported,
+        userinfo_encryption_alg_values_supported,
+        userinfo_encryption_enc_values_supported,
         display_values_supported,
         claim_types_supported,
         claims_supported,
+        acr_values_supported,
         claims_parameter_supported,
+        request_object_signing_alg_values_supported,
         request_parameter_supported,
         request_uri_parameter_supported,
         prompt_values_supported,
         device_authorization_endpoint,
+        pushed_authorization_request_endpoint,
+        end_session_endpoint,
+        dpop_signing_alg_values_supported,
+        backchannel_logout_supported,
+        backchannel_logout_session_supported,
         ..ProviderMetadata::default()
     };
 
@@ -195,6 +242,7 @@ pub(crate) async fn get(
 #[cfg(test)]
 mod tests {
     use hyper::{Request, StatusCode};
+    use mas_iana::jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg};
     use oauth2_types::oidc::ProviderMetadata;
     use sqlx::PgPool;
 
@@ -210,8 +258,44 @@ mod tests {
         response.assert_status(StatusCode::OK);
 
         let metadata: ProviderMetadata = response.json();
-        metadata
+        let metadata = metadata
             .validate(state.url_builder.oidc_issuer().as_str())
             .expect("Invalid metadata");
+
+        assert_eq!(
+            metadata.end_session_endpoint.as_ref(),
+            Some(&state.url_builder.oidc_end_session_endpoint())
+        );
+        assert_eq!(metadata.backchannel_logout_supported, Some(true));
+        assert_eq!(metadata.backchannel_logout_session_supported, Some(true));
+        assert_eq!(
+            metadata.pushed_authorization_request_endpoint.as_ref(),
+            Some(
+                &state
+                    .url_builder
+                    .oauth_pushed_authorization_request_endpoint()
+            )
+        );
+        assert_eq!(metadata.request_uri_parameter_supported, Some(true));
+        assert_eq!(metadata.request_parameter_supported, Some(true));
+        assert!(metadata
+            .request_object_signing_alg_values_supported
+            .as_ref()
+            .is_some_and(|algs| algs.contains(&JsonWebSignatureAlg::Rs256)));
+        assert!(metadata
+            .dpop_signing_alg_values_supported
+            .as_ref()
+            .is_some_and(|algs| algs.contains(&JsonWebSignatureAlg::Es256)
+                && algs.contains(&JsonWebSignatureAlg::EdDsa)
+                && !algs.contains(&JsonWebSignatureAlg::Hs256)));
+        assert!(metadata
+            .id_token_encryption_alg_values_supported
+            .as_ref()
+            .is_some_and(|algs| algs.contains(&JsonWebEncryptionAlg::RsaOaep)
+                && algs.contains(&JsonWebEncryptionAlg::EcdhEs)));
+        assert!(metadata
+            .userinfo_encryption_enc_values_supported
+            .as_ref()
+            .is_some_and(|encs| encs.contains(&JsonWebEncryptionEnc::A128CbcHs256)));
     }
 }
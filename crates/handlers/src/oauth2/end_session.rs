@@ -0,0 +1,542 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Handlers for the OpenID Connect RP-Initiated Logout endpoint
+//!
+//! See <https://openid.net/specs/openid-connect-rpinitiated-1_0.html>
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Form, Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::typed_header::TypedHeader;
+use hyper::StatusCode;
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    sentry::SentryEventID,
+    SessionInfoExt,
+};
+use mas_data_model::{AuditContext, AuditEventKind, Client};
+use mas_jose::{
+    claims::{self, OneOrMany},
+    jwt::Jwt,
+};
+use mas_keystore::Keystore;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{schedule_backchannel_logout_jobs, JobRepositoryExt, SyncDevicesJob},
+    oauth2::{OAuth2ClientRepository, OAuth2SessionFilter, OAuth2SessionRepository},
+    user::BrowserSessionRepository,
+    BoxClock, BoxRepository, BoxRng,
+};
+use mas_templates::{EndSessionContext, TemplateContext, Templates};
+use oauth2_types::oidc::RpInitiatedLogoutRequest;
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use crate::{impl_from_error_for_route, BoundActivityTracker, PreferredLanguage};
+
+#[derive(Debug, Error)]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error(transparent)]
+    Csrf(#[from] mas_axum_utils::csrf::CsrfError),
+
+    #[error("invalid id_token_hint")]
+    InvalidIdTokenHint,
+
+    #[error("client_id does not match the id_token_hint")]
+    ClientIdMismatch,
+
+    #[error("could not find client")]
+    ClientNotFound,
+
+    #[error("post_logout_redirect_uri requires either a client_id or an id_token_hint")]
+    MissingClient,
+
+    #[error("invalid post_logout_redirect_uri")]
+    InvalidPostLogoutRedirectUri(#[from] mas_data_model::InvalidPostLogoutRedirectUriError),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_templates::TemplateError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let event_id = sentry::capture_error(&self);
+        let response = match self {
+            RouteError::Internal(e) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+            e => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        };
+
+        (SentryEventID::from(event_id), response).into_response()
+    }
+}
+
+/// The body of a `POST` request to the end session endpoint
+///
+/// It is either the confirmation form rendered by this endpoint, or a logout
+/// request sent by the RP using the `POST` method.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum EndSessionForm {
+    Confirm(ProtectedForm<RpInitiatedLogoutRequest>),
+    Request(RpInitiatedLogoutRequest),
+}
+
+/// An RP-initiated logout request, once validated
+struct ValidatedRequest {
+    /// The client which initiated the logout, if it was identified
+    client: Option<Client>,
+
+    /// The subject of the `id_token_hint`, if one was given
+    subject: Option<String>,
+
+    /// Where to redirect the user after the logout, with the `state` parameter
+    /// already set
+    post_logout_redirect_uri: Option<Url>,
+}
+
+impl ValidatedRequest {
+    /// Where to send the user once the logout is done, or if there is no
+    /// session to end
+    fn destination(&self, url_builder: &UrlBuilder) -> Redirect {
+        if let Some(uri) = &self.post_logout_redirect_uri {
+            Redirect::to(uri.as_str())
+        } else {
+            url_builder.redirect(&mas_router::Login::default())
+        }
+    }
+}
+
+/// Validate the parameters of an RP-initiated logout request
+///
+/// The `id_token_hint` is not checked for expiration, as the spec suggests
+/// that expired ID Tokens should still be accepted as a hint.
+async fn validate_request(
+    repo: &mut BoxRepository,
+    key_store: &Keystore,
+    url_builder: &UrlBuilder,
+    params: &RpInitiatedLogoutRequest,
+) -> Result<ValidatedRequest, RouteError> {
+    let (client_id, subject) = if let Some(id_token_hint) = &params.id_token_hint {
+        let jwt: Jwt<'_, HashMap<String, serde_json::Value>> =
+            Jwt::try_from(id_token_hint.as_str()).map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        jwt.verify_with_jwks(&key_store.public_jwks())
+            .map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        let (_header, mut claims) = jwt.into_parts();
+
+        let issuer = url_builder.oidc_issuer().to_string();
+        claims::ISS
+            .extract_required_with_options(&mut claims, issuer.as_str())
+            .map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        let sub = claims::SUB
+            .extract_required(&mut claims)
+            .map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        let audience: OneOrMany<String> = claims
+            .remove("aud")
+            .and_then(|aud| serde_json::from_value(aud).ok())
+            .ok_or(RouteError::InvalidIdTokenHint)?;
+
+        // If the client_id is given, it must match the audience of the ID token,
+        // else we take it from the audience
+        let client_id = match (&params.client_id, &audience[..]) {
+            (Some(client_id), audience) if audience.contains(client_id) => client_id.clone(),
+            (Some(_), _) => return Err(RouteError::ClientIdMismatch),
+            (None, [aud]) => aud.clone(),
+            (None, _) => return Err(RouteError::InvalidIdTokenHint),
+        };
+
+        (Some(client_id), Some(sub))
+    } else {
+        (params.client_id.clone(), None)
+    };
+
+    let client = if let Some(client_id) = &client_id {
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(client_id)
+            .await?
+            .ok_or(RouteError::ClientNotFound)?;
+        Some(client)
+    } else {
+        None
+    };
+
+    let post_logout_redirect_uri = if let Some(uri) = &params.post_logout_redirect_uri {
+        let client = client.as_ref().ok_or(RouteError::MissingClient)?;
+        client.validate_post_logout_redirect_uri(uri)?;
+
+        let mut uri = uri.clone();
+        if let Some(state) = &params.state {
+            uri.query_pairs_mut().append_pair("state", state);
+        }
+        Some(uri)
+    } else {
+        None
+    };
+
+    Ok(ValidatedRequest {
+        client,
+        subject,
+        post_logout_redirect_uri,
+    })
+}
+
+#[tracing::instrument(name = "handlers.oauth2.end_session.get", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(key_store): State<Keystore>,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    Query(params): Query<RpInitiatedLogoutRequest>,
+) -> Result<Response, RouteError> {
+    let validated = validate_request(&mut repo, &key_store, &url_builder, &params).await?;
+
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    // If the ID token was issued to another user, there is no session to end here
+    let maybe_session = maybe_session.filter(|session| {
+        validated
+            .subject
+            .as_ref()
+            .map_or(true, |sub| *sub == session.user.sub)
+    });
+
+    let Some(session) = maybe_session else {
+        return Ok((cookie_jar, validated.destination(&url_builder)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let ctx = EndSessionContext::new(validated.client, params)
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_end_session(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.oauth2.end_session.post", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    locale: PreferredLanguage,
+    templates: State<Templates>,
+    State(key_store): State<Keystore>,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    Form(form): Form<EndSessionForm>,
+) -> Result<Response, RouteError> {
+    let form = match form {
+        EndSessionForm::Confirm(form) => form,
+        // The RP sent the logout request with a POST, treat it like a GET
+        EndSessionForm::Request(params) => {
+            return get(
+                rng,
+                clock,
+                locale,
+                templates,
+                State(key_store),
+                State(url_builder),
+                repo,
+                activity_tracker,
+                cookie_jar,
+                Query(params),
+            )
+            .await;
+        }
+    };
+
+    let params = cookie_jar.verify_form(&clock, form)?;
+
+    let validated = validate_request(&mut repo, &key_store, &url_builder, &params).await?;
+
+    let (session_info, mut cookie_jar) = cookie_jar.session_info();
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let maybe_session = maybe_session.filter(|session| {
+        validated
+            .subject
+            .as_ref()
+            .map_or(true, |sub| *sub == session.user.sub)
+    });
+
+    if let Some(session) = maybe_session {
+        activity_tracker
+            .record_browser_session(&clock, &session)
+            .await;
+
+        let filter = OAuth2SessionFilter::new()
+            .for_browser_session(&session)
+            .active_only();
+
+        // Notify the clients which registered a back-channel logout URI
+        schedule_backchannel_logout_jobs(&mut repo, filter).await?;
+
+        // End all the OAuth 2.0 sessions which were started from this browser
+        // session
+        let affected = repo.oauth2_session().finish_bulk(&clock, filter).await?;
+
+        let audit = AuditContext::user(&session.user)
+            .with_ip_address(activity_tracker.ip())
+            .with_user_agent(user_agent.map(|TypedHeader(ua)| ua.as_str().to_owned()));
+
+        // Schedule a job to sync the devices of the user with the homeserver
+        if affected > 0 {
+            repo.job()
+                .schedule_job(SyncDevicesJob::new(&session.user))
+                .await?;
+
+            repo.audit_event()
+                .add(
+                    &mut rng,
+                    &clock,
+                    &audit,
+                    AuditEventKind::SessionEnded,
+                    Some(&session.user),
+                    serde_json::json!({
+                        "session_type": "oauth2",
+                        "count": affected,
+                        "client_id": validated.client.as_ref().map(|client| client.id),
+                    }),
+                )
+                .await?;
+        }
+
+        let session = repo.browser_session().finish(&clock, session).await?;
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::SessionEnded,
+                Some(&session.user),
+                serde_json::json!({ "session_type": "browser", "session_id": session.id }),
+            )
+            .await?;
+
+        cookie_jar = cookie_jar.update_session_info(&session_info.mark_session_ended());
+    }
+
+    repo.save().await?;
+
+    Ok((cookie_jar, validated.destination(&url_builder)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_axum_utils::SessionInfoExt;
+    use mas_data_model::AuditEventKind;
+    use mas_router::SimpleRoute;
+    use mas_storage::{
+        audit::AuditEventFilter,
+        oauth2::{OAuth2ClientRepository, OAuth2SessionRepository},
+        user::{BrowserSessionRepository, UserRepository},
+        RepositoryAccess,
+    };
+    use oauth2_types::{
+        requests::GrantType,
+        scope::{Scope, OPENID},
+    };
+    use sqlx::PgPool;
+
+    use crate::{
+        oauth2::generate_id_token,
+        test_utils::{setup, CookieHelper, RequestBuilderExt, ResponseExt, TestState},
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_end_session(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+        let cookies = CookieHelper::new();
+
+        // Provision a user, a browser session, a client and an OAuth 2.0 session
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, "john".to_owned())
+            .await
+            .unwrap();
+        let browser_session = repo
+            .browser_session()
+            .add(&mut rng, &state.clock, &user, None)
+            .await
+            .unwrap();
+        let client = repo
+            .oauth2_client()
+            .add(
+                &mut rng,
+                &state.clock,
+                vec!["https://example.com/redirect".parse().unwrap()],
+                None,
+                None,
+                vec![GrantType::AuthorizationCode],
+                Some("Example".to_owned()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                vec!["https://example.com/logged-out".parse().unwrap()],
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut rng,
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let id_token = generate_id_token(
+            &mut rng,
+            &state.clock,
+            &state.url_builder,
+            &state.key_store,
+            &client,
+            None,
+            &browser_session,
+            None,
+            None,
+        )
+        .unwrap();
+
+        cookies.import(state.cookie_jar().set_session(&browser_session));
+
+        // An unregistered post_logout_redirect_uri is rejected
+        let request = Request::get(format!(
+            "{}?id_token_hint={id_token}&post_logout_redirect_uri=https%3A%2F%2Fexample.com%2Fother",
+            mas_router::OidcEndSession::PATH
+        ))
+        .empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // A valid request shows the confirmation page
+        let request = Request::get(format!(
+            "{}?id_token_hint={id_token}&post_logout_redirect_uri=https%3A%2F%2Fexample.com%2Flogged-out&state=abcd",
+            mas_router::OidcEndSession::PATH
+        ))
+        .empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("Example"));
+
+        let csrf_token = response
+            .body()
+            .split("name=\"csrf\" value=\"")
+            .nth(1)
+            .unwrap()
+            .split('\"')
+            .next()
+            .unwrap();
+
+        // Confirm the logout
+        let request = Request::post(mas_router::OidcEndSession::PATH).form(serde_json::json!({
+            "csrf": csrf_token,
+            "id_token_hint": id_token,
+            "post_logout_redirect_uri": "https://example.com/logged-out",
+            "state": "abcd",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "https://example.com/logged-out?state=abcd");
+
+        // Both the browser session and the OAuth 2.0 session should be finished
+        let mut repo = state.repository().await.unwrap();
+        let browser_session = repo
+            .browser_session()
+            .lookup(browser_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!browser_session.active());
+        let session = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_finished());
+
+        // Ending both sessions was recorded in the audit log
+        let filter = AuditEventFilter::new()
+            .for_user(&user)
+            .with_kind(AuditEventKind::SessionEnded);
+        assert_eq!(repo.audit_event().count(filter).await.unwrap(), 2);
+        repo.cancel().await.unwrap();
+
+        // Now that the user is logged out, the endpoint redirects straight away
+        let request = Request::get(format!(
+            "{}?client_id={}&post_logout_redirect_uri=https%3A%2F%2Fexample.com%2Flogged-out",
+            mas_router::OidcEndSession::PATH,
+            client.client_id,
+        ))
+        .empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "https://example.com/logged-out");
+    }
+}
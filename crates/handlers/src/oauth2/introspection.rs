@@ -22,7 +22,7 @@ use mas_storage::{
 };
 use oauth2_types::{
     errors::{ClientError, ClientErrorCode},
-    requests::{IntrospectionRequest, IntrospectionResponse},
+    requests::{IntrospectionRequest, IntrospectionResponse, TokenActor, TokenConfirmation},
     scope::ScopeToken,
 };
 use thiserror::Error;
@@ -153,6 +153,8 @@ const INACTIVE: IntrospectionResponse = IntrospectionResponse {
     aud: None,
     iss: None,
     jti: None,
+    cnf: None,
+    act: None,
 };
 
 const API_SCOPE: ScopeToken = ScopeToken::from_static("urn:matrix:org.matrix.msc2967.client:api:*");
@@ -246,6 +248,18 @@ pub(crate) async fn post(
                 (None, None)
             };
 
+            // Tokens obtained through a token exchange are meant for the session's
+            // client, on behalf of the client which asked for them
+            let (aud, act) = match session.acting_client_id {
+                Some(acting_client_id) => (
+                    Some(session.client_id.to_string()),
+                    Some(TokenActor {
+                        client_id: Some(acting_client_id.to_string()),
+                    }),
+                ),
+                None => (None, None),
+            };
+
             activity_tracker
                 .record_oauth2_session(&clock, &session, ip)
                 .await;
@@ -260,9 +274,13 @@ pub(crate) async fn post(
                 iat: Some(access_token.created_at),
                 nbf: Some(access_token.created_at),
                 sub,
-                aud: None,
+                aud,
                 iss: None,
                 jti: Some(access_token.jti()),
+                cnf: access_token
+                    .dpop_jkt
+                    .map(|jkt| TokenConfirmation { jkt: Some(jkt) }),
+                act,
             }
         }
 
@@ -305,6 +323,18 @@ pub(crate) async fn post(
                 (None, None)
             };
 
+            // Tokens obtained through a token exchange are meant for the session's
+            // client, on behalf of the client which asked for them
+            let (aud, act) = match session.acting_client_id {
+                Some(acting_client_id) => (
+                    Some(session.client_id.to_string()),
+                    Some(TokenActor {
+                        client_id: Some(acting_client_id.to_string()),
+                    }),
+                ),
+                None => (None, None),
+            };
+
             activity_tracker
                 .record_oauth2_session(&clock, &session, ip)
                 .await;
@@ -319,9 +349,13 @@ pub(crate) async fn post(
                 iat: Some(refresh_token.created_at),
                 nbf: Some(refresh_token.created_at),
                 sub,
-                aud: None,
+                aud,
                 iss: None,
                 jti: Some(refresh_token.jti()),
+                cnf: refresh_token
+                    .dpop_jkt
+                    .map(|jkt| TokenConfirmation { jkt: Some(jkt) }),
+                act,
             }
         }
 
@@ -381,6 +415,8 @@ pub(crate) async fn post(
                 aud: None,
                 iss: None,
                 jti: None,
+                cnf: None,
+                act: None,
             }
         }
 
@@ -440,6 +476,8 @@ pub(crate) async fn post(
                 aud: None,
                 iss: None,
                 jti: None,
+                cnf: None,
+                act: None,
             }
         }
     };
@@ -548,6 +586,7 @@ mod tests {
                 &mut repo,
                 &session,
                 Duration::microseconds(5 * 60 * 1000 * 1000),
+                None,
             )
             .await
             .unwrap();
@@ -7,14 +7,17 @@
 use std::collections::HashMap;
 
 use chrono::Duration;
+use mas_axum_utils::{client_authorization::fetch_jwks, http_client_factory::HttpClientFactory};
 use mas_data_model::{
     AccessToken, Authentication, AuthorizationGrant, BrowserSession, Client, RefreshToken, Session,
-    TokenType,
+    TokenType, ACR_MULTI_FACTOR,
 };
-use mas_iana::jose::JsonWebSignatureAlg;
+use mas_iana::jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg};
 use mas_jose::{
     claims::{self, hash_token},
     constraints::Constrainable,
+    jwa::AsymmetricEncryptionKey,
+    jwe::{JsonWebEncryptionHeader, Jwe, JweEncryptionError},
     jwt::{JsonWebSignatureHeader, Jwt},
 };
 use mas_keystore::Keystore;
@@ -26,8 +29,10 @@ pub mod authorization;
 pub mod consent;
 pub mod device;
 pub mod discovery;
+pub mod end_session;
 pub mod introspection;
 pub mod keys;
+pub mod pushed_authorization_request;
 pub mod registration;
 pub mod revoke;
 pub mod token;
@@ -45,6 +50,119 @@ pub(crate) enum IdTokenSignatureError {
     TokenHash(#[from] mas_jose::claims::TokenHashError),
 }
 
+#[derive(Debug, Error)]
+pub(crate) enum ResponseEncryptionError {
+    #[error("The client has no JWKS to encrypt the response with")]
+    NoJwks,
+
+    #[error("Failed to fetch the client JWKS")]
+    JwksFetch(#[source] axum::BoxError),
+
+    #[error("The client has no key suitable for {0}")]
+    NoEncryptionKey(JsonWebEncryptionAlg),
+
+    #[error(transparent)]
+    Encryption(#[from] JweEncryptionError),
+}
+
+/// A key of a client to encrypt responses with, resolved from its JWKS
+pub(crate) struct ClientEncryptionKey {
+    alg: JsonWebEncryptionAlg,
+    enc: JsonWebEncryptionEnc,
+    kid: Option<String>,
+    key: AsymmetricEncryptionKey,
+}
+
+impl ClientEncryptionKey {
+    /// Find a key suitable for the given algorithm in the client JWKS
+    ///
+    /// This may fetch the client JWKS, so it should be called before the
+    /// repository transaction does any write.
+    pub(crate) async fn resolve(
+        http_client_factory: &HttpClientFactory,
+        client: &Client,
+        alg: &JsonWebEncryptionAlg,
+        enc: Option<&JsonWebEncryptionEnc>,
+    ) -> Result<Self, ResponseEncryptionError> {
+        let jwks = client
+            .jwks
+            .as_ref()
+            .ok_or(ResponseEncryptionError::NoJwks)?;
+        let jwks = fetch_jwks(http_client_factory, jwks)
+            .await
+            .map_err(ResponseEncryptionError::JwksFetch)?;
+
+        let (jwk, key) = jwks
+            .encryption_key_for_algorithm(alg)
+            .ok_or_else(|| ResponseEncryptionError::NoEncryptionKey(alg.clone()))?;
+
+        Ok(Self {
+            alg: alg.clone(),
+            enc: enc.cloned().unwrap_or(JsonWebEncryptionEnc::A128CbcHs256),
+            kid: jwk.kid().map(ToOwned::to_owned),
+            key,
+        })
+    }
+
+    /// Encrypt a response payload as a JWE
+    pub(crate) fn encrypt(
+        &self,
+        rng: &mut (impl rand::RngCore + rand::CryptoRng),
+        content_type: Option<&str>,
+        payload: &[u8],
+    ) -> Result<Jwe, ResponseEncryptionError> {
+        let mut header = JsonWebEncryptionHeader::new(self.alg.clone(), self.enc.clone());
+        if let Some(content_type) = content_type {
+            header = header.with_cty(content_type.to_owned());
+        }
+
+        if let Some(kid) = &self.kid {
+            header = header.with_kid(kid.clone());
+        }
+
+        Ok(Jwe::encrypt_with_rng(rng, header, payload, &self.key)?)
+    }
+}
+
+/// Resolve the key to encrypt ID tokens with, if the client asked for
+/// encrypted ID tokens
+///
+/// This may fetch the client JWKS, so it should be called before the
+/// repository transaction does any write.
+pub(crate) async fn id_token_encryption_key(
+    http_client_factory: &HttpClientFactory,
+    client: &Client,
+) -> Result<Option<ClientEncryptionKey>, ResponseEncryptionError> {
+    let Some(alg) = &client.id_token_encrypted_response_alg else {
+        return Ok(None);
+    };
+
+    let key = ClientEncryptionKey::resolve(
+        http_client_factory,
+        client,
+        alg,
+        client.id_token_encrypted_response_enc.as_ref(),
+    )
+    .await?;
+
+    Ok(Some(key))
+}
+
+/// Encrypt a signed ID token if the client asked for encrypted ID tokens, by
+/// nesting it in a JWE
+pub(crate) fn encrypt_id_token(
+    rng: &mut (impl rand::RngCore + rand::CryptoRng),
+    key: Option<&ClientEncryptionKey>,
+    id_token: String,
+) -> Result<String, ResponseEncryptionError> {
+    let Some(key) = key else {
+        return Ok(id_token);
+    };
+
+    let id_token = key.encrypt(rng, Some("JWT"), id_token.as_bytes())?;
+    Ok(id_token.into_string())
+}
+
 pub(crate) fn generate_id_token(
     rng: &mut (impl rand::RngCore + rand::CryptoRng),
     clock: &impl Clock,
@@ -63,6 +181,7 @@ pub(crate) fn generate_id_token(
     claims::AUD.insert(&mut claims, client.client_id.clone())?;
     claims::IAT.insert(&mut claims, now)?;
     claims::EXP.insert(&mut claims, now + Duration::try_hours(1).unwrap())?;
+    claims::SID.insert(&mut claims, browser_session.id.to_string())?;
 
     if let Some(nonce) = grant.and_then(|grant| grant.nonce.as_ref()) {
         claims::NONCE.insert(&mut claims, nonce)?;
@@ -70,6 +189,19 @@ pub(crate) fn generate_id_token(
 
     if let Some(last_authentication) = last_authentication {
         claims::AUTH_TIME.insert(&mut claims, last_authentication.created_at)?;
+
+        let method = &last_authentication.authentication_method;
+        let amr = method.amr();
+        if !amr.is_empty() {
+            claims::AMR.insert(
+                &mut claims,
+                amr.iter().map(|&m| m.to_owned()).collect::<Vec<_>>(),
+            )?;
+        }
+
+        if method.is_multi_factor() {
+            claims::ACR.insert(&mut claims, ACR_MULTI_FACTOR.to_owned())?;
+        }
     }
 
     let alg = client
@@ -102,13 +234,14 @@ pub(crate) async fn generate_token_pair<R: RepositoryAccess>(
     repo: &mut R,
     session: &Session,
     ttl: Duration,
+    dpop_jkt: Option<String>,
 ) -> Result<(AccessToken, RefreshToken), R::Error> {
     let access_token_str = TokenType::AccessToken.generate(rng);
     let refresh_token_str = TokenType::RefreshToken.generate(rng);
 
     let access_token = repo
         .oauth2_access_token()
-        .add(rng, clock, session, access_token_str, Some(ttl))
+        .add(rng, clock, session, access_token_str, Some(ttl), dpop_jkt)
         .await?;
 
     let refresh_token = repo
@@ -0,0 +1,564 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeMap;
+
+use axum::{extract::State, response::IntoResponse, Json};
+use axum_extra::typed_header::TypedHeader;
+use chrono::Duration;
+use headers::{CacheControl, Pragma};
+use hyper::StatusCode;
+use mas_axum_utils::{
+    client_authorization::{ClientAuthorization, CredentialsVerificationError},
+    http_client_factory::HttpClientFactory,
+    sentry::SentryEventID,
+};
+use mas_data_model::{AuditContext, AuditEventKind};
+use mas_keystore::Encrypter;
+use mas_policy::{Policy, Violation};
+use mas_router::UrlBuilder;
+use mas_storage::{
+    audit::AuditEventRepository, oauth2::OAuth2PushedAuthorizationRequestRepository, BoxClock,
+    BoxRepository, BoxRng,
+};
+use oauth2_types::{
+    errors::{ClientError, ClientErrorCode},
+    requests::{GrantType, PushedAuthorizationResponse},
+};
+use thiserror::Error;
+
+use super::authorization::{request_object, request_object::RequestObjectError, Params};
+use crate::{impl_from_error_for_route, BoundActivityTracker};
+
+#[derive(Debug, Error)]
+pub(crate) enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("client not found")]
+    ClientNotFound,
+
+    #[error("client not allowed")]
+    ClientNotAllowed,
+
+    #[error("could not verify client credentials")]
+    ClientCredentialsVerification(#[from] CredentialsVerificationError),
+
+    #[error("the request_uri parameter can't be pushed")]
+    RequestUriNotAllowed,
+
+    #[error("invalid request object")]
+    InvalidRequestObject(#[from] RequestObjectError),
+
+    #[error("policy denied the request")]
+    DeniedByPolicy(Vec<Violation>),
+
+    #[error("invalid authorization request")]
+    InvalidParameters(#[source] serde::de::value::Error),
+
+    #[error("invalid redirect uri")]
+    InvalidRedirectUri(#[from] mas_data_model::InvalidRedirectUriError),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_policy::EvaluationError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let event_id = sentry::capture_error(&self);
+
+        let response = match self {
+            Self::Internal(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ClientError::from(ClientErrorCode::ServerError)),
+            ),
+            Self::ClientNotFound | Self::ClientCredentialsVerification(_) => (
+                StatusCode::UNAUTHORIZED,
+                Json(ClientError::from(ClientErrorCode::InvalidClient)),
+            ),
+            Self::ClientNotAllowed => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::UnauthorizedClient)),
+            ),
+            Self::RequestUriNotAllowed => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequest).with_description(
+                        "The request_uri parameter must not be pushed".to_owned(),
+                    ),
+                ),
+            ),
+            Self::InvalidRequestObject(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequestObject)
+                        .with_description(e.to_string()),
+                ),
+            ),
+            Self::DeniedByPolicy(violations) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequest).with_description(
+                        violations
+                            .into_iter()
+                            .map(|violation| violation.msg)
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ),
+                ),
+            ),
+            Self::InvalidParameters(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequest)
+                        .with_description(e.to_string()),
+                ),
+            ),
+            Self::InvalidRedirectUri(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequest).with_description(
+                        "The redirect_uri is not registered for this client".to_owned(),
+                    ),
+                ),
+            ),
+        };
+
+        (SentryEventID::from(event_id), response).into_response()
+    }
+}
+
+#[tracing::instrument(
+    name = "handlers.oauth2.pushed_authorization_request.post",
+    fields(client.id = client_authorization.client_id()),
+    skip_all,
+    err,
+)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    State(http_client_factory): State<HttpClientFactory>,
+    State(encrypter): State<Encrypter>,
+    State(url_builder): State<UrlBuilder>,
+    mut policy: Policy,
+    activity_tracker: BoundActivityTracker,
+    client_authorization: ClientAuthorization<BTreeMap<String, String>>,
+) -> Result<impl IntoResponse, RouteError> {
+    let client = client_authorization
+        .credentials
+        .fetch(&mut repo)
+        .await?
+        .ok_or(RouteError::ClientNotFound)?;
+
+    // Authenticate the client the same way as on the token endpoint
+    let method = client
+        .token_endpoint_auth_method
+        .as_ref()
+        .ok_or(RouteError::ClientNotAllowed)?;
+
+    client_authorization
+        .credentials
+        .verify(&http_client_factory, &encrypter, method, &client)
+        .await?;
+
+    if !client.grant_types.contains(&GrantType::AuthorizationCode) {
+        return Err(RouteError::ClientNotAllowed);
+    }
+
+    let mut parameters = client_authorization.form.unwrap_or_default();
+
+    // A pushed request can't itself refer to another request
+    if parameters.contains_key("request_uri") {
+        return Err(RouteError::RequestUriNotAllowed);
+    }
+
+    // If the request was sent as a signed request object, verify it now and
+    // store the parameters it contains
+    let signed_request_object = if let Some(request) = parameters.get("request") {
+        parameters = request_object::verify(
+            &clock,
+            &http_client_factory,
+            &encrypter,
+            &url_builder,
+            &client,
+            request,
+        )
+        .await?;
+        true
+    } else {
+        false
+    };
+
+    // Let the policy decide whether the client may send this request, for
+    // example if it has to be signed
+    let res = policy
+        .evaluate_authorization_request(&client, signed_request_object)
+        .await?;
+    if !res.valid() {
+        let audit = AuditContext::anonymous().with_ip_address(activity_tracker.ip());
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::PolicyDenied,
+                None,
+                serde_json::json!({
+                    "operation": "pushed_authorization_request",
+                    "client_id": client.id,
+                    "violations": res.violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+                }),
+            )
+            .await?;
+        repo.save().await?;
+
+        return Err(RouteError::DeniedByPolicy(res.violations));
+    }
+
+    // Validate the request now, so that the client gets the errors directly
+    // instead of on the authorization endpoint
+    let mut full_parameters = parameters.clone();
+    full_parameters.insert("client_id".to_owned(), client.client_id.clone());
+    let params = Params::from_parameters(full_parameters).map_err(RouteError::InvalidParameters)?;
+    client.resolve_redirect_uri(&params.auth.redirect_uri)?;
+
+    // Pushed requests are meant to be used right away, so they are short-lived
+    let expires_in = Duration::microseconds(60 * 1000 * 1000);
+
+    let request = repo
+        .oauth2_pushed_authorization_request()
+        .add(&mut rng, &clock, &client, parameters, expires_in)
+        .await?;
+
+    repo.save().await?;
+
+    let response = PushedAuthorizationResponse {
+        request_uri: request.request_uri(),
+        expires_in,
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        TypedHeader(CacheControl::new().with_no_store()),
+        TypedHeader(Pragma::no_cache()),
+        Json(response),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::Duration;
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_router::SimpleRoute;
+    use mas_storage::{
+        oauth2::{OAuth2ClientRepository, OAuth2PushedAuthorizationRequestRepository},
+        RepositoryAccess,
+    };
+    use oauth2_types::{
+        errors::{ClientError, ClientErrorCode},
+        registration::ClientRegistrationResponse,
+        requests::PushedAuthorizationResponse,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    async fn register_client(state: &TestState, require_par: bool) -> String {
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "require_pushed_authorization_requests": require_par,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let response: ClientRegistrationResponse = response.json();
+        response.client_id
+    }
+
+    fn authorize(client_id: &str, request_uri: &str) -> Request<String> {
+        let query =
+            serde_urlencoded::to_string([("client_id", client_id), ("request_uri", request_uri)])
+                .unwrap();
+
+        Request::get(format!(
+            "{}?{query}",
+            mas_router::OAuth2AuthorizationEndpoint::PATH
+        ))
+        .empty()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_pushed_authorization_request(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = register_client(&state, false).await;
+
+        // Push a valid request
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "redirect_uri": "https://example.com/callback",
+                "scope": "openid",
+                "state": "abcd",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: PushedAuthorizationResponse = response.json();
+        assert!(response
+            .request_uri
+            .starts_with("urn:ietf:params:oauth:request_uri:"));
+        assert_eq!(response.expires_in.num_seconds(), 60);
+
+        // The request can be used once on the authorization endpoint, which
+        // sends the user to the login page
+        let response = state
+            .request(authorize(&client_id, &response.request_uri))
+            .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let location = response.headers().get(LOCATION).unwrap().to_str().unwrap();
+        assert!(location.starts_with("/login"));
+
+        // Request URIs which were never issued are rejected
+        let response = state
+            .request(authorize(
+                &client_id,
+                "urn:ietf:params:oauth:request_uri:01J8QZ6M4T5W8V1D6KXC2Y3N7E",
+            ))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_pushed_authorization_request_single_use(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = register_client(&state, false).await;
+        let other_client_id = register_client(&state, false).await;
+
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "redirect_uri": "https://example.com/callback",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let PushedAuthorizationResponse { request_uri, .. } = response.json();
+
+        // Another client can't use the request
+        let response = state
+            .request(authorize(&other_client_id, &request_uri))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // The right client can use it, but only once
+        let response = state.request(authorize(&client_id, &request_uri)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        let response = state.request(authorize(&client_id, &request_uri)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_pushed_authorization_request_single_use_on_error(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = register_client(&state, false).await;
+
+        // With prompt=none and no session, the client gets an error back right away
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "redirect_uri": "https://example.com/callback",
+                "scope": "openid",
+                "prompt": "none",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let PushedAuthorizationResponse { request_uri, .. } = response.json();
+
+        let response = state.request(authorize(&client_id, &request_uri)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let location = response.headers().get(LOCATION).unwrap().to_str().unwrap();
+        assert!(location.starts_with("https://example.com/callback"));
+        assert!(location.contains("error=login_required"));
+
+        // The request was still consumed
+        let response = state.request(authorize(&client_id, &request_uri)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_pushed_authorization_request_single_use_on_invalid_request(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = register_client(&state, false).await;
+
+        // Store a request with a redirect URI which isn't registered, as if the
+        // client's redirect URIs changed after it was pushed
+        let mut rng = state.rng();
+        let mut repo = state.repository().await.unwrap();
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let parameters = BTreeMap::from([
+            ("response_type".to_owned(), "code".to_owned()),
+            (
+                "redirect_uri".to_owned(),
+                "https://example.com/other".to_owned(),
+            ),
+            ("scope".to_owned(), "openid".to_owned()),
+        ]);
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .add(
+                &mut rng,
+                &state.clock,
+                &client,
+                parameters,
+                Duration::minutes(1),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+        let request_uri = request.request_uri();
+
+        let response = state.request(authorize(&client_id, &request_uri)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(response.body().contains("Invalid redirect URI"));
+
+        // The request was still consumed
+        let response = state.request(authorize(&client_id, &request_uri)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert_eq!(response.body(), "invalid or expired request_uri");
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_pushed_authorization_request_invalid(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = register_client(&state, false).await;
+
+        // The redirect URI must be registered
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "redirect_uri": "https://example.com/other",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let error: ClientError = response.json();
+        assert_eq!(error.error, ClientErrorCode::InvalidRequest);
+
+        // The request_uri parameter can't be pushed
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "redirect_uri": "https://example.com/callback",
+                "scope": "openid",
+                "request_uri": "https://example.com/request.jwt",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Missing required parameters
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "redirect_uri": "https://example.com/callback",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Unknown client
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": "01J8QZ6M4T5W8V1D6KXC2Y3N7E",
+                "response_type": "code",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // Clients which can't use the authorization code grant can't push requests
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "token_endpoint_auth_method": "none",
+                "response_types": [],
+                "grant_types": ["urn:ietf:params:oauth:grant-type:device_code"],
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let error: ClientError = response.json();
+        assert_eq!(error.error, ClientErrorCode::UnauthorizedClient);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_require_pushed_authorization_requests(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = register_client(&state, true).await;
+
+        // Sending the request directly to the authorization endpoint is rejected
+        let query = serde_urlencoded::to_string([
+            ("client_id", client_id.as_str()),
+            ("response_type", "code"),
+            ("redirect_uri", "https://example.com/callback"),
+            ("scope", "openid"),
+        ])
+        .unwrap();
+        let request = Request::get(format!(
+            "{}?{query}",
+            mas_router::OAuth2AuthorizationEndpoint::PATH
+        ))
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Pushing it first works
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "redirect_uri": "https://example.com/callback",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let PushedAuthorizationResponse { request_uri, .. } = response.json();
+
+        let response = state.request(authorize(&client_id, &request_uri)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+    }
+}
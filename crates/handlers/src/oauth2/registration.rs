@@ -7,10 +7,14 @@
 use axum::{extract::State, response::IntoResponse, Json};
 use hyper::StatusCode;
 use mas_axum_utils::sentry::SentryEventID;
+use mas_data_model::{AuditContext, AuditEventKind};
 use mas_iana::oauth::OAuthClientAuthenticationMethod;
+use mas_jose::jwa::{SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS, SUPPORTED_ENCRYPTION_ALGORITHMS};
 use mas_keystore::Encrypter;
 use mas_policy::{Policy, Violation};
-use mas_storage::{oauth2::OAuth2ClientRepository, BoxClock, BoxRepository, BoxRng};
+use mas_storage::{
+    audit::AuditEventRepository, oauth2::OAuth2ClientRepository, BoxClock, BoxRepository, BoxRng,
+};
 use oauth2_types::{
     errors::{ClientError, ClientErrorCode},
     registration::{
@@ -25,7 +29,7 @@ use thiserror::Error;
 use tracing::info;
 use url::Url;
 
-use crate::impl_from_error_for_route;
+use crate::{impl_from_error_for_route, BoundActivityTracker};
 
 #[derive(Debug, Error)]
 pub(crate) enum RouteError {
@@ -41,6 +45,12 @@ pub(crate) enum RouteError {
     #[error("{0} is a public suffix, not a valid domain")]
     UrlIsPublicSuffix(&'static str),
 
+    #[error("{0} is not supported")]
+    UnsupportedAlgorithm(&'static str),
+
+    #[error("{0} requires either jwks or jwks_uri to be set")]
+    MissingEncryptionKeys(&'static str),
+
     #[error("denied by the policy: {0:?}")]
     PolicyDenied(Vec<Violation>),
 }
@@ -122,6 +132,17 @@ impl IntoResponse for RouteError {
             )
                 .into_response(),
 
+            // This error happens if the client asks for encrypted responses which we can't
+            // produce, either because of the algorithms or because of the missing keys.
+            e @ (Self::UnsupportedAlgorithm(_) | Self::MissingEncryptionKeys(_)) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidClientMetadata)
+                        .with_description(e.to_string()),
+                ),
+            )
+                .into_response(),
+
             // For policy violations, we return an `invalid_client_metadata` error with the details
             // of the violations in most cases. If a violation includes `redirect_uri` in the
             // message, we return an `invalid_redirect_uri` error instead.
@@ -195,6 +216,7 @@ pub(crate) async fn post(
     clock: BoxClock,
     mut repo: BoxRepository,
     mut policy: Policy,
+    activity_tracker: BoundActivityTracker,
     State(encrypter): State<Encrypter>,
     body: Result<Json<ClientMetadata>, axum::extract::rejection::JsonRejection>,
 ) -> Result<impl IntoResponse, RouteError> {
@@ -244,8 +266,88 @@ pub(crate) async fn post(
         }
     }
 
+    for post_logout_redirect_uri in metadata.post_logout_redirect_uris.iter().flatten() {
+        if host_is_public_suffix(post_logout_redirect_uri) {
+            return Err(RouteError::UrlIsPublicSuffix("post_logout_redirect_uri"));
+        }
+    }
+
+    if let Some(backchannel_logout_uri) = &metadata.backchannel_logout_uri {
+        if host_is_public_suffix(backchannel_logout_uri) {
+            return Err(RouteError::UrlIsPublicSuffix("backchannel_logout_uri"));
+        }
+    }
+
+    // Check that we are able to encrypt the ID tokens and userinfo responses if
+    // the client asked for it
+    let (id_token_encrypted_response_alg, id_token_encrypted_response_enc) = metadata
+        .id_token_encrypted_response()
+        .map(|(alg, enc)| (alg.clone(), enc.clone()))
+        .unzip();
+
+    let (userinfo_encrypted_response_alg, userinfo_encrypted_response_enc) = metadata
+        .userinfo_encrypted_response()
+        .map(|(alg, enc)| (alg.clone(), enc.clone()))
+        .unzip();
+
+    for (field, alg) in [
+        (
+            "id_token_encrypted_response_alg",
+            &id_token_encrypted_response_alg,
+        ),
+        (
+            "userinfo_encrypted_response_alg",
+            &userinfo_encrypted_response_alg,
+        ),
+    ] {
+        let Some(alg) = alg else { continue };
+
+        if !SUPPORTED_ENCRYPTION_ALGORITHMS.contains(alg) {
+            return Err(RouteError::UnsupportedAlgorithm(field));
+        }
+
+        if metadata.jwks.is_none() && metadata.jwks_uri.is_none() {
+            return Err(RouteError::MissingEncryptionKeys(field));
+        }
+    }
+
+    for (field, enc) in [
+        (
+            "id_token_encrypted_response_enc",
+            &id_token_encrypted_response_enc,
+        ),
+        (
+            "userinfo_encrypted_response_enc",
+            &userinfo_encrypted_response_enc,
+        ),
+    ] {
+        if enc
+            .as_ref()
+            .is_some_and(|enc| !SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS.contains(enc))
+        {
+            return Err(RouteError::UnsupportedAlgorithm(field));
+        }
+    }
+
     let res = policy.evaluate_client_registration(&metadata).await?;
     if !res.valid() {
+        let audit = AuditContext::anonymous().with_ip_address(activity_tracker.ip());
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::PolicyDenied,
+                None,
+                serde_json::json!({
+                    "operation": "client_registration",
+                    "redirect_uris": metadata.redirect_uris(),
+                    "violations": res.violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+                }),
+            )
+            .await?;
+        repo.save().await?;
+
         return Err(RouteError::PolicyDenied(res.violations));
     }
 
@@ -289,6 +391,19 @@ pub(crate) async fn post(
             metadata.token_endpoint_auth_method.clone(),
             metadata.token_endpoint_auth_signing_alg.clone(),
             metadata.initiate_login_uri.clone(),
+            metadata
+                .post_logout_redirect_uris
+                .clone()
+                .unwrap_or_default(),
+            metadata.backchannel_logout_uri.clone(),
+            metadata.backchannel_logout_session_required(),
+            metadata.require_pushed_authorization_requests(),
+            metadata.request_object_signing_alg.clone(),
+            metadata.require_signed_request_object(),
+            id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc,
         )
         .await?;
 
@@ -438,6 +553,47 @@ mod tests {
             response.error_description.unwrap(),
             "client_uri is not using a valid domain"
         );
+
+        // Asking for an encryption algorithm we don't support
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "client_secret_basic",
+                "jwks_uri": "https://example.com/jwks.json",
+                "id_token_encrypted_response_alg": "A128KW",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidClientMetadata);
+        assert_eq!(
+            response.error_description.unwrap(),
+            "id_token_encrypted_response_alg is not supported"
+        );
+
+        // Asking for encrypted responses without giving us any key
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "client_secret_basic",
+                "userinfo_encrypted_response_alg": "RSA-OAEP",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidClientMetadata);
+        assert_eq!(
+            response.error_description.unwrap(),
+            "userinfo_encrypted_response_alg requires either jwks or jwks_uri to be set"
+        );
     }
 
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
@@ -476,5 +632,32 @@ mod tests {
         response.assert_status(StatusCode::CREATED);
         let response: ClientRegistrationResponse = response.json();
         assert!(response.client_secret.is_some());
+
+        // A successful registration asking for encrypted ID tokens should get the
+        // default content encryption algorithm back
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "none",
+                "jwks": {
+                    "keys": [{
+                        "kty": "EC",
+                        "crv": "P-256",
+                        "x": "MKBCTNIcKUSDii11ySs3526iDZ8AiTo7Tu6KPAqv7D4",
+                        "y": "4Etl6SRW2YiLUrN5vfvVHuhp7x8PxltmWWlbbM4IFyM",
+                        "use": "enc",
+                    }],
+                },
+                "id_token_encrypted_response_alg": "ECDH-ES",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: serde_json::Value = response.json();
+        assert_eq!(response["id_token_encrypted_response_alg"], "ECDH-ES");
+        assert_eq!(response["id_token_encrypted_response_enc"], "A128CBC-HS256");
     }
 }
@@ -306,6 +306,7 @@ mod tests {
                 &mut repo,
                 &session,
                 Duration::microseconds(5 * 60 * 1000 * 1000),
+                None,
             )
             .await
             .unwrap();
@@ -373,6 +374,7 @@ mod tests {
                 &mut repo,
                 &session,
                 Duration::microseconds(5 * 60 * 1000 * 1000),
+                None,
             )
             .await
             .unwrap();
@@ -4,18 +4,27 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
+use std::collections::HashMap;
+
 use axum::{extract::State, response::IntoResponse, Json};
 use axum_extra::typed_header::TypedHeader;
 use chrono::Duration;
 use headers::{CacheControl, HeaderMap, HeaderMapExt, Pragma};
-use hyper::StatusCode;
+use hyper::{Method, StatusCode};
 use mas_axum_utils::{
-    client_authorization::{ClientAuthorization, CredentialsVerificationError},
+    client_authorization::{fetch_jwks, ClientAuthorization, CredentialsVerificationError},
+    dpop::{DPoPHeader, DPoPProof, DPoPProofError},
     http_client_factory::HttpClientFactory,
     sentry::SentryEventID,
 };
 use mas_data_model::{
-    AuthorizationGrantStage, Client, Device, DeviceCodeGrantState, SiteConfig, TokenType, UserAgent,
+    AuditContext, AuditEventKind, AuthorizationGrantStage, Client, Device, DeviceCodeGrantState,
+    SiteConfig, TokenType, User, UserAgent,
+};
+use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthAccessTokenType};
+use mas_jose::{
+    claims::{self, ClaimError, TimeOptions},
+    jwt::Jwt,
 };
 use mas_keystore::{Encrypter, Keystore};
 use mas_matrix::BoxHomeserverConnection;
@@ -23,11 +32,12 @@ use mas_oidc_client::types::scope::ScopeToken;
 use mas_policy::Policy;
 use mas_router::UrlBuilder;
 use mas_storage::{
+    audit::AuditEventRepository,
     oauth2::{
-        OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository,
+        OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository, OAuth2ClientRepository,
         OAuth2RefreshTokenRepository, OAuth2SessionRepository,
     },
-    user::BrowserSessionRepository,
+    user::{BrowserSessionRepository, UserRepository},
     BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
 };
 use oauth2_types::{
@@ -35,17 +45,27 @@ use oauth2_types::{
     pkce::CodeChallengeError,
     requests::{
         AccessTokenRequest, AccessTokenResponse, AuthorizationCodeGrant, ClientCredentialsGrant,
-        DeviceCodeGrant, GrantType, RefreshTokenGrant,
+        DeviceCodeGrant, GrantType, JwtBearerGrant, RefreshTokenGrant, TokenExchangeGrant,
+        TokenTypeIdentifier,
     },
     scope,
 };
+use serde_json::Value;
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, info};
 use ulid::Ulid;
 
-use super::{generate_id_token, generate_token_pair};
+use super::{encrypt_id_token, generate_id_token, generate_token_pair, id_token_encryption_key};
 use crate::{impl_from_error_for_route, BoundActivityTracker};
 
+/// The maximum time between the `iat` and `exp` claims of a JWT bearer
+/// assertion
+const MAX_ASSERTION_LIFETIME: Duration = Duration::microseconds(60 * 60 * 1000 * 1000);
+
+/// How far past its `exp` claim a JWT bearer assertion is still accepted, to
+/// account for clock skew
+const ASSERTION_LEEWAY: Duration = Duration::microseconds(5 * 60 * 1000 * 1000);
+
 #[derive(Debug, Error)]
 pub(crate) enum RouteError {
     #[error(transparent)]
@@ -113,6 +133,51 @@ pub(crate) enum RouteError {
 
     #[error("failed to provision device")]
     ProvisionDeviceFailed(#[source] anyhow::Error),
+
+    #[error("invalid DPoP proof")]
+    InvalidDPoPProof(#[from] DPoPProofError),
+
+    #[error("token {0} is bound to another DPoP key")]
+    DPoPKeyMismatch(Ulid),
+
+    #[error("unsupported subject token type {0}")]
+    UnsupportedSubjectTokenType(TokenTypeIdentifier),
+
+    #[error("unsupported requested token type {0}")]
+    UnsupportedRequestedTokenType(TokenTypeIdentifier),
+
+    #[error("actor tokens are not supported")]
+    ActorTokenNotSupported,
+
+    #[error("the resource parameter is not supported")]
+    TargetNotSupported,
+
+    #[error("the audience {0:?} is not a known client")]
+    UnknownAudience(String),
+
+    #[error("subject token is invalid")]
+    SubjectTokenInvalid,
+
+    #[error("requested scope was not granted to the subject token")]
+    ScopeNotGranted,
+
+    #[error("failed to load the client of the subject token")]
+    NoSuchSubjectClient,
+
+    #[error("invalid assertion")]
+    InvalidAssertion,
+
+    #[error("invalid assertion claims")]
+    InvalidAssertionClaims(#[from] ClaimError),
+
+    #[error("user in the assertion does not exist or is locked")]
+    AssertionUserNotFound,
+
+    #[error("assertion is valid for too long")]
+    AssertionLifetimeTooLong,
+
+    #[error("assertion was already used")]
+    AssertionReplayed,
 }
 
 impl IntoResponse for RouteError {
@@ -123,6 +188,7 @@ impl IntoResponse for RouteError {
             Self::Internal(_)
             | Self::NoSuchBrowserSession
             | Self::NoSuchOAuthSession
+            | Self::NoSuchSubjectClient
             | Self::ProvisionDeviceFailed(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ClientError::from(ClientErrorCode::ServerError)),
@@ -131,6 +197,30 @@ impl IntoResponse for RouteError {
                 StatusCode::BAD_REQUEST,
                 Json(ClientError::from(ClientErrorCode::InvalidRequest)),
             ),
+            Self::UnsupportedSubjectTokenType(_)
+            | Self::UnsupportedRequestedTokenType(_)
+            | Self::ActorTokenNotSupported
+            | Self::SubjectTokenInvalid => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequest)
+                        .with_description(self.to_string()),
+                ),
+            ),
+            Self::TargetNotSupported | Self::UnknownAudience(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidTarget)
+                        .with_description(self.to_string()),
+                ),
+            ),
+            Self::ScopeNotGranted => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidScope)
+                        .with_description(self.to_string()),
+                ),
+            ),
             Self::PkceVerification(err) => (
                 StatusCode::BAD_REQUEST,
                 Json(
@@ -170,6 +260,17 @@ impl IntoResponse for RouteError {
                 StatusCode::FORBIDDEN,
                 Json(ClientError::from(ClientErrorCode::AuthorizationPending)),
             ),
+            Self::InvalidAssertion
+            | Self::InvalidAssertionClaims(_)
+            | Self::AssertionUserNotFound
+            | Self::AssertionLifetimeTooLong
+            | Self::AssertionReplayed => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidGrant)
+                        .with_description(self.to_string()),
+                ),
+            ),
             Self::InvalidGrant
             | Self::DeviceCodeExchanged
             | Self::RefreshTokenNotFound
@@ -184,6 +285,17 @@ impl IntoResponse for RouteError {
                 StatusCode::BAD_REQUEST,
                 Json(ClientError::from(ClientErrorCode::UnsupportedGrantType)),
             ),
+            Self::InvalidDPoPProof(err) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidDpopProof)
+                        .with_description(err.to_string()),
+                ),
+            ),
+            Self::DPoPKeyMismatch(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::InvalidDpopProof)),
+            ),
         };
 
         (SentryEventID::from(event_id), response).into_response()
@@ -193,6 +305,7 @@ impl IntoResponse for RouteError {
 impl_from_error_for_route!(mas_storage::RepositoryError);
 impl_from_error_for_route!(mas_policy::EvaluationError);
 impl_from_error_for_route!(super::IdTokenSignatureError);
+impl_from_error_for_route!(super::ResponseEncryptionError);
 
 #[tracing::instrument(
     name = "handlers.oauth2.token.post",
@@ -213,6 +326,7 @@ pub(crate) async fn post(
     State(encrypter): State<Encrypter>,
     policy: Policy,
     user_agent: Option<TypedHeader<headers::UserAgent>>,
+    dpop: Option<TypedHeader<DPoPHeader>>,
     client_authorization: ClientAuthorization<AccessTokenRequest>,
 ) -> Result<impl IntoResponse, RouteError> {
     let user_agent = user_agent.map(|ua| UserAgent::parse(ua.as_str().to_owned()));
@@ -234,7 +348,37 @@ pub(crate) async fn post(
 
     let form = client_authorization.form.ok_or(RouteError::BadRequest)?;
 
-    let (reply, repo) = match form {
+    // If the client sent a DPoP proof, the issued tokens are bound to its key
+    let dpop_jkt = if let Some(TypedHeader(dpop)) = dpop {
+        let proof = DPoPProof::verify(dpop.proof(), clock.now())?;
+        proof.verify_request(&Method::POST, &url_builder.oauth_token_endpoint())?;
+        // The proof is recorded in the same transaction as the issued tokens
+        if !proof.consume(&mut repo, &clock).await? {
+            return Err(DPoPProofError::Replayed.into());
+        }
+        Some(proof.jkt().to_owned())
+    } else {
+        None
+    };
+    let token_type = if dpop_jkt.is_some() {
+        OAuthAccessTokenType::DPoP
+    } else {
+        OAuthAccessTokenType::Bearer
+    };
+
+    // Resolve the key to encrypt the ID token with before processing the grant, as
+    // it may need to fetch the client JWKS, which shouldn't happen while holding
+    // the writes of the transaction
+    let id_token_encryption_key = match form {
+        AccessTokenRequest::AuthorizationCode(_)
+        | AccessTokenRequest::RefreshToken(_)
+        | AccessTokenRequest::DeviceCode(_) => {
+            id_token_encryption_key(&http_client_factory, &client).await?
+        }
+        _ => None,
+    };
+
+    let (mut reply, repo) = match form {
         AccessTokenRequest::AuthorizationCode(grant) => {
             authorization_code_grant(
                 &mut rng,
@@ -248,6 +392,7 @@ pub(crate) async fn post(
                 repo,
                 &homeserver,
                 user_agent,
+                dpop_jkt,
             )
             .await?
         }
@@ -261,6 +406,7 @@ pub(crate) async fn post(
                 &site_config,
                 repo,
                 user_agent,
+                dpop_jkt,
             )
             .await?
         }
@@ -275,6 +421,7 @@ pub(crate) async fn post(
                 repo,
                 policy,
                 user_agent,
+                dpop_jkt,
             )
             .await?
         }
@@ -291,6 +438,40 @@ pub(crate) async fn post(
                 repo,
                 &homeserver,
                 user_agent,
+                dpop_jkt,
+            )
+            .await?
+        }
+        AccessTokenRequest::JwtBearer(grant) => {
+            jwt_bearer_grant(
+                &mut rng,
+                &clock,
+                &activity_tracker,
+                &grant,
+                &client,
+                &http_client_factory,
+                &url_builder,
+                &site_config,
+                repo,
+                &homeserver,
+                policy,
+                user_agent,
+                dpop_jkt,
+            )
+            .await?
+        }
+        AccessTokenRequest::TokenExchange(grant) => {
+            token_exchange_grant(
+                &mut rng,
+                &clock,
+                &activity_tracker,
+                &grant,
+                &client,
+                &site_config,
+                repo,
+                policy,
+                user_agent,
+                dpop_jkt,
             )
             .await?
         }
@@ -299,8 +480,18 @@ pub(crate) async fn post(
         }
     };
 
+    if let Some(id_token) = reply.id_token.take() {
+        reply.id_token = Some(encrypt_id_token(
+            &mut rng,
+            id_token_encryption_key.as_ref(),
+            id_token,
+        )?);
+    }
+
     repo.save().await?;
 
+    let reply = reply.with_token_type(token_type);
+
     let mut headers = HeaderMap::new();
     headers.typed_insert(CacheControl::new().with_no_store());
     headers.typed_insert(Pragma::no_cache());
@@ -321,6 +512,7 @@ async fn authorization_code_grant(
     mut repo: BoxRepository,
     homeserver: &BoxHomeserverConnection,
     user_agent: Option<UserAgent>,
+    dpop_jkt: Option<String>,
 ) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
     // Check that the client is allowed to use this grant type
     if !client.grant_types.contains(&GrantType::AuthorizationCode) {
@@ -426,7 +618,7 @@ async fn authorization_code_grant(
 
     let ttl = site_config.access_token_ttl;
     let (access_token, refresh_token) =
-        generate_token_pair(&mut rng, clock, &mut repo, &session, ttl).await?;
+        generate_token_pair(&mut rng, clock, &mut repo, &session, ttl, dpop_jkt).await?;
 
     let id_token = if session.scope.contains(&scope::OPENID) {
         Some(generate_id_token(
@@ -492,6 +684,7 @@ async fn refresh_token_grant(
     site_config: &SiteConfig,
     mut repo: BoxRepository,
     user_agent: Option<UserAgent>,
+    dpop_jkt: Option<String>,
 ) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
     // Check that the client is allowed to use this grant type
     if !client.grant_types.contains(&GrantType::RefreshToken) {
@@ -535,13 +728,21 @@ async fn refresh_token_grant(
         });
     }
 
+    // A refresh token bound to a DPoP key can only be used with a proof signed by
+    // that same key
+    if let Some(bound_jkt) = &refresh_token.dpop_jkt {
+        if dpop_jkt.as_deref() != Some(bound_jkt.as_str()) {
+            return Err(RouteError::DPoPKeyMismatch(refresh_token.id));
+        }
+    }
+
     activity_tracker
         .record_oauth2_session(clock, &session)
         .await;
 
     let ttl = site_config.access_token_ttl;
     let (new_access_token, new_refresh_token) =
-        generate_token_pair(rng, clock, &mut repo, &session, ttl).await?;
+        generate_token_pair(rng, clock, &mut repo, &session, ttl, dpop_jkt).await?;
 
     let refresh_token = repo
         .oauth2_refresh_token()
@@ -575,6 +776,7 @@ async fn client_credentials_grant(
     mut repo: BoxRepository,
     mut policy: Policy,
     user_agent: Option<UserAgent>,
+    dpop_jkt: Option<String>,
 ) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
     // Check that the client is allowed to use this grant type
     if !client.grant_types.contains(&GrantType::ClientCredentials) {
@@ -592,6 +794,18 @@ async fn client_credentials_grant(
         .evaluate_client_credentials_grant(&scope, client)
         .await?;
     if !res.valid() {
+        record_policy_denial(
+            rng,
+            clock,
+            activity_tracker,
+            repo,
+            user_agent.as_ref(),
+            "client_credentials_grant",
+            client,
+            None,
+            &res.violations,
+        )
+        .await?;
         return Err(RouteError::DeniedByPolicy(res.violations));
     }
 
@@ -601,6 +815,11 @@ async fn client_credentials_grant(
         .add_from_client_credentials(rng, clock, client, scope)
         .await?;
 
+    session = repo
+        .oauth2_session()
+        .record_grant_type(session, GrantType::ClientCredentials)
+        .await?;
+
     if let Some(user_agent) = user_agent {
         session = repo
             .oauth2_session()
@@ -613,7 +832,7 @@ async fn client_credentials_grant(
 
     let access_token = repo
         .oauth2_access_token()
-        .add(rng, clock, &session, access_token_str, Some(ttl))
+        .add(rng, clock, &session, access_token_str, Some(ttl), dpop_jkt)
         .await?;
 
     let mut params = AccessTokenResponse::new(access_token.access_token).with_expires_in(ttl);
@@ -645,6 +864,7 @@ async fn device_code_grant(
     mut repo: BoxRepository,
     homeserver: &BoxHomeserverConnection,
     user_agent: Option<UserAgent>,
+    dpop_jkt: Option<String>,
 ) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
     // Check that the client is allowed to use this grant type
     if !client.grant_types.contains(&GrantType::DeviceCode) {
@@ -696,6 +916,11 @@ async fn device_code_grant(
         .add_from_browser_session(rng, clock, client, &browser_session, grant.scope)
         .await?;
 
+    session = repo
+        .oauth2_session()
+        .record_grant_type(session, GrantType::DeviceCode)
+        .await?;
+
     // XXX: should we get the user agent from the device code grant instead?
     if let Some(user_agent) = user_agent {
         session = repo
@@ -709,7 +934,7 @@ async fn device_code_grant(
 
     let access_token = repo
         .oauth2_access_token()
-        .add(rng, clock, &session, access_token_str, Some(ttl))
+        .add(rng, clock, &session, access_token_str, Some(ttl), dpop_jkt)
         .await?;
 
     let mut params =
@@ -776,167 +1001,494 @@ async fn device_code_grant(
     Ok((params, repo))
 }
 
-#[cfg(test)]
-mod tests {
-    use hyper::Request;
-    use mas_data_model::{AccessToken, AuthorizationCode, RefreshToken};
-    use mas_router::SimpleRoute;
-    use oauth2_types::{
-        registration::ClientRegistrationResponse,
-        requests::{DeviceAuthorizationResponse, ResponseMode},
-        scope::{Scope, OPENID},
-    };
-    use sqlx::PgPool;
+#[allow(clippy::too_many_lines)]
+async fn token_exchange_grant(
+    rng: &mut BoxRng,
+    clock: &impl Clock,
+    activity_tracker: &BoundActivityTracker,
+    grant: &TokenExchangeGrant,
+    client: &Client,
+    site_config: &SiteConfig,
+    mut repo: BoxRepository,
+    mut policy: Policy,
+    user_agent: Option<UserAgent>,
+    dpop_jkt: Option<String>,
+) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
+    // Check that the client is allowed to use this grant type
+    if !client.grant_types.contains(&GrantType::TokenExchange) {
+        return Err(RouteError::UnauthorizedClient);
+    }
 
-    use super::*;
-    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+    // We only support exchanging a user's access token for another access token,
+    // without delegation
+    if grant.subject_token_type != TokenTypeIdentifier::AccessToken {
+        return Err(RouteError::UnsupportedSubjectTokenType(
+            grant.subject_token_type.clone(),
+        ));
+    }
 
-    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
-    async fn test_auth_code_grant(pool: PgPool) {
-        setup();
-        let state = TestState::from_pool(pool).await.unwrap();
+    if let Some(requested_token_type) = &grant.requested_token_type {
+        if *requested_token_type != TokenTypeIdentifier::AccessToken {
+            return Err(RouteError::UnsupportedRequestedTokenType(
+                requested_token_type.clone(),
+            ));
+        }
+    }
 
-        // Provision a client
-        let request =
-            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
-                "client_uri": "https://example.com/",
-                "redirect_uris": ["https://example.com/callback"],
-                "token_endpoint_auth_method": "none",
-                "response_types": ["code"],
-                "grant_types": ["authorization_code"],
-            }));
+    if grant.actor_token.is_some() || grant.actor_token_type.is_some() {
+        return Err(RouteError::ActorTokenNotSupported);
+    }
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::CREATED);
+    // The issued tokens are only valid on this server, so we can't honour a
+    // request to target a resource elsewhere
+    if grant.resource.is_some() {
+        return Err(RouteError::TargetNotSupported);
+    }
 
-        let ClientRegistrationResponse { client_id, .. } = response.json();
+    // The audience is the client ID of another registered client, to which the
+    // new session will belong
+    let audience = if let Some(audience) = &grant.audience {
+        let audience_client = repo
+            .oauth2_client()
+            .find_by_client_id(audience)
+            .await?
+            .ok_or_else(|| RouteError::UnknownAudience(audience.clone()))?;
+        Some(audience_client)
+    } else {
+        None
+    };
 
-        // Let's provision a user and create a session for them. This part is hard to
-        // test with just HTTP requests, so we'll use the repository directly.
-        let mut repo = state.repository().await.unwrap();
+    let subject_token = repo
+        .oauth2_access_token()
+        .find_by_token(&grant.subject_token)
+        .await?
+        .ok_or(RouteError::SubjectTokenInvalid)?;
 
-        let user = repo
-            .user()
-            .add(&mut state.rng(), &state.clock, "alice".to_owned())
-            .await
-            .unwrap();
+    if !subject_token.is_valid(clock.now()) {
+        return Err(RouteError::SubjectTokenInvalid);
+    }
 
-        let browser_session = repo
-            .browser_session()
-            .add(&mut state.rng(), &state.clock, &user, None)
-            .await
-            .unwrap();
+    // A subject token bound to a DPoP key can only be exchanged with a proof signed
+    // by that same key
+    if let Some(bound_jkt) = &subject_token.dpop_jkt {
+        if dpop_jkt.as_deref() != Some(bound_jkt.as_str()) {
+            return Err(RouteError::DPoPKeyMismatch(subject_token.id));
+        }
+    }
 
-        // Lookup the client in the database.
-        let client = repo
-            .oauth2_client()
-            .find_by_client_id(&client_id)
-            .await
-            .unwrap()
-            .unwrap();
+    let subject_session = repo
+        .oauth2_session()
+        .lookup(subject_token.session_id)
+        .await?
+        .ok_or(RouteError::NoSuchOAuthSession)?;
 
-        // Start a grant
-        let code = "thisisaverysecurecode";
-        let grant = repo
-            .oauth2_authorization_grant()
-            .add(
-                &mut state.rng(),
-                &state.clock,
-                &client,
-                "https://example.com/redirect".parse().unwrap(),
-                Scope::from_iter([OPENID]),
-                Some(AuthorizationCode {
-                    code: code.to_owned(),
-                    pkce: None,
-                }),
-                Some("state".to_owned()),
-                Some("nonce".to_owned()),
-                None,
-                ResponseMode::Query,
-                false,
-                false,
-            )
-            .await
-            .unwrap();
+    if !subject_session.is_valid() {
+        return Err(RouteError::SessionInvalid(subject_session.id));
+    }
 
-        let session = repo
-            .oauth2_session()
-            .add_from_browser_session(
-                &mut state.rng(),
-                &state.clock,
-                &client,
-                &browser_session,
-                grant.scope.clone(),
-            )
-            .await
-            .unwrap();
+    // Tokens which were not issued on behalf of a user can't be exchanged
+    let user_id = subject_session
+        .user_id
+        .ok_or(RouteError::SubjectTokenInvalid)?;
 
-        // And fulfill it
-        let grant = repo
-            .oauth2_authorization_grant()
-            .fulfill(&state.clock, &session, grant)
-            .await
-            .unwrap();
+    let user = repo
+        .user()
+        .lookup(user_id)
+        .await?
+        .filter(User::is_valid)
+        .ok_or(RouteError::SubjectTokenInvalid)?;
 
-        repo.save().await.unwrap();
+    let subject_client = repo
+        .oauth2_client()
+        .lookup(subject_session.client_id)
+        .await?
+        .ok_or(RouteError::NoSuchSubjectClient)?;
 
-        // Now call the token endpoint to get an access token.
-        let request =
-            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "authorization_code",
-                "code": code,
-                "redirect_uri": grant.redirect_uri,
-                "client_id": client.client_id,
-            }));
+    // Default to the scope of the subject token, and never allow asking for more
+    let scope = grant
+        .scope
+        .clone()
+        .unwrap_or_else(|| subject_session.scope.clone());
+    if !scope.is_subset(&subject_session.scope) {
+        return Err(RouteError::ScopeNotGranted);
+    }
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
+    // Make the request go through the policy engine
+    let res = policy
+        .evaluate_token_exchange(
+            &scope,
+            &subject_session.scope,
+            client,
+            &subject_client,
+            audience.as_ref(),
+            &user,
+        )
+        .await?;
+    if !res.valid() {
+        record_policy_denial(
+            rng,
+            clock,
+            activity_tracker,
+            repo,
+            user_agent.as_ref(),
+            "token_exchange_grant",
+            client,
+            Some(&user),
+            &res.violations,
+        )
+        .await?;
+        return Err(RouteError::DeniedByPolicy(res.violations));
+    }
 
-        let AccessTokenResponse { access_token, .. } = response.json();
+    // The new session is tied to the same browser session as the subject token,
+    // so that it ends when the user logs out
+    let browser_session = if let Some(user_session_id) = subject_session.user_session_id {
+        repo.browser_session().lookup(user_session_id).await?
+    } else {
+        None
+    };
 
-        // Check that the token is valid
-        assert!(state.is_access_token_valid(&access_token).await);
+    // Start the session. If an audience was requested, the session belongs to
+    // that client, so that the service it represents can check that the token
+    // was meant for it when introspecting it.
+    let session_client = audience.as_ref().unwrap_or(client);
+    let mut session = repo
+        .oauth2_session()
+        .add(
+            rng,
+            clock,
+            session_client,
+            Some(&user),
+            browser_session.as_ref(),
+            scope,
+        )
+        .await?;
 
-        // Exchange it again, this it should fail
-        let request =
-            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "authorization_code",
-                "code": code,
-                "redirect_uri": grant.redirect_uri,
-                "client_id": client.client_id,
-            }));
+    session = repo
+        .oauth2_session()
+        .record_grant_type(session, GrantType::TokenExchange)
+        .await?;
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::BAD_REQUEST);
-        let error: ClientError = response.json();
-        assert_eq!(error.error, ClientErrorCode::InvalidGrant);
+    // Remember which client asked for the session, so that introspection can
+    // tell the exchanged token apart from one issued to the session's client
+    session = repo
+        .oauth2_session()
+        .record_acting_client(session, client)
+        .await?;
 
-        // The token should still be valid
-        assert!(state.is_access_token_valid(&access_token).await);
+    if let Some(user_agent) = user_agent {
+        session = repo
+            .oauth2_session()
+            .record_user_agent(session, user_agent)
+            .await?;
+    }
 
-        // Now wait a bit
-        state.clock.advance(Duration::try_minutes(1).unwrap());
+    // The exchanged token must not outlive the subject token
+    let ttl = match subject_token.expires_at {
+        Some(expires_at) => std::cmp::min(site_config.access_token_ttl, expires_at - clock.now()),
+        None => site_config.access_token_ttl,
+    };
+    let access_token_str = TokenType::AccessToken.generate(rng);
 
-        // Exchange it again, this it should fail
+    let access_token = repo
+        .oauth2_access_token()
+        .add(rng, clock, &session, access_token_str, Some(ttl), dpop_jkt)
+        .await?;
+
+    let mut params = AccessTokenResponse::new(access_token.access_token)
+        .with_expires_in(ttl)
+        .with_issued_token_type(TokenTypeIdentifier::AccessToken);
+
+    // XXX: there is a potential (but unlikely) race here, where the activity for
+    // the session is recorded before the transaction is committed. We would have to
+    // save the repository here to fix that.
+    activity_tracker
+        .record_oauth2_session(clock, &session)
+        .await;
+
+    if !session.scope.is_empty() {
+        // We only return the scope if it's not empty
+        params = params.with_scope(session.scope);
+    }
+
+    Ok((params, repo))
+}
+
+#[allow(clippy::too_many_lines)]
+async fn jwt_bearer_grant(
+    rng: &mut BoxRng,
+    clock: &impl Clock,
+    activity_tracker: &BoundActivityTracker,
+    grant: &JwtBearerGrant,
+    client: &Client,
+    http_client_factory: &HttpClientFactory,
+    url_builder: &UrlBuilder,
+    site_config: &SiteConfig,
+    mut repo: BoxRepository,
+    homeserver: &BoxHomeserverConnection,
+    mut policy: Policy,
+    user_agent: Option<UserAgent>,
+    dpop_jkt: Option<String>,
+) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
+    // Check that the client is allowed to use this grant type
+    if !client.grant_types.contains(&GrantType::JwtBearer) {
+        return Err(RouteError::UnauthorizedClient);
+    }
+
+    let jwt: Jwt<'_, HashMap<String, Value>> =
+        Jwt::try_from(grant.assertion.as_str()).map_err(|_| RouteError::InvalidAssertion)?;
+
+    // The assertion must be signed with one of the keys of the client
+    if matches!(
+        jwt.header().alg(),
+        JsonWebSignatureAlg::None
+            | JsonWebSignatureAlg::Hs256
+            | JsonWebSignatureAlg::Hs384
+            | JsonWebSignatureAlg::Hs512
+    ) {
+        return Err(RouteError::InvalidAssertion);
+    }
+
+    let jwks = client.jwks.as_ref().ok_or(RouteError::InvalidAssertion)?;
+    let jwks = fetch_jwks(http_client_factory, jwks)
+        .await
+        .map_err(|_| RouteError::InvalidAssertion)?;
+    jwt.verify_with_jwks(&jwks)
+        .map_err(|_| RouteError::InvalidAssertion)?;
+
+    // As per RFC 7523 section 3, the assertion is issued by the client, must be
+    // intended for us and must not be expired. The subject is the username of the
+    // user the client acts on behalf of.
+    let mut claims = jwt.payload().clone();
+    let time_options = TimeOptions::new(clock.now()).leeway(ASSERTION_LEEWAY);
+
+    claims::ISS.extract_required_with_options(&mut claims, client.client_id.as_str())?;
+    claims::AUD
+        .extract_required_with_options(&mut claims, &url_builder.oidc_issuer().to_string())?;
+    let exp = claims::EXP.extract_required_with_options(&mut claims, &time_options)?;
+    claims::NBF.extract_optional_with_options(&mut claims, &time_options)?;
+    let iat = claims::IAT.extract_required_with_options(&mut claims, time_options)?;
+    let jti = claims::JTI.extract_required(&mut claims)?;
+    let username = claims::SUB.extract_required(&mut claims)?;
+
+    // Assertions must be short-lived, so that we only have to remember their
+    // identifier for a short time to prevent them from being replayed
+    if *exp - *iat > MAX_ASSERTION_LIFETIME {
+        return Err(RouteError::AssertionLifetimeTooLong);
+    }
+
+    let user = repo
+        .user()
+        .find_by_username(&username)
+        .await?
+        .filter(User::is_valid)
+        .ok_or(RouteError::AssertionUserNotFound)?;
+
+    // Default to an empty scope if none is provided
+    let scope = grant
+        .scope
+        .clone()
+        .unwrap_or_else(|| std::iter::empty::<ScopeToken>().collect());
+
+    // Make the request go through the policy engine
+    let res = policy.evaluate_jwt_bearer(&scope, client, &user).await?;
+    if !res.valid() {
+        record_policy_denial(
+            rng,
+            clock,
+            activity_tracker,
+            repo,
+            user_agent.as_ref(),
+            "jwt_bearer_grant",
+            client,
+            Some(&user),
+            &res.violations,
+        )
+        .await?;
+        return Err(RouteError::DeniedByPolicy(res.violations));
+    }
+
+    // Each assertion can only be used once. Its identifier is remembered in the
+    // same transaction as the session, until the assertion would be rejected as
+    // expired anyway.
+    let consumed = repo
+        .oauth2_jwt_bearer_assertion()
+        .consume(clock, client, &jti, *exp + ASSERTION_LEEWAY)
+        .await?;
+    if !consumed {
+        return Err(RouteError::AssertionReplayed);
+    }
+
+    // Start the session. It isn't tied to any browser session, but is listed
+    // alongside the other sessions of the user, with the client which created it.
+    let mut session = repo
+        .oauth2_session()
+        .add(rng, clock, client, Some(&user), None, scope)
+        .await?;
+
+    session = repo
+        .oauth2_session()
+        .record_grant_type(session, GrantType::JwtBearer)
+        .await?;
+
+    info!(
+        oauth2_session.id = %session.id,
+        client.id = %client.id,
+        user.id = %user.id,
+        "Client started a session on behalf of a user with a JWT bearer assertion"
+    );
+
+    if let Some(user_agent) = user_agent {
+        session = repo
+            .oauth2_session()
+            .record_user_agent(session, user_agent)
+            .await?;
+    }
+
+    let ttl = site_config.access_token_ttl;
+    let access_token_str = TokenType::AccessToken.generate(rng);
+
+    let access_token = repo
+        .oauth2_access_token()
+        .add(rng, clock, &session, access_token_str, Some(ttl), dpop_jkt)
+        .await?;
+
+    let mut params = AccessTokenResponse::new(access_token.access_token).with_expires_in(ttl);
+
+    // Lock the user sync to make sure we don't get into a race condition
+    repo.user().acquire_lock_for_sync(&user).await?;
+
+    // Look for device to provision
+    let mxid = homeserver.mxid(&user.username);
+    for scope in &*session.scope {
+        if let Some(device) = Device::from_scope_token(scope) {
+            homeserver
+                .create_device(&mxid, device.as_str())
+                .await
+                .map_err(RouteError::ProvisionDeviceFailed)?;
+        }
+    }
+
+    // XXX: there is a potential (but unlikely) race here, where the activity for
+    // the session is recorded before the transaction is committed. We would have to
+    // save the repository here to fix that.
+    activity_tracker
+        .record_oauth2_session(clock, &session)
+        .await;
+
+    if !session.scope.is_empty() {
+        // We only return the scope if it's not empty
+        params = params.with_scope(session.scope);
+    }
+
+    Ok((params, repo))
+}
+
+/// Record in the audit log that the policy denied a grant, and save it right
+/// away since the request is about to fail
+async fn record_policy_denial(
+    rng: &mut BoxRng,
+    clock: &impl Clock,
+    activity_tracker: &BoundActivityTracker,
+    mut repo: BoxRepository,
+    user_agent: Option<&UserAgent>,
+    operation: &str,
+    client: &Client,
+    user: Option<&User>,
+    violations: &[mas_policy::Violation],
+) -> Result<(), RouteError> {
+    let audit = AuditContext::anonymous()
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.map(|ua| ua.raw.clone()));
+    repo.audit_event()
+        .add(
+            rng,
+            clock,
+            &audit,
+            AuditEventKind::PolicyDenied,
+            user,
+            serde_json::json!({
+                "operation": operation,
+                "client_id": client.id,
+                "violations": violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+            }),
+        )
+        .await?;
+    repo.save().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::Request;
+    use mas_data_model::{AccessToken, AuthorizationCode, RefreshToken};
+    use mas_iana::jose::JsonWebEncryptionAlg;
+    use mas_jose::{
+        jwa::AsymmetricDecryptionKey,
+        jwe::Jwe,
+        jwk::{JsonWebKeyPrivateParameters, JsonWebKeyPublicParameters, PublicJsonWebKey},
+        jwt::{JsonWebSignatureHeader, Jwt},
+    };
+    use mas_keystore::PrivateKey;
+    use mas_router::SimpleRoute;
+    use oauth2_types::{
+        registration::ClientRegistrationResponse,
+        requests::{DeviceAuthorizationResponse, IntrospectionResponse, ResponseMode, TokenActor},
+        scope::{Scope, OPENID},
+    };
+    use sha2::Digest;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_auth_code_grant(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
         let request =
-            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "authorization_code",
-                "code": code,
-                "redirect_uri": grant.redirect_uri,
-                "client_id": client.client_id,
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
             }));
 
         let response = state.request(request).await;
-        response.assert_status(StatusCode::BAD_REQUEST);
-        let error: ClientError = response.json();
-        assert_eq!(error.error, ClientErrorCode::InvalidGrant);
+        response.assert_status(StatusCode::CREATED);
 
-        // And it should have revoked the token we got
-        assert!(!state.is_access_token_valid(&access_token).await);
+        let ClientRegistrationResponse { client_id, .. } = response.json();
 
-        // Try another one and wait for too long before exchanging it
+        // Let's provision a user and create a session for them. This part is hard to
+        // test with just HTTP requests, so we'll use the repository directly.
         let mut repo = state.repository().await.unwrap();
-        let code = "thisisanothercode";
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        // Lookup the client in the database.
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Start a grant
+        let code = "thisisaverysecurecode";
         let grant = repo
             .oauth2_authorization_grant()
             .add(
@@ -978,41 +1530,1118 @@ mod tests {
             .await
             .unwrap();
 
-        repo.save().await.unwrap();
+        repo.save().await.unwrap();
+
+        // Now call the token endpoint to get an access token.
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "authorization_code",
+                "code": code,
+                "redirect_uri": grant.redirect_uri,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let AccessTokenResponse { access_token, .. } = response.json();
+
+        // Check that the token is valid
+        assert!(state.is_access_token_valid(&access_token).await);
+
+        // Exchange it again, this it should fail
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "authorization_code",
+                "code": code,
+                "redirect_uri": grant.redirect_uri,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let error: ClientError = response.json();
+        assert_eq!(error.error, ClientErrorCode::InvalidGrant);
+
+        // The token should still be valid
+        assert!(state.is_access_token_valid(&access_token).await);
+
+        // Now wait a bit
+        state.clock.advance(Duration::try_minutes(1).unwrap());
+
+        // Exchange it again, this it should fail
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "authorization_code",
+                "code": code,
+                "redirect_uri": grant.redirect_uri,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let error: ClientError = response.json();
+        assert_eq!(error.error, ClientErrorCode::InvalidGrant);
+
+        // And it should have revoked the token we got
+        assert!(!state.is_access_token_valid(&access_token).await);
+
+        // Try another one and wait for too long before exchanging it
+        let mut repo = state.repository().await.unwrap();
+        let code = "thisisanothercode";
+        let grant = repo
+            .oauth2_authorization_grant()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                "https://example.com/redirect".parse().unwrap(),
+                Scope::from_iter([OPENID]),
+                Some(AuthorizationCode {
+                    code: code.to_owned(),
+                    pkce: None,
+                }),
+                Some("state".to_owned()),
+                Some("nonce".to_owned()),
+                None,
+                ResponseMode::Query,
+                false,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                grant.scope.clone(),
+            )
+            .await
+            .unwrap();
+
+        // And fulfill it
+        let grant = repo
+            .oauth2_authorization_grant()
+            .fulfill(&state.clock, &session, grant)
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        // Now wait a bit
+        state
+            .clock
+            .advance(Duration::microseconds(15 * 60 * 1000 * 1000));
+
+        // Exchange it, it should fail
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "authorization_code",
+                "code": code,
+                "redirect_uri": grant.redirect_uri,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_encrypted_id_token_and_userinfo(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // The key of Bob from RFC 7518, appendix C
+        let private_params: JsonWebKeyPrivateParameters =
+            serde_json::from_value(serde_json::json!({
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+                "y": "e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+                "d": "VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw",
+            }))
+            .unwrap();
+        let decryption_key = AsymmetricDecryptionKey::from_jwk_and_alg(
+            &private_params,
+            &JsonWebEncryptionAlg::EcdhEs,
+        )
+        .unwrap();
+
+        // Provision a client which wants encrypted ID tokens and userinfo responses
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "jwks": {
+                    "keys": [{
+                        "kty": "EC",
+                        "crv": "P-256",
+                        "use": "enc",
+                        "kid": "bob",
+                        "x": "weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+                        "y": "e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+                    }],
+                },
+                "id_token_encrypted_response_alg": "ECDH-ES",
+                "userinfo_encrypted_response_alg": "ECDH-ES",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let code = "thisisaverysecurecode";
+        let grant = repo
+            .oauth2_authorization_grant()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                "https://example.com/redirect".parse().unwrap(),
+                Scope::from_iter([OPENID]),
+                Some(AuthorizationCode {
+                    code: code.to_owned(),
+                    pkce: None,
+                }),
+                Some("state".to_owned()),
+                Some("nonce".to_owned()),
+                None,
+                ResponseMode::Query,
+                false,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                grant.scope.clone(),
+            )
+            .await
+            .unwrap();
+
+        let grant = repo
+            .oauth2_authorization_grant()
+            .fulfill(&state.clock, &session, grant)
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "authorization_code",
+                "code": code,
+                "redirect_uri": grant.redirect_uri,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let AccessTokenResponse {
+            access_token,
+            id_token,
+            ..
+        } = response.json();
+
+        // The ID token is a signed JWT nested in a JWE, encrypted with the client key
+        let id_token = id_token.unwrap();
+        let jwe = Jwe::try_from(id_token.as_str()).unwrap();
+        assert_eq!(jwe.header().kid(), Some("bob"));
+        assert_eq!(jwe.header().cty(), Some("JWT"));
+        let id_token = String::from_utf8(jwe.decrypt(&decryption_key).unwrap()).unwrap();
+
+        let id_token =
+            Jwt::<HashMap<String, serde_json::Value>>::try_from(id_token.as_str()).unwrap();
+        id_token
+            .verify_with_jwks(&state.key_store.public_jwks())
+            .unwrap();
+        assert_eq!(id_token.payload()["sub"], user.sub);
+        assert_eq!(id_token.payload()["nonce"], "nonce");
+
+        // The userinfo response is encrypted with the same key
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .bearer(&access_token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let jwe = Jwe::try_from(response.body().as_str()).unwrap();
+        assert_eq!(jwe.header().cty(), None);
+        let user_info: serde_json::Value =
+            serde_json::from_slice(&jwe.decrypt(&decryption_key).unwrap()).unwrap();
+        assert_eq!(user_info["sub"], user.sub);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_refresh_token_grant(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        // Let's provision a user and create a session for them. This part is hard to
+        // test with just HTTP requests, so we'll use the repository directly.
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        // Lookup the client in the database.
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Get a token pair
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (AccessToken { access_token, .. }, RefreshToken { refresh_token, .. }) =
+            generate_token_pair(
+                &mut state.rng(),
+                &state.clock,
+                &mut repo,
+                &session,
+                Duration::microseconds(5 * 60 * 1000 * 1000),
+                None,
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        // First check that the token is valid
+        assert!(state.is_access_token_valid(&access_token).await);
+
+        // Now call the token endpoint to get an access token.
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let old_access_token = access_token;
+        let old_refresh_token = refresh_token;
+        let response: AccessTokenResponse = response.json();
+        let access_token = response.access_token;
+        let refresh_token = response.refresh_token.expect("to have a refresh token");
+
+        // Check that the new token is valid
+        assert!(state.is_access_token_valid(&access_token).await);
+
+        // Check that the old token is no longer valid
+        assert!(!state.is_access_token_valid(&old_access_token).await);
+
+        // Call it again with the old token, it should fail
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": old_refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
+
+        // Call it again with the new token, it should work
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let _: AccessTokenResponse = response.json();
+    }
+
+    /// Sign a DPoP proof for the given request with the given key
+    fn dpop_proof(
+        state: &TestState,
+        key: &PrivateKey,
+        method: &str,
+        uri: &url::Url,
+        access_token: Option<&str>,
+    ) -> String {
+        let mut rng = state.rng();
+        let alg = JsonWebSignatureAlg::Es256;
+        let signer = key.signing_key_for_alg(&alg).unwrap();
+        let jwk = PublicJsonWebKey::new(JsonWebKeyPublicParameters::from(key));
+        let header = JsonWebSignatureHeader::new(alg)
+            .with_typ("dpop+jwt".to_owned())
+            .with_jwk(jwk);
+
+        let mut claims = serde_json::json!({
+            "jti": Ulid::from_datetime_with_source(state.clock.now().into(), &mut rng).to_string(),
+            "htm": method,
+            "htu": uri.as_str(),
+            "iat": state.clock.now().timestamp(),
+        });
+
+        if let Some(access_token) = access_token {
+            let hash = sha2::Sha256::digest(access_token.as_bytes());
+            claims["ath"] = data_encoding::BASE64URL_NOPAD.encode(&hash).into();
+        }
+
+        Jwt::sign_with_rng(&mut rng, header, claims, &signer)
+            .unwrap()
+            .into_string()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_dpop_bound_tokens(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        // Provision a user and an unbound token pair
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (_, RefreshToken { refresh_token, .. }) = generate_token_pair(
+            &mut state.rng(),
+            &state.clock,
+            &mut repo,
+            &session,
+            Duration::microseconds(5 * 60 * 1000 * 1000),
+            None,
+        )
+        .await
+        .unwrap();
+
+        repo.save().await.unwrap();
+
+        let key = PrivateKey::generate_ec_p256(state.rng());
+        let other_key = PrivateKey::generate_ec_p256(state.rng());
+        let token_endpoint = state.url_builder.oauth_token_endpoint();
+        let userinfo_endpoint = state.url_builder.oidc_userinfo_endpoint();
+
+        // A proof for another endpoint is rejected
+        let proof = dpop_proof(&state, &key, "POST", &userinfo_endpoint, None);
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH)
+            .header("DPoP", proof)
+            .form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidDpopProof);
+
+        // Refreshing with a valid proof binds the new tokens to the key
+        let proof = dpop_proof(&state, &key, "POST", &token_endpoint, None);
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH)
+            .header("DPoP", proof)
+            .form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: AccessTokenResponse = response.json();
+        assert_eq!(response.token_type, OAuthAccessTokenType::DPoP);
+        let access_token = response.access_token;
+        let refresh_token = response.refresh_token.expect("to have a refresh token");
+
+        // The access token can't be used as a bearer token
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .bearer(&access_token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // It can't be used with a proof from another key either
+        let proof = dpop_proof(
+            &state,
+            &other_key,
+            "GET",
+            &userinfo_endpoint,
+            Some(&access_token),
+        );
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .header("Authorization", format!("DPoP {access_token}"))
+            .header("DPoP", proof)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // But it works with a proof from the bound key
+        let proof = dpop_proof(&state, &key, "GET", &userinfo_endpoint, Some(&access_token));
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .header("Authorization", format!("DPoP {access_token}"))
+            .header("DPoP", proof.clone())
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        // The same proof can't be used twice
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .header("Authorization", format!("DPoP {access_token}"))
+            .header("DPoP", proof)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // The bound refresh token can't be used without a proof
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidDpopProof);
+
+        // Nor with a proof from another key
+        let proof = dpop_proof(&state, &other_key, "POST", &token_endpoint, None);
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH)
+            .header("DPoP", proof)
+            .form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidDpopProof);
+
+        // It works with a proof from the bound key
+        let proof = dpop_proof(&state, &key, "POST", &token_endpoint, None);
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH)
+            .header("DPoP", proof)
+            .form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: AccessTokenResponse = response.json();
+        assert_eq!(response.token_type, OAuthAccessTokenType::DPoP);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_client_credentials(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "token_endpoint_auth_method": "client_secret_post",
+                "grant_types": ["client_credentials"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let response: ClientRegistrationResponse = response.json();
+        let client_id = response.client_id;
+        let client_secret = response.client_secret.expect("to have a client secret");
+
+        // Call the token endpoint with an empty scope
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let response: AccessTokenResponse = response.json();
+        assert!(response.refresh_token.is_none());
+        assert!(response.expires_in.is_some());
+        assert!(response.scope.is_none());
+
+        // Revoke the token
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": response.access_token,
+            "client_id": client_id,
+            "client_secret": client_secret,
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        // We should be allowed to ask for the GraphQL API scope
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "scope": "urn:mas:graphql:*"
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let response: AccessTokenResponse = response.json();
+        assert!(response.refresh_token.is_none());
+        assert!(response.expires_in.is_some());
+        assert_eq!(response.scope, Some("urn:mas:graphql:*".parse().unwrap()));
+
+        // Revoke the token
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": response.access_token,
+            "client_id": client_id,
+            "client_secret": client_secret,
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        // We should be NOT allowed to ask for the MAS admin scope
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "scope": "urn:mas:admin"
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidScope);
+
+        // Now, if we add the client to the admin list in the policy, it should work
+        let state = {
+            let mut state = state;
+            state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
+                "admin_clients": [client_id]
+            }))
+            .await
+            .unwrap();
+            state
+        };
+
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "scope": "urn:mas:admin"
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let response: AccessTokenResponse = response.json();
+        assert!(response.refresh_token.is_none());
+        assert!(response.expires_in.is_some());
+        assert_eq!(response.scope, Some("urn:mas:admin".parse().unwrap()));
+
+        // Revoke the token
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": response.access_token,
+            "client_id": client_id,
+            "client_secret": client_secret,
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_token_exchange_grant(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision the client which holds the user's token
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse {
+            client_id: subject_client_id,
+            ..
+        } = response.json();
+
+        // Provision the backend client which will exchange the token
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "token_endpoint_auth_method": "client_secret_post",
+                "grant_types": ["urn:ietf:params:oauth:grant-type:token-exchange"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let response: ClientRegistrationResponse = response.json();
+        let client_id = response.client_id;
+        let client_secret = response.client_secret.expect("to have a client secret");
+
+        // Provision a user with a session on the first client
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let subject_client = repo
+            .oauth2_client()
+            .find_by_client_id(&subject_client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &subject_client,
+                &browser_session,
+                "openid urn:matrix:org.matrix.msc2967.client:api:*"
+                    .parse()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let (AccessToken { access_token, .. }, _) = generate_token_pair(
+            &mut state.rng(),
+            &state.clock,
+            &mut repo,
+            &session,
+            Duration::microseconds(5 * 60 * 1000 * 1000),
+            None,
+        )
+        .await
+        .unwrap();
+
+        repo.save().await.unwrap();
+
+        // The client is not allowed to exchange tokens by the policy
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "scope": "urn:matrix:org.matrix.msc2967.client:api:*",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidScope);
+
+        // Allow the client to exchange tokens
+        let state = {
+            let mut state = state;
+            state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
+                "token_exchange_clients": [client_id]
+            }))
+            .await
+            .unwrap();
+            state
+        };
+
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "scope": "urn:matrix:org.matrix.msc2967.client:api:*",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let response: AccessTokenResponse = response.json();
+        assert!(response.refresh_token.is_none());
+        assert_eq!(
+            response.issued_token_type,
+            Some(TokenTypeIdentifier::AccessToken)
+        );
+        assert_eq!(
+            response.scope,
+            Some(
+                "urn:matrix:org.matrix.msc2967.client:api:*"
+                    .parse()
+                    .unwrap()
+            )
+        );
+        // The exchanged token does not outlive the subject token
+        assert!(response.expires_in.unwrap() <= Duration::try_minutes(5).unwrap());
+        assert!(state.is_access_token_valid(&response.access_token).await);
+
+        // The scope can't be broader than the subject token's
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "scope": "urn:mas:graphql:*",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidScope);
+
+        // Tokens can't be requested for another client unless the policy allows
+        // this audience for the exchanging client
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "audience": subject_client_id,
+                "scope": "urn:matrix:org.matrix.msc2967.client:api:*",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+
+        // Allow the client to request tokens for the subject client
+        let state = {
+            let mut state = state;
+            state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
+                "token_exchange_clients": [client_id],
+                "token_exchange_audiences": {
+                    client_id.clone(): [subject_client_id],
+                },
+            }))
+            .await
+            .unwrap();
+            state
+        };
+
+        // Tokens can be requested for another registered client, which the new
+        // session then belongs to
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "audience": subject_client_id,
+                "scope": "urn:matrix:org.matrix.msc2967.client:api:*",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let response: AccessTokenResponse = response.json();
+        assert!(state.is_access_token_valid(&response.access_token).await);
+
+        let mut repo = state.repository().await.unwrap();
+        let exchanged_token = repo
+            .oauth2_access_token()
+            .find_by_token(&response.access_token)
+            .await
+            .unwrap()
+            .unwrap();
+        let exchanged_session = repo
+            .oauth2_session()
+            .lookup(exchanged_token.session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(exchanged_session.client_id, subject_client.id);
+        assert_eq!(exchanged_session.user_id, Some(user.id));
+        assert_eq!(
+            exchanged_session.acting_client_id.map(|id| id.to_string()),
+            Some(client_id.clone())
+        );
+        repo.cancel().await.unwrap();
+
+        // Introspecting the token tells the audience and the client which asked
+        // for it
+        let request =
+            Request::post(mas_router::OAuth2Introspection::PATH).form(serde_json::json!({
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "token": response.access_token,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: IntrospectionResponse = response.json();
+        assert!(response.active);
+        assert_eq!(response.client_id, Some(subject_client_id.clone()));
+        assert_eq!(response.aud, Some(subject_client_id.clone()));
+        assert_eq!(
+            response.act,
+            Some(TokenActor {
+                client_id: Some(client_id.clone()),
+            })
+        );
+
+        // Unknown audiences are rejected
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "audience": "bridge",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidTarget);
+
+        // Tokens can't be requested for a resource on another server
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "resource": "https://bridge.example.com/",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidTarget);
 
-        // Now wait a bit
-        state
-            .clock
-            .advance(Duration::microseconds(15 * 60 * 1000 * 1000));
+        // Only access tokens can be exchanged
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:id_token",
+            }));
 
-        // Exchange it, it should fail
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidRequest);
+
+        // Unknown subject tokens are rejected
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "authorization_code",
-                "code": code,
-                "redirect_uri": grant.redirect_uri,
-                "client_id": client.client_id,
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": "mat_not_a_real_token",
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
             }));
 
         let response = state.request(request).await;
         response.assert_status(StatusCode::BAD_REQUEST);
+
         let ClientError { error, .. } = response.json();
-        assert_eq!(error, ClientErrorCode::InvalidGrant);
+        assert_eq!(error, ClientErrorCode::InvalidRequest);
+
+        // The subject client itself is not allowed to use this grant
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": subject_client_id,
+                "subject_token": access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::UnauthorizedClient);
+    }
+
+    fn sign_assertion(state: &TestState, claims: serde_json::Value) -> String {
+        let key = state
+            .key_store
+            .signing_key_for_algorithm(&JsonWebSignatureAlg::Rs256)
+            .unwrap();
+        let signer = key
+            .params()
+            .signing_key_for_alg(&JsonWebSignatureAlg::Rs256)
+            .unwrap();
+        let header = JsonWebSignatureHeader::new(JsonWebSignatureAlg::Rs256);
+        Jwt::sign_with_rng(&mut state.rng(), header, claims, &signer)
+            .unwrap()
+            .into_string()
     }
 
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
-    async fn test_refresh_token_grant(pool: PgPool) {
+    async fn test_jwt_bearer_grant(pool: PgPool) {
         setup();
         let state = TestState::from_pool(pool).await.unwrap();
 
-        // Provision a client
+        // Provision a client which signs its assertions with one of our keys
         let request =
             Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
                 "client_uri": "https://example.com/",
-                "redirect_uris": ["https://example.com/callback"],
                 "token_endpoint_auth_method": "none",
-                "response_types": ["code"],
-                "grant_types": ["authorization_code", "refresh_token"],
+                "jwks": state.key_store.public_jwks(),
+                "grant_types": ["urn:ietf:params:oauth:grant-type:jwt-bearer"],
             }));
 
         let response = state.request(request).await;
@@ -1020,232 +2649,200 @@ mod tests {
 
         let ClientRegistrationResponse { client_id, .. } = response.json();
 
-        // Let's provision a user and create a session for them. This part is hard to
-        // test with just HTTP requests, so we'll use the repository directly.
+        // Provision a user
         let mut repo = state.repository().await.unwrap();
-
         let user = repo
             .user()
             .add(&mut state.rng(), &state.clock, "alice".to_owned())
             .await
             .unwrap();
+        repo.save().await.unwrap();
 
-        let browser_session = repo
-            .browser_session()
-            .add(&mut state.rng(), &state.clock, &user, None)
-            .await
-            .unwrap();
+        let claims = serde_json::json!({
+            "iss": client_id,
+            "sub": "alice",
+            "aud": state.url_builder.oidc_issuer().as_str(),
+            "iat": state.clock.now().timestamp(),
+            "exp": (state.clock.now() + Duration::try_minutes(5).unwrap()).timestamp(),
+            "jti": "assertion-1",
+        });
+        let assertion = sign_assertion(&state, claims.clone());
 
-        // Lookup the client in the database.
-        let client = repo
-            .oauth2_client()
-            .find_by_client_id(&client_id)
-            .await
-            .unwrap()
-            .unwrap();
+        // The client is not trusted by the policy yet
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
+                "client_id": client_id,
+                "assertion": assertion,
+                "scope": "urn:matrix:org.matrix.msc2967.client:api:*",
+            }));
 
-        // Get a token pair
-        let session = repo
-            .oauth2_session()
-            .add_from_browser_session(
-                &mut state.rng(),
-                &state.clock,
-                &client,
-                &browser_session,
-                Scope::from_iter([OPENID]),
-            )
-            .await
-            .unwrap();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
 
-        let (AccessToken { access_token, .. }, RefreshToken { refresh_token, .. }) =
-            generate_token_pair(
-                &mut state.rng(),
-                &state.clock,
-                &mut repo,
-                &session,
-                Duration::microseconds(5 * 60 * 1000 * 1000),
-            )
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidScope);
+
+        let state = {
+            let mut state = state;
+            state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
+                "jwt_bearer_clients": [client_id]
+            }))
             .await
             .unwrap();
+            state
+        };
 
-        repo.save().await.unwrap();
-
-        // First check that the token is valid
-        assert!(state.is_access_token_valid(&access_token).await);
-
-        // Now call the token endpoint to get an access token.
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "refresh_token",
-                "refresh_token": refresh_token,
-                "client_id": client.client_id,
+                "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
+                "client_id": client_id,
+                "assertion": assertion,
+                "scope": "urn:matrix:org.matrix.msc2967.client:api:*",
             }));
 
         let response = state.request(request).await;
         response.assert_status(StatusCode::OK);
 
-        let old_access_token = access_token;
-        let old_refresh_token = refresh_token;
         let response: AccessTokenResponse = response.json();
-        let access_token = response.access_token;
-        let refresh_token = response.refresh_token.expect("to have a refresh token");
+        assert!(response.refresh_token.is_none());
+        assert!(response.expires_in.is_some());
+        assert!(state.is_access_token_valid(&response.access_token).await);
 
-        // Check that the new token is valid
-        assert!(state.is_access_token_valid(&access_token).await);
+        // The session belongs to the user, without a browser session
+        let mut repo = state.repository().await.unwrap();
+        let access_token = repo
+            .oauth2_access_token()
+            .find_by_token(&response.access_token)
+            .await
+            .unwrap()
+            .unwrap();
+        let session = repo
+            .oauth2_session()
+            .lookup(access_token.session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.user_id, Some(user.id));
+        assert_eq!(session.user_session_id, None);
+        assert_eq!(session.grant_type, Some(GrantType::JwtBearer));
 
-        // Check that the old token is no longer valid
-        assert!(!state.is_access_token_valid(&old_access_token).await);
+        // The assertion is remembered in the database, so that it can't be
+        // replayed against another instance either
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let consumed = repo
+            .oauth2_jwt_bearer_assertion()
+            .consume(
+                &state.clock,
+                &client,
+                "assertion-1",
+                state.clock.now() + Duration::try_minutes(10).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(!consumed);
+        repo.cancel().await.unwrap();
 
-        // Call it again with the old token, it should fail
+        // The same assertion can't be used twice
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "refresh_token",
-                "refresh_token": old_refresh_token,
-                "client_id": client.client_id,
+                "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
+                "client_id": client_id,
+                "assertion": assertion,
             }));
 
         let response = state.request(request).await;
         response.assert_status(StatusCode::BAD_REQUEST);
+
         let ClientError { error, .. } = response.json();
         assert_eq!(error, ClientErrorCode::InvalidGrant);
 
-        // Call it again with the new token, it should work
+        // Assertions without an identifier are rejected
+        let mut no_jti_claims = claims.clone();
+        no_jti_claims.as_object_mut().unwrap().remove("jti");
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "refresh_token",
-                "refresh_token": refresh_token,
-                "client_id": client.client_id,
-            }));
-
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
-        let _: AccessTokenResponse = response.json();
-    }
-
-    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
-    async fn test_client_credentials(pool: PgPool) {
-        setup();
-        let state = TestState::from_pool(pool).await.unwrap();
-
-        // Provision a client
-        let request =
-            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
-                "client_uri": "https://example.com/",
-                "token_endpoint_auth_method": "client_secret_post",
-                "grant_types": ["client_credentials"],
+                "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
+                "client_id": client_id,
+                "assertion": sign_assertion(&state, no_jti_claims),
             }));
 
         let response = state.request(request).await;
-        response.assert_status(StatusCode::CREATED);
+        response.assert_status(StatusCode::BAD_REQUEST);
 
-        let response: ClientRegistrationResponse = response.json();
-        let client_id = response.client_id;
-        let client_secret = response.client_secret.expect("to have a client secret");
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
 
-        // Call the token endpoint with an empty scope
+        // Assertions valid for too long are rejected
+        let mut long_lived_claims = claims.clone();
+        long_lived_claims["jti"] = "assertion-2".into();
+        long_lived_claims["exp"] = (state.clock.now() + Duration::try_days(1).unwrap())
+            .timestamp()
+            .into();
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "client_credentials",
+                "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
                 "client_id": client_id,
-                "client_secret": client_secret,
+                "assertion": sign_assertion(&state, long_lived_claims),
             }));
 
         let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
-
-        let response: AccessTokenResponse = response.json();
-        assert!(response.refresh_token.is_none());
-        assert!(response.expires_in.is_some());
-        assert!(response.scope.is_none());
-
-        // Revoke the token
-        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
-            "token": response.access_token,
-            "client_id": client_id,
-            "client_secret": client_secret,
-        }));
+        response.assert_status(StatusCode::BAD_REQUEST);
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
 
-        // We should be allowed to ask for the GraphQL API scope
+        // Assertions for unknown users are rejected
+        let mut unknown_user_claims = claims.clone();
+        unknown_user_claims["sub"] = "bob".into();
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "client_credentials",
+                "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
                 "client_id": client_id,
-                "client_secret": client_secret,
-                "scope": "urn:mas:graphql:*"
+                "assertion": sign_assertion(&state, unknown_user_claims),
             }));
 
         let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
-
-        let response: AccessTokenResponse = response.json();
-        assert!(response.refresh_token.is_none());
-        assert!(response.expires_in.is_some());
-        assert_eq!(response.scope, Some("urn:mas:graphql:*".parse().unwrap()));
-
-        // Revoke the token
-        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
-            "token": response.access_token,
-            "client_id": client_id,
-            "client_secret": client_secret,
-        }));
+        response.assert_status(StatusCode::BAD_REQUEST);
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
 
-        // We should be NOT allowed to ask for the MAS admin scope
+        // Assertions for another audience are rejected
+        let mut wrong_audience_claims = claims.clone();
+        wrong_audience_claims["aud"] = "https://example.com/".into();
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "client_credentials",
+                "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
                 "client_id": client_id,
-                "client_secret": client_secret,
-                "scope": "urn:mas:admin"
+                "assertion": sign_assertion(&state, wrong_audience_claims),
             }));
 
         let response = state.request(request).await;
-        response.assert_status(StatusCode::FORBIDDEN);
+        response.assert_status(StatusCode::BAD_REQUEST);
 
         let ClientError { error, .. } = response.json();
-        assert_eq!(error, ClientErrorCode::InvalidScope);
-
-        // Now, if we add the client to the admin list in the policy, it should work
-        let state = {
-            let mut state = state;
-            state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
-                "admin_clients": [client_id]
-            }))
-            .await
-            .unwrap();
-            state
-        };
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
 
+        // Expired assertions are rejected
+        state.clock.advance(Duration::try_minutes(10).unwrap());
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "client_credentials",
+                "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
                 "client_id": client_id,
-                "client_secret": client_secret,
-                "scope": "urn:mas:admin"
+                "assertion": assertion,
             }));
 
         let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
-
-        let response: AccessTokenResponse = response.json();
-        assert!(response.refresh_token.is_none());
-        assert!(response.expires_in.is_some());
-        assert_eq!(response.scope, Some("urn:mas:admin".parse().unwrap()));
-
-        // Revoke the token
-        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
-            "token": response.access_token,
-            "client_id": client_id,
-            "client_secret": client_secret,
-        }));
+        response.assert_status(StatusCode::BAD_REQUEST);
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
     }
 
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
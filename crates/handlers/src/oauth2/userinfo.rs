@@ -11,7 +11,8 @@ use axum::{
 };
 use hyper::StatusCode;
 use mas_axum_utils::{
-    jwt::JwtResponse,
+    http_client_factory::HttpClientFactory,
+    jwt::{JweResponse, JwtResponse},
     sentry::SentryEventID,
     user_authorization::{AuthorizationVerificationError, UserAuthorization},
 };
@@ -29,6 +30,7 @@ use serde::Serialize;
 use serde_with::skip_serializing_none;
 use thiserror::Error;
 
+use super::{ClientEncryptionKey, ResponseEncryptionError};
 use crate::{impl_from_error_for_route, BoundActivityTracker};
 
 #[skip_serializing_none]
@@ -41,11 +43,11 @@ struct UserInfo {
 }
 
 #[derive(Serialize)]
-struct SignedUserInfo {
+struct SignedUserInfo<'a> {
     iss: String,
     aud: String,
     #[serde(flatten)]
-    user_info: UserInfo,
+    user_info: &'a UserInfo,
 }
 
 #[derive(Debug, Error)]
@@ -74,6 +76,8 @@ pub enum RouteError {
 impl_from_error_for_route!(mas_storage::RepositoryError);
 impl_from_error_for_route!(mas_keystore::WrongAlgorithmError);
 impl_from_error_for_route!(mas_jose::jwt::JwtSignatureError);
+impl_from_error_for_route!(ResponseEncryptionError);
+impl_from_error_for_route!(serde_json::Error);
 
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
@@ -99,9 +103,12 @@ pub async fn get(
     activity_tracker: BoundActivityTracker,
     mut repo: BoxRepository,
     State(key_store): State<Keystore>,
+    State(http_client_factory): State<HttpClientFactory>,
     user_authorization: UserAuthorization,
 ) -> Result<Response, RouteError> {
-    let session = user_authorization.protected(&mut repo, &clock).await?;
+    let session = user_authorization
+        .protected(&mut repo, &clock, &url_builder.oidc_userinfo_endpoint())
+        .await?;
 
     // This endpoint requires the `openid` scope.
     if !session.scope.contains("openid") {
@@ -142,7 +149,11 @@ pub async fn get(
         .await?
         .ok_or(RouteError::NoSuchClient)?;
 
-    if let Some(alg) = client.userinfo_signed_response_alg {
+    // Everything is loaded: commit the transaction before the client JWKS may be
+    // fetched to encrypt the response
+    repo.save().await?;
+
+    let signed_user_info = if let Some(alg) = client.userinfo_signed_response_alg.clone() {
         let key = key_store
             .signing_key_for_algorithm(&alg)
             .ok_or(RouteError::InvalidSigningKey)?;
@@ -153,11 +164,36 @@ pub async fn get(
 
         let user_info = SignedUserInfo {
             iss: url_builder.oidc_issuer().to_string(),
-            aud: client.client_id,
-            user_info,
+            aud: client.client_id.clone(),
+            user_info: &user_info,
+        };
+
+        Some(Jwt::sign_with_rng(&mut rng, header, user_info, &signer)?)
+    } else {
+        None
+    };
+
+    // If the client asked for encrypted responses, either nest the signed JWT or
+    // encrypt the plain JSON response
+    if let Some(alg) = &client.userinfo_encrypted_response_alg {
+        let (content_type, payload) = match signed_user_info {
+            Some(token) => (Some("JWT"), token.into_string().into_bytes()),
+            None => (None, serde_json::to_vec(&user_info)?),
         };
 
-        let token = Jwt::sign_with_rng(&mut rng, header, user_info, &signer)?;
+        let key = ClientEncryptionKey::resolve(
+            &http_client_factory,
+            &client,
+            alg,
+            client.userinfo_encrypted_response_enc.as_ref(),
+        )
+        .await?;
+        let jwe = key.encrypt(&mut rng, content_type, &payload)?;
+
+        return Ok(JweResponse(jwe).into_response());
+    }
+
+    if let Some(token) = signed_user_info {
         Ok(JwtResponse(token).into_response())
     } else {
         Ok(Json(user_info).into_response())
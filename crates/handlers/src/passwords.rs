@@ -15,6 +15,8 @@ use thiserror::Error;
 use zeroize::Zeroizing;
 use zxcvbn::zxcvbn;
 
+use crate::ldap::Ldap;
+
 pub type SchemeVersion = u16;
 
 #[derive(Debug, Error)]
@@ -24,6 +26,9 @@ pub struct PasswordManagerDisabledError;
 #[derive(Clone)]
 pub struct PasswordManager {
     inner: Option<Arc<InnerPasswordManager>>,
+
+    /// An optional LDAP directory against which passwords can also be checked
+    ldap: Option<Ldap>,
 }
 
 struct InnerPasswordManager {
@@ -78,13 +83,30 @@ impl PasswordManager {
                 current_version,
                 other_hashers,
             })),
+            ldap: None,
         })
     }
 
     /// Creates a new disabled password manager
     #[must_use]
     pub const fn disabled() -> Self {
-        Self { inner: None }
+        Self {
+            inner: None,
+            ldap: None,
+        }
+    }
+
+    /// Also check passwords against the given LDAP directory
+    #[must_use]
+    pub fn with_ldap(mut self, ldap: Ldap) -> Self {
+        self.ldap = Some(ldap);
+        self
+    }
+
+    /// Get the LDAP directory against which passwords are checked, if any
+    #[must_use]
+    pub fn ldap(&self) -> Option<&Ldap> {
+        self.ldap.as_ref()
     }
 
     /// Checks if the password manager is enabled or not
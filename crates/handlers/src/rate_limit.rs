@@ -20,13 +20,16 @@ pub enum AccountRecoveryLimitedError {
     Email(String),
 }
 
-#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[derive(Debug, Clone, thiserror::Error)]
 pub enum PasswordCheckLimitedError {
     #[error("Too many password checks for requester {0}")]
     Requester(RequesterFingerprint),
 
     #[error("Too many password checks for user {0}")]
     User(Ulid),
+
+    #[error("Too many password checks for username {0}")]
+    Username(String),
 }
 
 #[derive(Debug, Clone, thiserror::Error)]
@@ -77,6 +80,7 @@ struct LimiterInner {
     account_recovery_per_email: KeyedRateLimiter<String>,
     password_check_for_requester: KeyedRateLimiter<RequesterFingerprint>,
     password_check_for_user: KeyedRateLimiter<Ulid>,
+    password_check_for_username: KeyedRateLimiter<String>,
     registration_per_requester: KeyedRateLimiter<RequesterFingerprint>,
 }
 
@@ -91,6 +95,7 @@ impl LimiterInner {
             ),
             password_check_for_requester: RateLimiter::keyed(config.login.per_ip.to_quota()?),
             password_check_for_user: RateLimiter::keyed(config.login.per_account.to_quota()?),
+            password_check_for_username: RateLimiter::keyed(config.login.per_account.to_quota()?),
             registration_per_requester: RateLimiter::keyed(config.registration.to_quota()?),
         })
     }
@@ -126,6 +131,7 @@ impl Limiter {
                 this.inner.account_recovery_per_requester.retain_recent();
                 this.inner.password_check_for_requester.retain_recent();
                 this.inner.password_check_for_user.retain_recent();
+                this.inner.password_check_for_username.retain_recent();
                 this.inner.registration_per_requester.retain_recent();
 
                 interval.tick().await;
@@ -183,6 +189,34 @@ impl Limiter {
         Ok(())
     }
 
+    /// Check if a password check can be performed for a username, when the
+    /// targeted user is not known yet, like when checking the password
+    /// against an LDAP directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the operation is rate limited
+    pub fn check_password_for_username(
+        &self,
+        key: RequesterFingerprint,
+        username: &str,
+    ) -> Result<(), PasswordCheckLimitedError> {
+        self.inner
+            .password_check_for_requester
+            .check_key(&key)
+            .map_err(|_| PasswordCheckLimitedError::Requester(key))?;
+
+        // Directories usually match usernames case-insensitively, so we don't
+        // want to allow bypassing the limit by changing the case
+        let canonical_username = username.to_lowercase();
+        self.inner
+            .password_check_for_username
+            .check_key(&canonical_username)
+            .map_err(|_| PasswordCheckLimitedError::Username(canonical_username))?;
+
+        Ok(())
+    }
+
     /// Check if an account registration can be performed
     ///
     /// # Errors
@@ -274,4 +308,53 @@ mod tests {
         // The other account isn't rate-limited
         assert!(limiter.check_password(requesters[603], &bob).is_ok());
     }
+
+    #[test]
+    fn test_password_check_for_username_limiter() {
+        let limiter = Limiter::new(&RateLimitingConfig::default()).unwrap();
+
+        let requesters: [_; 768] = (0..=255)
+            .flat_map(|a| (0..3).map(move |b| RequesterFingerprint::new([a, a, b, b].into())))
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+
+        // The requester-level limit still applies
+        assert!(limiter
+            .check_password_for_username(requesters[0], "alice")
+            .is_ok());
+        assert!(limiter
+            .check_password_for_username(requesters[0], "alice")
+            .is_ok());
+        assert!(limiter
+            .check_password_for_username(requesters[0], "alice")
+            .is_ok());
+        assert!(limiter
+            .check_password_for_username(requesters[0], "bob")
+            .is_err());
+
+        // Distribute the requests on other IPs and vary the case of the username, so
+        // that we get rate-limited on the username
+        for requester in requesters.iter().skip(1).take(599) {
+            assert!(limiter
+                .check_password_for_username(*requester, "alice")
+                .is_ok());
+            assert!(limiter
+                .check_password_for_username(*requester, "Alice")
+                .is_ok());
+            assert!(limiter
+                .check_password_for_username(*requester, "ALICE")
+                .is_ok());
+        }
+
+        // We now have consumed 3+599*3 = 1800 cells on the username
+        assert!(limiter
+            .check_password_for_username(requesters[600], "aLiCe")
+            .is_err());
+
+        // The other username isn't rate-limited
+        assert!(limiter
+            .check_password_for_username(requesters[601], "bob")
+            .is_ok());
+    }
 }
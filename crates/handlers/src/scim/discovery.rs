@@ -0,0 +1,67 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use axum::{extract::State, response::IntoResponse};
+use mas_router::UrlBuilder;
+use serde::Serialize;
+
+use super::model::{
+    location, ListResponse, Meta, Scim, MAX_RESULTS, RESOURCE_TYPE_SCHEMA,
+    SERVICE_PROVIDER_CONFIG_SCHEMA, USER_SCHEMA,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceType {
+    schemas: [&'static str; 1],
+    id: &'static str,
+    name: &'static str,
+    endpoint: &'static str,
+    schema: &'static str,
+    meta: Meta,
+}
+
+#[tracing::instrument(name = "handler.scim.service_provider_config", skip_all)]
+pub async fn service_provider_config(State(url_builder): State<UrlBuilder>) -> impl IntoResponse {
+    let location = location(&url_builder, &["ServiceProviderConfig"]);
+
+    Scim(serde_json::json!({
+        "schemas": [SERVICE_PROVIDER_CONFIG_SCHEMA],
+        "patch": { "supported": true },
+        "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+        "filter": { "supported": true, "maxResults": MAX_RESULTS },
+        "changePassword": { "supported": false },
+        "sort": { "supported": false },
+        "etag": { "supported": false },
+        "authenticationSchemes": [{
+            "type": "oauthbearertoken",
+            "name": "OAuth Bearer Token",
+            "description": "Authentication with an access token which has the urn:mas:admin scope",
+            "primary": true,
+        }],
+        "meta": {
+            "resourceType": "ServiceProviderConfig",
+            "location": location,
+        },
+    }))
+}
+
+#[tracing::instrument(name = "handler.scim.resource_types", skip_all)]
+pub async fn resource_types(State(url_builder): State<UrlBuilder>) -> impl IntoResponse {
+    let user = ResourceType {
+        schemas: [RESOURCE_TYPE_SCHEMA],
+        id: "User",
+        name: "User",
+        endpoint: "/Users",
+        schema: USER_SCHEMA,
+        meta: Meta {
+            resource_type: "ResourceType",
+            created: None,
+            location: location(&url_builder, &["ResourceTypes", "User"]),
+        },
+    };
+
+    Scim(ListResponse::new(vec![user], 1, 1))
+}
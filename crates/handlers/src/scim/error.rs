@@ -0,0 +1,128 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use axum::{
+    extract::rejection::{JsonRejection, QueryRejection},
+    response::IntoResponse,
+};
+use hyper::StatusCode;
+use serde::Serialize;
+use ulid::Ulid;
+
+use super::model::{Scim, ERROR_SCHEMA};
+use crate::{admin::call_context::Rejection, impl_from_error_for_route};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error(transparent)]
+    Homeserver(anyhow::Error),
+
+    #[error(transparent)]
+    Unauthorized(#[from] Rejection),
+
+    #[error("Invalid request body")]
+    InvalidBody(#[from] JsonRejection),
+
+    #[error("Invalid query parameters")]
+    InvalidQuery(#[from] QueryRejection),
+
+    #[error("Unsupported filter {0:?}, only `userName eq \"...\"` is supported")]
+    UnsupportedFilter(String),
+
+    #[error("User {0} not found")]
+    NotFound(Ulid),
+
+    #[error("Username is not valid")]
+    UsernameNotValid,
+
+    #[error("The userName attribute can't be changed")]
+    UsernameChanged,
+
+    #[error("User already exists")]
+    UserAlreadyExists,
+
+    #[error("Username is reserved by the homeserver")]
+    UsernameReserved,
+
+    #[error("Email address {0:?} is not valid")]
+    EmailNotValid(String),
+
+    #[error("Unsupported patch operation {0:?}")]
+    UnsupportedPatchOperation(String),
+
+    #[error("Unsupported patch path {0:?}")]
+    UnsupportedPatchPath(String),
+
+    #[error("Invalid value for the {0} attribute")]
+    InvalidPatchValue(&'static str),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl RouteError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Internal(_) | Self::Homeserver(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Unauthorized(rejection) => rejection.status_code(),
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::UserAlreadyExists | Self::UsernameReserved => StatusCode::CONFLICT,
+            Self::InvalidBody(_)
+            | Self::InvalidQuery(_)
+            | Self::UnsupportedFilter(_)
+            | Self::UsernameNotValid
+            | Self::UsernameChanged
+            | Self::EmailNotValid(_)
+            | Self::UnsupportedPatchOperation(_)
+            | Self::UnsupportedPatchPath(_)
+            | Self::InvalidPatchValue(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// The `scimType` of the error, as defined in RFC 7644, section 3.12
+    fn scim_type(&self) -> Option<&'static str> {
+        match self {
+            Self::InvalidBody(_) | Self::InvalidQuery(_) | Self::UnsupportedPatchOperation(_) => {
+                Some("invalidSyntax")
+            }
+            Self::UnsupportedFilter(_) => Some("invalidFilter"),
+            Self::UsernameNotValid | Self::EmailNotValid(_) | Self::InvalidPatchValue(_) => {
+                Some("invalidValue")
+            }
+            Self::UsernameChanged => Some("mutability"),
+            Self::UserAlreadyExists | Self::UsernameReserved => Some("uniqueness"),
+            Self::UnsupportedPatchPath(_) => Some("invalidPath"),
+            Self::Internal(_) | Self::Homeserver(_) | Self::Unauthorized(_) | Self::NotFound(_) => {
+                None
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorResponse {
+    schemas: [&'static str; 1],
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scim_type: Option<&'static str>,
+    detail: String,
+}
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status_code();
+        let response = ErrorResponse {
+            schemas: [ERROR_SCHEMA],
+            status: status.as_u16().to_string(),
+            scim_type: self.scim_type(),
+            detail: self.to_string(),
+        };
+
+        (status, Scim(response)).into_response()
+    }
+}
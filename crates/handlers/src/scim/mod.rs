@@ -0,0 +1,66 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! SCIM 2.0 provisioning API, as described in [RFC 7643] and [RFC 7644]
+//!
+//! Only the `User` resource is supported, as there is no concept of groups in
+//! the service. Requests are authorized with the same tokens as the admin API,
+//! that is access tokens with the `urn:mas:admin` scope.
+//!
+//! [RFC 7643]: https://datatracker.ietf.org/doc/html/rfc7643
+//! [RFC 7644]: https://datatracker.ietf.org/doc/html/rfc7644
+
+use std::time::Duration;
+
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    routing::get,
+    Router,
+};
+use hyper::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use mas_http::CorsLayerExt;
+use mas_matrix::BoxHomeserverConnection;
+use mas_router::UrlBuilder;
+use mas_storage::BoxRng;
+use tower_http::cors::{Any, CorsLayer};
+
+use crate::admin::call_context::{CallContext, Rejection};
+
+mod discovery;
+mod error;
+mod model;
+mod users;
+
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    BoxHomeserverConnection: FromRef<S>,
+    UrlBuilder: FromRef<S>,
+    BoxRng: FromRequestParts<S>,
+    CallContext: FromRequestParts<S, Rejection = Rejection>,
+{
+    let router = Router::new()
+        .route(
+            "/ServiceProviderConfig",
+            get(self::discovery::service_provider_config),
+        )
+        .route("/ResourceTypes", get(self::discovery::resource_types))
+        .route("/Users", get(self::users::list).post(self::users::create))
+        .route(
+            "/Users/:id",
+            get(self::users::get)
+                .put(self::users::replace)
+                .patch(self::users::patch)
+                .delete(self::users::delete),
+        );
+
+    Router::new().nest("/scim/v2", router).layer(
+        CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_otel_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE])
+            .max_age(Duration::from_secs(60 * 60)),
+    )
+}
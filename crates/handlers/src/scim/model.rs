@@ -0,0 +1,210 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hyper::header::CONTENT_TYPE;
+use mas_data_model::UserEmail;
+use mas_router::UrlBuilder;
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+use url::Url;
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const RESOURCE_TYPE_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:ResourceType";
+pub const SERVICE_PROVIDER_CONFIG_SCHEMA: &str =
+    "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+pub const LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+/// The maximum number of resources returned in a single list response
+pub const MAX_RESULTS: usize = 100;
+
+/// A JSON response, with the `application/scim+json` content type
+pub struct Scim<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Scim<T> {
+    fn into_response(self) -> Response {
+        ([(CONTENT_TYPE, "application/scim+json")], Json(self.0)).into_response()
+    }
+}
+
+/// Build the absolute URL of a resource of the SCIM API
+pub fn location(url_builder: &UrlBuilder, segments: &[&str]) -> Url {
+    let mut url = url_builder.http_base();
+    url.path_segments_mut()
+        .expect("base URL must be a valid base")
+        .pop_if_empty()
+        .extend(["scim", "v2"])
+        .extend(segments);
+    url
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Meta {
+    pub resource_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    pub location: Url,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListResponse<T> {
+    schemas: [&'static str; 1],
+    total_results: usize,
+    start_index: usize,
+    items_per_page: usize,
+    #[serde(rename = "Resources")]
+    resources: Vec<T>,
+}
+
+impl<T> ListResponse<T> {
+    pub fn new(resources: Vec<T>, total_results: usize, start_index: usize) -> Self {
+        Self {
+            schemas: [LIST_RESPONSE_SCHEMA],
+            total_results,
+            start_index,
+            items_per_page: resources.len(),
+            resources,
+        }
+    }
+}
+
+/// An email address of a user
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Email {
+    pub value: String,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+/// A SCIM `User` resource
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    schemas: [&'static str; 1],
+    id: Ulid,
+    user_name: String,
+    active: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    emails: Vec<Email>,
+    meta: Meta,
+}
+
+impl User {
+    /// Build the resource from a user and its email addresses
+    ///
+    /// Only the verified email addresses are listed, starting with the primary
+    /// one.
+    pub fn new(
+        user: mas_data_model::User,
+        emails: Vec<UserEmail>,
+        url_builder: &UrlBuilder,
+    ) -> Self {
+        let mut emails: Vec<Email> = emails
+            .into_iter()
+            .filter(|email| email.confirmed_at.is_some())
+            .map(|email| Email {
+                primary: user.primary_user_email_id == Some(email.id),
+                value: email.email,
+            })
+            .collect();
+        emails.sort_by_key(|email| !email.primary);
+
+        Self {
+            schemas: [USER_SCHEMA],
+            id: user.id,
+            active: user.is_valid(),
+            emails,
+            meta: Meta {
+                resource_type: "User",
+                created: Some(user.created_at),
+                location: location(url_builder, &["Users", &user.id.to_string()]),
+            },
+            user_name: user.username,
+        }
+    }
+
+    pub fn location(&self) -> &Url {
+        &self.meta.location
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Name {
+    #[serde(default)]
+    formatted: Option<String>,
+    #[serde(default)]
+    given_name: Option<String>,
+    #[serde(default)]
+    family_name: Option<String>,
+}
+
+impl Name {
+    /// The display name built from the name components, if any
+    fn display_name(&self) -> Option<String> {
+        if let Some(formatted) = &self.formatted {
+            return Some(formatted.clone());
+        }
+
+        let parts: Vec<&str> = [self.given_name.as_deref(), self.family_name.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(" "))
+        }
+    }
+}
+
+/// The body of the `POST /Users` and `PUT /Users/:id` requests
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserRequest {
+    pub user_name: String,
+    #[serde(default)]
+    display_name: Option<String>,
+    #[serde(default)]
+    name: Option<Name>,
+    #[serde(default)]
+    pub emails: Option<Vec<Email>>,
+    #[serde(default)]
+    pub active: Option<bool>,
+}
+
+impl UserRequest {
+    /// The display name to set on the user, taken from the `displayName`
+    /// attribute, or else from the `name` attribute
+    pub fn display_name(&self) -> Option<String> {
+        self.display_name
+            .clone()
+            .or_else(|| self.name.as_ref().and_then(Name::display_name))
+    }
+}
+
+/// The body of the `PATCH /Users/:id` request
+#[derive(Deserialize)]
+pub struct PatchRequest {
+    #[serde(rename = "Operations")]
+    pub operations: Vec<PatchOperation>,
+}
+
+#[derive(Deserialize)]
+pub struct PatchOperation {
+    pub op: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+}
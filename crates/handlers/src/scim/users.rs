@@ -0,0 +1,978 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeSet;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Path, Query, State,
+    },
+    response::IntoResponse,
+    Json,
+};
+use hyper::{header::LOCATION, StatusCode};
+use mas_data_model::{AuditContext, AuditEventKind, WebhookEventKind};
+use mas_matrix::BoxHomeserverConnection;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{DeactivateUserJob, DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob},
+    user::UserFilter,
+    BoxClock, BoxRepository, BoxRng, Pagination,
+};
+use serde::Deserialize;
+use tracing::info;
+use ulid::Ulid;
+
+use super::{
+    error::RouteError,
+    model::{
+        Email, ListResponse, PatchOperation, PatchRequest, Scim, User, UserRequest, MAX_RESULTS,
+    },
+};
+use crate::{
+    admin::call_context::{CallContext, Rejection},
+    username_valid,
+};
+
+/// Parse a SCIM filter, returning the username to look for.
+///
+/// Provisioning clients only use filters to check whether a user already
+/// exists, so we only support the `userName eq "..."` filter.
+fn parse_filter(filter: &str) -> Option<&str> {
+    let mut parts = filter.trim().splitn(3, ' ');
+    let attribute = parts.next()?;
+    let operator = parts.next()?;
+    let value = parts.next()?.trim();
+
+    if !attribute.eq_ignore_ascii_case("userName") || !operator.eq_ignore_ascii_case("eq") {
+        return None;
+    }
+
+    value.strip_prefix('"')?.strip_suffix('"')
+}
+
+/// Parse a boolean value, which some clients send as a string
+fn parse_bool(value: &serde_json::Value) -> Option<bool> {
+    match value {
+        serde_json::Value::Bool(value) => Some(*value),
+        serde_json::Value::String(value) if value.eq_ignore_ascii_case("true") => Some(true),
+        serde_json::Value::String(value) if value.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+/// Changes to apply to a user
+#[derive(Default)]
+struct UserChanges {
+    display_name: Option<String>,
+    emails: Option<Vec<Email>>,
+    active: Option<bool>,
+}
+
+impl UserChanges {
+    /// Apply a single operation of a `PATCH` request
+    ///
+    /// Operations on the email addresses apply to the `current_emails` of the
+    /// user.
+    fn apply_patch(
+        &mut self,
+        user: &mas_data_model::User,
+        current_emails: &[Email],
+        operation: PatchOperation,
+    ) -> Result<(), RouteError> {
+        let op = operation.op.to_ascii_lowercase();
+        match (op.as_str(), operation.path, operation.value) {
+            // Without a path, the value is an object with the attributes to set
+            ("add" | "replace", None, Some(serde_json::Value::Object(attributes))) => {
+                for (attribute, value) in attributes {
+                    self.apply_attribute(user, current_emails, &op, &attribute, value)?;
+                }
+                Ok(())
+            }
+
+            ("add" | "replace", Some(path), Some(value)) => {
+                self.apply_attribute(user, current_emails, &op, &path, value)
+            }
+
+            ("remove", Some(path), _) if path.eq_ignore_ascii_case("emails") => {
+                self.emails = Some(Vec::new());
+                Ok(())
+            }
+
+            ("remove", Some(path), _) => Err(RouteError::UnsupportedPatchPath(path)),
+
+            (_, _, _) => Err(RouteError::UnsupportedPatchOperation(operation.op)),
+        }
+    }
+
+    fn apply_attribute(
+        &mut self,
+        user: &mas_data_model::User,
+        current_emails: &[Email],
+        op: &str,
+        path: &str,
+        value: serde_json::Value,
+    ) -> Result<(), RouteError> {
+        let lowercase_path = path.to_ascii_lowercase();
+        match lowercase_path.as_str() {
+            "active" => {
+                let active = parse_bool(&value).ok_or(RouteError::InvalidPatchValue("active"))?;
+                self.active = Some(active);
+            }
+
+            "displayname" | "name.formatted" => {
+                let display_name = value
+                    .as_str()
+                    .ok_or(RouteError::InvalidPatchValue("displayName"))?;
+                self.display_name = Some(display_name.to_owned());
+            }
+
+            "username" => {
+                if value.as_str() != Some(user.username.as_str()) {
+                    return Err(RouteError::UsernameChanged);
+                }
+            }
+
+            "emails" => {
+                let emails: Vec<Email> = serde_json::from_value(value)
+                    .map_err(|_| RouteError::InvalidPatchValue("emails"))?;
+                let current = self.emails.get_or_insert_with(|| current_emails.to_vec());
+                if op == "add" {
+                    current.extend(emails);
+                } else {
+                    *current = emails;
+                }
+            }
+
+            // Some clients set the primary email through a filtered path, like
+            // `emails[type eq "work"].value`
+            path if path.starts_with("emails[") && path.ends_with("].value") => {
+                let value = value
+                    .as_str()
+                    .ok_or(RouteError::InvalidPatchValue("emails"))?;
+                let current = self.emails.get_or_insert_with(|| current_emails.to_vec());
+                for email in current.iter_mut() {
+                    email.primary = false;
+                }
+                current.retain(|email| email.value != value);
+                current.insert(
+                    0,
+                    Email {
+                        value: value.to_owned(),
+                        primary: true,
+                    },
+                );
+            }
+
+            // Those attributes are not stored, but are commonly sent by clients
+            "externalid" | "name.givenname" | "name.familyname" => {}
+
+            _ => return Err(RouteError::UnsupportedPatchPath(path.to_owned())),
+        }
+
+        Ok(())
+    }
+}
+
+/// Replace the verified email addresses of a user with the given ones.
+///
+/// Addresses coming from the provisioning client are trusted, so they are
+/// marked as verified. Addresses which are still pending verification are left
+/// untouched, unless they are part of the list.
+async fn sync_emails(
+    repo: &mut BoxRepository,
+    rng: &mut BoxRng,
+    clock: &BoxClock,
+    audit: &AuditContext,
+    user: &mas_data_model::User,
+    emails: Vec<Email>,
+) -> Result<(), RouteError> {
+    // Validate everything before doing any change
+    for email in &emails {
+        if email.value.parse::<lettre::Address>().is_err() {
+            return Err(RouteError::EmailNotValid(email.value.clone()));
+        }
+    }
+
+    let primary = emails
+        .iter()
+        .find(|email| email.primary)
+        .or(emails.first())
+        .map(|email| email.value.clone());
+
+    let existing = repo.user_email().all(user).await?;
+    let mut seen = BTreeSet::new();
+    let mut primary_email = None;
+
+    for email in emails {
+        if !seen.insert(email.value.clone()) {
+            continue;
+        }
+
+        let user_email = match existing.iter().find(|e| e.email == email.value) {
+            Some(user_email) if user_email.confirmed_at.is_some() => user_email.clone(),
+
+            Some(user_email) => {
+                let user_email = repo
+                    .user_email()
+                    .mark_as_verified(clock, user_email.clone())
+                    .await?;
+
+                repo.job()
+                    .schedule_job(
+                        DispatchWebhookEventJob::new(WebhookEventKind::UserEmailVerified, user)
+                            .with_data(serde_json::json!({ "email": user_email.email })),
+                    )
+                    .await?;
+
+                user_email
+            }
+
+            None => {
+                let user_email = repo.user_email().add(rng, clock, user, email.value).await?;
+                let user_email = repo
+                    .user_email()
+                    .mark_as_verified(clock, user_email)
+                    .await?;
+
+                repo.audit_event()
+                    .add(
+                        rng,
+                        clock,
+                        audit,
+                        AuditEventKind::UserEmailAdded,
+                        Some(user),
+                        serde_json::json!({ "email": user_email.email }),
+                    )
+                    .await?;
+
+                repo.job()
+                    .schedule_job(
+                        DispatchWebhookEventJob::new(WebhookEventKind::UserEmailVerified, user)
+                            .with_data(serde_json::json!({ "email": user_email.email })),
+                    )
+                    .await?;
+
+                user_email
+            }
+        };
+
+        if primary.as_deref() == Some(user_email.email.as_str()) {
+            primary_email = Some(user_email);
+        }
+    }
+
+    // Remove the verified addresses which are not in the list anymore
+    for user_email in existing {
+        if user_email.confirmed_at.is_none() || seen.contains(&user_email.email) {
+            continue;
+        }
+
+        let email = user_email.email.clone();
+        repo.user_email().remove(user_email).await?;
+
+        repo.audit_event()
+            .add(
+                rng,
+                clock,
+                audit,
+                AuditEventKind::UserEmailRemoved,
+                Some(user),
+                serde_json::json!({ "email": email }),
+            )
+            .await?;
+    }
+
+    if let Some(primary_email) = primary_email {
+        if user.primary_user_email_id != Some(primary_email.id) {
+            repo.user_email().set_as_primary(&primary_email).await?;
+
+            repo.audit_event()
+                .add(
+                    rng,
+                    clock,
+                    audit,
+                    AuditEventKind::UserPrimaryEmailChanged,
+                    Some(user),
+                    serde_json::json!({ "email": primary_email.email }),
+                )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply the changes to a user, and schedule a job to provision it on the
+/// homeserver if needed.
+///
+/// Setting `active` to `false` locks the user, and setting it back to `true`
+/// unlocks and reactivates it.
+#[allow(clippy::too_many_arguments)]
+async fn apply_changes(
+    repo: &mut BoxRepository,
+    rng: &mut BoxRng,
+    clock: &BoxClock,
+    audit: &AuditContext,
+    homeserver: &BoxHomeserverConnection,
+    mut user: mas_data_model::User,
+    changes: UserChanges,
+    mut provision: bool,
+) -> Result<mas_data_model::User, RouteError> {
+    let mut job = ProvisionUserJob::new(&user);
+
+    if let Some(display_name) = changes.display_name {
+        job = job.set_display_name(display_name);
+        provision = true;
+    }
+
+    if let Some(emails) = changes.emails {
+        sync_emails(repo, rng, clock, audit, &user, emails).await?;
+        provision = true;
+
+        // The primary email address might have changed
+        user = repo
+            .user()
+            .lookup(user.id)
+            .await?
+            .ok_or(RouteError::NotFound(user.id))?;
+    }
+
+    match changes.active {
+        Some(false) if user.locked_at.is_none() => {
+            user = repo.user().lock(clock, user).await?;
+
+            repo.audit_event()
+                .add(
+                    rng,
+                    clock,
+                    audit,
+                    AuditEventKind::UserLocked,
+                    Some(&user),
+                    serde_json::json!({}),
+                )
+                .await?;
+
+            repo.job()
+                .schedule_job(DispatchWebhookEventJob::new(
+                    WebhookEventKind::UserLocked,
+                    &user,
+                ))
+                .await?;
+
+            info!(user.id = %user.id, "Locked user");
+        }
+
+        Some(true) if user.locked_at.is_some() => {
+            // Call the homeserver synchronously to reactivate the user, in case it was
+            // deactivated
+            let mxid = homeserver.mxid(&user.username);
+            homeserver
+                .reactivate_user(&mxid)
+                .await
+                .map_err(RouteError::Homeserver)?;
+
+            user = repo.user().unlock(user).await?;
+
+            repo.audit_event()
+                .add(
+                    rng,
+                    clock,
+                    audit,
+                    AuditEventKind::UserUnlocked,
+                    Some(&user),
+                    serde_json::json!({}),
+                )
+                .await?;
+
+            repo.job()
+                .schedule_job(DispatchWebhookEventJob::new(
+                    WebhookEventKind::UserUnlocked,
+                    &user,
+                ))
+                .await?;
+
+            info!(user.id = %user.id, "Unlocked user");
+        }
+
+        _ => {}
+    }
+
+    if provision {
+        repo.job().schedule_job(job).await?;
+    }
+
+    Ok(user)
+}
+
+/// Render a user, with its email addresses
+async fn render(
+    repo: &mut BoxRepository,
+    url_builder: &UrlBuilder,
+    user: mas_data_model::User,
+) -> Result<User, RouteError> {
+    let emails = repo.user_email().all(&user).await?;
+    Ok(User::new(user, emails, url_builder))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListParams {
+    filter: Option<String>,
+    start_index: Option<usize>,
+    count: Option<usize>,
+}
+
+#[tracing::instrument(name = "handler.scim.users.list", skip_all, err)]
+pub async fn list(
+    call_context: Result<CallContext, Rejection>,
+    State(url_builder): State<UrlBuilder>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> Result<impl IntoResponse, RouteError> {
+    let CallContext { mut repo, .. } = call_context?;
+    let Query(params) = params?;
+
+    let start_index = params.start_index.unwrap_or(1).max(1);
+    let count = params.count.unwrap_or(MAX_RESULTS).min(MAX_RESULTS);
+
+    let (users, total_results): (Vec<_>, usize) = if let Some(filter) = &params.filter {
+        let username =
+            parse_filter(filter).ok_or_else(|| RouteError::UnsupportedFilter(filter.clone()))?;
+
+        let users: Vec<_> = repo
+            .user()
+            .find_by_username(username)
+            .await?
+            .into_iter()
+            .collect();
+        let total_results = users.len();
+        let users = users
+            .into_iter()
+            .skip(start_index - 1)
+            .take(count)
+            .collect();
+
+        (users, total_results)
+    } else {
+        let filter = UserFilter::new();
+        let total_results = repo.user().count(filter).await?;
+
+        // Past the end of the list, the page is empty
+        if start_index > total_results {
+            return Ok(Scim(ListResponse::new(
+                Vec::new(),
+                total_results,
+                start_index,
+            )));
+        }
+
+        // SCIM paginates with an index, so we load everything up to the end of the
+        // requested page and skip what comes before it
+        let page = repo
+            .user()
+            .list(
+                filter,
+                Pagination::first((start_index - 1).saturating_add(count)),
+            )
+            .await?;
+        let users = page
+            .edges
+            .into_iter()
+            .skip(start_index - 1)
+            .take(count)
+            .collect();
+
+        (users, total_results)
+    };
+
+    let mut resources = Vec::with_capacity(count);
+    for user in users {
+        resources.push(render(&mut repo, &url_builder, user).await?);
+    }
+
+    Ok(Scim(ListResponse::new(
+        resources,
+        total_results,
+        start_index,
+    )))
+}
+
+#[tracing::instrument(name = "handler.scim.users.get", skip_all, fields(user.id = %id), err)]
+pub async fn get(
+    call_context: Result<CallContext, Rejection>,
+    State(url_builder): State<UrlBuilder>,
+    Path(id): Path<Ulid>,
+) -> Result<impl IntoResponse, RouteError> {
+    let CallContext { mut repo, .. } = call_context?;
+
+    let user = repo
+        .user()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    Ok(Scim(render(&mut repo, &url_builder, user).await?))
+}
+
+#[tracing::instrument(name = "handler.scim.users.create", skip_all, err)]
+pub async fn create(
+    call_context: Result<CallContext, Rejection>,
+    mut rng: BoxRng,
+    State(homeserver): State<BoxHomeserverConnection>,
+    State(url_builder): State<UrlBuilder>,
+    body: Result<Json<UserRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, RouteError> {
+    let CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    } = call_context?;
+    let Json(body) = body?;
+
+    if repo.user().exists(&body.user_name).await? {
+        return Err(RouteError::UserAlreadyExists);
+    }
+
+    // Do some basic check on the username
+    if !username_valid(&body.user_name) {
+        return Err(RouteError::UsernameNotValid);
+    }
+
+    // Ask the homeserver if the username is available
+    let homeserver_available = homeserver
+        .is_localpart_available(&body.user_name)
+        .await
+        .map_err(RouteError::Homeserver)?;
+
+    if !homeserver_available {
+        return Err(RouteError::UsernameReserved);
+    }
+
+    let changes = UserChanges {
+        display_name: body.display_name(),
+        emails: body.emails,
+        active: body.active,
+    };
+
+    let user = repo.user().add(&mut rng, &clock, body.user_name).await?;
+
+    repo.job()
+        .schedule_job(
+            DispatchWebhookEventJob::new(WebhookEventKind::UserRegistered, &user)
+                .with_data(serde_json::json!({ "method": "scim" })),
+        )
+        .await?;
+
+    let user = apply_changes(
+        &mut repo,
+        &mut rng,
+        &clock,
+        &audit,
+        &homeserver,
+        user,
+        changes,
+        true,
+    )
+    .await?;
+
+    info!(user.id = %user.id, "Provisioned user through SCIM");
+
+    let resource = render(&mut repo, &url_builder, user).await?;
+
+    repo.save().await?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, resource.location().to_string())],
+        Scim(resource),
+    ))
+}
+
+#[tracing::instrument(name = "handler.scim.users.replace", skip_all, fields(user.id = %id), err)]
+pub async fn replace(
+    call_context: Result<CallContext, Rejection>,
+    mut rng: BoxRng,
+    State(homeserver): State<BoxHomeserverConnection>,
+    State(url_builder): State<UrlBuilder>,
+    Path(id): Path<Ulid>,
+    body: Result<Json<UserRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, RouteError> {
+    let CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    } = call_context?;
+    let Json(body) = body?;
+
+    let user = repo
+        .user()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    // The username is used in the Matrix ID, so it can't change
+    if body.user_name != user.username {
+        return Err(RouteError::UsernameChanged);
+    }
+
+    let changes = UserChanges {
+        display_name: body.display_name(),
+        emails: body.emails,
+        active: body.active,
+    };
+
+    let user = apply_changes(
+        &mut repo,
+        &mut rng,
+        &clock,
+        &audit,
+        &homeserver,
+        user,
+        changes,
+        false,
+    )
+    .await?;
+
+    let resource = render(&mut repo, &url_builder, user).await?;
+
+    repo.save().await?;
+
+    Ok(Scim(resource))
+}
+
+#[tracing::instrument(name = "handler.scim.users.patch", skip_all, fields(user.id = %id), err)]
+pub async fn patch(
+    call_context: Result<CallContext, Rejection>,
+    mut rng: BoxRng,
+    State(homeserver): State<BoxHomeserverConnection>,
+    State(url_builder): State<UrlBuilder>,
+    Path(id): Path<Ulid>,
+    body: Result<Json<PatchRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, RouteError> {
+    let CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    } = call_context?;
+    let Json(body) = body?;
+
+    let user = repo
+        .user()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    let current_emails: Vec<Email> = repo
+        .user_email()
+        .all(&user)
+        .await?
+        .into_iter()
+        .filter(|email| email.confirmed_at.is_some())
+        .map(|email| Email {
+            primary: user.primary_user_email_id == Some(email.id),
+            value: email.email,
+        })
+        .collect();
+
+    let mut changes = UserChanges::default();
+    for operation in body.operations {
+        changes.apply_patch(&user, &current_emails, operation)?;
+    }
+
+    let user = apply_changes(
+        &mut repo,
+        &mut rng,
+        &clock,
+        &audit,
+        &homeserver,
+        user,
+        changes,
+        false,
+    )
+    .await?;
+
+    let resource = render(&mut repo, &url_builder, user).await?;
+
+    repo.save().await?;
+
+    Ok(Scim(resource))
+}
+
+#[tracing::instrument(name = "handler.scim.users.delete", skip_all, fields(user.id = %id), err)]
+pub async fn delete(
+    call_context: Result<CallContext, Rejection>,
+    mut rng: BoxRng,
+    Path(id): Path<Ulid>,
+) -> Result<impl IntoResponse, RouteError> {
+    let CallContext {
+        mut repo,
+        clock,
+        audit,
+        ..
+    } = call_context?;
+
+    let mut user = repo
+        .user()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::NotFound(id))?;
+
+    if user.locked_at.is_none() {
+        user = repo.user().lock(&clock, user).await?;
+    }
+
+    info!("Scheduling deactivation of user {}", user.id);
+    repo.job()
+        .schedule_job(DeactivateUserJob::new(&user, true))
+        .await?;
+
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &audit,
+            AuditEventKind::UserDeactivated,
+            Some(&user),
+            serde_json::json!({}),
+        )
+        .await?;
+
+    repo.save().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{
+        header::{CONTENT_TYPE, LOCATION},
+        Request, StatusCode,
+    };
+    use mas_matrix::{HomeserverConnection, ProvisionRequest};
+    use mas_storage::{user::UserRepository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{setup, RequestBuilderExt, ResponseExt, TestState};
+
+    fn scim_json(response: &hyper::Response<String>) -> serde_json::Value {
+        response.assert_header_value(CONTENT_TYPE, "application/scim+json");
+        serde_json::from_str(response.body()).unwrap()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_provisioning(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+
+        // Create a user
+        let request = Request::post("/scim/v2/Users")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+                "userName": "alice",
+                "name": { "givenName": "Alice", "familyName": "Liddell" },
+                "emails": [
+                    { "value": "alice@example.com", "type": "work", "primary": true },
+                    { "value": "alice@example.org" },
+                ],
+                "active": true,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let body = scim_json(&response);
+        let id = body["id"].as_str().unwrap().to_owned();
+        assert_eq!(body["userName"], "alice");
+        assert_eq!(body["active"], true);
+        assert_eq!(body["emails"][0]["value"], "alice@example.com");
+        assert_eq!(body["emails"][0]["primary"], true);
+        assert_eq!(body["emails"][1]["value"], "alice@example.org");
+        assert_eq!(body["emails"][1]["primary"], false);
+        assert_eq!(body["meta"]["resourceType"], "User");
+        assert_eq!(
+            response.headers().get(LOCATION).unwrap(),
+            body["meta"]["location"].as_str().unwrap()
+        );
+
+        // The same username can't be provisioned twice
+        let request = Request::post("/scim/v2/Users")
+            .bearer(&token)
+            .json(serde_json::json!({
+                "userName": "alice",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CONFLICT);
+        let body = scim_json(&response);
+        assert_eq!(body["scimType"], "uniqueness");
+        assert_eq!(body["status"], "409");
+
+        // Find the user by its username
+        let request = Request::get("/scim/v2/Users?filter=userName%20eq%20%22alice%22")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body = scim_json(&response);
+        assert_eq!(body["totalResults"], 1);
+        assert_eq!(body["Resources"][0]["id"], id);
+
+        let request = Request::get("/scim/v2/Users?filter=userName%20eq%20%22bob%22")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body = scim_json(&response);
+        assert_eq!(body["totalResults"], 0);
+
+        // Other filters are not supported
+        let request = Request::get("/scim/v2/Users?filter=title%20pr")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body = scim_json(&response);
+        assert_eq!(body["scimType"], "invalidFilter");
+
+        // Deactivate the user with a PATCH request, as sent by some clients
+        let request = Request::patch(format!("/scim/v2/Users/{id}"))
+            .bearer(&token)
+            .json(serde_json::json!({
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+                "Operations": [
+                    { "op": "Replace", "path": "active", "value": "False" },
+                    { "op": "Replace", "path": "emails[type eq \"work\"].value", "value": "alice@example.org" },
+                ],
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body = scim_json(&response);
+        assert_eq!(body["active"], false);
+        assert_eq!(body["emails"][0]["value"], "alice@example.org");
+        assert_eq!(body["emails"][0]["primary"], true);
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .find_by_username("alice")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(user.locked_at.is_some());
+        repo.save().await.unwrap();
+
+        // Provision the user on the homeserver, so that it can be reactivated
+        let mxid = state.homeserver_connection.mxid(&user.username);
+        state
+            .homeserver_connection
+            .provision_user(&ProvisionRequest::new(&mxid, &user.sub))
+            .await
+            .unwrap();
+
+        // Reactivate it, and replace its email addresses
+        let request = Request::put(format!("/scim/v2/Users/{id}"))
+            .bearer(&token)
+            .json(serde_json::json!({
+                "userName": "alice",
+                "displayName": "Alice",
+                "emails": [{ "value": "alice@example.net", "primary": true }],
+                "active": true,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body = scim_json(&response);
+        assert_eq!(body["active"], true);
+        assert_eq!(body["emails"].as_array().unwrap().len(), 1);
+        assert_eq!(body["emails"][0]["value"], "alice@example.net");
+
+        // The username can't change
+        let request = Request::put(format!("/scim/v2/Users/{id}"))
+            .bearer(&token)
+            .json(serde_json::json!({ "userName": "bob" }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body = scim_json(&response);
+        assert_eq!(body["scimType"], "mutability");
+
+        // Delete the user, which deactivates it
+        let request = Request::delete(format!("/scim/v2/Users/{id}"))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NO_CONTENT);
+
+        let request = Request::get(format!("/scim/v2/Users/{id}"))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body = scim_json(&response);
+        assert_eq!(body["active"], false);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_list(pool: PgPool) {
+        setup();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let token = state.token_with_scope("urn:mas:admin").await;
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        for username in ["alice", "bob", "charlie"] {
+            repo.user()
+                .add(&mut rng, &state.clock, username.to_owned())
+                .await
+                .unwrap();
+        }
+        repo.save().await.unwrap();
+
+        let request = Request::get("/scim/v2/Users?startIndex=2&count=1")
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body = scim_json(&response);
+        assert_eq!(body["totalResults"], 3);
+        assert_eq!(body["startIndex"], 2);
+        assert_eq!(body["itemsPerPage"], 1);
+        assert_eq!(body["Resources"].as_array().unwrap().len(), 1);
+
+        // Pages past the end of the list are empty
+        let request = Request::get(format!("/scim/v2/Users?startIndex={}", usize::MAX))
+            .bearer(&token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body = scim_json(&response);
+        assert_eq!(body["totalResults"], 3);
+        assert_eq!(body["itemsPerPage"], 0);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_unauthorized(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let request = Request::get("/scim/v2/Users").bearer("invalid").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+        let body = scim_json(&response);
+        assert_eq!(
+            body["schemas"][0],
+            "urn:ietf:params:scim:api:messages:2.0:Error"
+        );
+        assert_eq!(body["status"], "401");
+
+        // The discovery endpoints don't need authentication
+        let request = Request::get("/scim/v2/ServiceProviderConfig").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body = scim_json(&response);
+        assert_eq!(body["patch"]["supported"], true);
+    }
+}
@@ -5,6 +5,7 @@
 // Please see LICENSE in the repository root for full details.
 
 use std::{
+    collections::BTreeSet,
     convert::Infallible,
     sync::{Arc, Mutex, RwLock},
     task::{Context, Poll},
@@ -30,19 +31,24 @@ use mas_axum_utils::{
     ErrorWrapper,
 };
 use mas_config::RateLimitingConfig;
-use mas_data_model::SiteConfig;
+use mas_data_model::{SiteConfig, User, UserPasskey};
 use mas_i18n::Translator;
 use mas_keystore::{Encrypter, JsonWebKey, JsonWebKeySet, Keystore, PrivateKey};
 use mas_matrix::{BoxHomeserverConnection, HomeserverConnection, MockHomeserverConnection};
 use mas_policy::{InstantiateError, Policy, PolicyFactory};
 use mas_router::{SimpleRoute, UrlBuilder};
-use mas_storage::{clock::MockClock, BoxClock, BoxRepository, BoxRng};
+use mas_storage::{
+    clock::MockClock, user::UserPasskeyRepository, BoxClock, BoxRepository, BoxRng,
+    RepositoryAccess,
+};
 use mas_storage_pg::{DatabaseError, PgRepository};
 use mas_templates::{SiteConfigExt, Templates};
 use oauth2_types::{registration::ClientRegistrationResponse, requests::AccessTokenResponse};
-use rand::SeedableRng;
+use p256::ecdsa::{signature::Signer, DerSignature, SigningKey};
+use rand::{CryptoRng, RngCore, SeedableRng};
 use rand_chacha::ChaChaRng;
 use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use tower::{Layer, Service, ServiceExt};
 use url::Url;
@@ -51,7 +57,7 @@ use crate::{
     graphql,
     passwords::{Hasher, PasswordManager},
     upstream_oauth2::cache::MetadataCache,
-    ActivityTracker, BoundActivityTracker, Limiter, RequesterFingerprint,
+    webauthn, ActivityTracker, BoundActivityTracker, Limiter, RequesterFingerprint,
 };
 
 /// Setup rustcrypto and tracing for tests.
@@ -78,6 +84,9 @@ pub(crate) async fn policy_factory(
         register: "register/violation".to_owned(),
         client_registration: "client_registration/violation".to_owned(),
         authorization_grant: "authorization_grant/violation".to_owned(),
+        authorization_request: "authorization_request/violation".to_owned(),
+        token_exchange: "token_exchange/violation".to_owned(),
+        jwt_bearer: "jwt_bearer/violation".to_owned(),
         email: "email/violation".to_owned(),
     };
 
@@ -131,6 +140,8 @@ pub fn test_site_config() -> SiteConfig {
         account_recovery_allowed: true,
         captcha: None,
         minimum_password_complexity: 1,
+        upstream_oauth2_admin_api_allowed: true,
+        upstream_oauth2_config_provider_ids: BTreeSet::new(),
     }
 }
 
@@ -246,6 +257,7 @@ impl TestState {
             // with it
             .merge(crate::graphql_router(false, true))
             .merge(crate::admin_api_router().1)
+            .merge(crate::scim_router())
             .with_state(self.clone())
             .into_service();
 
@@ -791,3 +803,201 @@ where
         })
     }
 }
+
+/// A software `WebAuthn` authenticator holding a single ES256 passkey, used to
+/// answer the registration and authentication ceremonies in tests
+pub struct SoftPasskey {
+    key: SigningKey,
+    credential_id: Vec<u8>,
+    user_handle: Vec<u8>,
+}
+
+impl SoftPasskey {
+    pub fn new(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let mut credential_id = vec![0; 16];
+        rng.fill_bytes(&mut credential_id);
+        Self {
+            key: SigningKey::random(rng),
+            credential_id,
+            user_handle: Vec::new(),
+        }
+    }
+
+    /// Use the given credential ID instead of a random one
+    pub fn with_credential_id(mut self, credential_id: &[u8]) -> Self {
+        self.credential_id = credential_id.to_vec();
+        self
+    }
+
+    /// Override the user handle returned when authenticating
+    pub fn set_user_handle(&mut self, user_handle: &[u8]) {
+        self.user_handle = user_handle.to_vec();
+    }
+
+    /// Extract the `WebAuthn` options the server rendered in a
+    /// `data-options` attribute of the page
+    pub fn options_from_page(body: &str) -> serde_json::Value {
+        let options = body
+            .split("data-options=\"")
+            .nth(1)
+            .expect("No WebAuthn options in the page")
+            .split('"')
+            .next()
+            .unwrap()
+            .replace("&quot;", "\"")
+            .replace("&#x27;", "'")
+            .replace("&#x2f;", "/")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&");
+        serde_json::from_str(&options).expect("Invalid WebAuthn options")
+    }
+
+    /// Create the credential for the given creation options, like
+    /// `navigator.credentials.create()` would
+    pub fn register(&mut self, options: &serde_json::Value) -> String {
+        let options = &options["publicKey"];
+        let user_handle = options["user"]["id"].as_str().unwrap();
+        self.user_handle = decode_base64url(user_handle);
+
+        let client_data = client_data("webauthn.create", options["challenge"].as_str().unwrap());
+
+        // The attested credential data, with the public key in the COSE format
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let mut auth_data = authenticator_data(options["rp"]["id"].as_str().unwrap(), 0x45);
+        let credential_id_len = u16::try_from(self.credential_id.len()).unwrap();
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&credential_id_len.to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        auth_data.extend_from_slice(&[0xa5, 0x01, 0x02, 0x03, 0x26, 0x20, 0x01]);
+        auth_data.push(0x21);
+        cbor_bytes(&mut auth_data, point.x().unwrap());
+        auth_data.push(0x22);
+        cbor_bytes(&mut auth_data, point.y().unwrap());
+
+        // A "none" attestation object
+        let mut attestation_object = vec![0xa3];
+        cbor_text(&mut attestation_object, "fmt");
+        cbor_text(&mut attestation_object, "none");
+        cbor_text(&mut attestation_object, "attStmt");
+        attestation_object.push(0xa0);
+        cbor_text(&mut attestation_object, "authData");
+        cbor_bytes(&mut attestation_object, &auth_data);
+
+        serde_json::json!({
+            "id": encode_base64url(&self.credential_id),
+            "rawId": encode_base64url(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "attestationObject": encode_base64url(&attestation_object),
+                "clientDataJSON": encode_base64url(client_data.as_bytes()),
+            },
+        })
+        .to_string()
+    }
+
+    /// Sign the challenge of the given request options, like
+    /// `navigator.credentials.get()` would
+    pub fn authenticate(&self, options: &serde_json::Value) -> String {
+        let options = &options["publicKey"];
+        let client_data = client_data("webauthn.get", options["challenge"].as_str().unwrap());
+        let auth_data = authenticator_data(options["rpId"].as_str().unwrap(), 0x05);
+
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(client_data.as_bytes()));
+        let signature: DerSignature = self.key.sign(&signed);
+
+        serde_json::json!({
+            "id": encode_base64url(&self.credential_id),
+            "rawId": encode_base64url(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "authenticatorData": encode_base64url(&auth_data),
+                "clientDataJSON": encode_base64url(client_data.as_bytes()),
+                "signature": encode_base64url(signature.as_bytes()),
+                "userHandle": encode_base64url(&self.user_handle),
+            },
+        })
+        .to_string()
+    }
+}
+
+/// Register a passkey to the given user, going through the registration
+/// ceremony directly instead of the account page
+pub async fn add_passkey(state: &TestState, user: &User, passkey: &mut SoftPasskey) -> UserPasskey {
+    let relying_party = webauthn::relying_party(&state.url_builder, &state.site_config).unwrap();
+    let (options, registration) = relying_party
+        .start_passkey_registration(
+            webauthn::user_handle(user),
+            &user.username,
+            &user.username,
+            None,
+        )
+        .unwrap();
+    let credential = passkey.register(&serde_json::to_value(&options).unwrap());
+    let credential = serde_json::from_str(&credential).unwrap();
+    let credential = relying_party
+        .finish_passkey_registration(&credential, &registration)
+        .unwrap();
+
+    let mut repo = state.repository().await.unwrap();
+    let user_passkey = repo
+        .user_passkey()
+        .add(
+            &mut state.rng(),
+            &state.clock,
+            user,
+            "Test".to_owned(),
+            webauthn::encode_credential_id(credential.cred_id().as_ref()),
+            serde_json::to_string(&credential).unwrap(),
+        )
+        .await
+        .unwrap();
+    repo.save().await.unwrap();
+
+    user_passkey
+}
+
+fn encode_base64url(data: &[u8]) -> String {
+    use base64ct::Encoding;
+    base64ct::Base64UrlUnpadded::encode_string(data)
+}
+
+fn decode_base64url(data: &str) -> Vec<u8> {
+    use base64ct::Encoding;
+    base64ct::Base64UrlUnpadded::decode_vec(data).expect("Invalid base64url")
+}
+
+fn client_data(kind: &str, challenge: &str) -> String {
+    serde_json::json!({
+        "type": kind,
+        "challenge": challenge,
+        "origin": "https://example.com",
+        "crossOrigin": false,
+    })
+    .to_string()
+}
+
+/// The authenticator data, with a zero signature counter
+fn authenticator_data(rp_id: &str, flags: u8) -> Vec<u8> {
+    let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+    auth_data.push(flags);
+    auth_data.extend_from_slice(&[0; 4]);
+    auth_data
+}
+
+fn cbor_text(out: &mut Vec<u8>, text: &str) {
+    assert!(text.len() < 24);
+    out.push(0x60 | u8::try_from(text.len()).unwrap());
+    out.extend_from_slice(text.as_bytes());
+}
+
+fn cbor_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    if let Ok(len) = u8::try_from(data.len()) {
+        out.extend_from_slice(&[0x58, len]);
+    } else {
+        out.push(0x59);
+        out.extend_from_slice(&u16::try_from(data.len()).unwrap().to_be_bytes());
+    }
+    out.extend_from_slice(data);
+}
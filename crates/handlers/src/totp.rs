@@ -0,0 +1,262 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Time-based one-time passwords, as defined by RFC 6238, used as a second
+//! authentication factor.
+//!
+//! This uses the parameters most authenticator apps support: HMAC-SHA1,
+//! 6 digits and a 30 seconds period.
+
+use chrono::{DateTime, Utc};
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
+use hmac::{Hmac, Mac};
+use mas_keystore::Encrypter;
+use rand::{CryptoRng, RngCore};
+use sha1::Sha1;
+use url::Url;
+use zeroize::Zeroizing;
+
+/// Length in bytes of the generated secrets, as recommended by RFC 4226
+const SECRET_LENGTH: usize = 20;
+
+/// Number of digits in a code
+const DIGITS: u32 = 6;
+
+/// Duration of a time step, in seconds
+const PERIOD: i64 = 30;
+
+/// Number of time steps before and after the current one for which codes are
+/// still accepted, to account for clock drift
+const SKEW: i64 = 1;
+
+/// Number of recovery codes generated at once
+pub const RECOVERY_CODES_COUNT: usize = 10;
+
+/// Length in bytes of the generated recovery codes, which gives them 80 bits
+/// of entropy
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// Number of characters in each dash-separated group of a recovery code
+const RECOVERY_CODE_GROUP: usize = 4;
+
+/// Generate a new random TOTP secret
+pub fn generate_secret(rng: &mut (impl RngCore + CryptoRng)) -> Zeroizing<Vec<u8>> {
+    let mut secret = Zeroizing::new(vec![0; SECRET_LENGTH]);
+    rng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Encode a secret in base32, which is how users type it in authenticator
+/// apps
+#[must_use]
+pub fn encode_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+/// Build the `otpauth://` URI used to enroll the secret in an authenticator
+/// app
+///
+/// # Panics
+///
+/// Panics if the base URI is invalid, which should never happen
+#[must_use]
+pub fn provisioning_uri(secret: &[u8], issuer: &str, account_name: &str) -> Url {
+    let mut uri = Url::parse("otpauth://totp/").expect("valid base URI");
+    uri.set_path(&format!("{issuer}:{account_name}"));
+    uri.query_pairs_mut()
+        .append_pair("secret", &encode_secret(secret))
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &DIGITS.to_string())
+        .append_pair("period", &PERIOD.to_string());
+    uri
+}
+
+/// Compute the HOTP value for the given counter, as defined by RFC 4226
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = usize::from(hash[hash.len() - 1] & 0xf);
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    value % 10_u32.pow(DIGITS)
+}
+
+/// Get the time step for the given time
+fn time_step(now: DateTime<Utc>) -> i64 {
+    now.timestamp().div_euclid(PERIOD)
+}
+
+/// Check a code entered by the user against the secret
+///
+/// Codes for time steps up to `last_used_step` are rejected, so that a code
+/// can only be used once.
+///
+/// Returns the time step of the matching code, which should be saved as the
+/// new last used step, or `None` if the code is invalid.
+#[must_use]
+pub fn verify(
+    secret: &[u8],
+    code: &str,
+    now: DateTime<Utc>,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+
+    let current = time_step(now);
+    (current - SKEW..=current + SKEW)
+        // `None` compares lower than any `Some`, so this accepts all steps if no
+        // code was used yet
+        .filter(|step| last_used_step < Some(*step))
+        .find(|step| u64::try_from(*step).is_ok_and(|counter| hotp(secret, counter) == code))
+}
+
+/// Generate the code for the given time, like an authenticator app would
+#[cfg(test)]
+pub fn generate_code(secret: &[u8], now: DateTime<Utc>) -> String {
+    let counter = u64::try_from(time_step(now)).expect("time is after the epoch");
+    format!("{:0width$}", hotp(secret, counter), width = DIGITS as usize)
+}
+
+/// Generate a new set of recovery codes
+#[must_use]
+pub fn generate_recovery_codes(rng: &mut (impl RngCore + CryptoRng)) -> Vec<String> {
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let mut bytes = [0; RECOVERY_CODE_LENGTH];
+            rng.fill_bytes(&mut bytes);
+            let code: Vec<char> = BASE32_NOPAD.encode(&bytes).to_lowercase().chars().collect();
+            code.chunks(RECOVERY_CODE_GROUP)
+                .map(|group| group.iter().collect::<String>())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect()
+}
+
+/// Hash a recovery code for storage
+///
+/// The hash is keyed by the encryption secret, so that the codes can't be
+/// guessed offline from a database dump. The code is normalized first, so that
+/// the case, dashes and spaces don't matter when the user types it.
+#[must_use]
+pub fn hash_recovery_code(encrypter: &Encrypter, code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    HEXLOWER.encode(&encrypter.keyed_hash(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(timestamp, 0).unwrap()
+    }
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        // Test vectors from RFC 6238 Appendix B, truncated to 6 digits
+        for (timestamp, code) in [
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_111_111_111, "050471"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+        ] {
+            let step = verify(SECRET, code, at(timestamp), None);
+            assert_eq!(step, Some(time_step(at(timestamp))), "at {timestamp}");
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let now = at(1_111_111_111);
+        let step = time_step(now);
+
+        // Codes from the adjacent time steps are accepted
+        assert_eq!(
+            verify(SECRET, "050471", now - chrono::Duration::seconds(30), None),
+            Some(step)
+        );
+        assert_eq!(
+            verify(SECRET, "050471", now + chrono::Duration::seconds(30), None),
+            Some(step)
+        );
+
+        // But not further away
+        assert_eq!(
+            verify(SECRET, "050471", now + chrono::Duration::seconds(90), None),
+            None
+        );
+
+        // Spaces are ignored
+        assert_eq!(verify(SECRET, "050 471", now, None), Some(step));
+
+        // Malformed codes are rejected
+        assert_eq!(verify(SECRET, "50471", now, None), None);
+        assert_eq!(verify(SECRET, "0504710", now, None), None);
+        assert_eq!(verify(SECRET, "05047a", now, None), None);
+
+        // A code can't be used twice
+        assert_eq!(verify(SECRET, "050471", now, Some(step)), None);
+        assert_eq!(verify(SECRET, "050471", now, Some(step - 1)), Some(step));
+
+        assert_eq!(generate_code(SECRET, now), "050471");
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = provisioning_uri(SECRET, "example.com", "john");
+        assert_eq!(
+            uri.as_str(),
+            "otpauth://totp/example.com:john?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=example.com&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let encrypter = Encrypter::new(&[0x42; 32]);
+        let codes = generate_recovery_codes(&mut rng);
+        assert_eq!(codes.len(), RECOVERY_CODES_COUNT);
+
+        for code in &codes {
+            assert_eq!(code.len(), 19);
+            // The hash should not depend on how the user typed the code
+            assert_eq!(
+                hash_recovery_code(&encrypter, code),
+                hash_recovery_code(&encrypter, &code.replace('-', " ").to_uppercase())
+            );
+        }
+
+        // It depends on the encryption key
+        let other = Encrypter::new(&[0x43; 32]);
+        assert_ne!(
+            hash_recovery_code(&encrypter, &codes[0]),
+            hash_recovery_code(&other, &codes[0])
+        );
+    }
+}
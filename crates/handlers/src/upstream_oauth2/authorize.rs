@@ -6,19 +6,25 @@
 
 use axum::{
     extract::{Path, Query, State},
-    response::{IntoResponse, Redirect},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use hyper::StatusCode;
 use mas_axum_utils::{
     cookies::CookieJar, http_client_factory::HttpClientFactory, sentry::SentryEventID,
 };
-use mas_data_model::UpstreamOAuthProvider;
+use mas_data_model::{
+    UpstreamOAuthProvider, UpstreamOAuthProviderSamlBinding, UpstreamOAuthProviderSamlSettings,
+};
 use mas_oidc_client::requests::authorization_code::AuthorizationRequestData;
-use mas_router::UrlBuilder;
+use mas_router::{PostAuthAction, UrlBuilder};
+use mas_saml::AuthnRequest;
 use mas_storage::{
     upstream_oauth2::{UpstreamOAuthProviderRepository, UpstreamOAuthSessionRepository},
-    BoxClock, BoxRepository, BoxRng,
+    BoxClock, BoxRepository, BoxRng, Clock,
 };
+use mas_templates::{FormPostContext, Templates};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Serialize;
 use thiserror::Error;
 use ulid::Ulid;
 
@@ -33,6 +39,9 @@ pub(crate) enum RouteError {
     #[error("Provider not found")]
     ProviderNotFound,
 
+    #[error("Provider doesn't have OAuth 2.0 client settings")]
+    NotOAuthProvider,
+
     #[error(transparent)]
     Internal(Box<dyn std::error::Error>),
 }
@@ -40,12 +49,18 @@ pub(crate) enum RouteError {
 impl_from_error_for_route!(mas_oidc_client::error::DiscoveryError);
 impl_from_error_for_route!(mas_oidc_client::error::AuthorizationError);
 impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_templates::TemplateError);
 
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
         let event_id = sentry::capture_error(&self);
         let response = match self {
             Self::ProviderNotFound => (StatusCode::NOT_FOUND, "Provider not found").into_response(),
+            Self::NotOAuthProvider => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Provider doesn't have OAuth 2.0 client settings",
+            )
+                .into_response(),
             Self::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
         };
 
@@ -59,6 +74,7 @@ impl IntoResponse for RouteError {
     skip_all,
     err,
 )]
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn get(
     mut rng: BoxRng,
     clock: BoxClock,
@@ -66,10 +82,11 @@ pub(crate) async fn get(
     State(metadata_cache): State<MetadataCache>,
     mut repo: BoxRepository,
     State(url_builder): State<UrlBuilder>,
+    State(templates): State<Templates>,
     cookie_jar: CookieJar,
     Path(provider_id): Path<Ulid>,
     Query(query): Query<OptionalPostAuthAction>,
-) -> Result<impl IntoResponse, RouteError> {
+) -> Result<Response, RouteError> {
     let provider = repo
         .upstream_oauth_provider()
         .lookup(provider_id)
@@ -77,6 +94,27 @@ pub(crate) async fn get(
         .filter(UpstreamOAuthProvider::enabled)
         .ok_or(RouteError::ProviderNotFound)?;
 
+    if let Some(saml) = &provider.saml {
+        return saml_authorize(
+            rng,
+            clock,
+            repo,
+            &url_builder,
+            &templates,
+            cookie_jar,
+            &provider,
+            saml,
+            query.post_auth_action,
+        )
+        .await;
+    }
+
+    // Only OpenID Connect providers have those settings
+    let (Some(client_id), Some(scope)) = (provider.client_id.clone(), provider.scope.clone())
+    else {
+        return Err(RouteError::NotOAuthProvider);
+    };
+
     let http_service = http_client_factory.http_service("upstream_oauth2.authorize");
 
     // First, discover the provider
@@ -87,11 +125,7 @@ pub(crate) async fn get(
 
     let redirect_uri = url_builder.upstream_oauth_callback(provider.id);
 
-    let data = AuthorizationRequestData::new(
-        provider.client_id.clone(),
-        provider.scope.clone(),
-        redirect_uri,
-    );
+    let data = AuthorizationRequestData::new(client_id, scope, redirect_uri);
 
     let data = if let Some(methods) = lazy_metadata.pkce_methods().await? {
         data.with_code_challenge_methods_supported(methods)
@@ -133,5 +167,83 @@ pub(crate) async fn get(
 
     repo.save().await?;
 
-    Ok((cookie_jar, Redirect::temporary(url.as_str())))
+    Ok((cookie_jar, Redirect::temporary(url.as_str())).into_response())
+}
+
+/// The parameters sent to the identity provider with the HTTP-POST binding
+#[derive(Serialize)]
+struct SamlRequestParams {
+    #[serde(rename = "SAMLRequest")]
+    saml_request: String,
+
+    #[serde(rename = "RelayState")]
+    relay_state: String,
+}
+
+/// Start the authentication with a SAML 2.0 identity provider
+///
+/// The ID of the upstream session is sent as the relay state, so that the
+/// assertion consumer service can find it back, as the browser doesn't send
+/// our cookies on the cross-site POST request carrying the response.
+#[allow(clippy::too_many_arguments)]
+async fn saml_authorize(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    url_builder: &UrlBuilder,
+    templates: &Templates,
+    cookie_jar: CookieJar,
+    provider: &UpstreamOAuthProvider,
+    saml: &UpstreamOAuthProviderSamlSettings,
+    post_auth_action: Option<PostAuthAction>,
+) -> Result<Response, RouteError> {
+    let state = Alphanumeric.sample_string(&mut rng, 32);
+    // The request ID is checked against the `InResponseTo` attribute of the
+    // response, and must start with a letter or an underscore
+    let request_id = format!("_{}", Alphanumeric.sample_string(&mut rng, 32));
+
+    let session = repo
+        .upstream_oauth_session()
+        .add_saml(
+            &mut rng,
+            &clock,
+            provider,
+            state.clone(),
+            request_id.clone(),
+        )
+        .await?;
+
+    let cookie_jar = UpstreamSessionsCookie::load(&cookie_jar)
+        .add(session.id, provider.id, state, post_auth_action)
+        .save(cookie_jar, &clock);
+
+    repo.save().await?;
+
+    let assertion_consumer_service_url = url_builder.upstream_saml2_assertion_consumer(provider.id);
+    let request = AuthnRequest {
+        id: &request_id,
+        issue_instant: clock.now(),
+        destination: &saml.idp_sso_url,
+        issuer: &saml.sp_entity_id,
+        assertion_consumer_service_url: &assertion_consumer_service_url,
+        name_id_format: saml.name_id_format.as_deref(),
+    };
+    let relay_state = session.id.to_string();
+
+    let response = match saml.sso_binding {
+        UpstreamOAuthProviderSamlBinding::Redirect => {
+            Redirect::temporary(request.redirect_url(&relay_state).as_str()).into_response()
+        }
+        UpstreamOAuthProviderSamlBinding::Post => {
+            let params = SamlRequestParams {
+                saml_request: request.to_post_parameter(),
+                relay_state,
+            };
+            let ctx = FormPostContext::new(saml.idp_sso_url.clone(), params);
+            let rendered = templates.render_form_post(&ctx)?;
+            Html(rendered).into_response()
+        }
+    };
+
+    Ok((cookie_jar, response).into_response())
 }
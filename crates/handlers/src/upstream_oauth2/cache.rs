@@ -276,7 +276,7 @@ mod tests {
     use std::sync::atomic::{AtomicUsize, Ordering};
 
     use hyper::{body::Bytes, Request, Response, StatusCode};
-    use mas_data_model::UpstreamOAuthProviderClaimsImports;
+    use mas_data_model::{UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderKind};
     use mas_http::BoxCloneSyncService;
     use mas_iana::oauth::OAuthClientAuthenticationMethod;
     use mas_storage::{clock::MockClock, Clock};
@@ -482,6 +482,7 @@ mod tests {
         let service = BoxCloneSyncService::new(tower::service_fn(handler));
         let provider = UpstreamOAuthProvider {
             id: Ulid::nil(),
+            kind: UpstreamOAuthProviderKind::Oidc,
             issuer: "https://valid.example.com/".to_owned(),
             human_name: Some("Example Ltd.".to_owned()),
             brand_name: None,
@@ -490,15 +491,16 @@ mod tests {
             jwks_uri_override: None,
             authorization_endpoint_override: None,
             token_endpoint_override: None,
-            scope: Scope::from_iter([OPENID]),
-            client_id: "client_id".to_owned(),
+            scope: Some(Scope::from_iter([OPENID])),
+            client_id: Some("client_id".to_owned()),
             encrypted_client_secret: None,
             token_endpoint_signing_alg: None,
-            token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
+            token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::None),
             created_at: clock.now(),
             disabled_at: None,
             claims_imports: UpstreamOAuthProviderClaimsImports::default(),
             additional_authorization_parameters: Vec::new(),
+            saml: None,
         };
 
         // Without any override, it should just use discovery
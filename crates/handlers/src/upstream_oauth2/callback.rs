@@ -69,6 +69,9 @@ pub(crate) enum RouteError {
     #[error("Provider mismatch")]
     ProviderMismatch,
 
+    #[error("Session was started with a SAML 2.0 identity provider")]
+    SamlSession,
+
     #[error("Session already completed")]
     AlreadyCompleted,
 
@@ -143,6 +146,8 @@ pub(crate) async fn get(
         .lookup(provider_id)
         .await?
         .filter(UpstreamOAuthProvider::enabled)
+        // SAML providers send their responses to the assertion consumer service
+        .filter(UpstreamOAuthProvider::is_oidc)
         .ok_or(RouteError::ProviderNotFound)?;
 
     let sessions_cookie = UpstreamSessionsCookie::load(&cookie_jar);
@@ -161,6 +166,13 @@ pub(crate) async fn get(
         return Err(RouteError::ProviderMismatch);
     }
 
+    // Sessions started with a SAML 2.0 identity provider are completed by the
+    // assertion consumer service, and have a request ID instead of a nonce
+    if session.is_saml() {
+        return Err(RouteError::SamlSession);
+    }
+    let nonce = session.nonce.clone().ok_or(RouteError::SamlSession)?;
+
     if params.state != session.state_str {
         // The state in the session cookie should match the one from the params
         return Err(RouteError::StateMismatch);
@@ -202,12 +214,17 @@ pub(crate) async fn get(
         &encrypter,
     )?;
 
+    let client_id = provider
+        .client_id
+        .as_ref()
+        .ok_or(super::ProviderCredentialsError::NotOAuthProvider)?;
+
     let redirect_uri = url_builder.upstream_oauth_callback(provider.id);
 
     // TODO: all that should be borrowed
     let validation_data = AuthorizationValidationData {
         state: session.state_str.clone(),
-        nonce: session.nonce.clone(),
+        nonce,
         code_challenge_verifier: session.code_challenge_verifier.clone(),
         redirect_uri,
     };
@@ -217,7 +234,7 @@ pub(crate) async fn get(
         jwks: &jwks,
         // TODO: make that configurable
         signing_algorithm: &mas_iana::jose::JsonWebSignatureAlg::Rs256,
-        client_id: &provider.client_id,
+        client_id,
     };
 
     let (response, id_token) =
@@ -0,0 +1,87 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Conversions from the upstream provider configuration types to their data
+//! model counterparts.
+//!
+//! Those are shared between the configuration sync and the admin API, which
+//! both accept providers in the same shape as the configuration file.
+
+use mas_config::{
+    UpstreamOAuth2ClaimsImports, UpstreamOAuth2DiscoveryMode, UpstreamOAuth2ImportAction,
+    UpstreamOAuth2PkceMethod, UpstreamOAuth2SetEmailVerification,
+};
+use mas_data_model::{
+    UpsreamOAuthProviderSetEmailVerification, UpstreamOAuthProviderClaimsImports,
+    UpstreamOAuthProviderDiscoveryMode, UpstreamOAuthProviderImportAction,
+    UpstreamOAuthProviderImportPreference, UpstreamOAuthProviderPkceMode,
+    UpstreamOAuthProviderSubjectPreference,
+};
+
+fn map_import_action(config: UpstreamOAuth2ImportAction) -> UpstreamOAuthProviderImportAction {
+    match config {
+        UpstreamOAuth2ImportAction::Ignore => UpstreamOAuthProviderImportAction::Ignore,
+        UpstreamOAuth2ImportAction::Suggest => UpstreamOAuthProviderImportAction::Suggest,
+        UpstreamOAuth2ImportAction::Force => UpstreamOAuthProviderImportAction::Force,
+        UpstreamOAuth2ImportAction::Require => UpstreamOAuthProviderImportAction::Require,
+    }
+}
+
+/// Convert the claims imports configuration of a provider
+#[must_use]
+pub fn map_claims_imports(
+    config: &UpstreamOAuth2ClaimsImports,
+) -> UpstreamOAuthProviderClaimsImports {
+    UpstreamOAuthProviderClaimsImports {
+        subject: UpstreamOAuthProviderSubjectPreference {
+            template: config.subject.template.clone(),
+        },
+        localpart: UpstreamOAuthProviderImportPreference {
+            action: map_import_action(config.localpart.action),
+            template: config.localpart.template.clone(),
+        },
+        displayname: UpstreamOAuthProviderImportPreference {
+            action: map_import_action(config.displayname.action),
+            template: config.displayname.template.clone(),
+        },
+        email: UpstreamOAuthProviderImportPreference {
+            action: map_import_action(config.email.action),
+            template: config.email.template.clone(),
+        },
+        verify_email: match config.email.set_email_verification {
+            UpstreamOAuth2SetEmailVerification::Always => {
+                UpsreamOAuthProviderSetEmailVerification::Always
+            }
+            UpstreamOAuth2SetEmailVerification::Never => {
+                UpsreamOAuthProviderSetEmailVerification::Never
+            }
+            UpstreamOAuth2SetEmailVerification::Import => {
+                UpsreamOAuthProviderSetEmailVerification::Import
+            }
+        },
+    }
+}
+
+/// Convert the discovery mode configuration of a provider
+#[must_use]
+pub fn map_discovery_mode(
+    config: UpstreamOAuth2DiscoveryMode,
+) -> UpstreamOAuthProviderDiscoveryMode {
+    match config {
+        UpstreamOAuth2DiscoveryMode::Oidc => UpstreamOAuthProviderDiscoveryMode::Oidc,
+        UpstreamOAuth2DiscoveryMode::Insecure => UpstreamOAuthProviderDiscoveryMode::Insecure,
+        UpstreamOAuth2DiscoveryMode::Disabled => UpstreamOAuthProviderDiscoveryMode::Disabled,
+    }
+}
+
+/// Convert the PKCE method configuration of a provider
+#[must_use]
+pub fn map_pkce_method(config: UpstreamOAuth2PkceMethod) -> UpstreamOAuthProviderPkceMode {
+    match config {
+        UpstreamOAuth2PkceMethod::Auto => UpstreamOAuthProviderPkceMode::Auto,
+        UpstreamOAuth2PkceMethod::Always => UpstreamOAuthProviderPkceMode::S256,
+        UpstreamOAuth2PkceMethod::Never => UpstreamOAuthProviderPkceMode::Disabled,
+    }
+}
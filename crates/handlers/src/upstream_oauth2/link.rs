@@ -17,13 +17,13 @@ use mas_axum_utils::{
     sentry::SentryEventID,
     FancyError, SessionInfoExt,
 };
-use mas_data_model::{User, UserAgent};
+use mas_data_model::{User, UserAgent, WebhookEventKind};
 use mas_jose::jwt::Jwt;
 use mas_matrix::BoxHomeserverConnection;
 use mas_policy::Policy;
 use mas_router::UrlBuilder;
 use mas_storage::{
-    job::{JobRepositoryExt, ProvisionUserJob},
+    job::{DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob},
     upstream_oauth2::{UpstreamOAuthLinkRepository, UpstreamOAuthSessionRepository},
     user::{BrowserSessionRepository, UserEmailRepository, UserRepository},
     BoxClock, BoxRepository, BoxRng, RepositoryAccess,
@@ -331,9 +331,14 @@ pub(crate) async fn get(
                 .await?
                 .ok_or(RouteError::ProviderNotFound)?;
 
-            let payload = id_token
-                .map(|id_token| id_token.into_parts().1)
-                .unwrap_or_default();
+            // Attributes of a SAML assertion take the place of the ID token claims
+            let payload = if let Some(saml_attributes) = upstream_session.saml_attributes() {
+                minijinja::Value::from_serialize(saml_attributes)
+            } else {
+                id_token
+                    .map(|id_token| id_token.into_parts().1)
+                    .unwrap_or_default()
+            };
 
             let ctx = UpstreamRegister::default();
 
@@ -534,6 +539,20 @@ pub(crate) async fn post(
                 .associate_to_user(&link, &session.user)
                 .await?;
 
+            repo.job()
+                .schedule_job(
+                    DispatchWebhookEventJob::new(
+                        WebhookEventKind::UpstreamOAuthLinkCreated,
+                        &session.user,
+                    )
+                    .with_data(serde_json::json!({
+                        "link_id": link.id,
+                        "provider_id": link.provider_id,
+                        "subject": link.subject,
+                    })),
+                )
+                .await?;
+
             session
         }
 
@@ -568,9 +587,14 @@ pub(crate) async fn post(
                 .await?
                 .ok_or(RouteError::ProviderNotFound)?;
 
-            let payload = id_token
-                .map(|id_token| id_token.into_parts().1)
-                .unwrap_or_default();
+            // Attributes of a SAML assertion take the place of the ID token claims
+            let payload = if let Some(saml_attributes) = upstream_session.saml_attributes() {
+                minijinja::Value::from_serialize(saml_attributes)
+            } else {
+                id_token
+                    .map(|id_token| id_token.into_parts().1)
+                    .unwrap_or_default()
+            };
 
             // Is the email verified according to the upstream provider?
             let provider_email_verified = payload
@@ -785,6 +809,16 @@ pub(crate) async fn post(
 
             repo.job().schedule_job(job).await?;
 
+            repo.job()
+                .schedule_job(
+                    DispatchWebhookEventJob::new(WebhookEventKind::UserRegistered, &user)
+                        .with_data(serde_json::json!({
+                            "method": "upstream_oauth2",
+                            "provider_id": provider.id,
+                        })),
+                )
+                .await?;
+
             // If we have an email, add it to the user
             if let Some(email) = email {
                 let user_email = repo
@@ -804,6 +838,16 @@ pub(crate) async fn post(
                         .await?;
 
                     repo.user_email().set_as_primary(&user_email).await?;
+
+                    repo.job()
+                        .schedule_job(
+                            DispatchWebhookEventJob::new(
+                                WebhookEventKind::UserEmailVerified,
+                                &user,
+                            )
+                            .with_data(serde_json::json!({ "email": user_email.email })),
+                        )
+                        .await?;
                 }
             }
 
@@ -811,6 +855,17 @@ pub(crate) async fn post(
                 .associate_to_user(&link, &user)
                 .await?;
 
+            repo.job()
+                .schedule_job(
+                    DispatchWebhookEventJob::new(WebhookEventKind::UpstreamOAuthLinkCreated, &user)
+                        .with_data(serde_json::json!({
+                            "link_id": link.id,
+                            "provider_id": link.provider_id,
+                            "subject": link.subject,
+                        })),
+                )
+                .await?;
+
             repo.browser_session()
                 .add(&mut rng, &clock, &user, user_agent)
                 .await?
@@ -905,10 +960,10 @@ mod tests {
                     issuer: "https://example.com/".to_owned(),
                     human_name: Some("Example Ltd.".to_owned()),
                     brand_name: None,
-                    scope: Scope::from_iter([OPENID]),
-                    token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
+                    scope: Some(Scope::from_iter([OPENID])),
+                    token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::None),
                     token_endpoint_signing_alg: None,
-                    client_id: "client".to_owned(),
+                    client_id: Some("client".to_owned()),
                     encrypted_client_secret: None,
                     claims_imports,
                     authorization_endpoint_override: None,
@@ -917,6 +972,7 @@ mod tests {
                     discovery_mode: mas_data_model::UpstreamOAuthProviderDiscoveryMode::Oidc,
                     pkce_mode: mas_data_model::UpstreamOAuthProviderPkceMode::Auto,
                     additional_authorization_parameters: Vec::new(),
+                    saml: None,
                 },
             )
             .await
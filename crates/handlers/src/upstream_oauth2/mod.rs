@@ -16,15 +16,21 @@ use url::Url;
 pub(crate) mod authorize;
 pub(crate) mod cache;
 pub(crate) mod callback;
+mod config;
 mod cookie;
 pub(crate) mod link;
-mod template;
+pub(crate) mod saml;
+pub(crate) mod template;
 
+pub use self::config::{map_claims_imports, map_discovery_mode, map_pkce_method};
 use self::cookie::UpstreamSessions as UpstreamSessionsCookie;
 
 #[derive(Debug, Error)]
 #[allow(clippy::enum_variant_names)]
 enum ProviderCredentialsError {
+    #[error("Provider doesn't have OAuth 2.0 client settings")]
+    NotOAuthProvider,
+
     #[error("Provider doesn't have a client secret")]
     MissingClientSecret,
 
@@ -47,7 +53,13 @@ fn client_credentials_for_provider(
     keystore: &Keystore,
     encrypter: &Encrypter,
 ) -> Result<ClientCredentials, ProviderCredentialsError> {
-    let client_id = provider.client_id.clone();
+    // Only OpenID Connect providers have those settings
+    let (Some(client_id), Some(token_endpoint_auth_method)) = (
+        provider.client_id.clone(),
+        provider.token_endpoint_auth_method.clone(),
+    ) else {
+        return Err(ProviderCredentialsError::NotOAuthProvider);
+    };
 
     // Decrypt the client secret
     let client_secret = provider
@@ -60,7 +72,7 @@ fn client_credentials_for_provider(
         })
         .transpose()?;
 
-    let client_credentials = match provider.token_endpoint_auth_method {
+    let client_credentials = match token_endpoint_auth_method {
         OAuthClientAuthenticationMethod::None => ClientCredentials::None { client_id },
         OAuthClientAuthenticationMethod::ClientSecretPost => ClientCredentials::ClientSecretPost {
             client_id,
@@ -0,0 +1,716 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Handlers for the SAML 2.0 identity providers
+//!
+//! The authentication request is sent by the
+//! [`authorize`](super::authorize) handler, like for OAuth 2.0 providers.
+//! The identity provider then sends its response through a cross-site POST
+//! request to the assertion consumer service, which doesn't carry our
+//! cookies. The session is therefore found through the relay state, and the
+//! browser is then redirected to the [`complete`] handler, which checks that
+//! the session was started by the same browser.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Form,
+};
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use mas_axum_utils::{cookies::CookieJar, sentry::SentryEventID};
+use mas_data_model::UpstreamOAuthProvider;
+use mas_router::{UpstreamSaml2CompleteParams, UrlBuilder};
+use mas_saml::{validate_response, Assertion, ResponseValidationData, VerifyingKeys};
+use mas_storage::{
+    upstream_oauth2::{
+        UpstreamOAuthLinkRepository, UpstreamOAuthProviderRepository,
+        UpstreamOAuthSessionRepository,
+    },
+    BoxClock, BoxRepository, BoxRng, Clock,
+};
+use serde::Deserialize;
+use thiserror::Error;
+use ulid::Ulid;
+
+use super::{template::environment, UpstreamSessionsCookie};
+use crate::impl_from_error_for_route;
+
+#[derive(Debug, Error)]
+pub(crate) enum RouteError {
+    #[error("Session not found")]
+    SessionNotFound,
+
+    #[error("Provider not found")]
+    ProviderNotFound,
+
+    #[error("Provider mismatch")]
+    ProviderMismatch,
+
+    #[error("Session was not started with a SAML 2.0 identity provider")]
+    NotSamlSession,
+
+    #[error("Session already completed")]
+    AlreadyCompleted,
+
+    #[error("Session not completed")]
+    NotCompleted,
+
+    #[error("Invalid relay state")]
+    InvalidRelayState,
+
+    #[error("Invalid identity provider certificates")]
+    InvalidCertificates(#[source] mas_saml::CertificateError),
+
+    #[error("Invalid SAML response")]
+    InvalidResponse(#[source] mas_saml::ResponseError),
+
+    #[error("Could not extract subject from SAML assertion")]
+    ExtractSubject(#[source] minijinja::Error),
+
+    #[error("Subject is empty")]
+    EmptySubject,
+
+    #[error("Missing session cookie")]
+    MissingCookie,
+
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error>),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(super::cookie::UpstreamSessionNotFound);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let event_id = sentry::capture_error(&self);
+        let response = match self {
+            Self::ProviderNotFound => (StatusCode::NOT_FOUND, "Provider not found").into_response(),
+            Self::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+            Self::InvalidCertificates(e) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+            Self::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            e => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        };
+
+        (SentryEventID::from(event_id), response).into_response()
+    }
+}
+
+/// Lookup an enabled SAML provider
+async fn lookup_provider(
+    repo: &mut BoxRepository,
+    provider_id: Ulid,
+) -> Result<UpstreamOAuthProvider, RouteError> {
+    repo.upstream_oauth_provider()
+        .lookup(provider_id)
+        .await?
+        .filter(UpstreamOAuthProvider::enabled)
+        .filter(UpstreamOAuthProvider::is_saml)
+        .ok_or(RouteError::ProviderNotFound)
+}
+
+/// Convert the assertion to the claims available in the templates used to
+/// import the user attributes
+///
+/// Attributes are available both by their name and their friendly name, as a
+/// string if they have a single value, or as a list of strings otherwise. The
+/// subject identifier is available as `sub`.
+fn assertion_claims(assertion: &Assertion) -> serde_json::Value {
+    let mut claims = serde_json::Map::new();
+
+    for attribute in &assertion.attributes {
+        let value = match attribute.values.as_slice() {
+            [value] => serde_json::Value::String(value.clone()),
+            values => values
+                .iter()
+                .cloned()
+                .map(serde_json::Value::String)
+                .collect(),
+        };
+
+        if let Some(friendly_name) = &attribute.friendly_name {
+            claims.insert(friendly_name.clone(), value.clone());
+        }
+
+        claims.insert(attribute.name.clone(), value);
+    }
+
+    // The subject can't be overridden by an attribute
+    claims.insert(
+        "sub".to_owned(),
+        serde_json::Value::String(assertion.name_id.clone()),
+    );
+
+    serde_json::Value::Object(claims)
+}
+
+#[tracing::instrument(
+    name = "handlers.upstream_oauth2.saml.metadata",
+    fields(upstream_oauth_provider.id = %provider_id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn metadata(
+    mut repo: BoxRepository,
+    State(url_builder): State<UrlBuilder>,
+    Path(provider_id): Path<Ulid>,
+) -> Result<impl IntoResponse, RouteError> {
+    let provider = lookup_provider(&mut repo, provider_id).await?;
+    let saml = provider.saml.as_ref().ok_or(RouteError::ProviderNotFound)?;
+
+    repo.cancel().await?;
+
+    let assertion_consumer_service_url = url_builder.upstream_saml2_assertion_consumer(provider.id);
+    let metadata = mas_saml::ServiceProviderMetadata {
+        entity_id: &saml.sp_entity_id,
+        assertion_consumer_service_url: &assertion_consumer_service_url,
+        name_id_format: saml.name_id_format.as_deref(),
+    };
+
+    Ok((
+        [(CONTENT_TYPE, "application/samlmetadata+xml")],
+        metadata.to_xml(),
+    ))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AssertionConsumerForm {
+    #[serde(rename = "SAMLResponse")]
+    saml_response: String,
+
+    #[serde(rename = "RelayState")]
+    relay_state: String,
+}
+
+#[tracing::instrument(
+    name = "handlers.upstream_oauth2.saml.assertion_consumer",
+    fields(upstream_oauth_provider.id = %provider_id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn assertion_consumer(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    State(url_builder): State<UrlBuilder>,
+    Path(provider_id): Path<Ulid>,
+    Form(form): Form<AssertionConsumerForm>,
+) -> Result<impl IntoResponse, RouteError> {
+    let provider = lookup_provider(&mut repo, provider_id).await?;
+    let saml = provider.saml.as_ref().ok_or(RouteError::ProviderNotFound)?;
+
+    // The relay state is the ID of the session, set by the authorize handler
+    let session_id: Ulid = form
+        .relay_state
+        .parse()
+        .map_err(|_| RouteError::InvalidRelayState)?;
+
+    let session = repo
+        .upstream_oauth_session()
+        .lookup(session_id)
+        .await?
+        .ok_or(RouteError::SessionNotFound)?;
+
+    if provider.id != session.provider_id {
+        return Err(RouteError::ProviderMismatch);
+    }
+
+    let request_id = session
+        .saml_request_id
+        .as_deref()
+        .ok_or(RouteError::NotSamlSession)?;
+
+    if !session.is_pending() {
+        // The session was already completed, the response may be replayed
+        return Err(RouteError::AlreadyCompleted);
+    }
+
+    let keys = VerifyingKeys::from_certificates(&saml.idp_certificates)
+        .map_err(RouteError::InvalidCertificates)?;
+    let assertion_consumer_service_url = url_builder.upstream_saml2_assertion_consumer(provider.id);
+    let validation_data = ResponseValidationData {
+        idp_entity_id: &provider.issuer,
+        sp_entity_id: &saml.sp_entity_id,
+        assertion_consumer_service_url: &assertion_consumer_service_url,
+        request_id,
+        keys: &keys,
+    };
+
+    let assertion = validate_response(&form.saml_response, &validation_data, clock.now())
+        .map_err(RouteError::InvalidResponse)?;
+
+    let claims = assertion_claims(&assertion);
+
+    let env = {
+        let mut env = environment();
+        env.add_global("user", minijinja::Value::from_serialize(&claims));
+        env
+    };
+
+    let template = provider
+        .claims_imports
+        .subject
+        .template
+        .as_deref()
+        .unwrap_or("{{ user.sub }}");
+    let subject = env
+        .render_str(template, ())
+        .map_err(RouteError::ExtractSubject)?;
+
+    if subject.is_empty() {
+        return Err(RouteError::EmptySubject);
+    }
+
+    // Look for an existing link
+    let maybe_link = repo
+        .upstream_oauth_link()
+        .find_by_subject(&provider, &subject)
+        .await?;
+
+    let link = if let Some(link) = maybe_link {
+        link
+    } else {
+        repo.upstream_oauth_link()
+            .add(&mut rng, &clock, &provider, subject)
+            .await?
+    };
+
+    let session = repo
+        .upstream_oauth_session()
+        .complete_with_saml_assertion(&clock, session, &link, claims)
+        .await?;
+
+    repo.save().await?;
+
+    Ok(
+        url_builder.redirect(&mas_router::UpstreamSaml2Complete::new(
+            provider.id,
+            session.state_str,
+        )),
+    )
+}
+
+#[tracing::instrument(
+    name = "handlers.upstream_oauth2.saml.complete",
+    fields(upstream_oauth_provider.id = %provider_id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn complete(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    State(url_builder): State<UrlBuilder>,
+    cookie_jar: CookieJar,
+    Path(provider_id): Path<Ulid>,
+    Query(params): Query<UpstreamSaml2CompleteParams>,
+) -> Result<impl IntoResponse, RouteError> {
+    // This makes sure the session was started by this browser
+    let sessions_cookie = UpstreamSessionsCookie::load(&cookie_jar);
+    let (session_id, _post_auth_action) = sessions_cookie
+        .find_session(provider_id, &params.state)
+        .map_err(|_| RouteError::MissingCookie)?;
+
+    let session = repo
+        .upstream_oauth_session()
+        .lookup(session_id)
+        .await?
+        .ok_or(RouteError::SessionNotFound)?;
+
+    if provider_id != session.provider_id {
+        return Err(RouteError::ProviderMismatch);
+    }
+
+    if !session.is_completed() {
+        return Err(RouteError::NotCompleted);
+    }
+
+    let link_id = session.link_id().ok_or(RouteError::NotCompleted)?;
+
+    let cookie_jar = sessions_cookie
+        .add_link_to_session(session.id, link_id)?
+        .save(cookie_jar, &clock);
+
+    repo.cancel().await?;
+
+    Ok((
+        cookie_jar,
+        url_builder.redirect(&mas_router::UpstreamOAuth2Link::new(link_id)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_data_model::{
+        UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
+        UpstreamOAuthProviderPkceMode, UpstreamOAuthProviderSamlBinding,
+        UpstreamOAuthProviderSamlSettings,
+    };
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_router::Route;
+    use mas_saml::Attribute;
+    use mas_storage::upstream_oauth2::UpstreamOAuthProviderParams;
+    use oauth2_types::scope::{Scope, OPENID};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_utils::{setup, CookieHelper, RequestBuilderExt, ResponseExt, TestState};
+
+    fn provider_params(
+        sso_binding: UpstreamOAuthProviderSamlBinding,
+    ) -> UpstreamOAuthProviderParams {
+        UpstreamOAuthProviderParams {
+            issuer: "https://idp.example.com/metadata".to_owned(),
+            human_name: Some("Example IdP".to_owned()),
+            brand_name: None,
+            scope: None,
+            token_endpoint_auth_method: None,
+            token_endpoint_signing_alg: None,
+            client_id: None,
+            encrypted_client_secret: None,
+            claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+            authorization_endpoint_override: None,
+            token_endpoint_override: None,
+            jwks_uri_override: None,
+            discovery_mode: UpstreamOAuthProviderDiscoveryMode::Disabled,
+            pkce_mode: UpstreamOAuthProviderPkceMode::Disabled,
+            additional_authorization_parameters: Vec::new(),
+            saml: Some(UpstreamOAuthProviderSamlSettings {
+                sp_entity_id: "https://mas.example.com/".to_owned(),
+                idp_sso_url: "https://idp.example.com/sso".parse().unwrap(),
+                idp_certificates: Vec::new(),
+                sso_binding,
+                name_id_format: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_assertion_claims() {
+        let assertion = Assertion {
+            name_id: "subject".to_owned(),
+            name_id_format: None,
+            session_index: None,
+            attributes: vec![
+                Attribute {
+                    name: "urn:oid:0.9.2342.19200300.100.1.1".to_owned(),
+                    friendly_name: Some("uid".to_owned()),
+                    values: vec!["alice".to_owned()],
+                },
+                Attribute {
+                    name: "groups".to_owned(),
+                    friendly_name: None,
+                    values: vec!["admins".to_owned(), "users".to_owned()],
+                },
+                Attribute {
+                    name: "sub".to_owned(),
+                    friendly_name: None,
+                    values: vec!["mallory".to_owned()],
+                },
+            ],
+        };
+
+        assert_eq!(
+            assertion_claims(&assertion),
+            serde_json::json!({
+                "sub": "subject",
+                "uid": "alice",
+                "urn:oid:0.9.2342.19200300.100.1.1": "alice",
+                "groups": ["admins", "users"],
+            })
+        );
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_metadata_and_authorize(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let redirect_provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &state.clock,
+                provider_params(UpstreamOAuthProviderSamlBinding::Redirect),
+            )
+            .await
+            .unwrap();
+        let post_provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &state.clock,
+                provider_params(UpstreamOAuthProviderSamlBinding::Post),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request =
+            Request::get(&*mas_router::UpstreamSaml2Metadata::new(redirect_provider.id).path())
+                .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        response.assert_header_value(CONTENT_TYPE, "application/samlmetadata+xml");
+        assert!(response
+            .body()
+            .contains(r#"entityID="https://mas.example.com/""#));
+        assert!(response.body().contains(
+            state
+                .url_builder
+                .upstream_saml2_assertion_consumer(redirect_provider.id)
+                .as_str()
+        ));
+
+        // The HTTP-Redirect binding sends the request in the query parameters
+        let request =
+            Request::get(&*mas_router::UpstreamOAuth2Authorize::new(redirect_provider.id).path())
+                .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::TEMPORARY_REDIRECT);
+        let location = response.headers().get(LOCATION).unwrap().to_str().unwrap();
+        let location = url::Url::parse(location).unwrap();
+        assert_eq!(location.path(), "/sso");
+        let relay_state = location
+            .query_pairs()
+            .find(|(key, _)| key == "RelayState")
+            .unwrap()
+            .1;
+        assert!(location.query_pairs().any(|(key, _)| key == "SAMLRequest"));
+
+        // The relay state is the ID of the session
+        let session_id: Ulid = relay_state.parse().unwrap();
+        let mut repo = state.repository().await.unwrap();
+        let session = repo
+            .upstream_oauth_session()
+            .lookup(session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.provider_id, redirect_provider.id);
+        assert!(session.is_pending());
+        assert!(session.is_saml());
+        assert_eq!(session.nonce, None);
+        repo.cancel().await.unwrap();
+
+        // The HTTP-POST binding renders an auto-submitted form
+        let request =
+            Request::get(&*mas_router::UpstreamOAuth2Authorize::new(post_provider.id).path())
+                .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("https://idp.example.com/sso"));
+        assert!(response.body().contains("SAMLRequest"));
+
+        // SAML providers don't have an OAuth 2.0 callback
+        let request =
+            Request::get(&*mas_router::UpstreamOAuth2Callback::new(post_provider.id).path())
+                .empty();
+        let response = state.request(request).await;
+        assert_ne!(response.status(), StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_oauth_callback_refuses_saml_sessions(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+        let cookies = CookieHelper::new();
+
+        let mut repo = state.repository().await.unwrap();
+        let saml_provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &state.clock,
+                provider_params(UpstreamOAuthProviderSamlBinding::Redirect),
+            )
+            .await
+            .unwrap();
+        let oidc_provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &state.clock,
+                UpstreamOAuthProviderParams {
+                    issuer: "https://oidc.example.com/".to_owned(),
+                    scope: Some(Scope::from_iter([OPENID])),
+                    token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::None),
+                    client_id: Some("client".to_owned()),
+                    saml: None,
+                    ..provider_params(UpstreamOAuthProviderSamlBinding::Redirect)
+                },
+            )
+            .await
+            .unwrap();
+
+        let saml_session = repo
+            .upstream_oauth_session()
+            .add_saml(
+                &mut rng,
+                &state.clock,
+                &saml_provider,
+                "saml-state".to_owned(),
+                "_request".to_owned(),
+            )
+            .await
+            .unwrap();
+        let mismatched_session = repo
+            .upstream_oauth_session()
+            .add_saml(
+                &mut rng,
+                &state.clock,
+                &oidc_provider,
+                "oidc-state".to_owned(),
+                "_other-request".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let cookie_jar = UpstreamSessionsCookie::default()
+            .add(
+                saml_session.id,
+                saml_provider.id,
+                "saml-state".to_owned(),
+                None,
+            )
+            .add(
+                mismatched_session.id,
+                oidc_provider.id,
+                "oidc-state".to_owned(),
+                None,
+            )
+            .save(state.cookie_jar(), &state.clock);
+        cookies.import(cookie_jar);
+
+        // The OAuth 2.0 callback doesn't accept SAML providers
+        let path = mas_router::UpstreamOAuth2Callback::new(saml_provider.id).path();
+        let request = cookies
+            .with_cookies(Request::get(format!("{path}?state=saml-state&code=code")).empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        // Nor sessions started with a SAML provider, even if they are attached
+        // to an OpenID Connect provider
+        let path = mas_router::UpstreamOAuth2Callback::new(oidc_provider.id).path();
+        let request = cookies
+            .with_cookies(Request::get(format!("{path}?state=oidc-state&code=code")).empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(response
+            .body()
+            .contains("Session was started with a SAML 2.0 identity provider"));
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_assertion_consumer_invalid_relay_state(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &state.clock,
+                provider_params(UpstreamOAuthProviderSamlBinding::Redirect),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let path = mas_router::UpstreamSaml2AssertionConsumer::new(provider.id).path();
+
+        let request = Request::post(&*path).form(serde_json::json!({
+            "SAMLResponse": "",
+            "RelayState": "not a session",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let request = Request::post(&*path).form(serde_json::json!({
+            "SAMLResponse": "",
+            "RelayState": Ulid::nil().to_string(),
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_complete(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+        let cookies = CookieHelper::new();
+
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &state.clock,
+                provider_params(UpstreamOAuthProviderSamlBinding::Redirect),
+            )
+            .await
+            .unwrap();
+
+        let session = repo
+            .upstream_oauth_session()
+            .add_saml(
+                &mut rng,
+                &state.clock,
+                &provider,
+                "state".to_owned(),
+                "_request".to_owned(),
+            )
+            .await
+            .unwrap();
+
+        let link = repo
+            .upstream_oauth_link()
+            .add(&mut rng, &state.clock, &provider, "subject".to_owned())
+            .await
+            .unwrap();
+
+        let session = repo
+            .upstream_oauth_session()
+            .complete_with_saml_assertion(
+                &state.clock,
+                session,
+                &link,
+                serde_json::json!({ "sub": "subject" }),
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let path = mas_router::UpstreamSaml2Complete::new(provider.id, "state".to_owned());
+
+        // Without the cookie, the session can't be completed
+        let request = Request::get(&*path.path_and_query()).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let cookie_jar = UpstreamSessionsCookie::default()
+            .add(session.id, provider.id, "state".to_owned(), None)
+            .save(state.cookie_jar(), &state.clock);
+        cookies.import(cookie_jar);
+
+        let request = cookies.with_cookies(Request::get(&*path.path_and_query()).empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(
+            LOCATION,
+            &mas_router::UpstreamOAuth2Link::new(link.id).path_and_query(),
+        );
+    }
+}
@@ -13,7 +13,7 @@ use mas_axum_utils::{
     csrf::{CsrfExt, ProtectedForm},
     FancyError, SessionInfoExt,
 };
-use mas_data_model::SiteConfig;
+use mas_data_model::{AuditContext, AuditEventKind, SiteConfig};
 use mas_policy::Policy;
 use mas_router::UrlBuilder;
 use mas_storage::{
@@ -114,9 +114,27 @@ pub(crate) async fn post(
         return Err(anyhow::anyhow!("Invalid email address").into());
     }
 
+    let audit = AuditContext::user(&session.user).with_ip_address(activity_tracker.ip());
+
     // Run the email policy
     let res = policy.evaluate_email(&form.email).await?;
     if !res.valid() {
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::PolicyDenied,
+                Some(&session.user),
+                serde_json::json!({
+                    "operation": "add_email",
+                    "email": form.email,
+                    "violations": res.violations.iter().map(|v| &v.msg).collect::<Vec<_>>(),
+                }),
+            )
+            .await?;
+        repo.save().await?;
+
         return Err(FancyError::new(
             ErrorContext::new()
                 .with_description(format!("Email address {:?} denied by policy", form.email))
@@ -129,9 +147,23 @@ pub(crate) async fn post(
     let user_email = if let Some(user_email) = existing_user_email {
         user_email
     } else {
-        repo.user_email()
+        let user_email = repo
+            .user_email()
             .add(&mut rng, &clock, &session.user, form.email)
-            .await?
+            .await?;
+
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::UserEmailAdded,
+                Some(&session.user),
+                serde_json::json!({ "email": user_email.email }),
+            )
+            .await?;
+
+        user_email
     };
 
     // If the email was not confirmed, send a confirmation email & redirect to the
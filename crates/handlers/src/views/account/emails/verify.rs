@@ -14,9 +14,10 @@ use mas_axum_utils::{
     csrf::{CsrfExt, ProtectedForm},
     FancyError, SessionInfoExt,
 };
+use mas_data_model::WebhookEventKind;
 use mas_router::UrlBuilder;
 use mas_storage::{
-    job::{JobRepositoryExt, ProvisionUserJob},
+    job::{DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob},
     user::UserEmailRepository,
     BoxClock, BoxRepository, BoxRng, RepositoryAccess,
 };
@@ -137,7 +138,8 @@ pub(crate) async fn post(
         repo.user_email().set_as_primary(&user_email).await?;
     }
 
-    repo.user_email()
+    let user_email = repo
+        .user_email()
         .mark_as_verified(&clock, user_email)
         .await?;
 
@@ -145,6 +147,13 @@ pub(crate) async fn post(
         .schedule_job(ProvisionUserJob::new(&session.user))
         .await?;
 
+    repo.job()
+        .schedule_job(
+            DispatchWebhookEventJob::new(WebhookEventKind::UserEmailVerified, &session.user)
+                .with_data(serde_json::json!({ "email": user_email.email })),
+        )
+        .await?;
+
     repo.save().await?;
 
     activity_tracker
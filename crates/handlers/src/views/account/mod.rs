@@ -5,3 +5,5 @@
 // Please see LICENSE in the repository root for full details.
 
 pub mod emails;
+pub mod passkeys;
+pub mod totp;
@@ -0,0 +1,531 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use anyhow::Context;
+use axum::{
+    extract::{Form, State},
+    response::{Html, IntoResponse, Response},
+};
+use axum_extra::typed_header::TypedHeader;
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{AuditContext, AuditEventKind, BrowserSession, SiteConfig};
+use mas_i18n::DataLocale;
+use mas_router::{PostAuthAction, UrlBuilder};
+use mas_storage::{user::UserPasskeyRepository, BoxClock, BoxRepository, BoxRng};
+use mas_templates::{
+    AccountPasskeysContext, AccountPasskeysFormField, FieldError, FormError, FormState,
+    TemplateContext, Templates,
+};
+use serde::Deserialize;
+use ulid::Ulid;
+use webauthn_rs::prelude::{Passkey, PasskeyRegistration, RegisterPublicKeyCredential};
+
+use crate::{
+    webauthn::{
+        encode_credential_id, has_recent_authentication, relying_party, user_handle, CeremonyState,
+    },
+    BoundActivityTracker, PreferredLanguage,
+};
+
+/// Name of the cookie holding the state of the passkey registration
+static COOKIE_NAME: &str = "passkey-registration";
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub(crate) enum PasskeysForm {
+    /// Start registering a new passkey with the given name
+    Start { name: String },
+
+    /// Finish the registration with the credential created by the browser
+    Finish { credential: String },
+
+    /// Remove a passkey
+    Remove { passkey_id: Ulid },
+}
+
+#[tracing::instrument(name = "handlers.views.account_passkeys.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let passkeys = repo.user_passkey().all(&session.user).await?;
+    let ctx = AccountPasskeysContext::new(passkeys);
+
+    render(locale, ctx, session, &csrf_token, cookie_jar, &templates)
+}
+
+#[tracing::instrument(name = "handlers.views.account_passkeys.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    Form(form): Form<ProtectedForm<PasskeysForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    // Passkeys are enough to log in, so the user must have authenticated
+    // recently to add or remove one
+    if !has_recent_authentication(&mut repo, &clock, &session).await? {
+        let reauth = mas_router::Reauth::and_then(PostAuthAction::ManagePasskeys);
+        return Ok((cookie_jar, url_builder.redirect(&reauth)).into_response());
+    }
+
+    let audit = AuditContext::user(&session.user)
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.map(|TypedHeader(ua)| ua.as_str().to_owned()));
+
+    let webauthn = relying_party(&url_builder, &site_config)?;
+
+    match form {
+        PasskeysForm::Start { name } => {
+            let passkeys = repo.user_passkey().all(&session.user).await?;
+            let name = name.trim().to_owned();
+
+            if name.is_empty() {
+                let form_state = FormState::default()
+                    .with_error_on_field(AccountPasskeysFormField::Name, FieldError::Required);
+                let ctx = AccountPasskeysContext::new(passkeys).with_form_state(form_state);
+                return render(locale, ctx, session, &csrf_token, cookie_jar, &templates);
+            }
+
+            // Don't let the user register the same authenticator twice
+            let exclude = passkeys
+                .iter()
+                .map(|p| serde_json::from_str::<Passkey>(&p.credential))
+                .map(|p| p.map(|p| p.cred_id().clone()))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let (options, state) = webauthn.start_passkey_registration(
+                user_handle(&session.user),
+                &session.user.username,
+                &session.user.username,
+                Some(exclude),
+            )?;
+
+            let cookie_jar = CeremonyState::save(cookie_jar, COOKIE_NAME, &clock, (name, state));
+            let ctx = AccountPasskeysContext::new(passkeys)
+                .with_creation_options(serde_json::to_string(&options)?);
+
+            render(locale, ctx, session, &csrf_token, cookie_jar, &templates)
+        }
+
+        PasskeysForm::Finish { credential } => {
+            let state = CeremonyState::<(String, PasskeyRegistration)>::load(
+                &cookie_jar,
+                COOKIE_NAME,
+                &clock,
+            );
+            let credential = serde_json::from_str::<RegisterPublicKeyCredential>(&credential).ok();
+            let cookie_jar = cookie_jar.remove(COOKIE_NAME);
+
+            let passkey = if let (Some((name, state)), Some(credential)) = (state, credential) {
+                match webauthn.finish_passkey_registration(&credential, &state) {
+                    Ok(passkey) => Some((name, passkey)),
+                    Err(e) => {
+                        tracing::warn!(
+                            error = &e as &dyn std::error::Error,
+                            "Invalid passkey registration"
+                        );
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            let Some((name, passkey)) = passkey else {
+                let passkeys = repo.user_passkey().all(&session.user).await?;
+                let form_state =
+                    FormState::default().with_error_on_form(FormError::InvalidCredentials);
+                let ctx = AccountPasskeysContext::new(passkeys).with_form_state(form_state);
+                return render(locale, ctx, session, &csrf_token, cookie_jar, &templates);
+            };
+
+            let credential_id = encode_credential_id(passkey.cred_id().as_ref());
+
+            // Credential IDs are unique, and might already be registered to another user
+            if repo
+                .user_passkey()
+                .find_by_credential_id(&credential_id)
+                .await?
+                .is_some()
+            {
+                let passkeys = repo.user_passkey().all(&session.user).await?;
+                let form_state =
+                    FormState::default().with_error_on_form(FormError::InvalidCredentials);
+                let ctx = AccountPasskeysContext::new(passkeys).with_form_state(form_state);
+                return render(locale, ctx, session, &csrf_token, cookie_jar, &templates);
+            }
+
+            let credential = serde_json::to_string(&passkey)?;
+            let user_passkey = repo
+                .user_passkey()
+                .add(
+                    &mut rng,
+                    &clock,
+                    &session.user,
+                    name,
+                    credential_id,
+                    credential,
+                )
+                .await?;
+
+            repo.audit_event()
+                .add(
+                    &mut rng,
+                    &clock,
+                    &audit,
+                    AuditEventKind::UserPasskeyAdded,
+                    Some(&session.user),
+                    serde_json::json!({ "passkey_id": user_passkey.id, "name": user_passkey.name }),
+                )
+                .await?;
+
+            repo.save().await?;
+
+            let destination = mas_router::AccountPasskeys;
+            Ok((cookie_jar, url_builder.redirect(&destination)).into_response())
+        }
+
+        PasskeysForm::Remove { passkey_id } => {
+            let user_passkey = repo
+                .user_passkey()
+                .lookup(passkey_id)
+                .await?
+                .filter(|p| p.user_id == session.user.id)
+                .context("Could not find passkey")?;
+
+            repo.user_passkey().remove(user_passkey.clone()).await?;
+
+            repo.audit_event()
+                .add(
+                    &mut rng,
+                    &clock,
+                    &audit,
+                    AuditEventKind::UserPasskeyRemoved,
+                    Some(&session.user),
+                    serde_json::json!({ "passkey_id": user_passkey.id, "name": user_passkey.name }),
+                )
+                .await?;
+
+            repo.save().await?;
+
+            let destination = mas_router::AccountPasskeys;
+            Ok((cookie_jar, url_builder.redirect(&destination)).into_response())
+        }
+    }
+}
+
+fn render(
+    locale: DataLocale,
+    ctx: AccountPasskeysContext,
+    session: BrowserSession,
+    csrf_token: &CsrfToken,
+    cookie_jar: CookieJar,
+    templates: &Templates,
+) -> Result<Response, FancyError> {
+    let ctx = ctx
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_account_passkeys(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_axum_utils::SessionInfoExt;
+    use mas_data_model::{AuditEventKind, User};
+    use mas_router::SimpleRoute;
+    use mas_storage::{
+        audit::AuditEventFilter,
+        user::{
+            BrowserSessionRepository, UserPasskeyFilter, UserPasskeyRepository,
+            UserPasswordRepository, UserRepository,
+        },
+        RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        add_passkey, setup, CookieHelper, RequestBuilderExt, ResponseExt, SoftPasskey, TestState,
+    };
+
+    /// Provision a user with a browser session, and save the session cookie.
+    /// If `authenticated` is set, the session is authenticated with a password
+    /// at the current time.
+    async fn login(
+        state: &TestState,
+        cookies: &CookieHelper,
+        username: &str,
+        authenticated: bool,
+    ) -> User {
+        let mut rng = state.rng();
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, username.to_owned())
+            .await
+            .unwrap();
+        let browser_session = repo
+            .browser_session()
+            .add(&mut rng, &state.clock, &user, None)
+            .await
+            .unwrap();
+        if authenticated {
+            let password = repo
+                .user_password()
+                .add(&mut rng, &state.clock, &user, 1, "hash".to_owned(), None)
+                .await
+                .unwrap();
+            repo.browser_session()
+                .authenticate_with_password(&mut rng, &state.clock, &browser_session, &password)
+                .await
+                .unwrap();
+        }
+        repo.save().await.unwrap();
+
+        cookies.import(state.cookie_jar().set_session(&browser_session));
+        user
+    }
+
+    /// Load the passkeys page and extract the CSRF token from it
+    async fn csrf_token(state: &TestState, cookies: &CookieHelper) -> String {
+        let request = Request::get(mas_router::AccountPasskeys::PATH).empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+
+        response
+            .body()
+            .split("name=\"csrf\" value=\"")
+            .nth(1)
+            .unwrap()
+            .split('\"')
+            .next()
+            .unwrap()
+            .to_owned()
+    }
+
+    async fn post(
+        state: &TestState,
+        cookies: &CookieHelper,
+        form: serde_json::Value,
+    ) -> hyper::Response<String> {
+        let request = Request::post(mas_router::AccountPasskeys::PATH).form(form);
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response
+    }
+
+    /// Go through the registration of the given passkey on the account page
+    async fn register(
+        state: &TestState,
+        cookies: &CookieHelper,
+        passkey: &mut SoftPasskey,
+    ) -> hyper::Response<String> {
+        let csrf = csrf_token(state, cookies).await;
+        let response = post(
+            state,
+            cookies,
+            serde_json::json!({ "csrf": csrf, "action": "start", "name": "Laptop" }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+        let options = SoftPasskey::options_from_page(response.body());
+
+        let csrf = csrf_token(state, cookies).await;
+        post(
+            state,
+            cookies,
+            serde_json::json!({
+                "csrf": csrf,
+                "action": "finish",
+                "credential": passkey.register(&options),
+            }),
+        )
+        .await
+    }
+
+    async fn passkey_count(state: &TestState, user: &User) -> usize {
+        let mut repo = state.repository().await.unwrap();
+        let filter = UserPasskeyFilter::new().for_user(user);
+        let count = repo.user_passkey().count(filter).await.unwrap();
+        repo.cancel().await.unwrap();
+        count
+    }
+
+    async fn audit_count(state: &TestState, user: &User, kind: AuditEventKind) -> usize {
+        let mut repo = state.repository().await.unwrap();
+        let filter = AuditEventFilter::new().for_user(user).with_kind(kind);
+        let count = repo.audit_event().count(filter).await.unwrap();
+        repo.cancel().await.unwrap();
+        count
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_register(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let user = login(&state, &cookies, "john", true).await;
+
+        let mut passkey = SoftPasskey::new(&mut state.rng());
+        let response = register(&state, &cookies, &mut passkey).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, mas_router::AccountPasskeys::PATH);
+
+        assert_eq!(passkey_count(&state, &user).await, 1);
+        assert_eq!(
+            audit_count(&state, &user, AuditEventKind::UserPasskeyAdded).await,
+            1
+        );
+    }
+
+    /// Passkeys can only be managed shortly after authenticating
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_requires_recent_authentication(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // A session which was never authenticated is sent to the reauth page
+        let cookies = CookieHelper::new();
+        login(&state, &cookies, "john", false).await;
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({ "csrf": csrf, "action": "start", "name": "Laptop" }),
+        )
+        .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/reauth?kind=manage_passkeys");
+
+        // Same with an authentication which is too old
+        let cookies = CookieHelper::new();
+        let user = login(&state, &cookies, "alice", true).await;
+        let mut passkey = SoftPasskey::new(&mut state.rng());
+        let user_passkey = add_passkey(&state, &user, &mut passkey).await;
+        state
+            .clock
+            .advance(chrono::Duration::try_minutes(6).unwrap());
+
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({ "csrf": csrf, "action": "remove", "passkey_id": user_passkey.id }),
+        )
+        .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/reauth?kind=manage_passkeys");
+
+        assert_eq!(passkey_count(&state, &user).await, 1);
+    }
+
+    /// Credential IDs already registered, even to another user, are refused
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_duplicate_credential_id(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut repo = state.repository().await.unwrap();
+        let alice = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+        let credential_id = [0x42; 16];
+        let mut passkey = SoftPasskey::new(&mut state.rng()).with_credential_id(&credential_id);
+        add_passkey(&state, &alice, &mut passkey).await;
+
+        let cookies = CookieHelper::new();
+        let bob = login(&state, &cookies, "bob", true).await;
+        let mut passkey = SoftPasskey::new(&mut state.rng()).with_credential_id(&credential_id);
+        let response = register(&state, &cookies, &mut passkey).await;
+        response.assert_status(StatusCode::OK);
+
+        assert_eq!(passkey_count(&state, &alice).await, 1);
+        assert_eq!(passkey_count(&state, &bob).await, 0);
+        assert_eq!(
+            audit_count(&state, &bob, AuditEventKind::UserPasskeyAdded).await,
+            0
+        );
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_remove(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let user = login(&state, &cookies, "john", true).await;
+        let user_passkey =
+            add_passkey(&state, &user, &mut SoftPasskey::new(&mut state.rng())).await;
+
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({ "csrf": csrf, "action": "remove", "passkey_id": user_passkey.id }),
+        )
+        .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        assert_eq!(passkey_count(&state, &user).await, 0);
+        assert_eq!(
+            audit_count(&state, &user, AuditEventKind::UserPasskeyRemoved).await,
+            1
+        );
+    }
+}
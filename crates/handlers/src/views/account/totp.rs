@@ -0,0 +1,591 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use anyhow::Context;
+use axum::{
+    extract::{Form, State},
+    response::{Html, IntoResponse, Response},
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{BrowserSession, SiteConfig, UserTotpSecret};
+use mas_i18n::DataLocale;
+use mas_keystore::Encrypter;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    user::{UserTotpRecoveryCodeRepository, UserTotpRepository},
+    BoxClock, BoxRepository, BoxRng, Clock,
+};
+use mas_templates::{
+    AccountTotpContext, AccountTotpFormField, AccountTotpState, FormError, FormState,
+    TemplateContext, Templates,
+};
+use serde::Deserialize;
+use ulid::Ulid;
+use zeroize::Zeroizing;
+
+use crate::{totp, BoundActivityTracker, PreferredLanguage};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub(crate) enum TotpForm {
+    /// Start enrolling a new TOTP secret
+    Start,
+
+    /// Confirm the enrollment of a secret with a code
+    Confirm { secret_id: Ulid, code: String },
+
+    /// Replace the recovery codes of the active secret
+    RegenerateRecoveryCodes { code: String },
+
+    /// Remove the active secret
+    Remove { code: String },
+}
+
+#[tracing::instrument(name = "handlers.views.account_totp.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let state = current_state(&mut repo, &session).await?;
+
+    let ctx = AccountTotpContext::new(state)
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_account_totp(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.account_totp.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(encrypter): State<Encrypter>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<TotpForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    // Replacing the active secret would bypass the code check done when
+    // removing it, so the user has to remove it first
+    let enrolling = matches!(form, TotpForm::Start | TotpForm::Confirm { .. });
+    if enrolling && repo.user_totp().active(&session.user).await?.is_some() {
+        let state = current_state(&mut repo, &session).await?;
+        return render(
+            locale,
+            state,
+            FormState::default(),
+            session,
+            &csrf_token,
+            cookie_jar,
+            &templates,
+        );
+    }
+
+    let remove = matches!(form, TotpForm::Remove { .. });
+    let (state, form_state) = match form {
+        TotpForm::Start => {
+            let secret = totp::generate_secret(&mut rng);
+            let encrypted_secret = encrypter.encrypt_to_string(&secret)?;
+            let totp_secret = repo
+                .user_totp()
+                .add(&mut rng, &clock, &session.user, encrypted_secret)
+                .await?;
+
+            (
+                enrolling_state(&totp_secret, &secret, &site_config, &session),
+                FormState::default(),
+            )
+        }
+
+        TotpForm::Confirm { secret_id, code } => {
+            let totp_secret = repo
+                .user_totp()
+                .lookup(secret_id)
+                .await?
+                .filter(|s| s.user_id == session.user.id && !s.is_confirmed())
+                .context("Could not find TOTP secret")?;
+
+            let secret = Zeroizing::new(encrypter.decrypt_string(&totp_secret.encrypted_secret)?);
+
+            let confirmed = match totp::verify(&secret, &code, clock.now(), None) {
+                Some(step) => {
+                    let totp_secret = repo
+                        .user_totp()
+                        .confirm(&clock, totp_secret.clone())
+                        .await?;
+                    repo.user_totp().record_use(totp_secret, step).await?
+                }
+                None => None,
+            };
+
+            if let Some(totp_secret) = confirmed {
+                let codes =
+                    replace_recovery_codes(&mut rng, &clock, &mut repo, &encrypter, &totp_secret)
+                        .await?;
+
+                (
+                    AccountTotpState::RecoveryCodes { codes },
+                    FormState::default(),
+                )
+            } else {
+                (
+                    enrolling_state(&totp_secret, &secret, &site_config, &session),
+                    FormState::default().with_error_on_form(FormError::InvalidCode),
+                )
+            }
+        }
+
+        TotpForm::RegenerateRecoveryCodes { code } | TotpForm::Remove { code } => {
+            let totp_secret = repo
+                .user_totp()
+                .active(&session.user)
+                .await?
+                .context("TOTP is not enabled")?;
+
+            let secret = Zeroizing::new(encrypter.decrypt_string(&totp_secret.encrypted_secret)?);
+
+            let used = match totp::verify(&secret, &code, clock.now(), totp_secret.last_used_step) {
+                Some(step) => {
+                    repo.user_totp()
+                        .record_use(totp_secret.clone(), step)
+                        .await?
+                }
+                None => None,
+            };
+            let Some(totp_secret) = used else {
+                let remaining_recovery_codes = repo
+                    .user_totp_recovery_code()
+                    .count_unused(&totp_secret)
+                    .await?;
+
+                let state = AccountTotpState::Enabled {
+                    remaining_recovery_codes,
+                };
+                let form_state = FormState::default().with_error_on_form(FormError::InvalidCode);
+                return render(
+                    locale,
+                    state,
+                    form_state,
+                    session,
+                    &csrf_token,
+                    cookie_jar,
+                    &templates,
+                );
+            };
+
+            if remove {
+                repo.user_totp().remove(&clock, totp_secret).await?;
+                repo.save().await?;
+
+                let account = mas_router::Account::default();
+                return Ok((cookie_jar, url_builder.redirect(&account)).into_response());
+            }
+
+            let codes =
+                replace_recovery_codes(&mut rng, &clock, &mut repo, &encrypter, &totp_secret)
+                    .await?;
+            (
+                AccountTotpState::RecoveryCodes { codes },
+                FormState::default(),
+            )
+        }
+    };
+
+    repo.save().await?;
+
+    render(
+        locale,
+        state,
+        form_state,
+        session,
+        &csrf_token,
+        cookie_jar,
+        &templates,
+    )
+}
+
+/// Get the state of the page for the user of the given session
+async fn current_state(
+    repo: &mut BoxRepository,
+    session: &BrowserSession,
+) -> Result<AccountTotpState, FancyError> {
+    let Some(totp_secret) = repo.user_totp().active(&session.user).await? else {
+        return Ok(AccountTotpState::Disabled);
+    };
+
+    let remaining_recovery_codes = repo
+        .user_totp_recovery_code()
+        .count_unused(&totp_secret)
+        .await?;
+
+    Ok(AccountTotpState::Enabled {
+        remaining_recovery_codes,
+    })
+}
+
+/// Build the state showing the secret being enrolled
+fn enrolling_state(
+    totp_secret: &UserTotpSecret,
+    secret: &[u8],
+    site_config: &SiteConfig,
+    session: &BrowserSession,
+) -> AccountTotpState {
+    AccountTotpState::Enrolling {
+        secret_id: totp_secret.id,
+        secret: totp::encode_secret(secret),
+        provisioning_uri: totp::provisioning_uri(
+            secret,
+            &site_config.server_name,
+            &session.user.username,
+        ),
+    }
+}
+
+/// Generate a new set of recovery codes for the secret, and save their hashes
+async fn replace_recovery_codes(
+    rng: &mut BoxRng,
+    clock: &impl Clock,
+    repo: &mut BoxRepository,
+    encrypter: &Encrypter,
+    totp_secret: &UserTotpSecret,
+) -> Result<Vec<String>, FancyError> {
+    let codes = totp::generate_recovery_codes(rng);
+    let hashed_codes = codes
+        .iter()
+        .map(|c| totp::hash_recovery_code(encrypter, c))
+        .collect();
+
+    repo.user_totp_recovery_code()
+        .replace_all(rng, clock, totp_secret, hashed_codes)
+        .await?;
+
+    Ok(codes)
+}
+
+fn render(
+    locale: DataLocale,
+    state: AccountTotpState,
+    form_state: FormState<AccountTotpFormField>,
+    session: BrowserSession,
+    csrf_token: &CsrfToken,
+    cookie_jar: CookieJar,
+    templates: &Templates,
+) -> Result<Response, FancyError> {
+    let ctx = AccountTotpContext::new(state)
+        .with_form_state(form_state)
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_account_totp(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_axum_utils::SessionInfoExt;
+    use mas_data_model::User;
+    use mas_router::Route;
+    use mas_storage::{
+        user::{BrowserSessionRepository, UserRepository, UserTotpRepository},
+        Clock, RepositoryAccess,
+    };
+    use sqlx::PgPool;
+    use ulid::Ulid;
+
+    use crate::{
+        test_utils::{setup, CookieHelper, RequestBuilderExt, ResponseExt, TestState},
+        totp,
+    };
+
+    /// Provision a user with a browser session, and save the session cookie
+    async fn login(state: &TestState, cookies: &CookieHelper) -> User {
+        let mut rng = state.rng();
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, "john".to_owned())
+            .await
+            .unwrap();
+        let browser_session = repo
+            .browser_session()
+            .add(&mut rng, &state.clock, &user, None)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        cookies.import(state.cookie_jar().set_session(&browser_session));
+        user
+    }
+
+    /// Load the TOTP page and extract the CSRF token from it
+    async fn csrf_token(state: &TestState, cookies: &CookieHelper) -> String {
+        let request = Request::get(&*mas_router::AccountTotp::default().path_and_query()).empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+
+        response
+            .body()
+            .split("name=\"csrf\" value=\"")
+            .nth(1)
+            .unwrap()
+            .split('\"')
+            .next()
+            .unwrap()
+            .to_owned()
+    }
+
+    async fn post(
+        state: &TestState,
+        cookies: &CookieHelper,
+        form: serde_json::Value,
+    ) -> hyper::Response<String> {
+        let request =
+            Request::post(&*mas_router::AccountTotp::default().path_and_query()).form(form);
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_enroll(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let user = login(&state, &cookies).await;
+
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({ "csrf": csrf, "action": "start" }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+
+        // Find the secret being enrolled from the page
+        let secret_id: Ulid = response
+            .body()
+            .split("name=\"secret_id\" value=\"")
+            .nth(1)
+            .unwrap()
+            .split('\"')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut repo = state.repository().await.unwrap();
+        let totp_secret = repo.user_totp().lookup(secret_id).await.unwrap().unwrap();
+        let secret = state
+            .encrypter
+            .decrypt_string(&totp_secret.encrypted_secret)
+            .unwrap();
+        repo.cancel().await.unwrap();
+
+        // A wrong code is rejected
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({
+                "csrf": csrf,
+                "action": "confirm",
+                "secret_id": secret_id,
+                "code": "000000",
+            }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+        let mut repo = state.repository().await.unwrap();
+        assert!(repo.user_totp().active(&user).await.unwrap().is_none());
+        repo.cancel().await.unwrap();
+
+        // The right one enables TOTP and shows the recovery codes
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({
+                "csrf": csrf,
+                "action": "confirm",
+                "secret_id": secret_id,
+                "code": totp::generate_code(&secret, state.clock.now()),
+            }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("font-mono text-center\">"));
+
+        let mut repo = state.repository().await.unwrap();
+        let active = repo.user_totp().active(&user).await.unwrap().unwrap();
+        assert_eq!(active.id, secret_id);
+    }
+
+    /// Enrolling a new secret while one is active must not replace it, as
+    /// that would skip the code check done on removal
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_replace_rejected(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+        let cookies = CookieHelper::new();
+        let user = login(&state, &cookies).await;
+
+        // Enable TOTP for the user, and prepare a second unconfirmed secret
+        let mut repo = state.repository().await.unwrap();
+        let secret = totp::generate_secret(&mut rng);
+        let encrypted_secret = state.encrypter.encrypt_to_string(&secret).unwrap();
+        let active = repo
+            .user_totp()
+            .add(&mut rng, &state.clock, &user, encrypted_secret.clone())
+            .await
+            .unwrap();
+        let active = repo
+            .user_totp()
+            .confirm(&state.clock, active)
+            .await
+            .unwrap();
+        let pending = repo
+            .user_totp()
+            .add(&mut rng, &state.clock, &user, encrypted_secret)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Starting an enrollment doesn't create a new secret
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({ "csrf": csrf, "action": "start" }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+        assert!(!response.body().contains("name=\"secret_id\""));
+
+        // Confirming the other secret doesn't replace the active one
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({
+                "csrf": csrf,
+                "action": "confirm",
+                "secret_id": pending.id,
+                "code": totp::generate_code(&secret, state.clock.now()),
+            }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+
+        let mut repo = state.repository().await.unwrap();
+        let current = repo.user_totp().active(&user).await.unwrap().unwrap();
+        assert_eq!(current.id, active.id);
+        let pending = repo.user_totp().lookup(pending.id).await.unwrap().unwrap();
+        assert!(!pending.is_confirmed());
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_remove(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+        let cookies = CookieHelper::new();
+        let user = login(&state, &cookies).await;
+
+        let mut repo = state.repository().await.unwrap();
+        let secret = totp::generate_secret(&mut rng);
+        let encrypted_secret = state.encrypter.encrypt_to_string(&secret).unwrap();
+        let totp_secret = repo
+            .user_totp()
+            .add(&mut rng, &state.clock, &user, encrypted_secret)
+            .await
+            .unwrap();
+        repo.user_totp()
+            .confirm(&state.clock, totp_secret)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Removing the secret needs a valid code
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({ "csrf": csrf, "action": "remove", "code": "000000" }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+        let mut repo = state.repository().await.unwrap();
+        assert!(repo.user_totp().active(&user).await.unwrap().is_some());
+        repo.cancel().await.unwrap();
+
+        let csrf = csrf_token(&state, &cookies).await;
+        let response = post(
+            &state,
+            &cookies,
+            serde_json::json!({
+                "csrf": csrf,
+                "action": "remove",
+                "code": totp::generate_code(&secret, state.clock.now()),
+            }),
+        )
+        .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let mut repo = state.repository().await.unwrap();
+        assert!(repo.user_totp().active(&user).await.unwrap().is_none());
+    }
+}
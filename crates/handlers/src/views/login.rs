@@ -15,8 +15,10 @@ use mas_axum_utils::{
     csrf::{CsrfExt, CsrfToken, ProtectedForm},
     FancyError, SessionInfoExt,
 };
-use mas_data_model::{BrowserSession, UserAgent};
+use mas_config::LdapMode;
+use mas_data_model::{AuditActor, AuditContext, AuditEventKind, User, UserAgent, UserLdapLink};
 use mas_i18n::DataLocale;
+use mas_matrix::BoxHomeserverConnection;
 use mas_router::{UpstreamOAuth2Authorize, UrlBuilder};
 use mas_storage::{
     upstream_oauth2::UpstreamOAuthProviderRepository,
@@ -30,10 +32,14 @@ use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
 use zeroize::Zeroizing;
 
-use super::shared::OptionalPostAuthAction;
+use super::{
+    login_totp::{has_second_factor, PendingTotpLogin},
+    shared::OptionalPostAuthAction,
+};
 use crate::{
-    passwords::PasswordManager, BoundActivityTracker, Limiter, PreferredLanguage,
-    RequesterFingerprint, SiteConfig,
+    ldap::{Ldap, PasswordCredential},
+    passwords::PasswordManager,
+    BoundActivityTracker, Limiter, PreferredLanguage, RequesterFingerprint, SiteConfig,
 };
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -112,6 +118,7 @@ pub(crate) async fn post(
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
     State(limiter): State<Limiter>,
+    State(homeserver): State<BoxHomeserverConnection>,
     mut repo: BoxRepository,
     activity_tracker: BoundActivityTracker,
     requester: RequesterFingerprint,
@@ -121,6 +128,9 @@ pub(crate) async fn post(
     Form(form): Form<ProtectedForm<LoginForm>>,
 ) -> Result<Response, FancyError> {
     let user_agent = user_agent.map(|ua| UserAgent::parse(ua.as_str().to_owned()));
+    let audit = AuditContext::anonymous()
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.as_ref().map(|ua| ua.raw.clone()));
     if !site_config.password_login_enabled {
         // XXX: is it necessary to have better errors here?
         return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
@@ -162,31 +172,40 @@ pub(crate) async fn post(
         return Ok((cookie_jar, Html(content)).into_response());
     }
 
-    match login(
+    let (user, credential) = match login(
         password_manager,
         &mut repo,
-        rng,
+        &mut rng,
         &clock,
         limiter,
         requester,
+        &homeserver,
         &form.username,
         &form.password,
-        user_agent,
     )
     .await
     {
-        Ok(session_info) => {
-            repo.save().await?;
-
-            activity_tracker
-                .record_browser_session(&clock, &session_info)
-                .await;
-
-            let cookie_jar = cookie_jar.set_session(&session_info);
-            let reply = query.go_next(&url_builder);
-            Ok((cookie_jar, reply).into_response())
-        }
+        Ok(res) => res,
         Err(e) => {
+            // An internal error may have happened halfway through provisioning a
+            // user from the directory, so nothing gets saved in that case
+            let internal = matches!(e, FormError::Internal);
+
+            // Record the failed attempt against the targeted user, if it exists
+            if !internal {
+                let user = repo.user().find_by_username(&form.username).await?;
+                repo.audit_event()
+                    .add(
+                        &mut rng,
+                        &clock,
+                        &audit,
+                        AuditEventKind::UserLoginFailed,
+                        user.as_ref(),
+                        serde_json::json!({ "username": form.username, "error": e }),
+                    )
+                    .await?;
+            }
+
             let state = state.with_error_on_form(e);
 
             let content = render(
@@ -199,30 +218,121 @@ pub(crate) async fn post(
             )
             .await?;
 
-            Ok((cookie_jar, Html(content)).into_response())
+            if internal {
+                repo.cancel().await?;
+            } else {
+                repo.save().await?;
+            }
+
+            return Ok((cookie_jar, Html(content)).into_response());
         }
+    };
+
+    // If the user enrolled a TOTP second factor or a passkey, ask for it before
+    // starting the session
+    if has_second_factor(&mut repo, &user).await? {
+        // This saves the upgraded password hash, if any
+        repo.save().await?;
+
+        let cookie_jar = PendingTotpLogin::new(&clock, &user, &credential).save(cookie_jar);
+        let destination = mas_router::LoginTotp::from(query.post_auth_action);
+        return Ok((cookie_jar, url_builder.redirect(&destination)).into_response());
     }
+
+    // Start a new session
+    let session_info = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, user_agent)
+        .await?;
+
+    // And mark it as authenticated by the password
+    credential
+        .authenticate_session(&mut repo, &mut rng, &clock, &session_info)
+        .await?;
+
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &AuditContext {
+                actor: AuditActor::User { user_id: user.id },
+                ..audit
+            },
+            AuditEventKind::UserLogin,
+            Some(&user),
+            serde_json::json!({ "method": credential.method(), "session_id": session_info.id }),
+        )
+        .await?;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &session_info)
+        .await;
+
+    let cookie_jar = cookie_jar.set_session(&session_info);
+    let reply = query.go_next(&url_builder);
+    Ok((cookie_jar, reply).into_response())
 }
 
 // TODO: move that logic elsewhere?
 async fn login(
     password_manager: PasswordManager,
-    repo: &mut impl RepositoryAccess,
+    repo: &mut BoxRepository,
     mut rng: impl Rng + CryptoRng + Send,
     clock: &impl Clock,
     limiter: Limiter,
     requester: RequesterFingerprint,
+    homeserver: &BoxHomeserverConnection,
     username: &str,
     password: &str,
-    user_agent: Option<UserAgent>,
-) -> Result<BrowserSession, FormError> {
+) -> Result<(User, PasswordCredential), FormError> {
+    let ldap = password_manager.ldap();
+
+    // In primary mode, the directory is checked first, and passwords stored in the
+    // database are only used for users which are not in the directory
+    if let Some(ldap) = ldap.filter(|ldap| ldap.mode() == LdapMode::Primary) {
+        if let Some((user, link)) = ldap_login(
+            ldap, repo, &mut rng, clock, &limiter, requester, homeserver, username, password,
+        )
+        .await?
+        {
+            return Ok((user, PasswordCredential::Ldap(link)));
+        }
+    }
+
     // XXX: we're loosing the error context here
     // First, lookup the user
     let user = repo
         .user()
         .find_by_username(username)
         .await
-        .map_err(|_e| FormError::Internal)?
+        .map_err(|_e| FormError::Internal)?;
+
+    // And its password
+    let user_password = match &user {
+        Some(user) => repo
+            .user_password()
+            .active(user)
+            .await
+            .map_err(|_e| FormError::Internal)?,
+        None => None,
+    };
+
+    // In fallback mode, the directory is checked for users which don't have a
+    // password in the database
+    if let Some(ldap) = ldap.filter(|ldap| ldap.mode() == LdapMode::Fallback) {
+        if user_password.is_none() {
+            let (user, link) = ldap_login(
+                ldap, repo, &mut rng, clock, &limiter, requester, homeserver, username, password,
+            )
+            .await?
+            .ok_or(FormError::InvalidCredentials)?;
+            return Ok((user, PasswordCredential::Ldap(link)));
+        }
+    }
+
+    let user = user
         .filter(mas_data_model::User::is_valid)
         .ok_or(FormError::InvalidCredentials)?;
 
@@ -232,13 +342,7 @@ async fn login(
         FormError::RateLimitExceeded
     })?;
 
-    // And its password
-    let user_password = repo
-        .user_password()
-        .active(&user)
-        .await
-        .map_err(|_e| FormError::Internal)?
-        .ok_or(FormError::InvalidCredentials)?;
+    let user_password = user_password.ok_or(FormError::InvalidCredentials)?;
 
     let password = Zeroizing::new(password.as_bytes().to_vec());
 
@@ -270,20 +374,42 @@ async fn login(
         user_password
     };
 
-    // Start a new session
-    let user_session = repo
-        .browser_session()
-        .add(&mut rng, clock, &user, user_agent)
-        .await
-        .map_err(|_| FormError::Internal)?;
+    Ok((user, PasswordCredential::Password(user_password)))
+}
 
-    // And mark it as authenticated by the password
-    repo.browser_session()
-        .authenticate_with_password(&mut rng, clock, &user_session, &user_password)
+/// Check the password against the directory
+///
+/// Returns `None` if the user is not in the directory
+async fn ldap_login(
+    ldap: &Ldap,
+    repo: &mut BoxRepository,
+    rng: &mut (impl Rng + CryptoRng + Send),
+    clock: &impl Clock,
+    limiter: &Limiter,
+    requester: RequesterFingerprint,
+    homeserver: &BoxHomeserverConnection,
+    username: &str,
+    password: &str,
+) -> Result<Option<(User, UserLdapLink)>, FormError> {
+    limiter
+        .check_password_for_username(requester, username)
+        .map_err(|e| {
+            tracing::warn!(error = &e as &dyn std::error::Error);
+            FormError::RateLimitExceeded
+        })?;
+
+    // Nothing can fail after this call: the user it may provision from the
+    // directory must only be saved once the login succeeds
+    crate::ldap::login(ldap, repo, rng, clock, homeserver, username, password)
         .await
-        .map_err(|_| FormError::Internal)?;
-
-    Ok(user_session)
+        .map_err(|e| {
+            tracing::warn!(error = &e as &dyn std::error::Error, "LDAP login failed");
+            if e.is_internal() {
+                FormError::Internal
+            } else {
+                FormError::InvalidCredentials
+            }
+        })
 }
 
 async fn render(
@@ -312,10 +438,11 @@ mod test {
         header::{CONTENT_TYPE, LOCATION},
         Request, StatusCode,
     };
-    use mas_data_model::UpstreamOAuthProviderClaimsImports;
+    use mas_data_model::{AuditEventKind, UpstreamOAuthProviderClaimsImports};
     use mas_iana::oauth::OAuthClientAuthenticationMethod;
     use mas_router::Route;
     use mas_storage::{
+        audit::{AuditEventFilter, AuditEventRepository},
         upstream_oauth2::{UpstreamOAuthProviderParams, UpstreamOAuthProviderRepository},
         RepositoryAccess,
     };
@@ -368,10 +495,10 @@ mod test {
                     issuer: "https://first.com/".to_owned(),
                     human_name: Some("First Ltd.".to_owned()),
                     brand_name: None,
-                    scope: [OPENID].into_iter().collect(),
-                    token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
+                    scope: Some([OPENID].into_iter().collect()),
+                    token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::None),
                     token_endpoint_signing_alg: None,
-                    client_id: "client".to_owned(),
+                    client_id: Some("client".to_owned()),
                     encrypted_client_secret: None,
                     claims_imports: UpstreamOAuthProviderClaimsImports::default(),
                     authorization_endpoint_override: None,
@@ -380,6 +507,7 @@ mod test {
                     discovery_mode: mas_data_model::UpstreamOAuthProviderDiscoveryMode::Oidc,
                     pkce_mode: mas_data_model::UpstreamOAuthProviderPkceMode::Auto,
                     additional_authorization_parameters: Vec::new(),
+                    saml: None,
                 },
             )
             .await
@@ -403,10 +531,10 @@ mod test {
                     issuer: "https://second.com/".to_owned(),
                     human_name: None,
                     brand_name: None,
-                    scope: [OPENID].into_iter().collect(),
-                    token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
+                    scope: Some([OPENID].into_iter().collect()),
+                    token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::None),
                     token_endpoint_signing_alg: None,
-                    client_id: "client".to_owned(),
+                    client_id: Some("client".to_owned()),
                     encrypted_client_secret: None,
                     claims_imports: UpstreamOAuthProviderClaimsImports::default(),
                     authorization_endpoint_override: None,
@@ -415,6 +543,7 @@ mod test {
                     discovery_mode: mas_data_model::UpstreamOAuthProviderDiscoveryMode::Oidc,
                     pkce_mode: mas_data_model::UpstreamOAuthProviderPkceMode::Auto,
                     additional_authorization_parameters: Vec::new(),
+                    saml: None,
                 },
             )
             .await
@@ -478,6 +607,17 @@ mod test {
             .next()
             .unwrap();
 
+        // Submit the login form with the wrong password first
+        let request = Request::post("/login").form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "password": "hunter3",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+
         // Submit the login form
         let request = Request::post("/login").form(serde_json::json!({
             "csrf": csrf_token,
@@ -497,6 +637,22 @@ mod test {
         response.assert_status(StatusCode::OK);
         response.assert_header_value(CONTENT_TYPE, "text/html; charset=utf-8");
         assert!(response.body().contains("john"));
+
+        // Both attempts should have been recorded in the audit log
+        let mut repo = state.repository().await.unwrap();
+        let filter = AuditEventFilter::new().for_user(&user);
+        let failed = repo
+            .audit_event()
+            .count(filter.with_kind(AuditEventKind::UserLoginFailed))
+            .await
+            .unwrap();
+        assert_eq!(failed, 1);
+        let succeeded = repo
+            .audit_event()
+            .count(filter.with_kind(AuditEventKind::UserLogin))
+            .await
+            .unwrap();
+        assert_eq!(succeeded, 1);
     }
 
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
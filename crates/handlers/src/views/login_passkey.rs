@@ -0,0 +1,472 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use axum::{
+    extract::{Form, Query, State},
+    response::{Html, IntoResponse, Response},
+};
+use axum_extra::typed_header::TypedHeader;
+use hyper::StatusCode;
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{
+    AuditActor, AuditContext, AuditEventKind, SiteConfig, User, UserAgent, UserPasskey,
+};
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    user::{BrowserSessionRepository, UserPasskeyRepository, UserRepository},
+    BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
+};
+use mas_templates::{FormError, FormState, LoginPasskeyContext, TemplateContext, Templates};
+use serde::Deserialize;
+use webauthn_rs::{
+    prelude::{DiscoverableAuthentication, DiscoverableKey, Passkey, PublicKeyCredential},
+    Webauthn,
+};
+
+use super::shared::OptionalPostAuthAction;
+use crate::{
+    webauthn::{encode_credential_id, relying_party, user_handle, CeremonyState},
+    BoundActivityTracker, PreferredLanguage,
+};
+
+/// Name of the cookie holding the state of the passkey authentication
+static COOKIE_NAME: &str = "passkey-login";
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct LoginPasskeyForm {
+    credential: String,
+}
+
+#[tracing::instrument(name = "handlers.views.login_passkey.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    if let Some(session) = maybe_session {
+        activity_tracker
+            .record_browser_session(&clock, &session)
+            .await;
+
+        let reply = query.go_next(&url_builder);
+        return Ok((cookie_jar, reply).into_response());
+    };
+
+    // Passkeys are only offered alongside passwords
+    if !site_config.password_login_enabled {
+        let login = mas_router::Login::from(query.post_auth_action);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    }
+
+    let webauthn = relying_party(&url_builder, &site_config)?;
+    let (ctx, cookie_jar) = start_authentication(&clock, &webauthn, cookie_jar)?;
+
+    let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.login_passkey.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    Form(form): Form<ProtectedForm<LoginPasskeyForm>>,
+) -> Result<Response, FancyError> {
+    let user_agent = user_agent.map(|ua| UserAgent::parse(ua.as_str().to_owned()));
+    let audit = AuditContext::anonymous()
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.as_ref().map(|ua| ua.raw.clone()));
+    if !site_config.password_login_enabled {
+        return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
+    }
+
+    let form = cookie_jar.verify_form(&clock, form)?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let webauthn = relying_party(&url_builder, &site_config)?;
+
+    let state = CeremonyState::<DiscoverableAuthentication>::load(&cookie_jar, COOKIE_NAME, &clock);
+    let credential = serde_json::from_str::<PublicKeyCredential>(&form.credential).ok();
+
+    let verified = if let (Some(state), Some(credential)) = (state, credential) {
+        verify_passkey(&mut repo, &webauthn, state, &credential).await?
+    } else {
+        None
+    };
+
+    let Some((user, user_passkey, passkey)) = verified else {
+        // We don't know which user this was for, so the event isn't attached to any
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::UserLoginFailed,
+                None,
+                serde_json::json!({ "method": "passkey", "error": FormError::InvalidCredentials }),
+            )
+            .await?;
+
+        let (ctx, cookie_jar) = start_authentication(&clock, &webauthn, cookie_jar)?;
+        let ctx = ctx.with_form_state(
+            FormState::default().with_error_on_form(FormError::InvalidCredentials),
+        );
+        let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+        repo.save().await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    };
+
+    let credential = serde_json::to_string(&passkey)?;
+    let user_passkey = repo
+        .user_passkey()
+        .record_use(&clock, user_passkey, credential)
+        .await?;
+
+    // Start a new session, authenticated by the passkey
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, user_agent)
+        .await?;
+
+    repo.browser_session()
+        .authenticate_with_passkey(&mut rng, &clock, &session, &user_passkey, false)
+        .await?;
+
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &AuditContext {
+                actor: AuditActor::User { user_id: user.id },
+                ..audit
+            },
+            AuditEventKind::UserLogin,
+            Some(&user),
+            serde_json::json!({ "method": "passkey", "session_id": session.id }),
+        )
+        .await?;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let cookie_jar = cookie_jar.remove(COOKIE_NAME).set_session(&session);
+    let reply = query.go_next(&url_builder);
+    Ok((cookie_jar, reply).into_response())
+}
+
+/// Start a new discoverable authentication, saving its state in the cookie
+/// jar
+fn start_authentication(
+    clock: &impl Clock,
+    webauthn: &Webauthn,
+    cookie_jar: CookieJar,
+) -> Result<(LoginPasskeyContext, CookieJar), FancyError> {
+    let (options, state) = webauthn.start_discoverable_authentication()?;
+    let cookie_jar = CeremonyState::save(cookie_jar, COOKIE_NAME, clock, state);
+    let ctx = LoginPasskeyContext::new(serde_json::to_string(&options)?);
+    Ok((ctx, cookie_jar))
+}
+
+/// Check the response of the browser to the passkey authentication, and find
+/// the user it belongs to
+async fn verify_passkey(
+    repo: &mut BoxRepository,
+    webauthn: &Webauthn,
+    state: DiscoverableAuthentication,
+    credential: &PublicKeyCredential,
+) -> Result<Option<(User, UserPasskey, Passkey)>, FancyError> {
+    let (handle, credential_id) = match webauthn.identify_discoverable_authentication(credential) {
+        Ok(identified) => identified,
+        Err(e) => {
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                "Invalid passkey response"
+            );
+            return Ok(None);
+        }
+    };
+
+    let credential_id = encode_credential_id(credential_id);
+    let Some(user_passkey) = repo
+        .user_passkey()
+        .find_by_credential_id(&credential_id)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    // The user handle returned by the authenticator must match the owner of the
+    // passkey, and the user must still be allowed to log in
+    let Some(user) = repo
+        .user()
+        .lookup(user_passkey.user_id)
+        .await?
+        .filter(|user| user_handle(user) == handle)
+        .filter(User::is_valid)
+    else {
+        return Ok(None);
+    };
+
+    let mut passkey: Passkey = serde_json::from_str(&user_passkey.credential)?;
+    let result = match webauthn.finish_discoverable_authentication(
+        credential,
+        state,
+        &[DiscoverableKey::from(&passkey)],
+    ) {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                "Invalid passkey response"
+            );
+            return Ok(None);
+        }
+    };
+
+    // Keep track of the signature counter, to detect cloned authenticators
+    passkey.update_credential(&result);
+
+    Ok(Some((user, user_passkey, passkey)))
+}
+
+async fn render(
+    locale: DataLocale,
+    ctx: LoginPasskeyContext,
+    action: OptionalPostAuthAction,
+    csrf_token: CsrfToken,
+    repo: &mut impl RepositoryAccess,
+    templates: &Templates,
+) -> Result<String, FancyError> {
+    let next = action.load_context(repo).await?;
+    let ctx = if let Some(next) = next {
+        ctx.with_post_action(next)
+    } else {
+        ctx
+    };
+    let ctx = ctx.with_csrf(csrf_token.form_value()).with_language(locale);
+
+    let content = templates.render_login_passkey(&ctx)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_data_model::User;
+    use mas_router::Route;
+    use mas_storage::{
+        user::{
+            BrowserSessionFilter, BrowserSessionRepository, UserPasskeyRepository, UserRepository,
+        },
+        RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        add_passkey, setup, CookieHelper, RequestBuilderExt, ResponseExt, SoftPasskey, TestState,
+    };
+
+    async fn add_user(state: &TestState, username: &str) -> User {
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, username.to_owned())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+        user
+    }
+
+    /// Load the login page, returning the CSRF token and the request options
+    async fn start(state: &TestState, cookies: &CookieHelper) -> (String, serde_json::Value) {
+        let request = Request::get(&*mas_router::LoginPasskey::default().path_and_query()).empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+
+        let csrf_token = response
+            .body()
+            .split("name=\"csrf\" value=\"")
+            .nth(1)
+            .unwrap()
+            .split('\"')
+            .next()
+            .unwrap()
+            .to_owned();
+        let options = SoftPasskey::options_from_page(response.body());
+        (csrf_token, options)
+    }
+
+    async fn finish(
+        state: &TestState,
+        cookies: &CookieHelper,
+        csrf_token: &str,
+        credential: &str,
+    ) -> hyper::Response<String> {
+        let request = Request::post(&*mas_router::LoginPasskey::default().path_and_query()).form(
+            serde_json::json!({
+                "csrf": csrf_token,
+                "credential": credential,
+            }),
+        );
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response
+    }
+
+    async fn session_count(state: &TestState, user: &User) -> usize {
+        let mut repo = state.repository().await.unwrap();
+        let filter = BrowserSessionFilter::new().for_user(user).active_only();
+        let count = repo.browser_session().count(filter).await.unwrap();
+        repo.cancel().await.unwrap();
+        count
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_login(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let user = add_user(&state, "john").await;
+        let mut passkey = SoftPasskey::new(&mut state.rng());
+        let user_passkey = add_passkey(&state, &user, &mut passkey).await;
+
+        let (csrf_token, options) = start(&state, &cookies).await;
+        let response = finish(
+            &state,
+            &cookies,
+            &csrf_token,
+            &passkey.authenticate(&options),
+        )
+        .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/");
+
+        // A session was started, and the use of the passkey recorded
+        assert_eq!(session_count(&state, &user).await, 1);
+        let mut repo = state.repository().await.unwrap();
+        let user_passkey = repo
+            .user_passkey()
+            .lookup(user_passkey.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(user_passkey.last_used_at.is_some());
+    }
+
+    /// The user handle returned by the authenticator must be the one of the
+    /// owner of the passkey
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_wrong_user_handle(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let alice = add_user(&state, "alice").await;
+        let bob = add_user(&state, "bob").await;
+        let mut passkey = SoftPasskey::new(&mut state.rng());
+        add_passkey(&state, &alice, &mut passkey).await;
+
+        passkey.set_user_handle(&bob.id.to_bytes());
+
+        let (csrf_token, options) = start(&state, &cookies).await;
+        let response = finish(
+            &state,
+            &cookies,
+            &csrf_token,
+            &passkey.authenticate(&options),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+
+        assert_eq!(session_count(&state, &alice).await, 0);
+        assert_eq!(session_count(&state, &bob).await, 0);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_expired_ceremony(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let user = add_user(&state, "john").await;
+        let mut passkey = SoftPasskey::new(&mut state.rng());
+        add_passkey(&state, &user, &mut passkey).await;
+
+        let (csrf_token, options) = start(&state, &cookies).await;
+
+        // The challenge is only valid for 5 minutes
+        state
+            .clock
+            .advance(chrono::Duration::try_minutes(6).unwrap());
+
+        let response = finish(
+            &state,
+            &cookies,
+            &csrf_token,
+            &passkey.authenticate(&options),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+        assert_eq!(session_count(&state, &user).await, 0);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_locked_user(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let user = add_user(&state, "john").await;
+        let mut passkey = SoftPasskey::new(&mut state.rng());
+        add_passkey(&state, &user, &mut passkey).await;
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo.user().lock(&state.clock, user).await.unwrap();
+        repo.save().await.unwrap();
+
+        let (csrf_token, options) = start(&state, &cookies).await;
+        let response = finish(
+            &state,
+            &cookies,
+            &csrf_token,
+            &passkey.authenticate(&options),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+        assert_eq!(session_count(&state, &user).await, 0);
+    }
+}
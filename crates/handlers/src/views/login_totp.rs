@@ -0,0 +1,793 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use axum::{
+    extract::{Form, Query, State},
+    response::{Html, IntoResponse, Response},
+};
+use axum_extra::typed_header::TypedHeader;
+use chrono::{DateTime, Duration, Utc};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{
+    AuditActor, AuditContext, AuditEventKind, BrowserSession, SiteConfig, User, UserAgent,
+    UserPasskey, UserTotpRecoveryCode, UserTotpSecret,
+};
+use mas_i18n::DataLocale;
+use mas_keystore::Encrypter;
+use mas_router::{PostAuthAction, UrlBuilder};
+use mas_storage::{
+    user::{
+        BrowserSessionRepository, UserLdapLinkRepository, UserPasskeyFilter, UserPasskeyRepository,
+        UserPasswordRepository, UserRepository, UserTotpRecoveryCodeRepository, UserTotpRepository,
+    },
+    BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess, RepositoryError,
+};
+use mas_templates::{FormError, FormState, LoginTotpContext, TemplateContext, Templates};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+use webauthn_rs::{
+    prelude::{Passkey, PasskeyAuthentication, PublicKeyCredential},
+    Webauthn,
+};
+use zeroize::Zeroizing;
+
+use super::shared::OptionalPostAuthAction;
+use crate::{
+    ldap::PasswordCredential,
+    totp,
+    webauthn::{encode_credential_id, relying_party, CeremonyState},
+    BoundActivityTracker, Limiter, PreferredLanguage, RequesterFingerprint,
+};
+
+/// Name of the cookie holding the pending login
+static COOKIE_NAME: &str = "pending-totp-login";
+
+/// Name of the cookie holding the state of the passkey authentication
+static PASSKEY_COOKIE_NAME: &str = "passkey-second-factor";
+
+/// Pending logins expire after 10 minutes
+static PENDING_LOGIN_MAX_TIME: Duration = Duration::microseconds(10 * 60 * 1000 * 1000);
+
+/// A login which passed the password check, and is waiting for the user to
+/// provide their second factor, either a TOTP code or a passkey
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct PendingTotpLogin {
+    user_id: Ulid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user_password_id: Option<Ulid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user_ldap_link_id: Option<Ulid>,
+    /// The browser session to authenticate again, when re-authenticating
+    /// instead of logging in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    browser_session_id: Option<Ulid>,
+    created_at: DateTime<Utc>,
+}
+
+impl PendingTotpLogin {
+    /// Start a pending login for the given user, who was authenticated with the
+    /// given password
+    pub fn new(clock: &impl Clock, user: &User, credential: &PasswordCredential) -> Self {
+        let (user_password_id, user_ldap_link_id) = match credential {
+            PasswordCredential::Password(user_password) => (Some(user_password.id), None),
+            PasswordCredential::Ldap(user_ldap_link) => (None, Some(user_ldap_link.id)),
+        };
+
+        Self {
+            user_id: user.id,
+            user_password_id,
+            user_ldap_link_id,
+            browser_session_id: None,
+            created_at: clock.now(),
+        }
+    }
+
+    /// Authenticate the given existing session once the second factor is
+    /// checked, instead of starting a new one
+    #[must_use]
+    pub fn for_session(mut self, session: &BrowserSession) -> Self {
+        self.browser_session_id = Some(session.id);
+        self
+    }
+
+    /// Load the pending login from the cookie jar, if it is still valid
+    pub fn load(cookie_jar: &CookieJar, clock: &impl Clock) -> Option<Self> {
+        match cookie_jar.load::<Self>(COOKIE_NAME) {
+            Ok(Some(pending)) if clock.now() - pending.created_at <= PENDING_LOGIN_MAX_TIME => {
+                Some(pending)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!("Invalid pending TOTP login cookie: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Save the pending login to the cookie jar
+    pub fn save(&self, cookie_jar: CookieJar) -> CookieJar {
+        cookie_jar.save(COOKIE_NAME, self, false)
+    }
+
+    /// Remove the pending login from the cookie jar
+    pub fn clear(cookie_jar: CookieJar) -> CookieJar {
+        cookie_jar.remove(COOKIE_NAME)
+    }
+}
+
+/// Check whether the user enrolled a second factor, either a TOTP secret or a
+/// passkey
+pub(crate) async fn has_second_factor(
+    repo: &mut BoxRepository,
+    user: &User,
+) -> Result<bool, RepositoryError> {
+    if repo.user_totp().active(user).await?.is_some() {
+        return Ok(true);
+    }
+
+    let passkeys = repo
+        .user_passkey()
+        .count(UserPasskeyFilter::new().for_user(user))
+        .await?;
+    Ok(passkeys > 0)
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub(crate) enum LoginTotpForm {
+    Code { code: String },
+    RecoveryCode { recovery_code: String },
+    Passkey { credential: String },
+}
+
+/// Load the user and its password from the pending login, checking that they
+/// are still valid
+async fn load_pending(
+    repo: &mut BoxRepository,
+    pending: &PendingTotpLogin,
+) -> Result<Option<(User, PasswordCredential)>, RepositoryError> {
+    let Some(user) = repo
+        .user()
+        .lookup(pending.user_id)
+        .await?
+        .filter(User::is_valid)
+    else {
+        return Ok(None);
+    };
+
+    let credential = match (pending.user_password_id, pending.user_ldap_link_id) {
+        // The password may have changed since the first step
+        (Some(user_password_id), None) => repo
+            .user_password()
+            .active(&user)
+            .await?
+            .filter(|p| p.id == user_password_id)
+            .map(PasswordCredential::Password),
+
+        (None, Some(user_ldap_link_id)) => repo
+            .user_ldap_link()
+            .lookup(user_ldap_link_id)
+            .await?
+            .filter(|l| l.user_id == user.id)
+            .map(PasswordCredential::Ldap),
+
+        _ => None,
+    };
+
+    Ok(credential.map(|credential| (user, credential)))
+}
+
+/// Send the user back to the login page, dropping the pending login
+fn restart_login(
+    cookie_jar: CookieJar,
+    url_builder: &UrlBuilder,
+    post_auth_action: Option<PostAuthAction>,
+) -> Response {
+    let cookie_jar = PendingTotpLogin::clear(cookie_jar).remove(PASSKEY_COOKIE_NAME);
+    let login = mas_router::Login::from(post_auth_action);
+    (cookie_jar, url_builder.redirect(&login)).into_response()
+}
+
+/// Build the context of the page with the second factors enrolled by the user.
+///
+/// If the user has passkeys, this starts a new passkey authentication, saving
+/// its state in the cookie jar.
+async fn second_factors_context(
+    clock: &impl Clock,
+    repo: &mut BoxRepository,
+    webauthn: &Webauthn,
+    cookie_jar: CookieJar,
+    user: &User,
+) -> Result<(LoginTotpContext, CookieJar), FancyError> {
+    let mut ctx = LoginTotpContext::new(user.username.clone());
+
+    if repo.user_totp().active(user).await?.is_some() {
+        ctx = ctx.with_totp();
+    }
+
+    let passkeys = repo
+        .user_passkey()
+        .all(user)
+        .await?
+        .iter()
+        .map(|p| serde_json::from_str::<Passkey>(&p.credential))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if passkeys.is_empty() {
+        return Ok((ctx, cookie_jar));
+    }
+
+    let (options, state) = webauthn.start_passkey_authentication(&passkeys)?;
+    let cookie_jar = CeremonyState::save(cookie_jar, PASSKEY_COOKIE_NAME, clock, state);
+    let ctx = ctx.with_passkey_options(serde_json::to_string(&options)?);
+
+    Ok((ctx, cookie_jar))
+}
+
+#[tracing::instrument(name = "handlers.views.login_totp.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let pending = PendingTotpLogin::load(&cookie_jar, &clock);
+    let loaded = if let Some(pending) = &pending {
+        load_pending(&mut repo, pending).await?
+    } else {
+        None
+    };
+
+    let Some((user, _first_factor)) = loaded else {
+        return Ok(restart_login(
+            cookie_jar,
+            &url_builder,
+            query.post_auth_action,
+        ));
+    };
+
+    let webauthn = relying_party(&url_builder, &site_config)?;
+    let (ctx, cookie_jar) =
+        second_factors_context(&clock, &mut repo, &webauthn, cookie_jar, &user).await?;
+
+    let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.login_totp.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(limiter): State<Limiter>,
+    State(encrypter): State<Encrypter>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    requester: RequesterFingerprint,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    Form(form): Form<ProtectedForm<LoginTotpForm>>,
+) -> Result<Response, FancyError> {
+    let user_agent = user_agent.map(|ua| UserAgent::parse(ua.as_str().to_owned()));
+    let audit = AuditContext::anonymous()
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.as_ref().map(|ua| ua.raw.clone()));
+    let form = cookie_jar.verify_form(&clock, form)?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let pending = PendingTotpLogin::load(&cookie_jar, &clock);
+    let loaded = if let Some(pending) = &pending {
+        load_pending(&mut repo, pending).await?
+    } else {
+        None
+    };
+
+    let Some((user, first_factor)) = loaded else {
+        return Ok(restart_login(
+            cookie_jar,
+            &url_builder,
+            query.post_auth_action,
+        ));
+    };
+
+    let webauthn = relying_party(&url_builder, &site_config)?;
+
+    // Guessing codes is rate-limited like guessing passwords
+    if let Err(e) = limiter.check_password(requester, &user) {
+        tracing::warn!(error = &e as &dyn std::error::Error);
+        let (ctx, cookie_jar) =
+            second_factors_context(&clock, &mut repo, &webauthn, cookie_jar, &user).await?;
+        let content = render(
+            locale,
+            ctx.with_form_state(
+                FormState::default().with_error_on_form(FormError::RateLimitExceeded),
+            ),
+            query,
+            csrf_token,
+            &mut repo,
+            &templates,
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    // Check the second factor. Nothing is recorded yet, except the time step
+    // of an accepted TOTP code, which is claimed atomically so that the same
+    // code can't be used twice
+    let with_passkey = matches!(form, LoginTotpForm::Passkey { .. });
+    let method = match form {
+        LoginTotpForm::Code { .. } => "totp",
+        LoginTotpForm::RecoveryCode { .. } => "recovery_code",
+        LoginTotpForm::Passkey { .. } => "passkey",
+    };
+    let second_factor = match form {
+        LoginTotpForm::Code { code } => {
+            // The second factor may have been removed in the meantime
+            let Some(totp_secret) = repo.user_totp().active(&user).await? else {
+                return Ok(restart_login(
+                    cookie_jar,
+                    &url_builder,
+                    query.post_auth_action,
+                ));
+            };
+
+            let secret = Zeroizing::new(encrypter.decrypt_string(&totp_secret.encrypted_secret)?);
+            match totp::verify(&secret, &code, clock.now(), totp_secret.last_used_step) {
+                Some(step) => repo
+                    .user_totp()
+                    .record_use(totp_secret, step)
+                    .await?
+                    .map(SecondFactor::Totp),
+                None => None,
+            }
+        }
+
+        LoginTotpForm::RecoveryCode { recovery_code } => {
+            let Some(totp_secret) = repo.user_totp().active(&user).await? else {
+                return Ok(restart_login(
+                    cookie_jar,
+                    &url_builder,
+                    query.post_auth_action,
+                ));
+            };
+
+            let hashed_code = totp::hash_recovery_code(&encrypter, &recovery_code);
+            repo.user_totp_recovery_code()
+                .find_unused(&totp_secret, &hashed_code)
+                .await?
+                .map(SecondFactor::RecoveryCode)
+        }
+
+        LoginTotpForm::Passkey { credential } => {
+            let state = CeremonyState::<PasskeyAuthentication>::load(
+                &cookie_jar,
+                PASSKEY_COOKIE_NAME,
+                &clock,
+            );
+            let credential = serde_json::from_str::<PublicKeyCredential>(&credential).ok();
+
+            if let (Some(state), Some(credential)) = (state, credential) {
+                verify_passkey(&mut repo, &webauthn, &user, &state, &credential).await?
+            } else {
+                None
+            }
+        }
+    };
+
+    let Some(second_factor) = second_factor else {
+        let error = if with_passkey {
+            FormError::InvalidCredentials
+        } else {
+            FormError::InvalidCode
+        };
+
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::UserLoginFailed,
+                Some(&user),
+                serde_json::json!({ "username": user.username, "method": method, "error": error }),
+            )
+            .await?;
+
+        let (ctx, cookie_jar) =
+            second_factors_context(&clock, &mut repo, &webauthn, cookie_jar, &user).await?;
+        let content = render(
+            locale,
+            ctx.with_form_state(FormState::default().with_error_on_form(error)),
+            query,
+            csrf_token,
+            &mut repo,
+            &templates,
+        )
+        .await?;
+
+        repo.save().await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    };
+
+    // Start a new session, or pick the one being re-authenticated, and
+    // authenticate it with both the password and the second factor
+    let reauth_session_id = pending.as_ref().and_then(|p| p.browser_session_id);
+    let session = if let Some(session_id) = reauth_session_id {
+        let session = repo
+            .browser_session()
+            .lookup(session_id)
+            .await?
+            .filter(|s| s.active() && s.user.id == user.id);
+        let Some(session) = session else {
+            return Ok(restart_login(
+                cookie_jar,
+                &url_builder,
+                query.post_auth_action,
+            ));
+        };
+        session
+    } else {
+        repo.browser_session()
+            .add(&mut rng, &clock, &user, user_agent)
+            .await?
+    };
+
+    first_factor
+        .authenticate_session(&mut repo, &mut rng, &clock, &session)
+        .await?;
+
+    match second_factor {
+        SecondFactor::Totp(totp_secret) => {
+            repo.browser_session()
+                .authenticate_with_totp(&mut rng, &clock, &session, &totp_secret)
+                .await?;
+        }
+
+        SecondFactor::RecoveryCode(recovery_code) => {
+            let recovery_code = repo
+                .user_totp_recovery_code()
+                .consume(&clock, recovery_code)
+                .await?;
+            repo.browser_session()
+                .authenticate_with_totp_recovery_code(&mut rng, &clock, &session, &recovery_code)
+                .await?;
+        }
+
+        SecondFactor::Passkey(user_passkey, passkey) => {
+            let credential = serde_json::to_string(&passkey)?;
+            let user_passkey = repo
+                .user_passkey()
+                .record_use(&clock, user_passkey, credential)
+                .await?;
+            repo.browser_session()
+                .authenticate_with_passkey(&mut rng, &clock, &session, &user_passkey, true)
+                .await?;
+        }
+    }
+
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &AuditContext {
+                actor: AuditActor::User { user_id: user.id },
+                ..audit
+            },
+            AuditEventKind::UserLogin,
+            Some(&user),
+            serde_json::json!({ "method": method, "session_id": session.id }),
+        )
+        .await?;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let cookie_jar = PendingTotpLogin::clear(cookie_jar)
+        .remove(PASSKEY_COOKIE_NAME)
+        .set_session(&session);
+    let reply = query.go_next(&url_builder);
+    Ok((cookie_jar, reply).into_response())
+}
+
+/// Check the response of the browser to the passkey authentication, making
+/// sure the passkey belongs to the user
+async fn verify_passkey(
+    repo: &mut BoxRepository,
+    webauthn: &Webauthn,
+    user: &User,
+    state: &PasskeyAuthentication,
+    credential: &PublicKeyCredential,
+) -> Result<Option<SecondFactor>, FancyError> {
+    let result = match webauthn.finish_passkey_authentication(credential, state) {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                "Invalid passkey response"
+            );
+            return Ok(None);
+        }
+    };
+
+    let credential_id = encode_credential_id(result.cred_id().as_ref());
+    let Some(user_passkey) = repo
+        .user_passkey()
+        .find_by_credential_id(&credential_id)
+        .await?
+        .filter(|p| p.user_id == user.id)
+    else {
+        return Ok(None);
+    };
+
+    // Keep track of the signature counter, to detect cloned authenticators
+    let mut passkey: Passkey = serde_json::from_str(&user_passkey.credential)?;
+    passkey.update_credential(&result);
+
+    Ok(Some(SecondFactor::Passkey(user_passkey, Box::new(passkey))))
+}
+
+/// A second factor which was successfully checked
+enum SecondFactor {
+    /// A TOTP code, with its time step already recorded
+    Totp(UserTotpSecret),
+
+    /// An unused recovery code
+    RecoveryCode(UserTotpRecoveryCode),
+
+    /// A passkey, with its updated credential
+    Passkey(UserPasskey, Box<Passkey>),
+}
+
+async fn render(
+    locale: DataLocale,
+    ctx: LoginTotpContext,
+    action: OptionalPostAuthAction,
+    csrf_token: CsrfToken,
+    repo: &mut impl RepositoryAccess,
+    templates: &Templates,
+) -> Result<String, FancyError> {
+    let next = action.load_context(repo).await?;
+    let ctx = if let Some(next) = next {
+        ctx.with_post_action(next)
+    } else {
+        ctx
+    };
+    let ctx = ctx.with_csrf(csrf_token.form_value()).with_language(locale);
+
+    let content = templates.render_login_totp(&ctx)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_data_model::User;
+    use mas_router::Route;
+    use mas_storage::{
+        user::{
+            BrowserSessionFilter, BrowserSessionRepository, UserPasswordRepository, UserRepository,
+            UserTotpRecoveryCodeRepository, UserTotpRepository,
+        },
+        Clock, RepositoryAccess,
+    };
+    use sqlx::PgPool;
+    use zeroize::Zeroizing;
+
+    use crate::{
+        test_utils::{setup, CookieHelper, RequestBuilderExt, ResponseExt, TestState},
+        totp,
+    };
+
+    /// Provision a user with a password, an active TOTP secret and a recovery
+    /// code
+    async fn provision(state: &TestState) -> (User, Zeroizing<Vec<u8>>) {
+        let mut rng = state.rng();
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, "john".to_owned())
+            .await
+            .unwrap();
+        let (version, hash) = state
+            .password_manager
+            .hash(&mut rng, Zeroizing::new(b"hunter2".to_vec()))
+            .await
+            .unwrap();
+        repo.user_password()
+            .add(&mut rng, &state.clock, &user, version, hash, None)
+            .await
+            .unwrap();
+
+        let secret = totp::generate_secret(&mut rng);
+        let encrypted_secret = state.encrypter.encrypt_to_string(&secret).unwrap();
+        let totp_secret = repo
+            .user_totp()
+            .add(&mut rng, &state.clock, &user, encrypted_secret)
+            .await
+            .unwrap();
+        let totp_secret = repo
+            .user_totp()
+            .confirm(&state.clock, totp_secret)
+            .await
+            .unwrap();
+        repo.user_totp_recovery_code()
+            .replace_all(
+                &mut rng,
+                &state.clock,
+                &totp_secret,
+                vec![totp::hash_recovery_code(
+                    &state.encrypter,
+                    "abcd-efgh-ijkl-mnop",
+                )],
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        (user, secret)
+    }
+
+    /// Submit the login form with the right password, and get the CSRF token
+    /// to use on the second factor page
+    async fn password_step(state: &TestState, cookies: &CookieHelper) -> String {
+        let request = cookies.with_cookies(Request::get("/login").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response
+            .body()
+            .split("name=\"csrf\" value=\"")
+            .nth(1)
+            .unwrap()
+            .split('\"')
+            .next()
+            .unwrap()
+            .to_owned();
+
+        let request = Request::post("/login").form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "password": "hunter2",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/login/totp");
+
+        csrf_token
+    }
+
+    async fn submit(
+        state: &TestState,
+        cookies: &CookieHelper,
+        form: serde_json::Value,
+    ) -> hyper::Response<String> {
+        let request = Request::post(&*mas_router::LoginTotp::default().path_and_query()).form(form);
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_login_with_totp(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let (user, secret) = provision(&state).await;
+
+        let csrf_token = password_step(&state, &cookies).await;
+
+        // The password alone doesn't start a session
+        let mut repo = state.repository().await.unwrap();
+        let sessions = BrowserSessionFilter::new().for_user(&user);
+        assert_eq!(repo.browser_session().count(sessions).await.unwrap(), 0);
+        repo.cancel().await.unwrap();
+
+        // The second factor page shows the code field
+        let request = cookies.with_cookies(Request::get("/login/totp").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("name=\"code\""));
+
+        // A wrong code is rejected
+        let response = submit(
+            &state,
+            &cookies,
+            serde_json::json!({ "csrf": csrf_token, "action": "code", "code": "000000" }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+
+        // The right one starts a session authenticated with both factors
+        let code = totp::generate_code(&secret, state.clock.now());
+        let response = submit(
+            &state,
+            &cookies,
+            serde_json::json!({ "csrf": csrf_token, "action": "code", "code": code }),
+        )
+        .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        // The session is started, and the time step of the code is recorded
+        let mut repo = state.repository().await.unwrap();
+        assert_eq!(repo.browser_session().count(sessions).await.unwrap(), 1);
+        let totp_secret = repo.user_totp().active(&user).await.unwrap().unwrap();
+        assert!(totp_secret.last_used_step.is_some());
+        repo.cancel().await.unwrap();
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_login_with_recovery_code(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let (user, _secret) = provision(&state).await;
+
+        let csrf_token = password_step(&state, &cookies).await;
+
+        // The code is accepted however it is typed
+        let response = submit(
+            &state,
+            &cookies,
+            serde_json::json!({
+                "csrf": csrf_token,
+                "action": "recovery_code",
+                "recovery_code": "ABCD EFGH IJKL MNOP",
+            }),
+        )
+        .await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        let mut repo = state.repository().await.unwrap();
+        let totp_secret = repo.user_totp().active(&user).await.unwrap().unwrap();
+        assert_eq!(
+            repo.user_totp_recovery_code()
+                .count_unused(&totp_secret)
+                .await
+                .unwrap(),
+            0
+        );
+        repo.cancel().await.unwrap();
+
+        // It can only be used once
+        let cookies = CookieHelper::new();
+        let csrf_token = password_step(&state, &cookies).await;
+        let response = submit(
+            &state,
+            &cookies,
+            serde_json::json!({
+                "csrf": csrf_token,
+                "action": "recovery_code",
+                "recovery_code": "abcd-efgh-ijkl-mnop",
+            }),
+        )
+        .await;
+        response.assert_status(StatusCode::OK);
+    }
+}
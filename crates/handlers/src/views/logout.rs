@@ -8,23 +8,27 @@ use axum::{
     extract::{Form, State},
     response::IntoResponse,
 };
+use axum_extra::typed_header::TypedHeader;
 use mas_axum_utils::{
     cookies::CookieJar,
     csrf::{CsrfExt, ProtectedForm},
     FancyError, SessionInfoExt,
 };
+use mas_data_model::{AuditContext, AuditEventKind};
 use mas_router::{PostAuthAction, UrlBuilder};
-use mas_storage::{user::BrowserSessionRepository, BoxClock, BoxRepository};
+use mas_storage::{user::BrowserSessionRepository, BoxClock, BoxRepository, BoxRng};
 
 use crate::BoundActivityTracker;
 
 #[tracing::instrument(name = "handlers.views.logout.post", skip_all, err)]
 pub(crate) async fn post(
+    mut rng: BoxRng,
     clock: BoxClock,
     mut repo: BoxRepository,
     cookie_jar: CookieJar,
     State(url_builder): State<UrlBuilder>,
     activity_tracker: BoundActivityTracker,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
     Form(form): Form<ProtectedForm<Option<PostAuthAction>>>,
 ) -> Result<impl IntoResponse, FancyError> {
     let form = cookie_jar.verify_form(&clock, form)?;
@@ -38,7 +42,22 @@ pub(crate) async fn post(
             .record_browser_session(&clock, &session)
             .await;
 
-        repo.browser_session().finish(&clock, session).await?;
+        let session = repo.browser_session().finish(&clock, session).await?;
+
+        let audit = AuditContext::user(&session.user)
+            .with_ip_address(activity_tracker.ip())
+            .with_user_agent(user_agent.map(|TypedHeader(ua)| ua.as_str().to_owned()));
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &audit,
+                AuditEventKind::SessionEnded,
+                Some(&session.user),
+                serde_json::json!({ "session_type": "browser", "session_id": session.id }),
+            )
+            .await?;
+
         cookie_jar = cookie_jar.update_session_info(&session_info.mark_session_ended());
     }
 
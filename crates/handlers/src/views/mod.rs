@@ -8,6 +8,8 @@ pub mod account;
 pub mod app;
 pub mod index;
 pub mod login;
+pub mod login_passkey;
+pub mod login_totp;
 pub mod logout;
 pub mod reauth;
 pub mod recovery;
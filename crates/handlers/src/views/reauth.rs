@@ -24,8 +24,14 @@ use mas_templates::{ReauthContext, TemplateContext, Templates};
 use serde::Deserialize;
 use zeroize::Zeroizing;
 
-use super::shared::OptionalPostAuthAction;
-use crate::{passwords::PasswordManager, BoundActivityTracker, PreferredLanguage, SiteConfig};
+use super::{
+    login_totp::{has_second_factor, PendingTotpLogin},
+    shared::OptionalPostAuthAction,
+};
+use crate::{
+    ldap::PasswordCredential, passwords::PasswordManager, BoundActivityTracker, PreferredLanguage,
+    SiteConfig,
+};
 
 #[derive(Deserialize, Debug)]
 pub(crate) struct ReauthForm {
@@ -151,6 +157,20 @@ pub(crate) async fn post(
         user_password
     };
 
+    // Like on login, users with a second factor have to provide it before the
+    // session is marked as authenticated
+    if has_second_factor(&mut repo, &session.user).await? {
+        // This saves the upgraded password hash, if any
+        repo.save().await?;
+
+        let credential = PasswordCredential::Password(user_password);
+        let cookie_jar = PendingTotpLogin::new(&clock, &session.user, &credential)
+            .for_session(&session)
+            .save(cookie_jar);
+        let destination = mas_router::LoginTotp::from(query.post_auth_action);
+        return Ok((cookie_jar, url_builder.redirect(&destination)).into_response());
+    }
+
     // Mark the session as authenticated by the password
     repo.browser_session()
         .authenticate_with_password(&mut rng, &clock, &session, &user_password)
@@ -162,3 +182,125 @@ pub(crate) async fn post(
     let reply = query.go_next(&url_builder);
     Ok((cookie_jar, reply).into_response())
 }
+
+#[cfg(test)]
+mod test {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_axum_utils::SessionInfoExt;
+    use mas_storage::{
+        user::{
+            BrowserSessionFilter, BrowserSessionRepository, UserPasswordRepository, UserRepository,
+            UserTotpRepository,
+        },
+        Clock, RepositoryAccess,
+    };
+    use sqlx::PgPool;
+    use zeroize::Zeroizing;
+
+    use crate::{
+        test_utils::{setup, CookieHelper, RequestBuilderExt, ResponseExt, TestState},
+        totp,
+    };
+
+    /// Users with a second factor have to provide it again to re-authenticate
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_reauth_with_totp(pool: PgPool) {
+        setup();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+        let cookies = CookieHelper::new();
+
+        // Provision a user with a password, a TOTP secret and a browser session
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &state.clock, "john".to_owned())
+            .await
+            .unwrap();
+        let (version, hash) = state
+            .password_manager
+            .hash(&mut rng, Zeroizing::new(b"hunter2".to_vec()))
+            .await
+            .unwrap();
+        repo.user_password()
+            .add(&mut rng, &state.clock, &user, version, hash, None)
+            .await
+            .unwrap();
+        let secret = totp::generate_secret(&mut rng);
+        let encrypted_secret = state.encrypter.encrypt_to_string(&secret).unwrap();
+        let totp_secret = repo
+            .user_totp()
+            .add(&mut rng, &state.clock, &user, encrypted_secret)
+            .await
+            .unwrap();
+        repo.user_totp()
+            .confirm(&state.clock, totp_secret)
+            .await
+            .unwrap();
+        let browser_session = repo
+            .browser_session()
+            .add(&mut rng, &state.clock, &user, None)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        cookies.import(state.cookie_jar().set_session(&browser_session));
+
+        let request = cookies.with_cookies(Request::get("/reauth").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response
+            .body()
+            .split("name=\"csrf\" value=\"")
+            .nth(1)
+            .unwrap()
+            .split('\"')
+            .next()
+            .unwrap()
+            .to_owned();
+
+        // The password alone doesn't authenticate the session
+        let request = Request::post("/reauth").form(serde_json::json!({
+            "csrf": csrf_token,
+            "password": "hunter2",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/login/totp");
+
+        let mut repo = state.repository().await.unwrap();
+        let authentication = repo
+            .browser_session()
+            .get_last_authentication(&browser_session)
+            .await
+            .unwrap();
+        assert!(authentication.is_none());
+        repo.cancel().await.unwrap();
+
+        // Providing the code authenticates the existing session
+        let request = Request::post("/login/totp").form(serde_json::json!({
+            "csrf": csrf_token,
+            "action": "code",
+            "code": totp::generate_code(&secret, state.clock.now()),
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        let mut repo = state.repository().await.unwrap();
+        let authentication = repo
+            .browser_session()
+            .get_last_authentication(&browser_session)
+            .await
+            .unwrap();
+        assert!(authentication.is_some());
+        let totp_secret = repo.user_totp().active(&user).await.unwrap().unwrap();
+        assert!(totp_secret.last_used_step.is_some());
+        let sessions = BrowserSessionFilter::new().for_user(&user);
+        assert_eq!(repo.browser_session().count(sessions).await.unwrap(), 1);
+    }
+}
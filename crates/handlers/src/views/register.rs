@@ -19,13 +19,15 @@ use mas_axum_utils::{
     http_client_factory::HttpClientFactory,
     FancyError, SessionInfoExt,
 };
-use mas_data_model::{CaptchaConfig, UserAgent};
+use mas_data_model::{
+    AuditActor, AuditContext, AuditEventKind, CaptchaConfig, UserAgent, WebhookEventKind,
+};
 use mas_i18n::DataLocale;
 use mas_matrix::BoxHomeserverConnection;
 use mas_policy::Policy;
 use mas_router::UrlBuilder;
 use mas_storage::{
-    job::{JobRepositoryExt, ProvisionUserJob, VerifyEmailJob},
+    job::{DispatchWebhookEventJob, JobRepositoryExt, ProvisionUserJob, VerifyEmailJob},
     user::{BrowserSessionRepository, UserEmailRepository, UserPasswordRepository, UserRepository},
     BoxClock, BoxRepository, BoxRng, RepositoryAccess,
 };
@@ -126,6 +128,9 @@ pub(crate) async fn post(
     Form(form): Form<ProtectedForm<RegisterForm>>,
 ) -> Result<Response, FancyError> {
     let user_agent = user_agent.map(|ua| UserAgent::parse(ua.as_str().to_owned()));
+    let audit = AuditContext::anonymous()
+        .with_ip_address(activity_tracker.ip())
+        .with_user_agent(user_agent.as_ref().map(|ua| ua.raw.clone()));
     if !site_config.password_registration_enabled {
         return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
     }
@@ -147,6 +152,9 @@ pub(crate) async fn post(
         .await
         .is_ok();
 
+    // Keep track of the policy violations, so that they end up in the audit log
+    let mut policy_violations = Vec::new();
+
     // Validate the form
     let state = {
         let mut state = form.to_form_state();
@@ -213,6 +221,7 @@ pub(crate) async fn post(
             .await?;
 
         for violation in res.violations {
+            policy_violations.push(violation.msg.clone());
             match violation.field.as_deref() {
                 Some("email") => state.add_error_on_field(
                     RegisterFormField::Email,
@@ -261,6 +270,25 @@ pub(crate) async fn post(
         )
         .await?;
 
+        if !policy_violations.is_empty() {
+            repo.audit_event()
+                .add(
+                    &mut rng,
+                    &clock,
+                    &audit,
+                    AuditEventKind::PolicyDenied,
+                    None,
+                    serde_json::json!({
+                        "operation": "register",
+                        "username": form.username,
+                        "email": form.email,
+                        "violations": policy_violations,
+                    }),
+                )
+                .await?;
+            repo.save().await?;
+        }
+
         return Ok((cookie_jar, Html(content)).into_response());
     }
 
@@ -295,6 +323,20 @@ pub(crate) async fn post(
         .authenticate_with_password(&mut rng, &clock, &session, &user_password)
         .await?;
 
+    repo.audit_event()
+        .add(
+            &mut rng,
+            &clock,
+            &AuditContext {
+                actor: AuditActor::User { user_id: user.id },
+                ..audit
+            },
+            AuditEventKind::UserLogin,
+            Some(&user),
+            serde_json::json!({ "method": "registration", "session_id": session.id }),
+        )
+        .await?;
+
     repo.job()
         .schedule_job(VerifyEmailJob::new(&user_email).with_language(locale.to_string()))
         .await?;
@@ -303,6 +345,13 @@ pub(crate) async fn post(
         .schedule_job(ProvisionUserJob::new(&user))
         .await?;
 
+    repo.job()
+        .schedule_job(
+            DispatchWebhookEventJob::new(WebhookEventKind::UserRegistered, &user)
+                .with_data(serde_json::json!({ "method": "password" })),
+        )
+        .await?;
+
     repo.save().await?;
 
     activity_tracker
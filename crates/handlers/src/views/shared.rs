@@ -77,6 +77,8 @@ impl OptionalPostAuthAction {
 
             PostAuthAction::ChangePassword => PostAuthContextInner::ChangePassword,
 
+            PostAuthAction::ManagePasskeys => PostAuthContextInner::ManagePasskeys,
+
             PostAuthAction::LinkUpstream { id } => {
                 let link = repo
                     .upstream_oauth_link()
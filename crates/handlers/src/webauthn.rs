@@ -0,0 +1,109 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Helpers for the `WebAuthn` ceremonies used to register and authenticate with
+//! passkeys
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use chrono::{DateTime, Duration, Utc};
+use mas_axum_utils::cookies::CookieJar;
+use mas_data_model::{BrowserSession, SiteConfig, User};
+use mas_router::UrlBuilder;
+use mas_storage::{user::BrowserSessionRepository, BoxRepository, Clock, RepositoryError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use webauthn_rs::{
+    prelude::{Uuid, WebauthnError},
+    Webauthn, WebauthnBuilder,
+};
+
+/// Ceremonies expire after 5 minutes, which is the default timeout given to
+/// the browser
+static CEREMONY_MAX_TIME: Duration = Duration::microseconds(5 * 60 * 1000 * 1000);
+
+/// Passkeys can be used to log in on their own, so adding or removing one
+/// requires the user to have authenticated in the last 5 minutes
+static MAX_AUTHENTICATION_AGE: Duration = Duration::microseconds(5 * 60 * 1000 * 1000);
+
+/// Build the `WebAuthn` relying party for this server
+///
+/// Passkeys are scoped to the public hostname of the service, so changing it
+/// invalidates all the registered passkeys.
+///
+/// # Errors
+///
+/// Returns an error if the public base URL can't be used as a relying party
+pub fn relying_party(
+    url_builder: &UrlBuilder,
+    site_config: &SiteConfig,
+) -> Result<Webauthn, WebauthnError> {
+    let origin = url_builder.http_base();
+    WebauthnBuilder::new(url_builder.public_hostname(), &origin)?
+        .rp_name(&site_config.server_name)
+        .build()
+}
+
+/// The `WebAuthn` user handle of a user, derived from their ID
+#[must_use]
+pub fn user_handle(user: &User) -> Uuid {
+    Uuid::from_bytes(user.id.to_bytes())
+}
+
+/// Encode a credential ID the way it is saved in the database
+#[must_use]
+pub fn encode_credential_id(credential_id: &[u8]) -> String {
+    Base64UrlUnpadded::encode_string(credential_id)
+}
+
+/// Check whether the browser session was authenticated recently enough to
+/// manage passkeys
+///
+/// # Errors
+///
+/// Returns an error if the repository fails
+pub(crate) async fn has_recent_authentication(
+    repo: &mut BoxRepository,
+    clock: &impl Clock,
+    session: &BrowserSession,
+) -> Result<bool, RepositoryError> {
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(session)
+        .await?;
+
+    Ok(authentication.is_some_and(|auth| clock.now() - auth.created_at <= MAX_AUTHENTICATION_AGE))
+}
+
+/// The state of a `WebAuthn` ceremony in progress, saved in a cookie between
+/// the page issuing the challenge and the form submitting the response
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CeremonyState<T> {
+    state: T,
+    created_at: DateTime<Utc>,
+}
+
+impl<T: Serialize + DeserializeOwned> CeremonyState<T> {
+    /// Save the state of a ceremony in the given cookie
+    pub fn save(cookie_jar: CookieJar, name: &str, clock: &impl Clock, state: T) -> CookieJar {
+        let this = Self {
+            state,
+            created_at: clock.now(),
+        };
+        cookie_jar.save(name, &this, false)
+    }
+
+    /// Load the state of a ceremony from the given cookie, if it didn't expire
+    pub fn load(cookie_jar: &CookieJar, name: &str, clock: &impl Clock) -> Option<T> {
+        match cookie_jar.load::<Self>(name) {
+            Ok(Some(this)) if clock.now() - this.created_at <= CEREMONY_MAX_TIME => {
+                Some(this.state)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!("Invalid WebAuthn ceremony cookie: {}", e);
+                None
+            }
+        }
+    }
+}
@@ -495,6 +495,8 @@ mod oidc_core {
     use super::{Claim, Equality, Timestamp, TokenHash};
 
     pub const AUTH_TIME: Claim<Timestamp> = Claim::new("auth_time");
+    pub const ACR: Claim<String> = Claim::new("acr");
+    pub const AMR: Claim<Vec<String>> = Claim::new("amr");
     pub const NONCE: Claim<String, Equality<str>> = Claim::new("nonce");
     pub const AT_HASH: Claim<String, TokenHash> = Claim::new("at_hash");
     pub const C_HASH: Claim<String, TokenHash> = Claim::new("c_hash");
@@ -525,7 +527,31 @@ mod oidc_core {
     pub const UPDATED_AT: Claim<Timestamp> = Claim::new("updated_at");
 }
 
-pub use self::{oidc_core::*, rfc7519::*};
+/// Claims defined in OIDC.BackChannel sec. 2.4
+/// <https://openid.net/specs/openid-connect-backchannel-1_0.html#LogoutToken>
+mod oidc_backchannel {
+    use std::collections::HashMap;
+
+    use super::Claim;
+
+    pub const SID: Claim<String> = Claim::new("sid");
+    pub const EVENTS: Claim<HashMap<String, serde_json::Value>> = Claim::new("events");
+
+    /// The event type identifying a Logout Token in the `events` claim.
+    pub const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+}
+
+/// Claims defined in RFC9449 sec. 4.2
+/// <https://www.rfc-editor.org/rfc/rfc9449.html#section-4.2>
+mod rfc9449 {
+    use super::Claim;
+
+    pub const HTM: Claim<String> = Claim::new("htm");
+    pub const HTU: Claim<String> = Claim::new("htu");
+    pub const ATH: Claim<String> = Claim::new("ath");
+}
+
+pub use self::{oidc_backchannel::*, oidc_core::*, rfc7519::*, rfc9449::*};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,697 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Key management and content encryption algorithms used by JWE
+//!
+//! Ref: <https://www.rfc-editor.org/rfc/rfc7518.html#section-4>
+
+use aes_gcm::{
+    aead::{consts::U12, AeadInPlace},
+    AesGcm, KeyInit,
+};
+use cbc::cipher::{block_padding::Pkcs7, BlockCipher, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use digest::Mac;
+use elliptic_curve::{ecdh::SharedSecret, PublicKey, SecretKey};
+use mas_iana::jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebKeyEcEllipticCurve};
+use p256::NistP256;
+use p384::NistP384;
+use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
+use sha2::{Sha256, Sha384, Sha512};
+use signature::rand_core::CryptoRngCore;
+use thiserror::Error;
+
+use crate::jwk::{JsonWebKeyPrivateParameters, JsonWebKeyPublicParameters};
+
+type Aes192Gcm = AesGcm<aes::Aes192, U12>;
+
+#[derive(Debug, Error)]
+pub enum EncryptionKeyFromJwkError {
+    #[error("Invalid RSA parameters")]
+    Rsa {
+        #[from]
+        inner: rsa::errors::Error,
+    },
+
+    #[error("Invalid Elliptic Curve parameters")]
+    EllipticCurve {
+        #[from]
+        inner: elliptic_curve::Error,
+    },
+
+    #[error("Unsupported algorithm {alg}")]
+    UnsupportedAlgorithm { alg: JsonWebEncryptionAlg },
+
+    #[error("Key not suitable for algorithm {alg}")]
+    KeyNotSuitable { alg: JsonWebEncryptionAlg },
+}
+
+#[derive(Debug, Error)]
+pub enum EncryptionError {
+    #[error("Unsupported content encryption algorithm {enc}")]
+    UnsupportedContentEncryptionAlgorithm { enc: JsonWebEncryptionEnc },
+
+    #[error("Failed to encrypt the content encryption key")]
+    Rsa {
+        #[from]
+        inner: rsa::errors::Error,
+    },
+
+    #[error("Failed to derive the content encryption key")]
+    KeyDerivation { inner: concat_kdf::Error },
+
+    #[error("Failed to encrypt the content")]
+    ContentEncryption,
+}
+
+#[derive(Debug, Error)]
+pub enum DecryptionError {
+    #[error("Unsupported content encryption algorithm {enc}")]
+    UnsupportedContentEncryptionAlgorithm { enc: JsonWebEncryptionEnc },
+
+    #[error("Failed to decrypt the content encryption key")]
+    Rsa {
+        #[from]
+        inner: rsa::errors::Error,
+    },
+
+    #[error("Failed to derive the content encryption key")]
+    KeyDerivation { inner: concat_kdf::Error },
+
+    #[error("Missing ephemeral public key")]
+    MissingEphemeralKey,
+
+    #[error("Invalid ephemeral public key")]
+    InvalidEphemeralKey,
+
+    #[error("Unexpected encrypted key for direct key agreement")]
+    UnexpectedEncryptedKey,
+
+    #[error("Invalid content encryption key")]
+    InvalidContentEncryptionKey,
+
+    #[error("Invalid initialization vector")]
+    InvalidInitializationVector,
+
+    #[error("Failed to decrypt the content")]
+    ContentDecryption,
+}
+
+/// The result of the key management step when encrypting a JWE
+pub(crate) struct ContentEncryptionKey {
+    /// The content encryption key to use
+    pub cek: Vec<u8>,
+
+    /// The encrypted key to put in the JWE, empty for direct key agreement
+    pub encrypted_key: Vec<u8>,
+
+    /// The ephemeral public key to put in the `epk` header, if any
+    pub epk: Option<JsonWebKeyPublicParameters>,
+}
+
+/// An enum of all supported asymmetric key management algorithms encryption
+/// keys
+#[non_exhaustive]
+pub enum AsymmetricEncryptionKey {
+    RsaOaep(RsaPublicKey),
+    RsaOaep256(RsaPublicKey),
+    EcdhEsP256(PublicKey<NistP256>),
+    EcdhEsP384(PublicKey<NistP384>),
+}
+
+impl AsymmetricEncryptionKey {
+    /// Create a new encryption key for the given key management algorithm from
+    /// the given public JWK parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key parameters are not suitable for the given
+    /// algorithm.
+    pub fn from_jwk_and_alg(
+        params: &JsonWebKeyPublicParameters,
+        alg: &JsonWebEncryptionAlg,
+    ) -> Result<Self, EncryptionKeyFromJwkError> {
+        match (params, alg) {
+            (JsonWebKeyPublicParameters::Rsa(params), JsonWebEncryptionAlg::RsaOaep) => {
+                Ok(Self::RsaOaep(params.try_into()?))
+            }
+
+            (JsonWebKeyPublicParameters::Rsa(params), JsonWebEncryptionAlg::RsaOaep256) => {
+                Ok(Self::RsaOaep256(params.try_into()?))
+            }
+
+            (JsonWebKeyPublicParameters::Ec(params), JsonWebEncryptionAlg::EcdhEs) => {
+                match params.crv {
+                    JsonWebKeyEcEllipticCurve::P256 => Ok(Self::EcdhEsP256(params.try_into()?)),
+                    JsonWebKeyEcEllipticCurve::P384 => Ok(Self::EcdhEsP384(params.try_into()?)),
+                    _ => Err(EncryptionKeyFromJwkError::UnsupportedAlgorithm { alg: alg.clone() }),
+                }
+            }
+
+            (
+                _,
+                JsonWebEncryptionAlg::RsaOaep
+                | JsonWebEncryptionAlg::RsaOaep256
+                | JsonWebEncryptionAlg::EcdhEs,
+            ) => Err(EncryptionKeyFromJwkError::KeyNotSuitable { alg: alg.clone() }),
+
+            _ => Err(EncryptionKeyFromJwkError::UnsupportedAlgorithm { alg: alg.clone() }),
+        }
+    }
+
+    /// Get the key management algorithm used by this key
+    #[must_use]
+    pub const fn alg(&self) -> JsonWebEncryptionAlg {
+        match self {
+            Self::RsaOaep(_) => JsonWebEncryptionAlg::RsaOaep,
+            Self::RsaOaep256(_) => JsonWebEncryptionAlg::RsaOaep256,
+            Self::EcdhEsP256(_) | Self::EcdhEsP384(_) => JsonWebEncryptionAlg::EcdhEs,
+        }
+    }
+
+    /// Generate a content encryption key for the given content encryption
+    /// algorithm, and encrypt or agree on it with this key.
+    pub(crate) fn content_encryption_key(
+        &self,
+        rng: &mut impl CryptoRngCore,
+        enc: &JsonWebEncryptionEnc,
+        apu: Option<&[u8]>,
+        apv: Option<&[u8]>,
+    ) -> Result<ContentEncryptionKey, EncryptionError> {
+        let cek_len = content_key_len(enc).ok_or_else(|| {
+            EncryptionError::UnsupportedContentEncryptionAlgorithm { enc: enc.clone() }
+        })?;
+
+        match self {
+            Self::RsaOaep(key) | Self::RsaOaep256(key) => {
+                let mut cek = vec![0; cek_len];
+                rng.fill_bytes(&mut cek);
+
+                let padding = if matches!(self, Self::RsaOaep(_)) {
+                    Oaep::new::<sha1::Sha1>()
+                } else {
+                    Oaep::new::<Sha256>()
+                };
+
+                let encrypted_key = key.encrypt(&mut *rng, padding, &cek)?;
+
+                Ok(ContentEncryptionKey {
+                    cek,
+                    encrypted_key,
+                    epk: None,
+                })
+            }
+
+            Self::EcdhEsP256(key) => {
+                let ephemeral = elliptic_curve::ecdh::EphemeralSecret::random(&mut *rng);
+                let shared_secret = ephemeral.diffie_hellman(key);
+                let cek = ecdh_es_derive_key(&shared_secret, enc, cek_len, apu, apv)
+                    .map_err(|inner| EncryptionError::KeyDerivation { inner })?;
+
+                Ok(ContentEncryptionKey {
+                    cek,
+                    encrypted_key: Vec::new(),
+                    epk: Some(ephemeral.public_key().into()),
+                })
+            }
+
+            Self::EcdhEsP384(key) => {
+                let ephemeral = elliptic_curve::ecdh::EphemeralSecret::random(&mut *rng);
+                let shared_secret = ephemeral.diffie_hellman(key);
+                let cek = ecdh_es_derive_key(&shared_secret, enc, cek_len, apu, apv)
+                    .map_err(|inner| EncryptionError::KeyDerivation { inner })?;
+
+                Ok(ContentEncryptionKey {
+                    cek,
+                    encrypted_key: Vec::new(),
+                    epk: Some(ephemeral.public_key().into()),
+                })
+            }
+        }
+    }
+}
+
+impl From<PublicKey<NistP256>> for AsymmetricEncryptionKey {
+    fn from(key: PublicKey<NistP256>) -> Self {
+        Self::EcdhEsP256(key)
+    }
+}
+
+impl From<PublicKey<NistP384>> for AsymmetricEncryptionKey {
+    fn from(key: PublicKey<NistP384>) -> Self {
+        Self::EcdhEsP384(key)
+    }
+}
+
+/// An enum of all supported asymmetric key management algorithms decryption
+/// keys
+#[non_exhaustive]
+pub enum AsymmetricDecryptionKey {
+    RsaOaep(RsaPrivateKey),
+    RsaOaep256(RsaPrivateKey),
+    EcdhEsP256(SecretKey<NistP256>),
+    EcdhEsP384(SecretKey<NistP384>),
+}
+
+impl AsymmetricDecryptionKey {
+    /// Create a new decryption key for the given key management algorithm from
+    /// the given private JWK parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key parameters are not suitable for the given
+    /// algorithm.
+    pub fn from_jwk_and_alg(
+        params: &JsonWebKeyPrivateParameters,
+        alg: &JsonWebEncryptionAlg,
+    ) -> Result<Self, EncryptionKeyFromJwkError> {
+        match (params, alg) {
+            (JsonWebKeyPrivateParameters::Rsa(params), JsonWebEncryptionAlg::RsaOaep) => {
+                Ok(Self::RsaOaep(params.try_into()?))
+            }
+
+            (JsonWebKeyPrivateParameters::Rsa(params), JsonWebEncryptionAlg::RsaOaep256) => {
+                Ok(Self::RsaOaep256(params.try_into()?))
+            }
+
+            (JsonWebKeyPrivateParameters::Ec(params), JsonWebEncryptionAlg::EcdhEs) => {
+                match params.crv {
+                    JsonWebKeyEcEllipticCurve::P256 => Ok(Self::EcdhEsP256(params.try_into()?)),
+                    JsonWebKeyEcEllipticCurve::P384 => Ok(Self::EcdhEsP384(params.try_into()?)),
+                    _ => Err(EncryptionKeyFromJwkError::UnsupportedAlgorithm { alg: alg.clone() }),
+                }
+            }
+
+            (
+                _,
+                JsonWebEncryptionAlg::RsaOaep
+                | JsonWebEncryptionAlg::RsaOaep256
+                | JsonWebEncryptionAlg::EcdhEs,
+            ) => Err(EncryptionKeyFromJwkError::KeyNotSuitable { alg: alg.clone() }),
+
+            _ => Err(EncryptionKeyFromJwkError::UnsupportedAlgorithm { alg: alg.clone() }),
+        }
+    }
+
+    /// Get the key management algorithm used by this key
+    #[must_use]
+    pub const fn alg(&self) -> JsonWebEncryptionAlg {
+        match self {
+            Self::RsaOaep(_) => JsonWebEncryptionAlg::RsaOaep,
+            Self::RsaOaep256(_) => JsonWebEncryptionAlg::RsaOaep256,
+            Self::EcdhEsP256(_) | Self::EcdhEsP384(_) => JsonWebEncryptionAlg::EcdhEs,
+        }
+    }
+
+    /// Recover the content encryption key from the encrypted key, or agree on
+    /// it with the ephemeral public key.
+    pub(crate) fn content_encryption_key(
+        &self,
+        enc: &JsonWebEncryptionEnc,
+        encrypted_key: &[u8],
+        epk: Option<&JsonWebKeyPublicParameters>,
+        apu: Option<&[u8]>,
+        apv: Option<&[u8]>,
+    ) -> Result<Vec<u8>, DecryptionError> {
+        let cek_len = content_key_len(enc).ok_or_else(|| {
+            DecryptionError::UnsupportedContentEncryptionAlgorithm { enc: enc.clone() }
+        })?;
+
+        let cek = match self {
+            Self::RsaOaep(key) => key.decrypt(Oaep::new::<sha1::Sha1>(), encrypted_key)?,
+            Self::RsaOaep256(key) => key.decrypt(Oaep::new::<Sha256>(), encrypted_key)?,
+
+            Self::EcdhEsP256(key) => {
+                if !encrypted_key.is_empty() {
+                    return Err(DecryptionError::UnexpectedEncryptedKey);
+                }
+
+                let epk: PublicKey<NistP256> = epk
+                    .ok_or(DecryptionError::MissingEphemeralKey)?
+                    .ec()
+                    .ok_or(DecryptionError::InvalidEphemeralKey)?
+                    .try_into()
+                    .map_err(|_| DecryptionError::InvalidEphemeralKey)?;
+
+                let shared_secret =
+                    elliptic_curve::ecdh::diffie_hellman(key.to_nonzero_scalar(), epk.as_affine());
+                ecdh_es_derive_key(&shared_secret, enc, cek_len, apu, apv)
+                    .map_err(|inner| DecryptionError::KeyDerivation { inner })?
+            }
+
+            Self::EcdhEsP384(key) => {
+                if !encrypted_key.is_empty() {
+                    return Err(DecryptionError::UnexpectedEncryptedKey);
+                }
+
+                let epk: PublicKey<NistP384> = epk
+                    .ok_or(DecryptionError::MissingEphemeralKey)?
+                    .ec()
+                    .ok_or(DecryptionError::InvalidEphemeralKey)?
+                    .try_into()
+                    .map_err(|_| DecryptionError::InvalidEphemeralKey)?;
+
+                let shared_secret =
+                    elliptic_curve::ecdh::diffie_hellman(key.to_nonzero_scalar(), epk.as_affine());
+                ecdh_es_derive_key(&shared_secret, enc, cek_len, apu, apv)
+                    .map_err(|inner| DecryptionError::KeyDerivation { inner })?
+            }
+        };
+
+        if cek.len() != cek_len {
+            return Err(DecryptionError::InvalidContentEncryptionKey);
+        }
+
+        Ok(cek)
+    }
+}
+
+impl From<SecretKey<NistP256>> for AsymmetricDecryptionKey {
+    fn from(key: SecretKey<NistP256>) -> Self {
+        Self::EcdhEsP256(key)
+    }
+}
+
+impl From<SecretKey<NistP384>> for AsymmetricDecryptionKey {
+    fn from(key: SecretKey<NistP384>) -> Self {
+        Self::EcdhEsP384(key)
+    }
+}
+
+/// Derive the content encryption key from an ECDH shared secret, using the
+/// Concat KDF as defined in RFC 7518, section 4.6.2
+fn ecdh_es_derive_key<C: elliptic_curve::Curve>(
+    shared_secret: &SharedSecret<C>,
+    enc: &JsonWebEncryptionEnc,
+    cek_len: usize,
+    apu: Option<&[u8]>,
+    apv: Option<&[u8]>,
+) -> Result<Vec<u8>, concat_kdf::Error> {
+    fn length_prefixed(out: &mut Vec<u8>, data: &[u8]) {
+        // The length of the inputs we deal with always fit in a u32
+        #[allow(clippy::cast_possible_truncation)]
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(data);
+    }
+
+    let mut other_info = Vec::new();
+    length_prefixed(&mut other_info, enc.to_string().as_bytes());
+    length_prefixed(&mut other_info, apu.unwrap_or_default());
+    length_prefixed(&mut other_info, apv.unwrap_or_default());
+    #[allow(clippy::cast_possible_truncation)]
+    other_info.extend_from_slice(&((cek_len * 8) as u32).to_be_bytes());
+
+    concat_kdf::derive_key::<Sha256>(shared_secret.raw_secret_bytes(), &other_info, cek_len)
+}
+
+/// Get the length of the content encryption key for the given content
+/// encryption algorithm, or `None` if the algorithm is not supported
+pub(crate) fn content_key_len(enc: &JsonWebEncryptionEnc) -> Option<usize> {
+    match enc {
+        JsonWebEncryptionEnc::A128Gcm => Some(16),
+        JsonWebEncryptionEnc::A192Gcm => Some(24),
+        JsonWebEncryptionEnc::A128CbcHs256 | JsonWebEncryptionEnc::A256Gcm => Some(32),
+        JsonWebEncryptionEnc::A192CbcHs384 => Some(48),
+        JsonWebEncryptionEnc::A256CbcHs512 => Some(64),
+        _ => None,
+    }
+}
+
+/// Get the length of the initialization vector for the given content
+/// encryption algorithm, or `None` if the algorithm is not supported
+pub(crate) fn content_iv_len(enc: &JsonWebEncryptionEnc) -> Option<usize> {
+    match enc {
+        JsonWebEncryptionEnc::A128Gcm
+        | JsonWebEncryptionEnc::A192Gcm
+        | JsonWebEncryptionEnc::A256Gcm => Some(12),
+        JsonWebEncryptionEnc::A128CbcHs256
+        | JsonWebEncryptionEnc::A192CbcHs384
+        | JsonWebEncryptionEnc::A256CbcHs512 => Some(16),
+        _ => None,
+    }
+}
+
+/// Encrypt the plaintext with the given content encryption algorithm,
+/// returning the ciphertext and the authentication tag
+pub(crate) fn encrypt_content(
+    enc: &JsonWebEncryptionEnc,
+    cek: &[u8],
+    iv: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), EncryptionError> {
+    let res = match enc {
+        JsonWebEncryptionEnc::A128Gcm => gcm_encrypt::<aes_gcm::Aes128Gcm>(cek, iv, aad, plaintext),
+        JsonWebEncryptionEnc::A192Gcm => gcm_encrypt::<Aes192Gcm>(cek, iv, aad, plaintext),
+        JsonWebEncryptionEnc::A256Gcm => gcm_encrypt::<aes_gcm::Aes256Gcm>(cek, iv, aad, plaintext),
+        JsonWebEncryptionEnc::A128CbcHs256 => {
+            cbc_hs_encrypt::<aes::Aes128, hmac::Hmac<Sha256>>(cek, iv, aad, plaintext)
+        }
+        JsonWebEncryptionEnc::A192CbcHs384 => {
+            cbc_hs_encrypt::<aes::Aes192, hmac::Hmac<Sha384>>(cek, iv, aad, plaintext)
+        }
+        JsonWebEncryptionEnc::A256CbcHs512 => {
+            cbc_hs_encrypt::<aes::Aes256, hmac::Hmac<Sha512>>(cek, iv, aad, plaintext)
+        }
+        _ => {
+            return Err(EncryptionError::UnsupportedContentEncryptionAlgorithm { enc: enc.clone() })
+        }
+    };
+
+    res.ok_or(EncryptionError::ContentEncryption)
+}
+
+/// Decrypt the ciphertext with the given content encryption algorithm,
+/// checking the authentication tag
+pub(crate) fn decrypt_content(
+    enc: &JsonWebEncryptionEnc,
+    cek: &[u8],
+    iv: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Result<Vec<u8>, DecryptionError> {
+    if Some(iv.len()) != content_iv_len(enc) {
+        return Err(DecryptionError::InvalidInitializationVector);
+    }
+
+    let res = match enc {
+        JsonWebEncryptionEnc::A128Gcm => {
+            gcm_decrypt::<aes_gcm::Aes128Gcm>(cek, iv, aad, ciphertext, tag)
+        }
+        JsonWebEncryptionEnc::A192Gcm => gcm_decrypt::<Aes192Gcm>(cek, iv, aad, ciphertext, tag),
+        JsonWebEncryptionEnc::A256Gcm => {
+            gcm_decrypt::<aes_gcm::Aes256Gcm>(cek, iv, aad, ciphertext, tag)
+        }
+        JsonWebEncryptionEnc::A128CbcHs256 => {
+            cbc_hs_decrypt::<aes::Aes128, hmac::Hmac<Sha256>>(cek, iv, aad, ciphertext, tag)
+        }
+        JsonWebEncryptionEnc::A192CbcHs384 => {
+            cbc_hs_decrypt::<aes::Aes192, hmac::Hmac<Sha384>>(cek, iv, aad, ciphertext, tag)
+        }
+        JsonWebEncryptionEnc::A256CbcHs512 => {
+            cbc_hs_decrypt::<aes::Aes256, hmac::Hmac<Sha512>>(cek, iv, aad, ciphertext, tag)
+        }
+        _ => {
+            return Err(DecryptionError::UnsupportedContentEncryptionAlgorithm { enc: enc.clone() })
+        }
+    };
+
+    res.ok_or(DecryptionError::ContentDecryption)
+}
+
+fn gcm_encrypt<A: AeadInPlace + KeyInit>(
+    cek: &[u8],
+    iv: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Option<(Vec<u8>, Vec<u8>)> {
+    let cipher = A::new_from_slice(cek).ok()?;
+    let nonce = aes_gcm::Nonce::<A::NonceSize>::from_slice(iv);
+    let mut buffer = plaintext.to_vec();
+    let tag = cipher
+        .encrypt_in_place_detached(nonce, aad, &mut buffer)
+        .ok()?;
+    Some((buffer, tag.to_vec()))
+}
+
+fn gcm_decrypt<A: AeadInPlace + KeyInit>(
+    cek: &[u8],
+    iv: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Option<Vec<u8>> {
+    if tag.len() != 16 {
+        return None;
+    }
+
+    let cipher = A::new_from_slice(cek).ok()?;
+    let nonce = aes_gcm::Nonce::<A::NonceSize>::from_slice(iv);
+    let tag = aes_gcm::Tag::<A::TagSize>::from_slice(tag);
+    let mut buffer = ciphertext.to_vec();
+    cipher
+        .decrypt_in_place_detached(nonce, aad, &mut buffer, tag)
+        .ok()?;
+    Some(buffer)
+}
+
+/// Compute the `AES_CBC_HMAC_SHA2` authentication tag, as defined in RFC 7518,
+/// section 5.2.2.1
+fn cbc_hs_mac<M: Mac + KeyInit>(
+    mac_key: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+) -> Option<M> {
+    // The AAD length is expressed in bits
+    let al = (aad.len() as u64 * 8).to_be_bytes();
+
+    let mut mac = <M as Mac>::new_from_slice(mac_key).ok()?;
+    mac.update(aad);
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac.update(&al);
+    Some(mac)
+}
+
+fn cbc_hs_encrypt<C, M>(
+    cek: &[u8],
+    iv: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Option<(Vec<u8>, Vec<u8>)>
+where
+    C: BlockCipher + BlockEncryptMut + KeyInit,
+    M: Mac + KeyInit,
+{
+    // The first half of the key is used for the MAC, the second half for the
+    // encryption
+    let (mac_key, enc_key) = cek.split_at(cek.len() / 2);
+
+    let ciphertext = cbc::Encryptor::<C>::new_from_slices(enc_key, iv)
+        .ok()?
+        .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+
+    let mac = cbc_hs_mac::<M>(mac_key, aad, iv, &ciphertext)?;
+    let mut tag = mac.finalize().into_bytes().to_vec();
+    tag.truncate(mac_key.len());
+
+    Some((ciphertext, tag))
+}
+
+fn cbc_hs_decrypt<C, M>(
+    cek: &[u8],
+    iv: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Option<Vec<u8>>
+where
+    C: BlockCipher + BlockDecryptMut + KeyInit,
+    M: Mac + KeyInit,
+{
+    let (mac_key, enc_key) = cek.split_at(cek.len() / 2);
+
+    if tag.len() != mac_key.len() {
+        return None;
+    }
+
+    let mac = cbc_hs_mac::<M>(mac_key, aad, iv, ciphertext)?;
+    mac.verify_truncated_left(tag).ok()?;
+
+    cbc::Decryptor::<C>::new_from_slices(enc_key, iv)
+        .ok()?
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test vector from RFC 7518, appendix B.1
+    #[test]
+    fn test_a128cbc_hs256() {
+        let cek: Vec<u8> = (0x00..=0x1f).collect();
+        let plaintext = b"A cipher system must not be required to be secret, and it must be able to fall into the hands of the enemy without inconvenience";
+        let iv = [
+            0x1a, 0xf3, 0x8c, 0x2d, 0xc2, 0xb9, 0x6f, 0xfd, 0xd8, 0x66, 0x94, 0x09, 0x23, 0x41,
+            0xbc, 0x04,
+        ];
+        let aad = b"The second principle of Auguste Kerckhoffs";
+
+        let (ciphertext, tag) = encrypt_content(
+            &JsonWebEncryptionEnc::A128CbcHs256,
+            &cek,
+            &iv,
+            aad,
+            plaintext,
+        )
+        .unwrap();
+
+        assert_eq!(
+            tag,
+            [
+                0x65, 0x2c, 0x3f, 0xa3, 0x6b, 0x0a, 0x7c, 0x5b, 0x32, 0x19, 0xfa, 0xb3, 0xa3, 0x0b,
+                0xc1, 0xc4,
+            ]
+        );
+
+        let decrypted = decrypt_content(
+            &JsonWebEncryptionEnc::A128CbcHs256,
+            &cek,
+            &iv,
+            aad,
+            &ciphertext,
+            &tag,
+        )
+        .unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        // Tampering with the AAD should fail the decryption
+        let res = decrypt_content(
+            &JsonWebEncryptionEnc::A128CbcHs256,
+            &cek,
+            &iv,
+            b"The first principle of Auguste Kerckhoffs",
+            &ciphertext,
+            &tag,
+        );
+        assert!(matches!(res, Err(DecryptionError::ContentDecryption)));
+    }
+
+    /// Test vector from RFC 7518, appendix C
+    #[test]
+    fn test_ecdh_es_key_agreement() {
+        use base64ct::{Base64UrlUnpadded, Encoding};
+
+        use crate::{base64::Base64UrlNoPad, jwk::public_parameters::EcPublicParameters};
+
+        let decode = |s: &str| Base64UrlUnpadded::decode_vec(s).unwrap();
+
+        let bob = SecretKey::<NistP256>::from_slice(&decode(
+            "VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw",
+        ))
+        .unwrap();
+        let alice_epk = EcPublicParameters::new(
+            JsonWebKeyEcEllipticCurve::P256,
+            Base64UrlNoPad::new(decode("gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0")),
+            Base64UrlNoPad::new(decode("SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps")),
+        );
+        let alice_epk = JsonWebKeyPublicParameters::Ec(alice_epk);
+
+        let cek = AsymmetricDecryptionKey::from(bob)
+            .content_encryption_key(
+                &JsonWebEncryptionEnc::A128Gcm,
+                &[],
+                Some(&alice_epk),
+                Some(b"Alice"),
+                Some(b"Bob"),
+            )
+            .unwrap();
+
+        assert_eq!(cek, decode("VqqN6vgjbSBcIijNcacQGg"));
+    }
+}
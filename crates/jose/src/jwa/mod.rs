@@ -4,16 +4,21 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
-use mas_iana::jose::JsonWebSignatureAlg;
+use mas_iana::jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg};
 use sha2::{Sha256, Sha384, Sha512};
 
 mod asymmetric;
+pub(crate) mod encryption;
 pub(crate) mod hmac;
 mod signature;
 mod symmetric;
 
 pub use self::{
     asymmetric::{AsymmetricKeyFromJwkError, AsymmetricSigningKey, AsymmetricVerifyingKey},
+    encryption::{
+        AsymmetricDecryptionKey, AsymmetricEncryptionKey, DecryptionError, EncryptionError,
+        EncryptionKeyFromJwkError,
+    },
     symmetric::{InvalidAlgorithm, SymmetricKey},
 };
 
@@ -42,8 +47,11 @@ pub type Es384VerifyingKey = ecdsa::VerifyingKey<p384::NistP384>;
 pub type Es256KSigningKey = ecdsa::SigningKey<k256::Secp256k1>;
 pub type Es256KVerifyingKey = ecdsa::VerifyingKey<k256::Secp256k1>;
 
+pub type Ed25519SigningKey = ed25519_dalek::SigningKey;
+pub type Ed25519VerifyingKey = ed25519_dalek::VerifyingKey;
+
 /// All the signing algorithms supported by this crate.
-pub const SUPPORTED_SIGNING_ALGORITHMS: [JsonWebSignatureAlg; 12] = [
+pub const SUPPORTED_SIGNING_ALGORITHMS: [JsonWebSignatureAlg; 13] = [
     JsonWebSignatureAlg::Hs256,
     JsonWebSignatureAlg::Hs384,
     JsonWebSignatureAlg::Hs512,
@@ -56,4 +64,22 @@ pub const SUPPORTED_SIGNING_ALGORITHMS: [JsonWebSignatureAlg; 12] = [
     JsonWebSignatureAlg::Es256,
     JsonWebSignatureAlg::Es384,
     JsonWebSignatureAlg::Es256K,
+    JsonWebSignatureAlg::EdDsa,
+];
+
+/// All the key management algorithms supported by this crate for encryption.
+pub const SUPPORTED_ENCRYPTION_ALGORITHMS: [JsonWebEncryptionAlg; 3] = [
+    JsonWebEncryptionAlg::RsaOaep,
+    JsonWebEncryptionAlg::RsaOaep256,
+    JsonWebEncryptionAlg::EcdhEs,
+];
+
+/// All the content encryption algorithms supported by this crate.
+pub const SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS: [JsonWebEncryptionEnc; 6] = [
+    JsonWebEncryptionEnc::A128CbcHs256,
+    JsonWebEncryptionEnc::A192CbcHs384,
+    JsonWebEncryptionEnc::A256CbcHs512,
+    JsonWebEncryptionEnc::A128Gcm,
+    JsonWebEncryptionEnc::A192Gcm,
+    JsonWebEncryptionEnc::A256Gcm,
 ];
@@ -0,0 +1,410 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use signature::rand_core::CryptoRngCore;
+use thiserror::Error;
+
+use super::header::JsonWebEncryptionHeader;
+use crate::{
+    jwa::{
+        encryption::{content_iv_len, decrypt_content, encrypt_content},
+        AsymmetricDecryptionKey, AsymmetricEncryptionKey, DecryptionError, EncryptionError,
+    },
+    jwk::PublicJsonWebKey,
+};
+
+/// A JWE in the compact serialization
+#[derive(Clone, PartialEq, Eq)]
+pub struct Jwe {
+    raw: String,
+    header: JsonWebEncryptionHeader,
+    encrypted_key: Vec<u8>,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+    tag: Vec<u8>,
+}
+
+impl std::fmt::Display for Jwe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl std::fmt::Debug for Jwe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Jwe")
+            .field("raw", &"...")
+            .field("header", &self.header)
+            .field("encrypted_key", &"...")
+            .field("iv", &"...")
+            .field("ciphertext", &"...")
+            .field("tag", &"...")
+            .finish()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum JweDecodeError {
+    #[error("JWE must have exactly 5 parts")]
+    WrongPartCount,
+
+    #[error("failed to decode JWE header")]
+    DecodeHeader {
+        #[source]
+        inner: base64ct::Error,
+    },
+
+    #[error("failed to deserialize JWE header")]
+    DeserializeHeader {
+        #[source]
+        inner: serde_json::Error,
+    },
+
+    #[error("failed to decode JWE encrypted key")]
+    DecodeEncryptedKey {
+        #[source]
+        inner: base64ct::Error,
+    },
+
+    #[error("failed to decode JWE initialization vector")]
+    DecodeIv {
+        #[source]
+        inner: base64ct::Error,
+    },
+
+    #[error("failed to decode JWE ciphertext")]
+    DecodeCiphertext {
+        #[source]
+        inner: base64ct::Error,
+    },
+
+    #[error("failed to decode JWE authentication tag")]
+    DecodeTag {
+        #[source]
+        inner: base64ct::Error,
+    },
+}
+
+impl JweDecodeError {
+    fn decode_header(inner: base64ct::Error) -> Self {
+        Self::DecodeHeader { inner }
+    }
+
+    fn deserialize_header(inner: serde_json::Error) -> Self {
+        Self::DeserializeHeader { inner }
+    }
+
+    fn decode_encrypted_key(inner: base64ct::Error) -> Self {
+        Self::DecodeEncryptedKey { inner }
+    }
+
+    fn decode_iv(inner: base64ct::Error) -> Self {
+        Self::DecodeIv { inner }
+    }
+
+    fn decode_ciphertext(inner: base64ct::Error) -> Self {
+        Self::DecodeCiphertext { inner }
+    }
+
+    fn decode_tag(inner: base64ct::Error) -> Self {
+        Self::DecodeTag { inner }
+    }
+}
+
+impl TryFrom<String> for Jwe {
+    type Error = JweDecodeError;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let mut parts = raw.split('.');
+        let (Some(header), Some(encrypted_key), Some(iv), Some(ciphertext), Some(tag), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err(JweDecodeError::WrongPartCount);
+        };
+
+        let header =
+            Base64UrlUnpadded::decode_vec(header).map_err(JweDecodeError::decode_header)?;
+        let header = serde_json::from_slice(&header).map_err(JweDecodeError::deserialize_header)?;
+        let encrypted_key = Base64UrlUnpadded::decode_vec(encrypted_key)
+            .map_err(JweDecodeError::decode_encrypted_key)?;
+        let iv = Base64UrlUnpadded::decode_vec(iv).map_err(JweDecodeError::decode_iv)?;
+        let ciphertext =
+            Base64UrlUnpadded::decode_vec(ciphertext).map_err(JweDecodeError::decode_ciphertext)?;
+        let tag = Base64UrlUnpadded::decode_vec(tag).map_err(JweDecodeError::decode_tag)?;
+
+        Ok(Self {
+            raw,
+            header,
+            encrypted_key,
+            iv,
+            ciphertext,
+            tag,
+        })
+    }
+}
+
+impl TryFrom<&str> for Jwe {
+    type Error = JweDecodeError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::try_from(value.to_owned())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum JweEncryptionError {
+    #[error("key algorithm {key_alg} does not match the header algorithm {header_alg}")]
+    WrongAlgorithm {
+        key_alg: mas_iana::jose::JsonWebEncryptionAlg,
+        header_alg: mas_iana::jose::JsonWebEncryptionAlg,
+    },
+
+    #[error("failed to serialize header")]
+    EncodeHeader {
+        #[source]
+        inner: serde_json::Error,
+    },
+
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+}
+
+impl JweEncryptionError {
+    fn encode_header(inner: serde_json::Error) -> Self {
+        Self::EncodeHeader { inner }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum JweDecryptionError {
+    #[error("key algorithm {key_alg} does not match the header algorithm {header_alg}")]
+    WrongAlgorithm {
+        key_alg: mas_iana::jose::JsonWebEncryptionAlg,
+        header_alg: mas_iana::jose::JsonWebEncryptionAlg,
+    },
+
+    #[error(transparent)]
+    Decryption(#[from] DecryptionError),
+}
+
+impl Jwe {
+    /// Get the JWE header
+    #[must_use]
+    pub fn header(&self) -> &JsonWebEncryptionHeader {
+        &self.header
+    }
+
+    /// Get the raw JWE string as a borrowed [`str`]
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Get the raw JWE string as an owned [`String`]
+    #[must_use]
+    pub fn into_string(self) -> String {
+        self.raw
+    }
+
+    /// Encrypt the given plaintext for the given key using the given RNG.
+    ///
+    /// The ephemeral public key used for ECDH key agreements is added to the
+    /// header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key does not match the algorithm in the header,
+    /// if the content encryption algorithm is not supported or if the
+    /// encryption failed.
+    pub fn encrypt_with_rng<R>(
+        rng: &mut R,
+        header: JsonWebEncryptionHeader,
+        plaintext: &[u8],
+        key: &AsymmetricEncryptionKey,
+    ) -> Result<Self, JweEncryptionError>
+    where
+        R: CryptoRngCore,
+    {
+        let key_alg = key.alg();
+        if &key_alg != header.alg() {
+            return Err(JweEncryptionError::WrongAlgorithm {
+                key_alg,
+                header_alg: header.alg().clone(),
+            });
+        }
+
+        let enc = header.enc().clone();
+        let cek = key.content_encryption_key(rng, &enc, header.apu(), header.apv())?;
+
+        let header = match cek.epk {
+            Some(epk) => header.with_epk(PublicJsonWebKey::new(epk)),
+            None => header,
+        };
+
+        let iv_len = content_iv_len(&enc)
+            .ok_or(EncryptionError::UnsupportedContentEncryptionAlgorithm { enc: enc.clone() })?;
+        let mut iv = vec![0; iv_len];
+        rng.fill_bytes(&mut iv);
+
+        let header_ = serde_json::to_vec(&header).map_err(JweEncryptionError::encode_header)?;
+        let header_ = Base64UrlUnpadded::encode_string(&header_);
+
+        // The additional authenticated data is the encoded protected header
+        let (ciphertext, tag) =
+            encrypt_content(&enc, &cek.cek, &iv, header_.as_bytes(), plaintext)?;
+
+        let raw = format!(
+            "{header_}.{}.{}.{}.{}",
+            Base64UrlUnpadded::encode_string(&cek.encrypted_key),
+            Base64UrlUnpadded::encode_string(&iv),
+            Base64UrlUnpadded::encode_string(&ciphertext),
+            Base64UrlUnpadded::encode_string(&tag),
+        );
+
+        Ok(Self {
+            raw,
+            header,
+            encrypted_key: cek.encrypted_key,
+            iv,
+            ciphertext,
+            tag,
+        })
+    }
+
+    /// Decrypt this JWE using the given key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key does not match the algorithm in the header,
+    /// or if the decryption failed.
+    pub fn decrypt(&self, key: &AsymmetricDecryptionKey) -> Result<Vec<u8>, JweDecryptionError> {
+        let key_alg = key.alg();
+        if &key_alg != self.header.alg() {
+            return Err(JweDecryptionError::WrongAlgorithm {
+                key_alg,
+                header_alg: self.header.alg().clone(),
+            });
+        }
+
+        let cek = key.content_encryption_key(
+            self.header.enc(),
+            &self.encrypted_key,
+            self.header.epk().map(PublicJsonWebKey::params),
+            self.header.apu(),
+            self.header.apv(),
+        )?;
+
+        // The header is always the first part of the raw JWE
+        let (aad, _) = self.raw.split_once('.').unwrap_or_default();
+
+        let plaintext = decrypt_content(
+            self.header.enc(),
+            &cek,
+            &self.iv,
+            aad.as_bytes(),
+            &self.ciphertext,
+            &self.tag,
+        )?;
+
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_iana::jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc};
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::jwa::SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS;
+
+    fn round_trip(
+        encryption_key: &AsymmetricEncryptionKey,
+        decryption_key: &AsymmetricDecryptionKey,
+    ) {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let plaintext = b"Live long and prosper.";
+
+        for enc in SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS {
+            let header = JsonWebEncryptionHeader::new(encryption_key.alg(), enc.clone())
+                .with_kid("my-key")
+                .with_cty("JWT".to_owned());
+            let jwe = Jwe::encrypt_with_rng(&mut rng, header, plaintext, encryption_key).unwrap();
+
+            let jwe = Jwe::try_from(jwe.as_str()).unwrap();
+            assert_eq!(jwe.header().enc(), &enc);
+            assert_eq!(jwe.header().kid(), Some("my-key"));
+            assert_eq!(jwe.header().cty(), Some("JWT"));
+
+            let decrypted = jwe.decrypt(decryption_key).unwrap();
+            assert_eq!(decrypted, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_rsa_oaep() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let key = rsa::RsaPrivateKey::new(&mut rng, 2048).unwrap();
+
+        round_trip(
+            &AsymmetricEncryptionKey::RsaOaep(key.to_public_key()),
+            &AsymmetricDecryptionKey::RsaOaep(key.clone()),
+        );
+
+        round_trip(
+            &AsymmetricEncryptionKey::RsaOaep256(key.to_public_key()),
+            &AsymmetricDecryptionKey::RsaOaep256(key),
+        );
+    }
+
+    #[test]
+    fn test_ecdh_es() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+
+        let key = elliptic_curve::SecretKey::<p256::NistP256>::random(&mut rng);
+        round_trip(&key.public_key().into(), &key.into());
+
+        let key = elliptic_curve::SecretKey::<p384::NistP384>::random(&mut rng);
+        round_trip(&key.public_key().into(), &key.into());
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let key = elliptic_curve::SecretKey::<p256::NistP256>::random(&mut rng);
+        let other_key = elliptic_curve::SecretKey::<p256::NistP256>::random(&mut rng);
+
+        let header = JsonWebEncryptionHeader::new(
+            JsonWebEncryptionAlg::EcdhEs,
+            JsonWebEncryptionEnc::A256Gcm,
+        );
+        let jwe =
+            Jwe::encrypt_with_rng(&mut rng, header, b"hello", &key.public_key().into()).unwrap();
+
+        let res = jwe.decrypt(&other_key.into());
+        assert!(matches!(
+            res,
+            Err(JweDecryptionError::Decryption(
+                DecryptionError::ContentDecryption
+            ))
+        ));
+
+        let header = JsonWebEncryptionHeader::new(
+            JsonWebEncryptionAlg::RsaOaep,
+            JsonWebEncryptionEnc::A256Gcm,
+        );
+        let res = Jwe::encrypt_with_rng(&mut rng, header, b"hello", &key.public_key().into());
+        assert!(matches!(
+            res,
+            Err(JweEncryptionError::WrongAlgorithm { .. })
+        ));
+    }
+}
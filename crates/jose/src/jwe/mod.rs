@@ -0,0 +1,14 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Ref: <https://www.rfc-editor.org/rfc/rfc7516.html>
+
+mod encrypted;
+mod header;
+
+pub use self::{
+    encrypted::{Jwe, JweDecodeError, JweDecryptionError, JweEncryptionError},
+    header::JsonWebEncryptionHeader,
+};
@@ -7,8 +7,8 @@
 //! Ref: <https://www.rfc-editor.org/rfc/rfc7517.html>
 
 use mas_iana::jose::{
-    JsonWebKeyEcEllipticCurve, JsonWebKeyOperation, JsonWebKeyType, JsonWebKeyUse,
-    JsonWebSignatureAlg,
+    JsonWebEncryptionAlg, JsonWebKeyEcEllipticCurve, JsonWebKeyOperation, JsonWebKeyType,
+    JsonWebKeyUse, JsonWebSignatureAlg,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -18,6 +18,7 @@ use url::Url;
 use crate::{
     base64::{Base64, Base64UrlNoPad},
     constraints::{Constrainable, Constraint, ConstraintSet},
+    jwa::AsymmetricEncryptionKey,
 };
 
 pub(crate) mod private_parameters;
@@ -356,6 +357,36 @@ impl<P> JsonWebKeySet<P> {
     }
 }
 
+impl PublicJsonWebKeySet {
+    /// Find a key suitable to encrypt content with the given key management
+    /// algorithm. Returns `None` if no suitable key was found.
+    #[must_use]
+    pub fn encryption_key_for_algorithm(
+        &self,
+        alg: &JsonWebEncryptionAlg,
+    ) -> Option<(&PublicJsonWebKey, AsymmetricEncryptionKey)> {
+        self.keys.iter().find_map(|key| {
+            if !matches!(key.r#use, None | Some(JsonWebKeyUse::Enc)) {
+                return None;
+            }
+
+            // The `alg` of the key is parsed as a signature algorithm, so we
+            // compare its string representation instead
+            if key
+                .alg
+                .as_ref()
+                .is_some_and(|key_alg| key_alg.to_string() != alg.to_string())
+            {
+                return None;
+            }
+
+            let encryption_key =
+                AsymmetricEncryptionKey::from_jwk_and_alg(key.params(), alg).ok()?;
+            Some((key, encryption_key))
+        })
+    }
+}
+
 impl<P> FromIterator<JsonWebKey<P>> for JsonWebKeySet<P> {
     fn from_iter<T: IntoIterator<Item = JsonWebKey<P>>>(iter: T) -> Self {
         let keys = iter.into_iter().collect();
@@ -368,6 +399,24 @@ mod tests {
     use super::*;
     use crate::constraints::ConstraintSet;
 
+    #[test]
+    fn rsa_thumbprint() {
+        // Example from RFC 7638, section 3.1
+        let jwk = serde_json::json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        });
+
+        let jwk: PublicJsonWebKey = serde_json::from_value(jwk).unwrap();
+        assert_eq!(
+            jwk.params().thumbprint_sha256(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+
     #[test]
     fn load_google_keys() {
         let jwks = serde_json::json!({
@@ -413,6 +462,34 @@ mod tests {
         assert_eq!(candidates.len(), 1);
     }
 
+    #[test]
+    fn find_encryption_key() {
+        let n = "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw";
+        let jwks = serde_json::json!({
+            "keys": [
+                { "kty": "RSA", "use": "sig", "alg": "RS256", "kid": "sig", "n": n, "e": "AQAB" },
+                { "kty": "RSA", "use": "enc", "alg": "RSA-OAEP-256", "kid": "oaep-256", "n": n, "e": "AQAB" },
+                { "kty": "RSA", "use": "enc", "kid": "any", "n": n, "e": "AQAB" },
+            ]
+        });
+        let jwks: PublicJsonWebKeySet = serde_json::from_value(jwks).unwrap();
+
+        let (key, _) = jwks
+            .encryption_key_for_algorithm(&JsonWebEncryptionAlg::RsaOaep256)
+            .unwrap();
+        assert_eq!(key.kid(), Some("oaep-256"));
+
+        let (key, _) = jwks
+            .encryption_key_for_algorithm(&JsonWebEncryptionAlg::RsaOaep)
+            .unwrap();
+        assert_eq!(key.kid(), Some("any"));
+
+        // There is no EC key to use for ECDH-ES
+        assert!(jwks
+            .encryption_key_for_algorithm(&JsonWebEncryptionAlg::EcdhEs)
+            .is_none());
+    }
+
     #[allow(clippy::too_many_lines)]
     #[test]
     fn load_keycloak_keys() {
@@ -9,6 +9,7 @@ use mas_iana::jose::{
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use super::ParametersInfo;
 use crate::base64::Base64UrlNoPad;
@@ -50,6 +51,37 @@ impl JsonWebKeyPublicParameters {
             _ => None,
         }
     }
+
+    /// Compute the SHA-256 JWK Thumbprint of this key, as defined by RFC 7638
+    ///
+    /// The result is base64url-encoded, as used by the `jkt` confirmation
+    /// method
+    #[must_use]
+    pub fn thumbprint_sha256(&self) -> String {
+        // The thumbprint is computed over the required members of the key, in
+        // lexicographic order and without any whitespace
+        let canonical = match self {
+            Self::Rsa(p) => format!(
+                r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#,
+                p.e.encode(),
+                p.n.encode()
+            ),
+            Self::Ec(p) => format!(
+                r#"{{"crv":"{}","kty":"EC","x":"{}","y":"{}"}}"#,
+                p.crv,
+                p.x.encode(),
+                p.y.encode()
+            ),
+            Self::Okp(p) => format!(
+                r#"{{"crv":"{}","kty":"OKP","x":"{}"}}"#,
+                p.crv,
+                p.x.encode()
+            ),
+        };
+
+        let digest = Sha256::digest(canonical.as_bytes());
+        Base64UrlNoPad::new(digest.to_vec()).encode()
+    }
 }
 
 impl ParametersInfo for JsonWebKeyPublicParameters {
@@ -137,7 +169,7 @@ impl ParametersInfo for EcPublicParameters {
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct OkpPublicParameters {
-    crv: JsonWebKeyOkpEllipticCurve,
+    pub(crate) crv: JsonWebKeyOkpEllipticCurve,
 
     #[schemars(with = "String")]
     x: Base64UrlNoPad,
@@ -300,3 +332,56 @@ mod ec_impls {
         }
     }
 }
+
+mod okp_impls {
+    use ed25519_dalek::{SignatureError, VerifyingKey};
+    use mas_iana::jose::JsonWebKeyOkpEllipticCurve;
+
+    use super::{JsonWebKeyPublicParameters, OkpPublicParameters};
+    use crate::base64::Base64UrlNoPad;
+
+    impl TryFrom<OkpPublicParameters> for VerifyingKey {
+        type Error = SignatureError;
+        fn try_from(value: OkpPublicParameters) -> Result<Self, Self::Error> {
+            (&value).try_into()
+        }
+    }
+
+    impl TryFrom<&OkpPublicParameters> for VerifyingKey {
+        type Error = SignatureError;
+        fn try_from(value: &OkpPublicParameters) -> Result<Self, Self::Error> {
+            if value.crv != JsonWebKeyOkpEllipticCurve::Ed25519 {
+                return Err(SignatureError::new());
+            }
+
+            VerifyingKey::try_from(value.x.as_bytes())
+        }
+    }
+
+    impl From<VerifyingKey> for JsonWebKeyPublicParameters {
+        fn from(key: VerifyingKey) -> Self {
+            Self::from(&key)
+        }
+    }
+
+    impl From<&VerifyingKey> for JsonWebKeyPublicParameters {
+        fn from(key: &VerifyingKey) -> Self {
+            Self::Okp(key.into())
+        }
+    }
+
+    impl From<VerifyingKey> for OkpPublicParameters {
+        fn from(key: VerifyingKey) -> Self {
+            Self::from(&key)
+        }
+    }
+
+    impl From<&VerifyingKey> for OkpPublicParameters {
+        fn from(key: &VerifyingKey) -> Self {
+            Self {
+                crv: JsonWebKeyOkpEllipticCurve::Ed25519,
+                x: Base64UrlNoPad::new(key.to_bytes().to_vec()),
+            }
+        }
+    }
+}
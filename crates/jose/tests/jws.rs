@@ -115,8 +115,11 @@ macro_rules! asymetric_jwt_test {
                     };
                     let header = JsonWebSignatureHeader::new(alg.clone());
 
+                    // Pick the same key as the reference JWT, as there might be
+                    // multiple keys for the same algorithm
+                    let reference: Jwt<'_, Payload> = Jwt::try_from($jwt).unwrap();
                     let jwks = private_jwks();
-                    let key = jwks.signing_key_for_algorithm(&alg).unwrap();
+                    let key = jwks.find_key(&reference.header().into()).unwrap();
 
                     let key = mas_jose::jwa::AsymmetricSigningKey::from_jwk_and_alg(key.params(), &alg)
                         .unwrap();
@@ -131,10 +134,11 @@ macro_rules! asymetric_jwt_test {
                     let payload = Payload {
                         hello: "world".to_owned(),
                     };
-                    let header = JsonWebSignatureHeader::new(alg.clone());
-
+                    let reference: Jwt<'_, Payload> = Jwt::try_from($jwt).unwrap();
                     let jwks = private_jwks();
-                    let key = jwks.signing_key_for_algorithm(&alg).unwrap();
+                    let key = jwks.find_key(&reference.header().into()).unwrap();
+                    let header = JsonWebSignatureHeader::new(alg.clone())
+                        .with_kid(reference.header().kid().unwrap());
 
                     let key = mas_jose::jwa::AsymmetricSigningKey::from_jwk_and_alg(key.params(), &alg)
                         .unwrap();
@@ -213,7 +217,7 @@ asymetric_jwt_test!(es256, Es256, ES256_JWT);
 asymetric_jwt_test!(es384, Es384, ES384_JWT);
 asymetric_jwt_test!(es512, Es512, ES512_JWT, supported = false);
 asymetric_jwt_test!(es256k, Es256K, ES256K_JWT);
-asymetric_jwt_test!(eddsa_ed25519, EdDsa, EDDSA_ED25519_JWT, supported = false);
+asymetric_jwt_test!(eddsa_ed25519, EdDsa, EDDSA_ED25519_JWT);
 asymetric_jwt_test!(eddsa_ed448, EdDsa, EDDSA_ED448_JWT, supported = false);
 
 #[test]
@@ -10,12 +10,15 @@ use aead::Aead;
 use base64ct::{Base64, Encoding};
 use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
 use generic_array::GenericArray;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use thiserror::Error;
 
 /// Helps encrypting and decrypting data
 #[derive(Clone)]
 pub struct Encrypter {
     aead: Arc<ChaCha20Poly1305>,
+    mac: Arc<Hmac<Sha256>>,
 }
 
 #[derive(Debug, Error)]
@@ -28,12 +31,35 @@ pub enum DecryptError {
 
 impl Encrypter {
     /// Creates an [`Encrypter`] out of an encryption key
+    ///
+    /// # Panics
+    ///
+    /// Never panics, as HMAC accepts keys of any size
     #[must_use]
     pub fn new(key: &[u8; 32]) -> Self {
+        // The keyed hashes use a key derived from the encryption key, so that the
+        // same key is never used for two different algorithms
+        let mut derive =
+            <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key size");
+        derive.update(b"mas-keyed-hash");
+        let mac_key = derive.finalize().into_bytes();
+        let mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(&mac_key).expect("HMAC accepts any key size");
+        let mac = Arc::new(mac);
+
         let key = GenericArray::from_slice(key);
         let aead = ChaCha20Poly1305::new(key);
         let aead = Arc::new(aead);
-        Self { aead }
+        Self { aead, mac }
+    }
+
+    /// Compute a keyed hash of a payload, which can't be brute-forced without
+    /// knowing the encryption key
+    #[must_use]
+    pub fn keyed_hash(&self, payload: &[u8]) -> [u8; 32] {
+        let mut mac = Hmac::clone(&self.mac);
+        mac.update(payload);
+        mac.finalize().into_bytes().into()
     }
 
     /// Encrypt a payload
@@ -13,8 +13,12 @@ use elliptic_curve::{pkcs8::EncodePrivateKey, sec1::ToEncodedPoint};
 use mas_iana::jose::{JsonWebKeyType, JsonWebSignatureAlg};
 pub use mas_jose::jwk::{JsonWebKey, JsonWebKeySet};
 use mas_jose::{
+    constraints::Constrainable,
     jwa::{AsymmetricSigningKey, AsymmetricVerifyingKey},
-    jwk::{JsonWebKeyPublicParameters, ParametersInfo, PublicJsonWebKeySet},
+    jwk::{
+        JsonWebKeyPrivateParameters, JsonWebKeyPublicParameters, ParametersInfo,
+        PublicJsonWebKeySet,
+    },
 };
 use pem_rfc7468::PemLabel;
 use pkcs1::EncodeRsaPrivateKey;
@@ -44,6 +48,18 @@ pub enum LoadError {
         inner: rsa::errors::Error,
     },
 
+    #[error("Invalid elliptic curve private key")]
+    EllipticCurve {
+        #[from]
+        inner: elliptic_curve::Error,
+    },
+
+    #[error("Invalid Ed25519 private key")]
+    Ed25519 {
+        #[from]
+        inner: ed25519_dalek::SignatureError,
+    },
+
     #[error("Failed to decode PKCS1-encoded RSA key")]
     Pkcs1 {
         #[from]
@@ -92,6 +108,9 @@ pub enum LoadError {
     #[error("Unsupported format")]
     UnsupportedFormat,
 
+    #[error("Public parameters of the JWK don't match its private key")]
+    JwkPublicKeyMismatch,
+
     #[error("Could not decode encrypted payload")]
     InEncrypted {
         #[source]
@@ -125,6 +144,7 @@ pub enum PrivateKey {
     EcP256(Box<elliptic_curve::SecretKey<p256::NistP256>>),
     EcP384(Box<elliptic_curve::SecretKey<p384::NistP384>>),
     EcK256(Box<elliptic_curve::SecretKey<k256::Secp256k1>>),
+    Ed25519(Box<ed25519_dalek::SigningKey>),
 }
 
 /// Error returned when the key can't be used for the requested algorithm
@@ -160,6 +180,7 @@ impl PrivateKey {
                 k256::Secp256k1::OID => Ok(Self::EcK256(Box::new(info.try_into()?))),
                 oid => Err(LoadError::UnknownEllipticCurveOid { oid }),
             },
+            ed25519_dalek::pkcs8::ALGORITHM_OID => Ok(Self::Ed25519(Box::new(info.try_into()?))),
             oid => Err(LoadError::UnknownAlgorithmOid { oid }),
         }
     }
@@ -182,7 +203,7 @@ impl PrivateKey {
     /// Serialize the key as a DER document
     ///
     /// It will use the most common format depending on the key type: PKCS1 for
-    /// RSA keys and SEC1 for elliptic curve keys
+    /// RSA keys, SEC1 for elliptic curve keys and PKCS8 for Ed25519 keys
     ///
     /// # Errors
     ///
@@ -193,6 +214,7 @@ impl PrivateKey {
             PrivateKey::EcP256(key) => to_sec1_der(key)?,
             PrivateKey::EcP384(key) => to_sec1_der(key)?,
             PrivateKey::EcK256(key) => to_sec1_der(key)?,
+            PrivateKey::Ed25519(key) => to_ed25519_pkcs8(key).to_pkcs8_der()?.to_bytes(),
         };
 
         Ok(der)
@@ -209,6 +231,7 @@ impl PrivateKey {
             PrivateKey::EcP256(key) => key.to_pkcs8_der()?,
             PrivateKey::EcP384(key) => key.to_pkcs8_der()?,
             PrivateKey::EcK256(key) => key.to_pkcs8_der()?,
+            PrivateKey::Ed25519(key) => to_ed25519_pkcs8(key).to_pkcs8_der()?,
         };
 
         Ok(der.to_bytes())
@@ -217,7 +240,7 @@ impl PrivateKey {
     /// Serialize the key as a PEM document
     ///
     /// It will use the most common format depending on the key type: PKCS1 for
-    /// RSA keys and SEC1 for elliptic curve keys
+    /// RSA keys, SEC1 for elliptic curve keys and PKCS8 for Ed25519 keys
     ///
     /// # Errors
     ///
@@ -231,6 +254,7 @@ impl PrivateKey {
             PrivateKey::EcP256(key) => to_sec1_pem(key, line_ending)?,
             PrivateKey::EcP384(key) => to_sec1_pem(key, line_ending)?,
             PrivateKey::EcK256(key) => to_sec1_pem(key, line_ending)?,
+            PrivateKey::Ed25519(key) => to_ed25519_pkcs8(key).to_pkcs8_pem(line_ending)?,
         };
 
         Ok(pem)
@@ -408,6 +432,38 @@ impl PrivateKey {
         }
     }
 
+    /// Load a key from the private parameters of a JWK
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    ///   - the key is a symmetric key, or uses an unsupported curve
+    ///   - the private parameters are not a valid key
+    ///   - the public parameters don't match the private key
+    pub fn load_jwk(params: &JsonWebKeyPrivateParameters) -> Result<Self, LoadError> {
+        let key = match params {
+            JsonWebKeyPrivateParameters::Rsa(params) => Self::Rsa(Box::new(params.try_into()?)),
+            JsonWebKeyPrivateParameters::Ec(params) => match params.possible_algs() {
+                [JsonWebSignatureAlg::Es256] => Self::EcP256(Box::new(params.try_into()?)),
+                [JsonWebSignatureAlg::Es384] => Self::EcP384(Box::new(params.try_into()?)),
+                [JsonWebSignatureAlg::Es256K] => Self::EcK256(Box::new(params.try_into()?)),
+                _ => return Err(LoadError::UnsupportedFormat),
+            },
+            JsonWebKeyPrivateParameters::Okp(params) => Self::Ed25519(Box::new(params.try_into()?)),
+            JsonWebKeyPrivateParameters::Oct(_) => return Err(LoadError::UnsupportedFormat),
+        };
+
+        // The public parameters are derived from the private key, so they must
+        // be the same as the ones in the JWK
+        let public = JsonWebKeyPublicParameters::try_from(params.clone())
+            .map_err(|_| LoadError::UnsupportedFormat)?;
+        if JsonWebKeyPublicParameters::from(&key) != public {
+            return Err(LoadError::JwkPublicKeyMismatch);
+        }
+
+        Ok(key)
+    }
+
     /// Get an [`AsymmetricVerifyingKey`] out of this key, for the specified
     /// [`JsonWebSignatureAlg`]
     ///
@@ -444,6 +500,10 @@ impl PrivateKey {
                 AsymmetricVerifyingKey::es256k(key.public_key())
             }
 
+            (Self::Ed25519(key), JsonWebSignatureAlg::EdDsa) => {
+                AsymmetricVerifyingKey::ed25519(key.verifying_key())
+            }
+
             _ => return Err(WrongAlgorithmError),
         };
 
@@ -486,6 +546,10 @@ impl PrivateKey {
                 AsymmetricSigningKey::es256k(*key.clone())
             }
 
+            (Self::Ed25519(key), JsonWebSignatureAlg::EdDsa) => {
+                AsymmetricSigningKey::ed25519(*key.clone())
+            }
+
             _ => return Err(WrongAlgorithmError),
         };
 
@@ -519,6 +583,12 @@ impl PrivateKey {
         let key = elliptic_curve::SecretKey::random(&mut rng);
         Self::EcK256(Box::new(key))
     }
+
+    /// Generate an Edwards Curve key for the Ed25519 curve
+    pub fn generate_ed25519<R: RngCore + CryptoRng>(mut rng: R) -> Self {
+        let key = ed25519_dalek::SigningKey::generate(&mut rng);
+        Self::Ed25519(Box::new(key))
+    }
 }
 
 // The default implementation of SecretKey::to_sec1_pem/der do not include the
@@ -563,6 +633,16 @@ where
     ))
 }
 
+// The PKCS8 encoding of Ed25519 keys in `ed25519-dalek` includes the public
+// key, which OpenSSL doesn't. This encodes only the private key, so that it
+// matches the implementation in OpenSSL.
+fn to_ed25519_pkcs8(key: &ed25519_dalek::SigningKey) -> ed25519_dalek::pkcs8::KeypairBytes {
+    ed25519_dalek::pkcs8::KeypairBytes {
+        secret_key: key.to_bytes(),
+        public_key: None,
+    }
+}
+
 impl From<&PrivateKey> for JsonWebKeyPublicParameters {
     fn from(val: &PrivateKey) -> Self {
         match val {
@@ -570,6 +650,7 @@ impl From<&PrivateKey> for JsonWebKeyPublicParameters {
             PrivateKey::EcP256(key) => key.public_key().into(),
             PrivateKey::EcP384(key) => key.public_key().into(),
             PrivateKey::EcK256(key) => key.public_key().into(),
+            PrivateKey::Ed25519(key) => key.verifying_key().into(),
         }
     }
 }
@@ -581,6 +662,7 @@ impl ParametersInfo for PrivateKey {
             PrivateKey::EcP256(_) | PrivateKey::EcP384(_) | PrivateKey::EcK256(_) => {
                 JsonWebKeyType::Ec
             }
+            PrivateKey::Ed25519(_) => JsonWebKeyType::Okp,
         }
     }
 
@@ -597,20 +679,44 @@ impl ParametersInfo for PrivateKey {
             PrivateKey::EcP256(_) => &[JsonWebSignatureAlg::Es256],
             PrivateKey::EcP384(_) => &[JsonWebSignatureAlg::Es384],
             PrivateKey::EcK256(_) => &[JsonWebSignatureAlg::Es256K],
+            PrivateKey::Ed25519(_) => &[JsonWebSignatureAlg::EdDsa],
         }
     }
 }
 
+/// The state of a key in its rotation lifecycle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyState {
+    /// The key is published, but not used for signing yet. This gives time to
+    /// relying parties to refresh their cached copy of the key set before the
+    /// key starts being used.
+    Pending,
+
+    /// The key is published and used for signing
+    #[default]
+    Active,
+
+    /// The key is not used for signing anymore, but is still published so that
+    /// what was signed with it can still be verified
+    Retiring,
+}
+
 /// A structure to store a list of [`PrivateKey`]. The keys are held in an
 /// [`Arc`] to ensure they are only loaded once in memory and allow cheap
 /// cloning
+///
+/// Only the active keys are used for signing, and are accessible through
+/// [`Deref`]. Pending and retiring keys are only published in the public JSON
+/// Web Key Set.
 #[derive(Clone, Default)]
 pub struct Keystore {
     keys: Arc<JsonWebKeySet<PrivateKey>>,
+    inactive_keys: Arc<Vec<(KeyState, JsonWebKey<PrivateKey>)>>,
 }
 
 impl Keystore {
-    /// Create a keystore out of a JSON Web Key Set
+    /// Create a keystore out of a JSON Web Key Set, where all the keys are
+    /// active
     ///
     /// ```rust
     /// use mas_keystore::{Keystore, PrivateKey, JsonWebKey, JsonWebKeySet};
@@ -632,14 +738,69 @@ impl Keystore {
     #[must_use]
     pub fn new(keys: JsonWebKeySet<PrivateKey>) -> Self {
         let keys = Arc::new(keys);
-        Self { keys }
+        Self {
+            keys,
+            inactive_keys: Arc::default(),
+        }
+    }
+
+    /// Create a keystore out of a list of keys, each with its state in the
+    /// rotation lifecycle
+    ///
+    /// ```rust
+    /// use mas_keystore::{Keystore, KeyState, PrivateKey, JsonWebKey};
+    /// let old = PrivateKey::load_pem(include_str!("../tests/keys/rsa.pkcs1.pem")).unwrap();
+    /// let old = JsonWebKey::new(old).with_kid("old");
+    ///
+    /// let new = PrivateKey::load_pem(include_str!("../tests/keys/ec-p256.sec1.pem")).unwrap();
+    /// let new = JsonWebKey::new(new).with_kid("new");
+    ///
+    /// let keystore = Keystore::with_states([(KeyState::Active, old), (KeyState::Pending, new)]);
+    /// assert_eq!(keystore.len(), 1);
+    /// assert_eq!(keystore.public_jwks().len(), 2);
+    /// ```
+    #[must_use]
+    pub fn with_states<I>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = (KeyState, JsonWebKey<PrivateKey>)>,
+    {
+        let mut active_keys = Vec::new();
+        let mut inactive_keys = Vec::new();
+        for (state, key) in keys {
+            if state == KeyState::Active {
+                active_keys.push(key);
+            } else {
+                inactive_keys.push((state, key));
+            }
+        }
+
+        Self {
+            keys: Arc::new(JsonWebKeySet::new(active_keys)),
+            inactive_keys: Arc::new(inactive_keys),
+        }
+    }
+
+    /// Get the state of the key with the given ID. Returns [`None`] if no key
+    /// has this ID.
+    #[must_use]
+    pub fn key_state(&self, kid: &str) -> Option<KeyState> {
+        if self.keys.iter().any(|key| key.kid() == Some(kid)) {
+            return Some(KeyState::Active);
+        }
+
+        self.inactive_keys
+            .iter()
+            .find(|(_, key)| key.kid() == Some(kid))
+            .map(|(state, _)| *state)
     }
 
-    /// Get the public JSON Web Key Set for the keys stored in this [`Keystore`]
+    /// Get the public JSON Web Key Set for the keys stored in this
+    /// [`Keystore`], including the pending and retiring ones
     #[must_use]
     pub fn public_jwks(&self) -> PublicJsonWebKeySet {
         self.keys
             .iter()
+            .chain(self.inactive_keys.iter().map(|(_, key)| key))
             .map(|key| {
                 key.cloned_map(|params: &PrivateKey| JsonWebKeyPublicParameters::from(params))
             })
@@ -7,10 +7,11 @@
 use der::pem::LineEnding;
 use mas_iana::jose::JsonWebSignatureAlg;
 use mas_jose::{
-    jwk::ParametersInfo,
+    jwa::AsymmetricVerifyingKey,
+    jwk::{JsonWebKeyPrivateParameters, JsonWebKeyPublicParameters, ParametersInfo},
     jwt::{JsonWebSignatureHeader, Jwt},
 };
-use mas_keystore::{JsonWebKey, JsonWebKeySet, Keystore, PrivateKey};
+use mas_keystore::{JsonWebKey, JsonWebKeySet, KeyState, Keystore, LoadError, PrivateKey};
 use rand::SeedableRng;
 
 static PASSWORD: &str = "hunter2";
@@ -109,6 +110,8 @@ plain_test!(plain_ec_k256_sec1_pem, EcK256, "ec-k256.sec1.pem");
 plain_test!(plain_ec_k256_sec1_der, EcK256, "ec-k256.sec1.der");
 plain_test!(plain_ec_k256_pkcs8_pem, EcK256, "ec-k256.pkcs8.pem");
 plain_test!(plain_ec_k256_pkcs8_der, EcK256, "ec-k256.pkcs8.der");
+plain_test!(plain_ed25519_pkcs8_pem, Ed25519, "ed25519.pkcs8.pem");
+plain_test!(plain_ed25519_pkcs8_der, Ed25519, "ed25519.pkcs8.der");
 
 enc_test!(enc_rsa_pkcs8_pem, Rsa, "rsa.pkcs8.encrypted.pem");
 enc_test!(enc_rsa_pkcs8_der, Rsa, "rsa.pkcs8.encrypted.der");
@@ -118,6 +121,16 @@ enc_test!(enc_ec_p384_pkcs8_pem, EcP384, "ec-p384.pkcs8.encrypted.pem");
 enc_test!(enc_ec_p384_pkcs8_der, EcP384, "ec-p384.pkcs8.encrypted.der");
 enc_test!(enc_ec_k256_pkcs8_pem, EcK256, "ec-k256.pkcs8.encrypted.pem");
 enc_test!(enc_ec_k256_pkcs8_der, EcK256, "ec-k256.pkcs8.encrypted.der");
+enc_test!(
+    enc_ed25519_pkcs8_pem,
+    Ed25519,
+    "ed25519.pkcs8.encrypted.pem"
+);
+enc_test!(
+    enc_ed25519_pkcs8_der,
+    Ed25519,
+    "ed25519.pkcs8.encrypted.der"
+);
 
 // Test PEM/DER serialization
 pem_test!(serialize_rsa_pkcs1_pem, "rsa.pkcs1");
@@ -128,6 +141,8 @@ pem_test!(serialize_ec_p384_sec1_pem, "ec-p384.sec1");
 der_test!(serialize_ec_p384_sec1_der, "ec-p384.sec1");
 pem_test!(serialize_ec_k256_sec1_pem, "ec-k256.sec1");
 der_test!(serialize_ec_k256_sec1_der, "ec-k256.sec1");
+pem_test!(serialize_ed25519_pkcs8_pem, "ed25519.pkcs8");
+der_test!(serialize_ed25519_pkcs8_der, "ed25519.pkcs8");
 
 #[test]
 fn load_encrypted_as_unencrypted_error() {
@@ -204,3 +219,120 @@ fn generate_sign_and_verify() {
         token.verify_with_jwks(&jwks).unwrap();
     }
 }
+
+#[test]
+fn generate_sign_and_verify_ed25519() {
+    // Use a seeded RNG to keep the snapshot stable
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+
+    let ed25519 = PrivateKey::generate_ed25519(&mut rng);
+    insta::assert_snapshot!(&*ed25519.to_pem(LineEnding::LF).unwrap());
+
+    let keyset = Keystore::new(JsonWebKeySet::new(vec![JsonWebKey::new(ed25519)]));
+    let jwks = keyset.public_jwks();
+    insta::assert_yaml_snapshot!(jwks);
+
+    let alg = JsonWebSignatureAlg::EdDsa;
+    let key = keyset.signing_key_for_algorithm(&alg).unwrap();
+    let signer = key.params().signing_key_for_alg(&alg).unwrap();
+    let header = JsonWebSignatureHeader::new(alg.clone());
+    let token = Jwt::sign_with_rng(&mut rng, header, "", &signer).unwrap();
+    insta::assert_snapshot!(format!("jwt_{alg}"), token.as_str());
+
+    token.verify_with_jwks(&jwks).unwrap();
+}
+
+#[test]
+fn load_ed25519_jwk() {
+    // The example key from RFC 8037, appendix A.1
+    let jwk = serde_json::json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "d": "nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A",
+        "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo",
+    });
+    let params: JsonWebKeyPrivateParameters = serde_json::from_value(jwk).unwrap();
+    let key = PrivateKey::load_jwk(&params).unwrap();
+    assert!(matches!(key, PrivateKey::Ed25519(_)), "wrong key type");
+
+    // The public key is the one from the JWK
+    let public = JsonWebKeyPublicParameters::from(&key);
+    assert_eq!(
+        serde_json::to_value(&public).unwrap(),
+        serde_json::json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo",
+        }),
+    );
+
+    let alg = JsonWebSignatureAlg::EdDsa;
+    let header = JsonWebSignatureHeader::new(alg.clone());
+    let signer = key.signing_key_for_alg(&alg).unwrap();
+    let jwt = Jwt::sign(header, "hello", &signer).unwrap();
+    let verifier = AsymmetricVerifyingKey::from_jwk_and_alg(&public, &alg).unwrap();
+    jwt.verify(&verifier).unwrap();
+
+    // A JWK with a public key which doesn't match the private key is rejected
+    let jwk = serde_json::json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "d": "nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A",
+        "x": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+    });
+    let params: JsonWebKeyPrivateParameters = serde_json::from_value(jwk).unwrap();
+    assert!(matches!(
+        PrivateKey::load_jwk(&params),
+        Err(LoadError::JwkPublicKeyMismatch)
+    ));
+}
+
+#[test]
+fn key_states() {
+    let load =
+        |kid: &str, pem: &str| JsonWebKey::new(PrivateKey::load_pem(pem).unwrap()).with_kid(kid);
+    let keystore = Keystore::with_states([
+        (
+            KeyState::Retiring,
+            load("old", include_str!("./keys/ec-p384.sec1.pem")),
+        ),
+        (
+            KeyState::Active,
+            load("current", include_str!("./keys/ec-p256.sec1.pem")),
+        ),
+        (
+            KeyState::Pending,
+            load("next", include_str!("./keys/ec-k256.sec1.pem")),
+        ),
+    ]);
+
+    assert_eq!(keystore.key_state("old"), Some(KeyState::Retiring));
+    assert_eq!(keystore.key_state("current"), Some(KeyState::Active));
+    assert_eq!(keystore.key_state("next"), Some(KeyState::Pending));
+    assert_eq!(keystore.key_state("unknown"), None);
+
+    // Only the active key is used for signing
+    assert_eq!(
+        keystore.available_signing_algorithms(),
+        vec![JsonWebSignatureAlg::Es256]
+    );
+    assert!(keystore
+        .signing_key_for_algorithm(&JsonWebSignatureAlg::Es384)
+        .is_none());
+    assert!(keystore
+        .signing_key_for_algorithm(&JsonWebSignatureAlg::Es256K)
+        .is_none());
+
+    // But all of them are published
+    let jwks = keystore.public_jwks();
+    assert_eq!(jwks.len(), 3);
+
+    // Tokens signed with the retiring key can still be verified
+    let old = PrivateKey::load_pem(include_str!("./keys/ec-p384.sec1.pem")).unwrap();
+    let signer = old
+        .signing_key_for_alg(&JsonWebSignatureAlg::Es384)
+        .unwrap();
+    let header = JsonWebSignatureHeader::new(JsonWebSignatureAlg::Es384).with_kid("old");
+    let token = Jwt::sign(header, "hello", &signer).unwrap();
+    token.verify_with_jwks(&jwks).unwrap();
+}
@@ -10,7 +10,7 @@
 //! An utility crate to build flexible [`hyper`] listeners, with optional TLS
 //! and proxy protocol support.
 
-use self::{maybe_tls::TlsStreamInfo, proxy_protocol::ProxyProtocolV1Info};
+use self::{maybe_tls::TlsStreamInfo, proxy_protocol::ProxyProtocolInfo};
 
 pub mod maybe_tls;
 pub mod proxy_protocol;
@@ -22,7 +22,7 @@ pub mod unix_or_tcp;
 #[derive(Debug, Clone)]
 pub struct ConnectionInfo {
     tls: Option<TlsStreamInfo>,
-    proxy: Option<ProxyProtocolV1Info>,
+    proxy: Option<ProxyProtocolInfo>,
     net_peer_addr: Option<std::net::SocketAddr>,
 }
 
@@ -37,7 +37,7 @@ impl ConnectionInfo {
     /// Returns informations about the proxy protocol connection. Returns
     /// [`None`] if the connection was not using the proxy protocol.
     #[must_use]
-    pub fn get_proxy_ref(&self) -> Option<&ProxyProtocolV1Info> {
+    pub fn get_proxy_ref(&self) -> Option<&ProxyProtocolInfo> {
         self.proxy.as_ref()
     }
 
@@ -6,15 +6,17 @@
 
 use std::{
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, PoisonError, RwLock},
     task::{Context, Poll},
 };
 
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio_rustls::{
     rustls::{
-        pki_types::CertificateDer, ProtocolVersion, ServerConfig, ServerConnection,
-        SupportedCipherSuite,
+        pki_types::CertificateDer,
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+        ProtocolVersion, ServerConfig, ServerConnection, SupportedCipherSuite,
     },
     TlsAcceptor,
 };
@@ -221,3 +223,91 @@ impl MaybeTlsAcceptor {
         }
     }
 }
+
+/// A [`ResolvesServerCert`] which always uses the same certificate, which can
+/// be replaced at runtime
+///
+/// Replacing the certificate only affects new handshakes: connections which
+/// are already established are kept as-is.
+#[derive(Debug)]
+pub struct ReloadableCertResolver {
+    certified_key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadableCertResolver {
+    #[must_use]
+    pub fn new(certified_key: CertifiedKey) -> Self {
+        Self {
+            certified_key: RwLock::new(Arc::new(certified_key)),
+        }
+    }
+
+    /// Get the certificate currently in use
+    #[must_use]
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        // The lock only guards an `Arc` swap, which can't be left half-done by
+        // a panic, so it's fine to ignore the poisoning
+        self.certified_key
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replace the certificate used for new handshakes
+    pub fn replace(&self, certified_key: CertifiedKey) {
+        *self
+            .certified_key
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::new(certified_key);
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use tokio_rustls::rustls::{crypto::aws_lc_rs::sign::any_supported_type, sign::CertifiedKey};
+
+    use super::ReloadableCertResolver;
+
+    static SERVER_CERT_PEM: &[u8] = include_bytes!("../examples/demo/certs/server.pem");
+    static SERVER_KEY_PEM: &[u8] = include_bytes!("../examples/demo/certs/server-key.pem");
+    static CLIENT_CERT_PEM: &[u8] = include_bytes!("../examples/demo/certs/client.pem");
+    static CLIENT_KEY_PEM: &[u8] = include_bytes!("../examples/demo/certs/client-key.pem");
+
+    fn certified_key(cert_pem: &[u8], key_pem: &[u8]) -> CertifiedKey {
+        let chain = rustls_pemfile::certs(&mut BufReader::new(cert_pem))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = rustls_pemfile::private_key(&mut BufReader::new(key_pem))
+            .unwrap()
+            .unwrap();
+        let key = any_supported_type(&key).unwrap();
+        CertifiedKey::new(chain, key)
+    }
+
+    #[test]
+    fn replace_certificate() {
+        let server = certified_key(SERVER_CERT_PEM, SERVER_KEY_PEM);
+        let client = certified_key(CLIENT_CERT_PEM, CLIENT_KEY_PEM);
+        let server_cert = server.cert[0].clone();
+        let client_cert = client.cert[0].clone();
+        assert_ne!(server_cert, client_cert);
+
+        let resolver = ReloadableCertResolver::new(server);
+        let before = resolver.current();
+        assert_eq!(before.end_entity_cert().unwrap(), &server_cert);
+
+        resolver.replace(client);
+        assert_eq!(resolver.current().end_entity_cert().unwrap(), &client_cert);
+
+        // Handshakes which already got the previous certificate keep it
+        assert_eq!(before.end_entity_cert().unwrap(), &server_cert);
+    }
+}
@@ -8,7 +8,7 @@ use bytes::BytesMut;
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncReadExt};
 
-use super::ProxyProtocolV1Info;
+use super::ProxyProtocolInfo;
 use crate::rewind::Rewind;
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -19,7 +19,7 @@ pub struct ProxyAcceptor {
 #[derive(Debug, Error)]
 #[error(transparent)]
 pub enum ProxyAcceptError {
-    Parse(#[from] super::v1::ParseError),
+    Parse(#[from] super::info::ParseError),
     Read(#[from] std::io::Error),
 }
 
@@ -29,7 +29,7 @@ impl ProxyAcceptor {
         Self { _private: () }
     }
 
-    /// Accept a proxy-protocol stream
+    /// Accept a proxy-protocol stream, using either version of the protocol
     ///
     /// # Errors
     ///
@@ -38,15 +38,19 @@ impl ProxyAcceptor {
     pub async fn accept<T>(
         &self,
         mut stream: T,
-    ) -> Result<(ProxyProtocolV1Info, Rewind<T>), ProxyAcceptError>
+    ) -> Result<(ProxyProtocolInfo, Rewind<T>), ProxyAcceptError>
     where
         T: AsyncRead + Unpin,
     {
         let mut buf = BytesMut::new();
         let info = loop {
-            stream.read_buf(&mut buf).await?;
+            let read = stream.read_buf(&mut buf).await?;
+            if read == 0 {
+                // The peer closed the connection before sending a full header
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
 
-            match ProxyProtocolV1Info::parse(&mut buf) {
+            match ProxyProtocolInfo::parse(&mut buf) {
                 Ok(info) => break info,
                 Err(e) if e.not_enough_bytes() => {}
                 Err(e) => return Err(e.into()),
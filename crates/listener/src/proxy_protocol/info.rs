@@ -0,0 +1,176 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::net::SocketAddr;
+
+use bytes::Buf;
+use thiserror::Error;
+
+use super::{
+    v1::{self, ProxyProtocolV1Info},
+    v2::{self, ProxyProtocolV2Info, ProxyProtocolV2Tlvs},
+};
+
+/// Informations sent by the proxy, in either version of the proxy protocol
+#[derive(Debug, Clone)]
+pub enum ProxyProtocolInfo {
+    V1(ProxyProtocolV1Info),
+    V2(Box<ProxyProtocolV2Info>),
+}
+
+#[derive(Error, Debug)]
+#[error(transparent)]
+pub enum ParseError {
+    V1(#[from] v1::ParseError),
+    V2(#[from] v2::ParseError),
+}
+
+impl ParseError {
+    pub const fn not_enough_bytes(&self) -> bool {
+        match self {
+            Self::V1(e) => e.not_enough_bytes(),
+            Self::V2(e) => e.not_enough_bytes(),
+        }
+    }
+}
+
+impl From<ProxyProtocolV1Info> for ProxyProtocolInfo {
+    fn from(info: ProxyProtocolV1Info) -> Self {
+        Self::V1(info)
+    }
+}
+
+impl From<ProxyProtocolV2Info> for ProxyProtocolInfo {
+    fn from(info: ProxyProtocolV2Info) -> Self {
+        Self::V2(Box::new(info))
+    }
+}
+
+impl ProxyProtocolInfo {
+    /// Parse a proxy protocol header, detecting the version from its first
+    /// byte: v1 headers start with `PROXY`, v2 headers with a binary signature
+    /// starting with `\r`
+    pub(super) fn parse<B>(buf: &mut B) -> Result<Self, ParseError>
+    where
+        B: Buf + AsRef<[u8]>,
+    {
+        match buf.as_ref().first() {
+            None => Err(v1::ParseError::NotEnoughBytes.into()),
+            Some(&byte) if byte == v2::SIGNATURE[0] => Ok(ProxyProtocolV2Info::parse(buf)?.into()),
+            Some(_) => Ok(ProxyProtocolV1Info::parse(buf)?.into()),
+        }
+    }
+
+    #[must_use]
+    pub fn is_ipv4(&self) -> bool {
+        match self {
+            Self::V1(info) => info.is_ipv4(),
+            Self::V2(info) => info.is_ipv4(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_ipv6(&self) -> bool {
+        match self {
+            Self::V1(info) => info.is_ipv6(),
+            Self::V2(info) => info.is_ipv6(),
+        }
+    }
+
+    #[must_use]
+    pub const fn is_tcp(&self) -> bool {
+        match self {
+            Self::V1(info) => info.is_tcp(),
+            Self::V2(info) => info.is_tcp(),
+        }
+    }
+
+    #[must_use]
+    pub const fn is_udp(&self) -> bool {
+        match self {
+            Self::V1(info) => info.is_udp(),
+            Self::V2(info) => info.is_udp(),
+        }
+    }
+
+    #[must_use]
+    pub const fn source(&self) -> Option<&SocketAddr> {
+        match self {
+            Self::V1(info) => info.source(),
+            Self::V2(info) => info.source(),
+        }
+    }
+
+    #[must_use]
+    pub const fn destination(&self) -> Option<&SocketAddr> {
+        match self {
+            Self::V1(info) => info.destination(),
+            Self::V2(info) => info.destination(),
+        }
+    }
+
+    /// The TLVs sent by the proxy. Returns [`None`] for the v1 protocol, which
+    /// doesn't support them.
+    #[must_use]
+    pub const fn tlvs(&self) -> Option<&ProxyProtocolV2Tlvs> {
+        match self {
+            Self::V1(_) => None,
+            Self::V2(info) => Some(&info.tlvs),
+        }
+    }
+
+    /// The host name the client asked the proxy for, usually through TLS SNI
+    #[must_use]
+    pub fn authority(&self) -> Option<&str> {
+        self.tlvs()?.authority.as_deref()
+    }
+
+    /// Returns [`true`] if the client presented a TLS certificate to the proxy,
+    /// which the proxy successfully verified
+    #[must_use]
+    pub fn client_certificate_verified(&self) -> bool {
+        self.tlvs()
+            .and_then(|tlvs| tlvs.ssl.as_ref())
+            .is_some_and(v2::ProxyProtocolV2Ssl::client_certificate_verified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_version() {
+        let mut buf = b"PROXY TCP4 192.0.2.1 198.51.100.1 50000 443\r\nhello world".as_slice();
+        let info = ProxyProtocolInfo::parse(&mut buf).unwrap();
+        assert_eq!(buf, b"hello world");
+        assert!(matches!(info, ProxyProtocolInfo::V1(_)));
+        assert_eq!(info.source(), Some(&"192.0.2.1:50000".parse().unwrap()));
+        assert!(info.tlvs().is_none());
+        assert!(!info.client_certificate_verified());
+
+        let mut header = v2::SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0x00, 0x1E]);
+        header.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 1, 0xC3, 0x50, 0x01, 0xBB]);
+        header.extend_from_slice(&[0x02, 0x00, 0x0F]);
+        header.extend_from_slice(b"mas.example.com");
+        header.extend_from_slice(b"hello world");
+        let mut buf = header.as_slice();
+        let info = ProxyProtocolInfo::parse(&mut buf).unwrap();
+        assert_eq!(buf, b"hello world");
+        assert!(matches!(info, ProxyProtocolInfo::V2(_)));
+        assert_eq!(info.source(), Some(&"192.0.2.1:50000".parse().unwrap()));
+        assert_eq!(info.authority(), Some("mas.example.com"));
+        assert!(!info.client_certificate_verified());
+
+        // Partial headers of both versions
+        for partial in [&b""[..], b"PROXY TCP4", b"\r\n\r\n\0"] {
+            let mut buf = partial;
+            assert!(ProxyProtocolInfo::parse(&mut buf)
+                .unwrap_err()
+                .not_enough_bytes());
+        }
+    }
+}
@@ -5,11 +5,18 @@
 // Please see LICENSE in the repository root for full details.
 
 mod acceptor;
+mod info;
 mod maybe;
 mod v1;
+mod v2;
 
 pub use self::{
     acceptor::{ProxyAcceptError, ProxyAcceptor},
+    info::ProxyProtocolInfo,
     maybe::MaybeProxyAcceptor,
     v1::ProxyProtocolV1Info,
+    v2::{
+        ProxyProtocolV2Addresses, ProxyProtocolV2Command, ProxyProtocolV2Info, ProxyProtocolV2Ssl,
+        ProxyProtocolV2Tlvs,
+    },
 };
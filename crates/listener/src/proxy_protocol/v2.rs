@@ -0,0 +1,570 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Parser for the binary version 2 of the proxy protocol
+//!
+//! See <https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt>
+
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::Utf8Error,
+};
+
+use bytes::Buf;
+use thiserror::Error;
+
+/// The 12 bytes every v2 header starts with
+pub(super) const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Length of the fixed part of the header: the signature, the version and
+/// command, the address family and transport, and the length of the rest
+const HEADER_LEN: usize = 16;
+
+const PP2_TYPE_ALPN: u8 = 0x01;
+const PP2_TYPE_AUTHORITY: u8 = 0x02;
+const PP2_TYPE_UNIQUE_ID: u8 = 0x05;
+const PP2_TYPE_SSL: u8 = 0x20;
+const PP2_SUBTYPE_SSL_VERSION: u8 = 0x21;
+const PP2_SUBTYPE_SSL_CN: u8 = 0x22;
+const PP2_SUBTYPE_SSL_CIPHER: u8 = 0x23;
+const PP2_SUBTYPE_SSL_SIG_ALG: u8 = 0x24;
+const PP2_SUBTYPE_SSL_KEY_ALG: u8 = 0x25;
+const PP2_TYPE_NETNS: u8 = 0x30;
+const PP2_TYPE_AWS: u8 = 0xEA;
+const PP2_SUBTYPE_AWS_VPCE_ID: u8 = 0x01;
+
+const PP2_CLIENT_SSL: u8 = 0x01;
+const PP2_CLIENT_CERT_CONN: u8 = 0x02;
+const PP2_CLIENT_CERT_SESS: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyProtocolV2Command {
+    /// The connection was established by the proxy itself, e.g. for health
+    /// checks. The addresses should be ignored.
+    Local,
+
+    /// The connection was relayed on behalf of another peer
+    Proxy,
+}
+
+#[derive(Debug, Clone)]
+pub enum ProxyProtocolV2Addresses {
+    Tcp {
+        source: SocketAddr,
+        destination: SocketAddr,
+    },
+    Udp {
+        source: SocketAddr,
+        destination: SocketAddr,
+    },
+    /// The connection was made over a UNIX socket. The socket paths are not
+    /// kept.
+    Unix,
+    Unspecified,
+}
+
+/// Informations about the TLS connection between the client and the proxy
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ProxyProtocolV2Ssl {
+    /// Whether the client connected over TLS
+    pub client_ssl: bool,
+
+    /// Whether the client provided a certificate over this connection
+    pub client_cert_connection: bool,
+
+    /// Whether the client provided a certificate at least once over the TLS
+    /// session this connection belongs to
+    pub client_cert_session: bool,
+
+    /// Whether the client certificate was successfully verified, if one was
+    /// provided
+    pub verified: bool,
+
+    pub version: Option<String>,
+    pub common_name: Option<String>,
+    pub cipher: Option<String>,
+    pub signature_algorithm: Option<String>,
+    pub key_algorithm: Option<String>,
+}
+
+impl ProxyProtocolV2Ssl {
+    /// Returns [`true`] if the client presented a certificate which the proxy
+    /// successfully verified
+    #[must_use]
+    pub const fn client_certificate_verified(&self) -> bool {
+        (self.client_cert_connection || self.client_cert_session) && self.verified
+    }
+}
+
+/// The TLVs sent along the header. Unknown TLVs are ignored.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ProxyProtocolV2Tlvs {
+    /// The protocol negotiated with ALPN between the client and the proxy
+    pub alpn: Option<Vec<u8>>,
+
+    /// The host name the client asked for, usually through TLS SNI
+    pub authority: Option<String>,
+
+    /// An opaque identifier of the connection, generated by the proxy
+    pub unique_id: Option<Vec<u8>>,
+
+    pub ssl: Option<ProxyProtocolV2Ssl>,
+
+    /// The name of the network namespace the connection was received in
+    pub netns: Option<String>,
+
+    /// The ID of the AWS VPC endpoint the connection went through
+    pub aws_vpce_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ProxyProtocolV2Info {
+    pub command: ProxyProtocolV2Command,
+    pub addresses: ProxyProtocolV2Addresses,
+    pub tlvs: ProxyProtocolV2Tlvs,
+}
+
+#[derive(Error, Debug)]
+#[error("Invalid proxy protocol v2 header")]
+pub enum ParseError {
+    #[error("Not enough bytes provided")]
+    NotEnoughBytes,
+    NoSignature,
+    UnsupportedVersion(u8),
+    InvalidCommand(u8),
+    InvalidAddressFamily(u8),
+    InvalidTransport(u8),
+    AddressesTooShort,
+    TruncatedTlv,
+    InvalidSslTlv,
+    InvalidUtf8(#[from] Utf8Error),
+}
+
+impl ParseError {
+    pub const fn not_enough_bytes(&self) -> bool {
+        matches!(self, &Self::NotEnoughBytes)
+    }
+}
+
+/// Split the next TLV from the buffer, returning its type and value
+fn next_tlv<'a>(bytes: &mut &'a [u8]) -> Result<Option<(u8, &'a [u8])>, ParseError> {
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    if bytes.len() < 3 {
+        return Err(ParseError::TruncatedTlv);
+    }
+
+    let kind = bytes[0];
+    let len = usize::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+    if bytes.len() < 3 + len {
+        return Err(ParseError::TruncatedTlv);
+    }
+
+    let value = &bytes[3..3 + len];
+    *bytes = &bytes[3 + len..];
+    Ok(Some((kind, value)))
+}
+
+fn parse_string(value: &[u8]) -> Result<String, ParseError> {
+    Ok(std::str::from_utf8(value)?.to_owned())
+}
+
+fn parse_ssl(value: &[u8]) -> Result<ProxyProtocolV2Ssl, ParseError> {
+    if value.len() < 5 {
+        return Err(ParseError::InvalidSslTlv);
+    }
+
+    let client = value[0];
+    let verify = u32::from_be_bytes([value[1], value[2], value[3], value[4]]);
+    let mut ssl = ProxyProtocolV2Ssl {
+        client_ssl: client & PP2_CLIENT_SSL != 0,
+        client_cert_connection: client & PP2_CLIENT_CERT_CONN != 0,
+        client_cert_session: client & PP2_CLIENT_CERT_SESS != 0,
+        verified: verify == 0,
+        ..ProxyProtocolV2Ssl::default()
+    };
+
+    let mut rest = &value[5..];
+    while let Some((kind, value)) = next_tlv(&mut rest)? {
+        match kind {
+            PP2_SUBTYPE_SSL_VERSION => ssl.version = Some(parse_string(value)?),
+            PP2_SUBTYPE_SSL_CN => ssl.common_name = Some(parse_string(value)?),
+            PP2_SUBTYPE_SSL_CIPHER => ssl.cipher = Some(parse_string(value)?),
+            PP2_SUBTYPE_SSL_SIG_ALG => ssl.signature_algorithm = Some(parse_string(value)?),
+            PP2_SUBTYPE_SSL_KEY_ALG => ssl.key_algorithm = Some(parse_string(value)?),
+            _ => {}
+        }
+    }
+
+    Ok(ssl)
+}
+
+fn parse_tlvs(mut bytes: &[u8]) -> Result<ProxyProtocolV2Tlvs, ParseError> {
+    let mut tlvs = ProxyProtocolV2Tlvs::default();
+
+    while let Some((kind, value)) = next_tlv(&mut bytes)? {
+        match kind {
+            PP2_TYPE_ALPN => tlvs.alpn = Some(value.to_vec()),
+            PP2_TYPE_AUTHORITY => tlvs.authority = Some(parse_string(value)?),
+            PP2_TYPE_UNIQUE_ID => tlvs.unique_id = Some(value.to_vec()),
+            PP2_TYPE_SSL => tlvs.ssl = Some(parse_ssl(value)?),
+            PP2_TYPE_NETNS => tlvs.netns = Some(parse_string(value)?),
+            PP2_TYPE_AWS => {
+                if let Some((&PP2_SUBTYPE_AWS_VPCE_ID, id)) = value.split_first() {
+                    tlvs.aws_vpce_id = Some(parse_string(id)?);
+                }
+            }
+            // This includes the CRC32C checksum, which we don't verify, and the
+            // NOOP padding
+            _ => {}
+        }
+    }
+
+    Ok(tlvs)
+}
+
+impl ProxyProtocolV2Info {
+    pub(super) fn parse<B>(buf: &mut B) -> Result<Self, ParseError>
+    where
+        B: Buf + AsRef<[u8]>,
+    {
+        use ParseError as E;
+
+        let bytes = buf.as_ref();
+
+        // Fail early if what we have so far doesn't match the signature
+        let prefix = bytes.len().min(SIGNATURE.len());
+        if bytes[..prefix] != SIGNATURE[..prefix] {
+            return Err(E::NoSignature);
+        }
+
+        if bytes.len() < HEADER_LEN {
+            return Err(E::NotEnoughBytes);
+        }
+
+        let version = bytes[12] >> 4;
+        if version != 2 {
+            return Err(E::UnsupportedVersion(version));
+        }
+
+        let command = match bytes[12] & 0x0F {
+            0x0 => ProxyProtocolV2Command::Local,
+            0x1 => ProxyProtocolV2Command::Proxy,
+            command => return Err(E::InvalidCommand(command)),
+        };
+
+        let family = bytes[13] >> 4;
+        let transport = bytes[13] & 0x0F;
+        let len = usize::from(u16::from_be_bytes([bytes[14], bytes[15]]));
+        if bytes.len() < HEADER_LEN + len {
+            return Err(E::NotEnoughBytes);
+        }
+
+        let payload = &bytes[HEADER_LEN..HEADER_LEN + len];
+
+        let (addresses, tlvs) = match family {
+            // AF_UNSPEC
+            0x0 => (ProxyProtocolV2Addresses::Unspecified, payload),
+
+            // AF_INET
+            0x1 => {
+                if payload.len() < 12 {
+                    return Err(E::AddressesTooShort);
+                }
+
+                let source_address = Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]);
+                let destination_address =
+                    Ipv4Addr::new(payload[4], payload[5], payload[6], payload[7]);
+                let source_port = u16::from_be_bytes([payload[8], payload[9]]);
+                let destination_port = u16::from_be_bytes([payload[10], payload[11]]);
+
+                let source = (source_address, source_port).into();
+                let destination = (destination_address, destination_port).into();
+                let addresses = match transport {
+                    0x1 => ProxyProtocolV2Addresses::Tcp {
+                        source,
+                        destination,
+                    },
+                    0x2 => ProxyProtocolV2Addresses::Udp {
+                        source,
+                        destination,
+                    },
+                    transport => return Err(E::InvalidTransport(transport)),
+                };
+
+                (addresses, &payload[12..])
+            }
+
+            // AF_INET6
+            0x2 => {
+                if payload.len() < 36 {
+                    return Err(E::AddressesTooShort);
+                }
+
+                let mut source_address = [0; 16];
+                source_address.copy_from_slice(&payload[0..16]);
+                let mut destination_address = [0; 16];
+                destination_address.copy_from_slice(&payload[16..32]);
+                let source_port = u16::from_be_bytes([payload[32], payload[33]]);
+                let destination_port = u16::from_be_bytes([payload[34], payload[35]]);
+
+                let source = (Ipv6Addr::from(source_address), source_port).into();
+                let destination = (Ipv6Addr::from(destination_address), destination_port).into();
+                let addresses = match transport {
+                    0x1 => ProxyProtocolV2Addresses::Tcp {
+                        source,
+                        destination,
+                    },
+                    0x2 => ProxyProtocolV2Addresses::Udp {
+                        source,
+                        destination,
+                    },
+                    transport => return Err(E::InvalidTransport(transport)),
+                };
+
+                (addresses, &payload[36..])
+            }
+
+            // AF_UNIX
+            0x3 => {
+                if payload.len() < 216 {
+                    return Err(E::AddressesTooShort);
+                }
+
+                (ProxyProtocolV2Addresses::Unix, &payload[216..])
+            }
+
+            family => return Err(E::InvalidAddressFamily(family)),
+        };
+
+        let tlvs = parse_tlvs(tlvs)?;
+
+        buf.advance(HEADER_LEN + len);
+
+        Ok(Self {
+            command,
+            addresses,
+            tlvs,
+        })
+    }
+
+    #[must_use]
+    pub const fn is_local(&self) -> bool {
+        matches!(self.command, ProxyProtocolV2Command::Local)
+    }
+
+    #[must_use]
+    pub fn is_ipv4(&self) -> bool {
+        match &self.addresses {
+            ProxyProtocolV2Addresses::Udp {
+                source,
+                destination,
+            }
+            | ProxyProtocolV2Addresses::Tcp {
+                source,
+                destination,
+            } => source.is_ipv4() && destination.is_ipv4(),
+            ProxyProtocolV2Addresses::Unix | ProxyProtocolV2Addresses::Unspecified => false,
+        }
+    }
+
+    #[must_use]
+    pub fn is_ipv6(&self) -> bool {
+        match &self.addresses {
+            ProxyProtocolV2Addresses::Udp {
+                source,
+                destination,
+            }
+            | ProxyProtocolV2Addresses::Tcp {
+                source,
+                destination,
+            } => source.is_ipv6() && destination.is_ipv6(),
+            ProxyProtocolV2Addresses::Unix | ProxyProtocolV2Addresses::Unspecified => false,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_tcp(&self) -> bool {
+        matches!(self.addresses, ProxyProtocolV2Addresses::Tcp { .. })
+    }
+
+    #[must_use]
+    pub const fn is_udp(&self) -> bool {
+        matches!(self.addresses, ProxyProtocolV2Addresses::Udp { .. })
+    }
+
+    /// The address of the original client. Returns [`None`] for local
+    /// connections, as the addresses should then be ignored.
+    #[must_use]
+    pub const fn source(&self) -> Option<&SocketAddr> {
+        if self.is_local() {
+            return None;
+        }
+
+        match &self.addresses {
+            ProxyProtocolV2Addresses::Udp { source, .. }
+            | ProxyProtocolV2Addresses::Tcp { source, .. } => Some(source),
+            ProxyProtocolV2Addresses::Unix | ProxyProtocolV2Addresses::Unspecified => None,
+        }
+    }
+
+    /// The address the original client connected to. Returns [`None`] for
+    /// local connections, as the addresses should then be ignored.
+    #[must_use]
+    pub const fn destination(&self) -> Option<&SocketAddr> {
+        if self.is_local() {
+            return None;
+        }
+
+        match &self.addresses {
+            ProxyProtocolV2Addresses::Udp { destination, .. }
+            | ProxyProtocolV2Addresses::Tcp { destination, .. } => Some(destination),
+            ProxyProtocolV2Addresses::Unix | ProxyProtocolV2Addresses::Unspecified => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a v2 header from its parts
+    fn header(ver_cmd: u8, family: u8, payload: &[u8]) -> Vec<u8> {
+        let mut header = SIGNATURE.to_vec();
+        header.push(ver_cmd);
+        header.push(family);
+        header.extend_from_slice(&u16::try_from(payload.len()).unwrap().to_be_bytes());
+        header.extend_from_slice(payload);
+        header
+    }
+
+    fn tlv(kind: u8, value: &[u8]) -> Vec<u8> {
+        let mut tlv = vec![kind];
+        tlv.extend_from_slice(&u16::try_from(value.len()).unwrap().to_be_bytes());
+        tlv.extend_from_slice(value);
+        tlv
+    }
+
+    #[test]
+    fn test_parse_tcp4() {
+        let mut payload = vec![
+            192, 0, 2, 1, // source address
+            198, 51, 100, 1, // destination address
+            0xC3, 0x50, // source port (50000)
+            0x01, 0xBB, // destination port (443)
+        ];
+        payload.extend(tlv(PP2_TYPE_AUTHORITY, b"auth.example.com"));
+        payload.extend(tlv(PP2_TYPE_ALPN, b"h2"));
+        payload.extend(tlv(0x04, &[0; 8])); // NOOP padding
+
+        let mut ssl = vec![PP2_CLIENT_SSL | PP2_CLIENT_CERT_CONN, 0, 0, 0, 0];
+        ssl.extend(tlv(PP2_SUBTYPE_SSL_VERSION, b"TLSv1.3"));
+        ssl.extend(tlv(PP2_SUBTYPE_SSL_CN, b"client.example.com"));
+        payload.extend(tlv(PP2_TYPE_SSL, &ssl));
+
+        let mut buf = header(0x21, 0x11, &payload);
+        buf.extend_from_slice(b"hello world");
+        let mut buf = buf.as_slice();
+
+        let info = ProxyProtocolV2Info::parse(&mut buf).unwrap();
+        assert_eq!(buf, b"hello world");
+        assert!(info.is_tcp());
+        assert!(!info.is_udp());
+        assert!(!info.is_local());
+        assert!(info.is_ipv4());
+        assert!(!info.is_ipv6());
+        assert_eq!(info.source(), Some(&"192.0.2.1:50000".parse().unwrap()));
+        assert_eq!(
+            info.destination(),
+            Some(&"198.51.100.1:443".parse().unwrap())
+        );
+
+        assert_eq!(info.tlvs.authority.as_deref(), Some("auth.example.com"));
+        assert_eq!(info.tlvs.alpn.as_deref(), Some(b"h2".as_slice()));
+        let ssl = info.tlvs.ssl.unwrap();
+        assert!(ssl.client_ssl);
+        assert!(ssl.client_certificate_verified());
+        assert_eq!(ssl.version.as_deref(), Some("TLSv1.3"));
+        assert_eq!(ssl.common_name.as_deref(), Some("client.example.com"));
+    }
+
+    #[test]
+    fn test_parse_tcp6() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        payload.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        payload.extend_from_slice(&50000_u16.to_be_bytes());
+        payload.extend_from_slice(&443_u16.to_be_bytes());
+        let mut aws = vec![PP2_SUBTYPE_AWS_VPCE_ID];
+        aws.extend_from_slice(b"vpce-08d2bf15fac5001c9");
+        payload.extend(tlv(PP2_TYPE_AWS, &aws));
+
+        let buf = header(0x21, 0x21, &payload);
+        let mut buf = buf.as_slice();
+
+        let info = ProxyProtocolV2Info::parse(&mut buf).unwrap();
+        assert!(buf.is_empty());
+        assert!(info.is_tcp());
+        assert!(info.is_ipv6());
+        assert_eq!(info.source(), Some(&"[2001:db8::1]:50000".parse().unwrap()));
+        assert_eq!(
+            info.tlvs.aws_vpce_id.as_deref(),
+            Some("vpce-08d2bf15fac5001c9")
+        );
+        assert!(info.tlvs.ssl.is_none());
+    }
+
+    #[test]
+    fn test_parse_local() {
+        let buf = header(0x20, 0x00, &[]);
+        let mut buf = buf.as_slice();
+
+        let info = ProxyProtocolV2Info::parse(&mut buf).unwrap();
+        assert!(buf.is_empty());
+        assert!(info.is_local());
+        assert!(info.source().is_none());
+        assert!(info.destination().is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // Incomplete headers
+        let full = header(0x21, 0x11, &[0; 12]);
+        for len in [0, 5, 12, 16, 20] {
+            let mut buf = &full[..len];
+            let err = ProxyProtocolV2Info::parse(&mut buf).unwrap_err();
+            assert!(err.not_enough_bytes(), "{len}: {err:?}");
+        }
+
+        // Not a v2 header
+        let mut buf = b"PROXY TCP4 255.255.255.255 255.255.255.255 65535 65535\r\n".as_slice();
+        assert!(matches!(
+            ProxyProtocolV2Info::parse(&mut buf),
+            Err(ParseError::NoSignature)
+        ));
+
+        // Wrong version
+        let buf = header(0x11, 0x11, &[0; 12]);
+        assert!(matches!(
+            ProxyProtocolV2Info::parse(&mut buf.as_slice()),
+            Err(ParseError::UnsupportedVersion(1))
+        ));
+
+        // Truncated TLV
+        let mut payload = vec![0; 12];
+        payload.extend_from_slice(&[PP2_TYPE_AUTHORITY, 0, 10, b'a']);
+        let buf = header(0x21, 0x11, &payload);
+        assert!(matches!(
+            ProxyProtocolV2Info::parse(&mut buf.as_slice()),
+            Err(ParseError::TruncatedTlv)
+        ));
+    }
+}
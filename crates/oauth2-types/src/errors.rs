@@ -265,6 +265,20 @@ pub enum ClientErrorCode {
     /// From [RFC7009](https://www.rfc-editor.org/rfc/rfc7009#section-2.2.1).
     UnsupportedTokenType,
 
+    /// `invalid_dpop_proof`
+    ///
+    /// The DPoP proof is invalid.
+    ///
+    /// From [RFC9449](https://www.rfc-editor.org/rfc/rfc9449#section-5).
+    InvalidDpopProof,
+
+    /// `invalid_target`
+    ///
+    /// The requested resource or audience is invalid, unknown or malformed.
+    ///
+    /// From [RFC8693](https://www.rfc-editor.org/rfc/rfc8693#section-2.2.2).
+    InvalidTarget,
+
     /// Another error code.
     Unknown(String),
 }
@@ -297,6 +311,8 @@ impl core::fmt::Display for ClientErrorCode {
             ClientErrorCode::SlowDown => f.write_str("slow_down"),
             ClientErrorCode::ExpiredToken => f.write_str("expired_token"),
             ClientErrorCode::UnsupportedTokenType => f.write_str("unsupported_token_type"),
+            ClientErrorCode::InvalidDpopProof => f.write_str("invalid_dpop_proof"),
+            ClientErrorCode::InvalidTarget => f.write_str("invalid_target"),
             ClientErrorCode::Unknown(value) => f.write_str(value),
         }
     }
@@ -332,6 +348,8 @@ impl core::str::FromStr for ClientErrorCode {
             "slow_down" => Ok(ClientErrorCode::SlowDown),
             "expired_token" => Ok(ClientErrorCode::ExpiredToken),
             "unsupported_token_type" => Ok(ClientErrorCode::UnsupportedTokenType),
+            "invalid_dpop_proof" => Ok(ClientErrorCode::InvalidDpopProof),
+            "invalid_target" => Ok(ClientErrorCode::InvalidTarget),
             _ => Ok(ClientErrorCode::Unknown(s.to_owned())),
         }
     }
@@ -423,6 +441,10 @@ impl ClientErrorCode {
             ClientErrorCode::UnsupportedTokenType => {
                 "The authorization server does not support the revocation of the presented token type."
             },
+            ClientErrorCode::InvalidDpopProof => "The DPoP proof is invalid.",
+            ClientErrorCode::InvalidTarget => {
+                "The requested resource or audience is invalid, unknown or malformed."
+            }
             ClientErrorCode::Unknown(_) => "",
         }
     }
@@ -518,6 +540,14 @@ mod tests {
             serde_json::to_string(&ClientErrorCode::InvalidClientMetadata).unwrap(),
             "\"invalid_client_metadata\""
         );
+        assert_eq!(
+            serde_json::to_string(&ClientErrorCode::InvalidDpopProof).unwrap(),
+            "\"invalid_dpop_proof\""
+        );
+        assert_eq!(
+            serde_json::to_string(&ClientErrorCode::InvalidTarget).unwrap(),
+            "\"invalid_target\""
+        );
 
         assert_eq!(
             serde_json::to_string(&ClientErrorCode::Unknown("unknown_error_code".to_owned()))
@@ -612,6 +642,14 @@ mod tests {
             serde_json::from_str::<ClientErrorCode>("\"invalid_client_metadata\"").unwrap(),
             ClientErrorCode::InvalidClientMetadata
         );
+        assert_eq!(
+            serde_json::from_str::<ClientErrorCode>("\"invalid_dpop_proof\"").unwrap(),
+            ClientErrorCode::InvalidDpopProof
+        );
+        assert_eq!(
+            serde_json::from_str::<ClientErrorCode>("\"invalid_target\"").unwrap(),
+            ClientErrorCode::InvalidTarget
+        );
 
         assert_eq!(
             serde_json::from_str::<ClientErrorCode>("\"unknown_error_code\"").unwrap(),
@@ -596,6 +596,12 @@ pub struct ProviderMetadata {
     /// Defaults to `false`.
     pub require_pushed_authorization_requests: Option<bool>,
 
+    /// JSON array containing a list of the JWS algorithms supported for
+    /// [DPoP] proof JWTs.
+    ///
+    /// [DPoP]: https://www.rfc-editor.org/rfc/rfc9449.html
+    pub dpop_signing_alg_values_supported: Option<Vec<JsonWebSignatureAlg>>,
+
     /// Array containing the list of prompt values that this OP supports.
     ///
     /// This field can be used to detect if the OP supports the [prompt
@@ -614,6 +620,22 @@ pub struct ProviderMetadata {
     /// [RP-Initiated Logout endpoint]: https://openid.net/specs/openid-connect-rpinitiated-1_0.html
     pub end_session_endpoint: Option<Url>,
 
+    /// Boolean value specifying whether the OP supports [Back-Channel Logout].
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [Back-Channel Logout]: https://openid.net/specs/openid-connect-backchannel-1_0.html
+    pub backchannel_logout_supported: Option<bool>,
+
+    /// Boolean value specifying whether the OP can pass a `sid` (session ID)
+    /// Claim in the Logout Token to identify the RP session with the OP.
+    ///
+    /// If supported, the `sid` Claim is also included in ID Tokens issued by
+    /// the OP.
+    ///
+    /// Defaults to `false`.
+    pub backchannel_logout_session_supported: Option<bool>,
+
     /// URL where the user is able to access the account management capabilities
     /// of this OP.
     ///
@@ -119,6 +119,8 @@ pub struct ClientMetadataSerdeHelper {
     introspection_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
     introspection_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
     post_logout_redirect_uris: Option<Vec<Url>>,
+    backchannel_logout_uri: Option<Url>,
+    backchannel_logout_session_required: Option<bool>,
     #[serde(flatten)]
     extra: ClientMetadataLocalizedFields,
 }
@@ -166,6 +168,8 @@ impl From<VerifiedClientMetadata> for ClientMetadataSerdeHelper {
                     introspection_encrypted_response_alg,
                     introspection_encrypted_response_enc,
                     post_logout_redirect_uris,
+                    backchannel_logout_uri,
+                    backchannel_logout_session_required,
                 },
         } = metadata;
 
@@ -203,6 +207,8 @@ impl From<VerifiedClientMetadata> for ClientMetadataSerdeHelper {
             introspection_encrypted_response_alg,
             introspection_encrypted_response_enc,
             post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
             extra: ClientMetadataLocalizedFields {
                 client_name,
                 logo_uri,
@@ -250,6 +256,8 @@ impl From<ClientMetadataSerdeHelper> for ClientMetadata {
             introspection_encrypted_response_alg,
             introspection_encrypted_response_enc,
             post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
             extra:
                 ClientMetadataLocalizedFields {
                     client_name,
@@ -299,6 +307,8 @@ impl From<ClientMetadataSerdeHelper> for ClientMetadata {
             introspection_encrypted_response_alg,
             introspection_encrypted_response_enc,
             post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
         }
     }
 }
@@ -428,6 +428,21 @@ pub struct ClientMetadata {
     ///
     /// [RP-Initiated Logout endpoint]: https://openid.net/specs/openid-connect-rpinitiated-1_0.html
     pub post_logout_redirect_uris: Option<Vec<Url>>,
+
+    /// URL that will cause the client to log itself out when rendered by the
+    /// provider using the [Back-Channel Logout] protocol.
+    ///
+    /// This URL must not include a fragment component.
+    ///
+    /// [Back-Channel Logout]: https://openid.net/specs/openid-connect-backchannel-1_0.html
+    pub backchannel_logout_uri: Option<Url>,
+
+    /// Whether the client requires that a `sid` (session ID) Claim be included
+    /// in the Logout Token to identify the client session with the provider
+    /// when the `backchannel_logout_uri` is used.
+    ///
+    /// Defaults to `false`.
+    pub backchannel_logout_session_required: Option<bool>,
 }
 
 impl ClientMetadata {
@@ -560,6 +575,16 @@ impl ClientMetadata {
             )?;
         }
 
+        if let Some(url) = self
+            .backchannel_logout_uri
+            .as_ref()
+            .filter(|url| url.fragment().is_some())
+        {
+            return Err(
+                ClientMetadataVerificationError::BackchannelLogoutUriWithFragment(url.clone()),
+            );
+        }
+
         Ok(VerifiedClientMetadata { inner: self })
     }
 
@@ -736,6 +761,17 @@ impl ClientMetadata {
             .unwrap_or_default()
     }
 
+    /// Whether the client requires the `sid` Claim to be included in the
+    /// [Back-Channel Logout] tokens sent to it.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [Back-Channel Logout]: https://openid.net/specs/openid-connect-backchannel-1_0.html
+    #[must_use]
+    pub fn backchannel_logout_session_required(&self) -> bool {
+        self.backchannel_logout_session_required.unwrap_or_default()
+    }
+
     /// [JWE] `alg` and `enc` algorithms for encrypting responses of the
     /// [introspection endpoint].
     ///
@@ -874,6 +910,10 @@ pub enum ClientMetadataVerificationError {
     /// The given encryption field has an `enc` value but not `alg` value.
     #[error("{0} missing encryption alg value")]
     MissingEncryptionAlg(&'static str),
+
+    /// The back-channel logout URI has a fragment, which is not allowed.
+    #[error("backchannel logout URI with fragment: {0}")]
+    BackchannelLogoutUriWithFragment(Url),
 }
 
 /// The issuer response to dynamic client registration.
@@ -1402,4 +1442,23 @@ mod tests {
         metadata.introspection_encrypted_response_alg = Some(JsonWebEncryptionAlg::RsaOaep);
         metadata.validate().unwrap();
     }
+
+    #[test]
+    fn validate_backchannel_logout_uri() {
+        let mut metadata = valid_client_metadata();
+
+        // Err - Fragment
+        let wrong_uri = Url::parse("https://localhost/logout#fragment").unwrap();
+        metadata.backchannel_logout_uri = Some(wrong_uri.clone());
+        let uri = assert_matches!(
+            metadata.clone().validate(),
+            Err(ClientMetadataVerificationError::BackchannelLogoutUriWithFragment(uri)) => uri
+        );
+        assert_eq!(uri, wrong_uri);
+
+        // Ok - Path & Query
+        metadata.backchannel_logout_uri =
+            Some(Url::parse("https://localhost/logout?backchannel").unwrap());
+        metadata.validate().unwrap();
+    }
 }
@@ -542,6 +542,144 @@ impl fmt::Debug for DeviceCodeGrant {
     }
 }
 
+/// A request to the [Token Endpoint] for the [JWT Bearer] grant type.
+///
+/// [Token Endpoint]: https://www.rfc-editor.org/rfc/rfc6749#section-3.2
+/// [JWT Bearer]: https://www.rfc-editor.org/rfc/rfc7523#section-2.1
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JwtBearerGrant {
+    /// The signed JWT used as an authorization grant.
+    pub assertion: String,
+
+    /// The scope of the access request.
+    pub scope: Option<Scope>,
+}
+
+impl fmt::Debug for JwtBearerGrant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtBearerGrant")
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A request to the [Token Endpoint] for the [Token Exchange] grant type.
+///
+/// [Token Endpoint]: https://www.rfc-editor.org/rfc/rfc6749#section-3.2
+/// [Token Exchange]: https://www.rfc-editor.org/rfc/rfc8693
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TokenExchangeGrant {
+    /// The token that represents the identity of the party on behalf of whom
+    /// the request is being made.
+    pub subject_token: String,
+
+    /// The type of the `subject_token`.
+    pub subject_token_type: TokenTypeIdentifier,
+
+    /// The token that represents the identity of the acting party.
+    pub actor_token: Option<String>,
+
+    /// The type of the `actor_token`.
+    pub actor_token_type: Option<TokenTypeIdentifier>,
+
+    /// The URI of the target service or resource where the client intends to
+    /// use the requested token.
+    pub resource: Option<Url>,
+
+    /// The logical name of the target service where the client intends to use
+    /// the requested token.
+    pub audience: Option<String>,
+
+    /// The scope of the requested token.
+    pub scope: Option<Scope>,
+
+    /// The type of the requested token.
+    pub requested_token_type: Option<TokenTypeIdentifier>,
+}
+
+impl fmt::Debug for TokenExchangeGrant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenExchangeGrant")
+            .field("subject_token_type", &self.subject_token_type)
+            .field("actor_token_type", &self.actor_token_type)
+            .field("resource", &self.resource)
+            .field("audience", &self.audience)
+            .field("scope", &self.scope)
+            .field("requested_token_type", &self.requested_token_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// All possible values for the token type identifiers used in the [Token
+/// Exchange] grant type.
+///
+/// [Token Exchange]: https://www.rfc-editor.org/rfc/rfc8693#section-3
+#[derive(
+    Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, SerializeDisplay, DeserializeFromStr,
+)]
+pub enum TokenTypeIdentifier {
+    /// `urn:ietf:params:oauth:token-type:access_token`
+    AccessToken,
+
+    /// `urn:ietf:params:oauth:token-type:refresh_token`
+    RefreshToken,
+
+    /// `urn:ietf:params:oauth:token-type:id_token`
+    IdToken,
+
+    /// `urn:ietf:params:oauth:token-type:saml1`
+    Saml1,
+
+    /// `urn:ietf:params:oauth:token-type:saml2`
+    Saml2,
+
+    /// `urn:ietf:params:oauth:token-type:jwt`
+    Jwt,
+
+    /// An unknown value.
+    Unknown(String),
+}
+
+impl core::fmt::Display for TokenTypeIdentifier {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TokenTypeIdentifier::AccessToken => {
+                f.write_str("urn:ietf:params:oauth:token-type:access_token")
+            }
+            TokenTypeIdentifier::RefreshToken => {
+                f.write_str("urn:ietf:params:oauth:token-type:refresh_token")
+            }
+            TokenTypeIdentifier::IdToken => {
+                f.write_str("urn:ietf:params:oauth:token-type:id_token")
+            }
+            TokenTypeIdentifier::Saml1 => f.write_str("urn:ietf:params:oauth:token-type:saml1"),
+            TokenTypeIdentifier::Saml2 => f.write_str("urn:ietf:params:oauth:token-type:saml2"),
+            TokenTypeIdentifier::Jwt => f.write_str("urn:ietf:params:oauth:token-type:jwt"),
+            TokenTypeIdentifier::Unknown(s) => f.write_str(s),
+        }
+    }
+}
+
+impl core::str::FromStr for TokenTypeIdentifier {
+    type Err = core::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "urn:ietf:params:oauth:token-type:access_token" => Ok(TokenTypeIdentifier::AccessToken),
+            "urn:ietf:params:oauth:token-type:refresh_token" => {
+                Ok(TokenTypeIdentifier::RefreshToken)
+            }
+            "urn:ietf:params:oauth:token-type:id_token" => Ok(TokenTypeIdentifier::IdToken),
+            "urn:ietf:params:oauth:token-type:saml1" => Ok(TokenTypeIdentifier::Saml1),
+            "urn:ietf:params:oauth:token-type:saml2" => Ok(TokenTypeIdentifier::Saml2),
+            "urn:ietf:params:oauth:token-type:jwt" => Ok(TokenTypeIdentifier::Jwt),
+            s => Ok(TokenTypeIdentifier::Unknown(s.to_owned())),
+        }
+    }
+}
+
 /// All possible values for the `grant_type` parameter.
 #[derive(
     Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, SerializeDisplay, DeserializeFromStr,
@@ -571,6 +709,9 @@ pub enum GrantType {
     /// [`urn:openid:params:grant-type:ciba`](https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html)
     ClientInitiatedBackchannelAuthentication,
 
+    /// [`urn:ietf:params:oauth:grant-type:token-exchange`](https://www.rfc-editor.org/rfc/rfc8693)
+    TokenExchange,
+
     /// An unknown value.
     Unknown(String),
 }
@@ -588,6 +729,9 @@ impl core::fmt::Display for GrantType {
             GrantType::ClientInitiatedBackchannelAuthentication => {
                 f.write_str("urn:openid:params:grant-type:ciba")
             }
+            GrantType::TokenExchange => {
+                f.write_str("urn:ietf:params:oauth:grant-type:token-exchange")
+            }
             GrantType::Unknown(s) => f.write_str(s),
         }
     }
@@ -608,6 +752,7 @@ impl core::str::FromStr for GrantType {
             "urn:openid:params:grant-type:ciba" => {
                 Ok(GrantType::ClientInitiatedBackchannelAuthentication)
             }
+            "urn:ietf:params:oauth:grant-type:token-exchange" => Ok(GrantType::TokenExchange),
             s => Ok(GrantType::Unknown(s.to_owned())),
         }
     }
@@ -633,6 +778,14 @@ pub enum AccessTokenRequest {
     #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
     DeviceCode(DeviceCodeGrant),
 
+    /// A request with a JWT Bearer authorization grant.
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:jwt-bearer")]
+    JwtBearer(JwtBearerGrant),
+
+    /// A request in the Token Exchange flow.
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:token-exchange")]
+    TokenExchange(TokenExchangeGrant),
+
     /// An unsupported request.
     #[serde(skip_serializing, other)]
     Unsupported,
@@ -664,6 +817,9 @@ pub struct AccessTokenResponse {
 
     /// The scope of the access token.
     pub scope: Option<Scope>,
+
+    /// The type of the issued token, in the Token Exchange flow.
+    pub issued_token_type: Option<TokenTypeIdentifier>,
 }
 
 impl AccessTokenResponse {
@@ -677,6 +833,7 @@ impl AccessTokenResponse {
             token_type: OAuthAccessTokenType::Bearer,
             expires_in: None,
             scope: None,
+            issued_token_type: None,
         }
     }
 
@@ -694,6 +851,13 @@ impl AccessTokenResponse {
         self
     }
 
+    /// Sets the type of the access token in an `AccessTokenResponse`.
+    #[must_use]
+    pub fn with_token_type(mut self, token_type: OAuthAccessTokenType) -> Self {
+        self.token_type = token_type;
+        self
+    }
+
     /// Adds a scope to an `AccessTokenResponse`.
     #[must_use]
     pub fn with_scope(mut self, scope: Scope) -> Self {
@@ -707,6 +871,13 @@ impl AccessTokenResponse {
         self.expires_in = Some(expires_in);
         self
     }
+
+    /// Sets the type of the issued token in an `AccessTokenResponse`.
+    #[must_use]
+    pub fn with_issued_token_type(mut self, issued_token_type: TokenTypeIdentifier) -> Self {
+        self.issued_token_type = Some(issued_token_type);
+        self
+    }
 }
 
 impl fmt::Debug for AccessTokenResponse {
@@ -715,6 +886,7 @@ impl fmt::Debug for AccessTokenResponse {
             .field("token_type", &self.token_type)
             .field("expires_in", &self.expires_in)
             .field("scope", &self.scope)
+            .field("issued_token_type", &self.issued_token_type)
             .finish_non_exhaustive()
     }
 }
@@ -786,6 +958,34 @@ pub struct IntrospectionResponse {
 
     /// String identifier for the token.
     pub jti: Option<String>,
+
+    /// Confirmation method the token is bound to.
+    pub cnf: Option<TokenConfirmation>,
+
+    /// The party acting on behalf of the subject, for tokens obtained through
+    /// a token exchange.
+    pub act: Option<TokenActor>,
+}
+
+/// The confirmation claim of a sender-constrained token.
+///
+/// See [RFC9449](https://www.rfc-editor.org/rfc/rfc9449#section-6).
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct TokenConfirmation {
+    /// The base64url-encoded SHA-256 thumbprint of the DPoP public key the
+    /// token is bound to.
+    pub jkt: Option<String>,
+}
+
+/// The actor claim of a token obtained through a token exchange.
+///
+/// See [RFC8693](https://www.rfc-editor.org/rfc/rfc8693#section-4.1).
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct TokenActor {
+    /// Identifier of the client which obtained the token.
+    pub client_id: Option<String>,
 }
 
 /// A request to the [Revocation Endpoint].
@@ -872,6 +1072,46 @@ mod tests {
         assert_serde_json(&req, expected);
     }
 
+    #[test]
+    fn serde_jwt_bearer_grant() {
+        let expected = json!({
+            "grant_type": "urn:ietf:params:oauth:grant-type:jwt-bearer",
+            "assertion": "abcd",
+            "scope": "openid",
+        });
+
+        let req = AccessTokenRequest::JwtBearer(JwtBearerGrant {
+            assertion: "abcd".into(),
+            scope: Some(vec![OPENID].into_iter().collect()),
+        });
+
+        assert_serde_json(&req, expected);
+    }
+
+    #[test]
+    fn serde_token_exchange_grant() {
+        let expected = json!({
+            "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+            "subject_token": "abcd",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+            "audience": "bridge",
+            "scope": "openid",
+        });
+
+        let req = AccessTokenRequest::TokenExchange(TokenExchangeGrant {
+            subject_token: "abcd".into(),
+            subject_token_type: TokenTypeIdentifier::AccessToken,
+            actor_token: None,
+            actor_token_type: None,
+            resource: None,
+            audience: Some("bridge".into()),
+            scope: Some(vec![OPENID].into_iter().collect()),
+            requested_token_type: None,
+        });
+
+        assert_serde_json(&req, expected);
+    }
+
     #[test]
     fn serialize_grant_type() {
         assert_eq!(
@@ -902,6 +1142,10 @@ mod tests {
             serde_json::to_string(&GrantType::ClientInitiatedBackchannelAuthentication).unwrap(),
             "\"urn:openid:params:grant-type:ciba\""
         );
+        assert_eq!(
+            serde_json::to_string(&GrantType::TokenExchange).unwrap(),
+            "\"urn:ietf:params:oauth:grant-type:token-exchange\""
+        );
     }
 
     #[test]
@@ -935,6 +1179,13 @@ mod tests {
             serde_json::from_str::<GrantType>("\"urn:openid:params:grant-type:ciba\"").unwrap(),
             GrantType::ClientInitiatedBackchannelAuthentication
         );
+        assert_eq!(
+            serde_json::from_str::<GrantType>(
+                "\"urn:ietf:params:oauth:grant-type:token-exchange\""
+            )
+            .unwrap(),
+            GrantType::TokenExchange
+        );
     }
 
     #[test]
@@ -326,6 +326,7 @@ async fn pass_access_token_with_authorization_code() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: Some([ScopeToken::Openid].into_iter().collect()),
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
@@ -385,6 +386,7 @@ async fn fail_access_token_with_authorization_code_wrong_nonce() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: Some([ScopeToken::Openid].into_iter().collect()),
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
@@ -447,6 +449,7 @@ async fn fail_access_token_with_authorization_code_no_id_token() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: Some([ScopeToken::Openid].into_iter().collect()),
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
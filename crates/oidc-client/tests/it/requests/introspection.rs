@@ -74,6 +74,8 @@ async fn pass_introspect_token() {
                 aud: Some(CLIENT_ID.to_owned()),
                 iss: Some(issuer.to_string()),
                 jti: None,
+                cnf: None,
+                act: None,
             }),
         )
         .mount(&mock_server)
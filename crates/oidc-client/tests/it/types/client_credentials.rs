@@ -61,6 +61,7 @@ async fn pass_none() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: None,
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
@@ -106,6 +107,7 @@ async fn pass_client_secret_basic() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: None,
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
@@ -166,6 +168,7 @@ async fn pass_client_secret_post() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: None,
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
@@ -247,6 +250,7 @@ async fn pass_client_secret_jwt() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: None,
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
@@ -335,6 +339,7 @@ async fn pass_private_key_jwt_with_keystore() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: None,
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
@@ -404,6 +409,7 @@ async fn pass_private_key_jwt_with_custom_signing() {
                 token_type: OAuthAccessTokenType::Bearer,
                 expires_in: None,
                 scope: None,
+                issued_token_type: None,
             }),
         )
         .mount(&mock_server)
@@ -7,7 +7,8 @@
 use std::path::{Path, PathBuf};
 
 use mas_policy::model::{
-    AuthorizationGrantInput, ClientRegistrationInput, EmailInput, PasswordInput, RegisterInput,
+    AuthorizationGrantInput, AuthorizationRequestInput, ClientRegistrationInput, EmailInput,
+    JwtBearerInput, PasswordInput, RegisterInput, TokenExchangeInput,
 };
 use schemars::{gen::SchemaSettings, JsonSchema};
 
@@ -44,6 +45,9 @@ fn main() {
     write_schema::<RegisterInput>(output_root, "register_input.json");
     write_schema::<ClientRegistrationInput>(output_root, "client_registration_input.json");
     write_schema::<AuthorizationGrantInput>(output_root, "authorization_grant_input.json");
+    write_schema::<AuthorizationRequestInput>(output_root, "authorization_request_input.json");
+    write_schema::<TokenExchangeInput>(output_root, "token_exchange_input.json");
+    write_schema::<JwtBearerInput>(output_root, "jwt_bearer_input.json");
     write_schema::<EmailInput>(output_root, "email_input.json");
     write_schema::<PasswordInput>(output_root, "password_input.json");
 }
@@ -15,7 +15,10 @@ use opa_wasm::{
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncReadExt};
 
-use self::model::{AuthorizationGrantInput, ClientRegistrationInput, EmailInput, RegisterInput};
+use self::model::{
+    AuthorizationGrantInput, AuthorizationRequestInput, ClientRegistrationInput, EmailInput,
+    JwtBearerInput, RegisterInput, TokenExchangeInput,
+};
 pub use self::model::{EvaluationResult, Violation};
 use crate::model::GrantType;
 
@@ -55,15 +58,21 @@ pub struct Entrypoints {
     pub register: String,
     pub client_registration: String,
     pub authorization_grant: String,
+    pub authorization_request: String,
+    pub token_exchange: String,
+    pub jwt_bearer: String,
     pub email: String,
 }
 
 impl Entrypoints {
-    fn all(&self) -> [&str; 4] {
+    fn all(&self) -> [&str; 7] {
         [
             self.register.as_str(),
             self.client_registration.as_str(),
             self.authorization_grant.as_str(),
+            self.authorization_request.as_str(),
+            self.token_exchange.as_str(),
+            self.jwt_bearer.as_str(),
             self.email.as_str(),
         ]
     }
@@ -288,6 +297,37 @@ impl Policy {
         Ok(res)
     }
 
+    #[tracing::instrument(
+        name = "policy.evaluate.authorization_request",
+        skip_all,
+        fields(
+            input.client.id = %client.id,
+            input.signed_request_object = signed_request_object,
+        ),
+        err,
+    )]
+    pub async fn evaluate_authorization_request(
+        &mut self,
+        client: &Client,
+        signed_request_object: bool,
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let input = AuthorizationRequestInput {
+            client,
+            signed_request_object,
+        };
+
+        let [res]: [EvaluationResult; 1] = self
+            .instance
+            .evaluate(
+                &mut self.store,
+                &self.entrypoints.authorization_request,
+                &input,
+            )
+            .await?;
+
+        Ok(res)
+    }
+
     #[tracing::instrument(
         name = "policy.evaluate.client_credentials_grant",
         skip_all,
@@ -356,6 +396,75 @@ impl Policy {
 
         Ok(res)
     }
+
+    #[tracing::instrument(
+        name = "policy.evaluate.token_exchange",
+        skip_all,
+        fields(
+            input.scope = %scope,
+            input.subject_scope = %subject_scope,
+            input.client.id = %client.id,
+            input.subject_client.id = %subject_client.id,
+            input.audience.id = audience.map(|audience| tracing::field::display(audience.id)),
+            input.user.id = %user.id,
+        ),
+        err,
+    )]
+    pub async fn evaluate_token_exchange(
+        &mut self,
+        scope: &Scope,
+        subject_scope: &Scope,
+        client: &Client,
+        subject_client: &Client,
+        audience: Option<&Client>,
+        user: &User,
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let input = TokenExchangeInput {
+            user,
+            client,
+            subject_client,
+            audience,
+            scope,
+            subject_scope,
+        };
+
+        let [res]: [EvaluationResult; 1] = self
+            .instance
+            .evaluate(&mut self.store, &self.entrypoints.token_exchange, &input)
+            .await?;
+
+        Ok(res)
+    }
+
+    #[tracing::instrument(
+        name = "policy.evaluate.jwt_bearer",
+        skip_all,
+        fields(
+            input.scope = %scope,
+            input.client.id = %client.id,
+            input.user.id = %user.id,
+        ),
+        err,
+    )]
+    pub async fn evaluate_jwt_bearer(
+        &mut self,
+        scope: &Scope,
+        client: &Client,
+        user: &User,
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let input = JwtBearerInput {
+            user,
+            client,
+            scope,
+        };
+
+        let [res]: [EvaluationResult; 1] = self
+            .instance
+            .evaluate(&mut self.store, &self.entrypoints.jwt_bearer, &input)
+            .await?;
+
+        Ok(res)
+    }
 }
 
 #[cfg(test)]
@@ -382,6 +491,9 @@ mod tests {
             register: "register/violation".to_owned(),
             client_registration: "client_registration/violation".to_owned(),
             authorization_grant: "authorization_grant/violation".to_owned(),
+            authorization_request: "authorization_request/violation".to_owned(),
+            token_exchange: "token_exchange/violation".to_owned(),
+            jwt_bearer: "jwt_bearer/violation".to_owned(),
             email: "email/violation".to_owned(),
         };
 
@@ -114,6 +114,88 @@ pub struct AuthorizationGrantInput<'a> {
     pub grant_type: GrantType,
 }
 
+/// Input for the authorization request policy.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
+pub struct AuthorizationRequestInput<'a> {
+    /// The client which started the authorization request
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(with = "std::collections::HashMap<String, serde_json::Value>")
+    )]
+    pub client: &'a Client,
+
+    /// Whether the parameters were sent in a signed request object
+    pub signed_request_object: bool,
+}
+
+/// Input for the token exchange policy.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
+pub struct TokenExchangeInput<'a> {
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(with = "std::collections::HashMap<String, serde_json::Value>")
+    )]
+    pub user: &'a User,
+
+    /// The client requesting the new token
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(with = "std::collections::HashMap<String, serde_json::Value>")
+    )]
+    pub client: &'a Client,
+
+    /// The client to which the subject token was issued
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(with = "std::collections::HashMap<String, serde_json::Value>")
+    )]
+    pub subject_client: &'a Client,
+
+    /// The client the new token is requested for, if the `audience` parameter
+    /// was provided
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(with = "Option<std::collections::HashMap<String, serde_json::Value>>")
+    )]
+    pub audience: Option<&'a Client>,
+
+    /// The scope requested for the new token
+    #[cfg_attr(feature = "jsonschema", schemars(with = "String"))]
+    pub scope: &'a Scope,
+
+    /// The scope of the subject token
+    #[cfg_attr(feature = "jsonschema", schemars(with = "String"))]
+    pub subject_scope: &'a Scope,
+}
+
+/// Input for the JWT bearer grant policy.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
+pub struct JwtBearerInput<'a> {
+    /// The user named in the assertion
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(with = "std::collections::HashMap<String, serde_json::Value>")
+    )]
+    pub user: &'a User,
+
+    /// The client which signed the assertion
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(with = "std::collections::HashMap<String, serde_json::Value>")
+    )]
+    pub client: &'a Client,
+
+    #[cfg_attr(feature = "jsonschema", schemars(with = "String"))]
+    pub scope: &'a Scope,
+}
+
 /// Input for the email add policy.
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -23,6 +23,7 @@ pub enum PostAuthAction {
         id: Ulid,
     },
     ChangePassword,
+    ManagePasskeys,
     LinkUpstream {
         id: Ulid,
     },
@@ -70,6 +71,7 @@ impl PostAuthAction {
                 url_builder.redirect(&CompatLoginSsoComplete::new(*id, None))
             }
             Self::ChangePassword => url_builder.redirect(&AccountPasswordChange),
+            Self::ManagePasskeys => url_builder.redirect(&AccountPasskeys),
             Self::LinkUpstream { id } => url_builder.redirect(&UpstreamOAuth2Link::new(*id)),
             Self::ManageAccount { action } => url_builder.redirect(&Account {
                 action: action.clone(),
@@ -117,6 +119,14 @@ impl SimpleRoute for OidcUserinfo {
     const PATH: &'static str = "/oauth2/userinfo";
 }
 
+/// `GET|POST /oauth2/logout`
+#[derive(Default, Debug, Clone)]
+pub struct OidcEndSession;
+
+impl SimpleRoute for OidcEndSession {
+    const PATH: &'static str = "/oauth2/logout";
+}
+
 /// `POST /oauth2/introspect`
 #[derive(Default, Debug, Clone)]
 pub struct OAuth2Introspection;
@@ -149,6 +159,14 @@ impl SimpleRoute for OAuth2RegistrationEndpoint {
     const PATH: &'static str = "/oauth2/registration";
 }
 
+/// `POST /oauth2/par`
+#[derive(Default, Debug, Clone)]
+pub struct OAuth2PushedAuthorizationRequestEndpoint;
+
+impl SimpleRoute for OAuth2PushedAuthorizationRequestEndpoint {
+    const PATH: &'static str = "/oauth2/par";
+}
+
 /// `GET /authorize`
 #[derive(Default, Debug, Clone)]
 pub struct OAuth2AuthorizationEndpoint;
@@ -247,6 +265,54 @@ impl From<Option<PostAuthAction>> for Login {
     }
 }
 
+/// `GET|POST /login/totp`
+#[derive(Default, Debug, Clone)]
+pub struct LoginTotp {
+    post_auth_action: Option<PostAuthAction>,
+}
+
+impl Route for LoginTotp {
+    type Query = PostAuthAction;
+
+    fn route() -> &'static str {
+        "/login/totp"
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        self.post_auth_action.as_ref()
+    }
+}
+
+impl From<Option<PostAuthAction>> for LoginTotp {
+    fn from(post_auth_action: Option<PostAuthAction>) -> Self {
+        Self { post_auth_action }
+    }
+}
+
+/// `GET|POST /login/passkey`
+#[derive(Default, Debug, Clone)]
+pub struct LoginPasskey {
+    post_auth_action: Option<PostAuthAction>,
+}
+
+impl Route for LoginPasskey {
+    type Query = PostAuthAction;
+
+    fn route() -> &'static str {
+        "/login/passkey"
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        self.post_auth_action.as_ref()
+    }
+}
+
+impl From<Option<PostAuthAction>> for LoginPasskey {
+    fn from(post_auth_action: Option<PostAuthAction>) -> Self {
+        Self { post_auth_action }
+    }
+}
+
 /// `POST /logout`
 #[derive(Default, Debug, Clone)]
 pub struct Logout;
@@ -444,6 +510,39 @@ impl AccountAddEmail {
     }
 }
 
+/// `GET|POST /totp`
+#[derive(Default, Debug, Clone)]
+pub struct AccountTotp {
+    post_auth_action: Option<PostAuthAction>,
+}
+
+impl Route for AccountTotp {
+    type Query = PostAuthAction;
+    fn route() -> &'static str {
+        "/totp"
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        self.post_auth_action.as_ref()
+    }
+}
+
+impl AccountTotp {
+    #[must_use]
+    pub fn and_then(mut self, action: PostAuthAction) -> Self {
+        self.post_auth_action = Some(action);
+        self
+    }
+}
+
+/// `GET|POST /passkeys`
+#[derive(Default, Debug, Clone)]
+pub struct AccountPasskeys;
+
+impl SimpleRoute for AccountPasskeys {
+    const PATH: &'static str = "/passkeys";
+}
+
 /// Actions parameters as defined by MSC2965
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "action")]
@@ -712,6 +811,88 @@ impl Route for UpstreamOAuth2Link {
     }
 }
 
+/// `GET /upstream/saml2/metadata/:id`
+pub struct UpstreamSaml2Metadata {
+    id: Ulid,
+}
+
+impl UpstreamSaml2Metadata {
+    #[must_use]
+    pub const fn new(id: Ulid) -> Self {
+        Self { id }
+    }
+}
+
+impl Route for UpstreamSaml2Metadata {
+    type Query = ();
+    fn route() -> &'static str {
+        "/upstream/saml2/metadata/:provider_id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/upstream/saml2/metadata/{}", self.id).into()
+    }
+}
+
+/// `POST /upstream/saml2/acs/:id`
+pub struct UpstreamSaml2AssertionConsumer {
+    id: Ulid,
+}
+
+impl UpstreamSaml2AssertionConsumer {
+    #[must_use]
+    pub const fn new(id: Ulid) -> Self {
+        Self { id }
+    }
+}
+
+impl Route for UpstreamSaml2AssertionConsumer {
+    type Query = ();
+    fn route() -> &'static str {
+        "/upstream/saml2/acs/:provider_id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/upstream/saml2/acs/{}", self.id).into()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpstreamSaml2CompleteParams {
+    pub state: String,
+}
+
+/// `GET /upstream/saml2/complete/:id?state=:state`
+pub struct UpstreamSaml2Complete {
+    id: Ulid,
+    query: UpstreamSaml2CompleteParams,
+}
+
+impl UpstreamSaml2Complete {
+    #[must_use]
+    pub const fn new(id: Ulid, state: String) -> Self {
+        Self {
+            id,
+            query: UpstreamSaml2CompleteParams { state },
+        }
+    }
+}
+
+impl Route for UpstreamSaml2Complete {
+    type Query = UpstreamSaml2CompleteParams;
+    fn route() -> &'static str {
+        "/upstream/saml2/complete/:provider_id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/upstream/saml2/complete/{}", self.id).into()
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        Some(&self.query)
+    }
+}
+
 /// `GET|POST /link`
 #[derive(Default, Serialize, Deserialize, Debug, Clone)]
 pub struct DeviceCodeLink {
@@ -166,6 +166,12 @@ impl UrlBuilder {
         self.absolute_url_for(&crate::endpoints::OAuth2RegistrationEndpoint)
     }
 
+    /// OAuth 2.0 pushed authorization request endpoint
+    #[must_use]
+    pub fn oauth_pushed_authorization_request_endpoint(&self) -> Url {
+        self.absolute_url_for(&crate::endpoints::OAuth2PushedAuthorizationRequestEndpoint)
+    }
+
     /// OAuth 2.0 device authorization endpoint
     #[must_use]
     pub fn oauth_device_authorization_endpoint(&self) -> Url {
@@ -190,6 +196,12 @@ impl UrlBuilder {
         self.absolute_url_for(&crate::endpoints::OidcUserinfo)
     }
 
+    /// OIDC end session endpoint
+    #[must_use]
+    pub fn oidc_end_session_endpoint(&self) -> Url {
+        self.absolute_url_for(&crate::endpoints::OidcEndSession)
+    }
+
     /// JWKS URI
     #[must_use]
     pub fn jwks_uri(&self) -> Url {
@@ -226,6 +238,18 @@ impl UrlBuilder {
         self.absolute_url_for(&crate::endpoints::UpstreamOAuth2Authorize::new(id))
     }
 
+    /// Upstream SAML 2.0 service provider metadata URI
+    #[must_use]
+    pub fn upstream_saml2_metadata(&self, id: Ulid) -> Url {
+        self.absolute_url_for(&crate::endpoints::UpstreamSaml2Metadata::new(id))
+    }
+
+    /// Upstream SAML 2.0 assertion consumer service URI
+    #[must_use]
+    pub fn upstream_saml2_assertion_consumer(&self, id: Ulid) -> Url {
+        self.absolute_url_for(&crate::endpoints::UpstreamSaml2AssertionConsumer::new(id))
+    }
+
     /// Account management URI
     #[must_use]
     pub fn account_management_uri(&self) -> Url {
@@ -0,0 +1,139 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Authentication requests sent to the identity provider, through the
+//! HTTP-Redirect or HTTP-POST bindings
+
+use std::fmt::Write;
+
+use base64ct::{Base64, Encoding};
+use chrono::{DateTime, SecondsFormat, Utc};
+use url::Url;
+
+use crate::xml::{escape, ASSERTION_NAMESPACE, PROTOCOL_NAMESPACE};
+
+const HTTP_POST_BINDING: &str = "urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST";
+
+/// An unsigned `<samlp:AuthnRequest>`
+#[derive(Debug, Clone)]
+pub struct AuthnRequest<'a> {
+    /// The ID of the request, which must start with a letter or an underscore
+    pub id: &'a str,
+
+    /// When the request was issued
+    pub issue_instant: DateTime<Utc>,
+
+    /// The single sign-on service URL of the identity provider
+    pub destination: &'a Url,
+
+    /// Our own entity ID
+    pub issuer: &'a str,
+
+    /// Where the identity provider should send its response
+    pub assertion_consumer_service_url: &'a Url,
+
+    /// The format of the subject identifier to ask for, if any
+    pub name_id_format: Option<&'a str>,
+}
+
+impl AuthnRequest<'_> {
+    /// Serialize the request as an XML document
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!(
+            r#"<samlp:AuthnRequest xmlns:samlp="{PROTOCOL_NAMESPACE}" xmlns:saml="{ASSERTION_NAMESPACE}" ID="{id}" Version="2.0" IssueInstant="{issue_instant}" Destination="{destination}" AssertionConsumerServiceURL="{acs_url}" ProtocolBinding="{HTTP_POST_BINDING}">"#,
+            id = escape(self.id),
+            issue_instant = self
+                .issue_instant
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            destination = escape(self.destination.as_str()),
+            acs_url = escape(self.assertion_consumer_service_url.as_str()),
+        );
+
+        let _ = write!(xml, "<saml:Issuer>{}</saml:Issuer>", escape(self.issuer));
+
+        if let Some(name_id_format) = self.name_id_format {
+            let _ = write!(
+                xml,
+                r#"<samlp:NameIDPolicy Format="{}" AllowCreate="true"/>"#,
+                escape(name_id_format),
+            );
+        }
+
+        xml.push_str("</samlp:AuthnRequest>");
+        xml
+    }
+
+    /// Encode the request for the HTTP-POST binding, as the value of the
+    /// `SAMLRequest` form field
+    #[must_use]
+    pub fn to_post_parameter(&self) -> String {
+        Base64::encode_string(self.to_xml().as_bytes())
+    }
+
+    /// Build the URL to redirect the user to with the HTTP-Redirect binding
+    ///
+    /// The request is not signed, so the identity provider must not require
+    /// signed requests.
+    #[must_use]
+    pub fn redirect_url(&self, relay_state: &str) -> Url {
+        let deflated = miniz_oxide::deflate::compress_to_vec(self.to_xml().as_bytes(), 8);
+        let request = Base64::encode_string(&deflated);
+
+        let mut url = self.destination.clone();
+        url.query_pairs_mut()
+            .append_pair("SAMLRequest", &request)
+            .append_pair("RelayState", relay_state);
+        url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_redirect_url() {
+        let destination = Url::parse("https://idp.example.com/sso?tenant=1").unwrap();
+        let acs_url = Url::parse("https://mas.example.com/upstream/saml2/acs").unwrap();
+        let request = AuthnRequest {
+            id: "_request",
+            issue_instant: Utc.with_ymd_and_hms(2024, 10, 10, 10, 0, 0).unwrap(),
+            destination: &destination,
+            issuer: "https://mas.example.com/",
+            assertion_consumer_service_url: &acs_url,
+            name_id_format: Some("urn:oasis:names:tc:SAML:2.0:nameid-format:persistent"),
+        };
+
+        let xml = request.to_xml();
+        assert_eq!(
+            xml,
+            concat!(
+                r#"<samlp:AuthnRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" ID="_request" Version="2.0" IssueInstant="2024-10-10T10:00:00Z" Destination="https://idp.example.com/sso?tenant=1" AssertionConsumerServiceURL="https://mas.example.com/upstream/saml2/acs" ProtocolBinding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST">"#,
+                "<saml:Issuer>https://mas.example.com/</saml:Issuer>",
+                r#"<samlp:NameIDPolicy Format="urn:oasis:names:tc:SAML:2.0:nameid-format:persistent" AllowCreate="true"/>"#,
+                "</samlp:AuthnRequest>",
+            ),
+        );
+
+        let url = request.redirect_url("some-state");
+        assert_eq!(url.host_str(), Some("idp.example.com"));
+        assert_eq!(url.path(), "/sso");
+
+        let params: Vec<_> = url.query_pairs().collect();
+        assert_eq!(params.len(), 3);
+        assert_eq!(params[0].0, "tenant");
+        assert_eq!(params[1].0, "SAMLRequest");
+        assert_eq!(params[2].0, "RelayState");
+        assert_eq!(params[2].1, "some-state");
+
+        // The request is raw-deflated then base64-encoded
+        let deflated = Base64::decode_vec(&params[1].1).unwrap();
+        let inflated = miniz_oxide::inflate::decompress_to_vec(&deflated).unwrap();
+        assert_eq!(String::from_utf8(inflated).unwrap(), xml);
+    }
+}
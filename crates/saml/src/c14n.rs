@@ -0,0 +1,314 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Exclusive XML Canonicalization 1.0, without comments
+//!
+//! See <https://www.w3.org/TR/xml-exc-c14n/>
+
+use std::collections::BTreeMap;
+
+use roxmltree::{Node, NodeType};
+
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Canonicalize the subtree rooted at `node`, leaving out the `excluded`
+/// subtree if any.
+///
+/// `inclusive_prefixes` is the `PrefixList` of the `InclusiveNamespaces`
+/// element, where `#default` stands for the default namespace.
+pub(crate) fn canonicalize<'a>(
+    node: Node<'a, '_>,
+    excluded: Option<Node<'_, '_>>,
+    inclusive_prefixes: &[&'a str],
+) -> String {
+    let mut output = String::new();
+    let rendered = BTreeMap::new();
+    write_node(&mut output, node, excluded, inclusive_prefixes, &rendered);
+    output
+}
+
+/// Get the prefix of an element, as written in the source document
+fn element_prefix<'input>(node: Node<'_, 'input>) -> Option<&'input str> {
+    let input = node.document().input_text();
+    // The range of an element starts at its opening `<`
+    let start = node.range().start + 1;
+    let qname = input[start..]
+        .split(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        .next()?;
+    qname.split_once(':').map(|(prefix, _)| prefix)
+}
+
+/// Figure out which namespaces are visibly utilized by an element: the one of
+/// the element itself, the ones of its attributes, plus the ones listed in the
+/// inclusive prefixes
+fn utilized_namespaces<'a>(
+    node: Node<'a, '_>,
+    prefix: Option<&'a str>,
+    inclusive_prefixes: &[&'a str],
+) -> BTreeMap<&'a str, &'a str> {
+    let input = node.document().input_text();
+    let mut namespaces = BTreeMap::new();
+    namespaces.insert(
+        prefix.unwrap_or_default(),
+        node.tag_name().namespace().unwrap_or_default(),
+    );
+
+    for attribute in node.attributes() {
+        let Some(uri) = attribute.namespace() else {
+            continue;
+        };
+
+        if let Some((prefix, _)) = input[attribute.range_qname()].split_once(':') {
+            namespaces.insert(prefix, uri);
+        }
+    }
+
+    for &prefix in inclusive_prefixes {
+        if prefix == "#default" {
+            namespaces.insert("", node.default_namespace().unwrap_or_default());
+        } else if let Some(uri) = node.lookup_namespace_uri(Some(prefix)) {
+            namespaces.insert(prefix, uri);
+        }
+    }
+
+    // The `xml` namespace is never declared
+    namespaces.remove("xml");
+    namespaces
+}
+
+fn write_node<'a>(
+    output: &mut String,
+    node: Node<'a, '_>,
+    excluded: Option<Node<'_, '_>>,
+    inclusive_prefixes: &[&'a str],
+    rendered: &BTreeMap<&'a str, &'a str>,
+) {
+    match node.node_type() {
+        NodeType::Element => {}
+        NodeType::Text => {
+            escape_text(output, node.text().unwrap_or_default());
+            return;
+        }
+        NodeType::PI => {
+            if let Some(pi) = node.pi() {
+                output.push_str("<?");
+                output.push_str(pi.target);
+                if let Some(value) = pi.value {
+                    output.push(' ');
+                    output.push_str(value);
+                }
+                output.push_str("?>");
+            }
+            return;
+        }
+        NodeType::Root | NodeType::Comment => return,
+    }
+
+    if excluded == Some(node) {
+        return;
+    }
+
+    let prefix = element_prefix(node);
+    let input = node.document().input_text();
+    let namespaces = utilized_namespaces(node, prefix, inclusive_prefixes);
+
+    // Only render the declarations which differ from what the output ancestors
+    // already rendered. The default namespace is initially empty.
+    let mut rendered = rendered.clone();
+    let mut declarations = Vec::new();
+    for (prefix, uri) in namespaces {
+        let current = rendered.get(prefix).copied();
+        let needs_rendering = if prefix.is_empty() {
+            current.unwrap_or_default() != uri
+        } else {
+            current != Some(uri)
+        };
+
+        if needs_rendering {
+            declarations.push((prefix, uri));
+            rendered.insert(prefix, uri);
+        }
+    }
+
+    // Attributes are sorted by namespace URI, then by local name
+    let mut attributes: Vec<_> = node
+        .attributes()
+        .map(|attribute| {
+            let namespace = attribute.namespace().unwrap_or_default();
+            let qname = &input[attribute.range_qname()];
+            (namespace, attribute.name(), qname, attribute.value())
+        })
+        .collect();
+    attributes.sort_unstable_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    output.push('<');
+    write_qname(output, prefix, node.tag_name().name());
+
+    // The declarations are already sorted by prefix, with the default namespace
+    // first
+    for (prefix, uri) in declarations {
+        if prefix.is_empty() {
+            output.push_str(" xmlns=\"");
+        } else {
+            output.push_str(" xmlns:");
+            output.push_str(prefix);
+            output.push_str("=\"");
+        }
+        escape_attribute(output, uri);
+        output.push('"');
+    }
+
+    for (namespace, name, qname, value) in attributes {
+        output.push(' ');
+        if namespace == XML_NAMESPACE {
+            output.push_str("xml:");
+            output.push_str(name);
+        } else {
+            output.push_str(qname);
+        }
+        output.push_str("=\"");
+        escape_attribute(output, value);
+        output.push('"');
+    }
+
+    output.push('>');
+
+    for child in node.children() {
+        write_node(output, child, excluded, inclusive_prefixes, &rendered);
+    }
+
+    output.push_str("</");
+    write_qname(output, prefix, node.tag_name().name());
+    output.push('>');
+}
+
+fn write_qname(output: &mut String, prefix: Option<&str>, name: &str) {
+    if let Some(prefix) = prefix {
+        output.push_str(prefix);
+        output.push(':');
+    }
+    output.push_str(name);
+}
+
+fn escape_text(output: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '\r' => output.push_str("&#xD;"),
+            c => output.push(c),
+        }
+    }
+}
+
+fn escape_attribute(output: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '"' => output.push_str("&quot;"),
+            '\t' => output.push_str("&#x9;"),
+            '\n' => output.push_str("&#xA;"),
+            '\r' => output.push_str("&#xD;"),
+            c => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use roxmltree::Document;
+
+    use super::*;
+
+    fn c14n(xml: &str, inclusive_prefixes: &[&str]) -> String {
+        let document = Document::parse(xml).unwrap();
+        canonicalize(document.root_element(), None, inclusive_prefixes)
+    }
+
+    #[test]
+    fn test_basic() {
+        assert_eq!(
+            c14n(
+                r#"<a   b = "1"  a='2'><!-- comment --><c/>&lt;text&gt; &amp; "</a>"#,
+                &[]
+            ),
+            r#"<a a="2" b="1"><c></c>&lt;text&gt; &amp; "</a>"#,
+        );
+    }
+
+    #[test]
+    fn test_attribute_escaping() {
+        assert_eq!(
+            c14n(r#"<a b="&quot;&amp;&lt;&#9;&#10;&#13;>"/>"#, &[]),
+            r#"<a b="&quot;&amp;&lt;&#x9;&#xA;&#xD;>"></a>"#,
+        );
+    }
+
+    #[test]
+    fn test_only_utilized_namespaces() {
+        let xml =
+            r#"<x:a xmlns:x="urn:x" xmlns:y="urn:y" xmlns:z="urn:z"><x:b y:attr="1" z="2"/></x:a>"#;
+        assert_eq!(
+            c14n(xml, &[]),
+            r#"<x:a xmlns:x="urn:x"><x:b xmlns:y="urn:y" z="2" y:attr="1"></x:b></x:a>"#,
+        );
+
+        // Inclusive prefixes are rendered on the apex if they are in scope
+        assert_eq!(
+            c14n(xml, &["z", "unknown"]),
+            r#"<x:a xmlns:x="urn:x" xmlns:z="urn:z"><x:b xmlns:y="urn:y" z="2" y:attr="1"></x:b></x:a>"#,
+        );
+    }
+
+    #[test]
+    fn test_subtree_namespaces() {
+        // Namespaces declared on ancestors of the apex are rendered if utilized
+        let document =
+            Document::parse(r#"<root xmlns="urn:default" xmlns:p="urn:p"><p:a><b/></p:a></root>"#)
+                .unwrap();
+        let apex = document.root_element().first_element_child().unwrap();
+        assert_eq!(
+            canonicalize(apex, None, &[]),
+            r#"<p:a xmlns:p="urn:p"><b xmlns="urn:default"></b></p:a>"#,
+        );
+    }
+
+    #[test]
+    fn test_default_namespace_undeclaration() {
+        assert_eq!(
+            c14n(r#"<a xmlns="urn:a"><b xmlns=""><c/></b></a>"#, &[]),
+            r#"<a xmlns="urn:a"><b xmlns=""><c></c></b></a>"#,
+        );
+
+        // An empty default namespace is not rendered if nothing was rendered before
+        assert_eq!(
+            c14n(r#"<p:a xmlns:p="urn:p"><b xmlns=""/></p:a>"#, &[]),
+            r#"<p:a xmlns:p="urn:p"><b></b></p:a>"#,
+        );
+    }
+
+    #[test]
+    fn test_attribute_sorting() {
+        assert_eq!(
+            c14n(
+                r#"<a xmlns:b="urn:b" xmlns:a="urn:a" b:y="1" a:z="2" x="3" xml:lang="en" a:a="4"/>"#,
+                &[],
+            ),
+            r#"<a xmlns:a="urn:a" xmlns:b="urn:b" x="3" xml:lang="en" a:a="4" a:z="2" b:y="1"></a>"#,
+        );
+    }
+
+    #[test]
+    fn test_excluded_subtree() {
+        let document = Document::parse(r"<a><b><c/></b><d/></a>").unwrap();
+        let b = document.root_element().first_element_child().unwrap();
+        assert_eq!(
+            canonicalize(document.root_element(), Some(b), &[]),
+            r"<a><d></d></a>",
+        );
+    }
+}
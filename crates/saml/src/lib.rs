@@ -0,0 +1,31 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Utilities to act as a SAML 2.0 service provider.
+//!
+//! This covers the subset of the Web Browser SSO profile needed to log in
+//! through an upstream identity provider:
+//!
+//!  - generating the service provider metadata
+//!  - sending authentication requests through the HTTP-Redirect and HTTP-POST
+//!    bindings
+//!  - validating the signed responses sent back through the HTTP-POST binding
+//!
+//! Signatures must use RSA with SHA-2 and exclusive canonicalization.
+//! Encrypted assertions are not supported.
+
+mod authn_request;
+mod c14n;
+mod metadata;
+mod response;
+mod signature;
+mod xml;
+
+pub use self::{
+    authn_request::AuthnRequest,
+    metadata::ServiceProviderMetadata,
+    response::{validate_response, Assertion, Attribute, ResponseError, ResponseValidationData},
+    signature::{CertificateError, SignatureError, VerifyingKeys},
+};
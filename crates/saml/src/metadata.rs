@@ -0,0 +1,88 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Metadata describing our service provider, to be imported by identity
+//! providers
+
+use url::Url;
+
+use crate::xml::{escape, METADATA_NAMESPACE, PROTOCOL_NAMESPACE};
+
+const HTTP_POST_BINDING: &str = "urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST";
+
+/// The metadata of a service provider
+#[derive(Debug, Clone)]
+pub struct ServiceProviderMetadata<'a> {
+    /// Our own entity ID
+    pub entity_id: &'a str,
+
+    /// Where the identity provider should send its responses
+    pub assertion_consumer_service_url: &'a Url,
+
+    /// The format of the subject identifier we ask for, if any
+    pub name_id_format: Option<&'a str>,
+}
+
+impl ServiceProviderMetadata<'_> {
+    /// Serialize the metadata as an `<md:EntityDescriptor>` XML document
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let name_id_format = self
+            .name_id_format
+            .map(|format| format!("<md:NameIDFormat>{}</md:NameIDFormat>", escape(format)))
+            .unwrap_or_default();
+
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                r#"<md:EntityDescriptor xmlns:md="{metadata_ns}" entityID="{entity_id}">"#,
+                r#"<md:SPSSODescriptor AuthnRequestsSigned="false" WantAssertionsSigned="true" protocolSupportEnumeration="{protocol_ns}">"#,
+                "{name_id_format}",
+                r#"<md:AssertionConsumerService Binding="{binding}" Location="{acs_url}" index="0" isDefault="true"/>"#,
+                "</md:SPSSODescriptor>",
+                "</md:EntityDescriptor>",
+            ),
+            metadata_ns = METADATA_NAMESPACE,
+            protocol_ns = PROTOCOL_NAMESPACE,
+            entity_id = escape(self.entity_id),
+            name_id_format = name_id_format,
+            binding = HTTP_POST_BINDING,
+            acs_url = escape(self.assertion_consumer_service_url.as_str()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use roxmltree::Document;
+
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let acs_url = Url::parse("https://mas.example.com/upstream/saml2/01J/acs").unwrap();
+        let metadata = ServiceProviderMetadata {
+            entity_id: "https://mas.example.com/?a=1&b=2",
+            assertion_consumer_service_url: &acs_url,
+            name_id_format: None,
+        };
+
+        let xml = metadata.to_xml();
+        let document = Document::parse(&xml).unwrap();
+        let root = document.root_element();
+        assert!(root.has_tag_name((METADATA_NAMESPACE, "EntityDescriptor")));
+        assert_eq!(
+            root.attribute("entityID"),
+            Some("https://mas.example.com/?a=1&b=2")
+        );
+
+        let acs = root
+            .descendants()
+            .find(|node| node.has_tag_name((METADATA_NAMESPACE, "AssertionConsumerService")))
+            .unwrap();
+        assert_eq!(acs.attribute("Binding"), Some(HTTP_POST_BINDING));
+        assert_eq!(acs.attribute("Location"), Some(acs_url.as_str()));
+    }
+}
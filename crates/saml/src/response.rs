@@ -0,0 +1,387 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Validation of the responses sent by an identity provider to the assertion
+//! consumer service, through the HTTP-POST binding
+
+use std::string::FromUtf8Error;
+
+use base64ct::{Base64, Encoding};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use roxmltree::{Document, Node};
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    signature::{verify_enveloped_signature, SignatureError, VerifyingKeys},
+    xml::{
+        child, children, strip_whitespace, text, ASSERTION_NAMESPACE, PROTOCOL_NAMESPACE,
+        XSI_NAMESPACE,
+    },
+};
+
+const STATUS_SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+const BEARER: &str = "urn:oasis:names:tc:SAML:2.0:cm:bearer";
+
+/// How much the clocks of the identity provider and ours are allowed to drift
+/// apart
+fn allowed_clock_skew() -> Duration {
+    Duration::minutes(2)
+}
+
+/// An error which can happen when validating a response
+#[derive(Debug, Error)]
+pub enum ResponseError {
+    #[error("Invalid base64 encoding")]
+    Base64(#[from] base64ct::Error),
+
+    #[error("Response is not valid UTF-8")]
+    Utf8(#[from] FromUtf8Error),
+
+    #[error("Response is not a valid XML document")]
+    Xml(#[from] roxmltree::Error),
+
+    #[error("Document is not a SAML 2.0 response")]
+    NotAResponse,
+
+    #[error("Missing {0}")]
+    Missing(&'static str),
+
+    #[error("Invalid timestamp in {0}")]
+    InvalidTime(&'static str),
+
+    #[error("Identity provider returned the {code:?} status")]
+    Status {
+        code: String,
+        sub_code: Option<String>,
+        message: Option<String>,
+    },
+
+    #[error("Response was not sent to this service provider")]
+    DestinationMismatch,
+
+    #[error("Response is not in response to the expected request")]
+    InResponseToMismatch,
+
+    #[error("Response was not issued by the expected identity provider")]
+    IssuerMismatch,
+
+    #[error("Encrypted assertions are not supported")]
+    EncryptedAssertion,
+
+    #[error("Expected exactly one assertion in the response")]
+    AssertionCount,
+
+    #[error("Assertion is not valid yet")]
+    NotYetValid,
+
+    #[error("Assertion has expired")]
+    Expired,
+
+    #[error("Assertion is not intended for this service provider")]
+    AudienceMismatch,
+
+    #[error("Assertion has no valid bearer subject confirmation")]
+    SubjectConfirmation,
+
+    #[error("Invalid signature")]
+    Signature(#[from] SignatureError),
+}
+
+/// An attribute from an assertion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    /// The name of the attribute, which is often a URI
+    pub name: String,
+
+    /// A human-readable name for the attribute, if provided
+    pub friendly_name: Option<String>,
+
+    /// The values of the attribute
+    pub values: Vec<String>,
+}
+
+/// The information extracted from a valid assertion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assertion {
+    /// The identifier of the subject
+    pub name_id: String,
+
+    /// The format of the subject identifier, if provided
+    pub name_id_format: Option<String>,
+
+    /// The index of the session at the identity provider, if provided
+    pub session_index: Option<String>,
+
+    /// The attributes of the subject
+    pub attributes: Vec<Attribute>,
+}
+
+/// The data needed to validate a response
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseValidationData<'a> {
+    /// The entity ID of the identity provider which should have issued the
+    /// response
+    pub idp_entity_id: &'a str,
+
+    /// Our own entity ID, which should be the audience of the assertion
+    pub sp_entity_id: &'a str,
+
+    /// The URL of our assertion consumer service, where the response was sent
+    pub assertion_consumer_service_url: &'a Url,
+
+    /// The ID of the request this response should be in response to
+    pub request_id: &'a str,
+
+    /// The keys trusted to sign the response or the assertion
+    pub keys: &'a VerifyingKeys,
+}
+
+/// Parse a timestamp, which should be in UTC
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|time| time.and_utc())
+        })
+}
+
+/// Get a timestamp from an attribute of the given element
+fn time_attribute(
+    node: Node<'_, '_>,
+    name: &'static str,
+) -> Result<Option<DateTime<Utc>>, ResponseError> {
+    node.attribute(name)
+        .map(|value| parse_time(value).ok_or(ResponseError::InvalidTime(name)))
+        .transpose()
+}
+
+/// Check the validity window of an element
+fn check_validity(node: Node<'_, '_>, now: DateTime<Utc>) -> Result<(), ResponseError> {
+    if let Some(not_before) = time_attribute(node, "NotBefore")? {
+        if now + allowed_clock_skew() < not_before {
+            return Err(ResponseError::NotYetValid);
+        }
+    }
+
+    if let Some(not_on_or_after) = time_attribute(node, "NotOnOrAfter")? {
+        if now - allowed_clock_skew() >= not_on_or_after {
+            return Err(ResponseError::Expired);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the issuer child of an element is the expected one
+fn check_issuer(
+    node: Node<'_, '_>,
+    validation_data: &ResponseValidationData<'_>,
+    required: bool,
+) -> Result<(), ResponseError> {
+    match child(node, ASSERTION_NAMESPACE, "Issuer") {
+        Some(issuer) if text(issuer) == validation_data.idp_entity_id => Ok(()),
+        Some(_) => Err(ResponseError::IssuerMismatch),
+        None if required => Err(ResponseError::Missing("Issuer")),
+        None => Ok(()),
+    }
+}
+
+/// Verify the signature of an element, returning `false` if it is not signed
+fn is_signed(node: Node<'_, '_>, keys: &VerifyingKeys) -> Result<bool, SignatureError> {
+    match verify_enveloped_signature(node, keys) {
+        Ok(()) => Ok(true),
+        Err(SignatureError::Missing) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Check that the subject of the assertion has a valid bearer confirmation
+fn check_subject_confirmation(
+    subject: Node<'_, '_>,
+    validation_data: &ResponseValidationData<'_>,
+    now: DateTime<Utc>,
+) -> Result<(), ResponseError> {
+    for confirmation in children(subject, ASSERTION_NAMESPACE, "SubjectConfirmation") {
+        if confirmation.attribute("Method") != Some(BEARER) {
+            continue;
+        }
+
+        let Some(data) = child(confirmation, ASSERTION_NAMESPACE, "SubjectConfirmationData") else {
+            continue;
+        };
+
+        // Bearer confirmations must have an expiration
+        if time_attribute(data, "NotOnOrAfter")?.is_none() {
+            continue;
+        }
+
+        let recipient_matches = data.attribute("Recipient")
+            == Some(validation_data.assertion_consumer_service_url.as_str());
+        let request_matches = data.attribute("InResponseTo") == Some(validation_data.request_id);
+
+        if recipient_matches && request_matches && check_validity(data, now).is_ok() {
+            return Ok(());
+        }
+    }
+
+    Err(ResponseError::SubjectConfirmation)
+}
+
+/// Extract the attributes from the attribute statements of an assertion
+fn attributes(assertion: Node<'_, '_>) -> Vec<Attribute> {
+    children(assertion, ASSERTION_NAMESPACE, "AttributeStatement")
+        .flat_map(|statement| children(statement, ASSERTION_NAMESPACE, "Attribute"))
+        .filter_map(|attribute| {
+            let name = attribute.attribute("Name")?.to_owned();
+            let friendly_name = attribute.attribute("FriendlyName").map(ToOwned::to_owned);
+            let values = children(attribute, ASSERTION_NAMESPACE, "AttributeValue")
+                .filter(|value| value.attribute((XSI_NAMESPACE, "nil")) != Some("true"))
+                .map(text)
+                .collect();
+
+            Some(Attribute {
+                name,
+                friendly_name,
+                values,
+            })
+        })
+        .collect()
+}
+
+/// Validate a response sent through the HTTP-POST binding, in the
+/// `SAMLResponse` parameter, and extract its assertion.
+///
+/// Either the response or the assertion must be signed by one of the trusted
+/// keys.
+///
+/// # Errors
+///
+/// Returns an error if the response is malformed, is not successful, or if
+/// its assertion is not valid
+pub fn validate_response(
+    saml_response: &str,
+    validation_data: &ResponseValidationData<'_>,
+    now: DateTime<Utc>,
+) -> Result<Assertion, ResponseError> {
+    let document = Base64::decode_vec(&strip_whitespace(saml_response))?;
+    let document = String::from_utf8(document)?;
+    let document = Document::parse(&document)?;
+
+    let response = document.root_element();
+    if !response.has_tag_name((PROTOCOL_NAMESPACE, "Response"))
+        || response.attribute("Version") != Some("2.0")
+    {
+        return Err(ResponseError::NotAResponse);
+    }
+
+    if let Some(destination) = response.attribute("Destination") {
+        if destination != validation_data.assertion_consumer_service_url.as_str() {
+            return Err(ResponseError::DestinationMismatch);
+        }
+    }
+
+    if response.attribute("InResponseTo") != Some(validation_data.request_id) {
+        return Err(ResponseError::InResponseToMismatch);
+    }
+
+    check_issuer(response, validation_data, false)?;
+
+    let status_code = child(response, PROTOCOL_NAMESPACE, "Status")
+        .and_then(|status| child(status, PROTOCOL_NAMESPACE, "StatusCode"))
+        .ok_or(ResponseError::Missing("StatusCode"))?;
+    let code = status_code
+        .attribute("Value")
+        .ok_or(ResponseError::Missing("StatusCode"))?;
+    if code != STATUS_SUCCESS {
+        let sub_code = child(status_code, PROTOCOL_NAMESPACE, "StatusCode")
+            .and_then(|sub_code| sub_code.attribute("Value"))
+            .map(ToOwned::to_owned);
+        let message = status_code
+            .parent_element()
+            .and_then(|status| child(status, PROTOCOL_NAMESPACE, "StatusMessage"))
+            .map(text);
+
+        return Err(ResponseError::Status {
+            code: code.to_owned(),
+            sub_code,
+            message,
+        });
+    }
+
+    if child(response, ASSERTION_NAMESPACE, "EncryptedAssertion").is_some() {
+        return Err(ResponseError::EncryptedAssertion);
+    }
+
+    let mut assertions = children(response, ASSERTION_NAMESPACE, "Assertion");
+    let assertion = assertions.next().ok_or(ResponseError::AssertionCount)?;
+    if assertions.next().is_some() {
+        return Err(ResponseError::AssertionCount);
+    }
+
+    // Everything we use from now on is part of the assertion, which is covered
+    // either by its own signature or by the one of the response
+    let response_signed = is_signed(response, validation_data.keys)?;
+    let assertion_signed = is_signed(assertion, validation_data.keys)?;
+    if !response_signed && !assertion_signed {
+        return Err(SignatureError::Missing.into());
+    }
+
+    if assertion.attribute("Version") != Some("2.0") {
+        return Err(ResponseError::NotAResponse);
+    }
+
+    check_issuer(assertion, validation_data, true)?;
+
+    let conditions = child(assertion, ASSERTION_NAMESPACE, "Conditions")
+        .ok_or(ResponseError::Missing("Conditions"))?;
+    check_validity(conditions, now)?;
+
+    let mut audience_restrictions =
+        children(conditions, ASSERTION_NAMESPACE, "AudienceRestriction").peekable();
+    if audience_restrictions.peek().is_none() {
+        return Err(ResponseError::Missing("AudienceRestriction"));
+    }
+
+    for restriction in audience_restrictions {
+        let mut audiences = children(restriction, ASSERTION_NAMESPACE, "Audience");
+        if !audiences.any(|audience| text(audience) == validation_data.sp_entity_id) {
+            return Err(ResponseError::AudienceMismatch);
+        }
+    }
+
+    let subject = child(assertion, ASSERTION_NAMESPACE, "Subject")
+        .ok_or(ResponseError::Missing("Subject"))?;
+    check_subject_confirmation(subject, validation_data, now)?;
+
+    let name_id =
+        child(subject, ASSERTION_NAMESPACE, "NameID").ok_or(ResponseError::Missing("NameID"))?;
+    let name_id_format = name_id.attribute("Format").map(ToOwned::to_owned);
+    let name_id = text(name_id);
+    if name_id.is_empty() {
+        return Err(ResponseError::Missing("NameID"));
+    }
+
+    let authn_statement = child(assertion, ASSERTION_NAMESPACE, "AuthnStatement")
+        .ok_or(ResponseError::Missing("AuthnStatement"))?;
+    if let Some(session_not_on_or_after) = time_attribute(authn_statement, "SessionNotOnOrAfter")? {
+        if now - allowed_clock_skew() >= session_not_on_or_after {
+            return Err(ResponseError::Expired);
+        }
+    }
+    let session_index = authn_statement
+        .attribute("SessionIndex")
+        .map(ToOwned::to_owned);
+
+    Ok(Assertion {
+        name_id,
+        name_id_format,
+        session_index,
+        attributes: attributes(assertion),
+    })
+}
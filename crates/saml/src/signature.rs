@@ -0,0 +1,299 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Verification of enveloped XML signatures
+//!
+//! Only the subset of XML-DSig used by SAML is supported: a single reference
+//! to the signed element, with the enveloped signature and exclusive
+//! canonicalization transforms, signed with RSA and SHA-2.
+
+use base64ct::{Base64, Encoding};
+use roxmltree::Node;
+use rsa::{
+    pkcs1v15::{Signature, VerifyingKey},
+    signature::Verifier,
+    RsaPublicKey,
+};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use thiserror::Error;
+use x509_cert::{
+    der::{referenced::OwnedToRef, Decode, DecodePem},
+    Certificate,
+};
+
+use crate::{
+    c14n::canonicalize,
+    xml::{child, children, strip_whitespace, text, DSIG_NAMESPACE, EXC_C14N_NAMESPACE},
+};
+
+const EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+const ENVELOPED_SIGNATURE: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+
+/// An error which can happen when loading the certificates of an identity
+/// provider
+#[derive(Debug, Error)]
+pub enum CertificateError {
+    #[error("Invalid base64 encoding")]
+    Base64(#[from] base64ct::Error),
+
+    #[error("Invalid certificate")]
+    Der(#[from] x509_cert::der::Error),
+
+    #[error("Only RSA keys are supported")]
+    UnsupportedKey(#[source] rsa::pkcs8::spki::Error),
+}
+
+/// An error which can happen when verifying the signature of an element
+#[derive(Debug, Error)]
+pub enum SignatureError {
+    #[error("Element is not signed")]
+    Missing,
+
+    #[error("Malformed signature: {0}")]
+    Malformed(&'static str),
+
+    #[error("Unsupported algorithm {0:?}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Signature reference does not match the signed element")]
+    InvalidReference,
+
+    #[error("Digest of the signed element does not match")]
+    DigestMismatch,
+
+    #[error("Signature was not made by any of the trusted keys")]
+    Invalid,
+}
+
+/// The keys trusted to sign messages from an identity provider
+#[derive(Debug, Clone)]
+pub struct VerifyingKeys {
+    keys: Vec<RsaPublicKey>,
+}
+
+impl VerifyingKeys {
+    /// Load the keys from a list of X.509 certificates
+    ///
+    /// Each certificate is either PEM-encoded, or a base64-encoded DER
+    /// certificate as found in the identity provider metadata. The validity
+    /// period and issuer of the certificates are not checked: they are only
+    /// used as a way to distribute the keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the certificates is invalid, or doesn't
+    /// contain an RSA key
+    pub fn from_certificates<I, S>(certificates: I) -> Result<Self, CertificateError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let keys = certificates
+            .into_iter()
+            .map(|certificate| {
+                let certificate = certificate.as_ref().trim();
+                let certificate = if certificate.starts_with("-----BEGIN") {
+                    Certificate::from_pem(certificate)?
+                } else {
+                    let der = Base64::decode_vec(&strip_whitespace(certificate))?;
+                    Certificate::from_der(&der)?
+                };
+
+                let spki = certificate.tbs_certificate.subject_public_key_info;
+                RsaPublicKey::try_from(spki.owned_to_ref())
+                    .map_err(CertificateError::UnsupportedKey)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { keys })
+    }
+
+    /// Returns `true` if there are no trusted keys
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DigestAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    fn from_digest_method(uri: &str) -> Result<Self, SignatureError> {
+        match uri {
+            "http://www.w3.org/2001/04/xmlenc#sha256" => Ok(Self::Sha256),
+            "http://www.w3.org/2001/04/xmldsig-more#sha384" => Ok(Self::Sha384),
+            "http://www.w3.org/2001/04/xmlenc#sha512" => Ok(Self::Sha512),
+            uri => Err(SignatureError::UnsupportedAlgorithm(uri.to_owned())),
+        }
+    }
+
+    fn from_signature_method(uri: &str) -> Result<Self, SignatureError> {
+        match uri {
+            "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256" => Ok(Self::Sha256),
+            "http://www.w3.org/2001/04/xmldsig-more#rsa-sha384" => Ok(Self::Sha384),
+            "http://www.w3.org/2001/04/xmldsig-more#rsa-sha512" => Ok(Self::Sha512),
+            uri => Err(SignatureError::UnsupportedAlgorithm(uri.to_owned())),
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+            Self::Sha384 => Sha384::digest(data).to_vec(),
+            Self::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    fn verify(self, key: &RsaPublicKey, data: &[u8], signature: &Signature) -> bool {
+        let key = key.clone();
+        match self {
+            Self::Sha256 => VerifyingKey::<Sha256>::new(key).verify(data, signature),
+            Self::Sha384 => VerifyingKey::<Sha384>::new(key).verify(data, signature),
+            Self::Sha512 => VerifyingKey::<Sha512>::new(key).verify(data, signature),
+        }
+        .is_ok()
+    }
+}
+
+/// Get the algorithm of an element like `<ds:DigestMethod Algorithm="…"/>`
+fn algorithm<'a>(node: Node<'a, '_>, name: &'static str) -> Result<&'a str, SignatureError> {
+    child(node, DSIG_NAMESPACE, name)
+        .and_then(|method| method.attribute("Algorithm"))
+        .ok_or(SignatureError::Malformed(name))
+}
+
+/// Get the prefixes listed in the `InclusiveNamespaces` child of a
+/// canonicalization method, if any
+fn inclusive_prefixes<'a>(node: Node<'a, '_>) -> Vec<&'a str> {
+    child(node, EXC_C14N_NAMESPACE, "InclusiveNamespaces")
+        .and_then(|inclusive_namespaces| inclusive_namespaces.attribute("PrefixList"))
+        .map(|prefix_list| prefix_list.split_ascii_whitespace().collect())
+        .unwrap_or_default()
+}
+
+/// Verify the signature enveloped in the given element
+///
+/// # Errors
+///
+/// Returns an error if the element is not signed, or if the signature is not
+/// valid
+pub(crate) fn verify_enveloped_signature(
+    element: Node<'_, '_>,
+    keys: &VerifyingKeys,
+) -> Result<(), SignatureError> {
+    let mut signatures = children(element, DSIG_NAMESPACE, "Signature");
+    let signature = signatures.next().ok_or(SignatureError::Missing)?;
+    if signatures.next().is_some() {
+        return Err(SignatureError::Malformed("multiple signatures"));
+    }
+
+    let signed_info = child(signature, DSIG_NAMESPACE, "SignedInfo")
+        .ok_or(SignatureError::Malformed("SignedInfo"))?;
+
+    let canonicalization_method = child(signed_info, DSIG_NAMESPACE, "CanonicalizationMethod")
+        .ok_or(SignatureError::Malformed("CanonicalizationMethod"))?;
+    let canonicalization_algorithm = canonicalization_method
+        .attribute("Algorithm")
+        .ok_or(SignatureError::Malformed("CanonicalizationMethod"))?;
+    if canonicalization_algorithm != EXC_C14N {
+        return Err(SignatureError::UnsupportedAlgorithm(
+            canonicalization_algorithm.to_owned(),
+        ));
+    }
+
+    let signature_algorithm =
+        DigestAlgorithm::from_signature_method(algorithm(signed_info, "SignatureMethod")?)?;
+
+    // There must be exactly one reference, to the element itself
+    let mut references = children(signed_info, DSIG_NAMESPACE, "Reference");
+    let reference = references
+        .next()
+        .ok_or(SignatureError::Malformed("Reference"))?;
+    if references.next().is_some() {
+        return Err(SignatureError::Malformed("multiple references"));
+    }
+
+    let id = element
+        .attribute("ID")
+        .filter(|id| !id.is_empty())
+        .ok_or(SignatureError::InvalidReference)?;
+    let uri = reference
+        .attribute("URI")
+        .and_then(|uri| uri.strip_prefix('#'))
+        .ok_or(SignatureError::InvalidReference)?;
+    if uri != id {
+        return Err(SignatureError::InvalidReference);
+    }
+
+    // The ID must be unique in the whole document, so that there is no
+    // ambiguity on what was signed
+    let same_id = element
+        .document()
+        .descendants()
+        .filter(|node| node.attribute("ID") == Some(id))
+        .count();
+    if same_id != 1 {
+        return Err(SignatureError::InvalidReference);
+    }
+
+    // Only the enveloped signature and exclusive canonicalization transforms
+    // are supported, which is what the SAML specification recommends
+    let transforms = child(reference, DSIG_NAMESPACE, "Transforms")
+        .ok_or(SignatureError::Malformed("Transforms"))?;
+    let mut enveloped = false;
+    let mut reference_prefixes = None;
+    for transform in children(transforms, DSIG_NAMESPACE, "Transform") {
+        match transform.attribute("Algorithm") {
+            Some(ENVELOPED_SIGNATURE) => enveloped = true,
+            Some(EXC_C14N) => reference_prefixes = Some(inclusive_prefixes(transform)),
+            Some(uri) => return Err(SignatureError::UnsupportedAlgorithm(uri.to_owned())),
+            None => return Err(SignatureError::Malformed("Transform")),
+        }
+    }
+
+    let (true, Some(reference_prefixes)) = (enveloped, reference_prefixes) else {
+        return Err(SignatureError::Malformed("Transforms"));
+    };
+
+    // Check the digest of the element, without the signature
+    let digest_algorithm =
+        DigestAlgorithm::from_digest_method(algorithm(reference, "DigestMethod")?)?;
+    let digest_value = child(reference, DSIG_NAMESPACE, "DigestValue")
+        .ok_or(SignatureError::Malformed("DigestValue"))?;
+    let expected_digest = Base64::decode_vec(&strip_whitespace(&text(digest_value)))
+        .map_err(|_| SignatureError::Malformed("DigestValue"))?;
+
+    let canonical_element = canonicalize(element, Some(signature), &reference_prefixes);
+    if digest_algorithm.digest(canonical_element.as_bytes()) != expected_digest {
+        return Err(SignatureError::DigestMismatch);
+    }
+
+    // Check the signature of the SignedInfo element
+    let signature_value = child(signature, DSIG_NAMESPACE, "SignatureValue")
+        .ok_or(SignatureError::Malformed("SignatureValue"))?;
+    let signature_value = Base64::decode_vec(&strip_whitespace(&text(signature_value)))
+        .map_err(|_| SignatureError::Malformed("SignatureValue"))?;
+    let signature_value = Signature::try_from(signature_value.as_slice())
+        .map_err(|_| SignatureError::Malformed("SignatureValue"))?;
+
+    let canonical_signed_info = canonicalize(
+        signed_info,
+        None,
+        &inclusive_prefixes(canonicalization_method),
+    );
+
+    if keys.keys.iter().any(|key| {
+        signature_algorithm.verify(key, canonical_signed_info.as_bytes(), &signature_value)
+    }) {
+        Ok(())
+    } else {
+        Err(SignatureError::Invalid)
+    }
+}
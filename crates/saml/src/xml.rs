@@ -0,0 +1,69 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Helpers to navigate and write XML documents
+
+use roxmltree::Node;
+
+pub(crate) const PROTOCOL_NAMESPACE: &str = "urn:oasis:names:tc:SAML:2.0:protocol";
+pub(crate) const ASSERTION_NAMESPACE: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+pub(crate) const METADATA_NAMESPACE: &str = "urn:oasis:names:tc:SAML:2.0:metadata";
+pub(crate) const DSIG_NAMESPACE: &str = "http://www.w3.org/2000/09/xmldsig#";
+pub(crate) const EXC_C14N_NAMESPACE: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+pub(crate) const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// Iterate over the child elements of `node` with the given name
+pub(crate) fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    namespace: &'static str,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.has_tag_name((namespace, name)))
+}
+
+/// Find the first child element of `node` with the given name
+pub(crate) fn child<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    namespace: &'static str,
+    name: &'static str,
+) -> Option<Node<'a, 'input>> {
+    children(node, namespace, name).next()
+}
+
+/// Get the text content of an element, trimmed
+///
+/// All the descendant text nodes are concatenated: comments are dropped by the
+/// canonicalization, so a comment splitting a signed value must not truncate
+/// it.
+pub(crate) fn text(node: Node<'_, '_>) -> String {
+    let text: String = node
+        .descendants()
+        .filter(Node::is_text)
+        .filter_map(|node| node.text())
+        .collect();
+    text.trim().to_owned()
+}
+
+/// Remove the whitespace from a base64-encoded value, as it is often wrapped
+pub(crate) fn strip_whitespace(value: &str) -> String {
+    value.chars().filter(|c| !c.is_ascii_whitespace()).collect()
+}
+
+/// Escape a value to be used as text or as an attribute value
+pub(crate) fn escape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
@@ -0,0 +1,33 @@
+#!/bin/sh
+# Generates the test keys and signs the test responses
+# Requires the `openssl` and `xmlsec1` binaries
+
+set -eu
+
+cd "$(dirname "$0")"
+mkdir -p keys responses
+
+gen_cert() {
+  openssl req -x509 -newkey rsa:2048 -nodes -days 36500 \
+    -subj "/CN=$1.example.com" -keyout "keys/$1.key.pem" -out "keys/$1.cert.pem"
+}
+
+gen_cert idp
+gen_cert other
+# Only the certificate of the other key is needed
+rm keys/other.key.pem
+
+# The assertion is signed
+xmlsec1 --sign --privkey-pem keys/idp.key.pem \
+  --id-attr:ID urn:oasis:names:tc:SAML:2.0:assertion:Assertion \
+  --output responses/signed-assertion.xml templates/signed-assertion.xml
+
+# The whole response is signed
+xmlsec1 --sign --privkey-pem keys/idp.key.pem \
+  --id-attr:ID urn:oasis:names:tc:SAML:2.0:protocol:Response \
+  --output responses/signed-response.xml templates/signed-response.xml
+
+# The whole response is signed, and the NameID is split by a comment
+xmlsec1 --sign --privkey-pem keys/idp.key.pem \
+  --id-attr:ID urn:oasis:names:tc:SAML:2.0:protocol:Response \
+  --output responses/comment-in-name-id.xml templates/comment-in-name-id.xml
@@ -0,0 +1,324 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use base64ct::{Base64, Encoding};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use mas_saml::{
+    validate_response, Attribute, ResponseError, ResponseValidationData, SignatureError,
+    VerifyingKeys,
+};
+use url::Url;
+
+static SIGNED_ASSERTION: &str = include_str!("./responses/signed-assertion.xml");
+static SIGNED_RESPONSE: &str = include_str!("./responses/signed-response.xml");
+static COMMENT_IN_NAME_ID: &str = include_str!("./responses/comment-in-name-id.xml");
+static IDP_CERT: &str = include_str!("./keys/idp.cert.pem");
+static OTHER_CERT: &str = include_str!("./keys/other.cert.pem");
+
+fn now() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 10, 10, 10, 0, 5).unwrap()
+}
+
+fn acs_url() -> Url {
+    Url::parse("https://mas.example.com/upstream/saml2/acs").unwrap()
+}
+
+fn validate(
+    document: &str,
+    keys: &VerifyingKeys,
+    now: DateTime<Utc>,
+) -> Result<mas_saml::Assertion, ResponseError> {
+    let acs_url = acs_url();
+    let validation_data = ResponseValidationData {
+        idp_entity_id: "https://idp.example.com/metadata",
+        sp_entity_id: "https://mas.example.com/",
+        assertion_consumer_service_url: &acs_url,
+        request_id: "_request",
+        keys,
+    };
+
+    validate_response(
+        &Base64::encode_string(document.as_bytes()),
+        &validation_data,
+        now,
+    )
+}
+
+fn idp_keys() -> VerifyingKeys {
+    VerifyingKeys::from_certificates([IDP_CERT]).unwrap()
+}
+
+#[test]
+fn load_certificates() {
+    // PEM certificates and bare base64 DER certificates are both accepted
+    let der: String = IDP_CERT
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let keys = VerifyingKeys::from_certificates([OTHER_CERT, &der]).unwrap();
+    assert!(!keys.is_empty());
+
+    let assertion = validate(SIGNED_ASSERTION, &keys, now()).unwrap();
+    assert_eq!(assertion.name_id, "b8c1e2f0-6d4a-4c1e-9a6e-3f2d1c0b9a87");
+
+    assert!(VerifyingKeys::from_certificates(["not a certificate"]).is_err());
+}
+
+#[test]
+fn signed_assertion() {
+    let assertion = validate(SIGNED_ASSERTION, &idp_keys(), now()).unwrap();
+    assert_eq!(assertion.name_id, "b8c1e2f0-6d4a-4c1e-9a6e-3f2d1c0b9a87");
+    assert_eq!(
+        assertion.name_id_format.as_deref(),
+        Some("urn:oasis:names:tc:SAML:2.0:nameid-format:persistent")
+    );
+    assert_eq!(assertion.session_index.as_deref(), Some("_session"));
+    assert_eq!(
+        assertion.attributes,
+        vec![
+            Attribute {
+                name: "urn:oid:0.9.2342.19200300.100.1.1".to_owned(),
+                friendly_name: Some("uid".to_owned()),
+                values: vec!["alice".to_owned()],
+            },
+            Attribute {
+                name: "email".to_owned(),
+                friendly_name: None,
+                values: vec!["alice@example.com".to_owned()],
+            },
+            Attribute {
+                name: "displayName".to_owned(),
+                friendly_name: None,
+                values: vec!["Alice & Bob <3".to_owned()],
+            },
+            Attribute {
+                name: "groups".to_owned(),
+                friendly_name: None,
+                values: vec!["admins".to_owned(), "users".to_owned()],
+            },
+        ]
+    );
+}
+
+#[test]
+fn signed_response() {
+    let assertion = validate(SIGNED_RESPONSE, &idp_keys(), now()).unwrap();
+    assert_eq!(assertion.name_id, "b8c1e2f0-6d4a-4c1e-9a6e-3f2d1c0b9a87");
+    assert_eq!(assertion.session_index.as_deref(), Some("_session"));
+    assert_eq!(
+        assertion.attributes,
+        vec![
+            Attribute {
+                name: "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress"
+                    .to_owned(),
+                friendly_name: None,
+                values: vec!["alice@example.com".to_owned()],
+            },
+            Attribute {
+                name: "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/name".to_owned(),
+                friendly_name: None,
+                values: vec!["alice".to_owned()],
+            },
+            Attribute {
+                name: "empty".to_owned(),
+                friendly_name: None,
+                values: Vec::new(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn untrusted_key() {
+    let keys = VerifyingKeys::from_certificates([OTHER_CERT]).unwrap();
+    let error = validate(SIGNED_ASSERTION, &keys, now()).unwrap_err();
+    assert!(matches!(
+        error,
+        ResponseError::Signature(SignatureError::Invalid)
+    ));
+}
+
+#[test]
+fn tampered_assertion() {
+    let document = SIGNED_ASSERTION.replace("alice@example.com", "mallory@example.com");
+    let error = validate(&document, &idp_keys(), now()).unwrap_err();
+    assert!(matches!(
+        error,
+        ResponseError::Signature(SignatureError::DigestMismatch)
+    ));
+
+    // Changes outside the signed assertion are not detected by its signature,
+    // but the response itself is still checked
+    let document =
+        SIGNED_ASSERTION.replace(r#"InResponseTo="_request">"#, r#"InResponseTo="_other">"#);
+    let error = validate(&document, &idp_keys(), now()).unwrap_err();
+    assert!(matches!(error, ResponseError::InResponseToMismatch));
+}
+
+#[test]
+fn comment_in_signed_values() {
+    // Comments are not covered by the signature, so they can be added to a signed
+    // response without invalidating it. They must not truncate the values.
+    let document = SIGNED_RESPONSE
+        .replace(
+            ">b8c1e2f0-6d4a-4c1e-9a6e-3f2d1c0b9a87<",
+            ">b8c1e2f0<!---->-6d4a-4c1e-9a6e-3f2d1c0b9a87<",
+        )
+        .replace(">alice@example.com<", ">alice<!---->@example.com<");
+    assert_ne!(document, SIGNED_RESPONSE);
+
+    let assertion = validate(&document, &idp_keys(), now()).unwrap();
+    assert_eq!(assertion.name_id, "b8c1e2f0-6d4a-4c1e-9a6e-3f2d1c0b9a87");
+    assert_eq!(
+        assertion.attributes[0].values,
+        vec!["alice@example.com".to_owned()]
+    );
+
+    // The IdP signed a NameID split by a comment: the whole value is used
+    let assertion = validate(COMMENT_IN_NAME_ID, &idp_keys(), now()).unwrap();
+    assert_eq!(assertion.name_id, "ab");
+}
+
+#[test]
+fn unsigned() {
+    let start = SIGNED_ASSERTION.find("<ds:Signature").unwrap();
+    let end = SIGNED_ASSERTION.find("</ds:Signature>").unwrap() + "</ds:Signature>".len();
+    let document = format!("{}{}", &SIGNED_ASSERTION[..start], &SIGNED_ASSERTION[end..]);
+    let error = validate(&document, &idp_keys(), now()).unwrap_err();
+    assert!(matches!(
+        error,
+        ResponseError::Signature(SignatureError::Missing)
+    ));
+}
+
+#[test]
+fn signature_wrapping() {
+    // Move the signed assertion out of the way, and inject a forged one with
+    // the same ID
+    let start = SIGNED_ASSERTION.find("<saml:Assertion").unwrap();
+    let end = SIGNED_ASSERTION.find("</saml:Assertion>").unwrap() + "</saml:Assertion>".len();
+    let original = &SIGNED_ASSERTION[start..end];
+    let forged = original.replace("alice", "mallory");
+    let document = format!(
+        "{}{}<samlp:Extensions>{}</samlp:Extensions>{}",
+        &SIGNED_ASSERTION[..start],
+        forged,
+        original,
+        &SIGNED_ASSERTION[end..]
+    );
+
+    let error = validate(&document, &idp_keys(), now()).unwrap_err();
+    assert!(matches!(
+        error,
+        ResponseError::Signature(SignatureError::InvalidReference)
+    ));
+}
+
+#[test]
+fn wrong_audience() {
+    let acs_url = acs_url();
+    let keys = idp_keys();
+    let validation_data = ResponseValidationData {
+        idp_entity_id: "https://idp.example.com/metadata",
+        sp_entity_id: "https://other.example.com/",
+        assertion_consumer_service_url: &acs_url,
+        request_id: "_request",
+        keys: &keys,
+    };
+
+    let error = validate_response(
+        &Base64::encode_string(SIGNED_RESPONSE.as_bytes()),
+        &validation_data,
+        now(),
+    )
+    .unwrap_err();
+    assert!(matches!(error, ResponseError::AudienceMismatch));
+}
+
+#[test]
+fn wrong_issuer() {
+    let acs_url = acs_url();
+    let keys = idp_keys();
+    let validation_data = ResponseValidationData {
+        idp_entity_id: "https://other-idp.example.com/metadata",
+        sp_entity_id: "https://mas.example.com/",
+        assertion_consumer_service_url: &acs_url,
+        request_id: "_request",
+        keys: &keys,
+    };
+
+    let error = validate_response(
+        &Base64::encode_string(SIGNED_ASSERTION.as_bytes()),
+        &validation_data,
+        now(),
+    )
+    .unwrap_err();
+    assert!(matches!(error, ResponseError::IssuerMismatch));
+}
+
+#[test]
+fn wrong_destination() {
+    let acs_url = Url::parse("https://mas.example.com/upstream/saml2/other/acs").unwrap();
+    let keys = idp_keys();
+    let validation_data = ResponseValidationData {
+        idp_entity_id: "https://idp.example.com/metadata",
+        sp_entity_id: "https://mas.example.com/",
+        assertion_consumer_service_url: &acs_url,
+        request_id: "_request",
+        keys: &keys,
+    };
+
+    let error = validate_response(
+        &Base64::encode_string(SIGNED_RESPONSE.as_bytes()),
+        &validation_data,
+        now(),
+    )
+    .unwrap_err();
+    assert!(matches!(error, ResponseError::DestinationMismatch));
+}
+
+#[test]
+fn validity_window() {
+    let keys = idp_keys();
+
+    // Within the allowed clock skew
+    validate(SIGNED_ASSERTION, &keys, now() - Duration::minutes(1)).unwrap();
+    validate(SIGNED_ASSERTION, &keys, now() + Duration::minutes(6)).unwrap();
+
+    let error = validate(SIGNED_ASSERTION, &keys, now() - Duration::minutes(5)).unwrap_err();
+    assert!(matches!(error, ResponseError::NotYetValid));
+
+    let error = validate(SIGNED_ASSERTION, &keys, now() + Duration::minutes(10)).unwrap_err();
+    assert!(matches!(error, ResponseError::Expired));
+}
+
+#[test]
+fn error_status() {
+    let document = r#"<samlp:Response xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" ID="_response" Version="2.0" IssueInstant="2024-10-10T10:00:00Z" InResponseTo="_request">
+  <samlp:Status>
+    <samlp:StatusCode Value="urn:oasis:names:tc:SAML:2.0:status:Responder">
+      <samlp:StatusCode Value="urn:oasis:names:tc:SAML:2.0:status:AuthnFailed"/>
+    </samlp:StatusCode>
+    <samlp:StatusMessage>User cancelled</samlp:StatusMessage>
+  </samlp:Status>
+</samlp:Response>"#;
+
+    let error = validate(document, &idp_keys(), now()).unwrap_err();
+    let ResponseError::Status {
+        code,
+        sub_code,
+        message,
+    } = error
+    else {
+        panic!("unexpected error {error:?}");
+    };
+    assert_eq!(code, "urn:oasis:names:tc:SAML:2.0:status:Responder");
+    assert_eq!(
+        sub_code.as_deref(),
+        Some("urn:oasis:names:tc:SAML:2.0:status:AuthnFailed")
+    );
+    assert_eq!(message.as_deref(), Some("User cancelled"));
+}
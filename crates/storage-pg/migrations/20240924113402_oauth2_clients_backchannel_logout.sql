@@ -0,0 +1,9 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Add the columns needed for OpenID Connect Back-Channel Logout
+ALTER TABLE "oauth2_clients"
+  ADD COLUMN "backchannel_logout_uri" TEXT,
+  ADD COLUMN "backchannel_logout_session_required" BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,58 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- TOTP secrets enrolled by users as a second authentication factor
+CREATE TABLE "user_totp_secrets" (
+  "user_totp_secret_id" UUID NOT NULL
+    CONSTRAINT "user_totp_secrets_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_totp_secrets_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  -- The secret, encrypted with the site-wide encryption key
+  "encrypted_secret" TEXT NOT NULL,
+
+  -- The last time step for which a code was accepted, to prevent replays
+  "last_used_step" BIGINT,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "confirmed_at" TIMESTAMP WITH TIME ZONE,
+  "removed_at" TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX "user_totp_secrets_user_id_idx"
+  ON "user_totp_secrets" ("user_id");
+
+-- Single-use recovery codes, usable instead of a TOTP code
+CREATE TABLE "user_totp_recovery_codes" (
+  "user_totp_recovery_code_id" UUID NOT NULL
+    CONSTRAINT "user_totp_recovery_codes_pkey"
+    PRIMARY KEY,
+
+  "user_totp_secret_id" UUID NOT NULL
+    CONSTRAINT "user_totp_recovery_codes_user_totp_secret_id_fkey"
+    REFERENCES "user_totp_secrets" ("user_totp_secret_id"),
+
+  -- The SHA-256 hash of the code
+  "hashed_code" TEXT NOT NULL,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "consumed_at" TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX "user_totp_recovery_codes_user_totp_secret_id_idx"
+  ON "user_totp_recovery_codes" ("user_totp_secret_id");
+
+-- Record the second factor used by each authentication of a user_session
+ALTER TABLE "user_session_authentications"
+    ADD COLUMN "user_totp_secret_id" UUID
+        REFERENCES "user_totp_secrets" ("user_totp_secret_id")
+        ON DELETE SET NULL,
+
+    ADD COLUMN "user_totp_recovery_code_id" UUID
+        REFERENCES "user_totp_recovery_codes" ("user_totp_recovery_code_id")
+        ON DELETE SET NULL;
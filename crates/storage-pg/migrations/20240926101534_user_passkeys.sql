@@ -0,0 +1,39 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- WebAuthn credentials (passkeys) registered by users
+CREATE TABLE "user_passkeys" (
+  "user_passkey_id" UUID NOT NULL
+    CONSTRAINT "user_passkeys_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_passkeys_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  -- The credential ID, encoded in base64url. Those are globally unique, and
+  -- used to find the credential when logging in without a username
+  "credential_id" TEXT NOT NULL
+    CONSTRAINT "user_passkeys_credential_id_unique"
+    UNIQUE,
+
+  -- The name given to the passkey by the user
+  "name" TEXT NOT NULL,
+
+  -- The serialized credential, with its public key and signature counter
+  "credential" TEXT NOT NULL,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "last_used_at" TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX "user_passkeys_user_id_idx"
+  ON "user_passkeys" ("user_id");
+
+-- Record the passkey used by each authentication of a user_session
+ALTER TABLE "user_session_authentications"
+    ADD COLUMN "user_passkey_id" UUID
+        REFERENCES "user_passkeys" ("user_passkey_id")
+        ON DELETE SET NULL;
@@ -0,0 +1,33 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Clients can be required to use the Pushed Authorization Request endpoint
+ALTER TABLE "oauth2_clients"
+  ADD COLUMN "require_pushed_authorization_requests" BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Authorization requests pushed by clients, as defined by RFC 9126.
+-- They are referred to by a `request_uri` on the authorization endpoint, and
+-- can only be used once, shortly after being pushed.
+CREATE TABLE "oauth2_pushed_authorization_requests" (
+    "oauth2_pushed_authorization_request_id" UUID NOT NULL
+        PRIMARY KEY,
+
+    -- The client which pushed the request
+    "oauth2_client_id" UUID NOT NULL
+        REFERENCES "oauth2_clients" ("oauth2_client_id")
+        ON DELETE CASCADE,
+
+    -- The authorization request parameters, as a JSON object of strings
+    "parameters" JSONB NOT NULL,
+
+    -- Timestamp when the request was pushed
+    "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    -- Timestamp when the request stops being usable
+    "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    -- Timestamp when the request was used on the authorization endpoint
+    "consumed_at" TIMESTAMP WITH TIME ZONE
+);
@@ -0,0 +1,9 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Settings for signed request objects, as defined by RFC 9101
+ALTER TABLE "oauth2_clients"
+  ADD COLUMN "request_object_signing_alg" TEXT,
+  ADD COLUMN "require_signed_request_object" BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,12 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Record the JWK thumbprint of the DPoP key tokens are bound to, as defined by
+-- RFC 9449
+ALTER TABLE "oauth2_access_tokens"
+  ADD COLUMN "dpop_jkt" TEXT;
+
+ALTER TABLE "oauth2_refresh_tokens"
+  ADD COLUMN "dpop_jkt" TEXT;
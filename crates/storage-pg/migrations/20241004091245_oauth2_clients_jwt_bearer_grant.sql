@@ -0,0 +1,10 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Add a flag on oauth2_clients to indicate whether they support the JWT bearer
+-- authorization grant
+ALTER TABLE oauth2_clients
+    ADD COLUMN grant_type_jwt_bearer BOOLEAN
+        NOT NULL DEFAULT FALSE;
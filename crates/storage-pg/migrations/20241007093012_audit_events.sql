@@ -0,0 +1,46 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Append-only log of security-relevant events
+--
+-- There are deliberately no foreign keys on this table: the audit trail must
+-- not be affected by other rows being cleaned up
+CREATE TABLE "audit_events" (
+  "audit_event_id" UUID NOT NULL
+    CONSTRAINT "audit_events_pkey"
+    PRIMARY KEY,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+  -- The kind of event, like 'user.login' or 'session.ended'
+  "kind" TEXT NOT NULL,
+
+  -- The user concerned by the event, if any
+  "user_id" UUID,
+
+  -- Who triggered the event: 'anonymous', 'user', 'admin_token' or 'cli'
+  "actor_type" TEXT NOT NULL,
+
+  -- The user behind the actor, if any
+  "actor_user_id" UUID,
+
+  -- The OAuth 2.0 session of the admin token, for the 'admin_token' actor
+  "actor_oauth2_session_id" UUID,
+
+  "ip_address" INET,
+  "user_agent" TEXT,
+
+  -- Structured details about the event, which depend on its kind
+  "details" JSONB NOT NULL DEFAULT '{}'::jsonb
+);
+
+CREATE INDEX "audit_events_user_id_idx"
+  ON "audit_events" ("user_id");
+
+CREATE INDEX "audit_events_kind_idx"
+  ON "audit_events" ("kind");
+
+CREATE INDEX "audit_events_created_at_idx"
+  ON "audit_events" ("created_at");
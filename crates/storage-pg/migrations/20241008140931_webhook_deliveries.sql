@@ -0,0 +1,45 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Deliveries of user lifecycle events to the configured webhook targets
+--
+-- There is one row per event and per target. Rows which have neither
+-- `delivered_at` nor `failed_at` set are still being retried.
+CREATE TABLE "webhook_deliveries" (
+  "webhook_delivery_id" UUID NOT NULL
+    CONSTRAINT "webhook_deliveries_pkey"
+    PRIMARY KEY,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+  -- The name of the target, as set in the configuration
+  "target" TEXT NOT NULL,
+
+  -- The kind of event, like 'user.registered' or 'user.locked'
+  "event" TEXT NOT NULL,
+
+  -- The user concerned by the event. This is not a foreign key, so that
+  -- the delivery history is kept if the user gets removed
+  "user_id" UUID,
+
+  -- The event-specific data sent to the target
+  "data" JSONB NOT NULL DEFAULT '{}'::jsonb,
+
+  "attempts" INTEGER NOT NULL DEFAULT 0,
+  "last_attempt_at" TIMESTAMP WITH TIME ZONE,
+  "last_error" TEXT,
+
+  -- Set when the target acknowledged the delivery
+  "delivered_at" TIMESTAMP WITH TIME ZONE,
+
+  -- Set when the delivery ran out of attempts
+  "failed_at" TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX "webhook_deliveries_user_id_idx"
+  ON "webhook_deliveries" ("user_id");
+
+CREATE INDEX "webhook_deliveries_target_idx"
+  ON "webhook_deliveries" ("target");
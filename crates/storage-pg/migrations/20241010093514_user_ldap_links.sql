@@ -0,0 +1,31 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Links between users and entries of an LDAP directory
+CREATE TABLE "user_ldap_links" (
+  "user_ldap_link_id" UUID NOT NULL
+    CONSTRAINT "user_ldap_links_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_ldap_links_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  -- The stable identifier of the directory entry
+  "subject" TEXT NOT NULL
+    CONSTRAINT "user_ldap_links_subject_unique"
+    UNIQUE,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX "user_ldap_links_user_id_idx"
+  ON "user_ldap_links" ("user_id");
+
+-- Record the directory entry used by each authentication of a user_session
+ALTER TABLE "user_session_authentications"
+    ADD COLUMN "user_ldap_link_id" UUID
+        REFERENCES "user_ldap_links" ("user_ldap_link_id")
+        ON DELETE SET NULL;
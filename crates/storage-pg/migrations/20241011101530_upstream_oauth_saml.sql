@@ -0,0 +1,13 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Settings of the upstream providers which are SAML 2.0 identity providers
+ALTER TABLE "upstream_oauth_providers"
+    ADD COLUMN "saml" JSONB;
+
+-- Attributes of the SAML assertion which completed an authorization session,
+-- used in place of the ID token claims
+ALTER TABLE "upstream_oauth_authorization_sessions"
+    ADD COLUMN "saml_attributes" JSONB;
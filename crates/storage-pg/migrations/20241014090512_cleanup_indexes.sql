@@ -0,0 +1,80 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Indexes used by the database maintenance job to find the rows to clean up
+CREATE INDEX "oauth2_refresh_tokens_consumed_at_idx"
+  ON "oauth2_refresh_tokens" ("consumed_at")
+  WHERE "consumed_at" IS NOT NULL;
+
+CREATE INDEX "oauth2_sessions_finished_at_idx"
+  ON "oauth2_sessions" ("finished_at")
+  WHERE "finished_at" IS NOT NULL;
+
+CREATE INDEX "compat_sessions_finished_at_idx"
+  ON "compat_sessions" ("finished_at")
+  WHERE "finished_at" IS NOT NULL;
+
+CREATE INDEX "user_sessions_finished_at_idx"
+  ON "user_sessions" ("finished_at")
+  WHERE "finished_at" IS NOT NULL;
+
+CREATE INDEX "oauth2_authorization_grants_created_at_idx"
+  ON "oauth2_authorization_grants" ("created_at");
+
+CREATE INDEX "oauth2_device_code_grant_expires_at_idx"
+  ON "oauth2_device_code_grant" ("expires_at");
+
+CREATE INDEX "user_recovery_sessions_created_at_idx"
+  ON "user_recovery_sessions" ("created_at");
+
+CREATE INDEX "upstream_oauth_authorization_sessions_created_at_idx"
+  ON "upstream_oauth_authorization_sessions" ("created_at");
+
+-- Indexes on the foreign keys pointing to the rows being cleaned up, so that
+-- deleting them doesn't require a sequential scan of the referencing tables
+CREATE INDEX "oauth2_access_tokens_oauth2_session_id_idx"
+  ON "oauth2_access_tokens" ("oauth2_session_id");
+
+CREATE INDEX "oauth2_refresh_tokens_oauth2_session_id_idx"
+  ON "oauth2_refresh_tokens" ("oauth2_session_id");
+
+CREATE INDEX "oauth2_refresh_tokens_oauth2_access_token_id_idx"
+  ON "oauth2_refresh_tokens" ("oauth2_access_token_id");
+
+CREATE INDEX "oauth2_authorization_grants_oauth2_session_id_idx"
+  ON "oauth2_authorization_grants" ("oauth2_session_id");
+
+CREATE INDEX "oauth2_device_code_grant_oauth2_session_id_idx"
+  ON "oauth2_device_code_grant" ("oauth2_session_id");
+
+CREATE INDEX "oauth2_device_code_grant_user_session_id_idx"
+  ON "oauth2_device_code_grant" ("user_session_id");
+
+CREATE INDEX "oauth2_sessions_user_session_id_idx"
+  ON "oauth2_sessions" ("user_session_id");
+
+CREATE INDEX "compat_access_tokens_compat_session_id_idx"
+  ON "compat_access_tokens" ("compat_session_id");
+
+CREATE INDEX "compat_refresh_tokens_compat_session_id_idx"
+  ON "compat_refresh_tokens" ("compat_session_id");
+
+CREATE INDEX "compat_refresh_tokens_compat_access_token_id_idx"
+  ON "compat_refresh_tokens" ("compat_access_token_id");
+
+CREATE INDEX "compat_sso_logins_compat_session_id_idx"
+  ON "compat_sso_logins" ("compat_session_id");
+
+CREATE INDEX "compat_sessions_user_session_id_idx"
+  ON "compat_sessions" ("user_session_id");
+
+CREATE INDEX "user_session_authentications_user_session_id_idx"
+  ON "user_session_authentications" ("user_session_id");
+
+CREATE INDEX "user_session_authentications_upstream_session_id_idx"
+  ON "user_session_authentications" ("upstream_oauth_authorization_session_id");
+
+CREATE INDEX "user_recovery_tickets_user_recovery_session_id_idx"
+  ON "user_recovery_tickets" ("user_recovery_session_id");
@@ -0,0 +1,9 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Whether a passkey authentication was the second factor after a password,
+-- rather than a passwordless login
+ALTER TABLE "user_session_authentications"
+  ADD COLUMN "after_password" BOOLEAN NOT NULL DEFAULT FALSE;
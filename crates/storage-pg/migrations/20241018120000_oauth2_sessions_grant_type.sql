@@ -0,0 +1,9 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- The grant type which started the OAuth 2.0 session, so that sessions
+-- started on behalf of a user by a client can be told apart
+ALTER TABLE "oauth2_sessions"
+  ADD COLUMN "grant_type" TEXT;
@@ -0,0 +1,29 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- The JWT bearer assertions (RFC 7523) already used by clients to start a
+-- session on behalf of a user. An assertion can only be used once, so each
+-- `jti` is remembered until the assertion expires.
+CREATE TABLE "oauth2_jwt_bearer_assertions" (
+    -- The client which used the assertion
+    "oauth2_client_id" UUID NOT NULL
+        REFERENCES "oauth2_clients" ("oauth2_client_id")
+        ON DELETE CASCADE,
+
+    -- The `jti` claim of the assertion
+    "jti" TEXT NOT NULL,
+
+    -- Timestamp when the assertion was used
+    "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    -- Timestamp after which the assertion can't be used anymore
+    "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    PRIMARY KEY ("oauth2_client_id", "jti")
+);
+
+-- Used to remove the expired assertions
+CREATE INDEX "oauth2_jwt_bearer_assertions_expires_at_idx"
+  ON "oauth2_jwt_bearer_assertions" ("expires_at");
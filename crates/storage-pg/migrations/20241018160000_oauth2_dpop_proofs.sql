@@ -0,0 +1,27 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- The DPoP proofs (RFC 9449) already presented by clients. A proof can only be
+-- used once, so each `jti` is remembered with the key which signed it until
+-- the proof is too old to be accepted anyway.
+CREATE TABLE "oauth2_dpop_proofs" (
+    -- The SHA-256 thumbprint of the key which signed the proof
+    "jkt" TEXT NOT NULL,
+
+    -- The `jti` claim of the proof
+    "jti" TEXT NOT NULL,
+
+    -- Timestamp when the proof was used
+    "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    -- Timestamp after which the proof isn't accepted anymore
+    "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    PRIMARY KEY ("jkt", "jti")
+);
+
+-- Used to remove the expired proofs
+CREATE INDEX "oauth2_dpop_proofs_expires_at_idx"
+  ON "oauth2_dpop_proofs" ("expires_at");
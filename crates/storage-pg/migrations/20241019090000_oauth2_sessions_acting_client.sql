@@ -0,0 +1,11 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- The client which obtained the OAuth 2.0 session on behalf of the session's
+-- client, through a token exchange
+ALTER TABLE "oauth2_sessions"
+  ADD COLUMN "acting_oauth2_client_id" UUID
+    REFERENCES "oauth2_clients" ("oauth2_client_id")
+    ON DELETE SET NULL;
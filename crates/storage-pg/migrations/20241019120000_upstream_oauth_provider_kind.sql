@@ -0,0 +1,62 @@
+-- Copyright 2024 New Vector Ltd.
+--
+-- SPDX-License-Identifier: AGPL-3.0-only
+-- Please see LICENSE in the repository root for full details.
+
+-- Distinguish OpenID Connect providers from SAML 2.0 identity providers
+ALTER TABLE "upstream_oauth_providers"
+    ADD COLUMN "kind" TEXT NOT NULL DEFAULT 'oidc';
+
+-- Our own entity ID was stored as the client ID of SAML 2.0 providers, move
+-- it to the SAML settings
+UPDATE "upstream_oauth_providers"
+    SET "kind" = 'saml',
+        "saml" = "saml" || jsonb_build_object('sp_entity_id', "client_id")
+    WHERE "saml" IS NOT NULL;
+
+-- The OAuth 2.0 client settings are only set for OpenID Connect providers
+ALTER TABLE "upstream_oauth_providers"
+    ALTER COLUMN "scope" DROP NOT NULL,
+    ALTER COLUMN "client_id" DROP NOT NULL,
+    ALTER COLUMN "token_endpoint_auth_method" DROP NOT NULL;
+
+UPDATE "upstream_oauth_providers"
+    SET "scope" = NULL,
+        "client_id" = NULL,
+        "token_endpoint_auth_method" = NULL
+    WHERE "kind" = 'saml';
+
+ALTER TABLE "upstream_oauth_providers"
+    ADD CONSTRAINT "upstream_oauth_providers_kind_check"
+        CHECK ("kind" IN ('oidc', 'saml')),
+    ADD CONSTRAINT "upstream_oauth_providers_saml_check"
+        CHECK (("kind" = 'saml') = ("saml" IS NOT NULL)),
+    ADD CONSTRAINT "upstream_oauth_providers_oidc_check"
+        CHECK (
+            "kind" <> 'oidc'
+            OR (
+                "scope" IS NOT NULL
+                AND "client_id" IS NOT NULL
+                AND "token_endpoint_auth_method" IS NOT NULL
+            )
+        );
+
+-- The ID of the SAML 2.0 authentication request was stored as the nonce of
+-- the session, move it to its own column
+ALTER TABLE "upstream_oauth_authorization_sessions"
+    ADD COLUMN "saml_request_id" TEXT,
+    ALTER COLUMN "nonce" DROP NOT NULL;
+
+UPDATE "upstream_oauth_authorization_sessions"
+    SET "saml_request_id" = "nonce",
+        "nonce" = NULL
+    WHERE "upstream_oauth_provider_id" IN (
+        SELECT "upstream_oauth_provider_id"
+        FROM "upstream_oauth_providers"
+        WHERE "kind" = 'saml'
+    );
+
+-- A session is either an OpenID Connect or a SAML 2.0 one
+ALTER TABLE "upstream_oauth_authorization_sessions"
+    ADD CONSTRAINT "upstream_oauth_authorization_sessions_kind_check"
+        CHECK (("nonce" IS NULL) <> ("saml_request_id" IS NULL));
@@ -70,6 +70,8 @@ mod priv_ {
         pub(super) user_agent: Option<String>,
         pub(super) last_active_at: Option<DateTime<Utc>>,
         pub(super) last_active_ip: Option<IpAddr>,
+        pub(super) grant_type: Option<String>,
+        pub(super) acting_oauth2_client_id: Option<Uuid>,
     }
 }
 
@@ -97,6 +99,8 @@ impl TryFrom<AppSessionLookup> for AppSession {
             user_agent,
             last_active_at,
             last_active_ip,
+            grant_type,
+            acting_oauth2_client_id,
         } = value;
 
         let user_agent = user_agent.map(UserAgent::parse);
@@ -168,6 +172,13 @@ impl TryFrom<AppSessionLookup> for AppSession {
                         .source(e)
                 })?;
 
+                let grant_type = grant_type.map(|s| s.parse()).transpose().map_err(|e| {
+                    DatabaseInconsistencyError::on("oauth2_sessions")
+                        .column("grant_type")
+                        .row(id)
+                        .source(e)
+                })?;
+
                 let state = match value.finished_at {
                     None => SessionState::Valid,
                     Some(finished_at) => SessionState::Finished { finished_at },
@@ -184,6 +195,8 @@ impl TryFrom<AppSessionLookup> for AppSession {
                     user_agent,
                     last_active_at,
                     last_active_ip,
+                    grant_type,
+                    acting_client_id: acting_oauth2_client_id.map(Ulid::from),
                 };
 
                 Ok(AppSession::OAuth2(Box::new(session)))
@@ -312,6 +325,14 @@ impl<'c> AppSessionRepository for PgAppSessionRepository<'c> {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveIp)),
                 AppSessionLookupIden::LastActiveIp,
             )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::GrantType)),
+                AppSessionLookupIden::GrantType,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::ActingOAuth2ClientId)),
+                AppSessionLookupIden::ActingOauth2ClientId,
+            )
             .from(OAuth2Sessions::Table)
             .apply_filter(oauth2_filter)
             .clone();
@@ -364,6 +385,11 @@ impl<'c> AppSessionRepository for PgAppSessionRepository<'c> {
                 Expr::col((CompatSessions::Table, CompatSessions::LastActiveIp)),
                 AppSessionLookupIden::LastActiveIp,
             )
+            .expr_as(Expr::cust("NULL"), AppSessionLookupIden::GrantType)
+            .expr_as(
+                Expr::cust("NULL"),
+                AppSessionLookupIden::ActingOauth2ClientId,
+            )
             .from(CompatSessions::Table)
             .apply_filter(compat_filter)
             .clone();
@@ -572,6 +598,16 @@ mod tests {
                 None,
                 None,
                 Some("https://example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
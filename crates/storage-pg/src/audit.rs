@@ -0,0 +1,523 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! A module containing the PostgreSQL implementation of the
+//! [`AuditEventRepository`]
+
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+use mas_data_model::{AuditActor, AuditContext, AuditEvent, AuditEventKind, User};
+use mas_storage::{
+    audit::{AuditEventFilter, AuditEventRepository},
+    Clock, Page, Pagination,
+};
+use rand::RngCore;
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{
+    filter::{Filter, StatementExt},
+    iden::AuditEvents,
+    pagination::QueryBuilderExt,
+    DatabaseError, DatabaseInconsistencyError, ExecuteExt,
+};
+
+/// An implementation of [`AuditEventRepository`] for a PostgreSQL connection
+pub struct PgAuditEventRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgAuditEventRepository<'c> {
+    /// Create a new [`PgAuditEventRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+mod priv_ {
+    // The enum_def macro generates a public enum, which we don't want, because it
+    // triggers the missing docs warning
+    #![allow(missing_docs)]
+
+    use std::net::IpAddr;
+
+    use chrono::{DateTime, Utc};
+    use sea_query::enum_def;
+    use uuid::Uuid;
+
+    #[derive(Debug, sqlx::FromRow)]
+    #[enum_def]
+    pub(super) struct AuditEventLookup {
+        pub(super) audit_event_id: Uuid,
+        pub(super) created_at: DateTime<Utc>,
+        pub(super) kind: String,
+        pub(super) user_id: Option<Uuid>,
+        pub(super) actor_type: String,
+        pub(super) actor_user_id: Option<Uuid>,
+        pub(super) actor_oauth2_session_id: Option<Uuid>,
+        pub(super) ip_address: Option<IpAddr>,
+        pub(super) user_agent: Option<String>,
+        pub(super) details: serde_json::Value,
+    }
+}
+
+use priv_::{AuditEventLookup, AuditEventLookupIden};
+
+impl TryFrom<AuditEventLookup> for AuditEvent {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(value: AuditEventLookup) -> Result<Self, Self::Error> {
+        let id = Ulid::from(value.audit_event_id);
+
+        let kind: AuditEventKind = value.kind.parse().map_err(|e| {
+            DatabaseInconsistencyError::on("audit_events")
+                .column("kind")
+                .row(id)
+                .source(e)
+        })?;
+
+        let actor = match (
+            value.actor_type.as_str(),
+            value.actor_user_id,
+            value.actor_oauth2_session_id,
+        ) {
+            ("anonymous", None, None) => AuditActor::Anonymous,
+            ("user", Some(user_id), None) => AuditActor::User {
+                user_id: user_id.into(),
+            },
+            ("admin_token", user_id, Some(session_id)) => AuditActor::AdminToken {
+                session_id: session_id.into(),
+                user_id: user_id.map(Ulid::from),
+            },
+            ("cli", None, None) => AuditActor::Cli,
+            ("system", None, None) => AuditActor::System,
+            _ => {
+                return Err(DatabaseInconsistencyError::on("audit_events")
+                    .column("actor_type")
+                    .row(id))
+            }
+        };
+
+        Ok(AuditEvent {
+            id,
+            created_at: value.created_at,
+            kind,
+            user_id: value.user_id.map(Ulid::from),
+            actor,
+            ip_address: value.ip_address,
+            user_agent: value.user_agent,
+            details: value.details,
+        })
+    }
+}
+
+impl Filter for AuditEventFilter<'_> {
+    fn generate_condition(&self, _has_joins: bool) -> impl sea_query::IntoCondition {
+        sea_query::Condition::all()
+            .add_option(self.user().map(|user| {
+                Expr::col((AuditEvents::Table, AuditEvents::UserId)).eq(Uuid::from(user.id))
+            }))
+            .add_option(
+                self.kind().map(|kind| {
+                    Expr::col((AuditEvents::Table, AuditEvents::Kind)).eq(kind.as_str())
+                }),
+            )
+            .add_option(self.created_after_date().map(|created_after| {
+                Expr::col((AuditEvents::Table, AuditEvents::CreatedAt)).gt(created_after)
+            }))
+            .add_option(self.created_before_date().map(|created_before| {
+                Expr::col((AuditEvents::Table, AuditEvents::CreatedAt)).lt(created_before)
+            }))
+    }
+}
+
+#[async_trait]
+impl<'c> AuditEventRepository for PgAuditEventRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.audit_event.lookup",
+        skip_all,
+        fields(
+            db.query.text,
+            audit_event.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<AuditEvent>, Self::Error> {
+        let res = sqlx::query_as!(
+            AuditEventLookup,
+            r#"
+                SELECT audit_event_id
+                     , created_at
+                     , kind
+                     , user_id
+                     , actor_type
+                     , actor_user_id
+                     , actor_oauth2_session_id
+                     , ip_address as "ip_address: IpAddr"
+                     , user_agent
+                     , details
+                FROM audit_events
+                WHERE audit_event_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else { return Ok(None) };
+
+        Ok(Some(res.try_into()?))
+    }
+
+    #[tracing::instrument(
+        name = "db.audit_event.add",
+        skip_all,
+        fields(
+            db.query.text,
+            audit_event.id,
+            audit_event.kind = %kind,
+            audit_event.actor = context.actor.as_str(),
+            user.id,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        context: &AuditContext,
+        kind: AuditEventKind,
+        user: Option<&User>,
+        details: serde_json::Value,
+    ) -> Result<AuditEvent, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("audit_event.id", tracing::field::display(id));
+        if let Some(user) = user {
+            tracing::Span::current().record("user.id", tracing::field::display(user.id));
+        }
+
+        let (actor_user_id, actor_oauth2_session_id) = match context.actor {
+            AuditActor::AdminToken {
+                session_id,
+                user_id,
+            } => (user_id, Some(session_id)),
+            actor => (actor.user_id(), None),
+        };
+        let user_id = user.map(|user| user.id);
+
+        sqlx::query!(
+            r#"
+                INSERT INTO audit_events
+                    ( audit_event_id
+                    , created_at
+                    , kind
+                    , user_id
+                    , actor_type
+                    , actor_user_id
+                    , actor_oauth2_session_id
+                    , ip_address
+                    , user_agent
+                    , details
+                    )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            Uuid::from(id),
+            created_at,
+            kind.as_str(),
+            user_id.map(Uuid::from),
+            context.actor.as_str(),
+            actor_user_id.map(Uuid::from),
+            actor_oauth2_session_id.map(Uuid::from),
+            context.ip_address as Option<IpAddr>,
+            context.user_agent.as_deref(),
+            &details,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(AuditEvent {
+            id,
+            created_at,
+            kind,
+            user_id,
+            actor: context.actor,
+            ip_address: context.ip_address,
+            user_agent: context.user_agent.clone(),
+            details,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.audit_event.list",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn list(
+        &mut self,
+        filter: AuditEventFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<AuditEvent>, Self::Error> {
+        let (sql, arguments) = Query::select()
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::AuditEventId)),
+                AuditEventLookupIden::AuditEventId,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::CreatedAt)),
+                AuditEventLookupIden::CreatedAt,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::Kind)),
+                AuditEventLookupIden::Kind,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::UserId)),
+                AuditEventLookupIden::UserId,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::ActorType)),
+                AuditEventLookupIden::ActorType,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::ActorUserId)),
+                AuditEventLookupIden::ActorUserId,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::ActorOAuth2SessionId)),
+                AuditEventLookupIden::ActorOauth2SessionId,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::IpAddress)),
+                AuditEventLookupIden::IpAddress,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::UserAgent)),
+                AuditEventLookupIden::UserAgent,
+            )
+            .expr_as(
+                Expr::col((AuditEvents::Table, AuditEvents::Details)),
+                AuditEventLookupIden::Details,
+            )
+            .from(AuditEvents::Table)
+            .apply_filter(filter)
+            .generate_pagination((AuditEvents::Table, AuditEvents::AuditEventId), pagination)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let edges: Vec<AuditEventLookup> = sqlx::query_as_with(&sql, arguments)
+            .traced()
+            .fetch_all(&mut *self.conn)
+            .await?;
+
+        let page = pagination.process(edges).try_map(TryInto::try_into)?;
+
+        Ok(page)
+    }
+
+    #[tracing::instrument(
+        name = "db.audit_event.count",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn count(&mut self, filter: AuditEventFilter<'_>) -> Result<usize, Self::Error> {
+        let (sql, arguments) = Query::select()
+            .expr(Expr::col((AuditEvents::Table, AuditEvents::AuditEventId)).count())
+            .from(AuditEvents::Table)
+            .apply_filter(filter)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: i64 = sqlx::query_scalar_with(&sql, arguments)
+            .traced()
+            .fetch_one(&mut *self.conn)
+            .await?;
+
+        count
+            .try_into()
+            .map_err(DatabaseError::to_invalid_operation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use mas_data_model::{AuditActor, AuditContext, AuditEventKind};
+    use mas_storage::{
+        audit::AuditEventFilter, clock::MockClock, Clock, Pagination, RepositoryAccess,
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use sqlx::PgPool;
+
+    use crate::PgRepository;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_audit_event_repository(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let alice = repo
+            .user()
+            .add(&mut rng, &clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let bob = repo
+            .user()
+            .add(&mut rng, &clock, "bob".to_owned())
+            .await
+            .unwrap();
+
+        let all = AuditEventFilter::new();
+        assert_eq!(repo.audit_event().count(all).await.unwrap(), 0);
+
+        // A failed login attempt, by an anonymous client
+        let context = AuditContext::anonymous()
+            .with_ip_address(Some("203.0.113.1".parse().unwrap()))
+            .with_user_agent(Some("curl/8.0".to_owned()));
+        let failed_login = repo
+            .audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &context,
+                AuditEventKind::UserLoginFailed,
+                Some(&alice),
+                serde_json::json!({ "reason": "invalid_credentials" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(failed_login.actor, AuditActor::Anonymous);
+        assert_eq!(failed_login.user_id, Some(alice.id));
+
+        let lookup = repo
+            .audit_event()
+            .lookup(failed_login.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lookup, failed_login);
+
+        clock.advance(Duration::minutes(1));
+
+        // Alice logs in
+        let context = AuditContext::user(&alice);
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &context,
+                AuditEventKind::UserLogin,
+                Some(&alice),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+
+        clock.advance(Duration::minutes(1));
+        let before_lock = clock.now();
+
+        // Bob gets locked from the CLI
+        repo.audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &AuditContext::cli(),
+                AuditEventKind::UserLocked,
+                Some(&bob),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(repo.audit_event().count(all).await.unwrap(), 3);
+        assert_eq!(
+            repo.audit_event()
+                .count(all.for_user(&alice))
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            repo.audit_event()
+                .count(all.with_kind(AuditEventKind::UserLocked))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.audit_event()
+                .count(all.created_after(before_lock - Duration::seconds(1)))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.audit_event()
+                .count(all.created_before(before_lock - Duration::seconds(1)))
+                .await
+                .unwrap(),
+            2
+        );
+
+        // Events are listed in order, and the stored data round-trips
+        let page = repo
+            .audit_event()
+            .list(all.for_user(&alice), Pagination::first(10))
+            .await
+            .unwrap();
+        assert!(!page.has_next_page);
+        assert_eq!(page.edges.len(), 2);
+        assert_eq!(page.edges[0], failed_login);
+        assert_eq!(page.edges[1].actor, AuditActor::User { user_id: alice.id });
+
+        let page = repo
+            .audit_event()
+            .list(all.for_user(&bob), Pagination::first(10))
+            .await
+            .unwrap();
+        assert_eq!(page.edges.len(), 1);
+        assert_eq!(page.edges[0].kind, AuditEventKind::UserLocked);
+        assert_eq!(page.edges[0].actor, AuditActor::Cli);
+
+        // Events triggered by the server itself round-trip as well
+        let expired = repo
+            .audit_event()
+            .add(
+                &mut rng,
+                &clock,
+                &AuditContext::system(),
+                AuditEventKind::SessionEnded,
+                Some(&bob),
+                serde_json::json!({ "reason": "idle_timeout" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(expired.actor, AuditActor::System);
+        let page = repo
+            .audit_event()
+            .list(
+                all.with_kind(AuditEventKind::SessionEnded),
+                Pagination::first(10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.edges, vec![expired]);
+    }
+}
@@ -705,4 +705,172 @@ mod tests {
         assert!(!logins.has_next_page);
         assert_eq!(logins.edges, &[login]);
     }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_session_cleanup(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "john".to_owned())
+            .await
+            .unwrap();
+
+        // A session with tokens and an SSO login attached
+        let session1 = repo
+            .compat_session()
+            .add(
+                &mut rng,
+                &clock,
+                &user,
+                Device::generate(&mut rng),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        let access_token = repo
+            .compat_access_token()
+            .add(&mut rng, &clock, &session1, "access".to_owned(), None)
+            .await
+            .unwrap();
+        let refresh_token = repo
+            .compat_refresh_token()
+            .add(
+                &mut rng,
+                &clock,
+                &session1,
+                &access_token,
+                "refresh".to_owned(),
+            )
+            .await
+            .unwrap();
+        let login = repo
+            .compat_sso_login()
+            .add(
+                &mut rng,
+                &clock,
+                "login-token".to_owned(),
+                "https://example.com/callback".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        let login = repo
+            .compat_sso_login()
+            .fulfill(&clock, login, &session1)
+            .await
+            .unwrap();
+
+        // A plain session, and one which stays active
+        let session2 = repo
+            .compat_session()
+            .add(
+                &mut rng,
+                &clock,
+                &user,
+                Device::generate(&mut rng),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        let session3 = repo
+            .compat_session()
+            .add(
+                &mut rng,
+                &clock,
+                &user,
+                Device::generate(&mut rng),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let session1 = repo
+            .compat_session()
+            .finish(&clock, session1)
+            .await
+            .unwrap();
+        let session2 = repo
+            .compat_session()
+            .finish(&clock, session2)
+            .await
+            .unwrap();
+
+        // Nothing finished strictly before now
+        assert_eq!(
+            repo.compat_session()
+                .cleanup_finished(clock.now(), 10)
+                .await
+                .unwrap(),
+            0
+        );
+
+        clock.advance(Duration::try_hours(1).unwrap());
+
+        // Finished sessions are removed in batches
+        assert_eq!(
+            repo.compat_session()
+                .cleanup_finished(clock.now(), 1)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.compat_session()
+                .cleanup_finished(clock.now(), 1)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.compat_session()
+                .cleanup_finished(clock.now(), 1)
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert!(repo
+            .compat_session()
+            .lookup(session1.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .compat_session()
+            .lookup(session2.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .compat_session()
+            .lookup(session3.id)
+            .await
+            .unwrap()
+            .is_some());
+
+        // Everything attached to the first session went with it
+        assert!(repo
+            .compat_access_token()
+            .lookup(access_token.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .compat_refresh_token()
+            .lookup(refresh_token.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .compat_sso_login()
+            .lookup(login.id)
+            .await
+            .unwrap()
+            .is_none());
+    }
 }
@@ -253,6 +253,26 @@ impl Filter for CompatSessionFilter<'_> {
                 Expr::col((CompatSessions::Table, CompatSessions::LastActiveAt))
                     .lt(last_active_before)
             }))
+            .add_option(self.created_before().map(|created_before| {
+                Expr::col((CompatSessions::Table, CompatSessions::CreatedAt)).lt(created_before)
+            }))
+            .add_option(self.inactive_since().map(|inactive_since| {
+                // Sessions which were never active are considered active when
+                // they were created
+                sea_query::Condition::any()
+                    .add(
+                        Expr::col((CompatSessions::Table, CompatSessions::LastActiveAt))
+                            .lt(inactive_since),
+                    )
+                    .add(
+                        Expr::col((CompatSessions::Table, CompatSessions::LastActiveAt))
+                            .is_null()
+                            .and(
+                                Expr::col((CompatSessions::Table, CompatSessions::CreatedAt))
+                                    .lt(inactive_since),
+                            ),
+                    )
+            }))
             .add_option(self.device().map(|device| {
                 Expr::col((CompatSessions::Table, CompatSessions::DeviceId)).eq(device.as_str())
             }))
@@ -629,4 +649,51 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
 
         Ok(compat_session)
     }
+
+    #[tracing::instrument(
+        name = "db.compat_session.cleanup_finished",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                WITH sessions AS (
+                    SELECT compat_session_id
+                    FROM compat_sessions
+                    WHERE finished_at < $1
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                ),
+                refresh_tokens AS (
+                    DELETE FROM compat_refresh_tokens
+                    WHERE compat_session_id IN (SELECT compat_session_id FROM sessions)
+                ),
+                access_tokens AS (
+                    DELETE FROM compat_access_tokens
+                    WHERE compat_session_id IN (SELECT compat_session_id FROM sessions)
+                ),
+                sso_logins AS (
+                    DELETE FROM compat_sso_logins
+                    WHERE compat_session_id IN (SELECT compat_session_id FROM sessions)
+                )
+                DELETE FROM compat_sessions
+                WHERE compat_session_id IN (SELECT compat_session_id FROM sessions)
+            "#,
+            before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
 }
@@ -39,6 +39,18 @@ pub enum UserEmails {
     ConfirmedAt,
 }
 
+#[derive(sea_query::Iden)]
+pub enum UserPasskeys {
+    Table,
+    UserPasskeyId,
+    UserId,
+    CredentialId,
+    Name,
+    Credential,
+    CreatedAt,
+    LastUsedAt,
+}
+
 #[derive(sea_query::Iden)]
 pub enum CompatSessions {
     Table,
@@ -66,6 +78,46 @@ pub enum CompatSsoLogins {
     ExchangedAt,
 }
 
+#[derive(sea_query::Iden)]
+#[iden = "oauth2_clients"]
+pub enum OAuth2Clients {
+    Table,
+    #[iden = "oauth2_client_id"]
+    OAuth2ClientId,
+    EncryptedClientSecret,
+    ApplicationType,
+    RedirectUris,
+    GrantTypeAuthorizationCode,
+    GrantTypeRefreshToken,
+    GrantTypeClientCredentials,
+    GrantTypeDeviceCode,
+    GrantTypeTokenExchange,
+    GrantTypeJwtBearer,
+    ClientName,
+    LogoUri,
+    ClientUri,
+    PolicyUri,
+    TosUri,
+    JwksUri,
+    Jwks,
+    IdTokenSignedResponseAlg,
+    UserinfoSignedResponseAlg,
+    TokenEndpointAuthMethod,
+    TokenEndpointAuthSigningAlg,
+    InitiateLoginUri,
+    PostLogoutRedirectUris,
+    BackchannelLogoutUri,
+    BackchannelLogoutSessionRequired,
+    RequirePushedAuthorizationRequests,
+    RequestObjectSigningAlg,
+    RequireSignedRequestObject,
+    IdTokenEncryptedResponseAlg,
+    IdTokenEncryptedResponseEnc,
+    UserinfoEncryptedResponseAlg,
+    UserinfoEncryptedResponseEnc,
+    IsStatic,
+}
+
 #[derive(sea_query::Iden)]
 #[iden = "oauth2_sessions"]
 pub enum OAuth2Sessions {
@@ -82,6 +134,9 @@ pub enum OAuth2Sessions {
     UserAgent,
     LastActiveAt,
     LastActiveIp,
+    GrantType,
+    #[iden = "acting_oauth2_client_id"]
+    ActingOAuth2ClientId,
 }
 
 #[derive(sea_query::Iden)]
@@ -90,6 +145,7 @@ pub enum UpstreamOAuthProviders {
     Table,
     #[iden = "upstream_oauth_provider_id"]
     UpstreamOAuthProviderId,
+    Kind,
     Issuer,
     HumanName,
     BrandName,
@@ -107,6 +163,7 @@ pub enum UpstreamOAuthProviders {
     JwksUriOverride,
     TokenEndpointOverride,
     AuthorizationEndpointOverride,
+    Saml,
 }
 
 #[derive(sea_query::Iden)]
@@ -121,3 +178,56 @@ pub enum UpstreamOAuthLinks {
     Subject,
     CreatedAt,
 }
+
+#[derive(sea_query::Iden)]
+pub enum AuditEvents {
+    Table,
+    AuditEventId,
+    CreatedAt,
+    Kind,
+    UserId,
+    ActorType,
+    ActorUserId,
+    #[iden = "actor_oauth2_session_id"]
+    ActorOAuth2SessionId,
+    IpAddress,
+    UserAgent,
+    Details,
+}
+
+#[derive(sea_query::Iden)]
+pub enum WebhookDeliveries {
+    Table,
+    WebhookDeliveryId,
+    CreatedAt,
+    Target,
+    Event,
+    UserId,
+    Data,
+    Attempts,
+    LastAttemptAt,
+    LastError,
+    DeliveredAt,
+    FailedAt,
+}
+
+/// The schema in which the job queue tables live
+#[derive(sea_query::Iden)]
+#[iden = "apalis"]
+pub struct Apalis;
+
+#[derive(sea_query::Iden)]
+pub enum Jobs {
+    Table,
+    Id,
+    Job,
+    JobType,
+    Status,
+    Attempts,
+    MaxAttempts,
+    RunAt,
+    LastError,
+    LockAt,
+    LockBy,
+    DoneAt,
+}
@@ -7,10 +7,29 @@
 //! A module containing the PostgreSQL implementation of the [`JobRepository`].
 
 use async_trait::async_trait;
-use mas_storage::job::{JobId, JobRepository, JobSubmission};
+use chrono::{DateTime, Utc};
+use mas_storage::{
+    job::{JobFilter, JobId, JobQueueStats, JobRepository, JobStatus, JobSubmission, QueuedJob},
+    pagination::PaginationDirection,
+    Clock, Page, Pagination,
+};
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
 use sqlx::PgConnection;
+use ulid::Ulid;
 
-use crate::{DatabaseError, ExecuteExt};
+use crate::{
+    filter::{Filter, StatementExt},
+    iden::{Apalis, Jobs},
+    DatabaseError, DatabaseInconsistencyError, ExecuteExt,
+};
+
+/// The prefix apalis adds to the ULID of jobs when storing their ID as text
+const JOB_ID_PREFIX: &str = "JID-";
+
+fn job_id_to_text(id: Ulid) -> String {
+    format!("{JOB_ID_PREFIX}{id}")
+}
 
 /// An implementation of [`JobRepository`] for a PostgreSQL connection.
 pub struct PgJobRepository<'c> {
@@ -25,6 +44,87 @@ impl<'c> PgJobRepository<'c> {
     }
 }
 
+#[derive(sqlx::FromRow)]
+struct JobLookup {
+    id: String,
+    job_type: String,
+    status: String,
+    job: serde_json::Value,
+    attempts: i32,
+    max_attempts: i32,
+    run_at: DateTime<Utc>,
+    last_error: Option<String>,
+    lock_at: Option<DateTime<Utc>>,
+    lock_by: Option<String>,
+    done_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<JobLookup> for QueuedJob {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(value: JobLookup) -> Result<Self, Self::Error> {
+        let id: Ulid = value
+            .id
+            .strip_prefix(JOB_ID_PREFIX)
+            .unwrap_or(&value.id)
+            .parse()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("apalis.jobs")
+                    .column("id")
+                    .source(e)
+            })?;
+
+        let status: JobStatus = value.status.parse().map_err(|e| {
+            DatabaseInconsistencyError::on("apalis.jobs")
+                .column("status")
+                .row(id)
+                .source(e)
+        })?;
+
+        let attempts = u32::try_from(value.attempts).map_err(|e| {
+            DatabaseInconsistencyError::on("apalis.jobs")
+                .column("attempts")
+                .row(id)
+                .source(e)
+        })?;
+
+        let max_attempts = u32::try_from(value.max_attempts).map_err(|e| {
+            DatabaseInconsistencyError::on("apalis.jobs")
+                .column("max_attempts")
+                .row(id)
+                .source(e)
+        })?;
+
+        Ok(QueuedJob {
+            id,
+            name: value.job_type,
+            status,
+            payload: value.job,
+            attempts,
+            max_attempts,
+            run_at: value.run_at,
+            last_error: value.last_error,
+            locked_by: value.lock_by,
+            locked_at: value.lock_at,
+            done_at: value.done_at,
+        })
+    }
+}
+
+impl Filter for JobFilter<'_> {
+    fn generate_condition(&self, _has_joins: bool) -> impl sea_query::IntoCondition {
+        sea_query::Condition::all()
+            .add_option(
+                self.name()
+                    .map(|name| Expr::col((Jobs::Table, Jobs::JobType)).eq(name)),
+            )
+            .add_option(
+                self.status()
+                    .map(|status| Expr::col((Jobs::Table, Jobs::Status)).eq(status.as_str())),
+            )
+    }
+}
+
 #[async_trait]
 impl<'c> JobRepository for PgJobRepository<'c> {
     type Error = DatabaseError;
@@ -49,12 +149,13 @@ impl<'c> JobRepository for PgJobRepository<'c> {
 
         let res = sqlx::query!(
             r#"
-                INSERT INTO apalis.jobs (job, id, job_type)
-                VALUES ($1::json, $2::text, $3::text)
+                INSERT INTO apalis.jobs (job, id, job_type, run_at)
+                VALUES ($1::json, $2::text, $3::text, COALESCE($4, NOW()))
             "#,
             submission.payload(),
             id.to_string(),
             submission.name(),
+            submission.run_at(),
         )
         .traced()
         .execute(&mut *self.conn)
@@ -64,4 +165,513 @@ impl<'c> JobRepository for PgJobRepository<'c> {
 
         Ok(id)
     }
+
+    #[tracing::instrument(
+        name = "db.job.lookup",
+        skip_all,
+        fields(
+            db.query.text,
+            job.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<QueuedJob>, Self::Error> {
+        let res = sqlx::query_as!(
+            JobLookup,
+            r#"
+                SELECT id
+                     , job_type
+                     , status
+                     , job
+                     , attempts
+                     , max_attempts
+                     , run_at
+                     , last_error
+                     , lock_at
+                     , lock_by
+                     , done_at
+                FROM apalis.jobs
+                WHERE id = $1
+            "#,
+            job_id_to_text(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else { return Ok(None) };
+
+        Ok(Some(res.try_into()?))
+    }
+
+    #[tracing::instrument(
+        name = "db.job.list",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn list(
+        &mut self,
+        filter: JobFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<QueuedJob>, Self::Error> {
+        let mut query = Query::select();
+        query
+            .columns([
+                Jobs::Id,
+                Jobs::JobType,
+                Jobs::Status,
+                Jobs::Job,
+                Jobs::Attempts,
+                Jobs::MaxAttempts,
+                Jobs::RunAt,
+                Jobs::LastError,
+                Jobs::LockAt,
+                Jobs::LockBy,
+                Jobs::DoneAt,
+            ])
+            .from((Apalis, Jobs::Table))
+            .apply_filter(filter);
+
+        // The IDs are stored as text, so we can't use the generic pagination
+        // helper. ULIDs sort lexicographically, so this still works as long as
+        // they share the same prefix.
+        let id = Expr::col((Jobs::Table, Jobs::Id));
+        if let Some(after) = pagination.after {
+            query.and_where(id.clone().gt(job_id_to_text(after)));
+        }
+        if let Some(before) = pagination.before {
+            query.and_where(id.lt(job_id_to_text(before)));
+        }
+        let order = match pagination.direction {
+            PaginationDirection::Forward => sea_query::Order::Asc,
+            PaginationDirection::Backward => sea_query::Order::Desc,
+        };
+        query
+            .order_by((Jobs::Table, Jobs::Id), order)
+            .limit((pagination.count + 1) as u64);
+
+        let (sql, arguments) = query.build_sqlx(PostgresQueryBuilder);
+
+        let edges: Vec<JobLookup> = sqlx::query_as_with(&sql, arguments)
+            .traced()
+            .fetch_all(&mut *self.conn)
+            .await?;
+
+        let page = pagination.process(edges).try_map(TryInto::try_into)?;
+
+        Ok(page)
+    }
+
+    #[tracing::instrument(
+        name = "db.job.count",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn count(&mut self, filter: JobFilter<'_>) -> Result<usize, Self::Error> {
+        let (sql, arguments) = Query::select()
+            .expr(Expr::col((Jobs::Table, Jobs::Id)).count())
+            .from((Apalis, Jobs::Table))
+            .apply_filter(filter)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: i64 = sqlx::query_scalar_with(&sql, arguments)
+            .traced()
+            .fetch_one(&mut *self.conn)
+            .await?;
+
+        count
+            .try_into()
+            .map_err(DatabaseError::to_invalid_operation)
+    }
+
+    #[tracing::instrument(
+        name = "db.job.stats",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn stats(&mut self, clock: &dyn Clock) -> Result<Vec<JobQueueStats>, Self::Error> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT job_type
+                     , status
+                     , COUNT(*) AS "count!"
+                     , COUNT(*) FILTER (WHERE attempts > 0) AS "retried!"
+                     , MIN(run_at) FILTER (WHERE run_at <= $1) AS oldest_due_at
+                FROM apalis.jobs
+                WHERE status <> 'Done'
+                GROUP BY job_type, status
+                ORDER BY job_type, status
+            "#,
+            clock.now(),
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let status: JobStatus = row.status.parse().map_err(|e| {
+                    DatabaseInconsistencyError::on("apalis.jobs")
+                        .column("status")
+                        .source(e)
+                })?;
+
+                Ok(JobQueueStats {
+                    name: row.job_type,
+                    status,
+                    count: row
+                        .count
+                        .try_into()
+                        .map_err(DatabaseError::to_invalid_operation)?,
+                    retried: row
+                        .retried
+                        .try_into()
+                        .map_err(DatabaseError::to_invalid_operation)?,
+                    oldest_due_at: row.oldest_due_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(
+        name = "db.job.retry",
+        skip_all,
+        fields(
+            db.query.text,
+            job.id = %job.id,
+            job.name = job.name,
+        ),
+        err,
+    )]
+    async fn retry(&mut self, clock: &dyn Clock, job: QueuedJob) -> Result<QueuedJob, Self::Error> {
+        let run_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE apalis.jobs
+                SET status = 'Pending'
+                  , attempts = 0
+                  , run_at = $2
+                  , lock_at = NULL
+                  , lock_by = NULL
+                  , done_at = NULL
+                WHERE id = $1
+                  AND status IN ('Failed', 'Killed')
+            "#,
+            job_id_to_text(job.id),
+            run_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(QueuedJob {
+            status: JobStatus::Pending,
+            attempts: 0,
+            run_at,
+            locked_at: None,
+            locked_by: None,
+            done_at: None,
+            ..job
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.job.cancel",
+        skip_all,
+        fields(
+            db.query.text,
+            job.id = %job.id,
+            job.name = job.name,
+        ),
+        err,
+    )]
+    async fn cancel(
+        &mut self,
+        clock: &dyn Clock,
+        job: QueuedJob,
+    ) -> Result<QueuedJob, Self::Error> {
+        let done_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE apalis.jobs
+                SET status = 'Killed'
+                  , done_at = $2
+                WHERE id = $1
+                  AND status IN ('Pending', 'Retry')
+            "#,
+            job_id_to_text(job.id),
+            done_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(QueuedJob {
+            status: JobStatus::Killed,
+            done_at: Some(done_at),
+            ..job
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use mas_storage::{
+        clock::MockClock,
+        job::{
+            schedule_backchannel_logout_jobs, JobFilter, JobRepository, JobStatus, JobSubmission,
+            ProvisionUserJob,
+        },
+        oauth2::{OAuth2SessionFilter, OAuth2SessionRepository},
+        Clock, Pagination, RepositoryAccess,
+    };
+    use oauth2_types::{
+        requests::GrantType,
+        scope::{Scope, OPENID},
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use sqlx::PgPool;
+    use ulid::Ulid;
+    use url::Url;
+
+    use crate::PgRepository;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_job_repository(pool: PgPool) {
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let all = JobFilter::new();
+        assert_eq!(repo.job().count(all).await.unwrap(), 0);
+        assert!(repo.job().stats(&clock).await.unwrap().is_empty());
+
+        let first = repo
+            .job()
+            .schedule_submission(
+                JobSubmission::new(ProvisionUserJob::new_for_id(Ulid::nil()))
+                    .with_run_at(clock.now()),
+            )
+            .await
+            .unwrap();
+        let second = repo
+            .job()
+            .schedule_submission(
+                JobSubmission::new(ProvisionUserJob::new_for_id(Ulid::nil()))
+                    .with_run_at(clock.now()),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Simulate the first job running out of attempts
+        sqlx::query(
+            "UPDATE apalis.jobs SET status = 'Failed', attempts = 25, last_error = 'boom' WHERE id = $1",
+        )
+        .bind(first.to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+        assert_eq!(repo.job().count(all).await.unwrap(), 2);
+
+        let failed = JobFilter::new().with_status(JobStatus::Failed);
+        assert_eq!(repo.job().count(failed).await.unwrap(), 1);
+        let by_name = JobFilter::new().for_name("provision-user");
+        assert_eq!(repo.job().count(by_name).await.unwrap(), 2);
+        let other_name = JobFilter::new().for_name("sync-devices");
+        assert_eq!(repo.job().count(other_name).await.unwrap(), 0);
+
+        let page = repo
+            .job()
+            .list(failed, Pagination::first(10))
+            .await
+            .unwrap();
+        assert_eq!(page.edges.len(), 1);
+        let job = page.edges[0].clone();
+        assert_eq!(job.name, "provision-user");
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.attempts, 25);
+        assert_eq!(job.last_error.as_deref(), Some("boom"));
+        assert_eq!(job.payload["user_id"], Ulid::nil().to_string());
+
+        let lookup = repo.job().lookup(job.id).await.unwrap().unwrap();
+        assert_eq!(lookup, job);
+
+        // Pagination goes through all the jobs
+        let page = repo.job().list(all, Pagination::first(1)).await.unwrap();
+        assert_eq!(page.edges.len(), 1);
+        assert!(page.has_next_page);
+        let next = repo
+            .job()
+            .list(all, Pagination::first(1).after(page.edges[0].id))
+            .await
+            .unwrap();
+        assert_eq!(next.edges.len(), 1);
+        assert!(!next.has_next_page);
+        assert_ne!(page.edges[0].id, next.edges[0].id);
+
+        let stats = repo.job().stats(&clock).await.unwrap();
+        assert_eq!(stats.len(), 2);
+        let pending = stats
+            .iter()
+            .find(|s| s.status == JobStatus::Pending)
+            .unwrap();
+        assert_eq!(pending.name, "provision-user");
+        assert_eq!(pending.count, 1);
+        assert_eq!(pending.retried, 0);
+        assert_eq!(pending.oldest_due_at, Some(clock.now()));
+        let failed_stats = stats
+            .iter()
+            .find(|s| s.status == JobStatus::Failed)
+            .unwrap();
+        assert_eq!(failed_stats.count, 1);
+        assert_eq!(failed_stats.retried, 1);
+
+        // A pending job can't be retried, but can be cancelled
+        let pending_job = repo
+            .job()
+            .list(
+                JobFilter::new().with_status(JobStatus::Pending),
+                Pagination::first(1),
+            )
+            .await
+            .unwrap()
+            .edges
+            .remove(0);
+        assert!(repo.job().retry(&clock, pending_job.clone()).await.is_err());
+        let cancelled = repo.job().cancel(&clock, pending_job).await.unwrap();
+        assert_eq!(cancelled.status, JobStatus::Killed);
+        assert_eq!(cancelled.done_at, Some(clock.now()));
+
+        // A failed job can be retried, which resets its attempts
+        clock.advance(Duration::microseconds(60 * 1000 * 1000));
+        let retried = repo.job().retry(&clock, job).await.unwrap();
+        assert_eq!(retried.status, JobStatus::Pending);
+        assert_eq!(retried.attempts, 0);
+        assert_eq!(retried.run_at, clock.now());
+        // The last error is kept for reference
+        assert_eq!(retried.last_error.as_deref(), Some("boom"));
+        let lookup = repo.job().lookup(retried.id).await.unwrap().unwrap();
+        assert_eq!(lookup, retried);
+
+        // Cancelled jobs can be retried too
+        let cancelled = repo.job().lookup(cancelled.id).await.unwrap().unwrap();
+        assert_eq!(cancelled.status, JobStatus::Killed);
+        assert!(second.to_string().ends_with(&cancelled.id.to_string()));
+        repo.job().retry(&clock, cancelled).await.unwrap();
+
+        assert!(repo.job().lookup(Ulid::nil()).await.unwrap().is_none());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_schedule_backchannel_logout_jobs(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "john".to_owned())
+            .await
+            .unwrap();
+        let user_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+
+        // One client registered a back-channel logout URI, the other didn't
+        let mut clients = Vec::new();
+        for backchannel_logout_uri in [Some("https://example.com/logout"), None] {
+            let client = repo
+                .oauth2_client()
+                .add(
+                    &mut rng,
+                    &clock,
+                    vec!["https://example.com/redirect".parse().unwrap()],
+                    None,
+                    None,
+                    vec![GrantType::AuthorizationCode],
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Vec::new(),
+                    backchannel_logout_uri.map(|uri| Url::parse(uri).unwrap()),
+                    false,
+                    false,
+                    None,
+                    false,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            clients.push(client);
+        }
+
+        // Two sessions with the first client, one with the second
+        for client in [&clients[0], &clients[0], &clients[1]] {
+            repo.oauth2_session()
+                .add_from_browser_session(
+                    &mut rng,
+                    &clock,
+                    client,
+                    &user_session,
+                    Scope::from_iter([OPENID]),
+                )
+                .await
+                .unwrap();
+        }
+
+        let filter = OAuth2SessionFilter::new().for_user(&user).active_only();
+        let scheduled = schedule_backchannel_logout_jobs(&mut repo, filter)
+            .await
+            .unwrap();
+        assert_eq!(scheduled, 2);
+
+        let by_name = JobFilter::new().for_name("send-backchannel-logout");
+        assert_eq!(repo.job().count(by_name).await.unwrap(), 2);
+
+        // Only active sessions are matched, so nothing is scheduled once they ended
+        let page = repo
+            .oauth2_session()
+            .list(filter, Pagination::first(10))
+            .await
+            .unwrap();
+        for session in page.edges {
+            repo.oauth2_session().finish(&clock, session).await.unwrap();
+        }
+        let scheduled = schedule_backchannel_logout_jobs(&mut repo, filter)
+            .await
+            .unwrap();
+        assert_eq!(scheduled, 0);
+        assert_eq!(repo.job().count(by_name).await.unwrap(), 2);
+    }
 }
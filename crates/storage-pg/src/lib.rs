@@ -163,11 +163,13 @@
 use sqlx::migrate::Migrator;
 
 pub mod app_session;
+pub mod audit;
 pub mod compat;
 pub mod job;
 pub mod oauth2;
 pub mod upstream_oauth2;
 pub mod user;
+pub mod webhook;
 
 mod errors;
 pub(crate) mod filter;
@@ -36,6 +36,7 @@ struct OAuth2AccessTokenLookup {
     created_at: DateTime<Utc>,
     expires_at: Option<DateTime<Utc>>,
     revoked_at: Option<DateTime<Utc>>,
+    dpop_jkt: Option<String>,
 }
 
 impl From<OAuth2AccessTokenLookup> for AccessToken {
@@ -52,6 +53,7 @@ impl From<OAuth2AccessTokenLookup> for AccessToken {
             access_token: value.access_token,
             created_at: value.created_at,
             expires_at: value.expires_at,
+            dpop_jkt: value.dpop_jkt,
         }
     }
 }
@@ -70,6 +72,7 @@ impl<'c> OAuth2AccessTokenRepository for PgOAuth2AccessTokenRepository<'c> {
                      , expires_at
                      , revoked_at
                      , oauth2_session_id
+                     , dpop_jkt
 
                 FROM oauth2_access_tokens
 
@@ -106,6 +109,7 @@ impl<'c> OAuth2AccessTokenRepository for PgOAuth2AccessTokenRepository<'c> {
                      , expires_at
                      , revoked_at
                      , oauth2_session_id
+                     , dpop_jkt
 
                 FROM oauth2_access_tokens
 
@@ -139,6 +143,7 @@ impl<'c> OAuth2AccessTokenRepository for PgOAuth2AccessTokenRepository<'c> {
         session: &Session,
         access_token: String,
         expires_after: Option<Duration>,
+        dpop_jkt: Option<String>,
     ) -> Result<AccessToken, Self::Error> {
         let created_at = clock.now();
         let expires_at = expires_after.map(|d| created_at + d);
@@ -149,15 +154,17 @@ impl<'c> OAuth2AccessTokenRepository for PgOAuth2AccessTokenRepository<'c> {
         sqlx::query!(
             r#"
                 INSERT INTO oauth2_access_tokens
-                    (oauth2_access_token_id, oauth2_session_id, access_token, created_at, expires_at)
+                    (oauth2_access_token_id, oauth2_session_id, access_token, created_at, expires_at,
+                     dpop_jkt)
                 VALUES
-                    ($1, $2, $3, $4, $5)
+                    ($1, $2, $3, $4, $5, $6)
             "#,
             Uuid::from(id),
             Uuid::from(session.id),
             &access_token,
             created_at,
             expires_at,
+            dpop_jkt.as_deref(),
         )
             .traced()
         .execute(&mut *self.conn)
@@ -170,6 +177,7 @@ impl<'c> OAuth2AccessTokenRepository for PgOAuth2AccessTokenRepository<'c> {
             session_id: session.id,
             created_at,
             expires_at,
+            dpop_jkt,
         })
     }
 
@@ -505,4 +505,38 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
 
         Ok(grant)
     }
+
+    #[tracing::instrument(
+        name = "db.oauth2_authorization_grant.cleanup_stale",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_stale(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM oauth2_authorization_grants
+                WHERE oauth2_authorization_grant_id IN (
+                    SELECT oauth2_authorization_grant_id
+                    FROM oauth2_authorization_grants
+                    WHERE created_at < $1
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+            "#,
+            before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
 }
@@ -12,9 +12,15 @@ use std::{
 
 use async_trait::async_trait;
 use mas_data_model::{Client, JwksOrJwksUri, User};
-use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
+use mas_iana::{
+    jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg},
+    oauth::OAuthClientAuthenticationMethod,
+};
 use mas_jose::jwk::PublicJsonWebKeySet;
-use mas_storage::{oauth2::OAuth2ClientRepository, Clock};
+use mas_storage::{
+    oauth2::{OAuth2ClientFilter, OAuth2ClientRepository},
+    Clock, Page, Pagination,
+};
 use oauth2_types::{
     oidc::ApplicationType,
     requests::GrantType,
@@ -22,13 +28,21 @@ use oauth2_types::{
 };
 use opentelemetry_semantic_conventions::attribute::DB_QUERY_TEXT;
 use rand::RngCore;
+use sea_query::{enum_def, Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
 use sqlx::PgConnection;
 use tracing::{info_span, Instrument};
 use ulid::Ulid;
 use url::Url;
 use uuid::Uuid;
 
-use crate::{tracing::ExecuteExt, DatabaseError, DatabaseInconsistencyError};
+use crate::{
+    filter::{Filter, StatementExt},
+    iden::OAuth2Clients,
+    pagination::QueryBuilderExt,
+    tracing::ExecuteExt,
+    DatabaseError, DatabaseInconsistencyError,
+};
 
 /// An implementation of [`OAuth2ClientRepository`] for a PostgreSQL connection
 pub struct PgOAuth2ClientRepository<'c> {
@@ -44,7 +58,8 @@ impl<'c> PgOAuth2ClientRepository<'c> {
 }
 
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Debug)]
+#[derive(Debug, sqlx::FromRow)]
+#[enum_def]
 struct OAuth2ClientLookup {
     oauth2_client_id: Uuid,
     encrypted_client_secret: Option<String>,
@@ -54,6 +69,8 @@ struct OAuth2ClientLookup {
     grant_type_refresh_token: bool,
     grant_type_client_credentials: bool,
     grant_type_device_code: bool,
+    grant_type_token_exchange: bool,
+    grant_type_jwt_bearer: bool,
     client_name: Option<String>,
     logo_uri: Option<String>,
     client_uri: Option<String>,
@@ -66,6 +83,17 @@ struct OAuth2ClientLookup {
     token_endpoint_auth_method: Option<String>,
     token_endpoint_auth_signing_alg: Option<String>,
     initiate_login_uri: Option<String>,
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
+    backchannel_logout_session_required: bool,
+    require_pushed_authorization_requests: bool,
+    request_object_signing_alg: Option<String>,
+    require_signed_request_object: bool,
+    id_token_encrypted_response_alg: Option<String>,
+    id_token_encrypted_response_enc: Option<String>,
+    userinfo_encrypted_response_alg: Option<String>,
+    userinfo_encrypted_response_enc: Option<String>,
+    is_static: bool,
 }
 
 impl TryInto<Client> for OAuth2ClientLookup {
@@ -84,6 +112,18 @@ impl TryInto<Client> for OAuth2ClientLookup {
                 .source(e)
         })?;
 
+        let post_logout_redirect_uris: Result<Vec<Url>, _> = self
+            .post_logout_redirect_uris
+            .iter()
+            .map(|s| s.parse())
+            .collect();
+        let post_logout_redirect_uris = post_logout_redirect_uris.map_err(|e| {
+            DatabaseInconsistencyError::on("oauth2_clients")
+                .column("post_logout_redirect_uris")
+                .row(id)
+                .source(e)
+        })?;
+
         let application_type = self
             .application_type
             .map(|s| s.parse())
@@ -108,6 +148,12 @@ impl TryInto<Client> for OAuth2ClientLookup {
         if self.grant_type_device_code {
             grant_types.push(GrantType::DeviceCode);
         }
+        if self.grant_type_token_exchange {
+            grant_types.push(GrantType::TokenExchange);
+        }
+        if self.grant_type_jwt_bearer {
+            grant_types.push(GrantType::JwtBearer);
+        }
 
         let logo_uri = self.logo_uri.map(|s| s.parse()).transpose().map_err(|e| {
             DatabaseInconsistencyError::on("oauth2_clients")
@@ -189,6 +235,61 @@ impl TryInto<Client> for OAuth2ClientLookup {
                     .source(e)
             })?;
 
+        let request_object_signing_alg = self
+            .request_object_signing_alg
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("request_object_signing_alg")
+                    .row(id)
+                    .source(e)
+            })?;
+
+        let id_token_encrypted_response_alg = self
+            .id_token_encrypted_response_alg
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("id_token_encrypted_response_alg")
+                    .row(id)
+                    .source(e)
+            })?;
+
+        let id_token_encrypted_response_enc = self
+            .id_token_encrypted_response_enc
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("id_token_encrypted_response_enc")
+                    .row(id)
+                    .source(e)
+            })?;
+
+        let userinfo_encrypted_response_alg = self
+            .userinfo_encrypted_response_alg
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("userinfo_encrypted_response_alg")
+                    .row(id)
+                    .source(e)
+            })?;
+
+        let userinfo_encrypted_response_enc = self
+            .userinfo_encrypted_response_enc
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("userinfo_encrypted_response_enc")
+                    .row(id)
+                    .source(e)
+            })?;
+
         let initiate_login_uri = self
             .initiate_login_uri
             .map(|s| s.parse())
@@ -200,6 +301,17 @@ impl TryInto<Client> for OAuth2ClientLookup {
                     .source(e)
             })?;
 
+        let backchannel_logout_uri = self
+            .backchannel_logout_uri
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("backchannel_logout_uri")
+                    .row(id)
+                    .source(e)
+            })?;
+
         let jwks = match (self.jwks, self.jwks_uri) {
             (None, None) => None,
             (Some(jwks), None) => {
@@ -246,10 +358,29 @@ impl TryInto<Client> for OAuth2ClientLookup {
             token_endpoint_auth_method,
             token_endpoint_auth_signing_alg,
             initiate_login_uri,
+            post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required: self.backchannel_logout_session_required,
+            require_pushed_authorization_requests: self.require_pushed_authorization_requests,
+            request_object_signing_alg,
+            require_signed_request_object: self.require_signed_request_object,
+            id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc,
+            is_static: self.is_static,
         })
     }
 }
 
+impl Filter for OAuth2ClientFilter<'_> {
+    fn generate_condition(&self, _has_joins: bool) -> impl sea_query::IntoCondition {
+        sea_query::Condition::all().add_option(self.is_static().map(|is_static| {
+            Expr::col((OAuth2Clients::Table, OAuth2Clients::IsStatic)).eq(is_static)
+        }))
+    }
+}
+
 #[async_trait]
 impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
     type Error = DatabaseError;
@@ -275,6 +406,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , grant_type_refresh_token
                      , grant_type_client_credentials
                      , grant_type_device_code
+                     , grant_type_token_exchange
+                     , grant_type_jwt_bearer
                      , client_name
                      , logo_uri
                      , client_uri
@@ -287,6 +420,17 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , token_endpoint_auth_method
                      , token_endpoint_auth_signing_alg
                      , initiate_login_uri
+                     , post_logout_redirect_uris
+                     , backchannel_logout_uri
+                     , backchannel_logout_session_required
+                     , require_pushed_authorization_requests
+                     , request_object_signing_alg
+                     , require_signed_request_object
+                     , id_token_encrypted_response_alg
+                     , id_token_encrypted_response_enc
+                     , userinfo_encrypted_response_alg
+                     , userinfo_encrypted_response_enc
+                     , is_static
                 FROM oauth2_clients c
 
                 WHERE oauth2_client_id = $1
@@ -326,6 +470,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , grant_type_refresh_token
                      , grant_type_client_credentials
                      , grant_type_device_code
+                     , grant_type_token_exchange
+                     , grant_type_jwt_bearer
                      , client_name
                      , logo_uri
                      , client_uri
@@ -338,6 +484,17 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , token_endpoint_auth_method
                      , token_endpoint_auth_signing_alg
                      , initiate_login_uri
+                     , post_logout_redirect_uris
+                     , backchannel_logout_uri
+                     , backchannel_logout_session_required
+                     , require_pushed_authorization_requests
+                     , request_object_signing_alg
+                     , require_signed_request_object
+                     , id_token_encrypted_response_alg
+                     , id_token_encrypted_response_enc
+                     , userinfo_encrypted_response_alg
+                     , userinfo_encrypted_response_enc
+                     , is_static
                 FROM oauth2_clients c
 
                 WHERE oauth2_client_id = ANY($1::uuid[])
@@ -388,6 +545,16 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
         token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
         token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
         initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        require_signed_request_object: bool,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
     ) -> Result<Client, Self::Error> {
         let now = clock.now();
         let id = Ulid::from_datetime_with_source(now.into(), rng);
@@ -400,6 +567,10 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             .map_err(DatabaseError::to_invalid_operation)?;
 
         let redirect_uris_array = redirect_uris.iter().map(Url::to_string).collect::<Vec<_>>();
+        let post_logout_redirect_uris_array = post_logout_redirect_uris
+            .iter()
+            .map(Url::to_string)
+            .collect::<Vec<_>>();
 
         sqlx::query!(
             r#"
@@ -412,6 +583,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                     , grant_type_refresh_token
                     , grant_type_client_credentials
                     , grant_type_device_code
+                    , grant_type_token_exchange
+                    , grant_type_jwt_bearer
                     , client_name
                     , logo_uri
                     , client_uri
@@ -424,10 +597,20 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                     , token_endpoint_auth_method
                     , token_endpoint_auth_signing_alg
                     , initiate_login_uri
+                    , post_logout_redirect_uris
+                    , backchannel_logout_uri
+                    , backchannel_logout_session_required
+                    , require_pushed_authorization_requests
+                    , request_object_signing_alg
+                    , require_signed_request_object
+                    , id_token_encrypted_response_alg
+                    , id_token_encrypted_response_enc
+                    , userinfo_encrypted_response_alg
+                    , userinfo_encrypted_response_enc
                     , is_static
                     )
                 VALUES
-                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, FALSE)
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, FALSE)
             "#,
             Uuid::from(id),
             encrypted_client_secret,
@@ -437,6 +620,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             grant_types.contains(&GrantType::RefreshToken),
             grant_types.contains(&GrantType::ClientCredentials),
             grant_types.contains(&GrantType::DeviceCode),
+            grant_types.contains(&GrantType::TokenExchange),
+            grant_types.contains(&GrantType::JwtBearer),
             client_name,
             logo_uri.as_ref().map(Url::as_str),
             client_uri.as_ref().map(Url::as_str),
@@ -455,6 +640,24 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                 .as_ref()
                 .map(ToString::to_string),
             initiate_login_uri.as_ref().map(Url::as_str),
+            &post_logout_redirect_uris_array,
+            backchannel_logout_uri.as_ref().map(Url::as_str),
+            backchannel_logout_session_required,
+            require_pushed_authorization_requests,
+            request_object_signing_alg.as_ref().map(ToString::to_string),
+            require_signed_request_object,
+            id_token_encrypted_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            id_token_encrypted_response_enc
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_encrypted_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_encrypted_response_enc
+                .as_ref()
+                .map(ToString::to_string),
         )
         .traced()
         .execute(&mut *self.conn)
@@ -485,6 +688,17 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             token_endpoint_auth_method,
             token_endpoint_auth_signing_alg,
             initiate_login_uri,
+            post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
+            require_pushed_authorization_requests,
+            request_object_signing_alg,
+            require_signed_request_object,
+            id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc,
+            is_static: false,
         })
     }
 
@@ -505,6 +719,16 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
         jwks: Option<PublicJsonWebKeySet>,
         jwks_uri: Option<Url>,
         redirect_uris: Vec<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        require_signed_request_object: bool,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
     ) -> Result<Client, Self::Error> {
         let jwks_json = jwks
             .as_ref()
@@ -512,8 +736,11 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             .transpose()
             .map_err(DatabaseError::to_invalid_operation)?;
 
-        let client_auth_method = client_auth_method.to_string();
         let redirect_uris_array = redirect_uris.iter().map(Url::to_string).collect::<Vec<_>>();
+        let post_logout_redirect_uris_array = post_logout_redirect_uris
+            .iter()
+            .map(Url::to_string)
+            .collect::<Vec<_>>();
 
         sqlx::query!(
             r#"
@@ -525,13 +752,25 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                     , grant_type_refresh_token
                     , grant_type_client_credentials
                     , grant_type_device_code
+                    , grant_type_token_exchange
+                    , grant_type_jwt_bearer
                     , token_endpoint_auth_method
                     , jwks
                     , jwks_uri
+                    , post_logout_redirect_uris
+                    , backchannel_logout_uri
+                    , backchannel_logout_session_required
+                    , require_pushed_authorization_requests
+                    , request_object_signing_alg
+                    , require_signed_request_object
+                    , id_token_encrypted_response_alg
+                    , id_token_encrypted_response_enc
+                    , userinfo_encrypted_response_alg
+                    , userinfo_encrypted_response_enc
                     , is_static
                     )
                 VALUES
-                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, TRUE)
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, TRUE)
                 ON CONFLICT (oauth2_client_id)
                 DO
                     UPDATE SET encrypted_client_secret = EXCLUDED.encrypted_client_secret
@@ -540,9 +779,21 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                              , grant_type_refresh_token = EXCLUDED.grant_type_refresh_token
                              , grant_type_client_credentials = EXCLUDED.grant_type_client_credentials
                              , grant_type_device_code = EXCLUDED.grant_type_device_code
+                             , grant_type_token_exchange = EXCLUDED.grant_type_token_exchange
+                             , grant_type_jwt_bearer = EXCLUDED.grant_type_jwt_bearer
                              , token_endpoint_auth_method = EXCLUDED.token_endpoint_auth_method
                              , jwks = EXCLUDED.jwks
                              , jwks_uri = EXCLUDED.jwks_uri
+                             , post_logout_redirect_uris = EXCLUDED.post_logout_redirect_uris
+                             , backchannel_logout_uri = EXCLUDED.backchannel_logout_uri
+                             , backchannel_logout_session_required = EXCLUDED.backchannel_logout_session_required
+                             , require_pushed_authorization_requests = EXCLUDED.require_pushed_authorization_requests
+                             , request_object_signing_alg = EXCLUDED.request_object_signing_alg
+                             , require_signed_request_object = EXCLUDED.require_signed_request_object
+                             , id_token_encrypted_response_alg = EXCLUDED.id_token_encrypted_response_alg
+                             , id_token_encrypted_response_enc = EXCLUDED.id_token_encrypted_response_enc
+                             , userinfo_encrypted_response_alg = EXCLUDED.userinfo_encrypted_response_alg
+                             , userinfo_encrypted_response_enc = EXCLUDED.userinfo_encrypted_response_enc
                              , is_static = TRUE
             "#,
             Uuid::from(client_id),
@@ -552,9 +803,29 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             true,
             true,
             true,
-            client_auth_method,
+            true,
+            true,
+            client_auth_method.to_string(),
             jwks_json,
             jwks_uri.as_ref().map(Url::as_str),
+            &post_logout_redirect_uris_array,
+            backchannel_logout_uri.as_ref().map(Url::as_str),
+            backchannel_logout_session_required,
+            require_pushed_authorization_requests,
+            request_object_signing_alg.as_ref().map(ToString::to_string),
+            require_signed_request_object,
+            id_token_encrypted_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            id_token_encrypted_response_enc
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_encrypted_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_encrypted_response_enc
+                .as_ref()
+                .map(ToString::to_string),
         )
         .traced()
         .execute(&mut *self.conn)
@@ -577,6 +848,9 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                 GrantType::AuthorizationCode,
                 GrantType::RefreshToken,
                 GrantType::ClientCredentials,
+                GrantType::DeviceCode,
+                GrantType::TokenExchange,
+                GrantType::JwtBearer,
             ],
             client_name: None,
             logo_uri: None,
@@ -586,9 +860,20 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             jwks,
             id_token_signed_response_alg: None,
             userinfo_signed_response_alg: None,
-            token_endpoint_auth_method: None,
+            token_endpoint_auth_method: Some(client_auth_method),
             token_endpoint_auth_signing_alg: None,
             initiate_login_uri: None,
+            post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
+            require_pushed_authorization_requests,
+            request_object_signing_alg,
+            require_signed_request_object,
+            id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc,
+            is_static: true,
         })
     }
 
@@ -612,6 +897,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , grant_type_refresh_token
                      , grant_type_client_credentials
                      , grant_type_device_code
+                     , grant_type_token_exchange
+                     , grant_type_jwt_bearer
                      , client_name
                      , logo_uri
                      , client_uri
@@ -624,6 +911,17 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , token_endpoint_auth_method
                      , token_endpoint_auth_signing_alg
                      , initiate_login_uri
+                     , post_logout_redirect_uris
+                     , backchannel_logout_uri
+                     , backchannel_logout_session_required
+                     , require_pushed_authorization_requests
+                     , request_object_signing_alg
+                     , require_signed_request_object
+                     , id_token_encrypted_response_alg
+                     , id_token_encrypted_response_enc
+                     , userinfo_encrypted_response_alg
+                     , userinfo_encrypted_response_enc
+                     , is_static
                 FROM oauth2_clients c
                 WHERE is_static = TRUE
             "#,
@@ -637,6 +935,349 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             .collect()
     }
 
+    #[tracing::instrument(
+        name = "db.oauth2_client.list",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn list(
+        &mut self,
+        filter: OAuth2ClientFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<Client>, Self::Error> {
+        let (sql, arguments) = Query::select()
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::OAuth2ClientId)),
+                OAuth2ClientLookupIden::Oauth2ClientId,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::EncryptedClientSecret)),
+                OAuth2ClientLookupIden::EncryptedClientSecret,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::ApplicationType)),
+                OAuth2ClientLookupIden::ApplicationType,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::RedirectUris)),
+                OAuth2ClientLookupIden::RedirectUris,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::GrantTypeAuthorizationCode,
+                )),
+                OAuth2ClientLookupIden::GrantTypeAuthorizationCode,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::GrantTypeRefreshToken)),
+                OAuth2ClientLookupIden::GrantTypeRefreshToken,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::GrantTypeClientCredentials,
+                )),
+                OAuth2ClientLookupIden::GrantTypeClientCredentials,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::GrantTypeDeviceCode)),
+                OAuth2ClientLookupIden::GrantTypeDeviceCode,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::GrantTypeTokenExchange)),
+                OAuth2ClientLookupIden::GrantTypeTokenExchange,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::GrantTypeJwtBearer)),
+                OAuth2ClientLookupIden::GrantTypeJwtBearer,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::ClientName)),
+                OAuth2ClientLookupIden::ClientName,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::LogoUri)),
+                OAuth2ClientLookupIden::LogoUri,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::ClientUri)),
+                OAuth2ClientLookupIden::ClientUri,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::PolicyUri)),
+                OAuth2ClientLookupIden::PolicyUri,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::TosUri)),
+                OAuth2ClientLookupIden::TosUri,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::JwksUri)),
+                OAuth2ClientLookupIden::JwksUri,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::Jwks)),
+                OAuth2ClientLookupIden::Jwks,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::IdTokenSignedResponseAlg,
+                )),
+                OAuth2ClientLookupIden::IdTokenSignedResponseAlg,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::UserinfoSignedResponseAlg,
+                )),
+                OAuth2ClientLookupIden::UserinfoSignedResponseAlg,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::TokenEndpointAuthMethod)),
+                OAuth2ClientLookupIden::TokenEndpointAuthMethod,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::TokenEndpointAuthSigningAlg,
+                )),
+                OAuth2ClientLookupIden::TokenEndpointAuthSigningAlg,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::InitiateLoginUri)),
+                OAuth2ClientLookupIden::InitiateLoginUri,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::PostLogoutRedirectUris)),
+                OAuth2ClientLookupIden::PostLogoutRedirectUris,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::BackchannelLogoutUri)),
+                OAuth2ClientLookupIden::BackchannelLogoutUri,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::BackchannelLogoutSessionRequired,
+                )),
+                OAuth2ClientLookupIden::BackchannelLogoutSessionRequired,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::RequirePushedAuthorizationRequests,
+                )),
+                OAuth2ClientLookupIden::RequirePushedAuthorizationRequests,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::RequestObjectSigningAlg)),
+                OAuth2ClientLookupIden::RequestObjectSigningAlg,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::RequireSignedRequestObject,
+                )),
+                OAuth2ClientLookupIden::RequireSignedRequestObject,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::IdTokenEncryptedResponseAlg,
+                )),
+                OAuth2ClientLookupIden::IdTokenEncryptedResponseAlg,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::IdTokenEncryptedResponseEnc,
+                )),
+                OAuth2ClientLookupIden::IdTokenEncryptedResponseEnc,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::UserinfoEncryptedResponseAlg,
+                )),
+                OAuth2ClientLookupIden::UserinfoEncryptedResponseAlg,
+            )
+            .expr_as(
+                Expr::col((
+                    OAuth2Clients::Table,
+                    OAuth2Clients::UserinfoEncryptedResponseEnc,
+                )),
+                OAuth2ClientLookupIden::UserinfoEncryptedResponseEnc,
+            )
+            .expr_as(
+                Expr::col((OAuth2Clients::Table, OAuth2Clients::IsStatic)),
+                OAuth2ClientLookupIden::IsStatic,
+            )
+            .from(OAuth2Clients::Table)
+            .apply_filter(filter)
+            .generate_pagination(
+                (OAuth2Clients::Table, OAuth2Clients::OAuth2ClientId),
+                pagination,
+            )
+            .build_sqlx(PostgresQueryBuilder);
+
+        let edges: Vec<OAuth2ClientLookup> = sqlx::query_as_with(&sql, arguments)
+            .traced()
+            .fetch_all(&mut *self.conn)
+            .await?;
+
+        let page = pagination.process(edges).try_map(TryInto::try_into)?;
+
+        Ok(page)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_client.count",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn count(&mut self, filter: OAuth2ClientFilter<'_>) -> Result<usize, Self::Error> {
+        let (sql, arguments) = Query::select()
+            .expr(Expr::col((OAuth2Clients::Table, OAuth2Clients::OAuth2ClientId)).count())
+            .from(OAuth2Clients::Table)
+            .apply_filter(filter)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: i64 = sqlx::query_scalar_with(&sql, arguments)
+            .traced()
+            .fetch_one(&mut *self.conn)
+            .await?;
+
+        count
+            .try_into()
+            .map_err(DatabaseError::to_invalid_operation)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_client.update",
+        skip_all,
+        fields(
+            db.query.text,
+            %client.id,
+        ),
+        err,
+    )]
+    async fn update(
+        &mut self,
+        mut client: Client,
+        client_name: Option<String>,
+        redirect_uris: Vec<Url>,
+        grant_types: Vec<GrantType>,
+        token_endpoint_auth_method: OAuthClientAuthenticationMethod,
+        token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        jwks: Option<PublicJsonWebKeySet>,
+        jwks_uri: Option<Url>,
+    ) -> Result<Client, Self::Error> {
+        let jwks_json = jwks
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(DatabaseError::to_invalid_operation)?;
+
+        let redirect_uris_array = redirect_uris.iter().map(Url::to_string).collect::<Vec<_>>();
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_clients
+                SET client_name = $2
+                  , redirect_uris = $3
+                  , grant_type_authorization_code = $4
+                  , grant_type_refresh_token = $5
+                  , grant_type_client_credentials = $6
+                  , grant_type_device_code = $7
+                  , grant_type_token_exchange = $8
+                  , grant_type_jwt_bearer = $9
+                  , token_endpoint_auth_method = $10
+                  , token_endpoint_auth_signing_alg = $11
+                  , jwks = $12
+                  , jwks_uri = $13
+                WHERE oauth2_client_id = $1
+            "#,
+            Uuid::from(client.id),
+            client_name,
+            &redirect_uris_array,
+            grant_types.contains(&GrantType::AuthorizationCode),
+            grant_types.contains(&GrantType::RefreshToken),
+            grant_types.contains(&GrantType::ClientCredentials),
+            grant_types.contains(&GrantType::DeviceCode),
+            grant_types.contains(&GrantType::TokenExchange),
+            grant_types.contains(&GrantType::JwtBearer),
+            token_endpoint_auth_method.to_string(),
+            token_endpoint_auth_signing_alg
+                .as_ref()
+                .map(ToString::to_string),
+            jwks_json,
+            jwks_uri.as_ref().map(Url::as_str),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        let jwks = match (jwks, jwks_uri) {
+            (None, None) => None,
+            (Some(jwks), None) => Some(JwksOrJwksUri::Jwks(jwks)),
+            (None, Some(jwks_uri)) => Some(JwksOrJwksUri::JwksUri(jwks_uri)),
+            _ => return Err(DatabaseError::invalid_operation()),
+        };
+
+        client.client_name = client_name;
+        client.redirect_uris = redirect_uris;
+        client.grant_types = grant_types;
+        client.token_endpoint_auth_method = Some(token_endpoint_auth_method);
+        client.token_endpoint_auth_signing_alg = token_endpoint_auth_signing_alg;
+        client.jwks = jwks;
+
+        Ok(client)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_client.set_client_secret",
+        skip_all,
+        fields(
+            db.query.text,
+            %client.id,
+        ),
+        err,
+    )]
+    async fn set_client_secret(
+        &mut self,
+        mut client: Client,
+        encrypted_client_secret: Option<String>,
+    ) -> Result<Client, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_clients
+                SET encrypted_client_secret = $2
+                WHERE oauth2_client_id = $1
+            "#,
+            Uuid::from(client.id),
+            encrypted_client_secret,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        client.encrypted_client_secret = encrypted_client_secret;
+
+        Ok(client)
+    }
+
     #[tracing::instrument(
         name = "db.oauth2_client.get_consent_for_user",
         skip_all,
@@ -472,4 +472,38 @@ impl<'c> OAuth2DeviceCodeGrantRepository for PgOAuth2DeviceCodeGrantRepository<'
 
         Ok(device_code_grant)
     }
+
+    #[tracing::instrument(
+        name = "db.oauth2_device_code_grant.cleanup_expired",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_expired(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM oauth2_device_code_grant
+                WHERE oauth2_device_code_grant_id IN (
+                    SELECT oauth2_device_code_grant_id
+                    FROM oauth2_device_code_grant
+                    WHERE expires_at < $1
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+            "#,
+            before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
 }
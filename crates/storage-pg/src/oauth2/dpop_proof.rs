@@ -0,0 +1,95 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_storage::{oauth2::OAuth2DPoPProofRepository, Clock};
+use sqlx::PgConnection;
+
+use crate::{DatabaseError, ExecuteExt};
+
+/// An implementation of [`OAuth2DPoPProofRepository`] for a PostgreSQL
+/// connection
+pub struct PgOAuth2DPoPProofRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgOAuth2DPoPProofRepository<'c> {
+    /// Create a new [`PgOAuth2DPoPProofRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl<'c> OAuth2DPoPProofRepository for PgOAuth2DPoPProofRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.oauth2_dpop_proof.consume",
+        skip_all,
+        fields(
+            db.query.text,
+            oauth2_dpop_proof.jkt = jkt,
+            oauth2_dpop_proof.jti = jti,
+        ),
+        err,
+    )]
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        jkt: &str,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Self::Error> {
+        let created_at = clock.now();
+
+        let res = sqlx::query!(
+            r#"
+                INSERT INTO oauth2_dpop_proofs
+                    ( jkt
+                    , jti
+                    , created_at
+                    , expires_at
+                    )
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (jkt, jti) DO NOTHING
+            "#,
+            jkt,
+            jti,
+            created_at,
+            expires_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_dpop_proof.cleanup_expired",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM oauth2_dpop_proofs
+                WHERE expires_at < $1
+            "#,
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
+}
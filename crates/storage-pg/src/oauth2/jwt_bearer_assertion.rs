@@ -0,0 +1,97 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::Client;
+use mas_storage::{oauth2::OAuth2JwtBearerAssertionRepository, Clock};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{DatabaseError, ExecuteExt};
+
+/// An implementation of [`OAuth2JwtBearerAssertionRepository`] for a
+/// PostgreSQL connection
+pub struct PgOAuth2JwtBearerAssertionRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgOAuth2JwtBearerAssertionRepository<'c> {
+    /// Create a new [`PgOAuth2JwtBearerAssertionRepository`] from an active
+    /// PostgreSQL connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl<'c> OAuth2JwtBearerAssertionRepository for PgOAuth2JwtBearerAssertionRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.oauth2_jwt_bearer_assertion.consume",
+        skip_all,
+        fields(
+            db.query.text,
+            oauth2_client.id = %client.id,
+            oauth2_jwt_bearer_assertion.jti = jti,
+        ),
+        err,
+    )]
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        client: &Client,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Self::Error> {
+        let created_at = clock.now();
+
+        let res = sqlx::query!(
+            r#"
+                INSERT INTO oauth2_jwt_bearer_assertions
+                    ( oauth2_client_id
+                    , jti
+                    , created_at
+                    , expires_at
+                    )
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (oauth2_client_id, jti) DO NOTHING
+            "#,
+            Uuid::from(client.id),
+            jti,
+            created_at,
+            expires_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_jwt_bearer_assertion.cleanup_expired",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM oauth2_jwt_bearer_assertions
+                WHERE expires_at < $1
+            "#,
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
+}
@@ -11,23 +11,34 @@ mod access_token;
 mod authorization_grant;
 mod client;
 mod device_code_grant;
+mod dpop_proof;
+mod jwt_bearer_assertion;
+mod pushed_authorization_request;
 mod refresh_token;
 mod session;
 
 pub use self::{
     access_token::PgOAuth2AccessTokenRepository,
     authorization_grant::PgOAuth2AuthorizationGrantRepository, client::PgOAuth2ClientRepository,
-    device_code_grant::PgOAuth2DeviceCodeGrantRepository,
+    device_code_grant::PgOAuth2DeviceCodeGrantRepository, dpop_proof::PgOAuth2DPoPProofRepository,
+    jwt_bearer_assertion::PgOAuth2JwtBearerAssertionRepository,
+    pushed_authorization_request::PgOAuth2PushedAuthorizationRequestRepository,
     refresh_token::PgOAuth2RefreshTokenRepository, session::PgOAuth2SessionRepository,
 };
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
     use chrono::Duration;
-    use mas_data_model::{AuthorizationCode, UserAgent};
+    use mas_data_model::{AuthorizationCode, Device, UserAgent};
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
     use mas_storage::{
         clock::MockClock,
-        oauth2::{OAuth2DeviceCodeGrantParams, OAuth2SessionFilter, OAuth2SessionRepository},
+        oauth2::{
+            OAuth2ClientFilter, OAuth2DeviceCodeGrantParams, OAuth2SessionFilter,
+            OAuth2SessionRepository,
+        },
         Clock, Pagination,
     };
     use oauth2_types::{
@@ -81,6 +92,16 @@ mod tests {
                 None,
                 None,
                 Some("https://example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -263,6 +284,7 @@ mod tests {
                 &session,
                 "aabbcc".to_owned(),
                 Some(Duration::try_minutes(5).unwrap()),
+                None,
             )
             .await
             .unwrap();
@@ -314,75 +336,652 @@ mod tests {
             .await
             .unwrap();
 
-        // Lookup the same refresh token by id
-        let refresh_token_lookup = repo
-            .oauth2_refresh_token()
-            .lookup(refresh_token.id)
-            .await
-            .unwrap()
-            .expect("refresh token not found");
-        assert_eq!(refresh_token, refresh_token_lookup);
+        // Lookup the same refresh token by id
+        let refresh_token_lookup = repo
+            .oauth2_refresh_token()
+            .lookup(refresh_token.id)
+            .await
+            .unwrap()
+            .expect("refresh token not found");
+        assert_eq!(refresh_token, refresh_token_lookup);
+
+        // Find the same refresh token by token
+        let refresh_token_lookup = repo
+            .oauth2_refresh_token()
+            .find_by_token("aabbcc")
+            .await
+            .unwrap()
+            .expect("refresh token not found");
+        assert_eq!(refresh_token, refresh_token_lookup);
+
+        assert!(access_token.is_valid(clock.now()));
+        clock.advance(Duration::try_minutes(6).unwrap());
+        assert!(!access_token.is_valid(clock.now()));
+
+        // XXX: we might want to create a new access token
+        clock.advance(Duration::try_minutes(-6).unwrap()); // Go back in time
+        assert!(access_token.is_valid(clock.now()));
+
+        // Mark the access token as revoked
+        let access_token = repo
+            .oauth2_access_token()
+            .revoke(&clock, access_token)
+            .await
+            .unwrap();
+        assert!(!access_token.is_valid(clock.now()));
+
+        // Mark the refresh token as consumed
+        assert!(refresh_token.is_valid());
+        let refresh_token = repo
+            .oauth2_refresh_token()
+            .consume(&clock, refresh_token)
+            .await
+            .unwrap();
+        assert!(!refresh_token.is_valid());
+
+        // Record the user-agent on the session
+        assert!(session.user_agent.is_none());
+        let session = repo
+            .oauth2_session()
+            .record_user_agent(session, UserAgent::parse("Mozilla/5.0".to_owned()))
+            .await
+            .unwrap();
+        assert_eq!(session.user_agent.as_deref(), Some("Mozilla/5.0"));
+
+        // Reload the session and check the user-agent
+        let session = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .expect("session not found");
+        assert_eq!(session.user_agent.as_deref(), Some("Mozilla/5.0"));
+
+        // Record the grant type which started the session
+        assert!(session.grant_type.is_none());
+        let session = repo
+            .oauth2_session()
+            .record_grant_type(session, GrantType::AuthorizationCode)
+            .await
+            .unwrap();
+        assert_eq!(session.grant_type, Some(GrantType::AuthorizationCode));
+
+        let session = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .expect("session not found");
+        assert_eq!(session.grant_type, Some(GrantType::AuthorizationCode));
+
+        // Mark the session as finished
+        assert!(session.is_valid());
+        let session = repo.oauth2_session().finish(&clock, session).await.unwrap();
+        assert!(!session.is_valid());
+    }
+
+    /// Test the cleanup of consumed refresh tokens and finished sessions
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_cleanup(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let client = repo
+            .oauth2_client()
+            .add(
+                &mut rng,
+                &clock,
+                vec!["https://example.com/redirect".parse().unwrap()],
+                None,
+                None,
+                vec![GrantType::AuthorizationCode],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "john".to_owned())
+            .await
+            .unwrap();
+        let user_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut rng,
+                &clock,
+                &client,
+                &user_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+        let access_token = repo
+            .oauth2_access_token()
+            .add(
+                &mut rng,
+                &clock,
+                &session,
+                "aabbcc".to_owned(),
+                Some(Duration::try_minutes(5).unwrap()),
+                None,
+            )
+            .await
+            .unwrap();
+        let refresh_token = repo
+            .oauth2_refresh_token()
+            .add(
+                &mut rng,
+                &clock,
+                &session,
+                &access_token,
+                "aabbcc".to_owned(),
+            )
+            .await
+            .unwrap();
+
+        // Consume the refresh token and finish the sessions
+        let refresh_token = repo
+            .oauth2_refresh_token()
+            .consume(&clock, refresh_token)
+            .await
+            .unwrap();
+        let session = repo.oauth2_session().finish(&clock, session).await.unwrap();
+        let user_session = repo
+            .browser_session()
+            .finish(&clock, user_session)
+            .await
+            .unwrap();
+
+        // Nothing is old enough to be removed yet
+        let before = clock.now();
+        assert_eq!(
+            repo.oauth2_refresh_token()
+                .cleanup_consumed(before, 10)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            repo.oauth2_session()
+                .cleanup_finished(before, 10)
+                .await
+                .unwrap(),
+            0
+        );
+
+        clock.advance(Duration::try_hours(1).unwrap());
+        let before = clock.now();
+
+        assert_eq!(
+            repo.oauth2_refresh_token()
+                .cleanup_consumed(before, 10)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(repo
+            .oauth2_refresh_token()
+            .lookup(refresh_token.id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // The browser session is still referenced by the OAuth 2.0 session
+        assert_eq!(
+            repo.browser_session()
+                .cleanup_finished(before, 10)
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(
+            repo.oauth2_session()
+                .cleanup_finished(before, 10)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .oauth2_access_token()
+            .lookup(access_token.id)
+            .await
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            repo.browser_session()
+                .cleanup_finished(before, 10)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(repo
+            .browser_session()
+            .lookup(user_session.id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    /// Test that the cleanup of finished browser sessions keeps the ones still
+    /// referenced by an OAuth 2.0 session or a device code grant
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_browser_session_cleanup(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let client = repo
+            .oauth2_client()
+            .add(
+                &mut rng,
+                &clock,
+                vec!["https://example.com/redirect".parse().unwrap()],
+                None,
+                None,
+                vec![GrantType::DeviceCode],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "john".to_owned())
+            .await
+            .unwrap();
+        let password = repo
+            .user_password()
+            .add(&mut rng, &clock, &user, 1, "hash".to_owned(), None)
+            .await
+            .unwrap();
+
+        // A browser session with nothing but an authentication attached
+        let unreferenced = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+        repo.browser_session()
+            .authenticate_with_password(&mut rng, &clock, &unreferenced, &password)
+            .await
+            .unwrap();
+
+        // A browser session referenced by an OAuth 2.0 session
+        let with_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+        repo.oauth2_session()
+            .add_from_browser_session(
+                &mut rng,
+                &clock,
+                &client,
+                &with_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        // A browser session referenced by a device code grant
+        let with_grant = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+        let grant = repo
+            .oauth2_device_code_grant()
+            .add(
+                &mut rng,
+                &clock,
+                OAuth2DeviceCodeGrantParams {
+                    client: &client,
+                    scope: Scope::from_iter([OPENID]),
+                    device_code: "device_code".to_owned(),
+                    user_code: "user_code".to_owned(),
+                    expires_in: Duration::try_minutes(5).unwrap(),
+                    ip_address: None,
+                    user_agent: None,
+                },
+            )
+            .await
+            .unwrap();
+        let grant = repo
+            .oauth2_device_code_grant()
+            .fulfill(&clock, grant, &with_grant)
+            .await
+            .unwrap();
+
+        // A browser session referenced by a compatibility session
+        let with_compat = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+        let compat_session = repo
+            .compat_session()
+            .add(
+                &mut rng,
+                &clock,
+                &user,
+                Device::generate(&mut rng),
+                Some(&with_compat),
+                false,
+            )
+            .await
+            .unwrap();
+
+        // A browser session which stays active
+        let active = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+
+        for session in [&unreferenced, &with_session, &with_grant, &with_compat] {
+            repo.browser_session()
+                .finish(&clock, session.clone())
+                .await
+                .unwrap();
+        }
+
+        clock.advance(Duration::try_hours(1).unwrap());
+        let before = clock.now();
+
+        // Only the unreferenced session and the one used by the compatibility
+        // session are removed, one batch at a time
+        assert_eq!(
+            repo.browser_session()
+                .cleanup_finished(before, 1)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.browser_session()
+                .cleanup_finished(before, 1)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.browser_session()
+                .cleanup_finished(before, 1)
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert!(repo
+            .browser_session()
+            .lookup(unreferenced.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .browser_session()
+            .get_last_authentication(&unreferenced)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .browser_session()
+            .lookup(with_compat.id)
+            .await
+            .unwrap()
+            .is_none());
+        let compat_session = repo
+            .compat_session()
+            .lookup(compat_session.id)
+            .await
+            .unwrap()
+            .expect("compat session not found");
+        assert_eq!(compat_session.user_session_id, None);
+
+        assert!(repo
+            .browser_session()
+            .lookup(with_session.id)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(repo
+            .browser_session()
+            .lookup(with_grant.id)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(repo
+            .browser_session()
+            .lookup(active.id)
+            .await
+            .unwrap()
+            .is_some());
+
+        // Once the expired device code grant is gone, its browser session can be
+        // removed as well
+        assert_eq!(
+            repo.oauth2_device_code_grant()
+                .cleanup_expired(before, 10)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(repo
+            .oauth2_device_code_grant()
+            .lookup(grant.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            repo.browser_session()
+                .cleanup_finished(before, 10)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(repo
+            .browser_session()
+            .lookup(with_grant.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .browser_session()
+            .lookup(with_session.id)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    /// Test the [`OAuth2SessionRepository::list`] and
+    /// [`OAuth2SessionRepository::count`] methods.
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_client_repository(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let all = OAuth2ClientFilter::new();
+        let static_only = all.static_only();
+        let dynamic_only = all.dynamic_only();
+
+        assert_eq!(repo.oauth2_client().count(all).await.unwrap(), 0);
+
+        // Create a static client and a dynamic one
+        let static_client = repo
+            .oauth2_client()
+            .upsert_static(
+                Ulid::from_bytes([0x01; 16]),
+                OAuthClientAuthenticationMethod::None,
+                None,
+                None,
+                None,
+                vec!["https://static.example.com/callback".parse().unwrap()],
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(static_client.is_static);
+
+        let client = repo
+            .oauth2_client()
+            .add(
+                &mut rng,
+                &clock,
+                vec!["https://example.com/callback".parse().unwrap()],
+                Some("encrypted-secret".to_owned()),
+                None,
+                vec![GrantType::AuthorizationCode],
+                Some("Dynamic client".to_owned()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(OAuthClientAuthenticationMethod::ClientSecretBasic),
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(!client.is_static);
+
+        assert_eq!(repo.oauth2_client().count(all).await.unwrap(), 2);
+        assert_eq!(repo.oauth2_client().count(static_only).await.unwrap(), 1);
+        assert_eq!(repo.oauth2_client().count(dynamic_only).await.unwrap(), 1);
 
-        // Find the same refresh token by token
-        let refresh_token_lookup = repo
-            .oauth2_refresh_token()
-            .find_by_token("aabbcc")
+        let page = repo
+            .oauth2_client()
+            .list(dynamic_only, Pagination::first(10))
             .await
-            .unwrap()
-            .expect("refresh token not found");
-        assert_eq!(refresh_token, refresh_token_lookup);
-
-        assert!(access_token.is_valid(clock.now()));
-        clock.advance(Duration::try_minutes(6).unwrap());
-        assert!(!access_token.is_valid(clock.now()));
-
-        // XXX: we might want to create a new access token
-        clock.advance(Duration::try_minutes(-6).unwrap()); // Go back in time
-        assert!(access_token.is_valid(clock.now()));
+            .unwrap();
+        assert!(!page.has_next_page);
+        assert_eq!(page.edges, vec![client.clone()]);
 
-        // Mark the access token as revoked
-        let access_token = repo
-            .oauth2_access_token()
-            .revoke(&clock, access_token)
+        let page = repo
+            .oauth2_client()
+            .list(static_only, Pagination::first(10))
             .await
             .unwrap();
-        assert!(!access_token.is_valid(clock.now()));
+        assert_eq!(page.edges, vec![static_client]);
 
-        // Mark the refresh token as consumed
-        assert!(refresh_token.is_valid());
-        let refresh_token = repo
-            .oauth2_refresh_token()
-            .consume(&clock, refresh_token)
+        // Update the dynamic client
+        let client = repo
+            .oauth2_client()
+            .update(
+                client,
+                Some("Renamed client".to_owned()),
+                vec!["https://example.com/other-callback".parse().unwrap()],
+                vec![GrantType::ClientCredentials],
+                OAuthClientAuthenticationMethod::PrivateKeyJwt,
+                None,
+                None,
+                Some("https://example.com/jwks.json".parse().unwrap()),
+            )
             .await
             .unwrap();
-        assert!(!refresh_token.is_valid());
-
-        // Record the user-agent on the session
-        assert!(session.user_agent.is_none());
-        let session = repo
-            .oauth2_session()
-            .record_user_agent(session, UserAgent::parse("Mozilla/5.0".to_owned()))
+        let client = repo
+            .oauth2_client()
+            .set_client_secret(client, None)
             .await
             .unwrap();
-        assert_eq!(session.user_agent.as_deref(), Some("Mozilla/5.0"));
 
-        // Reload the session and check the user-agent
-        let session = repo
-            .oauth2_session()
-            .lookup(session.id)
+        let client_lookup = repo
+            .oauth2_client()
+            .lookup(client.id)
             .await
             .unwrap()
-            .expect("session not found");
-        assert_eq!(session.user_agent.as_deref(), Some("Mozilla/5.0"));
-
-        // Mark the session as finished
-        assert!(session.is_valid());
-        let session = repo.oauth2_session().finish(&clock, session).await.unwrap();
-        assert!(!session.is_valid());
+            .expect("client not found");
+        assert_eq!(client, client_lookup);
+        assert_eq!(client.client_name.as_deref(), Some("Renamed client"));
+        assert_eq!(client.grant_types, vec![GrantType::ClientCredentials]);
+        assert_eq!(
+            client.token_endpoint_auth_method,
+            Some(OAuthClientAuthenticationMethod::PrivateKeyJwt)
+        );
+        assert_eq!(client.encrypted_client_secret, None);
     }
 
-    /// Test the [`OAuth2SessionRepository::list`] and
-    /// [`OAuth2SessionRepository::count`] methods.
     #[sqlx::test(migrator = "crate::MIGRATOR")]
     async fn test_list_sessions(pool: PgPool) {
         let mut rng = ChaChaRng::seed_from_u64(42);
@@ -434,6 +1033,16 @@ mod tests {
                 None,
                 None,
                 Some("https://first.example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -458,6 +1067,16 @@ mod tests {
                 None,
                 None,
                 Some("https://second.example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -526,6 +1145,14 @@ mod tests {
 
         assert_eq!(repo.oauth2_session().count(filter).await.unwrap(), 4);
 
+        // Count the active sessions of each client in one go
+        let counts = repo
+            .oauth2_session()
+            .count_active_by_clients(BTreeSet::from([client1.id, client2.id, Ulid::nil()]))
+            .await
+            .unwrap();
+        assert_eq!(counts, BTreeMap::from([(client1.id, 1), (client2.id, 1)]));
+
         // Now filter for only one user
         let filter = OAuth2SessionFilter::new().for_user(&user1);
         let list = repo
@@ -757,6 +1384,16 @@ mod tests {
                 None,
                 None,
                 Some("https://example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -951,4 +1588,315 @@ mod tests {
             .await;
         assert!(res.is_err());
     }
+
+    /// Test the [`OAuth2PushedAuthorizationRequestRepository`] implementation
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_pushed_authorization_request_repository(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        // Provision a client
+        let client = repo
+            .oauth2_client()
+            .add(
+                &mut rng,
+                &clock,
+                vec!["https://example.com/redirect".parse().unwrap()],
+                None,
+                None,
+                vec![GrantType::AuthorizationCode],
+                Some("Example".to_owned()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+                true,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(client.require_pushed_authorization_requests);
+
+        // Lookup a non-existing request
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .lookup(Ulid::nil())
+            .await
+            .unwrap();
+        assert_eq!(request, None);
+
+        let parameters = BTreeMap::from([
+            ("response_type".to_owned(), "code".to_owned()),
+            ("scope".to_owned(), "openid".to_owned()),
+        ]);
+
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .add(
+                &mut rng,
+                &clock,
+                &client,
+                parameters.clone(),
+                Duration::try_minutes(1).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(request.client_id, client.id);
+        assert_eq!(request.parameters, parameters);
+        assert!(request.is_valid(clock.now()));
+
+        // Look it up again
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .lookup(request.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(request.parameters, parameters);
+        let id = request.id;
+
+        // Consume it
+        let stale = request.clone();
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .consume(&clock, request)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!request.is_valid(clock.now()));
+
+        // Consuming a copy loaded before it was consumed does nothing
+        let res = repo
+            .oauth2_pushed_authorization_request()
+            .consume(&clock, stale)
+            .await
+            .unwrap();
+        assert_eq!(res, None);
+
+        // It can't be consumed twice
+        let res = repo
+            .oauth2_pushed_authorization_request()
+            .consume(&clock, request)
+            .await;
+        assert!(res.is_err());
+
+        // Cleaning up does nothing until it expires
+        let count = repo
+            .oauth2_pushed_authorization_request()
+            .cleanup_expired(&clock)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        clock.advance(Duration::try_minutes(2).unwrap());
+        let count = repo
+            .oauth2_pushed_authorization_request()
+            .cleanup_expired(&clock)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .lookup(id)
+            .await
+            .unwrap();
+        assert_eq!(request, None);
+    }
+
+    /// Test the [`OAuth2JwtBearerAssertionRepository`] implementation
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_jwt_bearer_assertion_repository(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        // Provision two clients
+        let mut clients = Vec::new();
+        for _ in 0..2 {
+            let client = repo
+                .oauth2_client()
+                .add(
+                    &mut rng,
+                    &clock,
+                    Vec::new(),
+                    None,
+                    None,
+                    vec![GrantType::JwtBearer],
+                    Some("Example".to_owned()),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Vec::new(),
+                    None,
+                    false,
+                    false,
+                    None,
+                    false,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            clients.push(client);
+        }
+        let (client, other_client) = (&clients[0], &clients[1]);
+
+        let expires_at = clock.now() + Duration::try_minutes(5).unwrap();
+
+        // The first use of an assertion is recorded
+        let consumed = repo
+            .oauth2_jwt_bearer_assertion()
+            .consume(&clock, client, "jti-1", expires_at)
+            .await
+            .unwrap();
+        assert!(consumed);
+
+        // Using it again is refused
+        let consumed = repo
+            .oauth2_jwt_bearer_assertion()
+            .consume(&clock, client, "jti-1", expires_at)
+            .await
+            .unwrap();
+        assert!(!consumed);
+
+        // Another client can use the same jti, and so can the same client with
+        // another jti
+        let consumed = repo
+            .oauth2_jwt_bearer_assertion()
+            .consume(&clock, other_client, "jti-1", expires_at)
+            .await
+            .unwrap();
+        assert!(consumed);
+        let consumed = repo
+            .oauth2_jwt_bearer_assertion()
+            .consume(
+                &clock,
+                client,
+                "jti-2",
+                expires_at + Duration::try_minutes(10).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(consumed);
+
+        // Cleaning up does nothing until they expire
+        let count = repo
+            .oauth2_jwt_bearer_assertion()
+            .cleanup_expired(&clock)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        clock.advance(Duration::try_minutes(6).unwrap());
+        let count = repo
+            .oauth2_jwt_bearer_assertion()
+            .cleanup_expired(&clock)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // The assertion which wasn't cleaned up is still refused
+        let consumed = repo
+            .oauth2_jwt_bearer_assertion()
+            .consume(&clock, client, "jti-2", expires_at)
+            .await
+            .unwrap();
+        assert!(!consumed);
+    }
+
+    /// Test the [`OAuth2DPoPProofRepository`] implementation
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_dpop_proof_repository(pool: PgPool) {
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let expires_at = clock.now() + Duration::try_minutes(5).unwrap();
+
+        // The first use of a proof is recorded
+        let consumed = repo
+            .oauth2_dpop_proof()
+            .consume(&clock, "jkt-1", "jti-1", expires_at)
+            .await
+            .unwrap();
+        assert!(consumed);
+
+        // Using it again is refused
+        let consumed = repo
+            .oauth2_dpop_proof()
+            .consume(&clock, "jkt-1", "jti-1", expires_at)
+            .await
+            .unwrap();
+        assert!(!consumed);
+
+        // Another key can use the same jti, and so can the same key with another
+        // jti
+        let consumed = repo
+            .oauth2_dpop_proof()
+            .consume(&clock, "jkt-2", "jti-1", expires_at)
+            .await
+            .unwrap();
+        assert!(consumed);
+        let consumed = repo
+            .oauth2_dpop_proof()
+            .consume(
+                &clock,
+                "jkt-1",
+                "jti-2",
+                expires_at + Duration::try_minutes(10).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(consumed);
+
+        // Cleaning up does nothing until they expire
+        let count = repo
+            .oauth2_dpop_proof()
+            .cleanup_expired(&clock)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        clock.advance(Duration::try_minutes(6).unwrap());
+        let count = repo
+            .oauth2_dpop_proof()
+            .cleanup_expired(&clock)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // The proof which wasn't cleaned up is still refused
+        let consumed = repo
+            .oauth2_dpop_proof()
+            .consume(&clock, "jkt-1", "jti-2", expires_at)
+            .await
+            .unwrap();
+        assert!(!consumed);
+    }
 }
@@ -0,0 +1,222 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{Client, PushedAuthorizationRequest};
+use mas_storage::{oauth2::OAuth2PushedAuthorizationRequestRepository, Clock};
+use rand::RngCore;
+use sqlx::{types::Json, PgConnection};
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{DatabaseError, ExecuteExt};
+
+/// An implementation of [`OAuth2PushedAuthorizationRequestRepository`] for a
+/// PostgreSQL connection
+pub struct PgOAuth2PushedAuthorizationRequestRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgOAuth2PushedAuthorizationRequestRepository<'c> {
+    /// Create a new [`PgOAuth2PushedAuthorizationRequestRepository`] from an
+    /// active PostgreSQL connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct PushedAuthorizationRequestLookup {
+    oauth2_pushed_authorization_request_id: Uuid,
+    oauth2_client_id: Uuid,
+    parameters: Json<BTreeMap<String, String>>,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+impl From<PushedAuthorizationRequestLookup> for PushedAuthorizationRequest {
+    fn from(value: PushedAuthorizationRequestLookup) -> Self {
+        Self {
+            id: value.oauth2_pushed_authorization_request_id.into(),
+            client_id: value.oauth2_client_id.into(),
+            parameters: value.parameters.0,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            consumed_at: value.consumed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> OAuth2PushedAuthorizationRequestRepository
+    for PgOAuth2PushedAuthorizationRequestRepository<'c>
+{
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.oauth2_pushed_authorization_request.add",
+        skip_all,
+        fields(
+            db.query.text,
+            oauth2_pushed_authorization_request.id,
+            oauth2_client.id = %client.id,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parameters: BTreeMap<String, String>,
+        expires_in: Duration,
+    ) -> Result<PushedAuthorizationRequest, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "oauth2_pushed_authorization_request.id",
+            tracing::field::display(id),
+        );
+
+        let expires_at = created_at + expires_in;
+
+        sqlx::query!(
+            r#"
+                INSERT INTO oauth2_pushed_authorization_requests
+                    ( oauth2_pushed_authorization_request_id
+                    , oauth2_client_id
+                    , parameters
+                    , created_at
+                    , expires_at
+                    )
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::from(id),
+            Uuid::from(client.id),
+            Json(&parameters) as _,
+            created_at,
+            expires_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(PushedAuthorizationRequest {
+            id,
+            client_id: client.id,
+            parameters,
+            created_at,
+            expires_at,
+            consumed_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_pushed_authorization_request.lookup",
+        skip_all,
+        fields(
+            db.query.text,
+            oauth2_pushed_authorization_request.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(
+        &mut self,
+        id: Ulid,
+    ) -> Result<Option<PushedAuthorizationRequest>, Self::Error> {
+        let res = sqlx::query_as!(
+            PushedAuthorizationRequestLookup,
+            r#"
+                SELECT oauth2_pushed_authorization_request_id
+                     , oauth2_client_id
+                     , parameters as "parameters: Json<BTreeMap<String, String>>"
+                     , created_at
+                     , expires_at
+                     , consumed_at
+                FROM oauth2_pushed_authorization_requests
+
+                WHERE oauth2_pushed_authorization_request_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_pushed_authorization_request.consume",
+        skip_all,
+        fields(
+            db.query.text,
+            oauth2_pushed_authorization_request.id = %request.id,
+            oauth2_client.id = %request.client_id,
+        ),
+        err,
+    )]
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        request: PushedAuthorizationRequest,
+    ) -> Result<Option<PushedAuthorizationRequest>, Self::Error> {
+        let consumed_at = clock.now();
+        let request = request
+            .consume(consumed_at)
+            .map_err(DatabaseError::to_invalid_operation)?;
+
+        // Only update the request if it wasn't consumed in the meantime, so
+        // that a request can't be used twice by concurrent transactions
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_pushed_authorization_requests
+                SET consumed_at = $1
+                WHERE oauth2_pushed_authorization_request_id = $2
+                  AND consumed_at IS NULL
+            "#,
+            consumed_at,
+            Uuid::from(request.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        // The request was consumed by another transaction since it was loaded
+        if res.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(Some(request))
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_pushed_authorization_request.cleanup_expired",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM oauth2_pushed_authorization_requests
+                WHERE expires_at < $1
+            "#,
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
+}
@@ -36,6 +36,7 @@ struct OAuth2RefreshTokenLookup {
     consumed_at: Option<DateTime<Utc>>,
     oauth2_access_token_id: Option<Uuid>,
     oauth2_session_id: Uuid,
+    dpop_jkt: Option<String>,
 }
 
 impl From<OAuth2RefreshTokenLookup> for RefreshToken {
@@ -52,6 +53,7 @@ impl From<OAuth2RefreshTokenLookup> for RefreshToken {
             refresh_token: value.refresh_token,
             created_at: value.created_at,
             access_token_id: value.oauth2_access_token_id.map(Ulid::from),
+            dpop_jkt: value.dpop_jkt,
         }
     }
 }
@@ -79,6 +81,7 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
                      , consumed_at
                      , oauth2_access_token_id
                      , oauth2_session_id
+                     , dpop_jkt
                 FROM oauth2_refresh_tokens
 
                 WHERE oauth2_refresh_token_id = $1
@@ -114,6 +117,7 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
                      , consumed_at
                      , oauth2_access_token_id
                      , oauth2_session_id
+                     , dpop_jkt
                 FROM oauth2_refresh_tokens
 
                 WHERE refresh_token = $1
@@ -156,15 +160,16 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
             r#"
                 INSERT INTO oauth2_refresh_tokens
                     (oauth2_refresh_token_id, oauth2_session_id, oauth2_access_token_id,
-                     refresh_token, created_at)
+                     refresh_token, created_at, dpop_jkt)
                 VALUES
-                    ($1, $2, $3, $4, $5)
+                    ($1, $2, $3, $4, $5, $6)
             "#,
             Uuid::from(id),
             Uuid::from(session.id),
             Uuid::from(access_token.id),
             refresh_token,
             created_at,
+            access_token.dpop_jkt.as_deref(),
         )
         .traced()
         .execute(&mut *self.conn)
@@ -177,6 +182,7 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
             refresh_token,
             access_token_id: Some(access_token.id),
             created_at,
+            dpop_jkt: access_token.dpop_jkt.clone(),
         })
     }
 
@@ -214,4 +220,38 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
             .consume(consumed_at)
             .map_err(DatabaseError::to_invalid_operation)
     }
+
+    #[tracing::instrument(
+        name = "db.oauth2_refresh_token.cleanup_consumed",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_consumed(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM oauth2_refresh_tokens
+                WHERE oauth2_refresh_token_id IN (
+                    SELECT oauth2_refresh_token_id
+                    FROM oauth2_refresh_tokens
+                    WHERE consumed_at < $1
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+            "#,
+            before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
 }
@@ -4,7 +4,10 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
-use std::net::IpAddr;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
@@ -13,7 +16,10 @@ use mas_storage::{
     oauth2::{OAuth2SessionFilter, OAuth2SessionRepository},
     Clock, Page, Pagination,
 };
-use oauth2_types::scope::{Scope, ScopeToken};
+use oauth2_types::{
+    requests::GrantType,
+    scope::{Scope, ScopeToken},
+};
 use rand::RngCore;
 use sea_query::{enum_def, extension::postgres::PgExpr, Expr, PgFunc, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
@@ -55,6 +61,8 @@ struct OAuthSessionLookup {
     user_agent: Option<String>,
     last_active_at: Option<DateTime<Utc>>,
     last_active_ip: Option<IpAddr>,
+    grant_type: Option<String>,
+    acting_oauth2_client_id: Option<Uuid>,
 }
 
 impl TryFrom<OAuthSessionLookup> for Session {
@@ -74,6 +82,17 @@ impl TryFrom<OAuthSessionLookup> for Session {
                 .source(e)
         })?;
 
+        let grant_type = value
+            .grant_type
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_sessions")
+                    .column("grant_type")
+                    .row(id)
+                    .source(e)
+            })?;
+
         let state = match value.finished_at {
             None => SessionState::Valid,
             Some(finished_at) => SessionState::Finished { finished_at },
@@ -90,6 +109,8 @@ impl TryFrom<OAuthSessionLookup> for Session {
             user_agent: value.user_agent.map(UserAgent::parse),
             last_active_at: value.last_active_at,
             last_active_ip: value.last_active_ip,
+            grant_type,
+            acting_client_id: value.acting_oauth2_client_id.map(Ulid::from),
         })
     }
 }
@@ -133,6 +154,26 @@ impl Filter for OAuth2SessionFilter<'_> {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveAt))
                     .lt(last_active_before)
             }))
+            .add_option(self.created_before().map(|created_before| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::CreatedAt)).lt(created_before)
+            }))
+            .add_option(self.inactive_since().map(|inactive_since| {
+                // Sessions which were never active are considered active when
+                // they were created
+                sea_query::Condition::any()
+                    .add(
+                        Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveAt))
+                            .lt(inactive_since),
+                    )
+                    .add(
+                        Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveAt))
+                            .is_null()
+                            .and(
+                                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::CreatedAt))
+                                    .lt(inactive_since),
+                            ),
+                    )
+            }))
     }
 }
 
@@ -163,6 +204,8 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
                      , user_agent
                      , last_active_at
                      , last_active_ip as "last_active_ip: IpAddr"
+                     , grant_type
+                     , acting_oauth2_client_id
                 FROM oauth2_sessions
 
                 WHERE oauth2_session_id = $1
@@ -238,6 +281,8 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
             user_agent: None,
             last_active_at: None,
             last_active_ip: None,
+            grant_type: None,
+            acting_client_id: None,
         })
     }
 
@@ -360,6 +405,14 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveIp)),
                 OAuthSessionLookupIden::LastActiveIp,
             )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::GrantType)),
+                OAuthSessionLookupIden::GrantType,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::ActingOAuth2ClientId)),
+                OAuthSessionLookupIden::ActingOauth2ClientId,
+            )
             .from(OAuth2Sessions::Table)
             .apply_filter(filter)
             .generate_pagination(
@@ -403,6 +456,45 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
             .map_err(DatabaseError::to_invalid_operation)
     }
 
+    #[tracing::instrument(
+        name = "db.oauth2_session.count_active_by_clients",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn count_active_by_clients(
+        &mut self,
+        client_ids: BTreeSet<Ulid>,
+    ) -> Result<BTreeMap<Ulid, usize>, Self::Error> {
+        let client_ids: Vec<Uuid> = client_ids.into_iter().map(Uuid::from).collect();
+        let res = sqlx::query!(
+            r#"
+                SELECT oauth2_client_id
+                     , COUNT(*) AS "count!"
+                FROM oauth2_sessions
+                WHERE oauth2_client_id = ANY($1::uuid[])
+                  AND finished_at IS NULL
+                GROUP BY oauth2_client_id
+            "#,
+            &client_ids,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        res.into_iter()
+            .map(|r| {
+                let count = r
+                    .count
+                    .try_into()
+                    .map_err(DatabaseError::to_invalid_operation)?;
+                Ok((r.oauth2_client_id.into(), count))
+            })
+            .collect()
+    }
+
     #[tracing::instrument(
         name = "db.oauth2_session.record_batch_activity",
         skip_all,
@@ -486,4 +578,123 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
 
         Ok(session)
     }
+
+    #[tracing::instrument(
+        name = "db.oauth2_session.record_grant_type",
+        skip_all,
+        fields(
+            db.query.text,
+            %session.id,
+            client.id = %session.client_id,
+            session.grant_type = %grant_type,
+        ),
+        err,
+    )]
+    async fn record_grant_type(
+        &mut self,
+        mut session: Session,
+        grant_type: GrantType,
+    ) -> Result<Session, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET grant_type = $2
+                WHERE oauth2_session_id = $1
+            "#,
+            Uuid::from(session.id),
+            grant_type.to_string(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        session.grant_type = Some(grant_type);
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(session)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_session.record_acting_client",
+        skip_all,
+        fields(
+            db.query.text,
+            %session.id,
+            client.id = %session.client_id,
+            acting_client.id = %acting_client.id,
+        ),
+        err,
+    )]
+    async fn record_acting_client(
+        &mut self,
+        mut session: Session,
+        acting_client: &Client,
+    ) -> Result<Session, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET acting_oauth2_client_id = $2
+                WHERE oauth2_session_id = $1
+            "#,
+            Uuid::from(session.id),
+            Uuid::from(acting_client.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        session.acting_client_id = Some(acting_client.id);
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(session)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_session.cleanup_finished",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                WITH sessions AS (
+                    SELECT oauth2_session_id
+                    FROM oauth2_sessions
+                    WHERE finished_at < $1
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                ),
+                refresh_tokens AS (
+                    DELETE FROM oauth2_refresh_tokens
+                    WHERE oauth2_session_id IN (SELECT oauth2_session_id FROM sessions)
+                ),
+                access_tokens AS (
+                    DELETE FROM oauth2_access_tokens
+                    WHERE oauth2_session_id IN (SELECT oauth2_session_id FROM sessions)
+                ),
+                authorization_grants AS (
+                    DELETE FROM oauth2_authorization_grants
+                    WHERE oauth2_session_id IN (SELECT oauth2_session_id FROM sessions)
+                )
+                DELETE FROM oauth2_sessions
+                WHERE oauth2_session_id IN (SELECT oauth2_session_id FROM sessions)
+            "#,
+            before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
 }
@@ -9,6 +9,7 @@ use std::ops::{Deref, DerefMut};
 use futures_util::{future::BoxFuture, FutureExt, TryFutureExt};
 use mas_storage::{
     app_session::AppSessionRepository,
+    audit::AuditEventRepository,
     compat::{
         CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository,
         CompatSsoLoginRepository,
@@ -16,13 +17,16 @@ use mas_storage::{
     job::JobRepository,
     oauth2::{
         OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository, OAuth2ClientRepository,
-        OAuth2DeviceCodeGrantRepository, OAuth2RefreshTokenRepository, OAuth2SessionRepository,
+        OAuth2DPoPProofRepository, OAuth2DeviceCodeGrantRepository,
+        OAuth2JwtBearerAssertionRepository, OAuth2PushedAuthorizationRequestRepository,
+        OAuth2RefreshTokenRepository, OAuth2SessionRepository,
     },
     upstream_oauth2::{
         UpstreamOAuthLinkRepository, UpstreamOAuthProviderRepository,
         UpstreamOAuthSessionRepository,
     },
     user::{BrowserSessionRepository, UserEmailRepository, UserPasswordRepository, UserRepository},
+    webhook::WebhookDeliveryRepository,
     BoxRepository, MapErr, Repository, RepositoryAccess, RepositoryError, RepositoryTransaction,
 };
 use sqlx::{PgConnection, PgPool, Postgres, Transaction};
@@ -30,6 +34,7 @@ use tracing::Instrument;
 
 use crate::{
     app_session::PgAppSessionRepository,
+    audit::PgAuditEventRepository,
     compat::{
         PgCompatAccessTokenRepository, PgCompatRefreshTokenRepository, PgCompatSessionRepository,
         PgCompatSsoLoginRepository,
@@ -37,7 +42,8 @@ use crate::{
     job::PgJobRepository,
     oauth2::{
         PgOAuth2AccessTokenRepository, PgOAuth2AuthorizationGrantRepository,
-        PgOAuth2ClientRepository, PgOAuth2DeviceCodeGrantRepository,
+        PgOAuth2ClientRepository, PgOAuth2DPoPProofRepository, PgOAuth2DeviceCodeGrantRepository,
+        PgOAuth2JwtBearerAssertionRepository, PgOAuth2PushedAuthorizationRequestRepository,
         PgOAuth2RefreshTokenRepository, PgOAuth2SessionRepository,
     },
     upstream_oauth2::{
@@ -45,9 +51,12 @@ use crate::{
         PgUpstreamOAuthSessionRepository,
     },
     user::{
-        PgBrowserSessionRepository, PgUserEmailRepository, PgUserPasswordRepository,
-        PgUserRecoveryRepository, PgUserRepository, PgUserTermsRepository,
+        PgBrowserSessionRepository, PgUserEmailRepository, PgUserLdapLinkRepository,
+        PgUserPasskeyRepository, PgUserPasswordRepository, PgUserRecoveryRepository,
+        PgUserRepository, PgUserTermsRepository, PgUserTotpRecoveryCodeRepository,
+        PgUserTotpRepository,
     },
+    webhook::PgWebhookDeliveryRepository,
     DatabaseError,
 };
 
@@ -188,6 +197,30 @@ where
         Box::new(PgUserTermsRepository::new(self.conn.as_mut()))
     }
 
+    fn user_totp<'c>(
+        &'c mut self,
+    ) -> Box<dyn mas_storage::user::UserTotpRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserTotpRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_totp_recovery_code<'c>(
+        &'c mut self,
+    ) -> Box<dyn mas_storage::user::UserTotpRecoveryCodeRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserTotpRecoveryCodeRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_passkey<'c>(
+        &'c mut self,
+    ) -> Box<dyn mas_storage::user::UserPasskeyRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserPasskeyRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_ldap_link<'c>(
+        &'c mut self,
+    ) -> Box<dyn mas_storage::user::UserLdapLinkRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserLdapLinkRepository::new(self.conn.as_mut()))
+    }
+
     fn browser_session<'c>(
         &'c mut self,
     ) -> Box<dyn BrowserSessionRepository<Error = Self::Error> + 'c> {
@@ -198,6 +231,10 @@ where
         Box::new(PgAppSessionRepository::new(self.conn.as_mut()))
     }
 
+    fn audit_event<'c>(&'c mut self) -> Box<dyn AuditEventRepository<Error = Self::Error> + 'c> {
+        Box::new(PgAuditEventRepository::new(self.conn.as_mut()))
+    }
+
     fn oauth2_client<'c>(
         &'c mut self,
     ) -> Box<dyn OAuth2ClientRepository<Error = Self::Error> + 'c> {
@@ -236,6 +273,28 @@ where
         Box::new(PgOAuth2DeviceCodeGrantRepository::new(self.conn.as_mut()))
     }
 
+    fn oauth2_pushed_authorization_request<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2PushedAuthorizationRequestRepository<Error = Self::Error> + 'c> {
+        Box::new(PgOAuth2PushedAuthorizationRequestRepository::new(
+            self.conn.as_mut(),
+        ))
+    }
+
+    fn oauth2_dpop_proof<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2DPoPProofRepository<Error = Self::Error> + 'c> {
+        Box::new(PgOAuth2DPoPProofRepository::new(self.conn.as_mut()))
+    }
+
+    fn oauth2_jwt_bearer_assertion<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2JwtBearerAssertionRepository<Error = Self::Error> + 'c> {
+        Box::new(PgOAuth2JwtBearerAssertionRepository::new(
+            self.conn.as_mut(),
+        ))
+    }
+
     fn compat_session<'c>(
         &'c mut self,
     ) -> Box<dyn CompatSessionRepository<Error = Self::Error> + 'c> {
@@ -263,4 +322,10 @@ where
     fn job<'c>(&'c mut self) -> Box<dyn JobRepository<Error = Self::Error> + 'c> {
         Box::new(PgJobRepository::new(self.conn.as_mut()))
     }
+
+    fn webhook_delivery<'c>(
+        &'c mut self,
+    ) -> Box<dyn WebhookDeliveryRepository<Error = Self::Error> + 'c> {
+        Box::new(PgWebhookDeliveryRepository::new(self.conn.as_mut()))
+    }
 }
@@ -146,7 +146,7 @@ impl<'c> UpstreamOAuthLinkRepository for PgUpstreamOAuthLinkRepository<'c> {
             upstream_oauth_link.subject = subject,
             %upstream_oauth_provider.id,
             %upstream_oauth_provider.issuer,
-            %upstream_oauth_provider.client_id,
+            upstream_oauth_provider.client_id = upstream_oauth_provider.client_id.as_deref(),
         ),
         err,
     )]
@@ -188,7 +188,7 @@ impl<'c> UpstreamOAuthLinkRepository for PgUpstreamOAuthLinkRepository<'c> {
             upstream_oauth_link.subject = subject,
             %upstream_oauth_provider.id,
             %upstream_oauth_provider.issuer,
-            %upstream_oauth_provider.client_id,
+            upstream_oauth_provider.client_id = upstream_oauth_provider.client_id.as_deref(),
         ),
         err,
     )]
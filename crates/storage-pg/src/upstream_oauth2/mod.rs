@@ -19,7 +19,10 @@ pub use self::{
 #[cfg(test)]
 mod tests {
     use chrono::Duration;
-    use mas_data_model::UpstreamOAuthProviderClaimsImports;
+    use mas_data_model::{
+        AuthenticationMethod, UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderSamlBinding,
+        UpstreamOAuthProviderSamlSettings,
+    };
     use mas_storage::{
         clock::MockClock,
         upstream_oauth2::{
@@ -27,8 +30,8 @@ mod tests {
             UpstreamOAuthProviderParams, UpstreamOAuthProviderRepository,
             UpstreamOAuthSessionRepository,
         },
-        user::UserRepository,
-        Pagination, RepositoryAccess,
+        user::{BrowserSessionRepository, UserRepository},
+        Clock, Pagination, RepositoryAccess,
     };
     use oauth2_types::scope::{Scope, OPENID};
     use rand::SeedableRng;
@@ -56,11 +59,12 @@ mod tests {
                     issuer: "https://example.com/".to_owned(),
                     human_name: None,
                     brand_name: None,
-                    scope: Scope::from_iter([OPENID]),
-                    token_endpoint_auth_method:
+                    scope: Some(Scope::from_iter([OPENID])),
+                    token_endpoint_auth_method: Some(
                         mas_iana::oauth::OAuthClientAuthenticationMethod::None,
+                    ),
                     token_endpoint_signing_alg: None,
-                    client_id: "client-id".to_owned(),
+                    client_id: Some("client-id".to_owned()),
                     encrypted_client_secret: None,
                     claims_imports: UpstreamOAuthProviderClaimsImports::default(),
                     token_endpoint_override: None,
@@ -69,6 +73,7 @@ mod tests {
                     discovery_mode: mas_data_model::UpstreamOAuthProviderDiscoveryMode::Oidc,
                     pkce_mode: mas_data_model::UpstreamOAuthProviderPkceMode::Auto,
                     additional_authorization_parameters: Vec::new(),
+                    saml: None,
                 },
             )
             .await
@@ -82,13 +87,14 @@ mod tests {
             .unwrap()
             .expect("provider to be found in the database");
         assert_eq!(provider.issuer, "https://example.com/");
-        assert_eq!(provider.client_id, "client-id");
+        assert!(provider.is_oidc());
+        assert_eq!(provider.client_id.as_deref(), Some("client-id"));
 
         // It should be in the list of all providers
         let providers = repo.upstream_oauth_provider().all_enabled().await.unwrap();
         assert_eq!(providers.len(), 1);
         assert_eq!(providers[0].issuer, "https://example.com/");
-        assert_eq!(providers[0].client_id, "client-id");
+        assert_eq!(providers[0].client_id.as_deref(), Some("client-id"));
 
         // Start a session
         let session = repo
@@ -116,6 +122,8 @@ mod tests {
         assert!(session.is_pending());
         assert!(!session.is_completed());
         assert!(!session.is_consumed());
+        assert_eq!(session.nonce.as_deref(), Some("some-nonce"));
+        assert!(!session.is_saml());
 
         // Create a link
         let link = repo
@@ -298,11 +306,12 @@ mod tests {
                         issuer: ISSUER.to_owned(),
                         human_name: None,
                         brand_name: None,
-                        scope: scope.clone(),
-                        token_endpoint_auth_method:
+                        scope: Some(scope.clone()),
+                        token_endpoint_auth_method: Some(
                             mas_iana::oauth::OAuthClientAuthenticationMethod::None,
+                        ),
                         token_endpoint_signing_alg: None,
-                        client_id,
+                        client_id: Some(client_id),
                         encrypted_client_secret: None,
                         claims_imports: UpstreamOAuthProviderClaimsImports::default(),
                         token_endpoint_override: None,
@@ -311,6 +320,7 @@ mod tests {
                         discovery_mode: mas_data_model::UpstreamOAuthProviderDiscoveryMode::Oidc,
                         pkce_mode: mas_data_model::UpstreamOAuthProviderPkceMode::Auto,
                         additional_authorization_parameters: Vec::new(),
+                        saml: None,
                     },
                 )
                 .await
@@ -407,4 +417,296 @@ mod tests {
             .edges
             .is_empty());
     }
+
+    /// Test that the SAML settings of a provider and the attributes of a SAML
+    /// assertion are stored and loaded back
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_saml_provider(pool: PgPool) {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap();
+
+        let saml = UpstreamOAuthProviderSamlSettings {
+            sp_entity_id: "https://mas.example.com/".to_owned(),
+            idp_sso_url: "https://idp.example.com/sso".parse().unwrap(),
+            idp_certificates: vec!["MIIC...".to_owned()],
+            sso_binding: UpstreamOAuthProviderSamlBinding::Post,
+            name_id_format: None,
+        };
+
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &clock,
+                UpstreamOAuthProviderParams {
+                    issuer: "https://idp.example.com/metadata".to_owned(),
+                    human_name: None,
+                    brand_name: None,
+                    scope: None,
+                    token_endpoint_auth_method: None,
+                    token_endpoint_signing_alg: None,
+                    client_id: None,
+                    encrypted_client_secret: None,
+                    claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+                    token_endpoint_override: None,
+                    authorization_endpoint_override: None,
+                    jwks_uri_override: None,
+                    discovery_mode: mas_data_model::UpstreamOAuthProviderDiscoveryMode::Disabled,
+                    pkce_mode: mas_data_model::UpstreamOAuthProviderPkceMode::Disabled,
+                    additional_authorization_parameters: Vec::new(),
+                    saml: Some(saml.clone()),
+                },
+            )
+            .await
+            .unwrap();
+
+        let provider = repo
+            .upstream_oauth_provider()
+            .lookup(provider.id)
+            .await
+            .unwrap()
+            .expect("provider to be found in the database");
+        assert!(provider.is_saml());
+        assert_eq!(provider.saml.as_ref(), Some(&saml));
+        assert_eq!(provider.scope, None);
+        assert_eq!(provider.client_id, None);
+        assert_eq!(provider.token_endpoint_auth_method, None);
+
+        let page = repo
+            .upstream_oauth_provider()
+            .list(UpstreamOAuthProviderFilter::new(), Pagination::first(10))
+            .await
+            .unwrap();
+        assert_eq!(page.edges.len(), 1);
+        assert_eq!(page.edges[0].saml.as_ref(), Some(&saml));
+
+        let session = repo
+            .upstream_oauth_session()
+            .add_saml(
+                &mut rng,
+                &clock,
+                &provider,
+                "some-state".to_owned(),
+                "_some-request-id".to_owned(),
+            )
+            .await
+            .unwrap();
+
+        // The request ID is stored in its own column, not as the nonce
+        let session = repo
+            .upstream_oauth_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .expect("session to be found in the database");
+        assert!(session.is_saml());
+        assert_eq!(session.saml_request_id.as_deref(), Some("_some-request-id"));
+        assert_eq!(session.nonce, None);
+
+        let link = repo
+            .upstream_oauth_link()
+            .add(&mut rng, &clock, &provider, "a-subject".to_owned())
+            .await
+            .unwrap();
+
+        let attributes = serde_json::json!({
+            "sub": "a-subject",
+            "email": "alice@example.com",
+        });
+        let session = repo
+            .upstream_oauth_session()
+            .complete_with_saml_assertion(&clock, session, &link, attributes.clone())
+            .await
+            .unwrap();
+        assert_eq!(session.saml_attributes(), Some(&attributes));
+
+        // Reload the session
+        let session = repo
+            .upstream_oauth_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .expect("session to be found in the database");
+        assert!(session.is_completed());
+        assert_eq!(session.link_id(), Some(link.id));
+        assert_eq!(session.id_token(), None);
+        assert_eq!(session.saml_attributes(), Some(&attributes));
+
+        // The attributes are kept once the session is consumed
+        let session = repo
+            .upstream_oauth_session()
+            .consume(&clock, session)
+            .await
+            .unwrap();
+        assert_eq!(session.saml_attributes(), Some(&attributes));
+    }
+
+    /// Test the cleanup of stale upstream OAuth 2.0 sessions
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_session_cleanup(pool: PgPool) {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap();
+
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &clock,
+                UpstreamOAuthProviderParams {
+                    issuer: "https://example.com/".to_owned(),
+                    human_name: None,
+                    brand_name: None,
+                    scope: Some(Scope::from_iter([OPENID])),
+                    token_endpoint_auth_method: Some(
+                        mas_iana::oauth::OAuthClientAuthenticationMethod::None,
+                    ),
+                    token_endpoint_signing_alg: None,
+                    client_id: Some("client-id".to_owned()),
+                    encrypted_client_secret: None,
+                    claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+                    token_endpoint_override: None,
+                    authorization_endpoint_override: None,
+                    jwks_uri_override: None,
+                    discovery_mode: mas_data_model::UpstreamOAuthProviderDiscoveryMode::Oidc,
+                    pkce_mode: mas_data_model::UpstreamOAuthProviderPkceMode::Auto,
+                    additional_authorization_parameters: Vec::new(),
+                    saml: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "john".to_owned())
+            .await
+            .unwrap();
+        let link = repo
+            .upstream_oauth_link()
+            .add(&mut rng, &clock, &provider, "a-subject".to_owned())
+            .await
+            .unwrap();
+
+        // A completed session which was used to authenticate a browser session
+        let completed = repo
+            .upstream_oauth_session()
+            .add(
+                &mut rng,
+                &clock,
+                &provider,
+                "state1".to_owned(),
+                None,
+                "nonce1".to_owned(),
+            )
+            .await
+            .unwrap();
+        let completed = repo
+            .upstream_oauth_session()
+            .complete_with_link(&clock, completed, &link, None)
+            .await
+            .unwrap();
+        let browser_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+        repo.browser_session()
+            .authenticate_with_upstream(&mut rng, &clock, &browser_session, &completed)
+            .await
+            .unwrap();
+
+        // A session which was never completed
+        let pending = repo
+            .upstream_oauth_session()
+            .add(
+                &mut rng,
+                &clock,
+                &provider,
+                "state2".to_owned(),
+                None,
+                "nonce2".to_owned(),
+            )
+            .await
+            .unwrap();
+
+        clock.advance(Duration::try_hours(1).unwrap());
+        let before = clock.now();
+
+        // A session started at the cutoff is kept
+        let recent = repo
+            .upstream_oauth_session()
+            .add(
+                &mut rng,
+                &clock,
+                &provider,
+                "state3".to_owned(),
+                None,
+                "nonce3".to_owned(),
+            )
+            .await
+            .unwrap();
+
+        // Stale sessions are removed in batches
+        assert_eq!(
+            repo.upstream_oauth_session()
+                .cleanup_stale(before, 1)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.upstream_oauth_session()
+                .cleanup_stale(before, 1)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.upstream_oauth_session()
+                .cleanup_stale(before, 1)
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert!(repo
+            .upstream_oauth_session()
+            .lookup(completed.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .upstream_oauth_session()
+            .lookup(pending.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .upstream_oauth_session()
+            .lookup(recent.id)
+            .await
+            .unwrap()
+            .is_some());
+
+        // The link and the authentication are kept, the latter losing its
+        // reference to the session
+        assert!(repo
+            .upstream_oauth_link()
+            .lookup(link.id)
+            .await
+            .unwrap()
+            .is_some());
+        let authentication = repo
+            .browser_session()
+            .get_last_authentication(&browser_session)
+            .await
+            .unwrap()
+            .expect("authentication to be kept");
+        assert_eq!(
+            authentication.authentication_method,
+            AuthenticationMethod::Unknown
+        );
+    }
 }
@@ -6,7 +6,10 @@
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use mas_data_model::{UpstreamOAuthProvider, UpstreamOAuthProviderClaimsImports};
+use mas_data_model::{
+    UpstreamOAuthProvider, UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderKind,
+    UpstreamOAuthProviderSamlSettings,
+};
 use mas_storage::{
     upstream_oauth2::{
         UpstreamOAuthProviderFilter, UpstreamOAuthProviderParams, UpstreamOAuthProviderRepository,
@@ -48,14 +51,15 @@ impl<'c> PgUpstreamOAuthProviderRepository<'c> {
 #[enum_def]
 struct ProviderLookup {
     upstream_oauth_provider_id: Uuid,
+    kind: String,
     issuer: String,
     human_name: Option<String>,
     brand_name: Option<String>,
-    scope: String,
-    client_id: String,
+    scope: Option<String>,
+    client_id: Option<String>,
     encrypted_client_secret: Option<String>,
     token_endpoint_signing_alg: Option<String>,
-    token_endpoint_auth_method: String,
+    token_endpoint_auth_method: Option<String>,
     created_at: DateTime<Utc>,
     disabled_at: Option<DateTime<Utc>>,
     claims_imports: Json<UpstreamOAuthProviderClaimsImports>,
@@ -65,24 +69,42 @@ struct ProviderLookup {
     discovery_mode: String,
     pkce_mode: String,
     additional_parameters: Option<Json<Vec<(String, String)>>>,
+    saml: Option<Json<UpstreamOAuthProviderSamlSettings>>,
 }
 
 impl TryFrom<ProviderLookup> for UpstreamOAuthProvider {
     type Error = DatabaseInconsistencyError;
     fn try_from(value: ProviderLookup) -> Result<Self, Self::Error> {
         let id = value.upstream_oauth_provider_id.into();
-        let scope = value.scope.parse().map_err(|e| {
+        let kind = value.kind.parse().map_err(|e| {
             DatabaseInconsistencyError::on("upstream_oauth_providers")
-                .column("scope")
+                .column("kind")
                 .row(id)
                 .source(e)
         })?;
-        let token_endpoint_auth_method = value.token_endpoint_auth_method.parse().map_err(|e| {
+        let saml = value.saml.map(|Json(x)| x);
+        if (kind == UpstreamOAuthProviderKind::Saml) != saml.is_some() {
+            return Err(DatabaseInconsistencyError::on("upstream_oauth_providers")
+                .column("saml")
+                .row(id));
+        }
+
+        let scope = value.scope.map(|x| x.parse()).transpose().map_err(|e| {
             DatabaseInconsistencyError::on("upstream_oauth_providers")
-                .column("token_endpoint_auth_method")
+                .column("scope")
                 .row(id)
                 .source(e)
         })?;
+        let token_endpoint_auth_method = value
+            .token_endpoint_auth_method
+            .map(|x| x.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("upstream_oauth_providers")
+                    .column("token_endpoint_auth_method")
+                    .row(id)
+                    .source(e)
+            })?;
         let token_endpoint_signing_alg = value
             .token_endpoint_signing_alg
             .map(|x| x.parse())
@@ -148,6 +170,7 @@ impl TryFrom<ProviderLookup> for UpstreamOAuthProvider {
 
         Ok(UpstreamOAuthProvider {
             id,
+            kind,
             issuer: value.issuer,
             human_name: value.human_name,
             brand_name: value.brand_name,
@@ -165,6 +188,7 @@ impl TryFrom<ProviderLookup> for UpstreamOAuthProvider {
             discovery_mode,
             pkce_mode,
             additional_authorization_parameters,
+            saml,
         })
     }
 }
@@ -201,6 +225,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             r#"
                 SELECT
                     upstream_oauth_provider_id,
+                    kind,
                     issuer,
                     human_name,
                     brand_name,
@@ -217,7 +242,8 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                     token_endpoint_override,
                     discovery_mode,
                     pkce_mode,
-                    additional_parameters as "additional_parameters: Json<Vec<(String, String)>>"
+                    additional_parameters as "additional_parameters: Json<Vec<(String, String)>>",
+                    saml as "saml: Json<UpstreamOAuthProviderSamlSettings>"
                 FROM upstream_oauth_providers
                 WHERE upstream_oauth_provider_id = $1
             "#,
@@ -242,7 +268,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             db.query.text,
             upstream_oauth_provider.id,
             upstream_oauth_provider.issuer = %params.issuer,
-            upstream_oauth_provider.client_id = %params.client_id,
+            upstream_oauth_provider.client_id = params.client_id.as_deref(),
         ),
         err,
     )]
@@ -255,11 +281,13 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
         let created_at = clock.now();
         let id = Ulid::from_datetime_with_source(created_at.into(), rng);
         tracing::Span::current().record("upstream_oauth_provider.id", tracing::field::display(id));
+        let kind = params.kind();
 
         sqlx::query!(
             r#"
             INSERT INTO upstream_oauth_providers (
                 upstream_oauth_provider_id,
+                kind,
                 issuer,
                 human_name,
                 brand_name,
@@ -274,21 +302,26 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                 jwks_uri_override,
                 discovery_mode,
                 pkce_mode,
+                saml,
                 created_at
             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9,
-                      $10, $11, $12, $13, $14, $15, $16)
+                      $10, $11, $12, $13, $14, $15, $16, $17, $18)
         "#,
             Uuid::from(id),
+            kind.as_str(),
             &params.issuer,
             params.human_name.as_deref(),
             params.brand_name.as_deref(),
-            params.scope.to_string(),
-            params.token_endpoint_auth_method.to_string(),
+            params.scope.as_ref().map(ToString::to_string),
+            params
+                .token_endpoint_auth_method
+                .as_ref()
+                .map(ToString::to_string),
             params
                 .token_endpoint_signing_alg
                 .as_ref()
                 .map(ToString::to_string),
-            &params.client_id,
+            params.client_id.as_deref(),
             params.encrypted_client_secret.as_deref(),
             Json(&params.claims_imports) as _,
             params
@@ -302,6 +335,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             params.jwks_uri_override.as_ref().map(ToString::to_string),
             params.discovery_mode.as_str(),
             params.pkce_mode.as_str(),
+            params.saml.as_ref().map(Json) as _,
             created_at,
         )
         .traced()
@@ -310,6 +344,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
 
         Ok(UpstreamOAuthProvider {
             id,
+            kind,
             issuer: params.issuer,
             human_name: params.human_name,
             brand_name: params.brand_name,
@@ -327,6 +362,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             discovery_mode: params.discovery_mode,
             pkce_mode: params.pkce_mode,
             additional_authorization_parameters: params.additional_authorization_parameters,
+            saml: params.saml,
         })
     }
 
@@ -403,7 +439,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             db.query.text,
             upstream_oauth_provider.id = %id,
             upstream_oauth_provider.issuer = %params.issuer,
-            upstream_oauth_provider.client_id = %params.client_id,
+            upstream_oauth_provider.client_id = params.client_id.as_deref(),
         ),
         err,
     )]
@@ -414,11 +450,13 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
         params: UpstreamOAuthProviderParams,
     ) -> Result<UpstreamOAuthProvider, Self::Error> {
         let created_at = clock.now();
+        let kind = params.kind();
 
         let created_at = sqlx::query_scalar!(
             r#"
                 INSERT INTO upstream_oauth_providers (
                     upstream_oauth_provider_id,
+                    kind,
                     issuer,
                     human_name,
                     brand_name,
@@ -434,12 +472,14 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                     discovery_mode,
                     pkce_mode,
                     additional_parameters,
+                    saml,
                     created_at
                 ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9,
-                          $10, $11, $12, $13, $14, $15, $16, $17)
+                          $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
                 ON CONFLICT (upstream_oauth_provider_id)
                     DO UPDATE
                     SET
+                        kind = EXCLUDED.kind,
                         issuer = EXCLUDED.issuer,
                         human_name = EXCLUDED.human_name,
                         brand_name = EXCLUDED.brand_name,
@@ -455,20 +495,25 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                         jwks_uri_override = EXCLUDED.jwks_uri_override,
                         discovery_mode = EXCLUDED.discovery_mode,
                         pkce_mode = EXCLUDED.pkce_mode,
-                        additional_parameters = EXCLUDED.additional_parameters
+                        additional_parameters = EXCLUDED.additional_parameters,
+                        saml = EXCLUDED.saml
                 RETURNING created_at
             "#,
             Uuid::from(id),
+            kind.as_str(),
             &params.issuer,
             params.human_name.as_deref(),
             params.brand_name.as_deref(),
-            params.scope.to_string(),
-            params.token_endpoint_auth_method.to_string(),
+            params.scope.as_ref().map(ToString::to_string),
+            params
+                .token_endpoint_auth_method
+                .as_ref()
+                .map(ToString::to_string),
             params
                 .token_endpoint_signing_alg
                 .as_ref()
                 .map(ToString::to_string),
-            &params.client_id,
+            params.client_id.as_deref(),
             params.encrypted_client_secret.as_deref(),
             Json(&params.claims_imports) as _,
             params
@@ -483,6 +528,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             params.discovery_mode.as_str(),
             params.pkce_mode.as_str(),
             Json(&params.additional_authorization_parameters) as _,
+            params.saml.as_ref().map(Json) as _,
             created_at,
         )
         .traced()
@@ -491,6 +537,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
 
         Ok(UpstreamOAuthProvider {
             id,
+            kind,
             issuer: params.issuer,
             human_name: params.human_name,
             brand_name: params.brand_name,
@@ -508,6 +555,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             discovery_mode: params.discovery_mode,
             pkce_mode: params.pkce_mode,
             additional_authorization_parameters: params.additional_authorization_parameters,
+            saml: params.saml,
         })
     }
 
@@ -567,6 +615,10 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                 )),
                 ProviderLookupIden::UpstreamOauthProviderId,
             )
+            .expr_as(
+                Expr::col((UpstreamOAuthProviders::Table, UpstreamOAuthProviders::Kind)),
+                ProviderLookupIden::Kind,
+            )
             .expr_as(
                 Expr::col((
                     UpstreamOAuthProviders::Table,
@@ -683,6 +735,10 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                 )),
                 ProviderLookupIden::AdditionalParameters,
             )
+            .expr_as(
+                Expr::col((UpstreamOAuthProviders::Table, UpstreamOAuthProviders::Saml)),
+                ProviderLookupIden::Saml,
+            )
             .from(UpstreamOAuthProviders::Table)
             .apply_filter(filter)
             .generate_pagination(
@@ -754,6 +810,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             r#"
                 SELECT
                     upstream_oauth_provider_id,
+                    kind,
                     issuer,
                     human_name,
                     brand_name,
@@ -770,7 +827,8 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                     token_endpoint_override,
                     discovery_mode,
                     pkce_mode,
-                    additional_parameters as "additional_parameters: Json<Vec<(String, String)>>"
+                    additional_parameters as "additional_parameters: Json<Vec<(String, String)>>",
+                    saml as "saml: Json<UpstreamOAuthProviderSamlSettings>"
                 FROM upstream_oauth_providers
                 WHERE disabled_at IS NULL
             "#,
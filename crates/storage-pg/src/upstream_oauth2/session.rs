@@ -38,8 +38,10 @@ struct SessionLookup {
     upstream_oauth_link_id: Option<Uuid>,
     state: String,
     code_challenge_verifier: Option<String>,
-    nonce: String,
+    nonce: Option<String>,
+    saml_request_id: Option<String>,
     id_token: Option<String>,
+    saml_attributes: Option<serde_json::Value>,
     created_at: DateTime<Utc>,
     completed_at: Option<DateTime<Utc>>,
     consumed_at: Option<DateTime<Utc>>,
@@ -62,6 +64,7 @@ impl TryFrom<SessionLookup> for UpstreamOAuthAuthorizationSession {
                     completed_at,
                     link_id: link_id.into(),
                     id_token,
+                    saml_attributes: value.saml_attributes,
                 }
             }
             (Some(link_id), id_token, Some(completed_at), Some(consumed_at)) => {
@@ -69,6 +72,7 @@ impl TryFrom<SessionLookup> for UpstreamOAuthAuthorizationSession {
                     completed_at,
                     link_id: link_id.into(),
                     id_token,
+                    saml_attributes: value.saml_attributes,
                     consumed_at,
                 }
             }
@@ -84,6 +88,7 @@ impl TryFrom<SessionLookup> for UpstreamOAuthAuthorizationSession {
             provider_id: value.upstream_oauth_provider_id.into(),
             state_str: value.state,
             nonce: value.nonce,
+            saml_request_id: value.saml_request_id,
             code_challenge_verifier: value.code_challenge_verifier,
             created_at: value.created_at,
             state,
@@ -118,7 +123,9 @@ impl<'c> UpstreamOAuthSessionRepository for PgUpstreamOAuthSessionRepository<'c>
                     state,
                     code_challenge_verifier,
                     nonce,
+                    saml_request_id,
                     id_token,
+                    saml_attributes,
                     created_at,
                     completed_at,
                     consumed_at
@@ -143,7 +150,7 @@ impl<'c> UpstreamOAuthSessionRepository for PgUpstreamOAuthSessionRepository<'c>
             db.query.text,
             %upstream_oauth_provider.id,
             %upstream_oauth_provider.issuer,
-            %upstream_oauth_provider.client_id,
+            upstream_oauth_provider.client_id = upstream_oauth_provider.client_id.as_deref(),
             upstream_oauth_authorization_session.id,
         ),
         err,
@@ -195,7 +202,66 @@ impl<'c> UpstreamOAuthSessionRepository for PgUpstreamOAuthSessionRepository<'c>
             provider_id: upstream_oauth_provider.id,
             state_str,
             code_challenge_verifier,
-            nonce,
+            nonce: Some(nonce),
+            saml_request_id: None,
+            created_at,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.upstream_oauth_authorization_session.add_saml",
+        skip_all,
+        fields(
+            db.query.text,
+            %upstream_oauth_provider.id,
+            %upstream_oauth_provider.issuer,
+            upstream_oauth_authorization_session.id,
+        ),
+        err,
+    )]
+    async fn add_saml(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        upstream_oauth_provider: &UpstreamOAuthProvider,
+        state_str: String,
+        saml_request_id: String,
+    ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "upstream_oauth_authorization_session.id",
+            tracing::field::display(id),
+        );
+
+        sqlx::query!(
+            r#"
+                INSERT INTO upstream_oauth_authorization_sessions (
+                    upstream_oauth_authorization_session_id,
+                    upstream_oauth_provider_id,
+                    state,
+                    saml_request_id,
+                    created_at
+                ) VALUES ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::from(id),
+            Uuid::from(upstream_oauth_provider.id),
+            &state_str,
+            &saml_request_id,
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(UpstreamOAuthAuthorizationSession {
+            id,
+            state: UpstreamOAuthAuthorizationSessionState::default(),
+            provider_id: upstream_oauth_provider.id,
+            state_str,
+            code_challenge_verifier: None,
+            nonce: None,
+            saml_request_id: Some(saml_request_id),
             created_at,
         })
     }
@@ -243,6 +309,49 @@ impl<'c> UpstreamOAuthSessionRepository for PgUpstreamOAuthSessionRepository<'c>
         Ok(upstream_oauth_authorization_session)
     }
 
+    #[tracing::instrument(
+        name = "db.upstream_oauth_authorization_session.complete_with_saml_assertion",
+        skip_all,
+        fields(
+            db.query.text,
+            %upstream_oauth_authorization_session.id,
+            %upstream_oauth_link.id,
+        ),
+        err,
+    )]
+    async fn complete_with_saml_assertion(
+        &mut self,
+        clock: &dyn Clock,
+        upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
+        upstream_oauth_link: &UpstreamOAuthLink,
+        saml_attributes: serde_json::Value,
+    ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error> {
+        let completed_at = clock.now();
+
+        sqlx::query!(
+            r#"
+                UPDATE upstream_oauth_authorization_sessions
+                SET upstream_oauth_link_id = $1,
+                    completed_at = $2,
+                    saml_attributes = $3
+                WHERE upstream_oauth_authorization_session_id = $4
+            "#,
+            Uuid::from(upstream_oauth_link.id),
+            completed_at,
+            &saml_attributes,
+            Uuid::from(upstream_oauth_authorization_session.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        let upstream_oauth_authorization_session = upstream_oauth_authorization_session
+            .complete_with_saml_attributes(completed_at, upstream_oauth_link, saml_attributes)
+            .map_err(DatabaseError::to_invalid_operation)?;
+
+        Ok(upstream_oauth_authorization_session)
+    }
+
     /// Mark a session as consumed
     #[tracing::instrument(
         name = "db.upstream_oauth_authorization_session.consume",
@@ -278,4 +387,38 @@ impl<'c> UpstreamOAuthSessionRepository for PgUpstreamOAuthSessionRepository<'c>
 
         Ok(upstream_oauth_authorization_session)
     }
+
+    #[tracing::instrument(
+        name = "db.upstream_oauth_authorization_session.cleanup_stale",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_stale(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM upstream_oauth_authorization_sessions
+                WHERE upstream_oauth_authorization_session_id IN (
+                    SELECT upstream_oauth_authorization_session_id
+                    FROM upstream_oauth_authorization_sessions
+                    WHERE created_at < $1
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+            "#,
+            before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
 }
@@ -29,18 +29,26 @@ use crate::{
 };
 
 mod email;
+mod ldap;
+mod passkey;
 mod password;
 mod recovery;
 mod session;
 mod terms;
+mod totp;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::{
-    email::PgUserEmailRepository, password::PgUserPasswordRepository,
-    recovery::PgUserRecoveryRepository, session::PgBrowserSessionRepository,
+    email::PgUserEmailRepository,
+    ldap::PgUserLdapLinkRepository,
+    passkey::PgUserPasskeyRepository,
+    password::PgUserPasswordRepository,
+    recovery::PgUserRecoveryRepository,
+    session::PgBrowserSessionRepository,
     terms::PgUserTermsRepository,
+    totp::{PgUserTotpRecoveryCodeRepository, PgUserTotpRepository},
 };
 
 /// An implementation of [`UserRepository`] for a PostgreSQL connection
@@ -0,0 +1,384 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::{User, UserPasskey};
+use mas_storage::{
+    user::{UserPasskeyFilter, UserPasskeyRepository},
+    Clock, Page, Pagination,
+};
+use rand::RngCore;
+use sea_query::{enum_def, Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{
+    filter::{Filter, StatementExt},
+    iden::UserPasskeys,
+    pagination::QueryBuilderExt,
+    tracing::ExecuteExt,
+    DatabaseError,
+};
+
+/// An implementation of [`UserPasskeyRepository`] for a PostgreSQL connection
+pub struct PgUserPasskeyRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserPasskeyRepository<'c> {
+    /// Create a new [`PgUserPasskeyRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+#[enum_def]
+struct UserPasskeyLookup {
+    user_passkey_id: Uuid,
+    user_id: Uuid,
+    credential_id: String,
+    name: String,
+    credential: String,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserPasskeyLookup> for UserPasskey {
+    fn from(value: UserPasskeyLookup) -> Self {
+        Self {
+            id: value.user_passkey_id.into(),
+            user_id: value.user_id.into(),
+            credential_id: value.credential_id,
+            name: value.name,
+            credential: value.credential,
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
+
+impl Filter for UserPasskeyFilter<'_> {
+    fn generate_condition(&self, _has_joins: bool) -> impl sea_query::IntoCondition {
+        sea_query::Condition::all().add_option(self.user().map(|user| {
+            Expr::col((UserPasskeys::Table, UserPasskeys::UserId)).eq(Uuid::from(user.id))
+        }))
+    }
+}
+
+#[async_trait]
+impl<'c> UserPasskeyRepository for PgUserPasskeyRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_passkey.lookup",
+        skip_all,
+        fields(
+            db.query.text,
+            user_passkey.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserPasskey>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserPasskeyLookup,
+            r#"
+                SELECT user_passkey_id
+                     , user_id
+                     , credential_id
+                     , name
+                     , credential
+                     , created_at
+                     , last_used_at
+                FROM user_passkeys
+                WHERE user_passkey_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_passkey.find_by_credential_id",
+        skip_all,
+        fields(
+            db.query.text,
+            user_passkey.credential_id = credential_id,
+        ),
+        err,
+    )]
+    async fn find_by_credential_id(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<Option<UserPasskey>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserPasskeyLookup,
+            r#"
+                SELECT user_passkey_id
+                     , user_id
+                     , credential_id
+                     , name
+                     , credential
+                     , created_at
+                     , last_used_at
+                FROM user_passkeys
+                WHERE credential_id = $1
+            "#,
+            credential_id,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_passkey.all",
+        skip_all,
+        fields(
+            db.query.text,
+            %user.id,
+            %user.username,
+        ),
+        err,
+    )]
+    async fn all(&mut self, user: &User) -> Result<Vec<UserPasskey>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserPasskeyLookup,
+            r#"
+                SELECT user_passkey_id
+                     , user_id
+                     , credential_id
+                     , name
+                     , credential
+                     , created_at
+                     , last_used_at
+                FROM user_passkeys
+                WHERE user_id = $1
+                ORDER BY created_at ASC
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
+
+    #[tracing::instrument(
+        name = "db.user_passkey.list",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn list(
+        &mut self,
+        filter: UserPasskeyFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<UserPasskey>, DatabaseError> {
+        let (sql, arguments) = Query::select()
+            .expr_as(
+                Expr::col((UserPasskeys::Table, UserPasskeys::UserPasskeyId)),
+                UserPasskeyLookupIden::UserPasskeyId,
+            )
+            .expr_as(
+                Expr::col((UserPasskeys::Table, UserPasskeys::UserId)),
+                UserPasskeyLookupIden::UserId,
+            )
+            .expr_as(
+                Expr::col((UserPasskeys::Table, UserPasskeys::CredentialId)),
+                UserPasskeyLookupIden::CredentialId,
+            )
+            .expr_as(
+                Expr::col((UserPasskeys::Table, UserPasskeys::Name)),
+                UserPasskeyLookupIden::Name,
+            )
+            .expr_as(
+                Expr::col((UserPasskeys::Table, UserPasskeys::Credential)),
+                UserPasskeyLookupIden::Credential,
+            )
+            .expr_as(
+                Expr::col((UserPasskeys::Table, UserPasskeys::CreatedAt)),
+                UserPasskeyLookupIden::CreatedAt,
+            )
+            .expr_as(
+                Expr::col((UserPasskeys::Table, UserPasskeys::LastUsedAt)),
+                UserPasskeyLookupIden::LastUsedAt,
+            )
+            .from(UserPasskeys::Table)
+            .apply_filter(filter)
+            .generate_pagination(
+                (UserPasskeys::Table, UserPasskeys::UserPasskeyId),
+                pagination,
+            )
+            .build_sqlx(PostgresQueryBuilder);
+
+        let edges: Vec<UserPasskeyLookup> = sqlx::query_as_with(&sql, arguments)
+            .traced()
+            .fetch_all(&mut *self.conn)
+            .await?;
+
+        let page = pagination.process(edges).map(UserPasskey::from);
+
+        Ok(page)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_passkey.count",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn count(&mut self, filter: UserPasskeyFilter<'_>) -> Result<usize, Self::Error> {
+        let (sql, arguments) = Query::select()
+            .expr(Expr::col((UserPasskeys::Table, UserPasskeys::UserPasskeyId)).count())
+            .from(UserPasskeys::Table)
+            .apply_filter(filter)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: i64 = sqlx::query_scalar_with(&sql, arguments)
+            .traced()
+            .fetch_one(&mut *self.conn)
+            .await?;
+
+        count
+            .try_into()
+            .map_err(DatabaseError::to_invalid_operation)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_passkey.add",
+        skip_all,
+        fields(
+            db.query.text,
+            %user.id,
+            %user.username,
+            user_passkey.id,
+            user_passkey.name = name,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        name: String,
+        credential_id: String,
+        credential: String,
+    ) -> Result<UserPasskey, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("user_passkey.id", tracing::field::display(id));
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_passkeys
+                    (user_passkey_id, user_id, credential_id, name, credential, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user.id),
+            &credential_id,
+            &name,
+            &credential,
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(UserPasskey {
+            id,
+            user_id: user.id,
+            credential_id,
+            name,
+            credential,
+            created_at,
+            last_used_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.user_passkey.record_use",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_passkey.id,
+            user.id = %user_passkey.user_id,
+        ),
+        err,
+    )]
+    async fn record_use(
+        &mut self,
+        clock: &dyn Clock,
+        mut user_passkey: UserPasskey,
+        credential: String,
+    ) -> Result<UserPasskey, Self::Error> {
+        let last_used_at = clock.now();
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_passkeys
+                SET credential = $2
+                  , last_used_at = $3
+                WHERE user_passkey_id = $1
+            "#,
+            Uuid::from(user_passkey.id),
+            &credential,
+            last_used_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user_passkey.credential = credential;
+        user_passkey.last_used_at = Some(last_used_at);
+        Ok(user_passkey)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_passkey.remove",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_passkey.id,
+            user.id = %user_passkey.user_id,
+        ),
+        err,
+    )]
+    async fn remove(&mut self, user_passkey: UserPasskey) -> Result<(), Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM user_passkeys
+                WHERE user_passkey_id = $1
+            "#,
+            Uuid::from(user_passkey.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(())
+    }
+}
@@ -326,4 +326,38 @@ impl<'c> UserRecoveryRepository for PgUserRecoveryRepository<'c> {
 
         Ok(user_recovery_session)
     }
+
+    #[tracing::instrument(
+        name = "db.user_recovery.cleanup_sessions",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_sessions(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM user_recovery_sessions
+                WHERE user_recovery_session_id IN (
+                    SELECT user_recovery_session_id
+                    FROM user_recovery_sessions
+                    WHERE created_at < $1
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+            "#,
+            before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
 }
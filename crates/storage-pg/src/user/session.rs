@@ -10,7 +10,8 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mas_data_model::{
     Authentication, AuthenticationMethod, BrowserSession, Password,
-    UpstreamOAuthAuthorizationSession, User, UserAgent,
+    UpstreamOAuthAuthorizationSession, User, UserAgent, UserLdapLink, UserPasskey,
+    UserTotpRecoveryCode, UserTotpSecret,
 };
 use mas_storage::{
     user::{BrowserSessionFilter, BrowserSessionRepository},
@@ -95,6 +96,11 @@ struct AuthenticationLookup {
     created_at: DateTime<Utc>,
     user_password_id: Option<Uuid>,
     upstream_oauth_authorization_session_id: Option<Uuid>,
+    user_totp_secret_id: Option<Uuid>,
+    user_totp_recovery_code_id: Option<Uuid>,
+    user_passkey_id: Option<Uuid>,
+    user_ldap_link_id: Option<Uuid>,
+    after_password: bool,
 }
 
 impl TryFrom<AuthenticationLookup> for Authentication {
@@ -107,12 +113,39 @@ impl TryFrom<AuthenticationLookup> for Authentication {
             value
                 .upstream_oauth_authorization_session_id
                 .map(Into::into),
+            value.user_totp_secret_id.map(Into::into),
+            value.user_totp_recovery_code_id.map(Into::into),
+            value.user_passkey_id.map(Into::into),
+            value.user_ldap_link_id.map(Into::into),
         ) {
-            (Some(user_password_id), None) => AuthenticationMethod::Password { user_password_id },
-            (None, Some(upstream_oauth2_session_id)) => AuthenticationMethod::UpstreamOAuth2 {
-                upstream_oauth2_session_id,
-            },
-            (None, None) => AuthenticationMethod::Unknown,
+            (Some(user_password_id), None, None, None, None, None) => {
+                AuthenticationMethod::Password { user_password_id }
+            }
+            (None, Some(upstream_oauth2_session_id), None, None, None, None) => {
+                AuthenticationMethod::UpstreamOAuth2 {
+                    upstream_oauth2_session_id,
+                }
+            }
+            (None, None, Some(user_totp_secret_id), None, None, None) => {
+                AuthenticationMethod::Totp {
+                    user_totp_secret_id,
+                }
+            }
+            (None, None, None, Some(user_totp_recovery_code_id), None, None) => {
+                AuthenticationMethod::TotpRecoveryCode {
+                    user_totp_recovery_code_id,
+                }
+            }
+            (None, None, None, None, Some(user_passkey_id), None) => {
+                AuthenticationMethod::Passkey {
+                    user_passkey_id,
+                    after_password: value.after_password,
+                }
+            }
+            (None, None, None, None, None, Some(user_ldap_link_id)) => {
+                AuthenticationMethod::Ldap { user_ldap_link_id }
+            }
+            (None, None, None, None, None, None) => AuthenticationMethod::Unknown,
             _ => {
                 return Err(DatabaseInconsistencyError::on("user_session_authentications").row(id));
             }
@@ -145,6 +178,26 @@ impl crate::filter::Filter for BrowserSessionFilter<'_> {
             .add_option(self.last_active_before().map(|last_active_before| {
                 Expr::col((UserSessions::Table, UserSessions::LastActiveAt)).lt(last_active_before)
             }))
+            .add_option(self.created_before().map(|created_before| {
+                Expr::col((UserSessions::Table, UserSessions::CreatedAt)).lt(created_before)
+            }))
+            .add_option(self.inactive_since().map(|inactive_since| {
+                // Sessions which were never active are considered active when
+                // they were created
+                sea_query::Condition::any()
+                    .add(
+                        Expr::col((UserSessions::Table, UserSessions::LastActiveAt))
+                            .lt(inactive_since),
+                    )
+                    .add(
+                        Expr::col((UserSessions::Table, UserSessions::LastActiveAt))
+                            .is_null()
+                            .and(
+                                Expr::col((UserSessions::Table, UserSessions::CreatedAt))
+                                    .lt(inactive_since),
+                            ),
+                    )
+            }))
     }
 }
 
@@ -515,6 +568,205 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
         })
     }
 
+    #[tracing::instrument(
+        name = "db.browser_session.authenticate_with_totp",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_session.id,
+            %user_totp_secret.id,
+            user_session_authentication.id,
+        ),
+        err,
+    )]
+    async fn authenticate_with_totp(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_totp_secret: &UserTotpSecret,
+    ) -> Result<Authentication, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "user_session_authentication.id",
+            tracing::field::display(id),
+        );
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_session_authentications
+                    (user_session_authentication_id, user_session_id, created_at, user_totp_secret_id)
+                VALUES ($1, $2, $3, $4)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_session.id),
+            created_at,
+            Uuid::from(user_totp_secret.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(Authentication {
+            id,
+            created_at,
+            authentication_method: AuthenticationMethod::Totp {
+                user_totp_secret_id: user_totp_secret.id,
+            },
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.browser_session.authenticate_with_totp_recovery_code",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_session.id,
+            %user_totp_recovery_code.id,
+            user_session_authentication.id,
+        ),
+        err,
+    )]
+    async fn authenticate_with_totp_recovery_code(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_totp_recovery_code: &UserTotpRecoveryCode,
+    ) -> Result<Authentication, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "user_session_authentication.id",
+            tracing::field::display(id),
+        );
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_session_authentications
+                    (user_session_authentication_id, user_session_id, created_at, user_totp_recovery_code_id)
+                VALUES ($1, $2, $3, $4)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_session.id),
+            created_at,
+            Uuid::from(user_totp_recovery_code.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(Authentication {
+            id,
+            created_at,
+            authentication_method: AuthenticationMethod::TotpRecoveryCode {
+                user_totp_recovery_code_id: user_totp_recovery_code.id,
+            },
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.browser_session.authenticate_with_passkey",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_session.id,
+            %user_passkey.id,
+            user_session_authentication.id,
+        ),
+        err,
+    )]
+    async fn authenticate_with_passkey(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_passkey: &UserPasskey,
+        after_password: bool,
+    ) -> Result<Authentication, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "user_session_authentication.id",
+            tracing::field::display(id),
+        );
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_session_authentications
+                    (user_session_authentication_id, user_session_id, created_at, user_passkey_id, after_password)
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_session.id),
+            created_at,
+            Uuid::from(user_passkey.id),
+            after_password,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(Authentication {
+            id,
+            created_at,
+            authentication_method: AuthenticationMethod::Passkey {
+                user_passkey_id: user_passkey.id,
+                after_password,
+            },
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.browser_session.authenticate_with_ldap",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_session.id,
+            %user_ldap_link.id,
+            user_session_authentication.id,
+        ),
+        err,
+    )]
+    async fn authenticate_with_ldap(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_ldap_link: &UserLdapLink,
+    ) -> Result<Authentication, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "user_session_authentication.id",
+            tracing::field::display(id),
+        );
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_session_authentications
+                    (user_session_authentication_id, user_session_id, created_at, user_ldap_link_id)
+                VALUES ($1, $2, $3, $4)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_session.id),
+            created_at,
+            Uuid::from(user_ldap_link.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(Authentication {
+            id,
+            created_at,
+            authentication_method: AuthenticationMethod::Ldap {
+                user_ldap_link_id: user_ldap_link.id,
+            },
+        })
+    }
+
     #[tracing::instrument(
         name = "db.browser_session.get_last_authentication",
         skip_all,
@@ -535,6 +787,11 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
                      , created_at
                      , user_password_id
                      , upstream_oauth_authorization_session_id
+                     , user_totp_secret_id
+                     , user_totp_recovery_code_id
+                     , user_passkey_id
+                     , user_ldap_link_id
+                     , after_password
                 FROM user_session_authentications
                 WHERE user_session_id = $1
                 ORDER BY created_at DESC
@@ -600,4 +857,53 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
 
         Ok(())
     }
+
+    #[tracing::instrument(
+        name = "db.browser_session.cleanup_finished",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                WITH sessions AS (
+                    SELECT user_session_id
+                    FROM user_sessions
+                    WHERE finished_at < $1
+                      AND NOT EXISTS (
+                        SELECT 1
+                        FROM oauth2_sessions
+                        WHERE oauth2_sessions.user_session_id = user_sessions.user_session_id
+                      )
+                      AND NOT EXISTS (
+                        SELECT 1
+                        FROM oauth2_device_code_grant
+                        WHERE oauth2_device_code_grant.user_session_id = user_sessions.user_session_id
+                      )
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                ),
+                authentications AS (
+                    DELETE FROM user_session_authentications
+                    WHERE user_session_id IN (SELECT user_session_id FROM sessions)
+                )
+                DELETE FROM user_sessions
+                WHERE user_session_id IN (SELECT user_session_id FROM sessions)
+            "#,
+            before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
 }
@@ -5,13 +5,15 @@
 // Please see LICENSE in the repository root for full details.
 
 use chrono::Duration;
+use mas_data_model::{AuthenticationMethod, UserAgent};
 use mas_storage::{
     clock::MockClock,
     user::{
         BrowserSessionFilter, BrowserSessionRepository, UserEmailFilter, UserEmailRepository,
-        UserFilter, UserPasswordRepository, UserRepository,
+        UserFilter, UserLdapLinkRepository, UserPasskeyFilter, UserPasskeyRepository,
+        UserPasswordRepository, UserRepository, UserTotpRecoveryCodeRepository, UserTotpRepository,
     },
-    Pagination, RepositoryAccess,
+    Clock, Pagination, RepositoryAccess,
 };
 use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
@@ -661,6 +663,65 @@ async fn test_user_session(pool: PgPool) {
     assert_eq!(repo.browser_session().count(finished).await.unwrap(), 11);
 }
 
+/// Test the filters used to find expired browser sessions
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_session_expiration_filters(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let alice = repo
+        .user()
+        .add(&mut rng, &clock, "alice".to_owned())
+        .await
+        .unwrap();
+
+    // One session which is never used, and one which is used later on
+    let unused = repo
+        .browser_session()
+        .add(&mut rng, &clock, &alice, None)
+        .await
+        .unwrap();
+    let used = repo
+        .browser_session()
+        .add(&mut rng, &clock, &alice, None)
+        .await
+        .unwrap();
+
+    clock.advance(Duration::try_days(10).unwrap());
+    repo.browser_session()
+        .record_batch_activity(vec![(used.id, clock.now(), None)])
+        .await
+        .unwrap();
+
+    clock.advance(Duration::try_days(1).unwrap());
+    let now = clock.now();
+
+    // Only the unused session was inactive for more than 5 days
+    let filter =
+        BrowserSessionFilter::new().with_inactive_since(now - Duration::try_days(5).unwrap());
+    let page = repo
+        .browser_session()
+        .list(filter, Pagination::first(10))
+        .await
+        .unwrap();
+    assert_eq!(page.edges.len(), 1);
+    assert_eq!(page.edges[0].id, unused.id);
+
+    // Both sessions were inactive for more than 12 hours
+    let filter =
+        BrowserSessionFilter::new().with_inactive_since(now - Duration::try_hours(12).unwrap());
+    assert_eq!(repo.browser_session().count(filter).await.unwrap(), 2);
+
+    // Both sessions were created more than 5 days ago, but not more than 20
+    let filter =
+        BrowserSessionFilter::new().with_created_before(now - Duration::try_days(5).unwrap());
+    assert_eq!(repo.browser_session().count(filter).await.unwrap(), 2);
+    let filter =
+        BrowserSessionFilter::new().with_created_before(now - Duration::try_days(20).unwrap());
+    assert_eq!(repo.browser_session().count(filter).await.unwrap(), 0);
+}
+
 #[sqlx::test(migrator = "crate::MIGRATOR")]
 async fn test_user_terms(pool: PgPool) {
     let mut repo = PgRepository::from_pool(&pool).await.unwrap();
@@ -715,3 +776,576 @@ async fn test_user_terms(pool: PgPool) {
         .unwrap();
     assert_eq!(res, 2);
 }
+
+/// Test the user TOTP and recovery code repositories
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_totp(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    // The user should have no TOTP secret
+    assert!(repo.user_totp().active(&user).await.unwrap().is_none());
+
+    // Add a secret, it should not be active until confirmed
+    let secret = repo
+        .user_totp()
+        .add(&mut rng, &clock, &user, "encrypted".to_owned())
+        .await
+        .unwrap();
+    assert!(!secret.is_confirmed());
+    assert!(repo.user_totp().active(&user).await.unwrap().is_none());
+
+    let secret_lookup = repo
+        .user_totp()
+        .lookup(secret.id)
+        .await
+        .unwrap()
+        .expect("secret should be found");
+    assert_eq!(secret_lookup, secret);
+
+    let secret = repo.user_totp().confirm(&clock, secret).await.unwrap();
+    assert!(secret.is_confirmed());
+    let active = repo
+        .user_totp()
+        .active(&user)
+        .await
+        .unwrap()
+        .expect("user should have an active secret");
+    assert_eq!(active.id, secret.id);
+
+    // Record a use
+    let secret = repo
+        .user_totp()
+        .record_use(secret, 1234)
+        .await
+        .unwrap()
+        .expect("the time step should not have been used yet");
+    assert_eq!(secret.last_used_step, Some(1234));
+    let active = repo.user_totp().active(&user).await.unwrap().unwrap();
+    assert_eq!(active.last_used_step, Some(1234));
+
+    // Recording the same or an earlier step again should be refused
+    let replayed = repo
+        .user_totp()
+        .record_use(secret.clone(), 1234)
+        .await
+        .unwrap();
+    assert!(replayed.is_none());
+    let replayed = repo
+        .user_totp()
+        .record_use(secret.clone(), 1233)
+        .await
+        .unwrap();
+    assert!(replayed.is_none());
+    let active = repo.user_totp().active(&user).await.unwrap().unwrap();
+    assert_eq!(active.last_used_step, Some(1234));
+
+    // Add recovery codes
+    let codes = repo
+        .user_totp_recovery_code()
+        .replace_all(
+            &mut rng,
+            &clock,
+            &secret,
+            vec!["first".to_owned(), "second".to_owned()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(codes.len(), 2);
+    assert_eq!(
+        repo.user_totp_recovery_code()
+            .count_unused(&secret)
+            .await
+            .unwrap(),
+        2
+    );
+
+    // Consume one of them
+    let code = repo
+        .user_totp_recovery_code()
+        .find_unused(&secret, "first")
+        .await
+        .unwrap()
+        .expect("code should be found");
+    let code = repo
+        .user_totp_recovery_code()
+        .consume(&clock, code)
+        .await
+        .unwrap();
+    assert!(code.consumed_at.is_some());
+    assert!(repo
+        .user_totp_recovery_code()
+        .find_unused(&secret, "first")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        repo.user_totp_recovery_code()
+            .count_unused(&secret)
+            .await
+            .unwrap(),
+        1
+    );
+
+    // Authentications with the second factor should be recorded on sessions
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_totp(&mut rng, &clock, &session, &secret)
+        .await
+        .unwrap();
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::Totp {
+            user_totp_secret_id: secret.id
+        }
+    );
+
+    clock.advance(Duration::microseconds(10 * 1000 * 1000));
+    repo.browser_session()
+        .authenticate_with_totp_recovery_code(&mut rng, &clock, &session, &code)
+        .await
+        .unwrap();
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::TotpRecoveryCode {
+            user_totp_recovery_code_id: code.id
+        }
+    );
+
+    // Replacing the codes should drop the unused ones
+    repo.user_totp_recovery_code()
+        .replace_all(&mut rng, &clock, &secret, vec!["third".to_owned()])
+        .await
+        .unwrap();
+    assert!(repo
+        .user_totp_recovery_code()
+        .find_unused(&secret, "second")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        repo.user_totp_recovery_code()
+            .count_unused(&secret)
+            .await
+            .unwrap(),
+        1
+    );
+
+    // Confirming a second secret replaces the first one
+    clock.advance(Duration::microseconds(10 * 1000 * 1000));
+    let second_secret = repo
+        .user_totp()
+        .add(&mut rng, &clock, &user, "encrypted2".to_owned())
+        .await
+        .unwrap();
+    let second_secret = repo
+        .user_totp()
+        .confirm(&clock, second_secret)
+        .await
+        .unwrap();
+    assert!(repo.user_totp().lookup(secret.id).await.unwrap().is_none());
+    let active = repo.user_totp().active(&user).await.unwrap().unwrap();
+    assert_eq!(active.id, second_secret.id);
+
+    // Removing it disables the second factor
+    repo.user_totp()
+        .remove(&clock, second_secret)
+        .await
+        .unwrap();
+    assert!(repo.user_totp().active(&user).await.unwrap().is_none());
+
+    repo.save().await.unwrap();
+}
+
+/// Test the user passkey repository, by registering, using and removing a
+/// passkey
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_passkey(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    let all = UserPasskeyFilter::new().for_user(&user);
+    assert_eq!(repo.user_passkey().count(all).await.unwrap(), 0);
+    assert!(repo.user_passkey().all(&user).await.unwrap().is_empty());
+
+    let passkey = repo
+        .user_passkey()
+        .add(
+            &mut rng,
+            &clock,
+            &user,
+            "My laptop".to_owned(),
+            "Y3JlZGVudGlhbA".to_owned(),
+            "{}".to_owned(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(passkey.name, "My laptop");
+    assert!(passkey.last_used_at.is_none());
+
+    assert_eq!(repo.user_passkey().count(all).await.unwrap(), 1);
+    let page = repo
+        .user_passkey()
+        .list(all, Pagination::first(10))
+        .await
+        .unwrap();
+    assert_eq!(page.edges, vec![passkey.clone()]);
+
+    let lookup = repo
+        .user_passkey()
+        .lookup(passkey.id)
+        .await
+        .unwrap()
+        .expect("passkey should be found");
+    assert_eq!(lookup, passkey);
+
+    let found = repo
+        .user_passkey()
+        .find_by_credential_id("Y3JlZGVudGlhbA")
+        .await
+        .unwrap()
+        .expect("passkey should be found by its credential ID");
+    assert_eq!(found, passkey);
+
+    // Unknown credentials are not found
+    assert!(repo
+        .user_passkey()
+        .find_by_credential_id("b3RoZXI")
+        .await
+        .unwrap()
+        .is_none());
+
+    // Using the passkey updates the credential
+    clock.advance(Duration::microseconds(10 * 1000 * 1000));
+    let passkey = repo
+        .user_passkey()
+        .record_use(&clock, passkey, r#"{"counter":1}"#.to_owned())
+        .await
+        .unwrap();
+    assert_eq!(passkey.last_used_at, Some(clock.now()));
+    let lookup = repo
+        .user_passkey()
+        .lookup(passkey.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(lookup.credential, r#"{"counter":1}"#);
+
+    // It can be used to authenticate a browser session
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_passkey(&mut rng, &clock, &session, &passkey, false)
+        .await
+        .unwrap();
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::Passkey {
+            user_passkey_id: passkey.id,
+            after_password: false,
+        }
+    );
+    assert_eq!(authentication.authentication_method.amr(), &["hwk", "mfa"]);
+
+    // Or as a second factor after the password
+    clock.advance(Duration::try_seconds(1).unwrap());
+    repo.browser_session()
+        .authenticate_with_passkey(&mut rng, &clock, &session, &passkey, true)
+        .await
+        .unwrap();
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method.amr(),
+        &["pwd", "hwk", "mfa"]
+    );
+
+    // Removing it keeps the authentication around, without the passkey
+    repo.user_passkey().remove(passkey.clone()).await.unwrap();
+    assert!(repo
+        .user_passkey()
+        .lookup(passkey.id)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(repo.user_passkey().count(all).await.unwrap(), 0);
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::Unknown
+    );
+
+    repo.save().await.unwrap();
+}
+
+/// Test the user LDAP link repository
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_ldap_link(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    assert!(repo
+        .user_ldap_link()
+        .find_by_subject("3f8d2a7e-1f0e-4a4b-8d61-2b7ad6a8e0f1")
+        .await
+        .unwrap()
+        .is_none());
+
+    let link = repo
+        .user_ldap_link()
+        .add(
+            &mut rng,
+            &clock,
+            &user,
+            "3f8d2a7e-1f0e-4a4b-8d61-2b7ad6a8e0f1".to_owned(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(link.user_id, user.id);
+
+    let lookup = repo
+        .user_ldap_link()
+        .lookup(link.id)
+        .await
+        .unwrap()
+        .expect("link should be found");
+    assert_eq!(lookup, link);
+
+    let found = repo
+        .user_ldap_link()
+        .find_by_subject("3f8d2a7e-1f0e-4a4b-8d61-2b7ad6a8e0f1")
+        .await
+        .unwrap()
+        .expect("link should be found by its subject");
+    assert_eq!(found, link);
+
+    // The same directory entry can't be linked twice
+    let other = repo
+        .user()
+        .add(&mut rng, &clock, "jane".to_owned())
+        .await
+        .unwrap();
+    assert!(repo
+        .user_ldap_link()
+        .add(
+            &mut rng,
+            &clock,
+            &other,
+            "3f8d2a7e-1f0e-4a4b-8d61-2b7ad6a8e0f1".to_owned(),
+        )
+        .await
+        .is_err());
+}
+
+/// Test authenticating a browser session with an LDAP link
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_authenticate_with_ldap(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    let link = repo
+        .user_ldap_link()
+        .add(&mut rng, &clock, &user, "uid=john,ou=people".to_owned())
+        .await
+        .unwrap();
+
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_ldap(&mut rng, &clock, &session, &link)
+        .await
+        .unwrap();
+
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::Ldap {
+            user_ldap_link_id: link.id
+        }
+    );
+    assert_eq!(authentication.authentication_method.amr(), &["pwd"]);
+
+    repo.save().await.unwrap();
+}
+
+/// Test the cleanup of old user recovery sessions
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_recovery_cleanup(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+    let user_email = repo
+        .user_email()
+        .add(&mut rng, &clock, &user, "john@example.com".to_owned())
+        .await
+        .unwrap();
+
+    // Two sessions with a ticket each
+    let mut old_sessions = Vec::new();
+    for ticket in ["ticket1", "ticket2"] {
+        let session = repo
+            .user_recovery()
+            .add_session(
+                &mut rng,
+                &clock,
+                "john@example.com".to_owned(),
+                UserAgent::parse("Mozilla/5.0".to_owned()),
+                None,
+                "en".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.user_recovery()
+            .add_ticket(&mut rng, &clock, &session, &user_email, ticket.to_owned())
+            .await
+            .unwrap();
+        old_sessions.push(session);
+    }
+
+    clock.advance(Duration::try_hours(1).unwrap());
+    let before = clock.now();
+
+    // A session created at the cutoff is kept
+    let recent = repo
+        .user_recovery()
+        .add_session(
+            &mut rng,
+            &clock,
+            "john@example.com".to_owned(),
+            UserAgent::parse("Mozilla/5.0".to_owned()),
+            None,
+            "en".to_owned(),
+        )
+        .await
+        .unwrap();
+
+    // Old sessions are removed in batches
+    assert_eq!(
+        repo.user_recovery()
+            .cleanup_sessions(before, 1)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        repo.user_recovery()
+            .cleanup_sessions(before, 1)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        repo.user_recovery()
+            .cleanup_sessions(before, 1)
+            .await
+            .unwrap(),
+        0
+    );
+
+    for session in &old_sessions {
+        assert!(repo
+            .user_recovery()
+            .lookup_session(session.id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+    assert!(repo
+        .user_recovery()
+        .lookup_session(recent.id)
+        .await
+        .unwrap()
+        .is_some());
+
+    // Their tickets went with them
+    assert!(repo
+        .user_recovery()
+        .find_ticket("ticket1")
+        .await
+        .unwrap()
+        .is_none());
+    assert!(repo
+        .user_recovery()
+        .find_ticket("ticket2")
+        .await
+        .unwrap()
+        .is_none());
+
+    repo.save().await.unwrap();
+}
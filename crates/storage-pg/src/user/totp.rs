@@ -0,0 +1,511 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::{User, UserTotpRecoveryCode, UserTotpSecret};
+use mas_storage::{
+    user::{UserTotpRecoveryCodeRepository, UserTotpRepository},
+    Clock,
+};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{DatabaseError, ExecuteExt};
+
+/// An implementation of [`UserTotpRepository`] for a PostgreSQL connection
+pub struct PgUserTotpRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserTotpRepository<'c> {
+    /// Create a new [`PgUserTotpRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserTotpSecretLookup {
+    user_totp_secret_id: Uuid,
+    user_id: Uuid,
+    encrypted_secret: String,
+    last_used_step: Option<i64>,
+    created_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserTotpSecretLookup> for UserTotpSecret {
+    fn from(value: UserTotpSecretLookup) -> Self {
+        Self {
+            id: value.user_totp_secret_id.into(),
+            user_id: value.user_id.into(),
+            encrypted_secret: value.encrypted_secret,
+            last_used_step: value.last_used_step,
+            created_at: value.created_at,
+            confirmed_at: value.confirmed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> UserTotpRepository for PgUserTotpRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_totp.lookup",
+        skip_all,
+        fields(
+            db.query.text,
+            user_totp_secret.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserTotpSecret>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserTotpSecretLookup,
+            r#"
+                SELECT user_totp_secret_id
+                     , user_id
+                     , encrypted_secret
+                     , last_used_step
+                     , created_at
+                     , confirmed_at
+                FROM user_totp_secrets
+                WHERE user_totp_secret_id = $1
+                  AND removed_at IS NULL
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_totp.active",
+        skip_all,
+        fields(
+            db.query.text,
+            %user.id,
+            %user.username,
+        ),
+        err,
+    )]
+    async fn active(&mut self, user: &User) -> Result<Option<UserTotpSecret>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserTotpSecretLookup,
+            r#"
+                SELECT user_totp_secret_id
+                     , user_id
+                     , encrypted_secret
+                     , last_used_step
+                     , created_at
+                     , confirmed_at
+                FROM user_totp_secrets
+                WHERE user_id = $1
+                  AND confirmed_at IS NOT NULL
+                  AND removed_at IS NULL
+                ORDER BY confirmed_at DESC
+                LIMIT 1
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_totp.add",
+        skip_all,
+        fields(
+            db.query.text,
+            %user.id,
+            %user.username,
+            user_totp_secret.id,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        encrypted_secret: String,
+    ) -> Result<UserTotpSecret, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("user_totp_secret.id", tracing::field::display(id));
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_totp_secrets
+                    (user_totp_secret_id, user_id, encrypted_secret, created_at)
+                VALUES ($1, $2, $3, $4)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user.id),
+            encrypted_secret,
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(UserTotpSecret {
+            id,
+            user_id: user.id,
+            encrypted_secret,
+            last_used_step: None,
+            created_at,
+            confirmed_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.user_totp.confirm",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_totp_secret.id,
+            %user_totp_secret.user_id,
+        ),
+        err,
+    )]
+    async fn confirm(
+        &mut self,
+        clock: &dyn Clock,
+        mut user_totp_secret: UserTotpSecret,
+    ) -> Result<UserTotpSecret, Self::Error> {
+        let confirmed_at = clock.now();
+
+        // Remove any other secret the user had, confirmed or not
+        sqlx::query!(
+            r#"
+                UPDATE user_totp_secrets
+                SET removed_at = $3
+                WHERE user_id = $1
+                  AND user_totp_secret_id <> $2
+                  AND removed_at IS NULL
+            "#,
+            Uuid::from(user_totp_secret.user_id),
+            Uuid::from(user_totp_secret.id),
+            confirmed_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_totp_secrets
+                SET confirmed_at = $2
+                WHERE user_totp_secret_id = $1
+            "#,
+            Uuid::from(user_totp_secret.id),
+            confirmed_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user_totp_secret.confirmed_at = Some(confirmed_at);
+        Ok(user_totp_secret)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_totp.record_use",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_totp_secret.id,
+            user_totp_secret.last_used_step = step,
+        ),
+        err,
+    )]
+    async fn record_use(
+        &mut self,
+        mut user_totp_secret: UserTotpSecret,
+        step: i64,
+    ) -> Result<Option<UserTotpSecret>, Self::Error> {
+        // Only move the last used step forward, so that concurrent requests
+        // can't both accept the same code
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_totp_secrets
+                SET last_used_step = $2
+                WHERE user_totp_secret_id = $1
+                  AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            Uuid::from(user_totp_secret.id),
+            step,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user_totp_secret.last_used_step = Some(step);
+        Ok(Some(user_totp_secret))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_totp.remove",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_totp_secret.id,
+            %user_totp_secret.user_id,
+        ),
+        err,
+    )]
+    async fn remove(
+        &mut self,
+        clock: &dyn Clock,
+        user_totp_secret: UserTotpSecret,
+    ) -> Result<(), Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_totp_secrets
+                SET removed_at = $2
+                WHERE user_totp_secret_id = $1
+            "#,
+            Uuid::from(user_totp_secret.id),
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(())
+    }
+}
+
+/// An implementation of [`UserTotpRecoveryCodeRepository`] for a PostgreSQL
+/// connection
+pub struct PgUserTotpRecoveryCodeRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserTotpRecoveryCodeRepository<'c> {
+    /// Create a new [`PgUserTotpRecoveryCodeRepository`] from an active
+    /// PostgreSQL connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserTotpRecoveryCodeLookup {
+    user_totp_recovery_code_id: Uuid,
+    user_totp_secret_id: Uuid,
+    hashed_code: String,
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserTotpRecoveryCodeLookup> for UserTotpRecoveryCode {
+    fn from(value: UserTotpRecoveryCodeLookup) -> Self {
+        Self {
+            id: value.user_totp_recovery_code_id.into(),
+            user_totp_secret_id: value.user_totp_secret_id.into(),
+            hashed_code: value.hashed_code,
+            created_at: value.created_at,
+            consumed_at: value.consumed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> UserTotpRecoveryCodeRepository for PgUserTotpRecoveryCodeRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_totp_recovery_code.replace_all",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_totp_secret.id,
+        ),
+        err,
+    )]
+    async fn replace_all(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_totp_secret: &UserTotpSecret,
+        hashed_codes: Vec<String>,
+    ) -> Result<Vec<UserTotpRecoveryCode>, Self::Error> {
+        let created_at = clock.now();
+
+        // Codes which were already used are kept, as authentications reference
+        // them
+        sqlx::query!(
+            r#"
+                DELETE FROM user_totp_recovery_codes
+                WHERE user_totp_secret_id = $1
+                  AND consumed_at IS NULL
+            "#,
+            Uuid::from(user_totp_secret.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        let codes: Vec<UserTotpRecoveryCode> = hashed_codes
+            .into_iter()
+            .map(|hashed_code| UserTotpRecoveryCode {
+                id: Ulid::from_datetime_with_source(created_at.into(), rng),
+                user_totp_secret_id: user_totp_secret.id,
+                hashed_code,
+                created_at,
+                consumed_at: None,
+            })
+            .collect();
+
+        let ids: Vec<Uuid> = codes.iter().map(|code| Uuid::from(code.id)).collect();
+        let hashes: Vec<String> = codes.iter().map(|code| code.hashed_code.clone()).collect();
+
+        let res = sqlx::query!(
+            r#"
+                INSERT INTO user_totp_recovery_codes
+                    (user_totp_recovery_code_id, user_totp_secret_id, hashed_code, created_at)
+                SELECT id, $3, hashed_code, $4
+                FROM UNNEST($1::uuid[], $2::text[]) AS t(id, hashed_code)
+            "#,
+            &ids,
+            &hashes,
+            Uuid::from(user_totp_secret.id),
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, ids.len().try_into().unwrap_or(u64::MAX))?;
+
+        Ok(codes)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_totp_recovery_code.find_unused",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_totp_secret.id,
+        ),
+        err,
+    )]
+    async fn find_unused(
+        &mut self,
+        user_totp_secret: &UserTotpSecret,
+        hashed_code: &str,
+    ) -> Result<Option<UserTotpRecoveryCode>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserTotpRecoveryCodeLookup,
+            r#"
+                SELECT user_totp_recovery_code_id
+                     , user_totp_secret_id
+                     , hashed_code
+                     , created_at
+                     , consumed_at
+                FROM user_totp_recovery_codes
+                WHERE user_totp_secret_id = $1
+                  AND hashed_code = $2
+                  AND consumed_at IS NULL
+            "#,
+            Uuid::from(user_totp_secret.id),
+            hashed_code,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_totp_recovery_code.consume",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_totp_recovery_code.id,
+            %user_totp_recovery_code.user_totp_secret_id,
+        ),
+        err,
+    )]
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        mut user_totp_recovery_code: UserTotpRecoveryCode,
+    ) -> Result<UserTotpRecoveryCode, Self::Error> {
+        let consumed_at = clock.now();
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_totp_recovery_codes
+                SET consumed_at = $2
+                WHERE user_totp_recovery_code_id = $1
+                  AND consumed_at IS NULL
+            "#,
+            Uuid::from(user_totp_recovery_code.id),
+            consumed_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user_totp_recovery_code.consumed_at = Some(consumed_at);
+        Ok(user_totp_recovery_code)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_totp_recovery_code.count_unused",
+        skip_all,
+        fields(
+            db.query.text,
+            %user_totp_secret.id,
+        ),
+        err,
+    )]
+    async fn count_unused(
+        &mut self,
+        user_totp_secret: &UserTotpSecret,
+    ) -> Result<usize, Self::Error> {
+        let count = sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*) as "count!"
+                FROM user_totp_recovery_codes
+                WHERE user_totp_secret_id = $1
+                  AND consumed_at IS NULL
+            "#,
+            Uuid::from(user_totp_secret.id),
+        )
+        .traced()
+        .fetch_one(&mut *self.conn)
+        .await?;
+
+        count
+            .try_into()
+            .map_err(DatabaseError::to_invalid_operation)
+    }
+}
@@ -0,0 +1,670 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! A module containing the PostgreSQL implementation of the
+//! [`WebhookDeliveryRepository`]
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::{User, WebhookDelivery, WebhookDeliveryState, WebhookEventKind};
+use mas_storage::{
+    webhook::{WebhookDeliveryFilter, WebhookDeliveryRepository},
+    Clock, Page, Pagination,
+};
+use rand::RngCore;
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{
+    filter::{Filter, StatementExt},
+    iden::WebhookDeliveries,
+    pagination::QueryBuilderExt,
+    DatabaseError, DatabaseInconsistencyError, ExecuteExt,
+};
+
+/// An implementation of [`WebhookDeliveryRepository`] for a PostgreSQL
+/// connection
+pub struct PgWebhookDeliveryRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgWebhookDeliveryRepository<'c> {
+    /// Create a new [`PgWebhookDeliveryRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+mod priv_ {
+    // The enum_def macro generates a public enum, which we don't want, because it
+    // triggers the missing docs warning
+    #![allow(missing_docs)]
+
+    use chrono::{DateTime, Utc};
+    use sea_query::enum_def;
+    use uuid::Uuid;
+
+    #[derive(Debug, sqlx::FromRow)]
+    #[enum_def]
+    pub(super) struct WebhookDeliveryLookup {
+        pub(super) webhook_delivery_id: Uuid,
+        pub(super) created_at: DateTime<Utc>,
+        pub(super) target: String,
+        pub(super) event: String,
+        pub(super) user_id: Option<Uuid>,
+        pub(super) data: serde_json::Value,
+        pub(super) attempts: i32,
+        pub(super) last_attempt_at: Option<DateTime<Utc>>,
+        pub(super) last_error: Option<String>,
+        pub(super) delivered_at: Option<DateTime<Utc>>,
+        pub(super) failed_at: Option<DateTime<Utc>>,
+    }
+}
+
+use priv_::{WebhookDeliveryLookup, WebhookDeliveryLookupIden};
+
+impl TryFrom<WebhookDeliveryLookup> for WebhookDelivery {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(value: WebhookDeliveryLookup) -> Result<Self, Self::Error> {
+        let id = Ulid::from(value.webhook_delivery_id);
+
+        let event: WebhookEventKind = value.event.parse().map_err(|e| {
+            DatabaseInconsistencyError::on("webhook_deliveries")
+                .column("event")
+                .row(id)
+                .source(e)
+        })?;
+
+        let attempts = u32::try_from(value.attempts).map_err(|e| {
+            DatabaseInconsistencyError::on("webhook_deliveries")
+                .column("attempts")
+                .row(id)
+                .source(e)
+        })?;
+
+        let state = match (value.delivered_at, value.failed_at) {
+            (None, None) => WebhookDeliveryState::Pending,
+            (Some(delivered_at), None) => WebhookDeliveryState::Delivered { delivered_at },
+            (None, Some(failed_at)) => WebhookDeliveryState::Failed { failed_at },
+            (Some(_), Some(_)) => {
+                return Err(DatabaseInconsistencyError::on("webhook_deliveries")
+                    .column("failed_at")
+                    .row(id))
+            }
+        };
+
+        Ok(WebhookDelivery {
+            id,
+            state,
+            created_at: value.created_at,
+            target: value.target,
+            event,
+            user_id: value.user_id.map(Ulid::from),
+            data: value.data,
+            attempts,
+            last_attempt_at: value.last_attempt_at,
+            last_error: value.last_error,
+        })
+    }
+}
+
+impl Filter for WebhookDeliveryFilter<'_> {
+    fn generate_condition(&self, _has_joins: bool) -> impl sea_query::IntoCondition {
+        sea_query::Condition::all()
+            .add_option(self.target().map(|target| {
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::Target)).eq(target)
+            }))
+            .add_option(self.event().map(|event| {
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::Event)).eq(event.as_str())
+            }))
+            .add_option(self.user().map(|user| {
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::UserId))
+                    .eq(Uuid::from(user.id))
+            }))
+            .add_option(self.state().map(|state| {
+                let delivered_at =
+                    Expr::col((WebhookDeliveries::Table, WebhookDeliveries::DeliveredAt));
+                let failed_at = Expr::col((WebhookDeliveries::Table, WebhookDeliveries::FailedAt));
+                if state.is_delivered() {
+                    delivered_at.is_not_null()
+                } else if state.is_failed() {
+                    failed_at.is_not_null()
+                } else {
+                    delivered_at.is_null().and(failed_at.is_null())
+                }
+            }))
+    }
+}
+
+impl<'c> PgWebhookDeliveryRepository<'c> {
+    /// Record an attempt on a pending delivery, optionally closing it
+    ///
+    /// Returns the new number of attempts
+    async fn record_attempt(
+        &mut self,
+        delivery: &WebhookDelivery,
+        attempted_at: DateTime<Utc>,
+        error: Option<&str>,
+        delivered_at: Option<DateTime<Utc>>,
+    ) -> Result<u32, DatabaseError> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE webhook_deliveries
+                SET attempts = attempts + 1
+                  , last_attempt_at = $2
+                  , last_error = COALESCE($3, last_error)
+                  , delivered_at = $4
+                WHERE webhook_delivery_id = $1
+                  AND delivered_at IS NULL
+                  AND failed_at IS NULL
+            "#,
+            Uuid::from(delivery.id),
+            attempted_at,
+            error,
+            delivered_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(delivery.attempts.saturating_add(1))
+    }
+}
+
+#[async_trait]
+impl<'c> WebhookDeliveryRepository for PgWebhookDeliveryRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.webhook_delivery.lookup",
+        skip_all,
+        fields(
+            db.query.text,
+            webhook_delivery.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<WebhookDelivery>, Self::Error> {
+        let res = sqlx::query_as!(
+            WebhookDeliveryLookup,
+            r#"
+                SELECT webhook_delivery_id
+                     , created_at
+                     , target
+                     , event
+                     , user_id
+                     , data
+                     , attempts
+                     , last_attempt_at
+                     , last_error
+                     , delivered_at
+                     , failed_at
+                FROM webhook_deliveries
+                WHERE webhook_delivery_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else { return Ok(None) };
+
+        Ok(Some(res.try_into()?))
+    }
+
+    #[tracing::instrument(
+        name = "db.webhook_delivery.add",
+        skip_all,
+        fields(
+            db.query.text,
+            webhook_delivery.id,
+            webhook_delivery.target = target,
+            webhook_delivery.event = %event,
+            user.id,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        target: String,
+        event: WebhookEventKind,
+        user: Option<&User>,
+        data: serde_json::Value,
+    ) -> Result<WebhookDelivery, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("webhook_delivery.id", tracing::field::display(id));
+        if let Some(user) = user {
+            tracing::Span::current().record("user.id", tracing::field::display(user.id));
+        }
+
+        let user_id = user.map(|user| user.id);
+
+        sqlx::query!(
+            r#"
+                INSERT INTO webhook_deliveries
+                    ( webhook_delivery_id
+                    , created_at
+                    , target
+                    , event
+                    , user_id
+                    , data
+                    )
+                VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            Uuid::from(id),
+            created_at,
+            &target,
+            event.as_str(),
+            user_id.map(Uuid::from),
+            &data,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(WebhookDelivery {
+            id,
+            state: WebhookDeliveryState::Pending,
+            created_at,
+            target,
+            event,
+            user_id,
+            data,
+            attempts: 0,
+            last_attempt_at: None,
+            last_error: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.webhook_delivery.record_failed_attempt",
+        skip_all,
+        fields(
+            db.query.text,
+            %webhook_delivery.id,
+            webhook_delivery.attempts = webhook_delivery.attempts,
+        ),
+        err,
+    )]
+    async fn record_failed_attempt(
+        &mut self,
+        clock: &dyn Clock,
+        mut webhook_delivery: WebhookDelivery,
+        error: String,
+    ) -> Result<WebhookDelivery, Self::Error> {
+        let attempted_at = clock.now();
+        let attempts = self
+            .record_attempt(&webhook_delivery, attempted_at, Some(&error), None)
+            .await?;
+
+        webhook_delivery.attempts = attempts;
+        webhook_delivery.last_attempt_at = Some(attempted_at);
+        webhook_delivery.last_error = Some(error);
+
+        Ok(webhook_delivery)
+    }
+
+    #[tracing::instrument(
+        name = "db.webhook_delivery.mark_as_delivered",
+        skip_all,
+        fields(
+            db.query.text,
+            %webhook_delivery.id,
+        ),
+        err,
+    )]
+    async fn mark_as_delivered(
+        &mut self,
+        clock: &dyn Clock,
+        mut webhook_delivery: WebhookDelivery,
+    ) -> Result<WebhookDelivery, Self::Error> {
+        let delivered_at = clock.now();
+        let state = webhook_delivery
+            .state
+            .clone()
+            .deliver(delivered_at)
+            .map_err(DatabaseError::to_invalid_operation)?;
+
+        let attempts = self
+            .record_attempt(&webhook_delivery, delivered_at, None, Some(delivered_at))
+            .await?;
+
+        webhook_delivery.state = state;
+        webhook_delivery.attempts = attempts;
+        webhook_delivery.last_attempt_at = Some(delivered_at);
+
+        Ok(webhook_delivery)
+    }
+
+    #[tracing::instrument(
+        name = "db.webhook_delivery.mark_as_failed",
+        skip_all,
+        fields(
+            db.query.text,
+            %webhook_delivery.id,
+        ),
+        err,
+    )]
+    async fn mark_as_failed(
+        &mut self,
+        clock: &dyn Clock,
+        mut webhook_delivery: WebhookDelivery,
+    ) -> Result<WebhookDelivery, Self::Error> {
+        let failed_at = clock.now();
+        let state = webhook_delivery
+            .state
+            .clone()
+            .fail(failed_at)
+            .map_err(DatabaseError::to_invalid_operation)?;
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE webhook_deliveries
+                SET failed_at = $2
+                WHERE webhook_delivery_id = $1
+                  AND delivered_at IS NULL
+                  AND failed_at IS NULL
+            "#,
+            Uuid::from(webhook_delivery.id),
+            failed_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        webhook_delivery.state = state;
+
+        Ok(webhook_delivery)
+    }
+
+    #[tracing::instrument(
+        name = "db.webhook_delivery.list",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn list(
+        &mut self,
+        filter: WebhookDeliveryFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<WebhookDelivery>, Self::Error> {
+        let (sql, arguments) = Query::select()
+            .expr_as(
+                Expr::col((
+                    WebhookDeliveries::Table,
+                    WebhookDeliveries::WebhookDeliveryId,
+                )),
+                WebhookDeliveryLookupIden::WebhookDeliveryId,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::CreatedAt)),
+                WebhookDeliveryLookupIden::CreatedAt,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::Target)),
+                WebhookDeliveryLookupIden::Target,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::Event)),
+                WebhookDeliveryLookupIden::Event,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::UserId)),
+                WebhookDeliveryLookupIden::UserId,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::Data)),
+                WebhookDeliveryLookupIden::Data,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::Attempts)),
+                WebhookDeliveryLookupIden::Attempts,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::LastAttemptAt)),
+                WebhookDeliveryLookupIden::LastAttemptAt,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::LastError)),
+                WebhookDeliveryLookupIden::LastError,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::DeliveredAt)),
+                WebhookDeliveryLookupIden::DeliveredAt,
+            )
+            .expr_as(
+                Expr::col((WebhookDeliveries::Table, WebhookDeliveries::FailedAt)),
+                WebhookDeliveryLookupIden::FailedAt,
+            )
+            .from(WebhookDeliveries::Table)
+            .apply_filter(filter)
+            .generate_pagination(
+                (
+                    WebhookDeliveries::Table,
+                    WebhookDeliveries::WebhookDeliveryId,
+                ),
+                pagination,
+            )
+            .build_sqlx(PostgresQueryBuilder);
+
+        let edges: Vec<WebhookDeliveryLookup> = sqlx::query_as_with(&sql, arguments)
+            .traced()
+            .fetch_all(&mut *self.conn)
+            .await?;
+
+        let page = pagination.process(edges).try_map(TryInto::try_into)?;
+
+        Ok(page)
+    }
+
+    #[tracing::instrument(
+        name = "db.webhook_delivery.count",
+        skip_all,
+        fields(
+            db.query.text,
+        ),
+        err,
+    )]
+    async fn count(&mut self, filter: WebhookDeliveryFilter<'_>) -> Result<usize, Self::Error> {
+        let (sql, arguments) = Query::select()
+            .expr(
+                Expr::col((
+                    WebhookDeliveries::Table,
+                    WebhookDeliveries::WebhookDeliveryId,
+                ))
+                .count(),
+            )
+            .from(WebhookDeliveries::Table)
+            .apply_filter(filter)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: i64 = sqlx::query_scalar_with(&sql, arguments)
+            .traced()
+            .fetch_one(&mut *self.conn)
+            .await?;
+
+        count
+            .try_into()
+            .map_err(DatabaseError::to_invalid_operation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_data_model::WebhookEventKind;
+    use mas_storage::{
+        clock::MockClock, webhook::WebhookDeliveryFilter, Pagination, RepositoryAccess,
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use sqlx::PgPool;
+
+    use crate::PgRepository;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_webhook_delivery_repository(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let alice = repo
+            .user()
+            .add(&mut rng, &clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let all = WebhookDeliveryFilter::new();
+        assert_eq!(repo.webhook_delivery().count(all).await.unwrap(), 0);
+
+        let first = repo
+            .webhook_delivery()
+            .add(
+                &mut rng,
+                &clock,
+                "crm".to_owned(),
+                WebhookEventKind::UserRegistered,
+                Some(&alice),
+                serde_json::json!({ "username": "alice" }),
+            )
+            .await
+            .unwrap();
+        assert!(first.is_pending());
+        assert_eq!(first.attempts, 0);
+
+        let second = repo
+            .webhook_delivery()
+            .add(
+                &mut rng,
+                &clock,
+                "audit".to_owned(),
+                WebhookEventKind::UserLocked,
+                Some(&alice),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+
+        let lookup = repo
+            .webhook_delivery()
+            .lookup(first.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lookup, first);
+
+        // The first delivery fails once, then succeeds
+        let first = repo
+            .webhook_delivery()
+            .record_failed_attempt(&clock, first, "HTTP 502".to_owned())
+            .await
+            .unwrap();
+        assert!(first.is_pending());
+        assert_eq!(first.attempts, 1);
+        assert_eq!(first.last_error.as_deref(), Some("HTTP 502"));
+
+        let first = repo
+            .webhook_delivery()
+            .mark_as_delivered(&clock, first)
+            .await
+            .unwrap();
+        assert!(first.is_delivered());
+        assert_eq!(first.attempts, 2);
+
+        // A delivered event can't be marked as failed
+        assert!(repo
+            .webhook_delivery()
+            .mark_as_failed(&clock, first.clone())
+            .await
+            .is_err());
+
+        // The second delivery runs out of attempts
+        let second = repo
+            .webhook_delivery()
+            .record_failed_attempt(&clock, second, "connection refused".to_owned())
+            .await
+            .unwrap();
+        let second = repo
+            .webhook_delivery()
+            .mark_as_failed(&clock, second)
+            .await
+            .unwrap();
+        assert!(second.is_failed());
+
+        let lookup = repo
+            .webhook_delivery()
+            .lookup(second.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lookup, second);
+
+        assert_eq!(repo.webhook_delivery().count(all).await.unwrap(), 2);
+        assert_eq!(
+            repo.webhook_delivery()
+                .count(all.pending_only())
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            repo.webhook_delivery()
+                .count(all.delivered_only())
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.webhook_delivery()
+                .count(all.failed_only())
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.webhook_delivery()
+                .count(all.for_target("crm"))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.webhook_delivery()
+                .count(all.for_event(WebhookEventKind::UserLocked))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.webhook_delivery()
+                .count(all.for_user(&alice))
+                .await
+                .unwrap(),
+            2
+        );
+
+        let page = repo
+            .webhook_delivery()
+            .list(all.failed_only(), Pagination::first(10))
+            .await
+            .unwrap();
+        assert!(!page.has_next_page);
+        assert_eq!(page.edges, vec![second]);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Repository to record and query the audit log of security-relevant events
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::{AuditContext, AuditEvent, AuditEventKind, User};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{pagination::Page, repository_impl, Clock, Pagination};
+
+/// Filter parameters for listing audit events
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct AuditEventFilter<'a> {
+    user: Option<&'a User>,
+    kind: Option<AuditEventKind>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+}
+
+impl<'a> AuditEventFilter<'a> {
+    /// Create a new [`AuditEventFilter`] with default values
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter for events concerning a specific user
+    #[must_use]
+    pub fn for_user(mut self, user: &'a User) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Filter for events of a specific kind
+    #[must_use]
+    pub fn with_kind(mut self, kind: AuditEventKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Filter for events recorded after the given date
+    #[must_use]
+    pub fn created_after(mut self, created_after: DateTime<Utc>) -> Self {
+        self.created_after = Some(created_after);
+        self
+    }
+
+    /// Filter for events recorded before the given date
+    #[must_use]
+    pub fn created_before(mut self, created_before: DateTime<Utc>) -> Self {
+        self.created_before = Some(created_before);
+        self
+    }
+
+    /// Get the user filter
+    ///
+    /// Returns [`None`] if no user filter is set
+    #[must_use]
+    pub fn user(&self) -> Option<&User> {
+        self.user
+    }
+
+    /// Get the event kind filter
+    ///
+    /// Returns [`None`] if no kind filter is set
+    #[must_use]
+    pub fn kind(&self) -> Option<AuditEventKind> {
+        self.kind
+    }
+
+    /// Get the lower bound of the creation date filter
+    ///
+    /// Returns [`None`] if no lower bound is set
+    #[must_use]
+    pub fn created_after_date(&self) -> Option<DateTime<Utc>> {
+        self.created_after
+    }
+
+    /// Get the upper bound of the creation date filter
+    ///
+    /// Returns [`None`] if no upper bound is set
+    #[must_use]
+    pub fn created_before_date(&self) -> Option<DateTime<Utc>> {
+        self.created_before
+    }
+}
+
+/// An [`AuditEventRepository`] helps interacting with the [`AuditEvent`] saved
+/// in the storage backend
+///
+/// Audit events are append-only: once recorded, they can't be modified.
+#[async_trait]
+pub trait AuditEventRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup an [`AuditEvent`] by its ID
+    ///
+    /// Returns `None` if no [`AuditEvent`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`AuditEvent`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<AuditEvent>, Self::Error>;
+
+    /// Record a new [`AuditEvent`]
+    ///
+    /// Returns the newly recorded [`AuditEvent`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `context`: Who triggered the event, and from which client
+    /// * `kind`: The kind of event
+    /// * `user`: The [`User`] concerned by the event, if any
+    /// * `details`: Structured details about the event
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        context: &AuditContext,
+        kind: AuditEventKind,
+        user: Option<&User>,
+        details: serde_json::Value,
+    ) -> Result<AuditEvent, Self::Error>;
+
+    /// List [`AuditEvent`] with the given filter and pagination
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter parameters
+    /// * `pagination`: The pagination parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(
+        &mut self,
+        filter: AuditEventFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<AuditEvent>, Self::Error>;
+
+    /// Count the [`AuditEvent`] with the given filter
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count(&mut self, filter: AuditEventFilter<'_>) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(AuditEventRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<AuditEvent>, Self::Error>;
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        context: &AuditContext,
+        kind: AuditEventKind,
+        user: Option<&User>,
+        details: serde_json::Value,
+    ) -> Result<AuditEvent, Self::Error>;
+    async fn list(
+        &mut self,
+        filter: AuditEventFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<AuditEvent>, Self::Error>;
+    async fn count(&mut self, filter: AuditEventFilter<'_>) -> Result<usize, Self::Error>;
+);
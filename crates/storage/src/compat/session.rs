@@ -64,6 +64,8 @@ pub struct CompatSessionFilter<'a> {
     device: Option<&'a Device>,
     last_active_before: Option<DateTime<Utc>>,
     last_active_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    inactive_since: Option<DateTime<Utc>>,
 }
 
 impl<'a> CompatSessionFilter<'a> {
@@ -142,6 +144,39 @@ impl<'a> CompatSessionFilter<'a> {
         self.last_active_after
     }
 
+    /// Only return sessions created before the given time
+    #[must_use]
+    pub fn with_created_before(mut self, created_before: DateTime<Utc>) -> Self {
+        self.created_before = Some(created_before);
+        self
+    }
+
+    /// Get the created before filter
+    ///
+    /// Returns [`None`] if no created before filter was set
+    #[must_use]
+    pub fn created_before(&self) -> Option<DateTime<Utc>> {
+        self.created_before
+    }
+
+    /// Only return sessions which were not active since the given time.
+    ///
+    /// Unlike [`Self::with_last_active_before`], this also matches sessions
+    /// which were never active, using their creation time instead.
+    #[must_use]
+    pub fn with_inactive_since(mut self, inactive_since: DateTime<Utc>) -> Self {
+        self.inactive_since = Some(inactive_since);
+        self
+    }
+
+    /// Get the inactive since filter
+    ///
+    /// Returns [`None`] if no inactive since filter was set
+    #[must_use]
+    pub fn inactive_since(&self) -> Option<DateTime<Utc>> {
+        self.inactive_since
+    }
+
     /// Only return active compatibility sessions
     #[must_use]
     pub fn active_only(mut self) -> Self {
@@ -324,6 +359,26 @@ pub trait CompatSessionRepository: Send + Sync {
         compat_session: CompatSession,
         user_agent: UserAgent,
     ) -> Result<CompatSession, Self::Error>;
+
+    /// Cleanup compat sessions which finished before the given time, along
+    /// with their tokens and SSO logins
+    ///
+    /// Returns the number of sessions that were cleaned up, which is at most
+    /// `limit`
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: Sessions finished before this time are cleaned up
+    /// * `limit`: The maximum number of sessions to clean up
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(CompatSessionRepository:
@@ -369,4 +424,10 @@ repository_impl!(CompatSessionRepository:
         compat_session: CompatSession,
         user_agent: UserAgent,
     ) -> Result<CompatSession, Self::Error>;
+
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
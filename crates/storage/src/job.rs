@@ -6,21 +6,28 @@
 
 //! Repository to schedule persistent jobs.
 
-use std::{num::ParseIntError, ops::Deref};
+use std::{collections::HashMap, num::ParseIntError, ops::Deref, str::FromStr};
 
 pub use apalis_core::job::{Job, JobId};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::Session;
 use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use thiserror::Error;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
+use ulid::Ulid;
 
-use crate::repository_impl;
+use crate::{
+    oauth2::OAuth2SessionFilter, repository_impl, Clock, Page, Pagination, RepositoryAccess,
+};
 
 /// A job submission to be scheduled through the repository.
 pub struct JobSubmission {
     name: &'static str,
     payload: Value,
+    run_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -114,6 +121,7 @@ impl JobSubmission {
         Self {
             name: J::NAME,
             payload,
+            run_at: None,
         }
     }
 
@@ -133,6 +141,13 @@ impl JobSubmission {
         })
     }
 
+    /// Delay the execution of the job until the given time.
+    #[must_use]
+    pub fn with_run_at(mut self, run_at: DateTime<Utc>) -> Self {
+        self.run_at = Some(run_at);
+        self
+    }
+
     /// The name of the job.
     #[must_use]
     pub fn name(&self) -> &'static str {
@@ -144,9 +159,204 @@ impl JobSubmission {
     pub fn payload(&self) -> &Value {
         &self.payload
     }
+
+    /// The time at which the job should run, if it was delayed.
+    ///
+    /// Returns [`None`] if the job should run as soon as possible.
+    #[must_use]
+    pub fn run_at(&self) -> Option<DateTime<Utc>> {
+        self.run_at
+    }
+}
+
+/// The status of a job in the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum JobStatus {
+    /// The job is waiting to be picked up by a worker, either for the first
+    /// time or after a failed attempt
+    Pending,
+
+    /// The job is being processed by a worker
+    Running,
+
+    /// The job completed successfully
+    Done,
+
+    /// The job is waiting to be retried
+    Retry,
+
+    /// The job ran out of attempts
+    Failed,
+
+    /// The job was cancelled
+    Killed,
+}
+
+impl JobStatus {
+    /// All the possible statuses of a job
+    pub const ALL: [Self; 6] = [
+        Self::Pending,
+        Self::Running,
+        Self::Done,
+        Self::Retry,
+        Self::Failed,
+        Self::Killed,
+    ];
+
+    /// Get the status as it is stored in the database
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "Pending",
+            Self::Running => "Running",
+            Self::Done => "Done",
+            Self::Retry => "Retry",
+            Self::Failed => "Failed",
+            Self::Killed => "Killed",
+        }
+    }
+
+    /// Returns `true` if a job with this status can be scheduled again, which
+    /// is the case for jobs which failed or were cancelled
+    #[must_use]
+    pub const fn can_be_retried(self) -> bool {
+        matches!(self, Self::Failed | Self::Killed)
+    }
+
+    /// Returns `true` if a job with this status can be cancelled, which is the
+    /// case for jobs not picked up by a worker yet
+    #[must_use]
+    pub const fn can_be_cancelled(self) -> bool {
+        matches!(self, Self::Pending | Self::Retry)
+    }
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The error returned when parsing an unknown [`JobStatus`]
+#[derive(Debug, Error)]
+#[error("Unknown job status {0:?}")]
+pub struct UnknownJobStatusError(String);
+
+impl FromStr for JobStatus {
+    type Err = UnknownJobStatusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| UnknownJobStatusError(s.to_owned()))
+    }
+}
+
+/// A job stored in the queue, as seen by administrators
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedJob {
+    /// The ID of the job
+    pub id: Ulid,
+
+    /// The name of the job, as in [`Job::NAME`]
+    pub name: String,
+
+    /// The current status of the job
+    pub status: JobStatus,
+
+    /// The payload of the job
+    pub payload: Value,
+
+    /// How many times the job was attempted
+    pub attempts: u32,
+
+    /// How many times the job can be attempted before it fails
+    pub max_attempts: u32,
+
+    /// When the job is (or was) due to run
+    pub run_at: DateTime<Utc>,
+
+    /// The error of the last failed attempt, if any
+    pub last_error: Option<String>,
+
+    /// The worker which picked up the job, if any
+    pub locked_by: Option<String>,
+
+    /// When the job was picked up by a worker, if it was
+    pub locked_at: Option<DateTime<Utc>>,
+
+    /// When the job completed, failed or was cancelled
+    pub done_at: Option<DateTime<Utc>>,
+}
+
+/// Filter parameters for listing jobs in the queue
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct JobFilter<'a> {
+    name: Option<&'a str>,
+    status: Option<JobStatus>,
+}
+
+impl<'a> JobFilter<'a> {
+    /// Create a new [`JobFilter`] with default values
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter for jobs with a specific name
+    #[must_use]
+    pub fn for_name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Get the name filter
+    ///
+    /// Returns [`None`] if no name filter is set
+    #[must_use]
+    pub fn name(&self) -> Option<&'a str> {
+        self.name
+    }
+
+    /// Filter for jobs with a specific status
+    #[must_use]
+    pub fn with_status(mut self, status: JobStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Get the status filter
+    ///
+    /// Returns [`None`] if no status filter is set
+    #[must_use]
+    pub fn status(&self) -> Option<JobStatus> {
+        self.status
+    }
+}
+
+/// Statistics about the jobs with a given name and status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobQueueStats {
+    /// The name of the jobs
+    pub name: String,
+
+    /// The status of the jobs
+    pub status: JobStatus,
+
+    /// How many jobs have this name and status
+    pub count: usize,
+
+    /// How many of those jobs had at least one failed attempt
+    pub retried: usize,
+
+    /// The time at which the oldest of those jobs was due to run, ignoring
+    /// the jobs scheduled in the future
+    pub oldest_due_at: Option<DateTime<Utc>>,
 }
 
-/// A [`JobRepository`] is used to schedule jobs to be executed by a worker.
+/// A [`JobRepository`] is used to schedule jobs to be executed by a worker,
+/// and to inspect the job queue.
 #[async_trait]
 pub trait JobRepository: Send + Sync {
     /// The error type returned by the repository.
@@ -165,10 +375,107 @@ pub trait JobRepository: Send + Sync {
         &mut self,
         submission: JobSubmission,
     ) -> Result<JobId, Self::Error>;
+
+    /// Lookup a job in the queue by its ID
+    ///
+    /// Returns `None` if no job was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the job to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<QueuedJob>, Self::Error>;
+
+    /// List jobs in the queue matching the given filter, with the given
+    /// pagination
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter to apply
+    /// * `pagination`: The pagination parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(
+        &mut self,
+        filter: JobFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<QueuedJob>, Self::Error>;
+
+    /// Count the jobs in the queue matching the given filter
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter to apply
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count(&mut self, filter: JobFilter<'_>) -> Result<usize, Self::Error>;
+
+    /// Compute statistics about the jobs in the queue, grouped by name and
+    /// status
+    ///
+    /// Completed jobs are never cleaned up, so they are left out.
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to ignore jobs scheduled in the future
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn stats(&mut self, clock: &dyn Clock) -> Result<Vec<JobQueueStats>, Self::Error>;
+
+    /// Schedule a failed or cancelled job to run again, as soon as possible
+    ///
+    /// Its attempts counter is reset. Returns the updated job.
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to schedule the job
+    /// * `job`: The job to retry
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// job can't be retried
+    async fn retry(&mut self, clock: &dyn Clock, job: QueuedJob) -> Result<QueuedJob, Self::Error>;
+
+    /// Cancel a job which wasn't picked up by a worker yet
+    ///
+    /// Returns the updated job.
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to record when the job was cancelled
+    /// * `job`: The job to cancel
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// job can't be cancelled
+    async fn cancel(&mut self, clock: &dyn Clock, job: QueuedJob)
+        -> Result<QueuedJob, Self::Error>;
 }
 
 repository_impl!(JobRepository:
     async fn schedule_submission(&mut self, submission: JobSubmission) -> Result<JobId, Self::Error>;
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<QueuedJob>, Self::Error>;
+    async fn list(
+        &mut self,
+        filter: JobFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<QueuedJob>, Self::Error>;
+    async fn count(&mut self, filter: JobFilter<'_>) -> Result<usize, Self::Error>;
+    async fn stats(&mut self, clock: &dyn Clock) -> Result<Vec<JobQueueStats>, Self::Error>;
+    async fn retry(&mut self, clock: &dyn Clock, job: QueuedJob) -> Result<QueuedJob, Self::Error>;
+    async fn cancel(&mut self, clock: &dyn Clock, job: QueuedJob)
+        -> Result<QueuedJob, Self::Error>;
 );
 
 /// An extension trait for [`JobRepository`] to schedule jobs directly.
@@ -190,6 +497,22 @@ pub trait JobRepositoryExt {
         &mut self,
         job: J,
     ) -> Result<JobId, Self::Error>;
+
+    /// Schedule a job to be executed no earlier than the given time.
+    ///
+    /// # Parameters
+    ///
+    /// * `job` - The job to schedule.
+    /// * `run_at` - The time at which the job should run.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn schedule_job_at<J: Job + Serialize + Send>(
+        &mut self,
+        job: J,
+        run_at: DateTime<Utc>,
+    ) -> Result<JobId, Self::Error>;
 }
 
 #[async_trait]
@@ -218,12 +541,123 @@ where
         self.schedule_submission(JobSubmission::new_with_span_context(job, span_context))
             .await
     }
+
+    #[tracing::instrument(
+        name = "db.job.schedule_job_at",
+        skip_all,
+        fields(
+            job.name = J::NAME,
+            job.run_at = %run_at,
+        ),
+    )]
+    async fn schedule_job_at<J: Job + Serialize + Send>(
+        &mut self,
+        job: J,
+        run_at: DateTime<Utc>,
+    ) -> Result<JobId, Self::Error> {
+        let span = tracing::Span::current();
+        let ctx = span.context();
+        let span = ctx.span();
+        let span_context = span.span_context();
+
+        self.schedule_submission(
+            JobSubmission::new_with_span_context(job, span_context).with_run_at(run_at),
+        )
+        .await
+    }
+}
+
+/// Schedules [`SendBackchannelLogoutJob`]s for OAuth 2.0 sessions about to be
+/// ended, looking up each client only once
+#[derive(Debug, Default)]
+pub struct BackchannelLogoutScheduler {
+    /// Whether each client seen so far registered a back-channel logout URI
+    clients: HashMap<Ulid, bool>,
+}
+
+impl BackchannelLogoutScheduler {
+    /// Schedule a [`SendBackchannelLogoutJob`] for the given session, if its
+    /// client registered a back-channel logout URI
+    ///
+    /// This should be called *before* ending the session.
+    ///
+    /// Returns whether a job was scheduled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying repository fails
+    pub async fn schedule<R>(&mut self, repo: &mut R, session: &Session) -> Result<bool, R::Error>
+    where
+        R: RepositoryAccess + ?Sized,
+    {
+        let has_backchannel_logout = match self.clients.get(&session.client_id) {
+            Some(has_backchannel_logout) => *has_backchannel_logout,
+            None => {
+                let client = repo.oauth2_client().lookup(session.client_id).await?;
+                let has_backchannel_logout =
+                    client.is_some_and(|client| client.backchannel_logout_uri.is_some());
+                self.clients
+                    .insert(session.client_id, has_backchannel_logout);
+                has_backchannel_logout
+            }
+        };
+
+        if has_backchannel_logout {
+            repo.job()
+                .schedule_job(SendBackchannelLogoutJob::new(session))
+                .await?;
+        }
+
+        Ok(has_backchannel_logout)
+    }
+}
+
+/// Schedule a [`SendBackchannelLogoutJob`] for each OAuth 2.0 session matching
+/// the given filter, if its client registered a back-channel logout URI
+///
+/// This should be called *before* ending the sessions, as the filter usually
+/// only matches active sessions.
+///
+/// Returns the number of jobs scheduled.
+///
+/// # Errors
+///
+/// Returns an error if the underlying repository fails
+pub async fn schedule_backchannel_logout_jobs<R>(
+    repo: &mut R,
+    filter: OAuth2SessionFilter<'_>,
+) -> Result<usize, R::Error>
+where
+    R: RepositoryAccess + ?Sized,
+{
+    let mut scheduler = BackchannelLogoutScheduler::default();
+    let mut scheduled = 0;
+    let mut cursor = Pagination::first(100);
+    loop {
+        let page = repo.oauth2_session().list(filter, cursor).await?;
+
+        for session in page.edges {
+            cursor = cursor.after(session.id);
+
+            if scheduler.schedule(repo, &session).await? {
+                scheduled += 1;
+            }
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(scheduled)
 }
 
 mod jobs {
     // XXX: Move this somewhere else?
     use apalis_core::job::Job;
-    use mas_data_model::{Device, User, UserEmail, UserRecoverySession};
+    use mas_data_model::{
+        Device, Session, User, UserEmail, UserRecoverySession, WebhookDelivery, WebhookEventKind,
+    };
     use serde::{Deserialize, Serialize};
     use ulid::Ulid;
 
@@ -506,9 +940,129 @@ mod jobs {
     impl Job for SendAccountRecoveryEmailsJob {
         const NAME: &'static str = "send-account-recovery-email";
     }
+
+    /// A job to notify a client that one of its sessions ended, using the
+    /// OpenID Connect Back-Channel Logout protocol
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SendBackchannelLogoutJob {
+        oauth2_session_id: Ulid,
+    }
+
+    impl SendBackchannelLogoutJob {
+        /// Create a new job to notify the client of an OAuth 2.0 session that
+        /// the session ended
+        ///
+        /// # Parameters
+        ///
+        /// * `session` - The OAuth 2.0 session which ended
+        #[must_use]
+        pub fn new(session: &Session) -> Self {
+            Self {
+                oauth2_session_id: session.id,
+            }
+        }
+
+        /// The ID of the OAuth 2.0 session which ended
+        #[must_use]
+        pub fn oauth2_session_id(&self) -> Ulid {
+            self.oauth2_session_id
+        }
+    }
+
+    impl Job for SendBackchannelLogoutJob {
+        const NAME: &'static str = "send-backchannel-logout";
+    }
+
+    /// A job to fan out a user lifecycle event to the configured webhook
+    /// targets
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct DispatchWebhookEventJob {
+        event: WebhookEventKind,
+        user_id: Ulid,
+        #[serde(default)]
+        data: serde_json::Value,
+    }
+
+    impl DispatchWebhookEventJob {
+        /// Create a new job to send an event concerning a user to the webhook
+        /// targets
+        ///
+        /// # Parameters
+        ///
+        /// * `event` - The kind of event
+        /// * `user` - The user concerned by the event
+        #[must_use]
+        pub fn new(event: WebhookEventKind, user: &User) -> Self {
+            Self {
+                event,
+                user_id: user.id,
+                data: serde_json::Value::Null,
+            }
+        }
+
+        /// Attach event-specific data to the event
+        #[must_use]
+        pub fn with_data(mut self, data: serde_json::Value) -> Self {
+            self.data = data;
+            self
+        }
+
+        /// The kind of event to dispatch
+        #[must_use]
+        pub fn event(&self) -> WebhookEventKind {
+            self.event
+        }
+
+        /// The ID of the user concerned by the event
+        #[must_use]
+        pub fn user_id(&self) -> Ulid {
+            self.user_id
+        }
+
+        /// The event-specific data
+        #[must_use]
+        pub fn data(&self) -> &serde_json::Value {
+            &self.data
+        }
+    }
+
+    impl Job for DispatchWebhookEventJob {
+        const NAME: &'static str = "dispatch-webhook-event";
+    }
+
+    /// A job to attempt delivering an event to a webhook target
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SendWebhookJob {
+        webhook_delivery_id: Ulid,
+    }
+
+    impl SendWebhookJob {
+        /// Create a new job to attempt a webhook delivery
+        ///
+        /// # Parameters
+        ///
+        /// * `delivery` - The webhook delivery to attempt
+        #[must_use]
+        pub fn new(delivery: &WebhookDelivery) -> Self {
+            Self {
+                webhook_delivery_id: delivery.id,
+            }
+        }
+
+        /// The ID of the webhook delivery to attempt
+        #[must_use]
+        pub fn webhook_delivery_id(&self) -> Ulid {
+            self.webhook_delivery_id
+        }
+    }
+
+    impl Job for SendWebhookJob {
+        const NAME: &'static str = "send-webhook";
+    }
 }
 
 pub use self::jobs::{
-    DeactivateUserJob, DeleteDeviceJob, ProvisionDeviceJob, ProvisionUserJob, ReactivateUserJob,
-    SendAccountRecoveryEmailsJob, SyncDevicesJob, VerifyEmailJob,
+    DeactivateUserJob, DeleteDeviceJob, DispatchWebhookEventJob, ProvisionDeviceJob,
+    ProvisionUserJob, ReactivateUserJob, SendAccountRecoveryEmailsJob, SendBackchannelLogoutJob,
+    SendWebhookJob, SyncDevicesJob, VerifyEmailJob,
 };
@@ -7,6 +7,7 @@
 use std::num::NonZeroU32;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mas_data_model::{AuthorizationCode, AuthorizationGrant, Client, Session};
 use oauth2_types::{requests::ResponseMode, scope::Scope};
 use rand_core::RngCore;
@@ -145,6 +146,25 @@ pub trait OAuth2AuthorizationGrantRepository: Send + Sync {
         &mut self,
         authorization_grant: AuthorizationGrant,
     ) -> Result<AuthorizationGrant, Self::Error>;
+
+    /// Cleanup authorization grants which were created before the given time
+    ///
+    /// Returns the number of grants that were cleaned up, which is at most
+    /// `limit`
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: Grants created before this time are cleaned up
+    /// * `limit`: The maximum number of grants to clean up
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_stale(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(OAuth2AuthorizationGrantRepository:
@@ -186,4 +206,10 @@ repository_impl!(OAuth2AuthorizationGrantRepository:
         &mut self,
         authorization_grant: AuthorizationGrant,
     ) -> Result<AuthorizationGrant, Self::Error>;
+
+    async fn cleanup_stale(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
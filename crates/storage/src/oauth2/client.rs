@@ -4,18 +4,66 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+};
 
 use async_trait::async_trait;
 use mas_data_model::{Client, User};
-use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
+use mas_iana::{
+    jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg},
+    oauth::OAuthClientAuthenticationMethod,
+};
 use mas_jose::jwk::PublicJsonWebKeySet;
 use oauth2_types::{oidc::ApplicationType, requests::GrantType, scope::Scope};
 use rand_core::RngCore;
 use ulid::Ulid;
 use url::Url;
 
-use crate::{repository_impl, Clock};
+use crate::{pagination::Page, repository_impl, Clock, Pagination};
+
+/// Filter parameters for listing OAuth 2.0 clients
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct OAuth2ClientFilter<'a> {
+    /// Filter by whether the client is static, i.e. managed through the
+    /// configuration file
+    ///
+    /// If `None`, all clients are returned
+    is_static: Option<bool>,
+
+    _lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a> OAuth2ClientFilter<'a> {
+    /// Create a new [`OAuth2ClientFilter`] with default values
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return only static clients
+    #[must_use]
+    pub const fn static_only(mut self) -> Self {
+        self.is_static = Some(true);
+        self
+    }
+
+    /// Return only dynamic clients
+    #[must_use]
+    pub const fn dynamic_only(mut self) -> Self {
+        self.is_static = Some(false);
+        self
+    }
+
+    /// Get the static filter
+    ///
+    /// Returns `None` if the filter is not set
+    #[must_use]
+    pub const fn is_static(&self) -> Option<bool> {
+        self.is_static
+    }
+}
 
 /// An [`OAuth2ClientRepository`] helps interacting with [`Client`] saved in the
 /// storage backend
@@ -91,6 +139,27 @@ pub trait OAuth2ClientRepository: Send + Sync {
     ///   when using the `client_secret_jwt` or `private_key_jwt` authentication
     ///   methods
     /// * `initiate_login_uri`: The URI used to initiate a login, if given
+    /// * `post_logout_redirect_uris`: The list of URIs this client can redirect
+    ///   to after a logout
+    /// * `backchannel_logout_uri`: The URI used to notify this client of
+    ///   logouts, if given
+    /// * `backchannel_logout_session_required`: Whether this client requires
+    ///   the `sid` claim in the logout notifications
+    /// * `require_pushed_authorization_requests`: Whether this client must use
+    ///   the Pushed Authorization Request endpoint
+    /// * `request_object_signing_alg`: The algorithm this client must use to
+    ///   sign request objects, if any
+    /// * `require_signed_request_object`: Whether this client must send its
+    ///   authorization requests as signed request objects
+    /// * `id_token_encrypted_response_alg`: The algorithm used to encrypt the
+    ///   ID token. If none, the ID token will not be encrypted
+    /// * `id_token_encrypted_response_enc`: The content encryption algorithm
+    ///   used to encrypt the ID token
+    /// * `userinfo_encrypted_response_alg`: The algorithm used to encrypt the
+    ///   user info. If none, the user info endpoint will not encrypt the
+    ///   response
+    /// * `userinfo_encrypted_response_enc`: The content encryption algorithm
+    ///   used to encrypt the user info
     ///
     /// # Errors
     ///
@@ -116,6 +185,16 @@ pub trait OAuth2ClientRepository: Send + Sync {
         token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
         token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
         initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        require_signed_request_object: bool,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
     ) -> Result<Client, Self::Error>;
 
     /// Add or replace a static client
@@ -130,6 +209,27 @@ pub trait OAuth2ClientRepository: Send + Sync {
     /// * `jwks`: The client JWKS, if any
     /// * `jwks_uri`: The client JWKS URI, if any
     /// * `redirect_uris`: The list of redirect URIs used by this client
+    /// * `post_logout_redirect_uris`: The list of URIs this client can redirect
+    ///   to after a logout
+    /// * `backchannel_logout_uri`: The URI used to notify this client of
+    ///   logouts, if given
+    /// * `backchannel_logout_session_required`: Whether this client requires
+    ///   the `sid` claim in the logout notifications
+    /// * `require_pushed_authorization_requests`: Whether this client must use
+    ///   the Pushed Authorization Request endpoint
+    /// * `request_object_signing_alg`: The algorithm this client must use to
+    ///   sign request objects, if any
+    /// * `require_signed_request_object`: Whether this client must send its
+    ///   authorization requests as signed request objects
+    /// * `id_token_encrypted_response_alg`: The algorithm used to encrypt the
+    ///   ID token. If none, the ID token will not be encrypted
+    /// * `id_token_encrypted_response_enc`: The content encryption algorithm
+    ///   used to encrypt the ID token
+    /// * `userinfo_encrypted_response_alg`: The algorithm used to encrypt the
+    ///   user info. If none, the user info endpoint will not encrypt the
+    ///   response
+    /// * `userinfo_encrypted_response_enc`: The content encryption algorithm
+    ///   used to encrypt the user info
     ///
     /// # Errors
     ///
@@ -143,6 +243,16 @@ pub trait OAuth2ClientRepository: Send + Sync {
         jwks: Option<PublicJsonWebKeySet>,
         jwks_uri: Option<Url>,
         redirect_uris: Vec<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        require_signed_request_object: bool,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
     ) -> Result<Client, Self::Error>;
 
     /// List all static clients
@@ -152,6 +262,85 @@ pub trait OAuth2ClientRepository: Send + Sync {
     /// Returns [`Self::Error`] if the underlying repository fails
     async fn all_static(&mut self) -> Result<Vec<Client>, Self::Error>;
 
+    /// List [`Client`]s with the given filter and pagination
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter to apply
+    /// * `pagination`: The pagination parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(
+        &mut self,
+        filter: OAuth2ClientFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<Client>, Self::Error>;
+
+    /// Count the number of [`Client`]s with the given filter
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter to apply
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count(&mut self, filter: OAuth2ClientFilter<'_>) -> Result<usize, Self::Error>;
+
+    /// Update the settings of a dynamic client
+    ///
+    /// Returns the updated client
+    ///
+    /// # Parameters
+    ///
+    /// * `client`: The client to update
+    /// * `client_name`: The human-readable name of the client
+    /// * `redirect_uris`: The list of redirect URIs used by this client
+    /// * `grant_types`: The list of grant types this client can use
+    /// * `token_endpoint_auth_method`: The authentication method used by this
+    ///   client
+    /// * `token_endpoint_auth_signing_alg`: The algorithm used to sign the JWT
+    ///   used by the `client_secret_jwt` and `private_key_jwt` methods
+    /// * `jwks`: The JSON Web Key Set of the client
+    /// * `jwks_uri`: The URI of the JSON Web Key Set of the client
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    #[allow(clippy::too_many_arguments)]
+    async fn update(
+        &mut self,
+        client: Client,
+        client_name: Option<String>,
+        redirect_uris: Vec<Url>,
+        grant_types: Vec<GrantType>,
+        token_endpoint_auth_method: OAuthClientAuthenticationMethod,
+        token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        jwks: Option<PublicJsonWebKeySet>,
+        jwks_uri: Option<Url>,
+    ) -> Result<Client, Self::Error>;
+
+    /// Replace the client secret of a client
+    ///
+    /// Returns the updated client
+    ///
+    /// # Parameters
+    ///
+    /// * `client`: The client to update
+    /// * `encrypted_client_secret`: The new encrypted client secret, or `None`
+    ///   to remove it
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn set_client_secret(
+        &mut self,
+        client: Client,
+        encrypted_client_secret: Option<String>,
+    ) -> Result<Client, Self::Error>;
+
     /// Get the list of scopes that the user has given consent for the given
     /// client
     ///
@@ -246,6 +435,16 @@ repository_impl!(OAuth2ClientRepository:
         token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
         token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
         initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        require_signed_request_object: bool,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
     ) -> Result<Client, Self::Error>;
 
     async fn upsert_static(
@@ -256,10 +455,46 @@ repository_impl!(OAuth2ClientRepository:
         jwks: Option<PublicJsonWebKeySet>,
         jwks_uri: Option<Url>,
         redirect_uris: Vec<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        require_signed_request_object: bool,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
     ) -> Result<Client, Self::Error>;
 
     async fn all_static(&mut self) -> Result<Vec<Client>, Self::Error>;
 
+    async fn list(
+        &mut self,
+        filter: OAuth2ClientFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<Client>, Self::Error>;
+
+    async fn count(&mut self, filter: OAuth2ClientFilter<'_>) -> Result<usize, Self::Error>;
+
+    async fn update(
+        &mut self,
+        client: Client,
+        client_name: Option<String>,
+        redirect_uris: Vec<Url>,
+        grant_types: Vec<GrantType>,
+        token_endpoint_auth_method: OAuthClientAuthenticationMethod,
+        token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        jwks: Option<PublicJsonWebKeySet>,
+        jwks_uri: Option<Url>,
+    ) -> Result<Client, Self::Error>;
+
+    async fn set_client_secret(
+        &mut self,
+        client: Client,
+        encrypted_client_secret: Option<String>,
+    ) -> Result<Client, Self::Error>;
+
     async fn delete(&mut self, client: Client) -> Result<(), Self::Error>;
 
     async fn delete_by_id(&mut self, id: Ulid) -> Result<(), Self::Error>;
@@ -7,7 +7,7 @@
 use std::net::IpAddr;
 
 use async_trait::async_trait;
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use mas_data_model::{BrowserSession, Client, DeviceCodeGrant, Session, UserAgent};
 use oauth2_types::scope::Scope;
 use rand_core::RngCore;
@@ -183,6 +183,25 @@ pub trait OAuth2DeviceCodeGrantRepository: Send + Sync {
         device_code_grant: DeviceCodeGrant,
         session: &Session,
     ) -> Result<DeviceCodeGrant, Self::Error>;
+
+    /// Cleanup device code grants which expired before the given time
+    ///
+    /// Returns the number of grants that were cleaned up, which is at most
+    /// `limit`
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: Grants which expired before this time are cleaned up
+    /// * `limit`: The maximum number of grants to clean up
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_expired(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(OAuth2DeviceCodeGrantRepository:
@@ -225,4 +244,10 @@ repository_impl!(OAuth2DeviceCodeGrantRepository:
         device_code_grant: DeviceCodeGrant,
         session: &Session,
     ) -> Result<DeviceCodeGrant, Self::Error>;
+
+    async fn cleanup_expired(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
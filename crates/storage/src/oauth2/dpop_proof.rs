@@ -0,0 +1,68 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{repository_impl, Clock};
+
+/// An [`OAuth2DPoPProofRepository`] remembers the DPoP proofs presented by
+/// clients, so that each of them can only be used once
+#[async_trait]
+pub trait OAuth2DPoPProofRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Record that the proof with the given `jti` was signed by the key with
+    /// the given thumbprint
+    ///
+    /// Returns `false` if a proof with the same `jti` was already signed by
+    /// the same key, in which case nothing is recorded. If another transaction
+    /// is recording the same proof, this waits for it to finish.
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `jkt`: The SHA-256 thumbprint of the key which signed the proof
+    /// * `jti`: The `jti` claim of the proof
+    /// * `expires_at`: When the proof stops being accepted, after which it can
+    ///   be forgotten
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        jkt: &str,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Self::Error>;
+
+    /// Cleanup the proofs which expired
+    ///
+    /// Returns the number of proofs that were cleaned up
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(OAuth2DPoPProofRepository:
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        jkt: &str,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Self::Error>;
+
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+);
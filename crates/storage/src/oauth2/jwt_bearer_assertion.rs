@@ -0,0 +1,68 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::Client;
+
+use crate::{repository_impl, Clock};
+
+/// An [`OAuth2JwtBearerAssertionRepository`] remembers the JWT bearer
+/// assertions used by clients, so that each of them can only be used once
+#[async_trait]
+pub trait OAuth2JwtBearerAssertionRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Record that a client used the assertion with the given `jti`
+    ///
+    /// Returns `false` if the client already used an assertion with the same
+    /// `jti`, in which case nothing is recorded. If another transaction is
+    /// recording the same assertion, this waits for it to finish.
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `client`: The client which used the assertion
+    /// * `jti`: The `jti` claim of the assertion
+    /// * `expires_at`: When the assertion stops being usable, after which it
+    ///   can be forgotten
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        client: &Client,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Self::Error>;
+
+    /// Cleanup the assertions which expired
+    ///
+    /// Returns the number of assertions that were cleaned up
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(OAuth2JwtBearerAssertionRepository:
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        client: &Client,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Self::Error>;
+
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+);
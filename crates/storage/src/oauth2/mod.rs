@@ -10,14 +10,20 @@ mod access_token;
 mod authorization_grant;
 mod client;
 mod device_code_grant;
+mod dpop_proof;
+mod jwt_bearer_assertion;
+mod pushed_authorization_request;
 mod refresh_token;
 mod session;
 
 pub use self::{
     access_token::OAuth2AccessTokenRepository,
     authorization_grant::OAuth2AuthorizationGrantRepository,
-    client::OAuth2ClientRepository,
+    client::{OAuth2ClientFilter, OAuth2ClientRepository},
     device_code_grant::{OAuth2DeviceCodeGrantParams, OAuth2DeviceCodeGrantRepository},
+    dpop_proof::OAuth2DPoPProofRepository,
+    jwt_bearer_assertion::OAuth2JwtBearerAssertionRepository,
+    pushed_authorization_request::OAuth2PushedAuthorizationRequestRepository,
     refresh_token::OAuth2RefreshTokenRepository,
     session::{OAuth2SessionFilter, OAuth2SessionRepository},
 };
@@ -0,0 +1,116 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use mas_data_model::{Client, PushedAuthorizationRequest};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// An [`OAuth2PushedAuthorizationRequestRepository`] helps interacting with
+/// [`PushedAuthorizationRequest`] saved in the storage backend
+#[async_trait]
+pub trait OAuth2PushedAuthorizationRequestRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Save a new pushed authorization request
+    ///
+    /// Returns the newly created pushed authorization request
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: A random number generator
+    /// * `clock`: The clock used to generate timestamps
+    /// * `client`: The client which pushed the request
+    /// * `parameters`: The parameters of the authorization request
+    /// * `expires_in`: After how long the request stops being usable
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parameters: BTreeMap<String, String>,
+        expires_in: Duration,
+    ) -> Result<PushedAuthorizationRequest, Self::Error>;
+
+    /// Lookup a pushed authorization request by its ID
+    ///
+    /// Returns the pushed authorization request if found, [`None`] otherwise
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the pushed authorization request
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid)
+        -> Result<Option<PushedAuthorizationRequest>, Self::Error>;
+
+    /// Mark a pushed authorization request as used, so that it can't be used
+    /// again
+    ///
+    /// Returns the updated pushed authorization request, or `None` if it was
+    /// consumed in the meantime by another transaction
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `request`: The pushed authorization request to consume
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails or if the
+    /// given request was already marked as consumed
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        request: PushedAuthorizationRequest,
+    ) -> Result<Option<PushedAuthorizationRequest>, Self::Error>;
+
+    /// Cleanup pushed authorization requests which expired
+    ///
+    /// Returns the number of requests that were cleaned up
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(OAuth2PushedAuthorizationRequestRepository:
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parameters: BTreeMap<String, String>,
+        expires_in: Duration,
+    ) -> Result<PushedAuthorizationRequest, Self::Error>;
+
+    async fn lookup(&mut self, id: Ulid)
+        -> Result<Option<PushedAuthorizationRequest>, Self::Error>;
+
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        request: PushedAuthorizationRequest,
+    ) -> Result<Option<PushedAuthorizationRequest>, Self::Error>;
+
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+);
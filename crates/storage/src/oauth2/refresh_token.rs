@@ -5,6 +5,7 @@
 // Please see LICENSE in the repository root for full details.
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mas_data_model::{AccessToken, RefreshToken, Session};
 use rand_core::RngCore;
 use ulid::Ulid;
@@ -57,7 +58,8 @@ pub trait OAuth2RefreshTokenRepository: Send + Sync {
     /// * `clock`: The clock used to generate timestamps
     /// * `session`: The [`Session`] in which to create the [`RefreshToken`]
     /// * `access_token`: The [`AccessToken`] created alongside this
-    ///   [`RefreshToken`]
+    ///   [`RefreshToken`]. The refresh token is bound to the same DPoP key as
+    ///   this access token, if any
     /// * `refresh_token`: The refresh token to store
     ///
     /// # Errors
@@ -90,6 +92,25 @@ pub trait OAuth2RefreshTokenRepository: Send + Sync {
         clock: &dyn Clock,
         refresh_token: RefreshToken,
     ) -> Result<RefreshToken, Self::Error>;
+
+    /// Cleanup refresh tokens which were consumed before the given time
+    ///
+    /// Returns the number of refresh tokens that were cleaned up, which is at
+    /// most `limit`
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: Refresh tokens consumed before this time are cleaned up
+    /// * `limit`: The maximum number of refresh tokens to clean up
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_consumed(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(OAuth2RefreshTokenRepository:
@@ -114,4 +135,10 @@ repository_impl!(OAuth2RefreshTokenRepository:
         clock: &dyn Clock,
         refresh_token: RefreshToken,
     ) -> Result<RefreshToken, Self::Error>;
+
+    async fn cleanup_consumed(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
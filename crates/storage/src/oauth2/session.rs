@@ -4,12 +4,15 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 // Please see LICENSE in the repository root for full details.
 
-use std::net::IpAddr;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mas_data_model::{BrowserSession, Client, Device, Session, User, UserAgent};
-use oauth2_types::scope::Scope;
+use oauth2_types::{requests::GrantType, scope::Scope};
 use rand_core::RngCore;
 use ulid::Ulid;
 
@@ -42,6 +45,8 @@ pub struct OAuth2SessionFilter<'a> {
     scope: Option<&'a Scope>,
     last_active_before: Option<DateTime<Utc>>,
     last_active_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    inactive_since: Option<DateTime<Utc>>,
 }
 
 impl<'a> OAuth2SessionFilter<'a> {
@@ -126,6 +131,39 @@ impl<'a> OAuth2SessionFilter<'a> {
         self.last_active_after
     }
 
+    /// Only return sessions created before the given time
+    #[must_use]
+    pub fn with_created_before(mut self, created_before: DateTime<Utc>) -> Self {
+        self.created_before = Some(created_before);
+        self
+    }
+
+    /// Get the created before filter
+    ///
+    /// Returns [`None`] if no created before filter was set
+    #[must_use]
+    pub fn created_before(&self) -> Option<DateTime<Utc>> {
+        self.created_before
+    }
+
+    /// Only return sessions which were not active since the given time.
+    ///
+    /// Unlike [`Self::with_last_active_before`], this also matches sessions
+    /// which were never active, using their creation time instead.
+    #[must_use]
+    pub fn with_inactive_since(mut self, inactive_since: DateTime<Utc>) -> Self {
+        self.inactive_since = Some(inactive_since);
+        self
+    }
+
+    /// Get the inactive since filter
+    ///
+    /// Returns [`None`] if no inactive since filter was set
+    #[must_use]
+    pub fn inactive_since(&self) -> Option<DateTime<Utc>> {
+        self.inactive_since
+    }
+
     /// Only return active sessions
     #[must_use]
     pub fn active_only(mut self) -> Self {
@@ -346,6 +384,23 @@ pub trait OAuth2SessionRepository: Send + Sync {
     /// Returns [`Self::Error`] if the underlying repository fails
     async fn count(&mut self, filter: OAuth2SessionFilter<'_>) -> Result<usize, Self::Error>;
 
+    /// Count the active [`Session`]s of a batch of clients
+    ///
+    /// Returns a map of client IDs to the number of active sessions. Clients
+    /// without active sessions are not present in the map.
+    ///
+    /// # Parameters
+    ///
+    /// * `client_ids`: The IDs of the clients to count the sessions of
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count_active_by_clients(
+        &mut self,
+        client_ids: BTreeSet<Ulid>,
+    ) -> Result<BTreeMap<Ulid, usize>, Self::Error>;
+
     /// Record a batch of [`Session`] activity
     ///
     /// # Parameters
@@ -372,6 +427,59 @@ pub trait OAuth2SessionRepository: Send + Sync {
         session: Session,
         user_agent: UserAgent,
     ) -> Result<Session, Self::Error>;
+
+    /// Record the grant type which started a [`Session`]
+    ///
+    /// # Parameters
+    ///
+    /// * `session`: The [`Session`] to record the grant type for
+    /// * `grant_type`: The grant type to record
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_grant_type(
+        &mut self,
+        session: Session,
+        grant_type: GrantType,
+    ) -> Result<Session, Self::Error>;
+
+    /// Record the client which obtained a [`Session`] on behalf of the
+    /// session's client, through a token exchange
+    ///
+    /// # Parameters
+    ///
+    /// * `session`: The [`Session`] to record the acting client for
+    /// * `acting_client`: The client which asked for the session
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_acting_client(
+        &mut self,
+        session: Session,
+        acting_client: &Client,
+    ) -> Result<Session, Self::Error>;
+
+    /// Cleanup sessions which finished before the given time, along with
+    /// their tokens and authorization grants
+    ///
+    /// Returns the number of sessions that were cleaned up, which is at most
+    /// `limit`
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: Sessions finished before this time are cleaned up
+    /// * `limit`: The maximum number of sessions to clean up
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(OAuth2SessionRepository:
@@ -421,6 +529,11 @@ repository_impl!(OAuth2SessionRepository:
 
     async fn count(&mut self, filter: OAuth2SessionFilter<'_>) -> Result<usize, Self::Error>;
 
+    async fn count_active_by_clients(
+        &mut self,
+        client_ids: BTreeSet<Ulid>,
+    ) -> Result<BTreeMap<Ulid, usize>, Self::Error>;
+
     async fn record_batch_activity(
         &mut self,
         activity: Vec<(Ulid, DateTime<Utc>, Option<IpAddr>)>,
@@ -431,4 +544,22 @@ repository_impl!(OAuth2SessionRepository:
         session: Session,
         user_agent: UserAgent,
     ) -> Result<Session, Self::Error>;
+
+    async fn record_grant_type(
+        &mut self,
+        session: Session,
+        grant_type: GrantType,
+    ) -> Result<Session, Self::Error>;
+
+    async fn record_acting_client(
+        &mut self,
+        session: Session,
+        acting_client: &Client,
+    ) -> Result<Session, Self::Error>;
+
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
@@ -9,6 +9,7 @@ use thiserror::Error;
 
 use crate::{
     app_session::AppSessionRepository,
+    audit::AuditEventRepository,
     compat::{
         CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository,
         CompatSsoLoginRepository,
@@ -16,16 +17,20 @@ use crate::{
     job::JobRepository,
     oauth2::{
         OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository, OAuth2ClientRepository,
-        OAuth2DeviceCodeGrantRepository, OAuth2RefreshTokenRepository, OAuth2SessionRepository,
+        OAuth2DPoPProofRepository, OAuth2DeviceCodeGrantRepository,
+        OAuth2JwtBearerAssertionRepository, OAuth2PushedAuthorizationRequestRepository,
+        OAuth2RefreshTokenRepository, OAuth2SessionRepository,
     },
     upstream_oauth2::{
         UpstreamOAuthLinkRepository, UpstreamOAuthProviderRepository,
         UpstreamOAuthSessionRepository,
     },
     user::{
-        BrowserSessionRepository, UserEmailRepository, UserPasswordRepository,
-        UserRecoveryRepository, UserRepository, UserTermsRepository,
+        BrowserSessionRepository, UserEmailRepository, UserLdapLinkRepository,
+        UserPasskeyRepository, UserPasswordRepository, UserRecoveryRepository, UserRepository,
+        UserTermsRepository, UserTotpRecoveryCodeRepository, UserTotpRepository,
     },
+    webhook::WebhookDeliveryRepository,
 };
 
 /// A [`Repository`] helps interacting with the underlying storage backend.
@@ -132,6 +137,22 @@ pub trait RepositoryAccess: Send {
     /// Get an [`UserTermsRepository`]
     fn user_terms<'c>(&'c mut self) -> Box<dyn UserTermsRepository<Error = Self::Error> + 'c>;
 
+    /// Get an [`UserTotpRepository`]
+    fn user_totp<'c>(&'c mut self) -> Box<dyn UserTotpRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserTotpRecoveryCodeRepository`]
+    fn user_totp_recovery_code<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserTotpRecoveryCodeRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserPasskeyRepository`]
+    fn user_passkey<'c>(&'c mut self) -> Box<dyn UserPasskeyRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserLdapLinkRepository`]
+    fn user_ldap_link<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserLdapLinkRepository<Error = Self::Error> + 'c>;
+
     /// Get a [`BrowserSessionRepository`]
     fn browser_session<'c>(
         &'c mut self,
@@ -140,6 +161,9 @@ pub trait RepositoryAccess: Send {
     /// Get a [`AppSessionRepository`]
     fn app_session<'c>(&'c mut self) -> Box<dyn AppSessionRepository<Error = Self::Error> + 'c>;
 
+    /// Get an [`AuditEventRepository`]
+    fn audit_event<'c>(&'c mut self) -> Box<dyn AuditEventRepository<Error = Self::Error> + 'c>;
+
     /// Get an [`OAuth2ClientRepository`]
     fn oauth2_client<'c>(&'c mut self)
         -> Box<dyn OAuth2ClientRepository<Error = Self::Error> + 'c>;
@@ -169,6 +193,21 @@ pub trait RepositoryAccess: Send {
         &'c mut self,
     ) -> Box<dyn OAuth2DeviceCodeGrantRepository<Error = Self::Error> + 'c>;
 
+    /// Get an [`OAuth2PushedAuthorizationRequestRepository`]
+    fn oauth2_pushed_authorization_request<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2PushedAuthorizationRequestRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`OAuth2DPoPProofRepository`]
+    fn oauth2_dpop_proof<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2DPoPProofRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`OAuth2JwtBearerAssertionRepository`]
+    fn oauth2_jwt_bearer_assertion<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2JwtBearerAssertionRepository<Error = Self::Error> + 'c>;
+
     /// Get a [`CompatSessionRepository`]
     fn compat_session<'c>(
         &'c mut self,
@@ -191,6 +230,11 @@ pub trait RepositoryAccess: Send {
 
     /// Get a [`JobRepository`]
     fn job<'c>(&'c mut self) -> Box<dyn JobRepository<Error = Self::Error> + 'c>;
+
+    /// Get a [`WebhookDeliveryRepository`]
+    fn webhook_delivery<'c>(
+        &'c mut self,
+    ) -> Box<dyn WebhookDeliveryRepository<Error = Self::Error> + 'c>;
 }
 
 /// Implementations of the [`RepositoryAccess`], [`RepositoryTransaction`] and
@@ -201,6 +245,7 @@ mod impls {
     use super::RepositoryAccess;
     use crate::{
         app_session::AppSessionRepository,
+        audit::AuditEventRepository,
         compat::{
             CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository,
             CompatSsoLoginRepository,
@@ -208,7 +253,8 @@ mod impls {
         job::JobRepository,
         oauth2::{
             OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository,
-            OAuth2ClientRepository, OAuth2DeviceCodeGrantRepository, OAuth2RefreshTokenRepository,
+            OAuth2ClientRepository, OAuth2DPoPProofRepository, OAuth2DeviceCodeGrantRepository,
+            OAuth2PushedAuthorizationRequestRepository, OAuth2RefreshTokenRepository,
             OAuth2SessionRepository,
         },
         upstream_oauth2::{
@@ -216,9 +262,11 @@ mod impls {
             UpstreamOAuthSessionRepository,
         },
         user::{
-            BrowserSessionRepository, UserEmailRepository, UserPasswordRepository, UserRepository,
-            UserTermsRepository,
+            BrowserSessionRepository, UserEmailRepository, UserLdapLinkRepository,
+            UserPasskeyRepository, UserPasswordRepository, UserRepository, UserTermsRepository,
+            UserTotpRecoveryCodeRepository, UserTotpRepository,
         },
+        webhook::WebhookDeliveryRepository,
         MapErr, Repository, RepositoryTransaction,
     };
 
@@ -312,6 +360,31 @@ mod impls {
             Box::new(MapErr::new(self.inner.user_terms(), &mut self.mapper))
         }
 
+        fn user_totp<'c>(&'c mut self) -> Box<dyn UserTotpRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.user_totp(), &mut self.mapper))
+        }
+
+        fn user_totp_recovery_code<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserTotpRecoveryCodeRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.user_totp_recovery_code(),
+                &mut self.mapper,
+            ))
+        }
+
+        fn user_passkey<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserPasskeyRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.user_passkey(), &mut self.mapper))
+        }
+
+        fn user_ldap_link<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserLdapLinkRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.user_ldap_link(), &mut self.mapper))
+        }
+
         fn browser_session<'c>(
             &'c mut self,
         ) -> Box<dyn BrowserSessionRepository<Error = Self::Error> + 'c> {
@@ -324,6 +397,12 @@ mod impls {
             Box::new(MapErr::new(self.inner.app_session(), &mut self.mapper))
         }
 
+        fn audit_event<'c>(
+            &'c mut self,
+        ) -> Box<dyn AuditEventRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.audit_event(), &mut self.mapper))
+        }
+
         fn oauth2_client<'c>(
             &'c mut self,
         ) -> Box<dyn OAuth2ClientRepository<Error = Self::Error> + 'c> {
@@ -372,6 +451,33 @@ mod impls {
             ))
         }
 
+        fn oauth2_pushed_authorization_request<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2PushedAuthorizationRequestRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.oauth2_pushed_authorization_request(),
+                &mut self.mapper,
+            ))
+        }
+
+        fn oauth2_dpop_proof<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2DPoPProofRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.oauth2_dpop_proof(),
+                &mut self.mapper,
+            ))
+        }
+
+        fn oauth2_jwt_bearer_assertion<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2JwtBearerAssertionRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.oauth2_jwt_bearer_assertion(),
+                &mut self.mapper,
+            ))
+        }
+
         fn compat_session<'c>(
             &'c mut self,
         ) -> Box<dyn CompatSessionRepository<Error = Self::Error> + 'c> {
@@ -405,6 +511,12 @@ mod impls {
         fn job<'c>(&'c mut self) -> Box<dyn JobRepository<Error = Self::Error> + 'c> {
             Box::new(MapErr::new(self.inner.job(), &mut self.mapper))
         }
+
+        fn webhook_delivery<'c>(
+            &'c mut self,
+        ) -> Box<dyn WebhookDeliveryRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.webhook_delivery(), &mut self.mapper))
+        }
     }
 
     impl<R: RepositoryAccess + ?Sized> RepositoryAccess for Box<R> {
@@ -452,6 +564,28 @@ mod impls {
             (**self).user_terms()
         }
 
+        fn user_totp<'c>(&'c mut self) -> Box<dyn UserTotpRepository<Error = Self::Error> + 'c> {
+            (**self).user_totp()
+        }
+
+        fn user_totp_recovery_code<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserTotpRecoveryCodeRepository<Error = Self::Error> + 'c> {
+            (**self).user_totp_recovery_code()
+        }
+
+        fn user_passkey<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserPasskeyRepository<Error = Self::Error> + 'c> {
+            (**self).user_passkey()
+        }
+
+        fn user_ldap_link<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserLdapLinkRepository<Error = Self::Error> + 'c> {
+            (**self).user_ldap_link()
+        }
+
         fn browser_session<'c>(
             &'c mut self,
         ) -> Box<dyn BrowserSessionRepository<Error = Self::Error> + 'c> {
@@ -464,6 +598,12 @@ mod impls {
             (**self).app_session()
         }
 
+        fn audit_event<'c>(
+            &'c mut self,
+        ) -> Box<dyn AuditEventRepository<Error = Self::Error> + 'c> {
+            (**self).audit_event()
+        }
+
         fn oauth2_client<'c>(
             &'c mut self,
         ) -> Box<dyn OAuth2ClientRepository<Error = Self::Error> + 'c> {
@@ -500,6 +640,24 @@ mod impls {
             (**self).oauth2_device_code_grant()
         }
 
+        fn oauth2_pushed_authorization_request<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2PushedAuthorizationRequestRepository<Error = Self::Error> + 'c> {
+            (**self).oauth2_pushed_authorization_request()
+        }
+
+        fn oauth2_dpop_proof<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2DPoPProofRepository<Error = Self::Error> + 'c> {
+            (**self).oauth2_dpop_proof()
+        }
+
+        fn oauth2_jwt_bearer_assertion<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2JwtBearerAssertionRepository<Error = Self::Error> + 'c> {
+            (**self).oauth2_jwt_bearer_assertion()
+        }
+
         fn compat_session<'c>(
             &'c mut self,
         ) -> Box<dyn CompatSessionRepository<Error = Self::Error> + 'c> {
@@ -527,5 +685,11 @@ mod impls {
         fn job<'c>(&'c mut self) -> Box<dyn JobRepository<Error = Self::Error> + 'c> {
             (**self).job()
         }
+
+        fn webhook_delivery<'c>(
+            &'c mut self,
+        ) -> Box<dyn WebhookDeliveryRepository<Error = Self::Error> + 'c> {
+            (**self).webhook_delivery()
+        }
     }
 }
@@ -9,7 +9,7 @@ use std::marker::PhantomData;
 use async_trait::async_trait;
 use mas_data_model::{
     UpstreamOAuthProvider, UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
-    UpstreamOAuthProviderPkceMode,
+    UpstreamOAuthProviderKind, UpstreamOAuthProviderPkceMode, UpstreamOAuthProviderSamlSettings,
 };
 use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
 use oauth2_types::scope::Scope;
@@ -31,18 +31,21 @@ pub struct UpstreamOAuthProviderParams {
     /// A brand identifier, e.g. "apple" or "google"
     pub brand_name: Option<String>,
 
-    /// The scope to request during the authorization flow
-    pub scope: Scope,
+    /// The scope to request during the authorization flow. Only set for
+    /// OpenID Connect providers
+    pub scope: Option<Scope>,
 
-    /// The token endpoint authentication method
-    pub token_endpoint_auth_method: OAuthClientAuthenticationMethod,
+    /// The token endpoint authentication method. Only set for OpenID Connect
+    /// providers
+    pub token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
 
     /// The JWT signing algorithm to use when then `client_secret_jwt` or
     /// `private_key_jwt` authentication methods are used
     pub token_endpoint_signing_alg: Option<JsonWebSignatureAlg>,
 
-    /// The client ID to use when authenticating to the upstream
-    pub client_id: String,
+    /// The client ID to use when authenticating to the upstream. Only set for
+    /// OpenID Connect providers
+    pub client_id: Option<String>,
 
     /// The encrypted client secret to use when authenticating to the upstream
     pub encrypted_client_secret: Option<String>,
@@ -69,6 +72,21 @@ pub struct UpstreamOAuthProviderParams {
 
     /// Additional parameters to include in the authorization request
     pub additional_authorization_parameters: Vec<(String, String)>,
+
+    /// The settings of the provider if it is a SAML 2.0 identity provider
+    pub saml: Option<UpstreamOAuthProviderSamlSettings>,
+}
+
+impl UpstreamOAuthProviderParams {
+    /// The kind of provider described by those parameters
+    #[must_use]
+    pub fn kind(&self) -> UpstreamOAuthProviderKind {
+        if self.saml.is_some() {
+            UpstreamOAuthProviderKind::Saml
+        } else {
+            UpstreamOAuthProviderKind::Oidc
+        }
+    }
 }
 
 /// Filter parameters for listing upstream OAuth 2.0 providers
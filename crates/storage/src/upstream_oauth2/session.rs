@@ -5,6 +5,7 @@
 // Please see LICENSE in the repository root for full details.
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mas_data_model::{UpstreamOAuthAuthorizationSession, UpstreamOAuthLink, UpstreamOAuthProvider};
 use rand_core::RngCore;
 use ulid::Ulid;
@@ -63,6 +64,33 @@ pub trait UpstreamOAuthSessionRepository: Send + Sync {
         nonce: String,
     ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
 
+    /// Add a session for a SAML 2.0 identity provider to the database
+    ///
+    /// Returns the newly created session
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: the random number generator to use
+    /// * `clock`: the clock source
+    /// * `upstream_oauth_provider`: the SAML 2.0 identity provider for which
+    ///   to create the session
+    /// * `state`: the opaque state used to find back the session from the
+    ///   cookie
+    /// * `saml_request_id`: the ID of the authentication request sent to the
+    ///   identity provider
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add_saml(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        upstream_oauth_provider: &UpstreamOAuthProvider,
+        state: String,
+        saml_request_id: String,
+    ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
+
     /// Mark a session as completed and associate the given link
     ///
     /// Returns the updated session
@@ -86,6 +114,30 @@ pub trait UpstreamOAuthSessionRepository: Send + Sync {
         id_token: Option<String>,
     ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
 
+    /// Mark a session as completed from a SAML 2.0 assertion, and associate
+    /// the given link
+    ///
+    /// Returns the updated session
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: the clock source
+    /// * `upstream_oauth_authorization_session`: the session to update
+    /// * `upstream_oauth_link`: the link to associate with the session
+    /// * `saml_attributes`: the attributes from the assertion sent by the
+    ///   identity provider
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn complete_with_saml_assertion(
+        &mut self,
+        clock: &dyn Clock,
+        upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
+        upstream_oauth_link: &UpstreamOAuthLink,
+        saml_attributes: serde_json::Value,
+    ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
+
     /// Mark a session as consumed
     ///
     /// Returns the updated session
@@ -103,6 +155,26 @@ pub trait UpstreamOAuthSessionRepository: Send + Sync {
         clock: &dyn Clock,
         upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
     ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
+
+    /// Cleanup upstream authorization sessions which were created before the
+    /// given time
+    ///
+    /// Returns the number of sessions that were cleaned up, which is at most
+    /// `limit`
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: Sessions created before this time are cleaned up
+    /// * `limit`: The maximum number of sessions to clean up
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_stale(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(UpstreamOAuthSessionRepository:
@@ -121,6 +193,15 @@ repository_impl!(UpstreamOAuthSessionRepository:
         nonce: String,
     ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
 
+    async fn add_saml(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        upstream_oauth_provider: &UpstreamOAuthProvider,
+        state: String,
+        saml_request_id: String,
+    ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
+
     async fn complete_with_link(
         &mut self,
         clock: &dyn Clock,
@@ -129,9 +210,23 @@ repository_impl!(UpstreamOAuthSessionRepository:
         id_token: Option<String>,
     ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
 
+    async fn complete_with_saml_assertion(
+        &mut self,
+        clock: &dyn Clock,
+        upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
+        upstream_oauth_link: &UpstreamOAuthLink,
+        saml_attributes: serde_json::Value,
+    ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
+
     async fn consume(
         &mut self,
         clock: &dyn Clock,
         upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
     ) -> Result<UpstreamOAuthAuthorizationSession, Self::Error>;
+
+    async fn cleanup_stale(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
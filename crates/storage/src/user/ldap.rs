@@ -0,0 +1,81 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use mas_data_model::{User, UserLdapLink};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserLdapLinkRepository`] helps interacting with [`UserLdapLink`] saved
+/// in the storage backend
+#[async_trait]
+pub trait UserLdapLinkRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup an [`UserLdapLink`] by its ID
+    ///
+    /// Returns `None` if no [`UserLdapLink`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`UserLdapLink`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserLdapLink>, Self::Error>;
+
+    /// Find an [`UserLdapLink`] by the subject of the directory entry
+    ///
+    /// Returns `None` if no matching [`UserLdapLink`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `subject`: The stable identifier of the directory entry
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_subject(&mut self, subject: &str)
+        -> Result<Option<UserLdapLink>, Self::Error>;
+
+    /// Link a [`User`] to an entry of the LDAP directory
+    ///
+    /// Returns the newly created [`UserLdapLink`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] to link
+    /// * `subject`: The stable identifier of the directory entry
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        subject: String,
+    ) -> Result<UserLdapLink, Self::Error>;
+}
+
+repository_impl!(UserLdapLinkRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserLdapLink>, Self::Error>;
+    async fn find_by_subject(&mut self, subject: &str)
+        -> Result<Option<UserLdapLink>, Self::Error>;
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        subject: String,
+    ) -> Result<UserLdapLink, Self::Error>;
+);
@@ -14,17 +14,23 @@ use ulid::Ulid;
 use crate::{repository_impl, Clock, Page, Pagination};
 
 mod email;
+mod ldap;
+mod passkey;
 mod password;
 mod recovery;
 mod session;
 mod terms;
+mod totp;
 
 pub use self::{
     email::{UserEmailFilter, UserEmailRepository},
+    ldap::UserLdapLinkRepository,
+    passkey::{UserPasskeyFilter, UserPasskeyRepository},
     password::UserPasswordRepository,
     recovery::UserRecoveryRepository,
     session::{BrowserSessionFilter, BrowserSessionRepository},
     terms::UserTermsRepository,
+    totp::{UserTotpRecoveryCodeRepository, UserTotpRepository},
 };
 
 /// The state of a user account
@@ -0,0 +1,204 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use mas_data_model::{User, UserPasskey};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{pagination::Page, repository_impl, Clock, Pagination};
+
+/// Filter parameters for listing user passkeys
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct UserPasskeyFilter<'a> {
+    user: Option<&'a User>,
+}
+
+impl<'a> UserPasskeyFilter<'a> {
+    /// Create a new [`UserPasskeyFilter`] with default values
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter for passkeys of a specific user
+    #[must_use]
+    pub fn for_user(mut self, user: &'a User) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Get the user filter
+    ///
+    /// Returns [`None`] if no user filter is set
+    #[must_use]
+    pub fn user(&self) -> Option<&User> {
+        self.user
+    }
+}
+
+/// A [`UserPasskeyRepository`] helps interacting with [`UserPasskey`] saved in
+/// the storage backend
+#[async_trait]
+pub trait UserPasskeyRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup an [`UserPasskey`] by its ID
+    ///
+    /// Returns `None` if no [`UserPasskey`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`UserPasskey`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserPasskey>, Self::Error>;
+
+    /// Find an [`UserPasskey`] by its credential ID
+    ///
+    /// Returns `None` if no matching [`UserPasskey`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `credential_id`: The credential ID, encoded in base64url
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_credential_id(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<Option<UserPasskey>, Self::Error>;
+
+    /// Get all [`UserPasskey`] of a [`User`]
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] for whom to lookup the [`UserPasskey`]
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn all(&mut self, user: &User) -> Result<Vec<UserPasskey>, Self::Error>;
+
+    /// List [`UserPasskey`] with the given filter and pagination
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter parameters
+    /// * `pagination`: The pagination parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(
+        &mut self,
+        filter: UserPasskeyFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<UserPasskey>, Self::Error>;
+
+    /// Count the [`UserPasskey`] with the given filter
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count(&mut self, filter: UserPasskeyFilter<'_>) -> Result<usize, Self::Error>;
+
+    /// Register a new [`UserPasskey`] for a [`User`]
+    ///
+    /// Returns the newly created [`UserPasskey`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] for whom to register the [`UserPasskey`]
+    /// * `name`: The name given to the passkey by the user
+    /// * `credential_id`: The credential ID, encoded in base64url
+    /// * `credential`: The serialized credential
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        name: String,
+        credential_id: String,
+        credential: String,
+    ) -> Result<UserPasskey, Self::Error>;
+
+    /// Record that a [`UserPasskey`] was used to authenticate
+    ///
+    /// Returns the updated [`UserPasskey`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_passkey`: The [`UserPasskey`] which was used
+    /// * `credential`: The serialized credential, with its updated signature
+    ///   counter
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_use(
+        &mut self,
+        clock: &dyn Clock,
+        user_passkey: UserPasskey,
+        credential: String,
+    ) -> Result<UserPasskey, Self::Error>;
+
+    /// Delete a [`UserPasskey`]
+    ///
+    /// # Parameters
+    ///
+    /// * `user_passkey`: The [`UserPasskey`] to delete
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn remove(&mut self, user_passkey: UserPasskey) -> Result<(), Self::Error>;
+}
+
+repository_impl!(UserPasskeyRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserPasskey>, Self::Error>;
+    async fn find_by_credential_id(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<Option<UserPasskey>, Self::Error>;
+    async fn all(&mut self, user: &User) -> Result<Vec<UserPasskey>, Self::Error>;
+    async fn list(
+        &mut self,
+        filter: UserPasskeyFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<UserPasskey>, Self::Error>;
+    async fn count(&mut self, filter: UserPasskeyFilter<'_>) -> Result<usize, Self::Error>;
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        name: String,
+        credential_id: String,
+        credential: String,
+    ) -> Result<UserPasskey, Self::Error>;
+    async fn record_use(
+        &mut self,
+        clock: &dyn Clock,
+        user_passkey: UserPasskey,
+        credential: String,
+    ) -> Result<UserPasskey, Self::Error>;
+    async fn remove(&mut self, user_passkey: UserPasskey) -> Result<(), Self::Error>;
+);
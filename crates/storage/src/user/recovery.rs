@@ -7,6 +7,7 @@
 use std::net::IpAddr;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mas_data_model::{UserAgent, UserEmail, UserRecoverySession, UserRecoveryTicket};
 use rand_core::RngCore;
 use ulid::Ulid;
@@ -121,6 +122,26 @@ pub trait UserRecoveryRepository: Send + Sync {
         user_recovery_ticket: UserRecoveryTicket,
         user_recovery_session: UserRecoverySession,
     ) -> Result<UserRecoverySession, Self::Error>;
+
+    /// Cleanup recovery sessions which were created before the given time,
+    /// along with their tickets
+    ///
+    /// Returns the number of sessions that were cleaned up, which is at most
+    /// `limit`
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: Sessions created before this time are cleaned up
+    /// * `limit`: The maximum number of sessions to clean up
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_sessions(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(UserRecoveryRepository:
@@ -156,4 +177,10 @@ repository_impl!(UserRecoveryRepository:
         user_recovery_ticket: UserRecoveryTicket,
         user_recovery_session: UserRecoverySession,
     ) -> Result<UserRecoverySession, Self::Error>;
+
+    async fn cleanup_sessions(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
@@ -10,6 +10,7 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mas_data_model::{
     Authentication, BrowserSession, Password, UpstreamOAuthAuthorizationSession, User, UserAgent,
+    UserLdapLink, UserPasskey, UserTotpRecoveryCode, UserTotpSecret,
 };
 use rand_core::RngCore;
 use ulid::Ulid;
@@ -39,6 +40,8 @@ pub struct BrowserSessionFilter<'a> {
     state: Option<BrowserSessionState>,
     last_active_before: Option<DateTime<Utc>>,
     last_active_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    inactive_since: Option<DateTime<Utc>>,
 }
 
 impl<'a> BrowserSessionFilter<'a> {
@@ -91,6 +94,39 @@ impl<'a> BrowserSessionFilter<'a> {
         self.last_active_after
     }
 
+    /// Only return sessions created before the given time
+    #[must_use]
+    pub fn with_created_before(mut self, created_before: DateTime<Utc>) -> Self {
+        self.created_before = Some(created_before);
+        self
+    }
+
+    /// Get the created before filter
+    ///
+    /// Returns [`None`] if no created before filter was set
+    #[must_use]
+    pub fn created_before(&self) -> Option<DateTime<Utc>> {
+        self.created_before
+    }
+
+    /// Only return sessions which were not active since the given time.
+    ///
+    /// Unlike [`Self::with_last_active_before`], this also matches sessions
+    /// which were never active, using their creation time instead.
+    #[must_use]
+    pub fn with_inactive_since(mut self, inactive_since: DateTime<Utc>) -> Self {
+        self.inactive_since = Some(inactive_since);
+        self
+    }
+
+    /// Get the inactive since filter
+    ///
+    /// Returns [`None`] if no inactive since filter was set
+    #[must_use]
+    pub fn inactive_since(&self) -> Option<DateTime<Utc>> {
+        self.inactive_since
+    }
+
     /// Only return active browser sessions
     #[must_use]
     pub fn active_only(mut self) -> Self {
@@ -259,6 +295,93 @@ pub trait BrowserSessionRepository: Send + Sync {
         upstream_oauth_session: &UpstreamOAuthAuthorizationSession,
     ) -> Result<Authentication, Self::Error>;
 
+    /// Authenticate a [`BrowserSession`] with the given [`UserTotpSecret`], as
+    /// a second factor
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_session`: The session to authenticate
+    /// * `user_totp_secret`: The TOTP secret which was used to authenticate
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn authenticate_with_totp(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_totp_secret: &UserTotpSecret,
+    ) -> Result<Authentication, Self::Error>;
+
+    /// Authenticate a [`BrowserSession`] with the given
+    /// [`UserTotpRecoveryCode`], as a second factor
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_session`: The session to authenticate
+    /// * `user_totp_recovery_code`: The recovery code which was used to
+    ///   authenticate
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn authenticate_with_totp_recovery_code(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_totp_recovery_code: &UserTotpRecoveryCode,
+    ) -> Result<Authentication, Self::Error>;
+
+    /// Authenticate a [`BrowserSession`] with the given [`UserPasskey`]
+    ///
+    /// # Params
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_session`: The session to authenticate
+    /// * `user_passkey`: The passkey which was used to authenticate
+    /// * `after_password`: Whether the passkey was used as a second factor
+    ///   after a password
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn authenticate_with_passkey(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_passkey: &UserPasskey,
+        after_password: bool,
+    ) -> Result<Authentication, Self::Error>;
+
+    /// Authenticate a [`BrowserSession`] with the given [`UserLdapLink`]
+    ///
+    /// # Params
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_session`: The session to authenticate
+    /// * `user_ldap_link`: The link to the directory entry whose credentials
+    ///   were checked
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn authenticate_with_ldap(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_ldap_link: &UserLdapLink,
+    ) -> Result<Authentication, Self::Error>;
+
     /// Get the last successful authentication for a [`BrowserSession`]
     ///
     /// # Params
@@ -287,6 +410,29 @@ pub trait BrowserSessionRepository: Send + Sync {
         &mut self,
         activity: Vec<(Ulid, DateTime<Utc>, Option<IpAddr>)>,
     ) -> Result<(), Self::Error>;
+
+    /// Cleanup browser sessions which finished before the given time, along
+    /// with their authentications
+    ///
+    /// Sessions which are still referenced by an OAuth 2.0 session or a device
+    /// code grant are kept, and will be cleaned up once those are.
+    ///
+    /// Returns the number of sessions that were cleaned up, which is at most
+    /// `limit`
+    ///
+    /// # Parameters
+    ///
+    /// * `before`: Sessions finished before this time are cleaned up
+    /// * `limit`: The maximum number of sessions to clean up
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(BrowserSessionRepository:
@@ -334,6 +480,39 @@ repository_impl!(BrowserSessionRepository:
         upstream_oauth_session: &UpstreamOAuthAuthorizationSession,
     ) -> Result<Authentication, Self::Error>;
 
+    async fn authenticate_with_totp(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_totp_secret: &UserTotpSecret,
+    ) -> Result<Authentication, Self::Error>;
+
+    async fn authenticate_with_totp_recovery_code(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_totp_recovery_code: &UserTotpRecoveryCode,
+    ) -> Result<Authentication, Self::Error>;
+
+    async fn authenticate_with_passkey(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_passkey: &UserPasskey,
+        after_password: bool,
+    ) -> Result<Authentication, Self::Error>;
+
+    async fn authenticate_with_ldap(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_ldap_link: &UserLdapLink,
+    ) -> Result<Authentication, Self::Error>;
+
     async fn get_last_authentication(
         &mut self,
         user_session: &BrowserSession,
@@ -343,4 +522,10 @@ repository_impl!(BrowserSessionRepository:
         &mut self,
         activity: Vec<(Ulid, DateTime<Utc>, Option<IpAddr>)>,
     ) -> Result<(), Self::Error>;
+
+    async fn cleanup_finished(
+        &mut self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
@@ -0,0 +1,255 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use async_trait::async_trait;
+use mas_data_model::{User, UserTotpRecoveryCode, UserTotpSecret};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserTotpRepository`] helps interacting with [`UserTotpSecret`] saved in
+/// the storage backend
+#[async_trait]
+pub trait UserTotpRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup a [`UserTotpSecret`] by its ID
+    ///
+    /// Returns `None` if no secret was found or if it was removed
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the TOTP secret to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserTotpSecret>, Self::Error>;
+
+    /// Get the confirmed TOTP secret of a user
+    ///
+    /// Returns `None` if the user has not enrolled a TOTP second factor
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The user to get the TOTP secret for
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn active(&mut self, user: &User) -> Result<Option<UserTotpSecret>, Self::Error>;
+
+    /// Add a new, unconfirmed, TOTP secret for a user
+    ///
+    /// Returns the newly created [`UserTotpSecret`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The user to add the TOTP secret for
+    /// * `encrypted_secret`: The secret, encrypted with the site encryption key
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        encrypted_secret: String,
+    ) -> Result<UserTotpSecret, Self::Error>;
+
+    /// Confirm the enrollment of a TOTP secret
+    ///
+    /// This removes any other TOTP secret the user had, so that a user has at
+    /// most one active TOTP secret.
+    ///
+    /// Returns the confirmed [`UserTotpSecret`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_totp_secret`: The TOTP secret to confirm
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn confirm(
+        &mut self,
+        clock: &dyn Clock,
+        user_totp_secret: UserTotpSecret,
+    ) -> Result<UserTotpSecret, Self::Error>;
+
+    /// Record that a code for the given time step was accepted, so that it
+    /// can't be used again
+    ///
+    /// Returns the updated [`UserTotpSecret`], or `None` if a code for this
+    /// time step or a later one was already used
+    ///
+    /// # Parameters
+    ///
+    /// * `user_totp_secret`: The TOTP secret which was used
+    /// * `step`: The time step of the accepted code
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_use(
+        &mut self,
+        user_totp_secret: UserTotpSecret,
+        step: i64,
+    ) -> Result<Option<UserTotpSecret>, Self::Error>;
+
+    /// Remove a TOTP secret, disabling the second factor for the user
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_totp_secret`: The TOTP secret to remove
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn remove(
+        &mut self,
+        clock: &dyn Clock,
+        user_totp_secret: UserTotpSecret,
+    ) -> Result<(), Self::Error>;
+}
+
+repository_impl!(UserTotpRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserTotpSecret>, Self::Error>;
+    async fn active(&mut self, user: &User) -> Result<Option<UserTotpSecret>, Self::Error>;
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        encrypted_secret: String,
+    ) -> Result<UserTotpSecret, Self::Error>;
+    async fn confirm(
+        &mut self,
+        clock: &dyn Clock,
+        user_totp_secret: UserTotpSecret,
+    ) -> Result<UserTotpSecret, Self::Error>;
+    async fn record_use(
+        &mut self,
+        user_totp_secret: UserTotpSecret,
+        step: i64,
+    ) -> Result<Option<UserTotpSecret>, Self::Error>;
+    async fn remove(
+        &mut self,
+        clock: &dyn Clock,
+        user_totp_secret: UserTotpSecret,
+    ) -> Result<(), Self::Error>;
+);
+
+/// A [`UserTotpRecoveryCodeRepository`] helps interacting with
+/// [`UserTotpRecoveryCode`] saved in the storage backend
+#[async_trait]
+pub trait UserTotpRecoveryCodeRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Replace the recovery codes of a TOTP secret with a new set
+    ///
+    /// Any previous recovery code of this secret is deleted.
+    ///
+    /// Returns the newly created [`UserTotpRecoveryCode`]s
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_totp_secret`: The TOTP secret the codes are attached to
+    /// * `hashed_codes`: The hashes of the new recovery codes
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn replace_all(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_totp_secret: &UserTotpSecret,
+        hashed_codes: Vec<String>,
+    ) -> Result<Vec<UserTotpRecoveryCode>, Self::Error>;
+
+    /// Find an unused recovery code of a TOTP secret by its hash
+    ///
+    /// Returns `None` if no matching, unused, code was found
+    ///
+    /// # Parameters
+    ///
+    /// * `user_totp_secret`: The TOTP secret the code is attached to
+    /// * `hashed_code`: The hash of the code to look for
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_unused(
+        &mut self,
+        user_totp_secret: &UserTotpSecret,
+        hashed_code: &str,
+    ) -> Result<Option<UserTotpRecoveryCode>, Self::Error>;
+
+    /// Mark a recovery code as used
+    ///
+    /// Returns the consumed [`UserTotpRecoveryCode`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_totp_recovery_code`: The recovery code to consume
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        user_totp_recovery_code: UserTotpRecoveryCode,
+    ) -> Result<UserTotpRecoveryCode, Self::Error>;
+
+    /// Count the unused recovery codes of a TOTP secret
+    ///
+    /// # Parameters
+    ///
+    /// * `user_totp_secret`: The TOTP secret the codes are attached to
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count_unused(
+        &mut self,
+        user_totp_secret: &UserTotpSecret,
+    ) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(UserTotpRecoveryCodeRepository:
+    async fn replace_all(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_totp_secret: &UserTotpSecret,
+        hashed_codes: Vec<String>,
+    ) -> Result<Vec<UserTotpRecoveryCode>, Self::Error>;
+    async fn find_unused(
+        &mut self,
+        user_totp_secret: &UserTotpSecret,
+        hashed_code: &str,
+    ) -> Result<Option<UserTotpRecoveryCode>, Self::Error>;
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        user_totp_recovery_code: UserTotpRecoveryCode,
+    ) -> Result<UserTotpRecoveryCode, Self::Error>;
+    async fn count_unused(&mut self, user_totp_secret: &UserTotpSecret)
+        -> Result<usize, Self::Error>;
+);
@@ -0,0 +1,303 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Repository to keep track of the deliveries of user lifecycle events to
+//! webhook targets
+
+use async_trait::async_trait;
+use mas_data_model::{User, WebhookDelivery, WebhookEventKind};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock, Page, Pagination};
+
+/// The state of a webhook delivery
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookDeliveryState {
+    /// The delivery was not attempted yet, or will be retried
+    Pending,
+    /// The event was delivered
+    Delivered,
+    /// The delivery failed and won't be retried
+    Failed,
+}
+
+impl WebhookDeliveryState {
+    /// Returns [`true`] if we're looking for pending deliveries
+    #[must_use]
+    pub fn is_pending(self) -> bool {
+        matches!(self, Self::Pending)
+    }
+
+    /// Returns [`true`] if we're looking for delivered events
+    #[must_use]
+    pub fn is_delivered(self) -> bool {
+        matches!(self, Self::Delivered)
+    }
+
+    /// Returns [`true`] if we're looking for failed deliveries
+    #[must_use]
+    pub fn is_failed(self) -> bool {
+        matches!(self, Self::Failed)
+    }
+}
+
+/// Filter parameters for listing webhook deliveries
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct WebhookDeliveryFilter<'a> {
+    target: Option<&'a str>,
+    event: Option<WebhookEventKind>,
+    user: Option<&'a User>,
+    state: Option<WebhookDeliveryState>,
+}
+
+impl<'a> WebhookDeliveryFilter<'a> {
+    /// Create a new [`WebhookDeliveryFilter`] with default values
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter for deliveries to a specific target
+    #[must_use]
+    pub fn for_target(mut self, target: &'a str) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Get the target filter
+    ///
+    /// Returns [`None`] if no target filter is set
+    #[must_use]
+    pub fn target(&self) -> Option<&'a str> {
+        self.target
+    }
+
+    /// Filter for deliveries of a specific kind of event
+    #[must_use]
+    pub fn for_event(mut self, event: WebhookEventKind) -> Self {
+        self.event = Some(event);
+        self
+    }
+
+    /// Get the event kind filter
+    ///
+    /// Returns [`None`] if no event kind filter is set
+    #[must_use]
+    pub fn event(&self) -> Option<WebhookEventKind> {
+        self.event
+    }
+
+    /// Filter for deliveries of events concerning a specific user
+    #[must_use]
+    pub fn for_user(mut self, user: &'a User) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Get the user filter
+    ///
+    /// Returns [`None`] if no user filter is set
+    #[must_use]
+    pub fn user(&self) -> Option<&'a User> {
+        self.user
+    }
+
+    /// Only return pending deliveries
+    #[must_use]
+    pub fn pending_only(mut self) -> Self {
+        self.state = Some(WebhookDeliveryState::Pending);
+        self
+    }
+
+    /// Only return deliveries acknowledged by their target
+    #[must_use]
+    pub fn delivered_only(mut self) -> Self {
+        self.state = Some(WebhookDeliveryState::Delivered);
+        self
+    }
+
+    /// Only return deliveries which ran out of attempts
+    #[must_use]
+    pub fn failed_only(mut self) -> Self {
+        self.state = Some(WebhookDeliveryState::Failed);
+        self
+    }
+
+    /// Get the state filter
+    ///
+    /// Returns [`None`] if no state filter is set
+    #[must_use]
+    pub fn state(&self) -> Option<WebhookDeliveryState> {
+        self.state
+    }
+}
+
+/// A [`WebhookDeliveryRepository`] helps interacting with the
+/// [`WebhookDelivery`] saved in the storage backend
+#[async_trait]
+pub trait WebhookDeliveryRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup a [`WebhookDelivery`] by its ID
+    ///
+    /// Returns `None` if no [`WebhookDelivery`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`WebhookDelivery`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<WebhookDelivery>, Self::Error>;
+
+    /// Create a new pending [`WebhookDelivery`]
+    ///
+    /// Returns the newly created [`WebhookDelivery`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `target`: The name of the webhook target
+    /// * `event`: The kind of event to deliver
+    /// * `user`: The [`User`] concerned by the event, if any
+    /// * `data`: The event-specific data to send to the target
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        target: String,
+        event: WebhookEventKind,
+        user: Option<&User>,
+        data: serde_json::Value,
+    ) -> Result<WebhookDelivery, Self::Error>;
+
+    /// Record a failed attempt at delivering a [`WebhookDelivery`]
+    ///
+    /// The delivery stays pending, so that it can be retried.
+    ///
+    /// Returns the updated [`WebhookDelivery`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `delivery`: The [`WebhookDelivery`] which failed
+    /// * `error`: A description of the error
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_failed_attempt(
+        &mut self,
+        clock: &dyn Clock,
+        delivery: WebhookDelivery,
+        error: String,
+    ) -> Result<WebhookDelivery, Self::Error>;
+
+    /// Mark a [`WebhookDelivery`] as acknowledged by its target
+    ///
+    /// Returns the updated [`WebhookDelivery`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `delivery`: The [`WebhookDelivery`] to mark as delivered
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn mark_as_delivered(
+        &mut self,
+        clock: &dyn Clock,
+        delivery: WebhookDelivery,
+    ) -> Result<WebhookDelivery, Self::Error>;
+
+    /// Give up on a [`WebhookDelivery`], moving it to the dead-letter state
+    ///
+    /// Returns the updated [`WebhookDelivery`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `delivery`: The [`WebhookDelivery`] to mark as failed
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn mark_as_failed(
+        &mut self,
+        clock: &dyn Clock,
+        delivery: WebhookDelivery,
+    ) -> Result<WebhookDelivery, Self::Error>;
+
+    /// List [`WebhookDelivery`] with the given filter and pagination
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter parameters
+    /// * `pagination`: The pagination parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(
+        &mut self,
+        filter: WebhookDeliveryFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<WebhookDelivery>, Self::Error>;
+
+    /// Count the [`WebhookDelivery`] with the given filter
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count(&mut self, filter: WebhookDeliveryFilter<'_>) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(WebhookDeliveryRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<WebhookDelivery>, Self::Error>;
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        target: String,
+        event: WebhookEventKind,
+        user: Option<&User>,
+        data: serde_json::Value,
+    ) -> Result<WebhookDelivery, Self::Error>;
+    async fn record_failed_attempt(
+        &mut self,
+        clock: &dyn Clock,
+        delivery: WebhookDelivery,
+        error: String,
+    ) -> Result<WebhookDelivery, Self::Error>;
+    async fn mark_as_delivered(
+        &mut self,
+        clock: &dyn Clock,
+        delivery: WebhookDelivery,
+    ) -> Result<WebhookDelivery, Self::Error>;
+    async fn mark_as_failed(
+        &mut self,
+        clock: &dyn Clock,
+        delivery: WebhookDelivery,
+    ) -> Result<WebhookDelivery, Self::Error>;
+    async fn list(
+        &mut self,
+        filter: WebhookDeliveryFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<WebhookDelivery>, Self::Error>;
+    async fn count(&mut self, filter: WebhookDeliveryFilter<'_>) -> Result<usize, Self::Error>;
+);
@@ -8,17 +8,29 @@
 
 use std::str::FromStr;
 
+use anyhow::Context;
 use apalis_core::{
     builder::{WorkerBuilder, WorkerFactoryFn},
     context::JobContext,
     executor::TokioExecutor,
     job::Job,
+    layers::extensions::Extension,
     monitor::Monitor,
     utils::timer::TokioTimer,
 };
 use apalis_cron::CronStream;
-use chrono::{DateTime, Utc};
-use mas_storage::{oauth2::OAuth2AccessTokenRepository, RepositoryAccess};
+use chrono::{DateTime, Duration, Utc};
+use mas_storage::{
+    compat::CompatSessionRepository,
+    oauth2::{
+        OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository,
+        OAuth2DeviceCodeGrantRepository, OAuth2PushedAuthorizationRequestRepository,
+        OAuth2RefreshTokenRepository, OAuth2SessionRepository,
+    },
+    upstream_oauth2::UpstreamOAuthSessionRepository,
+    user::{BrowserSessionRepository, UserRecoveryRepository},
+    BoxRepository, Clock, RepositoryAccess, RepositoryError,
+};
 use tracing::{debug, info};
 
 use crate::{
@@ -26,6 +38,147 @@ use crate::{
     JobContextExt, State,
 };
 
+/// Settings of the database maintenance job
+#[derive(Debug, Clone)]
+pub struct MaintenanceSettings {
+    /// When to run the job, as a cron expression with seconds
+    pub schedule: String,
+
+    /// How many rows are removed at most in a single transaction
+    pub batch_size: usize,
+
+    /// How long consumed refresh tokens are kept
+    pub refresh_token_retention: Duration,
+
+    /// How long finished OAuth 2.0, compatibility and browser sessions are kept
+    pub session_retention: Duration,
+
+    /// How long authorization grants are kept after their creation
+    pub authorization_grant_retention: Duration,
+
+    /// How long device code grants are kept after they expired
+    pub device_code_grant_retention: Duration,
+
+    /// How long recovery sessions are kept after their creation
+    pub recovery_session_retention: Duration,
+
+    /// How long upstream OAuth 2.0 sessions are kept after their creation
+    pub upstream_oauth_session_retention: Duration,
+}
+
+impl Default for MaintenanceSettings {
+    fn default() -> Self {
+        Self {
+            schedule: "*/15 * * * * *".to_owned(),
+            batch_size: 1000,
+            refresh_token_retention: Duration::try_days(7).unwrap(),
+            session_retention: Duration::try_days(30).unwrap(),
+            authorization_grant_retention: Duration::try_days(1).unwrap(),
+            device_code_grant_retention: Duration::try_days(1).unwrap(),
+            recovery_session_retention: Duration::try_days(7).unwrap(),
+            upstream_oauth_session_retention: Duration::try_days(7).unwrap(),
+        }
+    }
+}
+
+/// A category of rows removed by the maintenance job
+#[derive(Debug, Clone, Copy)]
+enum Cleanup {
+    ConsumedRefreshTokens,
+    FinishedOAuth2Sessions,
+    FinishedCompatSessions,
+    FinishedBrowserSessions,
+    StaleAuthorizationGrants,
+    ExpiredDeviceCodeGrants,
+    RecoverySessions,
+    StaleUpstreamOAuthSessions,
+}
+
+impl Cleanup {
+    /// The categories, in the order in which they are cleaned up.
+    ///
+    /// Browser sessions come after the OAuth 2.0 sessions, as they can't be
+    /// removed while a session still references them.
+    const ALL: [Self; 8] = [
+        Self::ConsumedRefreshTokens,
+        Self::FinishedOAuth2Sessions,
+        Self::FinishedCompatSessions,
+        Self::StaleAuthorizationGrants,
+        Self::ExpiredDeviceCodeGrants,
+        Self::FinishedBrowserSessions,
+        Self::RecoverySessions,
+        Self::StaleUpstreamOAuthSessions,
+    ];
+
+    const fn description(self) -> &'static str {
+        match self {
+            Self::ConsumedRefreshTokens => "consumed refresh tokens",
+            Self::FinishedOAuth2Sessions => "finished OAuth 2.0 sessions",
+            Self::FinishedCompatSessions => "finished compatibility sessions",
+            Self::FinishedBrowserSessions => "finished browser sessions",
+            Self::StaleAuthorizationGrants => "stale authorization grants",
+            Self::ExpiredDeviceCodeGrants => "expired device code grants",
+            Self::RecoverySessions => "recovery sessions",
+            Self::StaleUpstreamOAuthSessions => "stale upstream OAuth 2.0 sessions",
+        }
+    }
+
+    const fn retention(self, settings: &MaintenanceSettings) -> Duration {
+        match self {
+            Self::ConsumedRefreshTokens => settings.refresh_token_retention,
+            Self::FinishedOAuth2Sessions
+            | Self::FinishedCompatSessions
+            | Self::FinishedBrowserSessions => settings.session_retention,
+            Self::StaleAuthorizationGrants => settings.authorization_grant_retention,
+            Self::ExpiredDeviceCodeGrants => settings.device_code_grant_retention,
+            Self::RecoverySessions => settings.recovery_session_retention,
+            Self::StaleUpstreamOAuthSessions => settings.upstream_oauth_session_retention,
+        }
+    }
+
+    /// Remove one batch of rows older than `before`, returning how many rows
+    /// were removed
+    async fn run(
+        self,
+        repo: &mut BoxRepository,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, RepositoryError> {
+        match self {
+            Self::ConsumedRefreshTokens => {
+                repo.oauth2_refresh_token()
+                    .cleanup_consumed(before, limit)
+                    .await
+            }
+            Self::FinishedOAuth2Sessions => {
+                repo.oauth2_session().cleanup_finished(before, limit).await
+            }
+            Self::FinishedCompatSessions => {
+                repo.compat_session().cleanup_finished(before, limit).await
+            }
+            Self::FinishedBrowserSessions => {
+                repo.browser_session().cleanup_finished(before, limit).await
+            }
+            Self::StaleAuthorizationGrants => {
+                repo.oauth2_authorization_grant()
+                    .cleanup_stale(before, limit)
+                    .await
+            }
+            Self::ExpiredDeviceCodeGrants => {
+                repo.oauth2_device_code_grant()
+                    .cleanup_expired(before, limit)
+                    .await
+            }
+            Self::RecoverySessions => repo.user_recovery().cleanup_sessions(before, limit).await,
+            Self::StaleUpstreamOAuthSessions => {
+                repo.upstream_oauth_session()
+                    .cleanup_stale(before, limit)
+                    .await
+            }
+        }
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct CleanupExpiredTokensJob {
     scheduled: DateTime<Utc>,
@@ -51,9 +204,22 @@ pub async fn cleanup_expired_tokens(
 
     let state = ctx.state();
     let clock = state.clock();
+    let settings = ctx
+        .data_opt::<MaintenanceSettings>()
+        .expect("maintenance settings not injected in job context")
+        .clone();
     let mut repo = state.repository().await?;
 
     let count = repo.oauth2_access_token().cleanup_expired(&clock).await?;
+    let par_count = repo
+        .oauth2_pushed_authorization_request()
+        .cleanup_expired(&clock)
+        .await?;
+    let assertion_count = repo
+        .oauth2_jwt_bearer_assertion()
+        .cleanup_expired(&clock)
+        .await?;
+    let dpop_proof_count = repo.oauth2_dpop_proof().cleanup_expired(&clock).await?;
     repo.save().await?;
 
     if count == 0 {
@@ -62,6 +228,50 @@ pub async fn cleanup_expired_tokens(
         info!(count, "cleaned up expired tokens");
     }
 
+    if par_count > 0 {
+        info!(
+            count = par_count,
+            "cleaned up expired pushed authorization requests"
+        );
+    }
+
+    if assertion_count > 0 {
+        info!(
+            count = assertion_count,
+            "cleaned up expired JWT bearer assertions"
+        );
+    }
+
+    if dpop_proof_count > 0 {
+        info!(count = dpop_proof_count, "cleaned up expired DPoP proofs");
+    }
+
+    for cleanup in Cleanup::ALL {
+        let before = clock.now() - cleanup.retention(&settings);
+
+        // Each batch is committed in its own transaction, so that the rows
+        // don't stay locked for too long
+        let mut total = 0;
+        loop {
+            let mut repo = state.repository().await?;
+            let count = cleanup.run(&mut repo, before, settings.batch_size).await?;
+            repo.save().await?;
+
+            total += count;
+            if count < settings.batch_size {
+                break;
+            }
+        }
+
+        if total > 0 {
+            info!(
+                count = total,
+                "cleaned up {what}",
+                what = cleanup.description()
+            );
+        }
+    }
+
     Ok(())
 }
 
@@ -69,15 +279,18 @@ pub(crate) fn register(
     suffix: &str,
     monitor: Monitor<TokioExecutor>,
     state: &State,
-) -> Monitor<TokioExecutor> {
-    let schedule = apalis_cron::Schedule::from_str("*/15 * * * * *").unwrap();
+    settings: MaintenanceSettings,
+) -> Result<Monitor<TokioExecutor>, anyhow::Error> {
+    let schedule = apalis_cron::Schedule::from_str(&settings.schedule)
+        .with_context(|| format!("invalid maintenance schedule {:?}", settings.schedule))?;
     let worker_name = format!("{job}-{suffix}", job = CleanupExpiredTokensJob::NAME);
     let worker = WorkerBuilder::new(worker_name)
         .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
         .layer(state.inject())
+        .layer(Extension(settings))
         .layer(metrics_layer())
         .layer(trace_layer())
         .build_fn(cleanup_expired_tokens);
 
-    monitor.register(worker)
+    Ok(monitor.register(worker))
 }
@@ -7,7 +7,9 @@
 use std::sync::Arc;
 
 use apalis_core::{executor::TokioExecutor, layers::extensions::Extension, monitor::Monitor};
+use mas_axum_utils::http_client_factory::HttpClientFactory;
 use mas_email::Mailer;
+use mas_keystore::Keystore;
 use mas_matrix::HomeserverConnection;
 use mas_router::UrlBuilder;
 use mas_storage::{BoxClock, BoxRepository, SystemClock};
@@ -21,10 +23,20 @@ use crate::storage::PostgresStorageFactory;
 mod database;
 mod email;
 mod matrix;
+mod oauth2;
+mod queue;
 mod recovery;
+mod sessions;
 mod storage;
 mod user;
 mod utils;
+mod webhooks;
+
+pub use self::{
+    database::MaintenanceSettings,
+    sessions::{SessionExpiration, SessionExpirationSettings},
+    webhooks::WebhookTarget,
+};
 
 #[derive(Clone)]
 struct State {
@@ -33,15 +45,22 @@ struct State {
     clock: SystemClock,
     homeserver: Arc<dyn HomeserverConnection<Error = anyhow::Error>>,
     url_builder: UrlBuilder,
+    key_store: Keystore,
+    http_client_factory: HttpClientFactory,
+    webhook_targets: Arc<Vec<WebhookTarget>>,
 }
 
 impl State {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pool: Pool<Postgres>,
         clock: SystemClock,
         mailer: Mailer,
         homeserver: impl HomeserverConnection<Error = anyhow::Error> + 'static,
         url_builder: UrlBuilder,
+        key_store: Keystore,
+        http_client_factory: HttpClientFactory,
+        webhook_targets: Vec<WebhookTarget>,
     ) -> Self {
         Self {
             pool,
@@ -49,6 +68,9 @@ impl State {
             clock,
             homeserver: Arc::new(homeserver),
             url_builder,
+            key_store,
+            http_client_factory,
+            webhook_targets: Arc::new(webhook_targets),
         }
     }
 
@@ -87,6 +109,24 @@ impl State {
     pub fn url_builder(&self) -> &UrlBuilder {
         &self.url_builder
     }
+
+    pub fn key_store(&self) -> &Keystore {
+        &self.key_store
+    }
+
+    pub fn http_client_factory(&self) -> &HttpClientFactory {
+        &self.http_client_factory
+    }
+
+    pub fn webhook_targets(&self) -> &[WebhookTarget] {
+        &self.webhook_targets
+    }
+
+    pub fn webhook_target(&self, name: &str) -> Option<&WebhookTarget> {
+        self.webhook_targets
+            .iter()
+            .find(|target| target.name == name)
+    }
 }
 
 trait JobContextExt {
@@ -131,28 +171,42 @@ pub(crate) use build;
 ///
 /// # Errors
 ///
-/// This function can fail if the database connection fails.
+/// This function can fail if the database connection fails, or if the
+/// maintenance schedule is invalid.
+#[allow(clippy::too_many_arguments)]
 pub async fn init(
     name: &str,
     pool: &Pool<Postgres>,
     mailer: &Mailer,
     homeserver: impl HomeserverConnection<Error = anyhow::Error> + 'static,
     url_builder: UrlBuilder,
-) -> Result<Monitor<TokioExecutor>, sqlx::Error> {
+    key_store: Keystore,
+    http_client_factory: HttpClientFactory,
+    webhook_targets: Vec<WebhookTarget>,
+    maintenance: MaintenanceSettings,
+    session_expiration: SessionExpirationSettings,
+) -> Result<Monitor<TokioExecutor>, anyhow::Error> {
     let state = State::new(
         pool.clone(),
         SystemClock::default(),
         mailer.clone(),
         homeserver,
         url_builder,
+        key_store,
+        http_client_factory,
+        webhook_targets,
     );
     let factory = PostgresStorageFactory::new(pool.clone());
     let monitor = Monitor::new().executor(TokioExecutor::new());
-    let monitor = self::database::register(name, monitor, &state);
+    let monitor = self::database::register(name, monitor, &state, maintenance)?;
     let monitor = self::email::register(name, monitor, &state, &factory);
     let monitor = self::matrix::register(name, monitor, &state, &factory);
     let monitor = self::user::register(name, monitor, &state, &factory);
     let monitor = self::recovery::register(name, monitor, &state, &factory);
+    let monitor = self::oauth2::register(name, monitor, &state, &factory);
+    let monitor = self::webhooks::register(name, monitor, &state, &factory);
+    let monitor = self::queue::register(name, monitor, &state);
+    let monitor = self::sessions::register(name, monitor, &state, session_expiration);
     // TODO: we might want to grab the join handle here
     factory.listen().await?;
     debug!(?monitor, "workers registered");
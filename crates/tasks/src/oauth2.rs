@@ -0,0 +1,261 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+use apalis_core::{context::JobContext, executor::TokioExecutor, monitor::Monitor};
+use chrono::{DateTime, Duration, Utc};
+use http::Request;
+use mas_data_model::Client;
+use mas_http::HttpServiceExt;
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_jose::{
+    claims,
+    constraints::Constrainable,
+    jwt::{JsonWebSignatureHeader, Jwt},
+};
+use mas_keystore::Keystore;
+use mas_storage::{
+    job::{JobWithSpanContext, SendBackchannelLogoutJob},
+    RepositoryAccess,
+};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    CryptoRng, RngCore,
+};
+use serde::Serialize;
+use tower::{Service, ServiceExt};
+use tracing::{info, warn};
+use ulid::Ulid;
+
+use crate::{storage::PostgresStorageFactory, JobContextExt, State};
+
+/// The body of a back-channel logout request
+#[derive(Serialize)]
+struct BackchannelLogoutRequest {
+    logout_token: String,
+}
+
+/// Job to notify a client that one of its sessions ended, by sending a Logout
+/// Token to its back-channel logout URI.
+///
+/// Errors while calling the client make the job fail, so that it gets retried
+/// later.
+#[tracing::instrument(
+    name = "job.send_backchannel_logout",
+    fields(oauth2_session.id = %job.oauth2_session_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn send_backchannel_logout(
+    job: JobWithSpanContext<SendBackchannelLogoutJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let clock = state.clock();
+    let mut rng = state.rng();
+    let mut repo = state.repository().await?;
+
+    let session = repo
+        .oauth2_session()
+        .lookup(job.oauth2_session_id())
+        .await?
+        .context("OAuth 2.0 session not found")?;
+
+    let client = repo
+        .oauth2_client()
+        .lookup(session.client_id)
+        .await?
+        .context("Client not found")?;
+
+    let Some(backchannel_logout_uri) = client.backchannel_logout_uri.clone() else {
+        info!("Client has no back-channel logout URI, not sending a notification");
+        return Ok(());
+    };
+
+    let Some(user_id) = session.user_id else {
+        info!("Session has no user, not sending a notification");
+        return Ok(());
+    };
+
+    let user = repo
+        .user()
+        .lookup(user_id)
+        .await?
+        .context("User not found")?;
+
+    // We don't need the database connection anymore, release it before calling
+    // the client
+    repo.cancel().await?;
+
+    if client.backchannel_logout_session_required && session.user_session_id.is_none() {
+        warn!("Client requires a session ID, but the session has none");
+        return Ok(());
+    }
+
+    let logout_token = logout_token(
+        &mut rng,
+        clock.now(),
+        state.url_builder().oidc_issuer().to_string(),
+        state.key_store(),
+        &client,
+        user.sub,
+        session.user_session_id,
+    )?;
+
+    let mut http_client = state
+        .http_client_factory()
+        .client("oauth2.backchannel_logout")
+        .request_bytes_to_body()
+        .form_urlencoded_request();
+
+    let request = Request::post(backchannel_logout_uri.as_str())
+        .body(BackchannelLogoutRequest { logout_token })?;
+
+    let response = http_client
+        .ready()
+        .await?
+        .call(request)
+        .await
+        .context("Failed to send the logout token to the client")?;
+
+    if !response.status().is_success() {
+        bail!(
+            "Client responded with an unexpected status code: {}",
+            response.status()
+        );
+    }
+
+    info!("Sent back-channel logout notification");
+
+    Ok(())
+}
+
+/// Build and sign the Logout Token sent to the client, as defined by the
+/// OpenID Connect Back-Channel Logout specification
+fn logout_token(
+    rng: &mut (impl RngCore + CryptoRng),
+    now: DateTime<Utc>,
+    issuer: String,
+    key_store: &Keystore,
+    client: &Client,
+    sub: String,
+    user_session_id: Option<Ulid>,
+) -> Result<String, anyhow::Error> {
+    let mut claims = HashMap::new();
+    claims::ISS.insert(&mut claims, issuer)?;
+    claims::SUB.insert(&mut claims, sub)?;
+    claims::AUD.insert(&mut claims, client.client_id.clone())?;
+    claims::IAT.insert(&mut claims, now)?;
+    claims::EXP.insert(&mut claims, now + Duration::try_minutes(2).unwrap())?;
+    claims::JTI.insert(&mut claims, Alphanumeric.sample_string(rng, 32))?;
+    claims::EVENTS.insert(
+        &mut claims,
+        HashMap::from([(
+            claims::BACKCHANNEL_LOGOUT_EVENT.to_owned(),
+            serde_json::json!({}),
+        )]),
+    )?;
+
+    if let Some(user_session_id) = user_session_id {
+        claims::SID.insert(&mut claims, user_session_id.to_string())?;
+    }
+
+    // Logout tokens are signed with the same algorithm as the ID tokens
+    let alg = client
+        .id_token_signed_response_alg
+        .clone()
+        .unwrap_or(JsonWebSignatureAlg::Rs256);
+    let key = key_store
+        .signing_key_for_algorithm(&alg)
+        .context("No signing key available for the client")?;
+    let signer = key.params().signing_key_for_alg(&alg)?;
+    let header = JsonWebSignatureHeader::new(alg)
+        .with_kid(key.kid().context("Signing key has no kid")?)
+        .with_typ("logout+jwt".to_owned());
+    let logout_token = Jwt::sign_with_rng(rng, header, claims, &signer)?;
+
+    Ok(logout_token.into_string())
+}
+
+pub(crate) fn register(
+    suffix: &str,
+    monitor: Monitor<TokioExecutor>,
+    state: &State,
+    storage_factory: &PostgresStorageFactory,
+) -> Monitor<TokioExecutor> {
+    let send_backchannel_logout_worker = crate::build!(SendBackchannelLogoutJob => send_backchannel_logout, suffix, state, storage_factory);
+
+    monitor.register(send_backchannel_logout_worker)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use mas_jose::jwt::Jwt;
+    use mas_keystore::{JsonWebKey, JsonWebKeySet, PrivateKey};
+    use rand::SeedableRng;
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_logout_token() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let now = Utc.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap();
+
+        let key =
+            PrivateKey::load_pem(include_str!("../../keystore/tests/keys/rsa.pkcs1.pem")).unwrap();
+        let key_store = Keystore::new(JsonWebKeySet::new(vec![
+            JsonWebKey::new(key).with_kid("rsa")
+        ]));
+
+        let client = Client::samples(now, &mut rng).remove(0);
+        let user_session_id = Ulid::from_datetime_with_source(now.into(), &mut rng);
+
+        let token = logout_token(
+            &mut rng,
+            now,
+            "https://example.com/".to_owned(),
+            &key_store,
+            &client,
+            "abcd".to_owned(),
+            Some(user_session_id),
+        )
+        .unwrap();
+
+        let jwt: Jwt<'_, HashMap<String, Value>> = Jwt::try_from(token.as_str()).unwrap();
+        jwt.verify_with_jwks(&key_store.public_jwks()).unwrap();
+        assert_eq!(jwt.header().typ(), Some("logout+jwt"));
+
+        let claims = jwt.payload();
+        assert_eq!(claims["iss"], "https://example.com/");
+        assert_eq!(claims["sub"], "abcd");
+        assert_eq!(claims["aud"], "client1");
+        assert_eq!(claims["sid"], user_session_id.to_string());
+        assert_eq!(
+            claims["events"],
+            serde_json::json!({ "http://schemas.openid.net/event/backchannel-logout": {} })
+        );
+        assert!(claims.contains_key("jti"));
+        // Logout tokens must not contain a nonce
+        assert!(!claims.contains_key("nonce"));
+
+        // The session ID is omitted if the session has none
+        let token = logout_token(
+            &mut rng,
+            now,
+            "https://example.com/".to_owned(),
+            &key_store,
+            &client,
+            "abcd".to_owned(),
+            None,
+        )
+        .unwrap();
+        let jwt: Jwt<'_, HashMap<String, Value>> = Jwt::try_from(token.as_str()).unwrap();
+        assert!(!jwt.payload().contains_key("sid"));
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Metrics about the job queue itself
+//!
+//! The queue is periodically inspected by a cron job, and the result is
+//! reported through observable instruments.
+//!
+//! Every worker inspects the same queue and reports the same values, so they
+//! must be aggregated across replicas with `max`, not `sum`.
+
+use std::{
+    collections::BTreeSet,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use apalis_core::{
+    builder::{WorkerBuilder, WorkerFactoryFn},
+    context::JobContext,
+    executor::TokioExecutor,
+    job::Job,
+    layers::extensions::Extension,
+    monitor::Monitor,
+    utils::timer::TokioTimer,
+};
+use apalis_cron::CronStream;
+use chrono::{DateTime, Utc};
+use mas_storage::{
+    job::{JobQueueStats, JobRepository, JobStatus},
+    Clock, RepositoryAccess,
+};
+use opentelemetry::{metrics::MetricsError, Key};
+use tracing::{debug, warn};
+
+use crate::{
+    utils::{metrics_layer, trace_layer, TracedJob},
+    JobContextExt, State,
+};
+
+const JOB_NAME: Key = Key::from_static_str("job.name");
+const JOB_STATUS: Key = Key::from_static_str("job.status");
+
+/// The last statistics collected about the job queue
+#[derive(Default)]
+struct QueueSnapshot {
+    collected_at: Option<DateTime<Utc>>,
+    stats: Vec<JobQueueStats>,
+
+    /// The name of every job seen in the queue since the worker started
+    names: BTreeSet<String>,
+}
+
+#[derive(Default, Clone)]
+pub struct CollectJobQueueStatsJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for CollectJobQueueStatsJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for CollectJobQueueStatsJob {
+    const NAME: &'static str = "collect-job-queue-stats";
+}
+
+impl TracedJob for CollectJobQueueStatsJob {}
+
+pub async fn collect_job_queue_stats(
+    job: CollectJobQueueStatsJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!("collect job queue stats job scheduled at {}", job.scheduled);
+
+    let state = ctx.state();
+    let clock = state.clock();
+    let snapshot = ctx
+        .data_opt::<Arc<Mutex<QueueSnapshot>>>()
+        .expect("queue snapshot not injected in job context")
+        .clone();
+
+    let mut repo = state.repository().await?;
+    let queue_stats = repo.job().stats(&clock).await?;
+    repo.cancel().await?;
+
+    let mut snapshot = snapshot.lock().expect("queue snapshot lock poisoned");
+    snapshot.collected_at = Some(clock.now());
+    snapshot
+        .names
+        .extend(queue_stats.iter().map(|s| s.name.clone()));
+    snapshot.stats = queue_stats;
+
+    Ok(())
+}
+
+/// Register the instruments reporting the last collected statistics
+fn register_instruments(snapshot: Arc<Mutex<QueueSnapshot>>) -> Result<(), MetricsError> {
+    let meter = opentelemetry::global::meter_with_version(
+        env!("CARGO_PKG_NAME"),
+        Some(env!("CARGO_PKG_VERSION")),
+        Some(opentelemetry_semantic_conventions::SCHEMA_URL),
+        None,
+    );
+
+    let jobs = meter
+        .i64_observable_up_down_counter("job.queue.jobs")
+        .with_description(
+            "The number of jobs in the queue, by name and status. Completed jobs are not counted.",
+        )
+        .with_unit("{job}")
+        .init();
+
+    let retried = meter
+        .i64_observable_up_down_counter("job.queue.retried")
+        .with_description(
+            "The number of jobs in the queue which had at least one failed attempt, by name and status.",
+        )
+        .with_unit("{job}")
+        .init();
+
+    let oldest_pending_age = meter
+        .f64_observable_gauge("job.queue.oldest_pending_age")
+        .with_description(
+            "How long the oldest pending job has been waiting to be picked up, by name.",
+        )
+        .with_unit("s")
+        .init();
+
+    meter.register_callback(
+        &[jobs.as_any(), retried.as_any(), oldest_pending_age.as_any()],
+        move |observer| {
+            let snapshot = snapshot.lock().expect("queue snapshot lock poisoned");
+            let Some(collected_at) = snapshot.collected_at else {
+                return;
+            };
+
+            // Report every job seen so far, even once it has left the queue, so
+            // that its values go back to zero instead of staying at their last
+            // observed value
+            for name in &snapshot.names {
+                for status in JobStatus::ALL {
+                    if status == JobStatus::Done {
+                        continue;
+                    }
+
+                    let stats = snapshot
+                        .stats
+                        .iter()
+                        .find(|s| s.name == *name && s.status == status);
+                    let attributes = [
+                        JOB_NAME.string(name.clone()),
+                        JOB_STATUS.string(status.as_str()),
+                    ];
+
+                    let count = stats.map_or(0, |s| s.count);
+                    let retried_count = stats.map_or(0, |s| s.retried);
+                    observer.observe_i64(
+                        &jobs,
+                        i64::try_from(count).unwrap_or(i64::MAX),
+                        &attributes,
+                    );
+                    observer.observe_i64(
+                        &retried,
+                        i64::try_from(retried_count).unwrap_or(i64::MAX),
+                        &attributes,
+                    );
+
+                    if status == JobStatus::Pending {
+                        let age = stats.and_then(|s| s.oldest_due_at).map_or(0.0, |due_at| {
+                            (collected_at - due_at)
+                                .to_std()
+                                .unwrap_or_default()
+                                .as_secs_f64()
+                        });
+                        observer.observe_f64(
+                            &oldest_pending_age,
+                            age,
+                            &[JOB_NAME.string(name.clone())],
+                        );
+                    }
+                }
+            }
+        },
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn register(
+    suffix: &str,
+    monitor: Monitor<TokioExecutor>,
+    state: &State,
+) -> Monitor<TokioExecutor> {
+    let snapshot = Arc::new(Mutex::new(QueueSnapshot::default()));
+    if let Err(e) = register_instruments(snapshot.clone()) {
+        warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to register the job queue metrics"
+        );
+    }
+
+    let schedule = apalis_cron::Schedule::from_str("*/30 * * * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = CollectJobQueueStatsJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(Extension(snapshot))
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(collect_job_queue_stats);
+
+    monitor.register(worker)
+}
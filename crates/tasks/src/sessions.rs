@@ -0,0 +1,701 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+//! Automatic expiration of inactive or old sessions
+//!
+//! Sessions are finished based on the activity recorded by the activity
+//! tracker. Finishing OAuth 2.0 and compatibility sessions also schedules a
+//! sync of the user's devices with the homeserver. Finishing a browser session
+//! also finishes the OAuth 2.0 sessions started from it, like a logout would.
+//! Every finished session is recorded in the audit log, with the system as
+//! actor.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use apalis_core::{
+    builder::{WorkerBuilder, WorkerFactoryFn},
+    context::JobContext,
+    executor::TokioExecutor,
+    job::Job,
+    layers::extensions::Extension,
+    monitor::Monitor,
+    utils::timer::TokioTimer,
+};
+use apalis_cron::CronStream;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{AuditContext, AuditEventKind};
+use mas_storage::{
+    compat::CompatSessionFilter,
+    job::{
+        schedule_backchannel_logout_jobs, BackchannelLogoutScheduler, JobRepositoryExt,
+        SyncDevicesJob,
+    },
+    oauth2::OAuth2SessionFilter,
+    user::BrowserSessionFilter,
+    BoxRepository, Clock, Pagination, RepositoryAccess,
+};
+use mas_storage_pg::PgRepository;
+use rand::RngCore;
+use sqlx::PgPool;
+use tracing::{debug, info};
+use ulid::Ulid;
+
+use crate::{
+    utils::{metrics_layer, trace_layer, TracedJob},
+    JobContextExt, State,
+};
+
+/// How many sessions are finished in a single transaction
+const BATCH_SIZE: usize = 100;
+
+/// How long sessions of a kind can last
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionExpiration {
+    /// How long a session can stay inactive before it is finished
+    pub idle_timeout: Option<Duration>,
+
+    /// How long a session can last in total, regardless of its activity
+    pub max_lifetime: Option<Duration>,
+}
+
+impl SessionExpiration {
+    /// The filters matching the sessions which should be finished, as of
+    /// `now`
+    fn cutoffs(self, now: DateTime<Utc>) -> impl Iterator<Item = Cutoff> {
+        let idle = self
+            .idle_timeout
+            .map(|idle_timeout| Cutoff::InactiveSince(now - idle_timeout));
+        let lifetime = self
+            .max_lifetime
+            .map(|max_lifetime| Cutoff::CreatedBefore(now - max_lifetime));
+        idle.into_iter().chain(lifetime)
+    }
+}
+
+/// Settings of the session expiration job
+#[derive(Debug, Clone, Default)]
+pub struct SessionExpirationSettings {
+    /// Expiration of OAuth 2.0 sessions
+    pub oauth2: SessionExpiration,
+
+    /// Expiration of OAuth 2.0 sessions of specific clients, overriding
+    /// [`Self::oauth2`]
+    pub oauth2_clients: BTreeMap<Ulid, SessionExpiration>,
+
+    /// Expiration of compatibility sessions
+    pub compat: SessionExpiration,
+
+    /// Expiration of browser sessions
+    pub browser: SessionExpiration,
+}
+
+impl SessionExpirationSettings {
+    fn is_enabled(&self) -> bool {
+        self.oauth2 != SessionExpiration::default()
+            || !self.oauth2_clients.is_empty()
+            || self.compat != SessionExpiration::default()
+            || self.browser != SessionExpiration::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Cutoff {
+    InactiveSince(DateTime<Utc>),
+    CreatedBefore(DateTime<Utc>),
+}
+
+impl Cutoff {
+    /// Why the sessions matching this cutoff are finished, as recorded in the
+    /// audit log
+    fn reason(self) -> &'static str {
+        match self {
+            Self::InactiveSince(_) => "idle_timeout",
+            Self::CreatedBefore(_) => "max_lifetime",
+        }
+    }
+
+    fn oauth2(self, filter: OAuth2SessionFilter<'_>) -> OAuth2SessionFilter<'_> {
+        match self {
+            Self::InactiveSince(since) => filter.with_inactive_since(since),
+            Self::CreatedBefore(before) => filter.with_created_before(before),
+        }
+    }
+
+    fn compat(self, filter: CompatSessionFilter<'_>) -> CompatSessionFilter<'_> {
+        match self {
+            Self::InactiveSince(since) => filter.with_inactive_since(since),
+            Self::CreatedBefore(before) => filter.with_created_before(before),
+        }
+    }
+
+    fn browser(self, filter: BrowserSessionFilter<'_>) -> BrowserSessionFilter<'_> {
+        match self {
+            Self::InactiveSince(since) => filter.with_inactive_since(since),
+            Self::CreatedBefore(before) => filter.with_created_before(before),
+        }
+    }
+}
+
+/// Record the end of the given sessions in the audit log, and schedule a sync
+/// of the devices of their users with the homeserver
+///
+/// `sessions` maps the ID of each user to the IDs of their finished sessions.
+/// Sessions which don't belong to a user are listed under `None`.
+async fn record_expired_sessions(
+    repo: &mut BoxRepository,
+    rng: &mut (dyn RngCore + Send),
+    clock: &dyn Clock,
+    session_type: &str,
+    reason: &str,
+    sessions: BTreeMap<Option<Ulid>, Vec<Ulid>>,
+) -> Result<(), anyhow::Error> {
+    let audit = AuditContext::system();
+    for (user_id, session_ids) in sessions {
+        let user = match user_id {
+            Some(user_id) => Some(
+                repo.user()
+                    .lookup(user_id)
+                    .await?
+                    .context("User not found")?,
+            ),
+            None => None,
+        };
+
+        for session_id in session_ids {
+            repo.audit_event()
+                .add(
+                    rng,
+                    clock,
+                    &audit,
+                    AuditEventKind::SessionEnded,
+                    user.as_ref(),
+                    serde_json::json!({
+                        "session_type": session_type,
+                        "session_id": session_id,
+                        "reason": reason,
+                    }),
+                )
+                .await?;
+        }
+
+        if let Some(user) = &user {
+            repo.job().schedule_job(SyncDevicesJob::new(user)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Finish the OAuth 2.0 sessions matching the filter, except the ones of the
+/// clients in `excluded_clients`
+async fn expire_oauth2_sessions(
+    pool: &PgPool,
+    rng: &mut (dyn RngCore + Send),
+    clock: &dyn Clock,
+    cutoff: Cutoff,
+    filter: OAuth2SessionFilter<'_>,
+    excluded_clients: &BTreeSet<Ulid>,
+) -> Result<usize, anyhow::Error> {
+    let filter = cutoff.oauth2(filter).active_only();
+    let mut backchannel_logout = BackchannelLogoutScheduler::default();
+    let mut count = 0;
+    let mut cursor = Pagination::first(BATCH_SIZE);
+    loop {
+        // Each batch is done in its own transaction, to avoid keeping the
+        // sessions locked for too long
+        let mut repo = PgRepository::from_pool(pool).await?.boxed();
+        let page = repo.oauth2_session().list(filter, cursor).await?;
+
+        let mut sessions = BTreeMap::<_, Vec<_>>::new();
+        for session in page.edges {
+            cursor = cursor.after(session.id);
+            if excluded_clients.contains(&session.client_id) {
+                continue;
+            }
+
+            backchannel_logout.schedule(&mut repo, &session).await?;
+            sessions
+                .entry(session.user_id)
+                .or_default()
+                .push(session.id);
+            repo.oauth2_session().finish(clock, session).await?;
+            count += 1;
+        }
+
+        record_expired_sessions(&mut repo, rng, clock, "oauth2", cutoff.reason(), sessions).await?;
+        repo.save().await?;
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(count)
+}
+
+async fn expire_compat_sessions(
+    pool: &PgPool,
+    rng: &mut (dyn RngCore + Send),
+    clock: &dyn Clock,
+    cutoff: Cutoff,
+) -> Result<usize, anyhow::Error> {
+    let filter = cutoff.compat(CompatSessionFilter::new()).active_only();
+    let mut count = 0;
+    let mut cursor = Pagination::first(BATCH_SIZE);
+    loop {
+        let mut repo = PgRepository::from_pool(pool).await?.boxed();
+        let page = repo.compat_session().list(filter, cursor).await?;
+
+        let mut sessions = BTreeMap::<_, Vec<_>>::new();
+        for (session, _) in page.edges {
+            cursor = cursor.after(session.id);
+            sessions
+                .entry(Some(session.user_id))
+                .or_default()
+                .push(session.id);
+            repo.compat_session().finish(clock, session).await?;
+            count += 1;
+        }
+
+        record_expired_sessions(&mut repo, rng, clock, "compat", cutoff.reason(), sessions).await?;
+        repo.save().await?;
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Finish the browser sessions matching the cutoff, along with the OAuth 2.0
+/// sessions started from them
+async fn expire_browser_sessions(
+    pool: &PgPool,
+    rng: &mut (dyn RngCore + Send),
+    clock: &dyn Clock,
+    cutoff: Cutoff,
+) -> Result<usize, anyhow::Error> {
+    let filter = cutoff.browser(BrowserSessionFilter::new()).active_only();
+    let audit = AuditContext::system();
+    let mut count = 0;
+    let mut cursor = Pagination::first(BATCH_SIZE);
+    loop {
+        let mut repo = PgRepository::from_pool(pool).await?.boxed();
+        let page = repo.browser_session().list(filter, cursor).await?;
+
+        for session in page.edges {
+            cursor = cursor.after(session.id);
+
+            let oauth2_filter = OAuth2SessionFilter::new()
+                .for_browser_session(&session)
+                .active_only();
+
+            // Notify the clients which have sessions started from this browser
+            // session that it ended
+            schedule_backchannel_logout_jobs(&mut repo, oauth2_filter).await?;
+
+            // End all the OAuth 2.0 sessions which were started from this
+            // browser session
+            let affected = repo
+                .oauth2_session()
+                .finish_bulk(clock, oauth2_filter)
+                .await?;
+
+            if affected > 0 {
+                repo.job()
+                    .schedule_job(SyncDevicesJob::new(&session.user))
+                    .await?;
+
+                repo.audit_event()
+                    .add(
+                        rng,
+                        clock,
+                        &audit,
+                        AuditEventKind::SessionEnded,
+                        Some(&session.user),
+                        serde_json::json!({
+                            "session_type": "oauth2",
+                            "count": affected,
+                            "reason": cutoff.reason(),
+                        }),
+                    )
+                    .await?;
+            }
+
+            let session = repo.browser_session().finish(clock, session).await?;
+            repo.audit_event()
+                .add(
+                    rng,
+                    clock,
+                    &audit,
+                    AuditEventKind::SessionEnded,
+                    Some(&session.user),
+                    serde_json::json!({
+                        "session_type": "browser",
+                        "session_id": session.id,
+                        "reason": cutoff.reason(),
+                    }),
+                )
+                .await?;
+            count += 1;
+        }
+
+        repo.save().await?;
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Finish all the sessions which expired as of now, according to the settings
+async fn expire(
+    pool: &PgPool,
+    rng: &mut (dyn RngCore + Send),
+    clock: &dyn Clock,
+    settings: &SessionExpirationSettings,
+) -> Result<(), anyhow::Error> {
+    let now = clock.now();
+
+    // Sessions of clients with specific settings
+    let mut count = 0;
+    for (client_id, expiration) in &settings.oauth2_clients {
+        let mut repo = PgRepository::from_pool(pool).await?.boxed();
+        let client = repo.oauth2_client().lookup(*client_id).await?;
+        repo.cancel().await?;
+
+        let Some(client) = client else {
+            debug!(%client_id, "client with session expiration settings not found");
+            continue;
+        };
+
+        for cutoff in expiration.cutoffs(now) {
+            let filter = OAuth2SessionFilter::new().for_client(&client);
+            count +=
+                expire_oauth2_sessions(pool, rng, clock, cutoff, filter, &BTreeSet::new()).await?;
+        }
+    }
+
+    // Sessions of all the other clients
+    let excluded_clients = settings.oauth2_clients.keys().copied().collect();
+    for cutoff in settings.oauth2.cutoffs(now) {
+        let filter = OAuth2SessionFilter::new();
+        count +=
+            expire_oauth2_sessions(pool, rng, clock, cutoff, filter, &excluded_clients).await?;
+    }
+
+    if count > 0 {
+        info!(count, "finished expired OAuth 2.0 sessions");
+    }
+
+    let mut count = 0;
+    for cutoff in settings.compat.cutoffs(now) {
+        count += expire_compat_sessions(pool, rng, clock, cutoff).await?;
+    }
+
+    if count > 0 {
+        info!(count, "finished expired compatibility sessions");
+    }
+
+    let mut count = 0;
+    for cutoff in settings.browser.cutoffs(now) {
+        count += expire_browser_sessions(pool, rng, clock, cutoff).await?;
+    }
+
+    if count > 0 {
+        info!(count, "finished expired browser sessions");
+    }
+
+    Ok(())
+}
+
+#[derive(Default, Clone)]
+pub struct ExpireSessionsJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for ExpireSessionsJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for ExpireSessionsJob {
+    const NAME: &'static str = "expire-sessions";
+}
+
+impl TracedJob for ExpireSessionsJob {}
+
+pub async fn expire_sessions(
+    job: ExpireSessionsJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!("expire sessions job scheduled at {}", job.scheduled);
+
+    let state = ctx.state();
+    let clock = state.clock();
+    let settings = ctx
+        .data_opt::<SessionExpirationSettings>()
+        .expect("session expiration settings not injected in job context");
+
+    expire(state.pool(), &mut state.rng(), &clock, settings).await?;
+
+    Ok(())
+}
+
+pub(crate) fn register(
+    suffix: &str,
+    monitor: Monitor<TokioExecutor>,
+    state: &State,
+    settings: SessionExpirationSettings,
+) -> Monitor<TokioExecutor> {
+    // Don't bother running the job if no session ever expires
+    if !settings.is_enabled() {
+        return monitor;
+    }
+
+    let schedule = apalis_cron::Schedule::from_str("0 * * * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = ExpireSessionsJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(Extension(settings))
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(expire_sessions);
+
+    monitor.register(worker)
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_data_model::Client;
+    use mas_storage::clock::MockClock;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    async fn add_client(
+        repo: &mut BoxRepository,
+        rng: &mut ChaChaRng,
+        clock: &MockClock,
+    ) -> Client {
+        repo.oauth2_client()
+            .add(
+                rng,
+                clock,
+                vec!["https://example.com/redirect".parse().unwrap()],
+                None,
+                None,
+                Vec::new(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_expire_sessions(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "john".to_owned())
+            .await
+            .unwrap();
+        let client = add_client(&mut repo, &mut rng, &clock).await;
+        let override_client = add_client(&mut repo, &mut rng, &clock).await;
+
+        let idle_browser_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+        let active_browser_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+
+        let idle_session = repo
+            .oauth2_session()
+            .add(
+                &mut rng,
+                &clock,
+                &client,
+                Some(&user),
+                None,
+                "openid".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        let old_override_session = repo
+            .oauth2_session()
+            .add(
+                &mut rng,
+                &clock,
+                &override_client,
+                Some(&user),
+                None,
+                "openid".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        let linked_session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut rng,
+                &clock,
+                &override_client,
+                &idle_browser_session,
+                "openid".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let settings = SessionExpirationSettings {
+            oauth2: SessionExpiration {
+                idle_timeout: Some(Duration::try_hours(1).unwrap()),
+                max_lifetime: None,
+            },
+            oauth2_clients: BTreeMap::from([(
+                override_client.id,
+                SessionExpiration {
+                    idle_timeout: None,
+                    max_lifetime: Some(Duration::try_days(1).unwrap()),
+                },
+            )]),
+            compat: SessionExpiration::default(),
+            browser: SessionExpiration {
+                idle_timeout: Some(Duration::try_hours(2).unwrap()),
+                max_lifetime: None,
+            },
+        };
+
+        clock.advance(Duration::try_hours(3).unwrap());
+
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+        let recent_session = repo
+            .oauth2_session()
+            .add(
+                &mut rng,
+                &clock,
+                &client,
+                Some(&user),
+                None,
+                "openid".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        repo.browser_session()
+            .record_batch_activity(vec![(active_browser_session.id, clock.now(), None)])
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        expire(&pool, &mut rng, &clock, &settings).await.unwrap();
+
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        // Sessions of the default clients are finished once idle
+        let session = repo
+            .oauth2_session()
+            .lookup(idle_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_finished());
+        let session = repo
+            .oauth2_session()
+            .lookup(recent_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_valid());
+
+        // The client with its own settings is excluded from the default idle
+        // timeout
+        let session = repo
+            .oauth2_session()
+            .lookup(old_override_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_valid());
+
+        // The idle browser session is finished, along with the OAuth 2.0
+        // session started from it
+        let session = repo
+            .browser_session()
+            .lookup(idle_browser_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!session.active());
+        let session = repo
+            .oauth2_session()
+            .lookup(linked_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_finished());
+
+        let session = repo
+            .browser_session()
+            .lookup(active_browser_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.active());
+        repo.cancel().await.unwrap();
+
+        // Once past its maximum lifetime, the session of the client with its
+        // own settings is finished
+        clock.advance(Duration::try_days(1).unwrap());
+        expire(&pool, &mut rng, &clock, &settings).await.unwrap();
+
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+        let session = repo
+            .oauth2_session()
+            .lookup(old_override_session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_finished());
+        repo.cancel().await.unwrap();
+    }
+}
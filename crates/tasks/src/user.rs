@@ -6,9 +6,13 @@
 
 use anyhow::Context;
 use apalis_core::{context::JobContext, executor::TokioExecutor, monitor::Monitor};
+use mas_data_model::WebhookEventKind;
 use mas_storage::{
     compat::CompatSessionFilter,
-    job::{DeactivateUserJob, JobWithSpanContext, ReactivateUserJob},
+    job::{
+        schedule_backchannel_logout_jobs, DeactivateUserJob, DispatchWebhookEventJob,
+        JobRepositoryExt, JobWithSpanContext, ReactivateUserJob,
+    },
     oauth2::OAuth2SessionFilter,
     user::{BrowserSessionFilter, UserRepository},
     RepositoryAccess,
@@ -56,13 +60,14 @@ async fn deactivate_user(
         .await?;
     info!(affected = n, "Killed all browser sessions for user");
 
-    let n = repo
-        .oauth2_session()
-        .finish_bulk(
-            &clock,
-            OAuth2SessionFilter::new().for_user(&user).active_only(),
-        )
-        .await?;
+    let filter = OAuth2SessionFilter::new().for_user(&user).active_only();
+    let n = schedule_backchannel_logout_jobs(&mut repo, filter).await?;
+    info!(
+        scheduled = n,
+        "Scheduled back-channel logout notifications for user"
+    );
+
+    let n = repo.oauth2_session().finish_bulk(&clock, filter).await?;
     info!(affected = n, "Killed all OAuth 2.0 sessions for user");
 
     let n = repo
@@ -74,6 +79,13 @@ async fn deactivate_user(
         .await?;
     info!(affected = n, "Killed all compatibility sessions for user");
 
+    repo.job()
+        .schedule_job(
+            DispatchWebhookEventJob::new(WebhookEventKind::UserDeactivated, &user)
+                .with_data(serde_json::json!({ "erase": job.hs_erase() })),
+        )
+        .await?;
+
     // Before calling back to the homeserver, commit the changes to the database, as
     // we want the user to be locked out as soon as possible
     repo.save().await?;
@@ -112,7 +124,15 @@ pub async fn reactivate_user(
 
     // We want to unlock the user from our side only once it has been reactivated on
     // the homeserver
-    let _user = repo.user().unlock(user).await?;
+    let user = repo.user().unlock(user).await?;
+
+    repo.job()
+        .schedule_job(DispatchWebhookEventJob::new(
+            WebhookEventKind::UserReactivated,
+            &user,
+        ))
+        .await?;
+
     repo.save().await?;
 
     Ok(())
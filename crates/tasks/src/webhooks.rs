@@ -0,0 +1,373 @@
+// Copyright 2024 New Vector Ltd.
+//
+// SPDX-License-Identifier: AGPL-3.0-only
+// Please see LICENSE in the repository root for full details.
+
+use anyhow::{bail, Context};
+use apalis_core::{context::JobContext, executor::TokioExecutor, monitor::Monitor};
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use data_encoding::HEXLOWER;
+use hmac::{Hmac, Mac};
+use http::{header::CONTENT_TYPE, Request};
+use mas_data_model::WebhookEventKind;
+use mas_http::HttpServiceExt;
+use mas_storage::{
+    job::{DispatchWebhookEventJob, JobRepositoryExt, JobWithSpanContext, SendWebhookJob},
+    RepositoryAccess,
+};
+use serde::Serialize;
+use sha2::Sha256;
+use tower::{Service, ServiceExt};
+use tracing::{info, warn};
+use ulid::Ulid;
+use url::Url;
+
+use crate::{storage::PostgresStorageFactory, JobContextExt, State};
+
+/// An HTTP endpoint which receives user lifecycle events
+#[derive(Debug, Clone)]
+pub struct WebhookTarget {
+    /// The name of the target, used to keep track of deliveries
+    pub name: String,
+
+    /// The URL to which events are sent
+    pub url: Url,
+
+    /// The secret used to sign the payloads
+    pub secret: String,
+
+    /// The events sent to this target. If empty, all events are sent.
+    pub events: Vec<WebhookEventKind>,
+
+    /// How many times a delivery is attempted before giving up on it
+    pub max_attempts: u32,
+}
+
+impl WebhookTarget {
+    fn accepts(&self, event: WebhookEventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// The body of a webhook request
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    id: Ulid,
+    #[serde(rename = "type")]
+    event: WebhookEventKind,
+    created_at: DateTime<Utc>,
+    data: &'a serde_json::Value,
+}
+
+/// Compute the hex-encoded HMAC-SHA256 signature of a payload
+fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take a key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    HEXLOWER.encode(&mac.finalize().into_bytes())
+}
+
+/// How long to wait before the next attempt, given the number of attempts
+/// already made.
+///
+/// This starts at 30 seconds, doubles after each attempt, and is capped at one
+/// hour.
+fn retry_delay(attempts: u32) -> Duration {
+    let base = Duration::try_seconds(30).unwrap();
+    let max = Duration::try_hours(1).unwrap();
+    let exponent = attempts.saturating_sub(1).min(7);
+    (base * 2_i32.pow(exponent)).min(max)
+}
+
+/// When to retry a failed delivery, given the number of attempts already made.
+///
+/// Returns `None` once the target's maximum number of attempts is reached, in
+/// which case the delivery should be marked as failed.
+fn next_attempt_at(now: DateTime<Utc>, attempts: u32, max_attempts: u32) -> Option<DateTime<Utc>> {
+    if attempts >= max_attempts {
+        None
+    } else {
+        Some(now + retry_delay(attempts))
+    }
+}
+
+/// Job to fan out a user lifecycle event to the configured webhook targets.
+///
+/// This creates one delivery per target interested in the event, and
+/// schedules a job to send each of them.
+#[tracing::instrument(
+    name = "job.dispatch_webhook_event",
+    fields(user.id = %job.user_id(), webhook.event = %job.event()),
+    skip_all,
+    err(Debug),
+)]
+async fn dispatch_webhook_event(
+    job: JobWithSpanContext<DispatchWebhookEventJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+
+    let targets: Vec<_> = state
+        .webhook_targets()
+        .iter()
+        .filter(|target| target.accepts(job.event()))
+        .collect();
+
+    if targets.is_empty() {
+        info!("No webhook target for this event, skipping");
+        return Ok(());
+    }
+
+    let clock = state.clock();
+    let mut rng = state.rng();
+    let mut repo = state.repository().await?;
+
+    let user = repo
+        .user()
+        .lookup(job.user_id())
+        .await?
+        .context("User not found")?;
+
+    let mut data = serde_json::json!({
+        "user": {
+            "id": user.id,
+            "username": user.username,
+        },
+    });
+    if let (Some(data), Some(extra)) = (data.as_object_mut(), job.data().as_object()) {
+        data.extend(extra.clone());
+    }
+
+    for target in targets {
+        let delivery = repo
+            .webhook_delivery()
+            .add(
+                &mut rng,
+                &clock,
+                target.name.clone(),
+                job.event(),
+                Some(&user),
+                data.clone(),
+            )
+            .await?;
+
+        repo.job()
+            .schedule_job(SendWebhookJob::new(&delivery))
+            .await?;
+
+        info!(
+            webhook_delivery.id = %delivery.id,
+            webhook.target = target.name,
+            "Scheduled webhook delivery"
+        );
+    }
+
+    repo.save().await?;
+
+    Ok(())
+}
+
+/// Job to attempt delivering an event to a webhook target.
+///
+/// Failed attempts are recorded on the delivery, and retried later with an
+/// exponential backoff. Once the target's maximum number of attempts is
+/// reached, the delivery is marked as failed and won't be retried.
+#[tracing::instrument(
+    name = "job.send_webhook",
+    fields(webhook_delivery.id = %job.webhook_delivery_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn send_webhook(
+    job: JobWithSpanContext<SendWebhookJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let clock = state.clock();
+    let mut repo = state.repository().await?;
+
+    let delivery = repo
+        .webhook_delivery()
+        .lookup(job.webhook_delivery_id())
+        .await?
+        .context("Webhook delivery not found")?;
+
+    if !delivery.is_pending() {
+        info!("Webhook delivery is not pending anymore, skipping");
+        return Ok(());
+    }
+
+    let Some(target) = state.webhook_target(&delivery.target) else {
+        warn!(
+            webhook.target = delivery.target,
+            "Webhook target is not configured anymore, giving up"
+        );
+        let delivery = repo
+            .webhook_delivery()
+            .record_failed_attempt(
+                &clock,
+                delivery,
+                "Webhook target is not configured anymore".to_owned(),
+            )
+            .await?;
+        repo.webhook_delivery()
+            .mark_as_failed(&clock, delivery)
+            .await?;
+        repo.save().await?;
+        return Ok(());
+    };
+
+    // Release the database connection while calling the target
+    repo.cancel().await?;
+
+    let body = serde_json::to_vec(&WebhookPayload {
+        id: delivery.id,
+        event: delivery.event,
+        created_at: delivery.created_at,
+        data: &delivery.data,
+    })?;
+
+    let timestamp = clock.now().timestamp();
+    let signature = sign_payload(&target.secret, timestamp, &body);
+
+    let request = Request::post(target.url.as_str())
+        .header(CONTENT_TYPE, "application/json")
+        .header("X-MAS-Webhook-Id", delivery.id.to_string())
+        .header("X-MAS-Webhook-Timestamp", timestamp.to_string())
+        .header("X-MAS-Webhook-Signature", format!("v1={signature}"))
+        .body(Bytes::from(body))?;
+
+    let result = async {
+        let mut http_client = state
+            .http_client_factory()
+            .client("webhook.send")
+            .request_bytes_to_body();
+
+        let response = http_client
+            .ready()
+            .await?
+            .call(request)
+            .await
+            .context("Failed to send the request to the webhook target")?;
+
+        if !response.status().is_success() {
+            bail!(
+                "Webhook target responded with an unexpected status code: {}",
+                response.status()
+            );
+        }
+
+        Ok(())
+    }
+    .await;
+
+    let mut repo = state.repository().await?;
+
+    match result {
+        Ok(()) => {
+            repo.webhook_delivery()
+                .mark_as_delivered(&clock, delivery)
+                .await?;
+            info!(webhook.target = target.name, "Delivered webhook");
+        }
+
+        Err(error) => {
+            let delivery = repo
+                .webhook_delivery()
+                .record_failed_attempt(&clock, delivery, format!("{error:#}"))
+                .await?;
+
+            if let Some(run_at) =
+                next_attempt_at(clock.now(), delivery.attempts, target.max_attempts)
+            {
+                warn!(
+                    webhook.target = target.name,
+                    attempts = delivery.attempts,
+                    %run_at,
+                    "Failed to deliver webhook, retrying later: {error:#}"
+                );
+                repo.job()
+                    .schedule_job_at(SendWebhookJob::new(&delivery), run_at)
+                    .await?;
+            } else {
+                warn!(
+                    webhook.target = target.name,
+                    attempts = delivery.attempts,
+                    "Failed to deliver webhook, giving up: {error:#}"
+                );
+                repo.webhook_delivery()
+                    .mark_as_failed(&clock, delivery)
+                    .await?;
+            }
+        }
+    }
+
+    repo.save().await?;
+
+    Ok(())
+}
+
+pub(crate) fn register(
+    suffix: &str,
+    monitor: Monitor<TokioExecutor>,
+    state: &State,
+    storage_factory: &PostgresStorageFactory,
+) -> Monitor<TokioExecutor> {
+    let dispatch_webhook_event_worker = crate::build!(DispatchWebhookEventJob => dispatch_webhook_event, suffix, state, storage_factory);
+    let send_webhook_worker =
+        crate::build!(SendWebhookJob => send_webhook, suffix, state, storage_factory);
+
+    monitor
+        .register(dispatch_webhook_event_worker)
+        .register(send_webhook_worker)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        let signature = sign_payload("whsec_test", 1_700_000_000, br#"{"hello":"world"}"#);
+        assert_eq!(
+            signature,
+            "f592bbf3951cfc94e560eecfb5d9dd4da6b0fff2e626235f8ab4b54860925d0b"
+        );
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let seconds = |attempts| retry_delay(attempts).num_seconds();
+        assert_eq!(seconds(1), 30);
+        assert_eq!(seconds(2), 60);
+        assert_eq!(seconds(3), 120);
+        assert_eq!(seconds(7), 1920);
+        // Capped at one hour
+        assert_eq!(seconds(8), 3600);
+        assert_eq!(seconds(9), 3600);
+        assert_eq!(seconds(u32::MAX), 3600);
+    }
+
+    #[test]
+    fn test_next_attempt_at() {
+        let now = Utc.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            next_attempt_at(now, 1, 3),
+            Some(now + Duration::try_seconds(30).unwrap())
+        );
+        assert_eq!(
+            next_attempt_at(now, 2, 3),
+            Some(now + Duration::try_seconds(60).unwrap())
+        );
+
+        // The delivery is given up on once the maximum number of attempts is
+        // reached
+        assert_eq!(next_attempt_at(now, 3, 3), None);
+        assert_eq!(next_attempt_at(now, 4, 3), None);
+    }
+}
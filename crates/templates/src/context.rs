@@ -21,11 +21,11 @@ use http::{Method, Uri, Version};
 use mas_data_model::{
     AuthorizationGrant, BrowserSession, Client, CompatSsoLogin, CompatSsoLoginState,
     DeviceCodeGrant, UpstreamOAuthLink, UpstreamOAuthProvider, User, UserAgent, UserEmail,
-    UserEmailVerification, UserRecoverySession,
+    UserEmailVerification, UserPasskey, UserRecoverySession,
 };
 use mas_i18n::DataLocale;
 use mas_router::{Account, GraphQL, PostAuthAction, UrlBuilder};
-use oauth2_types::scope::OPENID;
+use oauth2_types::{oidc::RpInitiatedLogoutRequest, scope::OPENID};
 use rand::{
     distributions::{Alphanumeric, DistString},
     Rng,
@@ -37,7 +37,7 @@ use url::Url;
 pub use self::{
     branding::SiteBranding, captcha::WithCaptcha, ext::SiteConfigExt, features::SiteFeatures,
 };
-use crate::{FieldError, FormField, FormState};
+use crate::{FieldError, FormError, FormField, FormState};
 
 /// Helper trait to construct context wrappers
 pub trait TemplateContext: Serialize {
@@ -404,6 +404,9 @@ pub enum PostAuthContextInner {
     /// Change the account password
     ChangePassword,
 
+    /// Manage the passkeys of the account
+    ManagePasskeys,
+
     /// Link an upstream account
     LinkUpstream {
         /// The upstream provider
@@ -739,6 +742,358 @@ impl ReauthContext {
     }
 }
 
+/// Fields of the TOTP login form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginTotpFormField {
+    /// The TOTP code field
+    Code,
+
+    /// The recovery code field
+    RecoveryCode,
+}
+
+impl FormField for LoginTotpFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Code | Self::RecoveryCode => false,
+        }
+    }
+}
+
+/// Context used by the `pages/login_totp.html` template
+#[derive(Serialize)]
+pub struct LoginTotpContext {
+    form: FormState<LoginTotpFormField>,
+    next: Option<PostAuthContext>,
+    username: String,
+    totp_enabled: bool,
+    passkey_options: Option<String>,
+}
+
+impl TemplateContext for LoginTotpContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            LoginTotpContext::new("john".to_owned()).with_totp(),
+            LoginTotpContext::new("john".to_owned())
+                .with_totp()
+                .with_form_state(FormState::default().with_error_on_form(FormError::InvalidCode)),
+            LoginTotpContext::new("john".to_owned())
+                .with_passkey_options(SAMPLE_PASSKEY_REQUEST_OPTIONS.to_owned()),
+            LoginTotpContext::new("john".to_owned())
+                .with_totp()
+                .with_passkey_options(SAMPLE_PASSKEY_REQUEST_OPTIONS.to_owned()),
+        ]
+    }
+}
+
+impl LoginTotpContext {
+    /// Constructs a context for the second factor login step of the given
+    /// user
+    #[must_use]
+    pub fn new(username: String) -> Self {
+        Self {
+            form: FormState::default(),
+            next: None,
+            username,
+            totp_enabled: false,
+            passkey_options: None,
+        }
+    }
+
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<LoginTotpFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Offer to use a TOTP code or a recovery code
+    #[must_use]
+    pub fn with_totp(self) -> Self {
+        Self {
+            totp_enabled: true,
+            ..self
+        }
+    }
+
+    /// Offer to use a passkey, with the given `WebAuthn` request options,
+    /// serialized as JSON
+    #[must_use]
+    pub fn with_passkey_options(self, passkey_options: String) -> Self {
+        Self {
+            passkey_options: Some(passkey_options),
+            ..self
+        }
+    }
+
+    /// Add a post authentication action to the context
+    #[must_use]
+    pub fn with_post_action(self, next: PostAuthContext) -> Self {
+        Self {
+            next: Some(next),
+            ..self
+        }
+    }
+}
+
+/// Fields of the TOTP management form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountTotpFormField {
+    /// The TOTP code field
+    Code,
+}
+
+impl FormField for AccountTotpFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Code => false,
+        }
+    }
+}
+
+/// The state of the TOTP second factor of a user, as shown on the
+/// `pages/account/totp.html` template
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AccountTotpState {
+    /// The user has no TOTP second factor
+    Disabled,
+
+    /// The user started enrolling a new secret, and needs to confirm it with a
+    /// code
+    Enrolling {
+        /// The ID of the pending secret
+        secret_id: Ulid,
+
+        /// The secret, encoded in base32
+        secret: String,
+
+        /// The `otpauth://` URI to add the secret to an authenticator app
+        provisioning_uri: Url,
+    },
+
+    /// New recovery codes were generated, and should be shown to the user
+    RecoveryCodes {
+        /// The recovery codes, in clear
+        codes: Vec<String>,
+    },
+
+    /// The user has a TOTP second factor
+    Enabled {
+        /// The number of recovery codes which were not used yet
+        remaining_recovery_codes: usize,
+    },
+}
+
+/// Context used by the `pages/account/totp.html` template
+#[derive(Serialize)]
+pub struct AccountTotpContext {
+    form: FormState<AccountTotpFormField>,
+    state: AccountTotpState,
+}
+
+impl TemplateContext for AccountTotpContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::new(AccountTotpState::Disabled),
+            Self::new(AccountTotpState::Enrolling {
+                secret_id: Ulid::from_datetime_with_source(now.into(), rng),
+                secret: "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_owned(),
+                provisioning_uri: Url::parse(
+                    "otpauth://totp/example.com:john?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=example.com",
+                )
+                .unwrap(),
+            })
+            .with_form_state(FormState::default().with_error_on_form(FormError::InvalidCode)),
+            Self::new(AccountTotpState::RecoveryCodes {
+                codes: vec![
+                    "abcd-efgh-ijkl-mnop".to_owned(),
+                    "qrst-uvwx-yz23-4567".to_owned(),
+                ],
+            }),
+            Self::new(AccountTotpState::Enabled {
+                remaining_recovery_codes: 8,
+            }),
+        ]
+    }
+}
+
+impl AccountTotpContext {
+    /// Constructs a context for the TOTP management page
+    #[must_use]
+    pub fn new(state: AccountTotpState) -> Self {
+        Self {
+            form: FormState::default(),
+            state,
+        }
+    }
+
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<AccountTotpFormField>) -> Self {
+        Self { form, ..self }
+    }
+}
+
+/// Sample `WebAuthn` options used to request an assertion in template samples
+const SAMPLE_PASSKEY_REQUEST_OPTIONS: &str = r#"{"publicKey":{"challenge":"Y2hhbGxlbmdl","timeout":300000,"rpId":"example.com","allowCredentials":[],"userVerification":"required"}}"#;
+
+/// Fields of the passkey login form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginPasskeyFormField {
+    /// The serialized credential returned by the browser
+    Credential,
+}
+
+impl FormField for LoginPasskeyFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Credential => false,
+        }
+    }
+}
+
+/// Context used by the `pages/login_passkey.html` template
+#[derive(Serialize)]
+pub struct LoginPasskeyContext {
+    form: FormState<LoginPasskeyFormField>,
+    next: Option<PostAuthContext>,
+    options: String,
+}
+
+impl TemplateContext for LoginPasskeyContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::new(SAMPLE_PASSKEY_REQUEST_OPTIONS.to_owned()),
+            Self::new(SAMPLE_PASSKEY_REQUEST_OPTIONS.to_owned()).with_form_state(
+                FormState::default().with_error_on_form(FormError::InvalidCredentials),
+            ),
+        ]
+    }
+}
+
+impl LoginPasskeyContext {
+    /// Constructs a context for the passkey login page, with the given
+    /// `WebAuthn` request options, serialized as JSON
+    #[must_use]
+    pub fn new(options: String) -> Self {
+        Self {
+            form: FormState::default(),
+            next: None,
+            options,
+        }
+    }
+
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<LoginPasskeyFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Add a post authentication action to the context
+    #[must_use]
+    pub fn with_post_action(self, next: PostAuthContext) -> Self {
+        Self {
+            next: Some(next),
+            ..self
+        }
+    }
+}
+
+/// Fields of the passkey management form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountPasskeysFormField {
+    /// The name of the new passkey
+    Name,
+
+    /// The serialized credential returned by the browser
+    Credential,
+}
+
+impl FormField for AccountPasskeysFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Name => true,
+            Self::Credential => false,
+        }
+    }
+}
+
+/// Context used by the `pages/account/passkeys.html` template
+#[derive(Serialize)]
+pub struct AccountPasskeysContext {
+    form: FormState<AccountPasskeysFormField>,
+    passkeys: Vec<UserPasskey>,
+    creation_options: Option<String>,
+}
+
+impl TemplateContext for AccountPasskeysContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        let passkeys = vec![UserPasskey {
+            id: Ulid::from_datetime_with_source(now.into(), rng),
+            user_id: Ulid::from_datetime_with_source(now.into(), rng),
+            credential_id: "Y3JlZGVudGlhbA".to_owned(),
+            name: "My laptop".to_owned(),
+            credential: "{}".to_owned(),
+            created_at: now,
+            last_used_at: Some(now),
+        }];
+
+        vec![
+            Self::new(Vec::new()),
+            Self::new(passkeys.clone()),
+            Self::new(passkeys).with_creation_options(
+                r#"{"publicKey":{"rp":{"name":"example.com","id":"example.com"},"user":{"id":"dXNlcg","name":"john","displayName":"john"},"challenge":"Y2hhbGxlbmdl","pubKeyCredParams":[{"type":"public-key","alg":-7}],"timeout":300000}}"#
+                    .to_owned(),
+            ),
+        ]
+    }
+}
+
+impl AccountPasskeysContext {
+    /// Constructs a context for the passkey management page, listing the given
+    /// passkeys
+    #[must_use]
+    pub fn new(passkeys: Vec<UserPasskey>) -> Self {
+        Self {
+            form: FormState::default(),
+            passkeys,
+            creation_options: None,
+        }
+    }
+
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<AccountPasskeysFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Start registering a new passkey, with the given `WebAuthn` creation
+    /// options, serialized as JSON
+    #[must_use]
+    pub fn with_creation_options(self, creation_options: String) -> Self {
+        Self {
+            creation_options: Some(creation_options),
+            ..self
+        }
+    }
+}
+
 /// Context used by the `sso.html` template
 #[derive(Serialize)]
 pub struct CompatSsoContext {
@@ -1452,6 +1807,48 @@ impl TemplateContext for DeviceConsentContext {
     }
 }
 
+/// Context used by the `end_session.html` template
+#[derive(Serialize, Debug)]
+pub struct EndSessionContext {
+    client: Option<Client>,
+    params: RpInitiatedLogoutRequest,
+}
+
+impl EndSessionContext {
+    /// Constructs a context for the RP-initiated logout confirmation page
+    #[must_use]
+    pub fn new(client: Option<Client>, params: RpInitiatedLogoutRequest) -> Self {
+        Self { client, params }
+    }
+}
+
+impl TemplateContext for EndSessionContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        Client::samples(now, rng)
+            .into_iter()
+            .map(|client| {
+                let params = RpInitiatedLogoutRequest {
+                    client_id: Some(client.client_id.clone()),
+                    post_logout_redirect_uri: client.post_logout_redirect_uris.first().cloned(),
+                    state: Some("state".to_owned()),
+                    ..RpInitiatedLogoutRequest::default()
+                };
+                Self {
+                    client: Some(client),
+                    params,
+                }
+            })
+            .chain(std::iter::once(Self {
+                client: None,
+                params: RpInitiatedLogoutRequest::default(),
+            }))
+            .collect()
+    }
+}
+
 /// Context used by the `form_post.html` template
 #[derive(Serialize)]
 pub struct FormPostContext<T> {
@@ -65,6 +65,9 @@ pub enum FormError {
 
     /// Failed to validate CAPTCHA
     Captcha,
+
+    /// The one-time code is not valid
+    InvalidCode,
 }
 
 #[derive(Debug, Default, Serialize)]
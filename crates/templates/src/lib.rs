@@ -34,16 +34,19 @@ mod macros;
 
 pub use self::{
     context::{
-        ApiDocContext, AppContext, CompatSsoContext, ConsentContext, DeviceConsentContext,
-        DeviceLinkContext, DeviceLinkFormField, EmailAddContext, EmailRecoveryContext,
-        EmailVerificationContext, EmailVerificationPageContext, EmptyContext, ErrorContext,
-        FormPostContext, IndexContext, LoginContext, LoginFormField, NotFoundContext,
-        PolicyViolationContext, PostAuthContext, PostAuthContextInner, ReauthContext,
-        ReauthFormField, RecoveryExpiredContext, RecoveryFinishContext, RecoveryFinishFormField,
-        RecoveryProgressContext, RecoveryStartContext, RecoveryStartFormField, RegisterContext,
-        RegisterFormField, SiteBranding, SiteConfigExt, SiteFeatures, TemplateContext,
-        UpstreamExistingLinkContext, UpstreamRegister, UpstreamRegisterFormField,
-        UpstreamSuggestLink, WithCaptcha, WithCsrf, WithLanguage, WithOptionalSession, WithSession,
+        AccountPasskeysContext, AccountPasskeysFormField, AccountTotpContext, AccountTotpFormField,
+        AccountTotpState, ApiDocContext, AppContext, CompatSsoContext, ConsentContext,
+        DeviceConsentContext, DeviceLinkContext, DeviceLinkFormField, EmailAddContext,
+        EmailRecoveryContext, EmailVerificationContext, EmailVerificationPageContext, EmptyContext,
+        EndSessionContext, ErrorContext, FormPostContext, IndexContext, LoginContext,
+        LoginFormField, LoginPasskeyContext, LoginPasskeyFormField, LoginTotpContext,
+        LoginTotpFormField, NotFoundContext, PolicyViolationContext, PostAuthContext,
+        PostAuthContextInner, ReauthContext, ReauthFormField, RecoveryExpiredContext,
+        RecoveryFinishContext, RecoveryFinishFormField, RecoveryProgressContext,
+        RecoveryStartContext, RecoveryStartFormField, RegisterContext, RegisterFormField,
+        SiteBranding, SiteConfigExt, SiteFeatures, TemplateContext, UpstreamExistingLinkContext,
+        UpstreamRegister, UpstreamRegisterFormField, UpstreamSuggestLink, WithCaptcha, WithCsrf,
+        WithLanguage, WithOptionalSession, WithSession,
     },
     forms::{FieldError, FormError, FormField, FormState, ToFormState},
 };
@@ -325,6 +328,12 @@ register_templates! {
     /// Render the login page
     pub fn render_login(WithLanguage<WithCsrf<LoginContext>>) { "pages/login.html" }
 
+    /// Render the TOTP step of the login page
+    pub fn render_login_totp(WithLanguage<WithCsrf<LoginTotpContext>>) { "pages/login_totp.html" }
+
+    /// Render the passkey login page
+    pub fn render_login_passkey(WithLanguage<WithCsrf<LoginPasskeyContext>>) { "pages/login_passkey.html" }
+
     /// Render the registration page
     pub fn render_register(WithLanguage<WithCsrf<WithCaptcha<RegisterContext>>>) { "pages/register.html" }
 
@@ -334,6 +343,9 @@ register_templates! {
     /// Render the policy violation page
     pub fn render_policy_violation(WithLanguage<WithCsrf<WithSession<PolicyViolationContext>>>) { "pages/policy_violation.html" }
 
+    /// Render the RP-initiated logout confirmation page
+    pub fn render_end_session(WithLanguage<WithCsrf<WithSession<EndSessionContext>>>) { "pages/end_session.html" }
+
     /// Render the legacy SSO login consent page
     pub fn render_sso_login(WithLanguage<WithCsrf<WithSession<CompatSsoContext>>>) { "pages/sso.html" }
 
@@ -346,6 +358,12 @@ register_templates! {
     /// Render the email verification page
     pub fn render_account_add_email(WithLanguage<WithCsrf<WithSession<EmailAddContext>>>) { "pages/account/emails/add.html" }
 
+    /// Render the TOTP second factor management page
+    pub fn render_account_totp(WithLanguage<WithCsrf<WithSession<AccountTotpContext>>>) { "pages/account/totp.html" }
+
+    /// Render the passkeys management page
+    pub fn render_account_passkeys(WithLanguage<WithCsrf<WithSession<AccountPasskeysContext>>>) { "pages/account/passkeys.html" }
+
     /// Render the account recovery start page
     pub fn render_recovery_start(WithLanguage<WithCsrf<RecoveryStartContext>>) { "pages/recovery/start.html" }
 
@@ -424,13 +442,18 @@ impl Templates {
         check::render_swagger(self, now, rng)?;
         check::render_swagger_callback(self, now, rng)?;
         check::render_login(self, now, rng)?;
+        check::render_login_totp(self, now, rng)?;
+        check::render_login_passkey(self, now, rng)?;
         check::render_register(self, now, rng)?;
         check::render_consent(self, now, rng)?;
         check::render_policy_violation(self, now, rng)?;
+        check::render_end_session(self, now, rng)?;
         check::render_sso_login(self, now, rng)?;
         check::render_index(self, now, rng)?;
         check::render_account_add_email(self, now, rng)?;
         check::render_account_verify_email(self, now, rng)?;
+        check::render_account_totp(self, now, rng)?;
+        check::render_account_passkeys(self, now, rng)?;
         check::render_recovery_start(self, now, rng)?;
         check::render_recovery_progress(self, now, rng)?;
         check::render_recovery_finish(self, now, rng)?;
@@ -1167,8 +1167,11 @@ export type UpstreamOAuth2LinkEdge = {
 
 export type UpstreamOAuth2Provider = CreationEvent & Node & {
   __typename?: 'UpstreamOAuth2Provider';
-  /** Client ID used for this provider. */
-  clientId: Scalars['String']['output'];
+  /**
+   * Client ID used for this provider. Not set for SAML 2.0 identity
+   * providers.
+   */
+  clientId?: Maybe<Scalars['String']['output']>;
   /** When the object was created. */
   createdAt: Scalars['DateTime']['output'];
   /** ID of the object. */
@@ -0,0 +1,35 @@
+# METADATA
+# schemas:
+#   - input: schema["authorization_request_input"]
+package authorization_request
+
+import future.keywords.in
+
+default allow := false
+
+allow {
+	count(violation) == 0
+}
+
+# Operators can require signed request objects from every client
+requires_signed_request_object(_) {
+	data.require_signed_request_object == true
+}
+
+# ...or only from the clients listed by ID
+requires_signed_request_object(client) {
+	is_array(data.require_signed_request_object)
+	some required in data.require_signed_request_object
+	client.id == required
+}
+
+# Clients can also ask for it themselves when registering, which can only make
+# this policy stricter
+requires_signed_request_object(client) {
+	client.require_signed_request_object
+}
+
+violation[{"msg": "client must use signed request objects"}] {
+	requires_signed_request_object(input.client)
+	not input.signed_request_object
+}
@@ -0,0 +1,38 @@
+package authorization_request
+
+client := {"id": "01H8PKNWKKRPCBW4YGH1RWV279"}
+
+test_signed_request_object {
+	allow with input.client as client
+		with input.signed_request_object as false
+
+	allow with input.client as client
+		with input.signed_request_object as true
+
+	# Required for every client
+	not allow with input.client as client
+		with input.signed_request_object as false
+		with data.require_signed_request_object as true
+
+	allow with input.client as client
+		with input.signed_request_object as true
+		with data.require_signed_request_object as true
+
+	# Required for specific clients
+	not allow with input.client as client
+		with input.signed_request_object as false
+		with data.require_signed_request_object as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	allow with input.client as client
+		with input.signed_request_object as false
+		with data.require_signed_request_object as ["01HWQCPA5KF10FNCETY9402WGF"]
+
+	# The client asked for it when registering
+	not allow with input.client as {"id": "01H8PKNWKKRPCBW4YGH1RWV279", "require_signed_request_object": true}
+		with input.signed_request_object as false
+
+	# The client can't relax the operator's requirement
+	not allow with input.client as {"id": "01H8PKNWKKRPCBW4YGH1RWV279", "require_signed_request_object": false}
+		with input.signed_request_object as false
+		with data.require_signed_request_object as true
+}
@@ -125,6 +125,17 @@ violation[{"msg": "client_credentials grant_type requires some form of client au
 	is_public_client
 }
 
+violation[{"msg": "token-exchange grant_type requires some form of client authentication"}] {
+	uses_grant_type("urn:ietf:params:oauth:grant-type:token-exchange")
+	is_public_client
+}
+
+violation[{"msg": "jwt-bearer grant_type requires a jwks or a jwks_uri"}] {
+	uses_grant_type("urn:ietf:params:oauth:grant-type:jwt-bearer")
+	not input.client_metadata.jwks
+	not input.client_metadata.jwks_uri
+}
+
 violation[{"msg": "missing redirect_uris"}] {
 	requires_redirect_uris
 	not input.client_metadata.redirect_uris
@@ -351,6 +351,37 @@ test_client_credentials_grant {
 	}
 }
 
+test_token_exchange_grant {
+	# Allowed for confidential clients
+	allow with input.client_metadata as {
+		"grant_types": ["urn:ietf:params:oauth:grant-type:token-exchange"],
+		"token_endpoint_auth_method": "client_secret_basic",
+		"client_uri": "https://example.com/",
+	}
+
+	# Disallowed for public clients
+	not allow with input.client_metadata as {
+		"grant_types": ["urn:ietf:params:oauth:grant-type:token-exchange"],
+		"token_endpoint_auth_method": "none",
+		"client_uri": "https://example.com/",
+	}
+}
+
+test_jwt_bearer_grant {
+	# Allowed for clients with keys
+	allow with input.client_metadata as {
+		"grant_types": ["urn:ietf:params:oauth:grant-type:jwt-bearer"],
+		"jwks_uri": "https://example.com/jwks",
+		"client_uri": "https://example.com/",
+	}
+
+	# Disallowed for clients without keys
+	not allow with input.client_metadata as {
+		"grant_types": ["urn:ietf:params:oauth:grant-type:jwt-bearer"],
+		"client_uri": "https://example.com/",
+	}
+}
+
 test_is_subdomain {
 	is_subdomain("example.com", "example.com")
 	is_subdomain("example.com", "app.example.com")
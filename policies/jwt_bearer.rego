@@ -0,0 +1,49 @@
+# METADATA
+# schemas:
+#   - input: schema["jwt_bearer_input"]
+package jwt_bearer
+
+import future.keywords.in
+
+default allow := false
+
+allow {
+	count(violation) == 0
+}
+
+# Clients have to be explicitly trusted to act on behalf of users
+allowed_client(client) {
+	some allowed in data.jwt_bearer_clients
+	client.id == allowed
+}
+
+# Special case to make empty scope work
+allowed_scope("") = true
+
+allowed_scope("openid") = true
+
+allowed_scope("email") = true
+
+# This grants access to the /graphql API endpoint
+allowed_scope("urn:mas:graphql:*") = true
+
+allowed_scope(scope) {
+	regex.match("^urn:matrix:org.matrix.msc2967.client:device:[A-Za-z0-9._~!$&'()*+,;=:@/-]{10,}$", scope)
+}
+
+allowed_scope("urn:matrix:org.matrix.msc2967.client:api:*") = true
+
+violation[{"msg": "client is not allowed to use the jwt-bearer grant"}] {
+	not allowed_client(input.client)
+}
+
+violation[{"msg": msg}] {
+	some scope in split(input.scope, " ")
+	not allowed_scope(scope)
+	msg := sprintf("scope '%s' not allowed", [scope])
+}
+
+violation[{"msg": "only one device scope is allowed at a time"}] {
+	scope_list := split(input.scope, " ")
+	count({key | scope_list[key]; startswith(scope_list[key], "urn:matrix:org.matrix.msc2967.client:device:")}) > 1
+}
@@ -0,0 +1,50 @@
+package jwt_bearer
+
+user := {"username": "john"}
+
+client := {"id": "01H8PKNWKKRPCBW4YGH1RWV279"}
+
+test_allowed_client {
+	allow with input.user as user
+		with input.client as client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.jwt_bearer_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.jwt_bearer_clients as ["01HWQCPA5KF10FNCETY9402WGF"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+}
+
+test_scopes {
+	allow with input.user as user
+		with input.client as client
+		with input.scope as "openid urn:mas:graphql:*"
+		with data.jwt_bearer_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	allow with input.user as user
+		with input.client as client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:* urn:matrix:org.matrix.msc2967.client:device:AAbbCCdd01"
+		with data.jwt_bearer_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	# Multiple device scope
+	not allow with input.user as user
+		with input.client as client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:device:AAbbCCdd01 urn:matrix:org.matrix.msc2967.client:device:AAbbCCdd02"
+		with data.jwt_bearer_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	# Admin scopes are never granted
+	not allow with input.user as user
+		with input.client as client
+		with input.scope as "urn:synapse:admin:*"
+		with data.jwt_bearer_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.scope as "urn:mas:admin"
+		with data.jwt_bearer_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+}
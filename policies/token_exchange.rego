@@ -0,0 +1,62 @@
+# METADATA
+# schemas:
+#   - input: schema["token_exchange_input"]
+package token_exchange
+
+import future.keywords.in
+
+default allow := false
+
+allow {
+	count(violation) == 0
+}
+
+# Clients have to be explicitly allowed to exchange tokens
+allowed_client(client) {
+	some allowed in data.token_exchange_clients
+	client.id == allowed
+}
+
+# Clients can only ask for tokens meant for the audiences they were explicitly
+# allowed to target
+allowed_audience(client, audience) {
+	some allowed in data.token_exchange_audiences[client.id]
+	audience.id == allowed
+}
+
+# Special case to make empty scope work
+allowed_scope("") = true
+
+allowed_scope("openid") = true
+
+allowed_scope("email") = true
+
+# This grants access to the /graphql API endpoint
+allowed_scope("urn:mas:graphql:*") = true
+
+# This grants access to the C-S API. Device scopes are not allowed, as an
+# exchanged token must not be able to act as a new device for the user
+allowed_scope("urn:matrix:org.matrix.msc2967.client:api:*") = true
+
+violation[{"msg": "client is not allowed to exchange tokens"}] {
+	not allowed_client(input.client)
+}
+
+violation[{"msg": "client is not allowed to request tokens for this audience"}] {
+	input.audience
+	not allowed_audience(input.client, input.audience)
+}
+
+violation[{"msg": msg}] {
+	some scope in split(input.scope, " ")
+	not allowed_scope(scope)
+	msg := sprintf("scope '%s' not allowed", [scope])
+}
+
+# The exchanged token can only be down-scoped
+violation[{"msg": msg}] {
+	some scope in split(input.scope, " ")
+	scope != ""
+	not scope in split(input.subject_scope, " ")
+	msg := sprintf("scope '%s' was not granted to the subject token", [scope])
+}
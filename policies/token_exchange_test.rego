@@ -0,0 +1,117 @@
+package token_exchange
+
+user := {"username": "john"}
+
+client := {"id": "01H8PKNWKKRPCBW4YGH1RWV279"}
+
+subject_client := {"id": "01HWQCPA5KF10FNCETY9402WGF"}
+
+test_allowed_client {
+	allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with input.subject_scope as "openid urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with input.subject_scope as "openid urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.token_exchange_clients as ["01HWQCPA5KF10FNCETY9402WGF"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with input.subject_scope as "openid urn:matrix:org.matrix.msc2967.client:api:*"
+}
+
+test_down_scoping {
+	allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as ""
+		with input.subject_scope as "openid urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as "openid urn:mas:graphql:*"
+		with input.subject_scope as "openid urn:mas:graphql:*"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as "openid urn:mas:graphql:*"
+		with input.subject_scope as "openid"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+}
+
+test_forbidden_scopes {
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:device:AAbbCCdd01"
+		with input.subject_scope as "urn:matrix:org.matrix.msc2967.client:device:AAbbCCdd01"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as "urn:synapse:admin:*"
+		with input.subject_scope as "urn:synapse:admin:*"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.scope as "urn:mas:admin"
+		with input.subject_scope as "urn:mas:admin"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+}
+
+audience := {"id": "01J9ZQ1N3B0SR3SGZ9C8K0EJ4Y"}
+
+test_audience {
+	allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.audience as audience
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with input.subject_scope as "openid urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+		with data.token_exchange_audiences as {"01H8PKNWKKRPCBW4YGH1RWV279": ["01J9ZQ1N3B0SR3SGZ9C8K0EJ4Y"]}
+
+	# The audience was not allowed for this client
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.audience as audience
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with input.subject_scope as "openid urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+
+	# The audience was allowed for another client
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.audience as audience
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with input.subject_scope as "openid urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+		with data.token_exchange_audiences as {"01HWQCPA5KF10FNCETY9402WGF": ["01J9ZQ1N3B0SR3SGZ9C8K0EJ4Y"]}
+
+	# Another audience was allowed for this client
+	not allow with input.user as user
+		with input.client as client
+		with input.subject_client as subject_client
+		with input.audience as audience
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+		with input.subject_scope as "openid urn:matrix:org.matrix.msc2967.client:api:*"
+		with data.token_exchange_clients as ["01H8PKNWKKRPCBW4YGH1RWV279"]
+		with data.token_exchange_audiences as {"01H8PKNWKKRPCBW4YGH1RWV279": ["01HWQCPA5KF10FNCETY9402WGF"]}
+}